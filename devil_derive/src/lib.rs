@@ -181,7 +181,10 @@ where
     N: ToTokens,
     C: ToTokens,
 {
+    // Schemas are only used by the BigQuery writer, which needs the native feature of the crate
+    // deriving them.
     quote! {
+        #[cfg(feature = "native")]
         impl crate::record::BigQuerySchema for #root_name {
             fn big_query_schema(name: &str) -> gcp_bigquery_client::model::table_field_schema::TableFieldSchema {
                 use crate::record::BigQuerySchema;
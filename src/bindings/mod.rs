@@ -35,10 +35,13 @@ impl Plan {
     }
 
     fn validate(&mut self) -> crate::Result<()> {
-        self.devil.validate()?;
+        self.devil
+            .validate()
+            .map_err(|e| crate::locate(e, "devil"))?;
         for (name, step) in &mut self.steps {
-            step.validate()
-                .map_err(|e| anyhow!("validate step {}: {}", name, e))?;
+            step.validate().map_err(|e| {
+                crate::locate(e, name.clone()).context(format!("validate step {name}"))
+            })?;
         }
         Ok(())
     }
@@ -52,23 +55,316 @@ pub struct Settings {
     pub defaults: Vec<Defaults>,
     #[serde(default)]
     pub locals: IndexMap<String, Value>,
+    pub on_error: Option<Value>,
+    pub mirror: Option<Mirror>,
+    #[serde(default)]
+    pub egress: IndexMap<String, Egress>,
+    #[serde(default)]
+    pub network: IndexMap<String, Network>,
+    pub seed: Option<u64>,
+    pub now: Option<String>,
+    pub targets: Option<Targets>,
+    pub scope: Option<Scope>,
+    pub budget: Option<Budget>,
+    #[serde(default)]
+    pub auth: IndexMap<String, Auth>,
     #[serde(flatten)]
     pub unrecognized: toml::Table,
 }
 
 impl Validate for Settings {
+    fn validate(&self) -> crate::Result<()> {
+        if let Some(first) = self.unrecognized.keys().next() {
+            return Err(crate::locate(
+                anyhow!(
+                    "unrecognized field devil.{} {}",
+                    if self.unrecognized.len() == 1 {
+                        ""
+                    } else {
+                        "s"
+                    },
+                    self.unrecognized.keys().join(", "),
+                ),
+                first,
+            ));
+        }
+        if let Some(mirror) = &self.mirror {
+            mirror.validate().map_err(|e| crate::locate(e, "mirror"))?;
+        }
+        for (name, egress) in &self.egress {
+            egress
+                .validate()
+                .map_err(|e| crate::locate(crate::locate(e, name), "egress"))?;
+        }
+        for (name, network) in &self.network {
+            network
+                .validate()
+                .map_err(|e| crate::locate(crate::locate(e, name), "network"))?;
+        }
+        if let Some(targets) = &self.targets {
+            targets
+                .validate()
+                .map_err(|e| crate::locate(e, "targets"))?;
+        }
+        if let Some(scope) = &self.scope {
+            scope.validate().map_err(|e| crate::locate(e, "scope"))?;
+        }
+        if let Some(budget) = &self.budget {
+            budget.validate().map_err(|e| crate::locate(e, "budget"))?;
+        }
+        for (name, auth) in &self.auth {
+            auth.validate()
+                .map_err(|e| crate::locate(crate::locate(e, name), "auth"))?;
+        }
+        Ok(())
+    }
+}
+
+/// Base URLs to run the whole plan against, either listed inline or read from a file with one
+/// per line.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Targets {
+    List(Vec<String>),
+    File {
+        file: String,
+        #[serde(flatten)]
+        unrecognized: toml::Table,
+    },
+}
+
+impl Validate for Targets {
+    fn validate(&self) -> crate::Result<()> {
+        match self {
+            Self::List(list) if list.is_empty() => bail!("devil.targets must not be empty"),
+            Self::File { unrecognized, .. } if !unrecognized.is_empty() => bail!(
+                "unrecognized field{} devil.targets.{}",
+                if unrecognized.len() == 1 { "" } else { "s" },
+                unrecognized.keys().join(", devil.targets."),
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The hosts, address ranges, and URLs a plan may send traffic to.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Scope {
+    #[serde(default)]
+    pub allow: Vec<String>,
+    #[serde(default)]
+    pub deny: Vec<String>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl Validate for Scope {
     fn validate(&self) -> crate::Result<()> {
         if !self.unrecognized.is_empty() {
             bail!(
-                "unrecognized field devil.{} {}",
+                "unrecognized field{} devil.scope.{}",
                 if self.unrecognized.len() == 1 {
                     ""
                 } else {
                     "s"
                 },
-                self.unrecognized.keys().join(", "),
+                self.unrecognized.keys().join(", devil.scope."),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Limits on the resources each run of a plan and each of its steps may use.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Budget {
+    pub run: Option<BudgetLimits>,
+    pub step: Option<BudgetLimits>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl Validate for Budget {
+    fn validate(&self) -> crate::Result<()> {
+        if !self.unrecognized.is_empty() {
+            bail!(
+                "unrecognized field{} devil.budget.{}",
+                if self.unrecognized.len() == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                self.unrecognized.keys().join(", devil.budget."),
             );
         }
+        for (name, limits) in [("run", &self.run), ("step", &self.step)] {
+            let Some(limits) = limits else {
+                continue;
+            };
+            if !limits.unrecognized.is_empty() {
+                return Err(crate::locate(
+                    anyhow!(
+                        "unrecognized field{} devil.budget.{name}.{}",
+                        if limits.unrecognized.len() == 1 {
+                            ""
+                        } else {
+                            "s"
+                        },
+                        limits
+                            .unrecognized
+                            .keys()
+                            .join(&format!(", devil.budget.{name}.")),
+                    ),
+                    name,
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BudgetLimits {
+    pub bytes_sent: Option<u64>,
+    pub bytes_received: Option<u64>,
+    pub connections: Option<u64>,
+    pub attempts: Option<u64>,
+    pub duration: Option<String>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+/// A named vantage point for steps to send traffic from.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Egress {
+    pub bind: Option<Value>,
+    pub proxy: Option<TcpProxy>,
+    pub resolver: Option<Value>,
+    pub rate: Option<Value>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl Validate for Egress {
+    fn validate(&self) -> crate::Result<()> {
+        if let Some(proxy) = &self.proxy {
+            proxy.validate()?;
+        }
+        if let Some(first) = self.unrecognized.keys().next() {
+            return Err(crate::locate(
+                anyhow!(
+                    "unrecognized field{} devil.egress.{}",
+                    if self.unrecognized.len() == 1 {
+                        ""
+                    } else {
+                        "s"
+                    },
+                    self.unrecognized.keys().join(", devil.egress."),
+                ),
+                first,
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Conditions to emulate on a step's connections, like a slow or lossy link.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Network {
+    pub bandwidth: Option<Value>,
+    pub latency: Option<Value>,
+    pub jitter: Option<Value>,
+    pub loss: Option<Value>,
+    pub fault: Option<TcpFault>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl Validate for Network {
+    fn validate(&self) -> crate::Result<()> {
+        if let Some(fault) = &self.fault {
+            fault.validate()?;
+        }
+        if let Some(first) = self.unrecognized.keys().next() {
+            return Err(crate::locate(
+                anyhow!(
+                    "unrecognized field{} devil.network.{}",
+                    if self.unrecognized.len() == 1 {
+                        ""
+                    } else {
+                        "s"
+                    },
+                    self.unrecognized.keys().join(", devil.network."),
+                ),
+                first,
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A login plan whose cookies and token are sent by steps tagged with its name in run.auth.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Auth {
+    pub path: Option<Value>,
+    #[serde(default)]
+    pub params: IndexMap<String, Value>,
+    pub header: Option<Value>,
+    pub token: Option<Value>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl Validate for Auth {
+    fn validate(&self) -> crate::Result<()> {
+        if self.path.is_none() {
+            bail!("devil.auth.path is required");
+        }
+        if let Some(first) = self.unrecognized.keys().next() {
+            return Err(crate::locate(
+                anyhow!(
+                    "unrecognized field{} devil.auth.{}",
+                    if self.unrecognized.len() == 1 {
+                        ""
+                    } else {
+                        "s"
+                    },
+                    self.unrecognized.keys().join(", devil.auth."),
+                ),
+                first,
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Mirror {
+    pub url: Option<Value>,
+    pub mode: Option<Value>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl Validate for Mirror {
+    fn validate(&self) -> crate::Result<()> {
+        if self.url.is_none() {
+            bail!("devil.mirror.url is required");
+        }
+        if let Some(first) = self.unrecognized.keys().next() {
+            return Err(crate::locate(
+                anyhow!(
+                    "unrecognized field{} devil.mirror.{}",
+                    if self.unrecognized.len() == 1 {
+                        ""
+                    } else {
+                        "s"
+                    },
+                    self.unrecognized.keys().join(", devil.mirror."),
+                ),
+                first,
+            ));
+        }
         Ok(())
     }
 }
@@ -77,6 +373,7 @@ impl Validate for Settings {
 pub struct Defaults {
     pub selector: Option<Selector>,
     pub graphql: Option<Graphql>,
+    pub grpc: Option<Grpc>,
     pub http: Option<Http>,
     pub h1c: Option<Http1>,
     pub h1: Option<Http1>,
@@ -91,6 +388,25 @@ pub struct Defaults {
     pub quic: Option<Quic>,
     pub dtls: Option<Tls>,
     pub udp: Option<Udp>,
+    pub crawl: Option<Crawl>,
+    pub discover: Option<Discover>,
+    pub forced_browse: Option<ForcedBrowse>,
+    pub vhost: Option<Vhost>,
+    pub takeover: Option<Takeover>,
+    pub banner: Option<Banner>,
+    pub session: Option<Session>,
+    pub command: Option<Command>,
+    pub script: Option<Script>,
+    pub grpc_reflect: Option<GrpcReflect>,
+    pub range: Option<Range>,
+    pub conditional: Option<Conditional>,
+    pub h2_attack: Option<H2Attack>,
+    pub alpn_matrix: Option<AlpnMatrix>,
+    pub keep_alive: Option<KeepAlive>,
+    pub tcp_burst: Option<TcpBurst>,
+    pub ws: Option<WebSocket>,
+    pub dns: Option<Dns>,
+    pub detect: Option<Detect>,
     pub run: Option<Run>,
     #[serde(default)]
     pub sync: IndexMap<String, Sync>,
@@ -117,6 +433,8 @@ pub enum ProtocolKind {
     GraphqlH2c,
     GraphqlH2,
     GraphqlH3,
+    GrpcH2c,
+    GrpcH2,
     Http,
     H1c,
     H1,
@@ -131,6 +449,26 @@ pub enum ProtocolKind {
     Dtls,
     Quic,
     Udp,
+    Module,
+    Crawl,
+    Discover,
+    ForcedBrowse,
+    Vhost,
+    Takeover,
+    Banner,
+    Session,
+    Command,
+    Script,
+    GrpcReflect,
+    Range,
+    Conditional,
+    H2Attack,
+    AlpnMatrix,
+    KeepAlive,
+    TcpBurst,
+    Ws,
+    Dns,
+    Detect,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -161,6 +499,9 @@ pub struct Step {
     #[serde(flatten)]
     pub protocols: StepProtocols,
     pub run: Option<Run>,
+    /// Whether the step changes state on the target, like deleting data or running a payload.
+    #[serde(default)]
+    pub destructive: bool,
     #[serde(default)]
     pub sync: IndexMap<String, Sync>,
     #[serde(default)]
@@ -186,6 +527,7 @@ impl Step {
         Step {
             run: run_defaults.into_iter().fold(self.run, Run::merge),
             protocols: self.protocols.apply_defaults(proto_defaults),
+            destructive: self.destructive,
             sync: self.sync,
             pause: self.pause,
             signal: self.signal,
@@ -333,6 +675,61 @@ impl Step {
                     x.validate()?;
                 };
             }
+            StepProtocols::GrpcH2c {
+                grpc,
+                h2c,
+                raw_h2c,
+                tcp,
+                raw_tcp,
+            } => {
+                self.unrecognized.remove("grpc");
+                self.unrecognized.remove("h2c");
+                self.unrecognized.remove("raw_h2c");
+                self.unrecognized.remove("tcp");
+                self.unrecognized.remove("raw_tcp");
+                grpc.validate()?;
+                h2c.validate()?;
+                if let Some(x) = &raw_h2c {
+                    x.validate()?;
+                };
+                if let Some(x) = &tcp {
+                    x.validate()?;
+                };
+                if let Some(x) = &raw_tcp {
+                    x.validate()?;
+                };
+            }
+            StepProtocols::GrpcH2 {
+                grpc,
+                h2,
+                raw_h2,
+                tls,
+                tcp,
+                raw_tcp,
+            } => {
+                self.unrecognized.remove("grpc");
+                self.unrecognized.remove("h2");
+                self.unrecognized.remove("raw_h2");
+                self.unrecognized.remove("tls");
+                self.unrecognized.remove("tcp");
+                self.unrecognized.remove("raw_tcp");
+                grpc.validate()?;
+                if let Some(x) = &h2 {
+                    x.validate()?;
+                };
+                if let Some(x) = &raw_h2 {
+                    x.validate()?;
+                };
+                if let Some(x) = &tls {
+                    x.validate()?;
+                };
+                if let Some(x) = &tcp {
+                    x.validate()?;
+                };
+                if let Some(x) = &raw_tcp {
+                    x.validate()?;
+                };
+            }
             StepProtocols::Http { http } => {
                 self.unrecognized.remove("http");
                 http.validate()?;
@@ -510,17 +907,100 @@ impl Step {
                 self.unrecognized.remove("udp");
                 udp.validate()?;
             }
+            StepProtocols::Module { module } => {
+                self.unrecognized.remove("module");
+                module.validate()?;
+            }
+            StepProtocols::Crawl { crawl } => {
+                self.unrecognized.remove("crawl");
+                crawl.validate()?;
+            }
+            StepProtocols::Discover { discover } => {
+                self.unrecognized.remove("discover");
+                discover.validate()?;
+            }
+            StepProtocols::ForcedBrowse { forced_browse } => {
+                self.unrecognized.remove("forced_browse");
+                forced_browse.validate()?;
+            }
+            StepProtocols::Vhost { vhost } => {
+                self.unrecognized.remove("vhost");
+                vhost.validate()?;
+            }
+            StepProtocols::Takeover { takeover } => {
+                self.unrecognized.remove("takeover");
+                takeover.validate()?;
+            }
+            StepProtocols::Banner { banner } => {
+                self.unrecognized.remove("banner");
+                banner.validate()?;
+            }
+            StepProtocols::Session { session } => {
+                self.unrecognized.remove("session");
+                session.validate()?;
+            }
+            StepProtocols::Command { command } => {
+                self.unrecognized.remove("command");
+                command.validate()?;
+            }
+            StepProtocols::Script { script } => {
+                self.unrecognized.remove("script");
+                script.validate()?;
+            }
+            StepProtocols::GrpcReflect { grpc_reflect } => {
+                self.unrecognized.remove("grpc_reflect");
+                grpc_reflect.validate()?;
+            }
+            StepProtocols::Range { range } => {
+                self.unrecognized.remove("range");
+                range.validate()?;
+            }
+            StepProtocols::Conditional { conditional } => {
+                self.unrecognized.remove("conditional");
+                conditional.validate()?;
+            }
+            StepProtocols::H2Attack { h2_attack } => {
+                self.unrecognized.remove("h2_attack");
+                h2_attack.validate()?;
+            }
+            StepProtocols::AlpnMatrix { alpn_matrix } => {
+                self.unrecognized.remove("alpn_matrix");
+                alpn_matrix.validate()?;
+            }
+            StepProtocols::KeepAlive { keep_alive } => {
+                self.unrecognized.remove("keep_alive");
+                keep_alive.validate()?;
+            }
+            StepProtocols::TcpBurst { tcp_burst } => {
+                self.unrecognized.remove("tcp_burst");
+                tcp_burst.validate()?;
+            }
+            StepProtocols::Ws { ws } => {
+                self.unrecognized.remove("ws");
+                ws.validate()?;
+            }
+            StepProtocols::Dns { dns } => {
+                self.unrecognized.remove("dns");
+                dns.validate()?;
+            }
+            StepProtocols::Detect { detect } => {
+                self.unrecognized.remove("detect");
+                detect.validate()?;
+            }
         }
-        if !self.unrecognized.is_empty() {
-            bail!(
-                "unrecognized field{} {}",
-                if self.unrecognized.len() == 1 {
-                    ""
-                } else {
-                    "s"
-                },
-                self.unrecognized.keys().join(", "),
-            );
+        if let Some(first) = self.unrecognized.keys().next() {
+            return Err(crate::locate(
+                anyhow!(
+                    "unrecognized field{} {}",
+                    if self.unrecognized.len() == 1 {
+                        ""
+                    } else {
+                        "s"
+                    },
+                    self.unrecognized.keys().join(", "),
+                ),
+                first,
+            ));
         }
         Ok(())
     }
@@ -569,6 +1049,22 @@ pub enum StepProtocols {
         quic: Option<Quic>,
         udp: Option<Udp>,
     },
+    // Plaintext gRPC needs h2c spelled out, otherwise we assume it runs over TLS.
+    GrpcH2c {
+        grpc: Grpc,
+        h2c: Http2,
+        raw_h2c: Option<RawHttp2>,
+        tcp: Option<Tcp>,
+        raw_tcp: Option<RawTcp>,
+    },
+    GrpcH2 {
+        grpc: Grpc,
+        h2: Option<Http2>,
+        raw_h2: Option<RawHttp2>,
+        tls: Option<Tls>,
+        tcp: Option<Tcp>,
+        raw_tcp: Option<RawTcp>,
+    },
     Http {
         http: Http,
     },
@@ -635,6 +1131,66 @@ pub enum StepProtocols {
     Udp {
         udp: Udp,
     },
+    Module {
+        module: Module,
+    },
+    Crawl {
+        crawl: Crawl,
+    },
+    Discover {
+        discover: Discover,
+    },
+    ForcedBrowse {
+        forced_browse: ForcedBrowse,
+    },
+    Vhost {
+        vhost: Vhost,
+    },
+    Takeover {
+        takeover: Takeover,
+    },
+    Banner {
+        banner: Banner,
+    },
+    Session {
+        session: Session,
+    },
+    Command {
+        command: Command,
+    },
+    Script {
+        script: Script,
+    },
+    GrpcReflect {
+        grpc_reflect: GrpcReflect,
+    },
+    Range {
+        range: Range,
+    },
+    Conditional {
+        conditional: Conditional,
+    },
+    H2Attack {
+        h2_attack: H2Attack,
+    },
+    AlpnMatrix {
+        alpn_matrix: AlpnMatrix,
+    },
+    KeepAlive {
+        keep_alive: KeepAlive,
+    },
+    TcpBurst {
+        tcp_burst: TcpBurst,
+    },
+    Ws {
+        ws: WebSocket,
+    },
+    Dns {
+        dns: Dns,
+    },
+    Detect {
+        detect: Detect,
+    },
 }
 
 impl StepProtocols {
@@ -718,6 +1274,34 @@ impl StepProtocols {
                 quic: Some(quic.unwrap_or_default().merge(default.quic)),
                 udp: Some(udp.unwrap_or_default().merge(default.udp)),
             },
+            Self::GrpcH2c {
+                grpc,
+                h2c,
+                raw_h2c,
+                tcp,
+                raw_tcp,
+            } => Self::GrpcH2c {
+                grpc: grpc.merge(default.grpc),
+                h2c: h2c.merge(default.h2c),
+                raw_h2c: Some(raw_h2c.unwrap_or_default().merge(default.raw_h2c)),
+                tcp: Some(tcp.unwrap_or_default().merge(default.tcp)),
+                raw_tcp: Some(raw_tcp.unwrap_or_default().merge(default.raw_tcp)),
+            },
+            Self::GrpcH2 {
+                grpc,
+                h2,
+                raw_h2,
+                tls,
+                tcp,
+                raw_tcp,
+            } => Self::GrpcH2 {
+                grpc: grpc.merge(default.grpc),
+                h2: Some(h2.unwrap_or_default().merge(default.h2)),
+                raw_h2: Some(raw_h2.unwrap_or_default().merge(default.raw_h2)),
+                tls: Some(tls.unwrap_or_default().merge(default.tls)),
+                tcp: Some(tcp.unwrap_or_default().merge(default.tcp)),
+                raw_tcp: Some(raw_tcp.unwrap_or_default().merge(default.raw_tcp)),
+            },
             Self::Http { http } => Self::Http {
                 http: http.merge(default.http),
             },
@@ -806,21 +1390,82 @@ impl StepProtocols {
             Self::Udp { udp } => Self::Udp {
                 udp: udp.merge(default.udp),
             },
-            _ => unreachable!(),
-        }
-    }
-
-    fn kind(&self) -> ProtocolKind {
-        match self {
-            Self::Graphql { .. } => ProtocolKind::Graphql,
-            Self::GraphqlH1c { .. } => ProtocolKind::GraphqlH1c,
-            Self::GraphqlH1 { .. } => ProtocolKind::GraphqlH1,
-            Self::GraphqlH2c { .. } => ProtocolKind::GraphqlH2c,
-            Self::GraphqlH2 { .. } => ProtocolKind::GraphqlH2,
-            Self::GraphqlH3 { .. } => ProtocolKind::GraphqlH3,
-            Self::Http { .. } => ProtocolKind::Http,
-            Self::H1c { .. } => ProtocolKind::H1c,
-            Self::H1 { .. } => ProtocolKind::H1,
+            // Modules apply their own plan's defaults.
+            Self::Module { module } => Self::Module { module },
+            Self::Crawl { crawl } => Self::Crawl {
+                crawl: crawl.merge(default.crawl),
+            },
+            Self::Discover { discover } => Self::Discover {
+                discover: discover.merge(default.discover),
+            },
+            Self::ForcedBrowse { forced_browse } => Self::ForcedBrowse {
+                forced_browse: forced_browse.merge(default.forced_browse),
+            },
+            Self::Vhost { vhost } => Self::Vhost {
+                vhost: vhost.merge(default.vhost),
+            },
+            Self::Takeover { takeover } => Self::Takeover {
+                takeover: takeover.merge(default.takeover),
+            },
+            Self::Banner { banner } => Self::Banner {
+                banner: banner.merge(default.banner),
+            },
+            Self::Session { session } => Self::Session {
+                session: session.merge(default.session),
+            },
+            Self::Command { command } => Self::Command {
+                command: command.merge(default.command),
+            },
+            Self::Script { script } => Self::Script {
+                script: script.merge(default.script),
+            },
+            Self::GrpcReflect { grpc_reflect } => Self::GrpcReflect {
+                grpc_reflect: grpc_reflect.merge(default.grpc_reflect),
+            },
+            Self::Range { range } => Self::Range {
+                range: range.merge(default.range),
+            },
+            Self::Conditional { conditional } => Self::Conditional {
+                conditional: conditional.merge(default.conditional),
+            },
+            Self::H2Attack { h2_attack } => Self::H2Attack {
+                h2_attack: h2_attack.merge(default.h2_attack),
+            },
+            Self::AlpnMatrix { alpn_matrix } => Self::AlpnMatrix {
+                alpn_matrix: alpn_matrix.merge(default.alpn_matrix),
+            },
+            Self::KeepAlive { keep_alive } => Self::KeepAlive {
+                keep_alive: keep_alive.merge(default.keep_alive),
+            },
+            Self::TcpBurst { tcp_burst } => Self::TcpBurst {
+                tcp_burst: tcp_burst.merge(default.tcp_burst),
+            },
+            Self::Ws { ws } => Self::Ws {
+                ws: ws.merge(default.ws),
+            },
+            Self::Dns { dns } => Self::Dns {
+                dns: dns.merge(default.dns),
+            },
+            Self::Detect { detect } => Self::Detect {
+                detect: detect.merge(default.detect),
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    fn kind(&self) -> ProtocolKind {
+        match self {
+            Self::Graphql { .. } => ProtocolKind::Graphql,
+            Self::GraphqlH1c { .. } => ProtocolKind::GraphqlH1c,
+            Self::GraphqlH1 { .. } => ProtocolKind::GraphqlH1,
+            Self::GraphqlH2c { .. } => ProtocolKind::GraphqlH2c,
+            Self::GraphqlH2 { .. } => ProtocolKind::GraphqlH2,
+            Self::GraphqlH3 { .. } => ProtocolKind::GraphqlH3,
+            Self::GrpcH2c { .. } => ProtocolKind::GrpcH2c,
+            Self::GrpcH2 { .. } => ProtocolKind::GrpcH2,
+            Self::Http { .. } => ProtocolKind::Http,
+            Self::H1c { .. } => ProtocolKind::H1c,
+            Self::H1 { .. } => ProtocolKind::H1,
             Self::H2c { .. } => ProtocolKind::H2c,
             Self::H2 { .. } => ProtocolKind::H2,
             Self::H3 { .. } => ProtocolKind::H3,
@@ -832,6 +1477,26 @@ impl StepProtocols {
             Self::RawTcp { .. } => ProtocolKind::RawTcp,
             Self::Quic { .. } => ProtocolKind::Quic,
             Self::Udp { .. } => ProtocolKind::Udp,
+            Self::Module { .. } => ProtocolKind::Module,
+            Self::Crawl { .. } => ProtocolKind::Crawl,
+            Self::Discover { .. } => ProtocolKind::Discover,
+            Self::ForcedBrowse { .. } => ProtocolKind::ForcedBrowse,
+            Self::Vhost { .. } => ProtocolKind::Vhost,
+            Self::Takeover { .. } => ProtocolKind::Takeover,
+            Self::Banner { .. } => ProtocolKind::Banner,
+            Self::Session { .. } => ProtocolKind::Session,
+            Self::Command { .. } => ProtocolKind::Command,
+            Self::Script { .. } => ProtocolKind::Script,
+            Self::GrpcReflect { .. } => ProtocolKind::GrpcReflect,
+            Self::Range { .. } => ProtocolKind::Range,
+            Self::Conditional { .. } => ProtocolKind::Conditional,
+            Self::H2Attack { .. } => ProtocolKind::H2Attack,
+            Self::AlpnMatrix { .. } => ProtocolKind::AlpnMatrix,
+            Self::KeepAlive { .. } => ProtocolKind::KeepAlive,
+            Self::TcpBurst { .. } => ProtocolKind::TcpBurst,
+            Self::Ws { .. } => ProtocolKind::Ws,
+            Self::Dns { .. } => ProtocolKind::Dns,
+            Self::Detect { .. } => ProtocolKind::Detect,
         }
     }
 }
@@ -847,6 +1512,12 @@ pub struct Run {
     pub count: Option<Value>,
     pub parallel: Option<Value>,
     pub share: Option<Value>,
+    pub on_error: Option<Value>,
+    pub follow: Option<Follow>,
+    pub egress: Option<Value>,
+    pub network: Option<Value>,
+    pub adaptive: Option<Adaptive>,
+    pub auth: Option<Value>,
     #[serde(flatten)]
     pub unrecognized: toml::Table,
 }
@@ -866,46 +1537,1403 @@ impl Merge for Run {
             count: first.count.or(second.count),
             parallel: first.parallel.or(second.parallel),
             share: first.share.or(second.share),
+            on_error: first.on_error.or(second.on_error),
+            follow: Follow::merge(first.follow, second.follow),
+            egress: first.egress.or(second.egress),
+            network: first.network.or(second.network),
+            adaptive: Adaptive::merge(first.adaptive, second.adaptive),
+            auth: first.auth.or(second.auth),
+            unrecognized: toml::Table::new(),
+        })
+    }
+}
+
+/// A controller which adjusts how many parallel jobs are in flight based on their results.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Adaptive {
+    pub algorithm: Option<Value>,
+    pub target_latency: Option<Value>,
+    pub max_error_rate: Option<Value>,
+    pub initial: Option<Value>,
+    pub min: Option<Value>,
+    pub max: Option<Value>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl Merge for Adaptive {
+    fn merge(first: Option<Self>, second: Option<Self>) -> Option<Self> {
+        let Some(first) = first else {
+            return second;
+        };
+        let Some(second) = second else {
+            return Some(first);
+        };
+        Some(Self {
+            algorithm: Value::merge(first.algorithm, second.algorithm),
+            target_latency: Value::merge(first.target_latency, second.target_latency),
+            max_error_rate: Value::merge(first.max_error_rate, second.max_error_rate),
+            initial: Value::merge(first.initial, second.initial),
+            min: Value::merge(first.min, second.min),
+            max: Value::merge(first.max, second.max),
+            unrecognized: toml::Table::new(),
+        })
+    }
+}
+
+/// Values found in a job's output to run the step again with.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Follow {
+    pub values: Option<Iterable>,
+    pub max_depth: Option<Value>,
+    pub max_jobs: Option<Value>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl Merge for Follow {
+    fn merge(first: Option<Self>, second: Option<Self>) -> Option<Self> {
+        let Some(first) = first else {
+            return second;
+        };
+        let Some(second) = second else {
+            return Some(first);
+        };
+        Some(Self {
+            values: first.values.or(second.values),
+            max_depth: Value::merge(first.max_depth, second.max_depth),
+            max_jobs: Value::merge(first.max_jobs, second.max_jobs),
+            unrecognized: toml::Table::new(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sync {
+    Barrier { count: Value },
+    Mutex,
+    PriorityMutex,
+    Semaphore { permits: Value },
+    PrioritySemaphore { permits: Value },
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Graphql {
+    pub url: Option<Value>,
+    pub query: Option<Value>,
+    #[serde(alias = "variables")]
+    pub params: Option<Table>,
+    pub operation: Option<Value>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl Graphql {
+    fn merge(self, second: Option<Self>) -> Self {
+        let Some(second) = second else {
+            return self;
+        };
+        Self {
+            url: Value::merge(self.url, second.url),
+            query: Value::merge(self.query, second.query),
+            params: Table::merge(self.params, second.params),
+            operation: Value::merge(self.operation, second.operation),
+            unrecognized: toml::Table::new(),
+        }
+    }
+
+    fn validate(&self) -> crate::Result<()> {
+        if !self.unrecognized.is_empty() {
+            bail!(
+                "unrecognized field{} {}",
+                if self.unrecognized.len() == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                self.unrecognized.keys().join(", "),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// A unary gRPC call, with the request given either as an already encoded protobuf message or
+/// as JSON to encode using a descriptor set.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Grpc {
+    pub url: Option<Value>,
+    pub service: Option<Value>,
+    pub method: Option<Value>,
+    pub message: Option<Value>,
+    pub json: Option<Value>,
+    pub descriptor_set: Option<Value>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl Grpc {
+    fn merge(self, second: Option<Self>) -> Self {
+        let Some(second) = second else {
+            return self;
+        };
+        Self {
+            url: Value::merge(self.url, second.url),
+            service: Value::merge(self.service, second.service),
+            method: Value::merge(self.method, second.method),
+            message: Value::merge(self.message, second.message),
+            json: Value::merge(self.json, second.json),
+            descriptor_set: Value::merge(self.descriptor_set, second.descriptor_set),
+            unrecognized: toml::Table::new(),
+        }
+    }
+
+    fn validate(&self) -> crate::Result<()> {
+        if !self.unrecognized.is_empty() {
+            bail!(
+                "unrecognized field{} {}",
+                if self.unrecognized.len() == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                self.unrecognized.keys().join(", "),
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Http {
+    pub url: Option<Value>,
+    pub method: Option<Value>,
+    pub headers: Option<Table>,
+    pub add_content_length: Option<Value>,
+    pub body: Option<Value>,
+    pub http3: Option<Value>,
+    pub similarity: Option<ValueOrArray<Value>>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl Http {
+    fn merge(self, second: Option<Self>) -> Self {
+        let Some(second) = second else {
+            return self;
+        };
+        Self {
+            url: Value::merge(self.url, second.url),
+            method: Value::merge(self.method, second.method),
+            headers: Table::merge(self.headers, second.headers),
+            add_content_length: Value::merge(self.add_content_length, second.add_content_length),
+            body: Value::merge(self.body, second.body),
+            http3: Value::merge(self.http3, second.http3),
+            similarity: ValueOrArray::merge(self.similarity, second.similarity),
+            unrecognized: toml::Table::new(),
+        }
+    }
+
+    fn validate(&self) -> crate::Result<()> {
+        if !self.unrecognized.is_empty() {
+            bail!(
+                "unrecognized field{} {}",
+                if self.unrecognized.len() == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                self.unrecognized.keys().join(", "),
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Http1 {
+    pub version_string: Option<Value>,
+    pub sign: Option<Sign>,
+    pub cache: Option<Value>,
+    pub chunked: Option<Http1Chunked>,
+    pub expect_continue: Option<Http1ExpectContinue>,
+    pub head: Option<Value>,
+    pub read: Option<Http1Read>,
+    pub permissive: Option<Value>,
+    #[serde(flatten, default)]
+    pub common: Http,
+}
+
+impl Http1 {
+    fn merge(self, default: Option<Self>) -> Self {
+        let Some(default) = default else {
+            return self;
+        };
+        Self {
+            version_string: Value::merge(self.version_string, default.version_string),
+            sign: Sign::merge(self.sign, default.sign),
+            cache: Value::merge(self.cache, default.cache),
+            chunked: Http1Chunked::merge(self.chunked, default.chunked),
+            expect_continue: Http1ExpectContinue::merge(
+                self.expect_continue,
+                default.expect_continue,
+            ),
+            head: Value::merge(self.head, default.head),
+            read: Http1Read::merge(self.read, default.read),
+            permissive: Value::merge(self.permissive, default.permissive),
+            common: self.common.merge(Some(default.common)),
+        }
+    }
+
+    fn validate(&self) -> crate::Result<()> {
+        if let Some(sign) = &self.sign {
+            sign.validate()?;
+        }
+        if let Some(chunked) = &self.chunked {
+            chunked.validate()?;
+        }
+        if let Some(expect_continue) = &self.expect_continue {
+            expect_continue.validate()?;
+        }
+        if let Some(read) = &self.read {
+            read.validate()?;
+        }
+        if self.head.is_some() {
+            if self.common.headers.is_some() {
+                bail!("headers can't be set with head, which must include them");
+            }
+            if self.sign.is_some() || self.expect_continue.is_some() {
+                bail!("sign and expect_continue add headers, so they can't be set with head");
+            }
+        }
+        if self.common.http3.is_some() {
+            bail!("http3 is only supported for http steps");
+        }
+        self.common.validate()?;
+        Ok(())
+    }
+}
+
+/// Sends the body with chunked transfer encoding.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Http1Chunked {
+    pub sizes: Option<ValueOrArray<Value>>,
+    pub extensions: Option<ValueOrArray<Value>>,
+    pub trailers: Option<Table>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl Merge for Http1Chunked {
+    fn merge(first: Option<Self>, second: Option<Self>) -> Option<Self> {
+        let Some(first) = first else {
+            return second;
+        };
+        let Some(second) = second else {
+            return Some(first);
+        };
+        Some(Self {
+            sizes: ValueOrArray::merge(first.sizes, second.sizes),
+            extensions: ValueOrArray::merge(first.extensions, second.extensions),
+            trailers: Table::merge(first.trailers, second.trailers),
             unrecognized: toml::Table::new(),
         })
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
-pub enum Sync {
-    Barrier { count: Value },
-    Mutex,
-    PriorityMutex,
-    Semaphore { permits: Value },
-    PrioritySemaphore { permits: Value },
-}
-
+impl Http1Chunked {
+    fn validate(&self) -> crate::Result<()> {
+        if !self.unrecognized.is_empty() {
+            bail!(
+                "unrecognized field{} http1.chunked.{}",
+                if self.unrecognized.len() == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                self.unrecognized.keys().join(", http1.chunked."),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Waits for a 100 Continue response before sending the body.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Http1ExpectContinue {
+    pub header: Option<Value>,
+    pub timeout: Option<Value>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl Merge for Http1ExpectContinue {
+    fn merge(first: Option<Self>, second: Option<Self>) -> Option<Self> {
+        let Some(first) = first else {
+            return second;
+        };
+        let Some(second) = second else {
+            return Some(first);
+        };
+        Some(Self {
+            header: Value::merge(first.header, second.header),
+            timeout: Value::merge(first.timeout, second.timeout),
+            unrecognized: toml::Table::new(),
+        })
+    }
+}
+
+impl Http1ExpectContinue {
+    fn validate(&self) -> crate::Result<()> {
+        if !self.unrecognized.is_empty() {
+            bail!(
+                "unrecognized field{} http1.expect_continue.{}",
+                if self.unrecognized.len() == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                self.unrecognized.keys().join(", http1.expect_continue."),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// When to stop reading a response besides when its framing is complete or the connection
+/// closes.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Http1Read {
+    pub content_length: Option<Value>,
+    pub idle: Option<Value>,
+    pub pattern: Option<Value>,
+    pub bytes: Option<Value>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl Merge for Http1Read {
+    fn merge(first: Option<Self>, second: Option<Self>) -> Option<Self> {
+        let Some(first) = first else {
+            return second;
+        };
+        let Some(second) = second else {
+            return Some(first);
+        };
+        Some(Self {
+            content_length: Value::merge(first.content_length, second.content_length),
+            idle: Value::merge(first.idle, second.idle),
+            pattern: Value::merge(first.pattern, second.pattern),
+            bytes: Value::merge(first.bytes, second.bytes),
+            unrecognized: toml::Table::new(),
+        })
+    }
+}
+
+impl Http1Read {
+    fn validate(&self) -> crate::Result<()> {
+        if !self.unrecognized.is_empty() {
+            bail!(
+                "unrecognized field{} http1.read.{}",
+                if self.unrecognized.len() == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                self.unrecognized.keys().join(", http1.read."),
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Sign {
+    pub kind: Option<Value>,
+    pub access_key: Option<Value>,
+    pub secret_key: Option<Value>,
+    pub session_token: Option<Value>,
+    pub region: Option<Value>,
+    pub service: Option<Value>,
+    pub algorithm: Option<Value>,
+    pub components: Option<Value>,
+    pub separator: Option<Value>,
+    pub encoding: Option<Value>,
+    pub header: Option<Value>,
+    pub prefix: Option<Value>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl Merge for Sign {
+    fn merge(first: Option<Self>, second: Option<Self>) -> Option<Self> {
+        let Some(first) = first else {
+            return second;
+        };
+        let Some(second) = second else {
+            return Some(first);
+        };
+        Some(Self {
+            kind: Value::merge(first.kind, second.kind),
+            access_key: Value::merge(first.access_key, second.access_key),
+            secret_key: Value::merge(first.secret_key, second.secret_key),
+            session_token: Value::merge(first.session_token, second.session_token),
+            region: Value::merge(first.region, second.region),
+            service: Value::merge(first.service, second.service),
+            algorithm: Value::merge(first.algorithm, second.algorithm),
+            components: Value::merge(first.components, second.components),
+            separator: Value::merge(first.separator, second.separator),
+            encoding: Value::merge(first.encoding, second.encoding),
+            header: Value::merge(first.header, second.header),
+            prefix: Value::merge(first.prefix, second.prefix),
+            unrecognized: toml::Table::new(),
+        })
+    }
+}
+
+impl Sign {
+    fn validate(&self) -> crate::Result<()> {
+        if self.kind.is_none() {
+            bail!("sign.kind is required");
+        }
+        if !self.unrecognized.is_empty() {
+            bail!(
+                "unrecognized field{} sign.{}",
+                if self.unrecognized.len() == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                self.unrecognized.keys().join(", sign."),
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Http2 {
+    pub trailers: Option<Table>,
+    #[serde(flatten, default)]
+    pub common: Http,
+}
+
+impl Http2 {
+    fn merge(self, default: Option<Self>) -> Self {
+        let Some(default) = default else {
+            return self;
+        };
+        Self {
+            trailers: Table::merge(self.trailers, default.trailers),
+            common: self.common.merge(Some(default.common)),
+        }
+    }
+
+    fn validate(&self) -> crate::Result<()> {
+        if self.common.http3.is_some() {
+            bail!("http3 is only supported for http steps");
+        }
+        self.common.validate()?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Http3 {
+    #[serde(flatten)]
+    pub common: Http,
+}
+
+impl Http3 {
+    fn merge(self, default: Option<Self>) -> Self {
+        let Some(default) = default else {
+            return self;
+        };
+        Self {
+            common: self.common.merge(Some(default.common)),
+        }
+    }
+
+    fn validate(&self) -> crate::Result<()> {
+        if self.common.http3.is_some() {
+            bail!("http3 is only supported for http steps");
+        }
+        self.common.validate()?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Tls {
+    pub host: Option<Value>,
+    pub port: Option<Value>,
+    pub server_name: Option<Value>,
+    pub alpn: Option<ValueOrArray<Value>>,
+    pub body: Option<Value>,
+    pub version: Option<Value>,
+    pub min_version: Option<Value>,
+    pub max_version: Option<Value>,
+    pub cipher_suites: Option<ValueOrArray<Value>>,
+    pub pin: Option<TlsPin>,
+    pub client: Option<TlsClient>,
+    pub ca: Option<Value>,
+    pub ca_file: Option<Value>,
+    pub insecure: Option<Value>,
+    pub resume: Option<Value>,
+    pub session_tickets: Option<Value>,
+    pub expect: Option<ValueOrArray<Expect>>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl Tls {
+    fn merge(self, default: Option<Self>) -> Self {
+        let Some(default) = default else {
+            return self;
+        };
+        Self {
+            host: Value::merge(self.host, default.host),
+            port: Value::merge(self.port, default.port),
+            server_name: Value::merge(self.server_name, default.server_name),
+            alpn: ValueOrArray::merge(self.alpn, default.alpn),
+            body: Value::merge(self.body, default.body),
+            version: Value::merge(self.version, default.version),
+            min_version: Value::merge(self.min_version, default.min_version),
+            max_version: Value::merge(self.max_version, default.max_version),
+            cipher_suites: ValueOrArray::merge(self.cipher_suites, default.cipher_suites),
+            pin: TlsPin::merge(self.pin, default.pin),
+            client: TlsClient::merge(self.client, default.client),
+            ca: Value::merge(self.ca, default.ca),
+            ca_file: Value::merge(self.ca_file, default.ca_file),
+            insecure: Value::merge(self.insecure, default.insecure),
+            resume: Value::merge(self.resume, default.resume),
+            session_tickets: Value::merge(self.session_tickets, default.session_tickets),
+            expect: ValueOrArray::merge(self.expect, default.expect),
+            unrecognized: toml::Table::new(),
+        }
+    }
+
+    fn validate(&self) -> crate::Result<()> {
+        if !self.unrecognized.is_empty() {
+            bail!(
+                "unrecognized field{} {}",
+                if self.unrecognized.len() == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                self.unrecognized.keys().join(", "),
+            );
+        }
+        if let Some(pin) = &self.pin {
+            if !pin.unrecognized.is_empty() {
+                bail!(
+                    "unrecognized field{} tls.pin.{}",
+                    if pin.unrecognized.len() == 1 { "" } else { "s" },
+                    pin.unrecognized.keys().join(", tls.pin."),
+                );
+            }
+        }
+        if let Some(client) = &self.client {
+            if !client.unrecognized.is_empty() {
+                bail!(
+                    "unrecognized field{} tls.client.{}",
+                    if client.unrecognized.len() == 1 {
+                        ""
+                    } else {
+                        "s"
+                    },
+                    client.unrecognized.keys().join(", tls.client."),
+                );
+            }
+        }
+        for expect in self.expect.iter().flatten() {
+            expect.validate("tls")?;
+        }
+        Ok(())
+    }
+}
+
+/// Values the server's certificate must match for the handshake to be accepted.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct TlsPin {
+    pub spki_sha256: Option<ValueOrArray<Value>>,
+    pub serial: Option<ValueOrArray<Value>>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl Merge for TlsPin {
+    fn merge(first: Option<Self>, second: Option<Self>) -> Option<Self> {
+        let Some(first) = first else {
+            return second;
+        };
+        let Some(second) = second else {
+            return Some(first);
+        };
+        Some(Self {
+            spki_sha256: ValueOrArray::merge(first.spki_sha256, second.spki_sha256),
+            serial: ValueOrArray::merge(first.serial, second.serial),
+            unrecognized: toml::Table::new(),
+        })
+    }
+}
+
+/// A certificate for the client to present if the server asks for one. The chain and key are
+/// each given as PEM, either inline or as the path of a file to read.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct TlsClient {
+    pub cert: Option<Value>,
+    pub cert_file: Option<Value>,
+    pub key: Option<Value>,
+    pub key_file: Option<Value>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl Merge for TlsClient {
+    fn merge(first: Option<Self>, second: Option<Self>) -> Option<Self> {
+        let Some(first) = first else {
+            return second;
+        };
+        let Some(second) = second else {
+            return Some(first);
+        };
+        // Inline and file values are alternatives, so a default only fills in a missing pair.
+        let (cert, cert_file) = if first.cert.is_some() || first.cert_file.is_some() {
+            (first.cert, first.cert_file)
+        } else {
+            (second.cert, second.cert_file)
+        };
+        let (key, key_file) = if first.key.is_some() || first.key_file.is_some() {
+            (first.key, first.key_file)
+        } else {
+            (second.key, second.key_file)
+        };
+        Some(Self {
+            cert,
+            cert_file,
+            key,
+            key_file,
+            unrecognized: toml::Table::new(),
+        })
+    }
+}
+
+/// A pattern checked against received bytes as they arrive.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Expect {
+    pub pattern: Option<Value>,
+    pub within: Option<Value>,
+    pub abort: Option<Value>,
+    pub on_fail: Option<Value>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl Merge for Expect {
+    fn merge(first: Option<Self>, second: Option<Self>) -> Option<Self> {
+        let Some(first) = first else {
+            return second;
+        };
+        let Some(second) = second else {
+            return Some(first);
+        };
+        Some(Self {
+            pattern: Value::merge(first.pattern, second.pattern),
+            within: Value::merge(first.within, second.within),
+            abort: Value::merge(first.abort, second.abort),
+            on_fail: Value::merge(first.on_fail, second.on_fail),
+            unrecognized: toml::Table::new(),
+        })
+    }
+}
+
+impl Expect {
+    fn validate(&self, protocol: &str) -> crate::Result<()> {
+        if self.pattern.is_none() {
+            bail!("{protocol}.expect.pattern is required");
+        }
+        if !self.unrecognized.is_empty() {
+            bail!(
+                "unrecognized field{} {protocol}.expect.{}",
+                if self.unrecognized.len() == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                self.unrecognized
+                    .keys()
+                    .join(&format!(", {protocol}.expect.")),
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Module {
+    pub path: Option<Value>,
+    #[serde(default)]
+    pub params: IndexMap<String, Value>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl Module {
+    fn validate(&self) -> crate::Result<()> {
+        if self.path.is_none() {
+            bail!("module.path is required");
+        }
+        if !self.unrecognized.is_empty() {
+            bail!(
+                "unrecognized field{} {}",
+                if self.unrecognized.len() == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                self.unrecognized.keys().join(", "),
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Crawl {
+    pub url: Option<Value>,
+    pub scope: Option<Value>,
+    pub max_depth: Option<Value>,
+    pub max_pages: Option<Value>,
+    pub form_steps: Option<Value>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl Crawl {
+    fn merge(self, default: Option<Self>) -> Self {
+        let Some(default) = default else {
+            return self;
+        };
+        Self {
+            url: Value::merge(self.url, default.url),
+            scope: Value::merge(self.scope, default.scope),
+            max_depth: Value::merge(self.max_depth, default.max_depth),
+            max_pages: Value::merge(self.max_pages, default.max_pages),
+            form_steps: Value::merge(self.form_steps, default.form_steps),
+            unrecognized: toml::Table::new(),
+        }
+    }
+
+    fn validate(&self) -> crate::Result<()> {
+        if self.url.is_none() {
+            bail!("crawl.url is required");
+        }
+        if !self.unrecognized.is_empty() {
+            bail!(
+                "unrecognized field{} {}",
+                if self.unrecognized.len() == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                self.unrecognized.keys().join(", "),
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Discover {
+    pub url: Option<Value>,
+    pub robots: Option<Value>,
+    pub sitemaps: Option<Value>,
+    pub max_sitemaps: Option<Value>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl Discover {
+    fn merge(self, default: Option<Self>) -> Self {
+        let Some(default) = default else {
+            return self;
+        };
+        Self {
+            url: Value::merge(self.url, default.url),
+            robots: Value::merge(self.robots, default.robots),
+            sitemaps: Value::merge(self.sitemaps, default.sitemaps),
+            max_sitemaps: Value::merge(self.max_sitemaps, default.max_sitemaps),
+            unrecognized: toml::Table::new(),
+        }
+    }
+
+    fn validate(&self) -> crate::Result<()> {
+        if self.url.is_none() {
+            bail!("discover.url is required");
+        }
+        if !self.unrecognized.is_empty() {
+            bail!(
+                "unrecognized field{} {}",
+                if self.unrecognized.len() == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                self.unrecognized.keys().join(", "),
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ForcedBrowse {
+    pub url: Option<Value>,
+    pub words: Option<Iterable>,
+    pub extensions: Option<Iterable>,
+    pub max_depth: Option<Value>,
+    pub max_requests: Option<Value>,
+    pub concurrency: Option<Value>,
+    pub length_tolerance: Option<Value>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl ForcedBrowse {
+    fn merge(self, default: Option<Self>) -> Self {
+        let Some(default) = default else {
+            return self;
+        };
+        Self {
+            url: Value::merge(self.url, default.url),
+            words: self.words.or(default.words),
+            extensions: self.extensions.or(default.extensions),
+            max_depth: Value::merge(self.max_depth, default.max_depth),
+            max_requests: Value::merge(self.max_requests, default.max_requests),
+            concurrency: Value::merge(self.concurrency, default.concurrency),
+            length_tolerance: Value::merge(self.length_tolerance, default.length_tolerance),
+            unrecognized: toml::Table::new(),
+        }
+    }
+
+    fn validate(&self) -> crate::Result<()> {
+        if self.url.is_none() {
+            bail!("forced_browse.url is required");
+        }
+        if self.words.is_none() {
+            bail!("forced_browse.words is required");
+        }
+        if !self.unrecognized.is_empty() {
+            bail!(
+                "unrecognized field{} {}",
+                if self.unrecognized.len() == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                self.unrecognized.keys().join(", "),
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Vhost {
+    pub url: Option<Value>,
+    pub hosts: Option<Iterable>,
+    pub sni: Option<Value>,
+    pub length_tolerance: Option<Value>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl Vhost {
+    fn merge(self, default: Option<Self>) -> Self {
+        let Some(default) = default else {
+            return self;
+        };
+        Self {
+            url: Value::merge(self.url, default.url),
+            hosts: self.hosts.or(default.hosts),
+            sni: Value::merge(self.sni, default.sni),
+            length_tolerance: Value::merge(self.length_tolerance, default.length_tolerance),
+            unrecognized: toml::Table::new(),
+        }
+    }
+
+    fn validate(&self) -> crate::Result<()> {
+        if self.url.is_none() {
+            bail!("vhost.url is required");
+        }
+        if self.hosts.is_none() {
+            bail!("vhost.hosts is required");
+        }
+        if !self.unrecognized.is_empty() {
+            bail!(
+                "unrecognized field{} {}",
+                if self.unrecognized.len() == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                self.unrecognized.keys().join(", "),
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Takeover {
+    pub hosts: Option<Iterable>,
+    pub resolver: Option<Value>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl Takeover {
+    fn merge(self, default: Option<Self>) -> Self {
+        let Some(default) = default else {
+            return self;
+        };
+        Self {
+            hosts: self.hosts.or(default.hosts),
+            resolver: Value::merge(self.resolver, default.resolver),
+            unrecognized: toml::Table::new(),
+        }
+    }
+
+    fn validate(&self) -> crate::Result<()> {
+        if self.hosts.is_none() {
+            bail!("takeover.hosts is required");
+        }
+        if !self.unrecognized.is_empty() {
+            bail!(
+                "unrecognized field{} {}",
+                if self.unrecognized.len() == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                self.unrecognized.keys().join(", "),
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Banner {
+    pub targets: Option<Iterable>,
+    pub probe: Option<Value>,
+    pub timeout: Option<Value>,
+    pub max_bytes: Option<Value>,
+    pub concurrency: Option<Value>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl Banner {
+    fn merge(self, default: Option<Self>) -> Self {
+        let Some(default) = default else {
+            return self;
+        };
+        Self {
+            targets: self.targets.or(default.targets),
+            probe: Value::merge(self.probe, default.probe),
+            timeout: Value::merge(self.timeout, default.timeout),
+            max_bytes: Value::merge(self.max_bytes, default.max_bytes),
+            concurrency: Value::merge(self.concurrency, default.concurrency),
+            unrecognized: toml::Table::new(),
+        }
+    }
+
+    fn validate(&self) -> crate::Result<()> {
+        if self.targets.is_none() {
+            bail!("banner.targets is required");
+        }
+        if !self.unrecognized.is_empty() {
+            bail!(
+                "unrecognized field{} {}",
+                if self.unrecognized.len() == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                self.unrecognized.keys().join(", "),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Virtual users which each run a plan as a journey, with their own cookies and variables.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub path: Option<Value>,
+    #[serde(default)]
+    pub params: IndexMap<String, Value>,
+    pub users: Option<Value>,
+    pub iterations: Option<Value>,
+    pub ramp_up: Option<Value>,
+    pub think_time: Option<ThinkTime>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl Session {
+    fn merge(self, default: Option<Self>) -> Self {
+        let Some(default) = default else {
+            return self;
+        };
+        let mut params = default.params;
+        params.extend(self.params);
+        Self {
+            path: Value::merge(self.path, default.path),
+            params,
+            users: Value::merge(self.users, default.users),
+            iterations: Value::merge(self.iterations, default.iterations),
+            ramp_up: Value::merge(self.ramp_up, default.ramp_up),
+            think_time: ThinkTime::merge(self.think_time, default.think_time),
+            unrecognized: toml::Table::new(),
+        }
+    }
+
+    fn validate(&self) -> crate::Result<()> {
+        if self.path.is_none() {
+            bail!("session.path is required");
+        }
+        if !self.unrecognized.is_empty() {
+            bail!(
+                "unrecognized field{} {}",
+                if self.unrecognized.len() == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                self.unrecognized.keys().join(", "),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// How long a virtual user pauses between steps.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ThinkTime {
+    pub distribution: Option<Value>,
+    pub min: Option<Value>,
+    pub max: Option<Value>,
+    pub mean: Option<Value>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl Merge for ThinkTime {
+    fn merge(first: Option<Self>, second: Option<Self>) -> Option<Self> {
+        let Some(first) = first else {
+            return second;
+        };
+        let Some(second) = second else {
+            return Some(first);
+        };
+        Some(Self {
+            distribution: Value::merge(first.distribution, second.distribution),
+            min: Value::merge(first.min, second.min),
+            max: Value::merge(first.max, second.max),
+            mean: Value::merge(first.mean, second.mean),
+            unrecognized: toml::Table::new(),
+        })
+    }
+}
+
+/// Runs a local program, for tools devil doesn't implement itself.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Command {
+    pub program: Option<Value>,
+    pub args: Option<Iterable>,
+    pub stdin: Option<Value>,
+    #[serde(default)]
+    pub env: IndexMap<String, Value>,
+    pub dir: Option<Value>,
+    pub timeout: Option<Value>,
+    pub max_output: Option<Value>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl Command {
+    fn merge(self, default: Option<Self>) -> Self {
+        let Some(default) = default else {
+            return self;
+        };
+        let mut env = default.env;
+        env.extend(self.env);
+        Self {
+            program: Value::merge(self.program, default.program),
+            args: self.args.or(default.args),
+            stdin: Value::merge(self.stdin, default.stdin),
+            env,
+            dir: Value::merge(self.dir, default.dir),
+            timeout: Value::merge(self.timeout, default.timeout),
+            max_output: Value::merge(self.max_output, default.max_output),
+            unrecognized: toml::Table::new(),
+        }
+    }
+
+    fn validate(&self) -> crate::Result<()> {
+        if self.program.is_none() {
+            bail!("command.program is required");
+        }
+        if !self.unrecognized.is_empty() {
+            bail!(
+                "unrecognized field{} {}",
+                if self.unrecognized.len() == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                self.unrecognized.keys().join(", "),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Runs a Rhai script for glue logic CEL can't express.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Script {
+    pub source: Option<Value>,
+    pub max_operations: Option<Value>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl Script {
+    fn merge(self, default: Option<Self>) -> Self {
+        let Some(default) = default else {
+            return self;
+        };
+        Self {
+            source: Value::merge(self.source, default.source),
+            max_operations: Value::merge(self.max_operations, default.max_operations),
+            unrecognized: toml::Table::new(),
+        }
+    }
+
+    fn validate(&self) -> crate::Result<()> {
+        if self.source.is_none() {
+            bail!("script.source is required");
+        }
+        if !self.unrecognized.is_empty() {
+            bail!(
+                "unrecognized field{} {}",
+                if self.unrecognized.len() == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                self.unrecognized.keys().join(", "),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Lists a gRPC server's services and methods using server reflection.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct GrpcReflect {
+    pub url: Option<Value>,
+    pub timeout: Option<Value>,
+    pub save_descriptors: Option<Value>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl GrpcReflect {
+    fn merge(self, default: Option<Self>) -> Self {
+        let Some(default) = default else {
+            return self;
+        };
+        Self {
+            url: Value::merge(self.url, default.url),
+            timeout: Value::merge(self.timeout, default.timeout),
+            save_descriptors: Value::merge(self.save_descriptors, default.save_descriptors),
+            unrecognized: toml::Table::new(),
+        }
+    }
+
+    fn validate(&self) -> crate::Result<()> {
+        if self.url.is_none() {
+            bail!("grpc_reflect.url is required");
+        }
+        if !self.unrecognized.is_empty() {
+            bail!(
+                "unrecognized field{} {}",
+                if self.unrecognized.len() == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                self.unrecognized.keys().join(", "),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Sends unusual Range requests for a resource and checks the partial responses.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Range {
+    pub url: Option<Value>,
+    pub max_parts: Option<Value>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl Range {
+    fn merge(self, default: Option<Self>) -> Self {
+        let Some(default) = default else {
+            return self;
+        };
+        Self {
+            url: Value::merge(self.url, default.url),
+            max_parts: Value::merge(self.max_parts, default.max_parts),
+            unrecognized: toml::Table::new(),
+        }
+    }
+
+    fn validate(&self) -> crate::Result<()> {
+        if self.url.is_none() {
+            bail!("range.url is required");
+        }
+        if !self.unrecognized.is_empty() {
+            bail!(
+                "unrecognized field{} {}",
+                if self.unrecognized.len() == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                self.unrecognized.keys().join(", "),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Checks how a resource handles conditional request headers.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Conditional {
+    pub url: Option<Value>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl Conditional {
+    fn merge(self, default: Option<Self>) -> Self {
+        let Some(default) = default else {
+            return self;
+        };
+        Self {
+            url: Value::merge(self.url, default.url),
+            unrecognized: toml::Table::new(),
+        }
+    }
+
+    fn validate(&self) -> crate::Result<()> {
+        if self.url.is_none() {
+            bail!("conditional.url is required");
+        }
+        if !self.unrecognized.is_empty() {
+            bail!(
+                "unrecognized field{} {}",
+                if self.unrecognized.len() == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                self.unrecognized.keys().join(", "),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Sends HTTP/2 frame sequences known to exhaust or confuse servers and records the replies.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct H2Attack {
+    pub url: Option<Value>,
+    pub attack: Option<Value>,
+    pub count: Option<Value>,
+    pub timeout: Option<Value>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl H2Attack {
+    fn merge(self, default: Option<Self>) -> Self {
+        let Some(default) = default else {
+            return self;
+        };
+        Self {
+            url: Value::merge(self.url, default.url),
+            attack: Value::merge(self.attack, default.attack),
+            count: Value::merge(self.count, default.count),
+            timeout: Value::merge(self.timeout, default.timeout),
+            unrecognized: toml::Table::new(),
+        }
+    }
+
+    fn validate(&self) -> crate::Result<()> {
+        if self.url.is_none() {
+            bail!("h2_attack.url is required");
+        }
+        if self.attack.is_none() {
+            bail!("h2_attack.attack is required");
+        }
+        if !self.unrecognized.is_empty() {
+            bail!(
+                "unrecognized field{} {}",
+                if self.unrecognized.len() == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                self.unrecognized.keys().join(", "),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Requests a URL offering each ALPN protocol in turn and compares the responses.
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
-pub struct Graphql {
+pub struct AlpnMatrix {
     pub url: Option<Value>,
-    pub query: Option<Value>,
-    pub params: Option<Table>,
-    pub operation: Option<Value>,
     #[serde(flatten)]
     pub unrecognized: toml::Table,
 }
 
-impl Graphql {
-    fn merge(self, second: Option<Self>) -> Self {
-        let Some(second) = second else {
+impl AlpnMatrix {
+    fn merge(self, default: Option<Self>) -> Self {
+        let Some(default) = default else {
             return self;
         };
         Self {
-            url: Value::merge(self.url, second.url),
-            query: Value::merge(self.query, second.query),
-            params: Table::merge(self.params, second.params),
-            operation: Value::merge(self.operation, second.operation),
+            url: Value::merge(self.url, default.url),
             unrecognized: toml::Table::new(),
         }
     }
 
     fn validate(&self) -> crate::Result<()> {
+        if self.url.is_none() {
+            bail!("alpn_matrix.url is required");
+        }
         if !self.unrecognized.is_empty() {
             bail!(
                 "unrecognized field{} {}",
@@ -921,33 +2949,37 @@ impl Graphql {
     }
 }
 
+/// Holds many idle keep-alive connections open to find how many a server will keep.
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
-pub struct Http {
+pub struct KeepAlive {
     pub url: Option<Value>,
-    pub method: Option<Value>,
-    pub headers: Option<Table>,
-    pub add_content_length: Option<Value>,
-    pub body: Option<Value>,
+    pub connections: Option<Value>,
+    pub hold: Option<Value>,
+    pub interval: Option<Value>,
+    pub timeout: Option<Value>,
     #[serde(flatten)]
     pub unrecognized: toml::Table,
 }
 
-impl Http {
-    fn merge(self, second: Option<Self>) -> Self {
-        let Some(second) = second else {
+impl KeepAlive {
+    fn merge(self, default: Option<Self>) -> Self {
+        let Some(default) = default else {
             return self;
         };
         Self {
-            url: Value::merge(self.url, second.url),
-            method: Value::merge(self.method, second.method),
-            headers: Table::merge(self.headers, second.headers),
-            add_content_length: Value::merge(self.add_content_length, second.add_content_length),
-            body: Value::merge(self.body, second.body),
+            url: Value::merge(self.url, default.url),
+            connections: Value::merge(self.connections, default.connections),
+            hold: Value::merge(self.hold, default.hold),
+            interval: Value::merge(self.interval, default.interval),
+            timeout: Value::merge(self.timeout, default.timeout),
             unrecognized: toml::Table::new(),
         }
     }
 
     fn validate(&self) -> crate::Result<()> {
+        if self.url.is_none() {
+            bail!("keep_alive.url is required");
+        }
         if !self.unrecognized.is_empty() {
             bail!(
                 "unrecognized field{} {}",
@@ -963,88 +2995,214 @@ impl Http {
     }
 }
 
+/// Opens many TCP connections at once and records how long each took to establish.
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
-pub struct Http1 {
-    pub version_string: Option<Value>,
-    #[serde(flatten, default)]
-    pub common: Http,
+pub struct TcpBurst {
+    pub host: Option<Value>,
+    pub port: Option<Value>,
+    pub connections: Option<Value>,
+    pub timeout: Option<Value>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
 }
 
-impl Http1 {
+impl TcpBurst {
     fn merge(self, default: Option<Self>) -> Self {
         let Some(default) = default else {
             return self;
         };
         Self {
-            version_string: Value::merge(self.version_string, default.version_string),
-            common: self.common.merge(Some(default.common)),
+            host: Value::merge(self.host, default.host),
+            port: Value::merge(self.port, default.port),
+            connections: Value::merge(self.connections, default.connections),
+            timeout: Value::merge(self.timeout, default.timeout),
+            unrecognized: toml::Table::new(),
         }
     }
 
     fn validate(&self) -> crate::Result<()> {
-        self.common.validate()?;
+        if self.host.is_none() {
+            bail!("tcp_burst.host is required");
+        }
+        if self.port.is_none() {
+            bail!("tcp_burst.port is required");
+        }
+        if !self.unrecognized.is_empty() {
+            bail!(
+                "unrecognized field{} {}",
+                if self.unrecognized.len() == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                self.unrecognized.keys().join(", "),
+            );
+        }
         Ok(())
     }
 }
 
+/// Upgrades an HTTP/1.1 connection to a WebSocket and exchanges a scripted sequence of frames.
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
-pub struct Http2 {
-    pub trailers: Option<Table>,
-    #[serde(flatten, default)]
-    pub common: Http,
+pub struct WebSocket {
+    pub url: Option<Value>,
+    pub headers: Option<Table>,
+    pub frames: Option<ValueOrArray<WebSocketFrame>>,
+    pub timeout: Option<Value>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
 }
 
-impl Http2 {
+impl WebSocket {
     fn merge(self, default: Option<Self>) -> Self {
         let Some(default) = default else {
             return self;
         };
         Self {
-            trailers: Table::merge(self.trailers, default.trailers),
-            common: self.common.merge(Some(default.common)),
+            url: Value::merge(self.url, default.url),
+            headers: Table::merge(self.headers, default.headers),
+            frames: ValueOrArray::merge(self.frames, default.frames),
+            timeout: Value::merge(self.timeout, default.timeout),
+            unrecognized: toml::Table::new(),
         }
     }
 
     fn validate(&self) -> crate::Result<()> {
-        self.common.validate()?;
+        if self.url.is_none() {
+            bail!("ws.url is required");
+        }
+        for frame in self.frames.iter().flatten() {
+            frame.validate()?;
+        }
+        if !self.unrecognized.is_empty() {
+            bail!(
+                "unrecognized field{} {}",
+                if self.unrecognized.len() == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                self.unrecognized.keys().join(", "),
+            );
+        }
         Ok(())
     }
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
-pub struct Http3 {
+pub struct WebSocketFrame {
+    pub opcode: Option<Value>,
+    pub fin: Option<Value>,
+    pub rsv: Option<Value>,
+    pub mask: Option<Value>,
+    pub close_code: Option<Value>,
+    pub payload: Option<Value>,
+    pub receive: Option<Value>,
     #[serde(flatten)]
-    pub common: Http,
+    pub unrecognized: toml::Table,
 }
 
-impl Http3 {
+impl Merge for WebSocketFrame {
+    fn merge(first: Option<Self>, second: Option<Self>) -> Option<Self> {
+        let Some(first) = first else {
+            return second;
+        };
+        let Some(second) = second else {
+            return Some(first);
+        };
+        Some(Self {
+            opcode: Value::merge(first.opcode, second.opcode),
+            fin: Value::merge(first.fin, second.fin),
+            rsv: Value::merge(first.rsv, second.rsv),
+            mask: Value::merge(first.mask, second.mask),
+            close_code: Value::merge(first.close_code, second.close_code),
+            payload: Value::merge(first.payload, second.payload),
+            receive: Value::merge(first.receive, second.receive),
+            unrecognized: toml::Table::new(),
+        })
+    }
+}
+
+impl Validate for WebSocketFrame {
+    fn validate(&self) -> crate::Result<()> {
+        if !self.unrecognized.is_empty() {
+            bail!(
+                "unrecognized field{} {}",
+                if self.unrecognized.len() == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                self.unrecognized.keys().join(", "),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Sends one DNS query and records the raw reply and its parsed records.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Dns {
+    pub name: Option<Value>,
+    #[serde(rename = "type")]
+    pub record_type: Option<Value>,
+    pub server: Option<Value>,
+    pub transport: Option<Value>,
+    pub recursion_desired: Option<Value>,
+    pub timeout: Option<Value>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl Dns {
     fn merge(self, default: Option<Self>) -> Self {
         let Some(default) = default else {
             return self;
         };
         Self {
-            common: self.common.merge(Some(default.common)),
+            name: Value::merge(self.name, default.name),
+            record_type: Value::merge(self.record_type, default.record_type),
+            server: Value::merge(self.server, default.server),
+            transport: Value::merge(self.transport, default.transport),
+            recursion_desired: Value::merge(self.recursion_desired, default.recursion_desired),
+            timeout: Value::merge(self.timeout, default.timeout),
+            unrecognized: toml::Table::new(),
         }
     }
 
     fn validate(&self) -> crate::Result<()> {
-        self.common.validate()?;
+        if self.name.is_none() {
+            bail!("dns.name is required");
+        }
+        if !self.unrecognized.is_empty() {
+            bail!(
+                "unrecognized field{} {}",
+                if self.unrecognized.len() == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                self.unrecognized.keys().join(", "),
+            );
+        }
         Ok(())
     }
 }
 
+/// Connects to a port and identifies the protocol it speaks.
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
-pub struct Tls {
+pub struct Detect {
     pub host: Option<Value>,
     pub port: Option<Value>,
     pub alpn: Option<ValueOrArray<Value>>,
-    pub body: Option<Value>,
-    pub version: Option<Value>,
+    pub wait: Option<Value>,
+    pub timeout: Option<Value>,
+    pub local: Option<Value>,
     #[serde(flatten)]
     pub unrecognized: toml::Table,
 }
 
-impl Tls {
+impl Detect {
     fn merge(self, default: Option<Self>) -> Self {
         let Some(default) = default else {
             return self;
@@ -1053,13 +3211,20 @@ impl Tls {
             host: Value::merge(self.host, default.host),
             port: Value::merge(self.port, default.port),
             alpn: ValueOrArray::merge(self.alpn, default.alpn),
-            body: Value::merge(self.body, default.body),
-            version: Value::merge(self.version, default.version),
+            wait: Value::merge(self.wait, default.wait),
+            timeout: Value::merge(self.timeout, default.timeout),
+            local: Value::merge(self.local, default.local),
             unrecognized: toml::Table::new(),
         }
     }
 
     fn validate(&self) -> crate::Result<()> {
+        if self.host.is_none() {
+            bail!("detect.host is required");
+        }
+        if self.port.is_none() {
+            bail!("detect.port is required");
+        }
         if !self.unrecognized.is_empty() {
             bail!(
                 "unrecognized field{} {}",
@@ -1080,6 +3245,10 @@ pub struct Tcp {
     pub host: Option<Value>,
     pub port: Option<Value>,
     pub body: Option<Value>,
+    pub proxies: Option<Vec<TcpProxy>>,
+    pub proxy_protocol: Option<TcpProxyProtocol>,
+    pub fault: Option<TcpFault>,
+    pub expect: Option<ValueOrArray<Expect>>,
     //pub close: Option<TcpClose>,
     #[serde(flatten)]
     pub unrecognized: toml::Table,
@@ -1094,6 +3263,12 @@ impl Tcp {
             host: Value::merge(self.host, default.host),
             port: Value::merge(self.port, default.port),
             body: Value::merge(self.body, default.body),
+            // Proxy chains are replaced rather than merged so a step can opt out with an empty
+            // list.
+            proxies: self.proxies.or(default.proxies),
+            proxy_protocol: TcpProxyProtocol::merge(self.proxy_protocol, default.proxy_protocol),
+            fault: TcpFault::merge(self.fault, default.fault),
+            expect: ValueOrArray::merge(self.expect, default.expect),
             //close: TcpClose::merge(self.close, default.close),
             unrecognized: toml::Table::new(),
         }
@@ -1103,6 +3278,18 @@ impl Tcp {
         //if let Some(c) = &self.close {
         //    c.validate()?;
         //}
+        for proxy in self.proxies.iter().flatten() {
+            proxy.validate()?;
+        }
+        if let Some(p) = &self.proxy_protocol {
+            p.validate()?;
+        }
+        if let Some(f) = &self.fault {
+            f.validate()?;
+        }
+        for expect in self.expect.iter().flatten() {
+            expect.validate("tcp")?;
+        }
         if !self.unrecognized.is_empty() {
             bail!(
                 "unrecognized field{} {}",
@@ -1118,6 +3305,128 @@ impl Tcp {
     }
 }
 
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct TcpProxy {
+    pub kind: Option<Value>,
+    pub host: Option<Value>,
+    pub port: Option<Value>,
+    pub username: Option<Value>,
+    pub password: Option<Value>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl TcpProxy {
+    fn validate(&self) -> crate::Result<()> {
+        if !self.unrecognized.is_empty() {
+            bail!(
+                "unrecognized field{} tcp.proxies.{}",
+                if self.unrecognized.len() == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                self.unrecognized.keys().join(", tcp.proxies."),
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct TcpProxyProtocol {
+    pub version: Option<Value>,
+    pub source_host: Option<Value>,
+    pub source_port: Option<Value>,
+    pub dest_host: Option<Value>,
+    pub dest_port: Option<Value>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl Merge for TcpProxyProtocol {
+    fn merge(first: Option<Self>, second: Option<Self>) -> Option<Self> {
+        let Some(first) = first else {
+            return second;
+        };
+        let Some(second) = second else {
+            return Some(first);
+        };
+        Some(Self {
+            version: Value::merge(first.version, second.version),
+            source_host: Value::merge(first.source_host, second.source_host),
+            source_port: Value::merge(first.source_port, second.source_port),
+            dest_host: Value::merge(first.dest_host, second.dest_host),
+            dest_port: Value::merge(first.dest_port, second.dest_port),
+            unrecognized: toml::Table::new(),
+        })
+    }
+}
+
+impl TcpProxyProtocol {
+    fn validate(&self) -> crate::Result<()> {
+        if !self.unrecognized.is_empty() {
+            bail!(
+                "unrecognized field{} tcp.proxy_protocol.{}",
+                if self.unrecognized.len() == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                self.unrecognized.keys().join(", tcp.proxy_protocol."),
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct TcpFault {
+    pub drop_after: Option<Value>,
+    pub corrupt_offset: Option<Value>,
+    pub corrupt_length: Option<Value>,
+    pub duplicate_writes: Option<Value>,
+    pub delay_acks: Option<Value>,
+    #[serde(flatten)]
+    pub unrecognized: toml::Table,
+}
+
+impl Merge for TcpFault {
+    fn merge(first: Option<Self>, second: Option<Self>) -> Option<Self> {
+        let Some(first) = first else {
+            return second;
+        };
+        let Some(second) = second else {
+            return Some(first);
+        };
+        Some(Self {
+            drop_after: Value::merge(first.drop_after, second.drop_after),
+            corrupt_offset: Value::merge(first.corrupt_offset, second.corrupt_offset),
+            corrupt_length: Value::merge(first.corrupt_length, second.corrupt_length),
+            duplicate_writes: Value::merge(first.duplicate_writes, second.duplicate_writes),
+            delay_acks: Value::merge(first.delay_acks, second.delay_acks),
+            unrecognized: toml::Table::new(),
+        })
+    }
+}
+
+impl TcpFault {
+    fn validate(&self) -> crate::Result<()> {
+        if !self.unrecognized.is_empty() {
+            bail!(
+                "unrecognized field{} tcp.fault.{}",
+                if self.unrecognized.len() == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                self.unrecognized.keys().join(", tcp.fault."),
+            );
+        }
+        Ok(())
+    }
+}
+
 //#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 //pub struct TcpClose {
 //    pub timeout: Option<Value>,
@@ -1273,6 +3582,7 @@ impl Validate for TcpSegment {
 pub struct Quic {
     pub host: Option<Value>,
     pub port: Option<Value>,
+    pub alpn: Option<ValueOrArray<Value>>,
     pub body: Option<Value>,
     pub tls_version: Option<Value>,
     #[serde(flatten)]
@@ -1287,6 +3597,7 @@ impl Quic {
         Self {
             host: Value::merge(self.host, default.host),
             port: Value::merge(self.port, default.port),
+            alpn: ValueOrArray::merge(self.alpn, default.alpn),
             body: Value::merge(self.body, default.body),
             tls_version: Value::merge(self.tls_version, default.tls_version),
             unrecognized: toml::Table::new(),
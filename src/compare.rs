@@ -0,0 +1,234 @@
+//! Compare two runs stored by [`SqliteWriter`](crate::record::SqliteWriter), like a nightly scan
+//! and the one before it.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::Serialize;
+
+/// Where a response came from, which is stable across runs of the same plan.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct ResponseKey {
+    pub step: String,
+    pub job: String,
+    pub protocol: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChangedResponse {
+    #[serde(flatten)]
+    pub key: ResponseKey,
+    pub base_status: Option<u16>,
+    pub head_status: Option<u16>,
+    pub body_changed: bool,
+    /// How many bits of the bodies' simhashes differ, when both runs computed them.
+    pub simhash_distance: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LatencyRegression {
+    pub step: String,
+    pub base_mean_ms: f64,
+    pub head_mean_ms: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Finding {
+    pub step: String,
+    pub kind: String,
+    pub target: String,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Comparison {
+    pub base: String,
+    pub head: String,
+    pub new_responses: Vec<ResponseKey>,
+    pub removed_responses: Vec<ResponseKey>,
+    pub changed_responses: Vec<ChangedResponse>,
+    pub latency_regressions: Vec<LatencyRegression>,
+    pub new_findings: Vec<Finding>,
+}
+
+impl Comparison {
+    /// Whether anything got worse, for failing CI when it did. Removed and changed responses
+    /// aren't counted since they're often expected when the target changes.
+    pub fn regressed(&self) -> bool {
+        !self.latency_regressions.is_empty() || !self.new_findings.is_empty()
+    }
+}
+
+struct Response {
+    status: Option<u16>,
+    duration_ms: Option<f64>,
+    body: Option<String>,
+    simhash: Option<String>,
+}
+
+/// Compare the `head` run against `base`.
+///
+/// A step's latency has regressed when its mean response time grew by more than
+/// `latency_threshold`, as a fraction of the base mean.
+pub fn compare(
+    db: &str,
+    base: &str,
+    head: &str,
+    latency_threshold: f64,
+) -> anyhow::Result<Comparison> {
+    let conn =
+        rusqlite::Connection::open_with_flags(db, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let base_responses = responses(&conn, base)?;
+    let head_responses = responses(&conn, head)?;
+
+    let mut out = Comparison {
+        base: base.to_owned(),
+        head: head.to_owned(),
+        ..Default::default()
+    };
+    for (key, resp) in &head_responses {
+        let Some(prev) = base_responses.get(key) else {
+            out.new_responses.push(key.clone());
+            continue;
+        };
+        let simhash_distance = resp
+            .simhash
+            .as_deref()
+            .zip(prev.simhash.as_deref())
+            .and_then(|(head, base)| {
+                let head = u64::from_str_radix(head, 16).ok()?;
+                let base = u64::from_str_radix(base, 16).ok()?;
+                Some((head ^ base).count_ones())
+            });
+        // Bodies may not be stored, so differing hashes count as a change too.
+        let body_changed = resp.body != prev.body || simhash_distance.is_some_and(|d| d > 0);
+        if resp.status != prev.status || body_changed {
+            out.changed_responses.push(ChangedResponse {
+                key: key.clone(),
+                base_status: prev.status,
+                head_status: resp.status,
+                body_changed,
+                simhash_distance,
+            });
+        }
+    }
+    out.removed_responses = base_responses
+        .keys()
+        .filter(|key| !head_responses.contains_key(key))
+        .cloned()
+        .collect();
+
+    let base_latency = mean_latency(&base_responses);
+    for (step, head_mean_ms) in mean_latency(&head_responses) {
+        let Some(&base_mean_ms) = base_latency.get(&step) else {
+            continue;
+        };
+        if head_mean_ms > base_mean_ms * (1.0 + latency_threshold) {
+            out.latency_regressions.push(LatencyRegression {
+                step,
+                base_mean_ms,
+                head_mean_ms,
+            });
+        }
+    }
+
+    let base_findings = findings(&conn, base)?;
+    out.new_findings = findings(&conn, head)?
+        .difference(&base_findings)
+        .cloned()
+        .collect();
+    Ok(out)
+}
+
+fn responses(
+    conn: &rusqlite::Connection,
+    run: &str,
+) -> anyhow::Result<BTreeMap<ResponseKey, Response>> {
+    let mut stmt = conn.prepare(
+        "SELECT step, job, protocol, status, duration_ms, json_extract(record, '$.body'),
+            json_extract(record, '$.simhash')
+            FROM responses WHERE run = ?1 ORDER BY rowid",
+    )?;
+    let mut out = BTreeMap::new();
+    let mut rows = stmt.query([run])?;
+    while let Some(row) = rows.next()? {
+        let key = ResponseKey {
+            step: row.get(0)?,
+            job: row.get(1)?,
+            protocol: row.get(2)?,
+        };
+        // Keep the first response when a job has several, like after a redirect.
+        out.entry(key).or_insert(Response {
+            status: row.get(3)?,
+            duration_ms: row.get(4)?,
+            body: row.get(5)?,
+            simhash: row.get(6)?,
+        });
+    }
+    if out.is_empty() {
+        anyhow::bail!("no responses stored for run {run}");
+    }
+    Ok(out)
+}
+
+fn mean_latency(responses: &BTreeMap<ResponseKey, Response>) -> BTreeMap<String, f64> {
+    let mut totals = BTreeMap::<_, (f64, u32)>::new();
+    for (key, resp) in responses {
+        if let Some(ms) = resp.duration_ms {
+            let total = totals.entry(key.step.clone()).or_default();
+            total.0 += ms;
+            total.1 += 1;
+        }
+    }
+    totals
+        .into_iter()
+        .map(|(step, (sum, count))| (step, sum / f64::from(count)))
+        .collect()
+}
+
+/// The mean of each timing phase of each step's responses in `run`, in milliseconds, keyed by
+/// step and phase. Phases are the durations recorded on responses, like `time_to_first_byte`.
+pub fn phase_timings(db: &str, run: &str) -> anyhow::Result<BTreeMap<(String, String), f64>> {
+    let conn =
+        rusqlite::Connection::open_with_flags(db, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let mut stmt = conn.prepare("SELECT step, record FROM responses WHERE run = ?1")?;
+    let mut totals = BTreeMap::<_, (f64, u32)>::new();
+    let mut rows = stmt.query([run])?;
+    while let Some(row) = rows.next()? {
+        let step: String = row.get(0)?;
+        let record: String = row.get(1)?;
+        let record: serde_json::Value = serde_json::from_str(&record)?;
+        let serde_json::Value::Object(record) = record else {
+            continue;
+        };
+        for (phase, value) in record {
+            let secs = value.get("secs").and_then(serde_json::Value::as_f64);
+            let nanos = value.get("nanos").and_then(serde_json::Value::as_f64);
+            if let (Some(secs), Some(nanos)) = (secs, nanos) {
+                let total = totals.entry((step.clone(), phase)).or_default();
+                total.0 += secs * 1000.0 + nanos / 1_000_000.0;
+                total.1 += 1;
+            }
+        }
+    }
+    if totals.is_empty() {
+        anyhow::bail!("no response timings stored for run {run}");
+    }
+    Ok(totals
+        .into_iter()
+        .map(|(key, (sum, count))| (key, sum / f64::from(count)))
+        .collect())
+}
+
+fn findings(conn: &rusqlite::Connection, run: &str) -> anyhow::Result<BTreeSet<Finding>> {
+    let mut stmt =
+        conn.prepare("SELECT step, kind, target, detail FROM findings WHERE run = ?1")?;
+    let rows = stmt.query_map([run], |row| {
+        Ok(Finding {
+            step: row.get(0)?,
+            kind: row.get(1)?,
+            target: row.get(2)?,
+            detail: row.get(3)?,
+        })
+    })?;
+    Ok(rows.collect::<Result<_, _>>()?)
+}
@@ -0,0 +1,243 @@
+//! Parsing and evaluation of Content-Security-Policy headers, so checks can name the directive
+//! that makes a policy weak instead of matching on the header's text.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+/// Fetch directives and the directives each falls back to in order, from CSP level 3.
+const FALLBACKS: &[(&str, &[&str])] = &[
+    ("child-src", &["default-src"]),
+    ("connect-src", &["default-src"]),
+    ("font-src", &["default-src"]),
+    ("frame-src", &["child-src", "default-src"]),
+    ("img-src", &["default-src"]),
+    ("manifest-src", &["default-src"]),
+    ("media-src", &["default-src"]),
+    ("object-src", &["default-src"]),
+    ("script-src", &["default-src"]),
+    ("script-src-attr", &["script-src", "default-src"]),
+    ("script-src-elem", &["script-src", "default-src"]),
+    ("style-src", &["default-src"]),
+    ("style-src-attr", &["style-src", "default-src"]),
+    ("style-src-elem", &["style-src", "default-src"]),
+    ("worker-src", &["child-src", "script-src", "default-src"]),
+];
+
+/// Directives that without a policy leave script execution unrestricted.
+const REQUIRED: &[&str] = &["script-src", "object-src", "base-uri"];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Policy {
+    /// Each directive's sources by lowercase name. Repeats of a directive are ignored, as
+    /// browsers do.
+    pub directives: BTreeMap<String, Vec<String>>,
+    /// The sources in effect for each fetch directive after falling back to the directives it
+    /// inherits from. Fetch directives without any policy are left out.
+    pub effective: BTreeMap<String, Vec<String>>,
+    pub weaknesses: Vec<Weakness>,
+}
+
+/// A directive which allows more than a strict policy would.
+#[derive(Debug, Clone, Serialize)]
+pub struct Weakness {
+    pub directive: String,
+    /// One of `unsafe_inline`, `unsafe_eval`, `wildcard`, or `missing`.
+    pub kind: &'static str,
+    /// The source responsible, if any.
+    pub source: Option<String>,
+}
+
+impl Policy {
+    /// Parse a serialized policy. Headers combined with commas hold several policies, and only
+    /// the first is parsed.
+    pub fn parse(header: &str) -> Self {
+        let policy = header.split(',').next().unwrap_or_default();
+        let mut directives = BTreeMap::new();
+        for directive in policy.split(';') {
+            let mut tokens = directive.split_ascii_whitespace();
+            let Some(name) = tokens.next() else {
+                continue;
+            };
+            directives
+                .entry(name.to_ascii_lowercase())
+                .or_insert_with(|| tokens.map(str::to_owned).collect());
+        }
+        let effective: BTreeMap<String, Vec<String>> = FALLBACKS
+            .iter()
+            .filter_map(|(name, fallbacks)| {
+                let sources = std::iter::once(name)
+                    .chain(fallbacks.iter())
+                    .find_map(|directive| directives.get(*directive))?;
+                Some((name.to_string(), sources.clone()))
+            })
+            .collect();
+        let weaknesses = weaknesses(&directives, &effective);
+        Self {
+            directives,
+            effective,
+            weaknesses,
+        }
+    }
+}
+
+fn weaknesses(
+    directives: &BTreeMap<String, Vec<String>>,
+    effective: &BTreeMap<String, Vec<String>>,
+) -> Vec<Weakness> {
+    let mut out = Vec::new();
+    for name in REQUIRED {
+        if !directives.contains_key(*name) && !effective.contains_key(*name) {
+            out.push(Weakness {
+                directive: name.to_string(),
+                kind: "missing",
+                source: None,
+            });
+        }
+    }
+    for (name, sources) in effective {
+        let has = |keyword: &str| sources.iter().any(|s| s.eq_ignore_ascii_case(keyword));
+        // Nonces and hashes make browsers ignore 'unsafe-inline', and with 'strict-dynamic'
+        // they also ignore host and scheme sources.
+        let nonced = sources.iter().any(|s| is_nonce_or_hash(s));
+        let strict_dynamic = nonced && has("'strict-dynamic'");
+        let script = name.starts_with("script-src") || name == "worker-src";
+        let inline = script || name.starts_with("style-src");
+        let mut push = |kind, source: &str| {
+            out.push(Weakness {
+                directive: name.clone(),
+                kind,
+                source: Some(source.to_owned()),
+            })
+        };
+        for source in sources {
+            if inline && !nonced && source.eq_ignore_ascii_case("'unsafe-inline'") {
+                push("unsafe_inline", source);
+            } else if script && source.eq_ignore_ascii_case("'unsafe-eval'") {
+                push("unsafe_eval", source);
+            } else if !strict_dynamic && is_wildcard(source) {
+                push("wildcard", source);
+            }
+        }
+    }
+    out
+}
+
+fn is_nonce_or_hash(source: &str) -> bool {
+    let source = source.to_ascii_lowercase();
+    ["'nonce-", "'sha256-", "'sha384-", "'sha512-"]
+        .iter()
+        .any(|prefix| source.starts_with(prefix))
+}
+
+/// Whether a source allows any host, like `*` or a scheme without a host like `https:`.
+fn is_wildcard(source: &str) -> bool {
+    source == "*"
+        || source.strip_suffix(':').is_some_and(|scheme| {
+            !scheme.is_empty()
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds<'a>(policy: &'a Policy, directive: &str) -> Vec<&'a str> {
+        policy
+            .weaknesses
+            .iter()
+            .filter(|w| w.directive == directive)
+            .map(|w| w.kind)
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_directives() {
+        let policy = Policy::parse(
+            "Default-Src 'self'; script-src 'self' https://cdn.example; script-src 'none'; ; \
+             img-src *, script-src 'unsafe-eval'",
+        );
+        assert_eq!(
+            policy.directives,
+            BTreeMap::from([
+                ("default-src".to_owned(), vec!["'self'".to_owned()]),
+                (
+                    "script-src".to_owned(),
+                    vec!["'self'".to_owned(), "https://cdn.example".to_owned()]
+                ),
+                ("img-src".to_owned(), vec!["*".to_owned()]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_default_src_fallback() {
+        let policy = Policy::parse("default-src 'self'; script-src 'none'; base-uri 'none'");
+        assert_eq!(policy.effective.len(), FALLBACKS.len());
+        assert_eq!(policy.effective["img-src"], ["'self'"]);
+        assert_eq!(policy.effective["frame-src"], ["'self'"]);
+        assert_eq!(policy.effective["script-src-elem"], ["'none'"]);
+        assert_eq!(policy.effective["worker-src"], ["'none'"]);
+        assert!(policy.weaknesses.is_empty());
+
+        let policy = Policy::parse("img-src 'self'");
+        assert_eq!(policy.effective.keys().collect::<Vec<_>>(), ["img-src"]);
+        assert_eq!(kinds(&policy, "script-src"), ["missing"]);
+        assert_eq!(kinds(&policy, "object-src"), ["missing"]);
+        assert_eq!(kinds(&policy, "base-uri"), ["missing"]);
+    }
+
+    #[test]
+    fn test_unsafe_inline() {
+        let policy = Policy::parse(
+            "script-src 'unsafe-inline' 'unsafe-eval'; style-src 'UNSAFE-INLINE'; \
+             img-src 'unsafe-inline'; object-src 'none'; base-uri 'none'",
+        );
+        assert_eq!(
+            kinds(&policy, "script-src"),
+            ["unsafe_inline", "unsafe_eval"]
+        );
+        assert_eq!(
+            kinds(&policy, "worker-src"),
+            ["unsafe_inline", "unsafe_eval"]
+        );
+        assert_eq!(kinds(&policy, "style-src-attr"), ["unsafe_inline"]);
+        // Only script and style directives run inline content.
+        assert!(kinds(&policy, "img-src").is_empty());
+    }
+
+    #[test]
+    fn test_nonce_and_hash() {
+        let policy = Policy::parse(
+            "script-src 'nonce-abc' 'unsafe-inline' 'strict-dynamic' https:; object-src 'none'; \
+             base-uri 'none'",
+        );
+        assert!(policy.weaknesses.is_empty(), "{:?}", policy.weaknesses);
+
+        // Without 'strict-dynamic' a hash still disables 'unsafe-inline', but not host sources.
+        let policy = Policy::parse(
+            "script-src 'SHA256-abc' 'unsafe-inline' https:; object-src 'none'; base-uri 'none'",
+        );
+        assert_eq!(kinds(&policy, "script-src"), ["wildcard"]);
+        assert_eq!(
+            policy.weaknesses[0].source.as_deref(),
+            Some("https:"),
+            "{:?}",
+            policy.weaknesses
+        );
+    }
+
+    #[test]
+    fn test_is_wildcard() {
+        assert!(is_wildcard("*"));
+        assert!(is_wildcard("https:"));
+        assert!(is_wildcard("web+app:"));
+        assert!(!is_wildcard(":"));
+        assert!(!is_wildcard("https://example.com"));
+        assert!(!is_wildcard("*.example.com"));
+        assert!(!is_wildcard("'self'"));
+    }
+}
@@ -0,0 +1,389 @@
+//! Controller and worker modes for spreading a plan's jobs over several hosts.
+//!
+//! The controller connects to each worker and assigns it a shard of the plan. Messages are
+//! newline delimited JSON. Workers stream back each step's output as it finishes and the
+//! controller merges the jobs of each step once every worker is done.
+//!
+//! Workers only run plans signed with the secret in [`SECRET_ENV`], which the controller and its
+//! workers must share. Each connection starts with the worker sending a random challenge, and the
+//! controller signs it along with the assignment, so captured assignments can't be replayed.
+//! Traffic is not encrypted, so plans and outputs are visible to anyone on the network path.
+//! Workers also refuse plans with command, script or module steps, which would run code or read
+//! files on the worker's host.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail};
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use futures::future::try_join_all;
+use hmac::digest::KeyInit;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use svix_ksuid::KsuidMs;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+use tracing::{debug, error};
+
+use crate::exec::{Executor, Shard};
+use crate::{Plan, RunName, StepProtocols};
+
+/// The environment variable holding the secret shared by a controller and its workers.
+pub const SECRET_ENV: &str = "DEVIL_WORKER_SECRET";
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Message {
+    Challenge {
+        nonce: String,
+    },
+    Assign {
+        plan: String,
+        run: String,
+        shard: u64,
+        shards: u64,
+        /// The base64 HMAC-SHA256 of the challenge and assignment, keyed by the shared secret.
+        mac: String,
+    },
+    Step {
+        output: serde_json::Value,
+    },
+    Done,
+    Error {
+        message: String,
+    },
+}
+
+/// How long a controller has to answer the challenge with a signed assignment.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The longest assignment a worker reads, since the controller isn't authenticated until it's
+/// been read.
+const MAX_ASSIGN_BYTES: u64 = 16 << 20;
+
+/// The longest challenge a controller reads from a worker.
+const MAX_CHALLENGE_BYTES: u64 = 1024;
+
+/// Accept plans from controllers on `addr` and run them until the process is stopped.
+///
+/// Each controller is authenticated as it connects, but plans are run one at a time so each has
+/// the host to itself. Only plans signed with `secret` are run.
+pub async fn serve(addr: &str, secret: &str) -> anyhow::Result<()> {
+    if secret.is_empty() {
+        bail!("a worker secret is required");
+    }
+    let listener = TcpListener::bind(addr).await?;
+    let (assigned, mut assignments) = mpsc::channel(1);
+    let accept = tokio::spawn(accept(listener, secret.into(), assigned));
+    while let Some((peer, assignment)) = assignments.recv().await {
+        if let Err(e) = work(assignment).await {
+            error!("run shard for {peer}: {e:#}");
+        }
+    }
+    accept.await?
+}
+
+async fn accept(
+    listener: TcpListener,
+    secret: Arc<str>,
+    assigned: mpsc::Sender<(SocketAddr, Assignment)>,
+) -> anyhow::Result<()> {
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        debug!("controller {peer} connected");
+        let secret = secret.clone();
+        let assigned = assigned.clone();
+        // Authenticate separately so a controller which stalls can't hold up the others.
+        tokio::spawn(async move {
+            match timeout(HANDSHAKE_TIMEOUT, authenticate(stream, &secret)).await {
+                Ok(Ok(Some(assignment))) => {
+                    let _ = assigned.send((peer, assignment)).await;
+                }
+                Ok(Ok(None)) => {}
+                Ok(Err(e)) => error!("authenticate controller {peer}: {e:#}"),
+                Err(_) => error!("controller {peer} didn't send an assignment in time"),
+            }
+        });
+    }
+}
+
+/// A shard of a plan assigned by an authenticated controller.
+#[derive(Debug)]
+struct Assignment {
+    write: OwnedWriteHalf,
+    plan: String,
+    run: String,
+    shard: u64,
+    shards: u64,
+}
+
+/// Challenge a controller and wait for its signed assignment, returning None if it disconnects
+/// first.
+async fn authenticate(stream: TcpStream, secret: &str) -> anyhow::Result<Option<Assignment>> {
+    let (read, mut write) = stream.into_split();
+    let mut nonce = [0; 32];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let nonce = BASE64_STANDARD.encode(nonce);
+    send(
+        &mut write,
+        &Message::Challenge {
+            nonce: nonce.clone(),
+        },
+    )
+    .await?;
+    let Some(line) = read_frame(&mut BufReader::new(read), MAX_ASSIGN_BYTES).await? else {
+        return Ok(None);
+    };
+    let Message::Assign {
+        plan,
+        run,
+        shard,
+        shards,
+        mac,
+    } = serde_json::from_str(&line)?
+    else {
+        bail!("expected assign message");
+    };
+    let mac = BASE64_STANDARD
+        .decode(mac)
+        .map_err(|e| anyhow!("invalid assignment signature: {e}"))?;
+    signer(secret, &nonce, &plan, &run, shard, shards)
+        .verify_slice(&mac)
+        .map_err(|_| anyhow!("assignment not signed with the worker secret"))?;
+    Ok(Some(Assignment {
+        write,
+        plan,
+        run,
+        shard,
+        shards,
+    }))
+}
+
+async fn work(assignment: Assignment) -> anyhow::Result<()> {
+    let Assignment {
+        mut write,
+        plan,
+        run,
+        shard,
+        shards,
+    } = assignment;
+    let result = async {
+        if shards == 0 || shard >= shards {
+            bail!("invalid shard {shard} of {shards}");
+        }
+        let plan = Plan::parse(&plan)?;
+        check_plan(&plan)?;
+        let run = run
+            .parse::<KsuidMs>()
+            .map_err(|e| anyhow!("invalid run id {run:?}: {e:?}"))?;
+        // Share the controller's run ID so outputs from every worker can be correlated.
+        let name = RunName {
+            plan: plan.name.clone(),
+            run,
+        };
+        let mut executor = Executor::new(&plan, name)?.with_shard(Shard {
+            index: shard,
+            count: shards,
+        });
+        while executor.current().is_some() {
+            let output = crate::secret::to_json(&executor.next().await?)?;
+            send(&mut write, &Message::Step { output }).await?;
+        }
+        anyhow::Ok(())
+    }
+    .await;
+    match result {
+        Ok(()) => send(&mut write, &Message::Done).await,
+        Err(e) => {
+            let message = format!("{e:#}");
+            send(&mut write, &Message::Error { message }).await?;
+            Err(e)
+        }
+    }
+}
+
+/// Refuse steps which would run code or read files on the worker's host.
+fn check_plan(plan: &Plan) -> anyhow::Result<()> {
+    for (name, step) in &plan.steps {
+        let kind = match step.protocols {
+            StepProtocols::Command { .. } => "command",
+            StepProtocols::Script { .. } => "script",
+            StepProtocols::Module { .. } => "module",
+            _ => continue,
+        };
+        bail!("step {name} is a {kind} step, which workers don't run");
+    }
+    Ok(())
+}
+
+/// The MAC over an assignment answering the worker's challenge `nonce`.
+fn signer(
+    secret: &str,
+    nonce: &str,
+    plan: &str,
+    run: &str,
+    shard: u64,
+    shards: u64,
+) -> Hmac<sha2::Sha256> {
+    let mut mac = <Hmac<sha2::Sha256> as KeyInit>::new_from_slice(secret.as_bytes())
+        .expect("hmac accepts keys of any length");
+    // Length prefix the variable fields so they can't be shifted between each other.
+    for field in [nonce, plan, run] {
+        mac.update(&(field.len() as u64).to_le_bytes());
+        mac.update(field.as_bytes());
+    }
+    mac.update(&shard.to_le_bytes());
+    mac.update(&shards.to_le_bytes());
+    mac
+}
+
+async fn send(w: &mut (impl AsyncWriteExt + Unpin), message: &Message) -> anyhow::Result<()> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    w.write_all(&line).await?;
+    Ok(())
+}
+
+/// Read one message of at most `limit` bytes, so an unauthenticated peer can't make us buffer
+/// without bound. Returns None if the connection closed first.
+async fn read_frame(
+    r: &mut (impl AsyncBufRead + Unpin),
+    limit: u64,
+) -> anyhow::Result<Option<String>> {
+    let mut line = Vec::new();
+    r.take(limit + 1).read_until(b'\n', &mut line).await?;
+    if line.last() == Some(&b'\n') {
+        line.pop();
+    } else if line.len() as u64 > limit {
+        bail!("message longer than {limit} bytes");
+    }
+    if line.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(String::from_utf8(line)?))
+}
+
+/// Run the plan across the workers, returning the merged output of each step in order.
+///
+/// Jobs are keyed the same on every worker, so merging a step combines the workers' jobs. Fields
+/// summarizing a whole step, like latency percentiles, are kept from the first worker, which is
+/// also the only one to run steps that can't be split. `secret` must match the workers' secret.
+pub async fn coordinate(
+    text: &str,
+    workers: &[String],
+    secret: &str,
+) -> anyhow::Result<Vec<serde_json::Value>> {
+    if workers.is_empty() {
+        bail!("at least one worker is required");
+    }
+    if secret.is_empty() {
+        bail!("a worker secret is required");
+    }
+    let plan = Plan::parse(text)?;
+    check_plan(&plan)?;
+    let run = RunName::new(plan.name.clone()).run;
+    let shards = try_join_all(workers.iter().enumerate().map(|(i, addr)| {
+        let shard = i.try_into().unwrap();
+        let shards = workers.len().try_into().unwrap();
+        let run = run.to_string();
+        async move {
+            collect(addr, secret, text, run, shard, shards)
+                .await
+                .map_err(|e| anyhow!("worker {addr}: {e:#}"))
+        }
+    }))
+    .await?;
+
+    let mut shards = shards.into_iter();
+    let mut merged = shards.next().unwrap_or_default();
+    for shard in shards {
+        if shard.len() != merged.len() {
+            bail!("workers ran different numbers of steps");
+        }
+        for (step, output) in merged.iter_mut().zip(shard) {
+            merge(step, output);
+        }
+    }
+    Ok(merged)
+}
+
+async fn collect(
+    addr: &str,
+    secret: &str,
+    plan: &str,
+    run: String,
+    shard: u64,
+    shards: u64,
+) -> anyhow::Result<Vec<serde_json::Value>> {
+    let stream = TcpStream::connect(addr).await?;
+    let (read, mut write) = stream.into_split();
+    let mut read = BufReader::new(read);
+    let Some(line) = read_frame(&mut read, MAX_CHALLENGE_BYTES).await? else {
+        bail!("connection closed before the worker sent a challenge");
+    };
+    let Message::Challenge { nonce } = serde_json::from_str(&line)? else {
+        bail!("expected challenge message");
+    };
+    let mac = signer(secret, &nonce, plan, &run, shard, shards)
+        .finalize()
+        .into_bytes();
+    let assign = Message::Assign {
+        plan: plan.to_owned(),
+        run,
+        shard,
+        shards,
+        mac: BASE64_STANDARD.encode(mac),
+    };
+    send(&mut write, &assign).await?;
+    let mut steps = Vec::new();
+    let mut lines = read.lines();
+    while let Some(line) = lines.next_line().await? {
+        match serde_json::from_str(&line)? {
+            Message::Step { output } => steps.push(output),
+            Message::Done => return Ok(steps),
+            Message::Error { message } => bail!("{message}"),
+            Message::Challenge { .. } => bail!("unexpected challenge message"),
+            Message::Assign { .. } => bail!("unexpected assign message"),
+        }
+    }
+    bail!("connection closed before the plan finished")
+}
+
+fn merge(step: &mut serde_json::Value, other: serde_json::Value) {
+    let Some(jobs) = step
+        .get_mut("jobs")
+        .and_then(serde_json::Value::as_object_mut)
+    else {
+        return;
+    };
+    let Some(serde_json::Value::Object(other)) = other.get("jobs").cloned() else {
+        return;
+    };
+    for (key, job) in other {
+        // Each job runs on a single worker, so keys don't collide.
+        jobs.entry(key).or_insert(job);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_read_frame_limit() {
+        let mut r: &[u8] = b"12345\n123456\n";
+        assert_eq!(
+            read_frame(&mut r, 5).await.unwrap().as_deref(),
+            Some("12345")
+        );
+        assert!(read_frame(&mut r, 5).await.is_err());
+        let mut r: &[u8] = b"";
+        assert_eq!(read_frame(&mut r, 5).await.unwrap(), None);
+    }
+}
@@ -0,0 +1,362 @@
+//! Package everything recorded about a step from runs stored by
+//! [`SqliteWriter`](crate::record::SqliteWriter) into a single archive, for attaching to pentest
+//! reports and vendor disclosures.
+//!
+//! The archive is an uncompressed tar holding:
+//!
+//! - `manifest.json`: the findings and errors, timestamps, and a SHA-256 of every other file.
+//! - `plan.toml`: the step's table from the plan, when the plan is given.
+//! - `step.json`: the step's full output.
+//! - `jobs/<key>/<protocol>_sent.bin` and `_received.bin`: the bytes written to and read from
+//!   tcp and tls connections, so tls files hold the decrypted traffic.
+//! - `jobs/<key>/raw_tcp.pcap`: the segments of raw_tcp jobs, rebuilt as IP packets.
+//!
+//! TLS keys aren't captured, so there's no keylog.
+
+use std::net::IpAddr;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail};
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use svix_ksuid::{KsuidLike, KsuidMs};
+
+use crate::compare::Finding;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Manifest {
+    pub plan: String,
+    pub run: String,
+    pub step: String,
+    /// When the run started, from its ID.
+    pub run_started: Option<DateTime<Utc>>,
+    pub exported: DateTime<Utc>,
+    pub findings: Vec<Finding>,
+    pub errors: Vec<serde_json::Value>,
+    pub files: Vec<EvidenceFile>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EvidenceFile {
+    pub path: String,
+    pub size: usize,
+    /// The hex SHA-256 of the file's contents.
+    pub sha256: String,
+}
+
+/// Write the evidence for `step` in `run` to a tar archive at `path`. If the plan's text is
+/// given, the step's table is included too.
+pub fn export(
+    db: &str,
+    run: &str,
+    step: &str,
+    plan: Option<&str>,
+    path: &str,
+) -> anyhow::Result<Manifest> {
+    let conn =
+        rusqlite::Connection::open_with_flags(db, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let plan_name: String = conn
+        .query_row("SELECT plan FROM runs WHERE run = ?1", [run], |row| {
+            row.get(0)
+        })
+        .map_err(|e| anyhow!("find run {run}: {e}"))?;
+    let record: String = conn
+        .query_row(
+            "SELECT record FROM steps WHERE run = ?1 AND step = ?2",
+            [run, step],
+            |row| row.get(0),
+        )
+        .map_err(|e| anyhow!("find step {step} in run {run}: {e}"))?;
+    let record: serde_json::Value = serde_json::from_str(&record)?;
+    let mut stmt = conn
+        .prepare("SELECT step, kind, target, detail FROM findings WHERE run = ?1 AND step = ?2")?;
+    let findings = stmt
+        .query_map([run, step], |row| {
+            Ok(Finding {
+                step: row.get(0)?,
+                kind: row.get(1)?,
+                target: row.get(2)?,
+                detail: row.get(3)?,
+            })
+        })?
+        .collect::<Result<_, _>>()?;
+
+    let run_started = run
+        .parse::<KsuidMs>()
+        .ok()
+        .and_then(|id| DateTime::from_timestamp(id.timestamp_seconds(), 0));
+    let mut files = Vec::new();
+    if let Some(text) = plan {
+        files.push((
+            "plan.toml".to_owned(),
+            plan_fragment(text, step)?.into_bytes(),
+        ));
+    }
+    files.push(("step.json".to_owned(), serde_json::to_vec_pretty(&record)?));
+    let jobs = record.get("jobs").and_then(serde_json::Value::as_object);
+    for (key, job) in jobs.into_iter().flatten() {
+        // Keys may be arbitrary strings, so keep them from escaping the job's directory.
+        let dir = format!("jobs/{}", key.replace(['/', '\\'], "_"));
+        for protocol in ["tcp", "tls"] {
+            for direction in ["sent", "received"] {
+                let body = job
+                    .get(protocol)
+                    .and_then(|output| output.get(direction))
+                    .and_then(|pdu| pdu.get("body"))
+                    .and_then(decode_bytes);
+                if let Some(body) = body {
+                    files.push((format!("{dir}/{protocol}_{direction}.bin"), body));
+                }
+            }
+        }
+        if let Some(pcap) = job.get("raw_tcp").and_then(|raw| pcap(raw, run_started)) {
+            files.push((format!("{dir}/raw_tcp.pcap"), pcap));
+        }
+    }
+
+    let exported = Utc::now();
+    let manifest = Manifest {
+        plan: plan_name,
+        run: run.to_owned(),
+        step: step.to_owned(),
+        run_started,
+        exported,
+        findings,
+        errors: record
+            .get("errors")
+            .and_then(serde_json::Value::as_array)
+            .cloned()
+            .unwrap_or_default(),
+        files: files
+            .iter()
+            .map(|(path, data)| EvidenceFile {
+                path: path.clone(),
+                size: data.len(),
+                sha256: Sha256::digest(data)
+                    .iter()
+                    .map(|b| format!("{b:02x}"))
+                    .collect(),
+            })
+            .collect(),
+    };
+
+    let root = format!("{run}.{step}");
+    let mtime = u64::try_from(exported.timestamp()).unwrap_or_default();
+    let mut archive = Vec::new();
+    append(
+        &mut archive,
+        &format!("{root}/manifest.json"),
+        &serde_json::to_vec_pretty(&manifest)?,
+        mtime,
+    )?;
+    for (path, data) in &files {
+        append(&mut archive, &format!("{root}/{path}"), data, mtime)?;
+    }
+    // Two empty blocks end the archive.
+    archive.resize(archive.len() + 1024, 0);
+    std::fs::write(path, archive).map_err(|e| anyhow!("write {path}: {e}"))?;
+    Ok(manifest)
+}
+
+/// The plan's settings and the step's table, as a plan of their own.
+fn plan_fragment(text: &str, step: &str) -> anyhow::Result<String> {
+    let plan: toml_edit::DocumentMut = text.parse()?;
+    let mut fragment = toml_edit::DocumentMut::new();
+    if let Some(devil) = plan.get("devil") {
+        fragment.insert("devil", devil.clone());
+    }
+    let table = plan
+        .get(step)
+        .ok_or_else(|| anyhow!("plan has no step {step}"))?;
+    fragment.insert(step, table.clone());
+    Ok(fragment.to_string())
+}
+
+/// The bytes of a serialized MaybeUtf8.
+fn decode_bytes(value: &serde_json::Value) -> Option<Vec<u8>> {
+    if let Some(utf8) = value.get("utf8").and_then(serde_json::Value::as_str) {
+        return Some(utf8.as_bytes().to_vec());
+    }
+    let base64 = value.get("base64").and_then(serde_json::Value::as_str)?;
+    base64::prelude::BASE64_STANDARD.decode(base64).ok()
+}
+
+/// Convert a serialized duration.
+fn duration(value: Option<&serde_json::Value>) -> Option<Duration> {
+    let value = value?;
+    let secs = value.get("secs")?.as_u64()?;
+    let nanos = value.get("nanos")?.as_u64()?;
+    Some(Duration::from_secs(secs) + Duration::from_nanos(nanos))
+}
+
+/// Append a file to a ustar archive.
+fn append(archive: &mut Vec<u8>, path: &str, data: &[u8], mtime: u64) -> anyhow::Result<()> {
+    if path.len() > 100 {
+        bail!("archive path {path} is longer than 100 bytes");
+    }
+    let mut header = [0; 512];
+    header[..path.len()].copy_from_slice(path.as_bytes());
+    write_octal(&mut header[100..108], 0o644);
+    write_octal(&mut header[108..116], 0);
+    write_octal(&mut header[116..124], 0);
+    write_octal(&mut header[124..136], data.len() as u64);
+    write_octal(&mut header[136..148], mtime);
+    header[156] = b'0';
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+    // The checksum is calculated as if its own field were spaces.
+    header[148..156].fill(b' ');
+    let checksum: u64 = header.iter().map(|b| u64::from(*b)).sum();
+    write_octal(&mut header[148..155], checksum);
+    archive.extend(header);
+    archive.extend(data);
+    archive.resize(archive.len().next_multiple_of(512), 0);
+    Ok(())
+}
+
+/// Zero padded octal digits filling all but the last byte of the field, which is left NUL.
+fn write_octal(field: &mut [u8], value: u64) {
+    let width = field.len() - 1;
+    let digits = format!("{value:0width$o}");
+    field[..width].copy_from_slice(&digits.as_bytes()[digits.len() - width..]);
+    field[width] = 0;
+}
+
+/// Rebuild a raw_tcp job's segments as a pcap of IP packets, ordered by when they were sent or
+/// received.
+fn pcap(raw_tcp: &serde_json::Value, start: Option<DateTime<Utc>>) -> Option<Vec<u8>> {
+    let local: IpAddr = raw_tcp.get("src_host")?.as_str()?.parse().ok()?;
+    let remote: IpAddr = raw_tcp.get("dest_ip")?.as_str()?.parse().ok()?;
+    let mut packets = Vec::new();
+    for (direction, src, dst) in [("sent", local, remote), ("received", remote, local)] {
+        for segment in raw_tcp.get(direction)?.as_array()? {
+            let offset = duration(segment.get(direction)).unwrap_or_default();
+            packets.push((offset, packet(segment, src, dst)?));
+        }
+    }
+    packets.sort_by_key(|(offset, _)| *offset);
+
+    let start = start.map_or(0, |start| start.timestamp_micros());
+    let mut out = Vec::new();
+    out.extend(0xa1b2c3d4_u32.to_le_bytes());
+    out.extend(2_u16.to_le_bytes());
+    out.extend(4_u16.to_le_bytes());
+    // Timezone offset and timestamp accuracy.
+    out.extend(0_u32.to_le_bytes());
+    out.extend(0_u32.to_le_bytes());
+    out.extend(65535_u32.to_le_bytes());
+    // LINKTYPE_RAW, for packets starting with their IP header.
+    out.extend(101_u32.to_le_bytes());
+    for (offset, packet) in packets {
+        let micros = start + i64::try_from(offset.as_micros()).unwrap_or_default();
+        out.extend(((micros / 1_000_000) as u32).to_le_bytes());
+        out.extend(((micros % 1_000_000) as u32).to_le_bytes());
+        out.extend((packet.len() as u32).to_le_bytes());
+        out.extend((packet.len() as u32).to_le_bytes());
+        out.extend(packet);
+    }
+    Some(out)
+}
+
+fn packet(segment: &serde_json::Value, src: IpAddr, dst: IpAddr) -> Option<Vec<u8>> {
+    let field = |key: &str| segment.get(key).and_then(serde_json::Value::as_u64);
+    let payload = base64::prelude::BASE64_STANDARD
+        .decode(segment.get("payload")?.as_str()?)
+        .ok()?;
+    let mut options = Vec::new();
+    for option in segment.get("options")?.as_array()? {
+        encode_option(option, &mut options)?;
+    }
+    // Pad with nops to a whole number of words.
+    options.resize(options.len().next_multiple_of(4), 1);
+
+    let mut tcp = Vec::with_capacity(20 + options.len() + payload.len());
+    tcp.extend((field("source")? as u16).to_be_bytes());
+    tcp.extend((field("destination")? as u16).to_be_bytes());
+    tcp.extend((field("sequence_number")? as u32).to_be_bytes());
+    tcp.extend((field("acknowledgment")? as u32).to_be_bytes());
+    tcp.push((((20 + options.len()) / 4) as u8) << 4);
+    tcp.push(field("flags")? as u8);
+    tcp.extend((field("window")? as u16).to_be_bytes());
+    tcp.extend((field("checksum").unwrap_or_default() as u16).to_be_bytes());
+    tcp.extend((field("urgent_ptr")? as u16).to_be_bytes());
+    tcp.extend(options);
+    tcp.extend(payload);
+
+    let mut out = Vec::with_capacity(40 + tcp.len());
+    match (src, dst) {
+        (IpAddr::V4(src), IpAddr::V4(dst)) => {
+            let mut header = [0; 20];
+            header[0] = 0x45;
+            header[2..4].copy_from_slice(&((20 + tcp.len()) as u16).to_be_bytes());
+            // Don't fragment.
+            header[6] = 0x40;
+            header[8] = 64;
+            header[9] = 6;
+            header[12..16].copy_from_slice(&src.octets());
+            header[16..20].copy_from_slice(&dst.octets());
+            let checksum = ipv4_checksum(&header);
+            header[10..12].copy_from_slice(&checksum.to_be_bytes());
+            out.extend(header);
+        }
+        (IpAddr::V6(src), IpAddr::V6(dst)) => {
+            out.extend([0x60, 0, 0, 0]);
+            out.extend((tcp.len() as u16).to_be_bytes());
+            // Next header is TCP, with a hop limit of 64.
+            out.extend([6, 64]);
+            out.extend(src.octets());
+            out.extend(dst.octets());
+        }
+        _ => return None,
+    }
+    out.extend(tcp);
+    Some(out)
+}
+
+/// Encode a serialized TcpSegmentOptionOutput.
+fn encode_option(option: &serde_json::Value, out: &mut Vec<u8>) -> Option<()> {
+    let (kind, value) = option.as_object()?.iter().next()?;
+    match kind.as_str() {
+        "nop" => out.push(1),
+        "mss" => {
+            out.extend([2, 4]);
+            out.extend((value.as_u64()? as u16).to_be_bytes());
+        }
+        "wscale" => out.extend([3, 3, value.as_u64()? as u8]),
+        "sack_permitted" => out.extend([4, 2]),
+        "sack" => {
+            let edges = value.as_array()?;
+            out.extend([5, (2 + 4 * edges.len()) as u8]);
+            for edge in edges {
+                out.extend((edge.as_u64()? as u32).to_be_bytes());
+            }
+        }
+        "timestamps" => {
+            out.extend([8, 10]);
+            out.extend((value.get("tsval")?.as_u64()? as u32).to_be_bytes());
+            out.extend((value.get("tsecr")?.as_u64()? as u32).to_be_bytes());
+        }
+        "generic" => {
+            let data = base64::prelude::BASE64_STANDARD
+                .decode(value.get("value")?.as_str()?)
+                .ok()?;
+            out.extend([value.get("kind")?.as_u64()? as u8, (2 + data.len()) as u8]);
+            out.extend(data);
+        }
+        _ => return None,
+    }
+    Some(())
+}
+
+fn ipv4_checksum(header: &[u8]) -> u16 {
+    let mut sum: u32 = header
+        .chunks(2)
+        .map(|word| u32::from(u16::from_be_bytes([word[0], word[1]])))
+        .sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
@@ -0,0 +1,153 @@
+use std::sync::Mutex;
+use std::time::Instant;
+
+use chrono::TimeDelta;
+use tokio::sync::Notify;
+
+use crate::JobOutput;
+use crate::{AdaptiveAlgorithm, AdaptiveOutput, AdaptivePlanOutput, ConcurrencySampleOutput};
+
+/// How much a single job moves the smoothed error rate.
+const ERROR_RATE_WEIGHT: f64 = 0.1;
+/// How much to cut the limit by when a job misses the targets.
+const BACKOFF: f64 = 0.9;
+
+/// Limits how many jobs are in flight, raising the limit while jobs meet the plan's targets and
+/// lowering it when they don't.
+#[derive(Debug)]
+pub(super) struct Controller {
+    plan: AdaptivePlanOutput,
+    start: Instant,
+    state: Mutex<ControllerState>,
+    notify: Notify,
+}
+
+#[derive(Debug)]
+struct ControllerState {
+    limit: f64,
+    in_flight: u64,
+    error_rate: f64,
+    max_healthy_limit: u64,
+    samples: Vec<ConcurrencySampleOutput>,
+}
+
+impl Controller {
+    pub fn new(mut plan: AdaptivePlanOutput, max_parallel: usize) -> Self {
+        // The step's parallel setting is still a hard cap.
+        plan.max = plan
+            .max
+            .min(u64::try_from(max_parallel).unwrap_or(u64::MAX));
+        plan.min = plan.min.min(plan.max);
+        plan.initial = plan.initial.clamp(plan.min, plan.max);
+        Self {
+            start: Instant::now(),
+            state: Mutex::new(ControllerState {
+                limit: plan.initial as f64,
+                in_flight: 0,
+                error_rate: 0.0,
+                max_healthy_limit: 0,
+                samples: Vec::new(),
+            }),
+            notify: Notify::new(),
+            plan,
+        }
+    }
+
+    /// Wait until another job is allowed to start.
+    pub async fn acquire(&self) {
+        loop {
+            // Register for wakeups before checking so a release between the check and the wait
+            // isn't missed.
+            let notified = self.notify.notified();
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.in_flight < (state.limit as u64).max(1) {
+                    state.in_flight += 1;
+                    return;
+                }
+            }
+            notified.await;
+        }
+    }
+
+    /// Record a finished job and adjust the limit based on how it went.
+    pub fn release(&self, latency: std::time::Duration, failed: bool) {
+        let mut state = self.state.lock().unwrap();
+        state.in_flight -= 1;
+        state.error_rate += ERROR_RATE_WEIGHT * (f64::from(u8::from(failed)) - state.error_rate);
+        let slow = self
+            .plan
+            .target_latency
+            .as_ref()
+            .and_then(|target| target.0.to_std().ok())
+            .is_some_and(|target| latency > target);
+        let erroring = self
+            .plan
+            .max_error_rate
+            .is_some_and(|max| state.error_rate > max);
+        let limit = state.limit;
+        let next = if erroring {
+            limit * BACKOFF
+        } else {
+            match self.plan.algorithm {
+                AdaptiveAlgorithm::Aimd if slow => limit * BACKOFF,
+                AdaptiveAlgorithm::Aimd => limit + 1.0 / limit,
+                AdaptiveAlgorithm::Gradient => {
+                    let gradient = self
+                        .plan
+                        .target_latency
+                        .as_ref()
+                        .and_then(|target| target.0.to_std().ok())
+                        .map_or(1.0, |target| {
+                            (target.as_secs_f64() / latency.as_secs_f64().max(f64::EPSILON))
+                                .clamp(0.5, 1.0)
+                        });
+                    // Smooth toward the new estimate, with a queue allowance to keep probing.
+                    0.8 * limit + 0.2 * (limit * gradient + limit.sqrt())
+                }
+            }
+        };
+        state.limit = next.clamp(self.plan.min as f64, self.plan.max as f64);
+        if !slow && !erroring {
+            state.max_healthy_limit = state.max_healthy_limit.max(limit as u64);
+        }
+        if state.limit as u64 != limit as u64 || state.samples.is_empty() {
+            let sample = ConcurrencySampleOutput {
+                elapsed: TimeDelta::from_std(self.start.elapsed()).unwrap().into(),
+                limit: state.limit as u64,
+                in_flight: state.in_flight,
+                latency: TimeDelta::from_std(latency).unwrap().into(),
+                error_rate: state.error_rate,
+            };
+            state.samples.push(sample);
+        }
+        drop(state);
+        self.notify.notify_waiters();
+    }
+
+    pub fn output(&self) -> AdaptiveOutput {
+        let state = self.state.lock().unwrap();
+        AdaptiveOutput {
+            plan: self.plan.clone(),
+            samples: state.samples.clone(),
+            max_healthy_limit: state.max_healthy_limit,
+        }
+    }
+}
+
+/// Whether a job failed outright or its server signalled it was overloaded.
+pub(super) fn failed(job: &JobOutput) -> bool {
+    let overloaded = |status: Option<u16>| status.is_some_and(|s| s == 429 || s >= 500);
+    [&job.h1, &job.h1c].into_iter().flatten().any(|h| {
+        !h.errors.is_empty() || overloaded(h.response.as_ref().and_then(|r| r.status_code))
+    }) || [&job.h2, &job.h2c].into_iter().flatten().any(|h| {
+        !h.errors.is_empty() || overloaded(h.response.as_ref().and_then(|r| r.status_code))
+    }) || job.h3.as_ref().is_some_and(|h| {
+        !h.errors.is_empty() || overloaded(h.response.as_ref().and_then(|r| r.status_code))
+    }) || job.http.as_ref().is_some_and(|h| {
+        !h.errors.is_empty() || overloaded(h.response.as_ref().and_then(|r| r.status_code))
+    }) || job.tls.as_ref().is_some_and(|t| !t.errors.is_empty())
+        || job.tcp.as_ref().is_some_and(|t| !t.errors.is_empty())
+        || job.raw_tcp.as_ref().is_some_and(|t| !t.errors.is_empty())
+        || job.quic.as_ref().is_some_and(|t| !t.errors.is_empty())
+}
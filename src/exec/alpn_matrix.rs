@@ -0,0 +1,239 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use base64::Engine;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::{
+    AddContentLength, AlpnAttemptOutput, AlpnMatrixOutput, AlpnMatrixPlanOutput, CacheMode,
+    Http1PlanOutput, Http2PlanOutput, HttpHeader, IterableKey, JobOutput, MaybeUtf8,
+    RawHttp2PlanOutput, RawTcpPlanOutput, StepPlanOutput, TcpPlanOutput, TlsPlanOutput,
+};
+
+use super::runner::Runner;
+use super::{Context, Executor};
+
+/// The ALPN offers to try, where None sends no ALPN extension.
+const OFFERS: [Option<&str>; 3] = [Some("h2"), Some("http/1.1"), None];
+
+/// The top of the protocol stack for one connection.
+#[derive(Debug, Clone, Copy)]
+pub(super) enum Layer {
+    /// Only complete the TLS handshake, to see whether the offer is accepted.
+    Handshake,
+    Http1,
+    Http2,
+}
+
+/// For each offer, complete a handshake to learn what the server selects, then request the URL
+/// with the selected protocol and compare the response to the first one.
+pub(super) async fn alpn_matrix(
+    ctx: &Context,
+    plan: AlpnMatrixPlanOutput,
+) -> (AlpnMatrixOutput, Vec<(IterableKey, Arc<JobOutput>)>) {
+    let mut out = AlpnMatrixOutput {
+        attempts: Vec::new(),
+        accepted: Vec::new(),
+        consistent: true,
+        error: None,
+        plan,
+    };
+    let mut jobs = Vec::new();
+    if out.plan.url.scheme() != "https" {
+        out.error = Some("alpn_matrix.url must be https".to_owned());
+        return (out, jobs);
+    }
+    let mut baseline: Option<(String, Option<u16>, u64, String)> = None;
+    for offer in OFFERS {
+        let mut attempt = AlpnAttemptOutput {
+            offered: offer.unwrap_or("none").to_owned(),
+            negotiated: None,
+            http_version: "http/1.1".to_owned(),
+            status_code: None,
+            length: 0,
+            body_sha256: None,
+            differences: Vec::new(),
+            error: None,
+        };
+        let alpn: Vec<_> = offer.into_iter().map(|p| MaybeUtf8(p.into())).collect();
+        let url = &out.plan.url;
+        let handshake = match fetch(ctx, url, &alpn, Layer::Handshake, false, &mut jobs).await {
+            Ok(job) => job,
+            Err(e) => {
+                attempt.error = Some(format!("{e:#}"));
+                out.attempts.push(attempt);
+                continue;
+            }
+        };
+        out.accepted.push(attempt.offered.clone());
+        attempt.negotiated = handshake
+            .tls
+            .as_ref()
+            .and_then(|tls| tls.alpn.as_ref())
+            .map(ToString::to_string);
+        let layer = if attempt.negotiated.as_deref() == Some("h2") {
+            attempt.http_version = "h2".to_owned();
+            Layer::Http2
+        } else {
+            Layer::Http1
+        };
+
+        let job = match fetch(ctx, url, &alpn, layer, false, &mut jobs).await {
+            Ok(job) => job,
+            Err(e) => {
+                attempt.error = Some(format!("{e:#}"));
+                out.attempts.push(attempt);
+                continue;
+            }
+        };
+        let (status_code, body) = job.response_summary();
+        let hash = base64::prelude::BASE64_STANDARD.encode(Sha256::digest(body));
+        attempt.status_code = status_code;
+        attempt.length = body.len().try_into().unwrap_or(u64::MAX);
+        attempt.body_sha256 = Some(hash.clone());
+        match &baseline {
+            None => baseline = Some((attempt.offered.clone(), status_code, attempt.length, hash)),
+            Some((offered, baseline_status, baseline_length, baseline_hash)) => {
+                if status_code != *baseline_status {
+                    attempt.differences.push(format!(
+                        "status {} differs from {} with {offered}",
+                        status(status_code),
+                        status(*baseline_status),
+                    ));
+                }
+                if attempt.length != *baseline_length {
+                    attempt.differences.push(format!(
+                        "length {} differs from {baseline_length} with {offered}",
+                        attempt.length,
+                    ));
+                } else if hash != *baseline_hash {
+                    attempt
+                        .differences
+                        .push(format!("body differs from {offered}"));
+                }
+            }
+        }
+        out.consistent &= attempt.differences.is_empty();
+        out.attempts.push(attempt);
+    }
+    (out, jobs)
+}
+
+fn status(code: Option<u16>) -> String {
+    code.map_or_else(|| "none".to_owned(), |c| c.to_string())
+}
+
+/// Connect to the URL's host offering the given ALPN protocols, recording the job. Insecure
+/// connections accept any certificate.
+pub(super) async fn fetch(
+    ctx: &Context,
+    url: &Url,
+    alpn: &[MaybeUtf8],
+    layer: Layer,
+    insecure: bool,
+    jobs: &mut Vec<(IterableKey, Arc<JobOutput>)>,
+) -> anyhow::Result<Arc<JobOutput>> {
+    let key = IterableKey::Uint(jobs.len().try_into().unwrap_or(u64::MAX));
+    let mut job_name = ctx.job_name.clone();
+    job_name.job = key.clone();
+    let ctx = Arc::new(ctx.for_job(job_name.clone()));
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow!("alpn_matrix.url is missing host"))?
+        .to_owned();
+    let port = url
+        .port_or_known_default()
+        .ok_or_else(|| anyhow!("alpn_matrix.url is missing port"))?;
+
+    let mut stack = Vec::with_capacity(5);
+    match layer {
+        Layer::Handshake => {}
+        Layer::Http1 => stack.push(StepPlanOutput::H1(Http1PlanOutput {
+            headers: vec![HttpHeader {
+                key: Some(MaybeUtf8("Host".into())),
+                value: MaybeUtf8(super::crawl::authority(url)?.into()),
+            }],
+            url: url.clone(),
+            method: Some(MaybeUtf8("GET".into())),
+            version_string: Some(MaybeUtf8("HTTP/1.1".into())),
+            add_content_length: AddContentLength::Auto,
+            body: MaybeUtf8::default(),
+            sign: None,
+            cache: CacheMode::Off,
+            chunked: None,
+            expect_continue: None,
+            head: None,
+            read: None,
+            permissive: false,
+        })),
+        Layer::Http2 => {
+            stack.push(StepPlanOutput::H2(Http2PlanOutput {
+                url: url.clone(),
+                method: Some(MaybeUtf8("GET".into())),
+                add_content_length: AddContentLength::Auto,
+                headers: Vec::new(),
+                trailers: Vec::new(),
+                body: MaybeUtf8::default(),
+            }));
+            stack.push(StepPlanOutput::RawH2(RawHttp2PlanOutput {
+                host: host.clone(),
+                port,
+                preamble: None,
+                frames: Vec::new(),
+            }));
+        }
+    }
+    stack.push(StepPlanOutput::Tls(TlsPlanOutput {
+        host: host.clone(),
+        port,
+        server_name: None,
+        alpn: alpn.to_vec(),
+        body: MaybeUtf8::default(),
+        min_version: None,
+        max_version: None,
+        cipher_suites: Vec::new(),
+        pin: None,
+        client: None,
+        ca: None,
+        ca_file: None,
+        insecure,
+        resume: None,
+        session_tickets: true,
+        expect: Vec::new(),
+    }));
+    stack.push(StepPlanOutput::Tcp(TcpPlanOutput {
+        host: host.clone(),
+        port,
+        body: MaybeUtf8::default(),
+        proxies: Vec::new(),
+        proxy_protocol: None,
+        fault: None,
+        expect: Vec::new(),
+    }));
+    stack.push(StepPlanOutput::RawTcp(RawTcpPlanOutput {
+        dest_host: host,
+        dest_port: port,
+        src_host: None,
+        src_port: None,
+        isn: 0,
+        window: 1000,
+        segments: Vec::new(),
+    }));
+
+    let mut runners = stack
+        .into_iter()
+        .enumerate()
+        .map(|(i, req)| Runner::new(ctx.clone(), req, i == 0))
+        .collect::<crate::Result<Vec<_>>>()?;
+    let mut size_hint = runners[0].executor_size_hint();
+    for r in &mut runners {
+        size_hint = r.size_hint(size_hint);
+    }
+    let runner = Executor::start_runners(None, runners, 1)
+        .await?
+        .expect("any stack should have at least one protocol");
+    let job = Arc::new(Executor::iteration(runner, None, job_name).await?.0);
+    jobs.push((key, job.clone()));
+    Ok(job)
+}
@@ -0,0 +1,87 @@
+//! Logins shared by the steps tagged with their name in run.auth. A login runs before the first
+//! tagged step and again when a tagged step is rejected as unauthorized, so long runs outlast the
+//! credentials they started with.
+
+use std::sync::Arc;
+
+use anyhow::anyhow;
+
+use super::cookies::CookieJar;
+use super::socket::SocketProvider;
+use super::{ConfirmDestructive, Executor, State};
+use crate::cel_functions::CelState;
+use crate::{
+    AuthPlanOutput, AuthRequest, Evaluate, ModuleOutput, Plan, PlanValue, RunName, RunOutput,
+    StepOutput, StepPlanOutputs,
+};
+
+/// A login's settings and the credentials it was last given.
+#[derive(Debug)]
+pub(super) struct Login {
+    pub request: AuthRequest,
+    /// The cookies and token from the last time the login ran, or None if it hasn't.
+    pub jar: Option<Arc<CookieJar>>,
+}
+
+impl Login {
+    pub fn new(request: AuthRequest) -> Self {
+        Self { request, jar: None }
+    }
+}
+
+/// Run a login plan with a new cookie jar, then add the token evaluated from its steps to the
+/// jar's headers.
+pub(super) async fn login(
+    plan: AuthPlanOutput,
+    token: Option<&PlanValue<String>>,
+    run: svix_ksuid::KsuidMs,
+    sockets: Arc<dyn SocketProvider>,
+    confirm_destructive: Option<ConfirmDestructive>,
+    cel: Arc<CelState>,
+) -> anyhow::Result<(ModuleOutput, Arc<CookieJar>)> {
+    let text = super::read_to_string(&plan.path)
+        .await
+        .map_err(|e| anyhow!("read login {}: {e}", plan.path))?;
+    let login = Plan::parse(&text)?;
+    let jar = Arc::new(CookieJar::default());
+    let mut output = RunOutput::new(RunName {
+        plan: login.name.clone(),
+        run,
+    });
+    let mut executor = Executor::with_cel_state(&login, output.name.clone(), plan.params, cel)?
+        .with_socket_provider(sockets)
+        .with_cookie_jar(jar.clone());
+    executor.confirm_destructive = confirm_destructive;
+    while executor.current().is_some() {
+        // Boxed since logins may run modules.
+        let step = Box::pin(executor.next()).await?;
+        output.steps.insert(step.name.step.clone(), Arc::new(step));
+    }
+    output.usage = Some(executor.usage());
+    if let Some(token) = token {
+        let token = token
+            .evaluate(&State {
+                data: &executor.outputs,
+                locals: &executor.locals,
+                current: StepPlanOutputs::default(),
+                run_while: None,
+                run_for: None,
+                run_count: None,
+                previous: None,
+                run_name: &output.name,
+                job_name: None,
+                cel: &executor.cel,
+            })
+            .map_err(|e| anyhow!("evaluate token from login {}: {e:#}", plan.path))?;
+        jar.set_header(plan.header, token);
+    }
+    Ok((ModuleOutput(Arc::new(output)), jar))
+}
+
+/// Whether any of a step's responses were 401 Unauthorized.
+pub(super) fn unauthorized(output: &StepOutput) -> bool {
+    output
+        .jobs
+        .values()
+        .any(|job| job.response_summary().0 == Some(401))
+}
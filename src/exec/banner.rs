@@ -0,0 +1,118 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Instant;
+
+use anyhow::{anyhow, bail};
+use bytes::Bytes;
+use chrono::TimeDelta;
+use futures::{stream, StreamExt};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::{BannerOutput, BannerPlanOutput, BannerResultOutput, MaybeUtf8};
+
+use super::socket::SocketOptions;
+use super::{dns, network, proxy, Context};
+
+/// Connect to every target concurrently, optionally send the probe, and record whatever comes
+/// back before the timeout.
+pub(super) async fn banner(ctx: &Context, plan: BannerPlanOutput) -> BannerOutput {
+    let concurrency = usize::try_from(plan.concurrency).unwrap_or(usize::MAX);
+    let results: Vec<_> = stream::iter(plan.targets.iter())
+        .map(|target| grab(ctx, &plan, target))
+        .buffered(concurrency)
+        .collect()
+        .await;
+    BannerOutput {
+        open: results
+            .iter()
+            .filter(|r| r.open)
+            .map(|r| r.target.clone())
+            .collect(),
+        results,
+        plan,
+    }
+}
+
+async fn grab(ctx: &Context, plan: &BannerPlanOutput, target: &str) -> BannerResultOutput {
+    let mut out = BannerResultOutput {
+        target: target.to_owned(),
+        address: None,
+        open: false,
+        banner: None,
+        connect_duration: None,
+        time_to_first_byte: None,
+        error: None,
+    };
+    let timeout = plan.timeout.0.to_std().unwrap_or_default();
+    let max_bytes = usize::try_from(plan.max_bytes).unwrap_or(usize::MAX);
+    let mut banner = Vec::new();
+    let result = tokio::time::timeout(timeout, async {
+        let (host, port) = target
+            .rsplit_once(':')
+            .and_then(|(host, port)| Some((host, port.parse().ok()?)))
+            .ok_or_else(|| anyhow!("banner target '{target}' is not host:port"))?;
+        if let Some(egress) = &ctx.egress {
+            egress.acquire().await;
+        }
+        let proxy = ctx
+            .egress
+            .as_ref()
+            .and_then(|egress| egress.plan.proxy.as_ref());
+        let (dest_host, dest_port) = proxy.map_or((host, port), |p| (p.host.as_str(), p.port));
+        let remote = dns::lookup(ctx, dest_host, dest_port)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("no addresses found for '{dest_host}'"))?;
+        out.address = Some(remote.to_string());
+        let local = ctx
+            .egress
+            .as_ref()
+            .and_then(|egress| egress.local_addr(remote))
+            .unwrap_or_else(|| {
+                if remote.is_ipv4() {
+                    SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0)
+                } else {
+                    SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0)
+                }
+            });
+        let start = Instant::now();
+        let mut stream = network::connect(ctx, local, remote, SocketOptions::default()).await?;
+        if let Some(proxy) = proxy {
+            if let Some(e) = proxy::handshake(&mut stream, proxy, host, port).await.error {
+                bail!("proxy {}:{}: {e}", proxy.host, proxy.port);
+            }
+        }
+        out.open = true;
+        out.connect_duration = Some(TimeDelta::from_std(start.elapsed()).unwrap().into());
+        if let Some(probe) = &plan.probe {
+            stream.write_all(probe).await?;
+            stream.flush().await?;
+        }
+        let mut buf = vec![0; max_bytes.min(65536)];
+        // Keep reading until the limit, the peer closes, or the timeout cuts us off.
+        while banner.len() < max_bytes {
+            let n = stream.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            if out.time_to_first_byte.is_none() {
+                out.time_to_first_byte = Some(TimeDelta::from_std(start.elapsed()).unwrap().into());
+            }
+            banner.extend_from_slice(&buf[..n]);
+        }
+        anyhow::Ok(())
+    })
+    .await;
+    match result {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => out.error = Some(format!("{e:#}")),
+        // Services that send a short banner and then wait are expected to hit the timeout.
+        Err(_) if out.open => {}
+        Err(_) => out.error = Some("timed out".to_owned()),
+    }
+    if !banner.is_empty() {
+        banner.truncate(max_bytes);
+        out.banner = Some(MaybeUtf8(Bytes::from(banner).into()));
+    }
+    out
+}
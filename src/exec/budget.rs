@@ -0,0 +1,288 @@
+//! Counts the connections and bytes a run uses over TCP and UDP, refusing new connections once the plan's
+//! budget for them is spent.
+
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::Poll;
+
+use chrono::TimeDelta;
+use futures::future::BoxFuture;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use super::socket::{BoxDatagram, BoxStream, Datagram, SocketOptions, SocketProvider};
+use crate::{Budget, UsageOutput};
+
+/// A run's traffic so far.
+#[derive(Debug)]
+pub(super) struct Meter {
+    budget: Budget,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    connections: AtomicU64,
+    /// The totals when the current step started.
+    step_start: Mutex<Totals>,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub(super) struct Totals {
+    bytes_sent: u64,
+    bytes_received: u64,
+    connections: u64,
+}
+
+impl Meter {
+    pub fn new(budget: Budget) -> Self {
+        Self {
+            budget,
+            bytes_sent: AtomicU64::default(),
+            bytes_received: AtomicU64::default(),
+            connections: AtomicU64::default(),
+            step_start: Mutex::default(),
+        }
+    }
+
+    pub fn budget(&self) -> &Budget {
+        &self.budget
+    }
+
+    /// Count traffic from now on towards a new step.
+    pub fn start_step(&self) {
+        *self.step_start.lock().unwrap() = self.totals();
+    }
+
+    /// The totals when the current step started, to restore after running a module's steps
+    /// within it.
+    pub fn step_start(&self) -> Totals {
+        *self.step_start.lock().unwrap()
+    }
+
+    pub fn restore_step_start(&self, start: Totals) {
+        *self.step_start.lock().unwrap() = start;
+    }
+
+    /// The traffic since the current step started.
+    pub fn step_usage(&self, attempts: u64, duration: TimeDelta) -> UsageOutput {
+        let start = *self.step_start.lock().unwrap();
+        let totals = self.totals();
+        usage(
+            Totals {
+                bytes_sent: totals.bytes_sent - start.bytes_sent,
+                bytes_received: totals.bytes_received - start.bytes_received,
+                connections: totals.connections - start.connections,
+            },
+            attempts,
+            duration,
+        )
+    }
+
+    /// The traffic of the whole run.
+    pub fn run_usage(&self, attempts: u64, duration: TimeDelta) -> UsageOutput {
+        usage(self.totals(), attempts, duration)
+    }
+
+    fn totals(&self) -> Totals {
+        Totals {
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            connections: self.connections.load(Ordering::Relaxed),
+        }
+    }
+
+    fn check_connect(&self) -> io::Result<()> {
+        let zero = TimeDelta::zero();
+        let exhausted = if self.budget.run.blocks_connections(&self.run_usage(0, zero)) {
+            "run"
+        } else if self
+            .budget
+            .step
+            .blocks_connections(&self.step_usage(0, zero))
+        {
+            "step"
+        } else {
+            return Ok(());
+        };
+        Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("devil.budget.{exhausted} is spent"),
+        ))
+    }
+}
+
+fn usage(totals: Totals, attempts: u64, duration: TimeDelta) -> UsageOutput {
+    UsageOutput {
+        bytes_sent: totals.bytes_sent,
+        bytes_received: totals.bytes_received,
+        connections: totals.connections,
+        attempts,
+        duration: duration.into(),
+    }
+}
+
+/// Wraps a socket provider to count each connection and the bytes sent and received on it.
+#[derive(Debug)]
+pub(super) struct MeteredSocketProvider {
+    inner: Arc<dyn SocketProvider>,
+    meter: Arc<Meter>,
+}
+
+impl MeteredSocketProvider {
+    pub fn wrap(inner: Arc<dyn SocketProvider>, meter: &Arc<Meter>) -> Arc<dyn SocketProvider> {
+        Arc::new(Self {
+            inner,
+            meter: meter.clone(),
+        })
+    }
+
+    fn meter(
+        &self,
+        connect: BoxFuture<'static, io::Result<BoxStream>>,
+    ) -> BoxFuture<'static, io::Result<BoxStream>> {
+        let meter = self.meter.clone();
+        Box::pin(async move {
+            meter.check_connect()?;
+            let stream = connect.await?;
+            meter.connections.fetch_add(1, Ordering::Relaxed);
+            Ok(Box::new(MeteredStream { stream, meter }) as BoxStream)
+        })
+    }
+}
+
+impl SocketProvider for MeteredSocketProvider {
+    fn connect_tcp(
+        &self,
+        local_addr: SocketAddr,
+        remote_addr: SocketAddr,
+    ) -> BoxFuture<'static, io::Result<BoxStream>> {
+        self.meter(self.inner.connect_tcp(local_addr, remote_addr))
+    }
+
+    fn connect_tcp_with_options(
+        &self,
+        local_addr: SocketAddr,
+        remote_addr: SocketAddr,
+        options: SocketOptions,
+    ) -> BoxFuture<'static, io::Result<BoxStream>> {
+        self.meter(
+            self.inner
+                .connect_tcp_with_options(local_addr, remote_addr, options),
+        )
+    }
+
+    fn connect_udp(
+        &self,
+        local_addr: SocketAddr,
+        remote_addr: SocketAddr,
+    ) -> BoxFuture<'static, io::Result<BoxDatagram>> {
+        let connect = self.inner.connect_udp(local_addr, remote_addr);
+        let meter = self.meter.clone();
+        Box::pin(async move {
+            meter.check_connect()?;
+            let socket = connect.await?;
+            meter.connections.fetch_add(1, Ordering::Relaxed);
+            Ok(Box::new(MeteredDatagram { socket, meter }) as BoxDatagram)
+        })
+    }
+
+    fn lookup_host(
+        &self,
+        host: String,
+        port: u16,
+    ) -> BoxFuture<'static, io::Result<Vec<SocketAddr>>> {
+        self.inner.lookup_host(host, port)
+    }
+
+    fn capture_raw_tcp(&self) -> bool {
+        self.inner.capture_raw_tcp()
+    }
+}
+
+#[derive(Debug)]
+struct MeteredStream {
+    stream: BoxStream,
+    meter: Arc<Meter>,
+}
+
+impl AsyncRead for MeteredStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        let poll = Pin::new(&mut self.stream).poll_read(cx, buf);
+        let read = (buf.filled().len() - before) as u64;
+        self.meter.bytes_received.fetch_add(read, Ordering::Relaxed);
+        poll
+    }
+}
+
+impl AsyncWrite for MeteredStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.stream).poll_write(cx, buf);
+        if let Poll::Ready(Ok(written)) = &poll {
+            self.meter
+                .bytes_sent
+                .fetch_add(*written as u64, Ordering::Relaxed);
+        }
+        poll
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+}
+
+#[derive(Debug)]
+struct MeteredDatagram {
+    socket: BoxDatagram,
+    meter: Arc<Meter>,
+}
+
+impl Datagram for MeteredDatagram {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    fn poll_send_ready(&self, cx: &mut std::task::Context<'_>) -> Poll<io::Result<()>> {
+        self.socket.poll_send_ready(cx)
+    }
+
+    fn poll_send(&self, cx: &mut std::task::Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let poll = self.socket.poll_send(cx, buf);
+        if let Poll::Ready(Ok(sent)) = &poll {
+            self.meter
+                .bytes_sent
+                .fetch_add(*sent as u64, Ordering::Relaxed);
+        }
+        poll
+    }
+
+    fn poll_recv(
+        &self,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        let poll = self.socket.poll_recv(cx, buf);
+        let read = (buf.filled().len() - before) as u64;
+        self.meter.bytes_received.fetch_add(read, Ordering::Relaxed);
+        poll
+    }
+}
@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::{Http1Response, HttpHeader, MaybeUtf8};
+
+/// A private client-side HTTP cache shared by all steps in a run. Only validators are used, since
+/// requests are always sent so their behavior can be observed.
+#[derive(Debug, Default)]
+pub(crate) struct HttpCache {
+    entries: Mutex<HashMap<(Vec<u8>, String), Entry>>,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    etag: Option<MaybeUtf8>,
+    last_modified: Option<MaybeUtf8>,
+}
+
+impl HttpCache {
+    /// The conditional headers to send for a request based on a previously stored response.
+    pub fn conditional_headers(&self, method: &[u8], url: &url::Url) -> Vec<HttpHeader> {
+        let entries = self.entries.lock().unwrap();
+        let Some(entry) = entries.get(&(method.to_vec(), url.to_string())) else {
+            return Vec::new();
+        };
+        entry
+            .etag
+            .iter()
+            .map(|etag| HttpHeader {
+                key: Some(MaybeUtf8("If-None-Match".into())),
+                value: etag.clone(),
+            })
+            .chain(entry.last_modified.iter().map(|date| HttpHeader {
+                key: Some(MaybeUtf8("If-Modified-Since".into())),
+                value: date.clone(),
+            }))
+            .collect()
+    }
+
+    /// Store the validators of a response if it's cacheable. Returns whether it was stored.
+    pub fn store(&self, method: &[u8], url: &url::Url, response: &Http1Response) -> bool {
+        // A 304 confirms the stored response is still valid, so keep it as is.
+        if response.status_code == Some(304) {
+            return false;
+        }
+        if !matches!(method, b"GET" | b"HEAD") || response.status_code != Some(200) {
+            return false;
+        }
+        let headers = response.headers.as_deref().unwrap_or_default();
+        let no_store = find(headers, "cache-control").is_some_and(|cc| {
+            String::from_utf8_lossy(cc)
+                .split(',')
+                .any(|d| d.trim().eq_ignore_ascii_case("no-store"))
+        });
+        let entry = Entry {
+            etag: find(headers, "etag").cloned(),
+            last_modified: find(headers, "last-modified").cloned(),
+        };
+        if no_store || (entry.etag.is_none() && entry.last_modified.is_none()) {
+            return false;
+        }
+        self.entries
+            .lock()
+            .unwrap()
+            .insert((method.to_vec(), url.to_string()), entry);
+        true
+    }
+}
+
+fn find<'a>(headers: &'a [HttpHeader], name: &str) -> Option<&'a MaybeUtf8> {
+    headers
+        .iter()
+        .find(|h| {
+            h.key
+                .as_ref()
+                .is_some_and(|k| k.eq_ignore_ascii_case(name.as_bytes()))
+        })
+        .map(|h| &h.value)
+}
@@ -0,0 +1,65 @@
+use encoding_rs::Encoding;
+use regex::bytes::Regex;
+use std::sync::OnceLock;
+
+use crate::HttpHeader;
+
+/// How far into an HTML body to look for a meta charset declaration, matching the HTML spec's
+/// prescan limit.
+const META_PRESCAN_LEN: usize = 1024;
+
+/// Detect a body's charset and decode it to text. The charset comes from a byte order mark, the
+/// Content-Type header, or an HTML meta tag in that order. Returns None if no charset is declared
+/// and the body isn't valid UTF-8.
+pub(super) fn decode(headers: &[HttpHeader], body: &[u8]) -> Option<(String, String)> {
+    let encoding = Encoding::for_bom(body)
+        .map(|(encoding, _)| encoding)
+        .or_else(|| content_type_charset(headers))
+        .or_else(|| meta_charset(body));
+    let Some(encoding) = encoding else {
+        return std::str::from_utf8(body)
+            .ok()
+            .map(|text| ("UTF-8".to_owned(), text.to_owned()));
+    };
+    let (text, encoding, _) = encoding.decode(body);
+    Some((encoding.name().to_owned(), text.into_owned()))
+}
+
+fn content_type_charset(headers: &[HttpHeader]) -> Option<&'static Encoding> {
+    let content_type = headers.iter().find(|h| {
+        h.key
+            .as_ref()
+            .is_some_and(|k| k.eq_ignore_ascii_case(b"content-type"))
+    })?;
+    content_type
+        .value
+        .split(|b| *b == b';')
+        .skip(1)
+        .find_map(|param| {
+            let param = param.trim_ascii();
+            let (key, value) = param.split_at(param.iter().position(|b| *b == b'=')?);
+            if !key.eq_ignore_ascii_case(b"charset") {
+                return None;
+            }
+            Encoding::for_label(trim_quotes(value[1..].trim_ascii()))
+        })
+}
+
+fn meta_charset(body: &[u8]) -> Option<&'static Encoding> {
+    static META: OnceLock<Regex> = OnceLock::new();
+    let meta = META.get_or_init(|| {
+        Regex::new(r#"(?i-u)<meta[^>]+charset\s*=\s*["']?([A-Za-z0-9_\-:.]+)"#).unwrap()
+    });
+    let head = &body[..body.len().min(META_PRESCAN_LEN)];
+    Encoding::for_label(meta.captures(head)?.get(1)?.as_bytes())
+}
+
+fn trim_quotes(mut s: &[u8]) -> &[u8] {
+    while let [b'"' | b'\'', rest @ ..] = s {
+        s = rest;
+    }
+    while let [rest @ .., b'"' | b'\''] = s {
+        s = rest;
+    }
+    s
+}
@@ -0,0 +1,163 @@
+//! Command steps, which run a local program and capture what it prints.
+//!
+//! Commands only run when the executor allows them, since they're not sandboxed. Devil only
+//! limits what the program inherits and how long it runs:
+//!
+//! - The environment is cleared except for `PATH` and the step's `env`, so devil's secrets and
+//!   credentials in environment variables aren't passed on.
+//! - The program runs in a fresh scratch directory unless the step sets `dir`, and the directory
+//!   is removed afterwards.
+//! - The program is killed once `timeout` passes or the step is cancelled.
+//! - At most `max_output` bytes of stdout and stderr are kept.
+//!
+//! The program still runs as devil's user with its file system, network and process access.
+//! Processes it starts in the background aren't killed with it, and it isn't subject to the
+//! plan's scope or budget.
+
+use crate::{CommandOutput, CommandPlanOutput};
+
+/// Run the program with only the configured environment, in a scratch directory unless one is
+/// given, and capture what it prints.
+#[cfg(feature = "command")]
+pub(super) async fn command(plan: CommandPlanOutput) -> anyhow::Result<CommandOutput> {
+    use std::process::Stdio;
+    use std::time::Instant;
+
+    use anyhow::anyhow;
+    use bytes::Bytes;
+    use chrono::TimeDelta;
+    use svix_ksuid::{KsuidLike, KsuidMs};
+    use tokio::io::AsyncWriteExt;
+
+    use crate::MaybeUtf8;
+
+    let scratch = match &plan.dir {
+        Some(_) => None,
+        None => {
+            let name = format!("devil-command-{}", KsuidMs::new(None, None));
+            let dir = std::env::temp_dir().join(name);
+            tokio::fs::create_dir(&dir).await?;
+            Some(dir)
+        }
+    };
+    let mut cmd = tokio::process::Command::new(&plan.program);
+    cmd.args(&plan.args)
+        .env_clear()
+        .envs(&plan.env)
+        .current_dir(plan.dir.as_deref().map_or_else(
+            || {
+                scratch
+                    .clone()
+                    .expect("scratch dir should be created when dir is unset")
+            },
+            Into::into,
+        ))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    // Keep PATH so programs can be found by name, but nothing else from devil's environment.
+    if let Some(path) = std::env::var_os("PATH") {
+        if !plan.env.contains_key("PATH") {
+            cmd.env("PATH", path);
+        }
+    }
+
+    let start = Instant::now();
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| anyhow!("start {}: {e}", plan.program))?;
+    let mut stdin = child.stdin.take().expect("stdin should be piped");
+    let stdout = child.stdout.take().expect("stdout should be piped");
+    let stderr = child.stderr.take().expect("stderr should be piped");
+    let input = plan.stdin.clone();
+    let max = usize::try_from(plan.max_output).unwrap_or(usize::MAX);
+    let timeout = plan.timeout.0.to_std().unwrap_or_default();
+    let result = tokio::time::timeout(timeout, async {
+        let write = async {
+            if let Some(input) = input {
+                // The program may exit without reading everything, which isn't an error.
+                let _ = stdin.write_all(&input).await;
+            }
+            drop(stdin);
+        };
+        let ((), stdout, stderr, status) = tokio::join!(
+            write,
+            read_capped(stdout, max),
+            read_capped(stderr, max),
+            child.wait(),
+        );
+        anyhow::Ok((stdout?, stderr?, status?))
+    })
+    .await;
+    let duration = TimeDelta::from_std(start.elapsed()).unwrap_or(TimeDelta::MAX);
+    if result.is_err() {
+        // Stop the program before removing the directory it runs in.
+        let _ = child.kill().await;
+    }
+    if let Some(dir) = scratch {
+        let _ = tokio::fs::remove_dir_all(dir).await;
+    }
+
+    let out = |stdout: Vec<u8>, stderr: Vec<u8>| {
+        (
+            MaybeUtf8(Bytes::from(stdout).into()),
+            MaybeUtf8(Bytes::from(stderr).into()),
+        )
+    };
+    match result {
+        Ok(result) => {
+            let ((stdout, stdout_truncated), (stderr, stderr_truncated), status) = result?;
+            let (stdout, stderr) = out(stdout, stderr);
+            Ok(CommandOutput {
+                plan,
+                stdout,
+                stderr,
+                exit_code: status.code().map(i64::from),
+                timed_out: false,
+                truncated: stdout_truncated || stderr_truncated,
+                duration: duration.into(),
+            })
+        }
+        Err(_) => {
+            let (stdout, stderr) = out(Vec::new(), Vec::new());
+            Ok(CommandOutput {
+                plan,
+                stdout,
+                stderr,
+                exit_code: None,
+                timed_out: true,
+                truncated: false,
+                duration: duration.into(),
+            })
+        }
+    }
+}
+
+/// Read everything from `r` so the program doesn't block on a full pipe, keeping the first `max`
+/// bytes.
+#[cfg(feature = "command")]
+async fn read_capped(
+    mut r: impl tokio::io::AsyncRead + Unpin,
+    max: usize,
+) -> std::io::Result<(Vec<u8>, bool)> {
+    use tokio::io::AsyncReadExt;
+
+    let mut out = Vec::new();
+    let mut truncated = false;
+    let mut buf = [0; 8192];
+    loop {
+        let n = r.read(&mut buf).await?;
+        if n == 0 {
+            return Ok((out, truncated));
+        }
+        let keep = n.min(max - out.len());
+        out.extend_from_slice(&buf[..keep]);
+        truncated |= keep < n;
+    }
+}
+
+#[cfg(not(feature = "command"))]
+pub(super) async fn command(_: CommandPlanOutput) -> anyhow::Result<CommandOutput> {
+    anyhow::bail!("command steps are disabled, build devil with the command feature to enable them")
+}
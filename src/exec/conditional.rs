@@ -0,0 +1,193 @@
+use std::sync::Arc;
+
+use crate::{
+    ConditionalCaseOutput, ConditionalOutput, ConditionalPlanOutput, IterableKey, JobOutput,
+};
+
+use super::range::{header, request};
+use super::Context;
+
+/// A date before any resource was modified.
+const EPOCH: &str = "Thu, 01 Jan 1970 00:00:00 GMT";
+/// A date after the server's clock, which servers must treat as invalid.
+const FUTURE: &str = "Fri, 31 Dec 9999 23:59:59 GMT";
+const MISMATCH: &str = "\"devil-mismatch\"";
+
+/// GET the resource to capture its validators, then send each conditional variant they allow.
+pub(super) async fn conditional(
+    ctx: &Context,
+    plan: ConditionalPlanOutput,
+) -> (ConditionalOutput, Vec<(IterableKey, Arc<JobOutput>)>) {
+    let mut out = ConditionalOutput {
+        status_code: None,
+        etag: None,
+        last_modified: None,
+        cases: Vec::new(),
+        passed: 0,
+        failed: 0,
+        error: None,
+        plan,
+    };
+    let mut jobs = Vec::new();
+    let job = match request(ctx, &out.plan.url, &[], &mut jobs).await {
+        Ok(job) => job,
+        Err(e) => {
+            out.error = Some(format!("{e:#}"));
+            return (out, jobs);
+        }
+    };
+    out.status_code = job.response_summary().0;
+    out.etag = header(&job, "etag");
+    out.last_modified = header(&job, "last-modified");
+    if out.status_code != Some(200) {
+        out.error = Some("the resource must return 200 without conditions".to_owned());
+        return (out, jobs);
+    }
+    if out.etag.is_none() && out.last_modified.is_none() {
+        out.error = Some("the response has no ETag or Last-Modified validator".to_owned());
+        return (out, jobs);
+    }
+
+    let cases = cases(out.etag.as_deref(), out.last_modified.as_deref());
+    for (name, headers, expected_status) in cases {
+        let mut case = ConditionalCaseOutput {
+            name: name.to_owned(),
+            headers: headers.iter().map(|(k, v)| format!("{k}: {v}")).collect(),
+            expected_status,
+            status_code: None,
+            passed: false,
+            issue: None,
+            error: None,
+        };
+        let headers: Vec<_> = headers.iter().map(|(k, v)| (*k, v.as_str())).collect();
+        match request(ctx, &out.plan.url, &headers, &mut jobs).await {
+            Ok(job) => {
+                let (status_code, body) = job.response_summary();
+                case.status_code = status_code;
+                case.passed = status_code == Some(expected_status);
+                if status_code == Some(304) && !body.is_empty() {
+                    case.passed = false;
+                    case.issue = Some("304 response has a body".to_owned());
+                } else if status_code == Some(304) && header(&job, "etag") != out.etag {
+                    case.issue = Some("304 response has a different ETag".to_owned());
+                }
+            }
+            Err(e) => case.error = Some(format!("{e:#}")),
+        }
+        if case.passed {
+            out.passed += 1;
+        } else {
+            out.failed += 1;
+        }
+        out.cases.push(case);
+    }
+    (out, jobs)
+}
+
+/// The name, headers, and RFC 9110 status of each case the validators allow.
+fn cases(
+    etag: Option<&str>,
+    last_modified: Option<&str>,
+) -> Vec<(&'static str, Vec<(&'static str, String)>, u16)> {
+    let mut cases = Vec::new();
+    let mut case = |name, headers: &[(&'static str, &str)], status| {
+        cases.push((
+            name,
+            headers.iter().map(|(k, v)| (*k, (*v).to_owned())).collect(),
+            status,
+        ));
+    };
+    if let Some(etag) = etag {
+        let weak = etag.starts_with("W/");
+        let strong = etag.trim_start_matches("W/");
+        let weakened = format!("W/{strong}");
+        // Change the last character inside the quotes.
+        let corrupted = match strong.char_indices().rev().nth(1) {
+            Some((i, c)) if strong.len() > 2 => {
+                let replacement = if c == 'x' { 'y' } else { 'x' };
+                format!("{}{replacement}\"", &strong[..i])
+            }
+            _ => MISMATCH.to_owned(),
+        };
+        let list = format!("{MISMATCH}, {etag}");
+
+        case("if_none_match_current", &[("If-None-Match", etag)], 304);
+        case("if_none_match_wildcard", &[("If-None-Match", "*")], 304);
+        case("if_none_match_other", &[("If-None-Match", MISMATCH)], 200);
+        case(
+            "if_none_match_corrupted",
+            &[("If-None-Match", &corrupted)],
+            200,
+        );
+        case("if_none_match_list", &[("If-None-Match", &list)], 304);
+        // If-None-Match uses weak comparison, so the weak form matches a strong ETag too.
+        case("if_none_match_weak", &[("If-None-Match", &weakened)], 304);
+        // If-Match uses strong comparison, which never matches a weak ETag.
+        case(
+            "if_match_current",
+            &[("If-Match", etag)],
+            if weak { 412 } else { 200 },
+        );
+        case("if_match_wildcard", &[("If-Match", "*")], 200);
+        case("if_match_other", &[("If-Match", MISMATCH)], 412);
+        case("if_match_corrupted", &[("If-Match", &corrupted)], 412);
+        if !weak {
+            case("if_match_weak", &[("If-Match", &weakened)], 412);
+        }
+        if let Some(last_modified) = last_modified {
+            // If-Modified-Since is ignored when If-None-Match is present.
+            case(
+                "if_none_match_precedence",
+                &[
+                    ("If-None-Match", MISMATCH),
+                    ("If-Modified-Since", last_modified),
+                ],
+                200,
+            );
+            // If-Unmodified-Since is ignored when If-Match is present.
+            case(
+                "if_match_precedence",
+                &[("If-Match", "*"), ("If-Unmodified-Since", EPOCH)],
+                200,
+            );
+        }
+    }
+    if let Some(last_modified) = last_modified {
+        case(
+            "if_modified_since_current",
+            &[("If-Modified-Since", last_modified)],
+            304,
+        );
+        case(
+            "if_modified_since_epoch",
+            &[("If-Modified-Since", EPOCH)],
+            200,
+        );
+        case(
+            "if_modified_since_future",
+            &[("If-Modified-Since", FUTURE)],
+            200,
+        );
+        case(
+            "if_modified_since_invalid",
+            &[("If-Modified-Since", "yesterday")],
+            200,
+        );
+        case(
+            "if_unmodified_since_current",
+            &[("If-Unmodified-Since", last_modified)],
+            200,
+        );
+        case(
+            "if_unmodified_since_epoch",
+            &[("If-Unmodified-Since", EPOCH)],
+            412,
+        );
+        case(
+            "if_unmodified_since_invalid",
+            &[("If-Unmodified-Since", "yesterday")],
+            200,
+        );
+    }
+    cases
+}
@@ -0,0 +1,88 @@
+use std::io::Read;
+
+use anyhow::{anyhow, bail};
+#[cfg(feature = "native")]
+use flate2::read::{DeflateDecoder, MultiGzDecoder, ZlibDecoder};
+
+use crate::HttpHeader;
+
+/// The most a body may expand to when decoded, so compression bombs can't exhaust memory.
+const MAX_DECODED_LEN: u64 = 64 << 20;
+
+/// Remove the content codings listed by a response's Content-Encoding headers from its body.
+/// Returns the codings as a lowercase, comma separated list along with the decoded body, or None
+/// if the body wasn't encoded.
+pub(super) fn decompress(
+    headers: &[HttpHeader],
+    body: &[u8],
+) -> Option<(String, anyhow::Result<Vec<u8>>)> {
+    let codings: Vec<_> = headers
+        .iter()
+        .filter(|h| {
+            h.key
+                .as_ref()
+                .is_some_and(|k| k.eq_ignore_ascii_case(b"content-encoding"))
+        })
+        .flat_map(|h| {
+            String::from_utf8_lossy(&h.value)
+                .split(',')
+                .map(|coding| coding.trim().to_ascii_lowercase())
+                .collect::<Vec<_>>()
+        })
+        .filter(|coding| !coding.is_empty() && coding != "identity")
+        .collect();
+    if codings.is_empty() {
+        return None;
+    }
+    // Bodyless responses like those to HEAD requests still list the codings a body would have.
+    let decoded = if body.is_empty() {
+        Ok(Vec::new())
+    } else {
+        decode(&codings, body)
+    };
+    Some((codings.join(", "), decoded))
+}
+
+/// Undo codings in the reverse of the order they were applied.
+fn decode(codings: &[String], body: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut body = body.to_vec();
+    for coding in codings.iter().rev() {
+        let reader: Box<dyn Read + '_> = match coding.as_str() {
+            #[cfg(feature = "native")]
+            "gzip" | "x-gzip" => Box::new(MultiGzDecoder::new(body.as_slice())),
+            // Some servers send raw deflate data instead of the zlib format the spec requires.
+            #[cfg(feature = "native")]
+            "deflate" if is_zlib(&body) => Box::new(ZlibDecoder::new(body.as_slice())),
+            #[cfg(feature = "native")]
+            "deflate" => Box::new(DeflateDecoder::new(body.as_slice())),
+            #[cfg(feature = "native")]
+            "br" => Box::new(brotli::Decompressor::new(body.as_slice(), 4096)),
+            #[cfg(feature = "native")]
+            "zstd" => Box::new(zstd::stream::read::Decoder::new(body.as_slice())?),
+            #[cfg(not(feature = "native"))]
+            "gzip" | "x-gzip" | "deflate" | "br" | "zstd" => {
+                bail!("decoding {coding} requires the native feature")
+            }
+            _ => bail!("unsupported content coding {coding:?}"),
+        };
+        let mut decoded = Vec::new();
+        reader
+            .take(MAX_DECODED_LEN + 1)
+            .read_to_end(&mut decoded)
+            .map_err(|e| anyhow!("decode {coding}: {e}"))?;
+        if decoded.len() as u64 > MAX_DECODED_LEN {
+            bail!("{coding} body decodes to more than {MAX_DECODED_LEN} bytes");
+        }
+        body = decoded;
+    }
+    Ok(body)
+}
+
+/// Whether data starts with a zlib header, per RFC 1950 section 2.2.
+#[cfg(feature = "native")]
+fn is_zlib(data: &[u8]) -> bool {
+    match data {
+        [cmf, flg, ..] => cmf & 0x0f == 8 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0,
+        _ => false,
+    }
+}
@@ -0,0 +1,153 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::HttpHeader;
+
+/// Cookies set by responses, sent with later requests. Only virtual users in session steps and
+/// steps tagged with run.auth have a jar, so other steps send exactly the headers in the plan.
+#[derive(Debug, Default)]
+pub(super) struct CookieJar {
+    cookies: Mutex<Vec<Cookie>>,
+    /// Headers sent with every request, like a token from a login.
+    headers: Mutex<Vec<(String, String)>>,
+}
+
+#[derive(Debug, Clone)]
+struct Cookie {
+    name: String,
+    value: String,
+    domain: String,
+    host_only: bool,
+    path: String,
+    secure: bool,
+    expires: Option<Instant>,
+}
+
+impl CookieJar {
+    /// Send a header with every request, replacing any earlier value.
+    pub fn set_header(&self, name: String, value: String) {
+        let mut headers = self.headers.lock().unwrap();
+        headers.retain(|(n, _)| !n.eq_ignore_ascii_case(&name));
+        headers.push((name, value));
+    }
+
+    /// The headers to send with every request.
+    pub fn headers(&self) -> Vec<(String, String)> {
+        self.headers.lock().unwrap().clone()
+    }
+
+    /// The Cookie header value for a request, if any stored cookies apply.
+    pub fn header(&self, url: &url::Url) -> Option<String> {
+        let host = url.host_str()?.to_ascii_lowercase();
+        let now = Instant::now();
+        let mut cookies = self.cookies.lock().unwrap();
+        cookies.retain(|c| c.expires.map_or(true, |expires| expires > now));
+        let value = cookies
+            .iter()
+            .filter(|c| {
+                let domain_matches = if c.host_only {
+                    host == c.domain
+                } else {
+                    host == c.domain || host.ends_with(&format!(".{}", c.domain))
+                };
+                domain_matches
+                    && path_matches(url.path(), &c.path)
+                    && (!c.secure || url.scheme() == "https")
+            })
+            .map(|c| format!("{}={}", c.name, c.value))
+            .collect::<Vec<_>>()
+            .join("; ");
+        (!value.is_empty()).then_some(value)
+    }
+
+    /// Store the cookies from a response's Set-Cookie headers.
+    pub fn store(&self, url: &url::Url, headers: &[HttpHeader]) {
+        let Some(host) = url.host_str().map(str::to_ascii_lowercase) else {
+            return;
+        };
+        let mut cookies = self.cookies.lock().unwrap();
+        for header in headers {
+            if !header
+                .key
+                .as_ref()
+                .is_some_and(|k| k.eq_ignore_ascii_case(b"set-cookie"))
+            {
+                continue;
+            }
+            let Some(cookie) = parse(&String::from_utf8_lossy(&header.value), &host, url.path())
+            else {
+                continue;
+            };
+            cookies.retain(|c| {
+                c.name != cookie.name || c.domain != cookie.domain || c.path != cookie.path
+            });
+            // An expiry in the past is how servers delete cookies.
+            if cookie
+                .expires
+                .map_or(true, |expires| expires > Instant::now())
+            {
+                cookies.push(cookie);
+            }
+        }
+    }
+}
+
+fn parse(header: &str, host: &str, request_path: &str) -> Option<Cookie> {
+    let mut parts = header.split(';');
+    let (name, value) = parts.next()?.split_once('=')?;
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+    let mut cookie = Cookie {
+        name: name.to_owned(),
+        value: value.trim().to_owned(),
+        domain: host.to_owned(),
+        host_only: true,
+        // The default path is the request path's directory.
+        path: match request_path.rfind('/') {
+            Some(0) | None => "/".to_owned(),
+            Some(i) => request_path[..i].to_owned(),
+        },
+        secure: false,
+        expires: None,
+    };
+    for attr in parts {
+        let (key, value) = attr.split_once('=').unwrap_or((attr, ""));
+        let value = value.trim();
+        match key.trim().to_ascii_lowercase().as_str() {
+            "domain" if !value.is_empty() => {
+                let domain = value.trim_start_matches('.').to_ascii_lowercase();
+                // Servers may only set cookies for themselves or a parent domain.
+                if host != domain && !host.ends_with(&format!(".{domain}")) {
+                    return None;
+                }
+                cookie.domain = domain;
+                cookie.host_only = false;
+            }
+            "path" if value.starts_with('/') => cookie.path = value.to_owned(),
+            "secure" => cookie.secure = true,
+            "max-age" => {
+                if let Ok(seconds) = value.parse::<i64>() {
+                    let seconds = u64::try_from(seconds).unwrap_or(0);
+                    cookie.expires = Some(Instant::now() + Duration::from_secs(seconds));
+                }
+            }
+            // Max-Age takes precedence over Expires.
+            "expires" if cookie.expires.is_none() => {
+                if let Ok(date) = chrono::DateTime::parse_from_rfc2822(value) {
+                    let remaining = date.with_timezone(&chrono::Utc) - chrono::Utc::now();
+                    cookie.expires = Some(Instant::now() + remaining.to_std().unwrap_or_default());
+                }
+            }
+            _ => {}
+        }
+    }
+    Some(cookie)
+}
+
+fn path_matches(request_path: &str, cookie_path: &str) -> bool {
+    request_path == cookie_path
+        || request_path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || request_path[cookie_path.len()..].starts_with('/'))
+}
@@ -0,0 +1,268 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, OnceLock};
+
+use anyhow::anyhow;
+use indexmap::IndexMap;
+use scraper::{ElementRef, Html, Selector};
+use url::Url;
+
+use crate::{
+    AddContentLength, CrawlFieldOutput, CrawlFormOutput, CrawlOutput, CrawlPageOutput,
+    CrawlPlanOutput, HttpHeader, HttpPlanOutput, HttpRequest, IterableKey, JobOutput, MaybeUtf8,
+    PlanValue, PlanValueTable, Run, Step, StepPlanOutput, StepProtocols,
+};
+
+use super::runner::Runner;
+use super::{Context, Executor};
+
+/// Breadth first crawl from the plan's seed URL. Returns the discovered surface and the job for
+/// each fetched page.
+pub(super) async fn crawl(
+    ctx: &Context,
+    plan: CrawlPlanOutput,
+) -> (CrawlOutput, Vec<(IterableKey, Arc<JobOutput>)>) {
+    let mut out = CrawlOutput {
+        pages: Vec::new(),
+        forms: Vec::new(),
+        form_steps: Vec::new(),
+        plan,
+    };
+    let mut jobs = Vec::new();
+    let mut seen = HashSet::from([out.plan.url.clone()]);
+    let mut seen_forms = HashSet::new();
+    let mut queue = VecDeque::from([(out.plan.url.clone(), 0)]);
+
+    while let Some((url, depth)) = queue.pop_front() {
+        if u64::try_from(out.pages.len()).unwrap_or(u64::MAX) >= out.plan.max_pages {
+            break;
+        }
+        let key = IterableKey::Uint(out.pages.len().try_into().unwrap_or(u64::MAX));
+        let mut job_name = ctx.job_name.clone();
+        job_name.job = key.clone();
+        let job = match fetch(ctx, url.clone(), job_name).await {
+            Ok(job) => job,
+            Err(e) => {
+                tracing::debug!(%url, ?e, "crawl fetch failed");
+                continue;
+            }
+        };
+        let response = job.http.as_ref().and_then(|h| h.response.clone());
+        let mut page = CrawlPageOutput {
+            url: url.clone(),
+            depth,
+            status_code: response.as_ref().and_then(|r| r.status_code),
+            links: Vec::new(),
+        };
+        if let Some(response) = response {
+            let text = response.text.clone().unwrap_or_else(|| {
+                String::from_utf8_lossy(response.body.as_deref().unwrap_or_default()).into_owned()
+            });
+            let html = Html::parse_document(&text);
+            page.links = links(&html, &url);
+            for form in forms(&html, &url) {
+                let id = (
+                    form.action.clone(),
+                    form.method.clone(),
+                    form.fields
+                        .iter()
+                        .map(|f| f.name.clone())
+                        .collect::<Vec<_>>(),
+                );
+                if seen_forms.insert(id) {
+                    out.forms.push(form);
+                }
+            }
+        }
+        if depth < out.plan.max_depth {
+            for link in &page.links {
+                if link.as_str().starts_with(&out.plan.scope) && seen.insert(link.clone()) {
+                    queue.push_back((link.clone(), depth + 1));
+                }
+            }
+        }
+        out.pages.push(page);
+        jobs.push((key, Arc::new(job)));
+    }
+    (out, jobs)
+}
+
+pub(super) async fn fetch(
+    ctx: &Context,
+    url: Url,
+    job_name: crate::JobName,
+) -> anyhow::Result<JobOutput> {
+    let ctx = Arc::new(ctx.for_job(job_name.clone()));
+    let authority = authority(&url)?;
+    let mut runner = Runner::new(
+        ctx,
+        StepPlanOutput::Http(HttpPlanOutput {
+            method: Some(MaybeUtf8("GET".into())),
+            add_content_length: AddContentLength::Auto,
+            headers: vec![HttpHeader {
+                key: Some(MaybeUtf8("Host".into())),
+                value: MaybeUtf8(authority.into()),
+            }],
+            body: MaybeUtf8::default(),
+            url,
+            http3: false,
+            similarity: Vec::new(),
+        }),
+        true,
+    )?;
+    let hint = runner.executor_size_hint();
+    runner.size_hint(hint);
+    let runner = Executor::start_runners(None, vec![runner], 1)
+        .await?
+        .expect("any stack should have at least one protocol");
+    Ok(Executor::iteration(runner, None, job_name).await?.0)
+}
+
+/// The Host header value for a URL.
+pub(super) fn authority(url: &Url) -> anyhow::Result<String> {
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow!("url {url} is missing host"))?;
+    Ok(match url.port() {
+        Some(port) => format!("{host}:{port}"),
+        None => host.to_owned(),
+    })
+}
+
+fn selector(cell: &'static OnceLock<Selector>, css: &str) -> &'static Selector {
+    cell.get_or_init(|| Selector::parse(css).expect("crawl selectors should be valid"))
+}
+
+/// All http(s) URLs linked from a page, without fragments.
+fn links(html: &Html, base: &Url) -> Vec<Url> {
+    static LINKS: OnceLock<Selector> = OnceLock::new();
+    let links = selector(&LINKS, "a[href], area[href], iframe[src], frame[src]");
+    let mut found: Vec<Url> = Vec::new();
+    for element in html.select(links) {
+        let Some(href) = element
+            .value()
+            .attr("href")
+            .or_else(|| element.value().attr("src"))
+        else {
+            continue;
+        };
+        let Ok(mut url) = base.join(href) else {
+            continue;
+        };
+        if !matches!(url.scheme(), "http" | "https") {
+            continue;
+        }
+        url.set_fragment(None);
+        if !found.contains(&url) {
+            found.push(url);
+        }
+    }
+    found
+}
+
+fn forms(html: &Html, page: &Url) -> Vec<CrawlFormOutput> {
+    static FORMS: OnceLock<Selector> = OnceLock::new();
+    static FIELDS: OnceLock<Selector> = OnceLock::new();
+    let fields = selector(
+        &FIELDS,
+        "input[name], textarea[name], select[name], button[name]",
+    );
+    html.select(selector(&FORMS, "form"))
+        .filter_map(|form| {
+            let action = match form.value().attr("action") {
+                Some(action) if !action.is_empty() => page.join(action).ok()?,
+                _ => page.clone(),
+            };
+            Some(CrawlFormOutput {
+                page: page.clone(),
+                action,
+                method: form
+                    .value()
+                    .attr("method")
+                    .unwrap_or("GET")
+                    .to_ascii_uppercase(),
+                enctype: form.value().attr("enctype").map(str::to_owned),
+                fields: form.select(fields).map(field).collect(),
+            })
+        })
+        .collect()
+}
+
+fn field(element: ElementRef) -> CrawlFieldOutput {
+    static OPTIONS: OnceLock<Selector> = OnceLock::new();
+    let el = element.value();
+    let kind = match el.name() {
+        "input" => el.attr("type").unwrap_or("text").to_ascii_lowercase(),
+        name => name.to_owned(),
+    };
+    let value = match el.name() {
+        "textarea" => Some(element.text().collect()),
+        "select" => {
+            let options: Vec<_> = element.select(selector(&OPTIONS, "option")).collect();
+            options
+                .iter()
+                .find(|o| o.value().attr("selected").is_some())
+                .or(options.first())
+                .map(|o| {
+                    o.value()
+                        .attr("value")
+                        .map(str::to_owned)
+                        .unwrap_or_else(|| o.text().collect())
+                })
+        }
+        _ => el.attr("value").map(str::to_owned),
+    };
+    CrawlFieldOutput {
+        name: el.attr("name").unwrap_or_default().to_owned(),
+        kind,
+        value,
+    }
+}
+
+/// Build a step submitting a form with its default values, as a starting point for fuzzing.
+pub(super) fn form_step(form: &CrawlFormOutput) -> Step {
+    let pairs = form
+        .fields
+        .iter()
+        // Only the clicked submit button is sent, so leave them all out.
+        .filter(|f| !matches!(f.kind.as_str(), "submit" | "button" | "image" | "reset"))
+        .map(|f| (f.name.as_str(), f.value.as_deref().unwrap_or_default()));
+    let encoded = form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(pairs)
+        .finish();
+    let mut url = form.action.clone();
+    let mut headers = Vec::new();
+    if let Ok(authority) = authority(&url) {
+        headers.push((
+            PlanValue::Literal(MaybeUtf8("Host".into())),
+            PlanValue::Literal(MaybeUtf8(authority.into())),
+        ));
+    }
+    let body = if form.method == "GET" {
+        url.set_query(Some(&encoded));
+        None
+    } else {
+        headers.push((
+            PlanValue::Literal(MaybeUtf8("Content-Type".into())),
+            PlanValue::Literal(MaybeUtf8("application/x-www-form-urlencoded".into())),
+        ));
+        Some(MaybeUtf8(encoded.into()))
+    };
+    Step {
+        protocols: StepProtocols::Http {
+            http: HttpRequest {
+                url: PlanValue::Literal(url),
+                method: PlanValue::Literal(Some(MaybeUtf8(form.method.clone().into()))),
+                headers: PlanValueTable(headers),
+                add_content_length: PlanValue::Literal(AddContentLength::Auto),
+                body: PlanValue::Literal(body),
+                http3: PlanValue::Literal(false),
+                similarity: Vec::new(),
+            },
+        },
+        run: Run::default(),
+        destructive: false,
+        sync: IndexMap::new(),
+        pause: IndexMap::new(),
+        signal: IndexMap::new(),
+    }
+}
@@ -0,0 +1,157 @@
+//! Flags signs that a reused HTTP/1 connection fell out of step with its requests, as happens when
+//! a front end and back end disagree on where a request ends. Each job's response is compared to
+//! the framing its own headers declare, and to the timing of the step's other jobs.
+
+use chrono::TimeDelta;
+
+use crate::{DesyncKind, DesyncOutput, HttpHeader, JobOutput, StepOutput};
+
+/// Responses whose first byte arrived this many times sooner than the step's median were likely
+/// already waiting on the connection.
+const EARLY_FACTOR: i32 = 10;
+/// The fewest responses with timing for a median to be meaningful.
+const MIN_TIMED: usize = 3;
+
+/// Add desync indicators for the step's jobs, which shared a connection in order, to its output.
+pub(super) fn detect(output: &mut StepOutput) {
+    let exchanges: Vec<_> = output
+        .jobs
+        .iter()
+        .filter_map(|(key, job)| Some((key.to_string(), exchange(job)?)))
+        .collect();
+    let mut timings: Vec<_> = exchanges
+        .iter()
+        .filter_map(|(_, exchange)| exchange.response.as_ref()?.time_to_first_byte)
+        .collect();
+    timings.sort();
+    let median = timings
+        .get(timings.len() / 2)
+        .filter(|_| timings.len() >= MIN_TIMED)
+        .copied();
+
+    let mut unanswered = false;
+    for (job, exchange) in exchanges {
+        let Some(response) = &exchange.response else {
+            unanswered = exchange.requested;
+            continue;
+        };
+        let finding = |kind, detail: &str| DesyncOutput {
+            job: job.clone(),
+            kind,
+            detail: detail.to_owned(),
+            extra_bytes: None,
+            time_to_first_byte: None,
+            median_time_to_first_byte: None,
+        };
+        let mut found = Vec::new();
+        if let Some(extra) = overflow(response) {
+            let (kind, detail) = if extra.starts_with(b"HTTP/1.") {
+                (
+                    DesyncKind::DoubleResponse,
+                    "another response followed the body",
+                )
+            } else {
+                (
+                    DesyncKind::EarlyBytes,
+                    "bytes followed the body's declared end",
+                )
+            };
+            found.push(DesyncOutput {
+                extra_bytes: Some(extra.len() as u64),
+                ..finding(kind, detail)
+            });
+        }
+        if let (Some(ttfb), Some(median)) = (response.time_to_first_byte, median) {
+            if ttfb * EARLY_FACTOR < median {
+                found.push(DesyncOutput {
+                    time_to_first_byte: Some(ttfb.into()),
+                    median_time_to_first_byte: Some(median.into()),
+                    ..finding(
+                        DesyncKind::EarlyBytes,
+                        "the response arrived as soon as it was read",
+                    )
+                });
+            }
+        }
+        if unanswered && !found.is_empty() {
+            found.push(finding(
+                DesyncKind::TimeoutThenBurst,
+                "the previous request got no response",
+            ));
+        }
+        unanswered = false;
+        output.desync.extend(found);
+    }
+}
+
+/// The parts of a job's HTTP/1 exchange that framing and timing are checked in.
+struct Exchange<'a> {
+    requested: bool,
+    response: Option<Response<'a>>,
+}
+
+struct Response<'a> {
+    headers: &'a [HttpHeader],
+    body: &'a [u8],
+    time_to_first_byte: Option<TimeDelta>,
+}
+
+fn exchange(job: &JobOutput) -> Option<Exchange<'_>> {
+    macro_rules! http {
+        ($output:expr) => {
+            if let Some(output) = $output {
+                return Some(Exchange {
+                    requested: output.request.is_some(),
+                    response: output.response.as_ref().map(|response| Response {
+                        headers: response.headers.as_deref().unwrap_or_default(),
+                        // Framing is checked against the bytes as read.
+                        body: response
+                            .raw_body
+                            .as_ref()
+                            .or(response.body.as_ref())
+                            .map(|body| body.as_bytes())
+                            .unwrap_or_default(),
+                        time_to_first_byte: response.time_to_first_byte.as_ref().map(|d| d.0),
+                    }),
+                });
+            }
+        };
+    }
+    http!(job
+        .http
+        .as_ref()
+        .filter(|http| http.protocol.as_deref() == Some("HTTP/1.1")));
+    http!(job.http1());
+    None
+}
+
+/// The bytes read past the end of the body as declared by Transfer-Encoding or Content-Length,
+/// not counting blank lines which servers may send between responses.
+fn overflow<'a>(response: &Response<'a>) -> Option<&'a [u8]> {
+    let chunked = response.headers.iter().any(|h| {
+        named(h, "transfer-encoding")
+            && String::from_utf8_lossy(&h.value)
+                .to_ascii_lowercase()
+                .contains("chunked")
+    });
+    let end = if chunked {
+        super::http1::decode_chunked(response.body).1?
+    } else {
+        let length = response
+            .headers
+            .iter()
+            .filter(|h| named(h, "content-length"))
+            .find_map(|h| atoi::atoi::<usize>(&h.value))?;
+        length.min(response.body.len())
+    };
+    let extra = &response.body[end..];
+    let start = extra.iter().position(|b| !matches!(b, b'\r' | b'\n'))?;
+    Some(&extra[start..])
+}
+
+fn named(header: &HttpHeader, name: &str) -> bool {
+    header
+        .key
+        .as_ref()
+        .is_some_and(|k| k.eq_ignore_ascii_case(name.as_bytes()))
+}
@@ -0,0 +1,196 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use bytes::Bytes;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use url::Url;
+
+use crate::{DetectOutput, DetectPlanOutput, IterableKey, JobOutput, MaybeUtf8};
+
+use super::alpn_matrix::{self, Layer};
+use super::{grpc_reflect, permissive, Context};
+
+/// The most bytes of a banner or reply to keep.
+const MAX_BYTES: usize = 1024;
+
+/// Identify what a port speaks. A server that sends something first is named by its banner,
+/// since TLS and HTTP servers always wait for the client. Otherwise a TLS handshake is tried,
+/// followed by an HTTP request over it, and failing that a plaintext HTTP request.
+pub(super) async fn detect(
+    ctx: &Context,
+    plan: DetectPlanOutput,
+) -> (DetectOutput, Vec<(IterableKey, Arc<JobOutput>)>) {
+    let mut out = DetectOutput {
+        verdict: "closed".to_owned(),
+        open: false,
+        banner: None,
+        tls: false,
+        tls_version: None,
+        alpn: None,
+        status_code: None,
+        reply: None,
+        errors: Vec::new(),
+        plan,
+    };
+    let mut jobs = Vec::new();
+    let timeout = out.plan.timeout.0.to_std().unwrap_or_default();
+    let wait = out.plan.wait.0.to_std().unwrap_or_default();
+
+    let banner = match exchange(ctx, &out.plan, None, wait, timeout).await {
+        Ok(banner) => banner,
+        Err(e) => {
+            out.errors.push(format!("{e:#}"));
+            return (out, jobs);
+        }
+    };
+    out.open = true;
+    if !banner.is_empty() {
+        out.verdict = classify(&banner).unwrap_or("tcp").to_owned();
+        out.banner = Some(MaybeUtf8(Bytes::from(banner).into()));
+        return (out, jobs);
+    }
+
+    match tls(ctx, &mut out, &mut jobs).await {
+        Ok(true) => return (out, jobs),
+        Ok(false) => {}
+        Err(e) => out.errors.push(format!("tls: {e:#}")),
+    }
+
+    let request = format!(
+        "GET / HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+        authority(&out.plan),
+    );
+    let reply = match exchange(ctx, &out.plan, Some(request.as_bytes()), timeout, timeout).await {
+        Ok(reply) => reply,
+        Err(e) => {
+            out.errors.push(format!("{e:#}"));
+            Vec::new()
+        }
+    };
+    out.verdict = classify(&reply).unwrap_or("tcp").to_owned();
+    if out.verdict == "h1c" {
+        out.status_code = permissive::parse(&reply).status_code;
+    }
+    if !reply.is_empty() {
+        out.reply = Some(MaybeUtf8(Bytes::from(reply).into()));
+    }
+    (out, jobs)
+}
+
+/// Try a TLS handshake, then request / with the negotiated protocol. Returns whether the
+/// handshake completed, in which case the verdict is set.
+async fn tls(
+    ctx: &Context,
+    out: &mut DetectOutput,
+    jobs: &mut Vec<(IterableKey, Arc<JobOutput>)>,
+) -> anyhow::Result<bool> {
+    let url = Url::parse(&format!("https://{}/", authority(&out.plan)))?;
+    let timeout = out.plan.timeout.0.to_std().unwrap_or_default();
+    let alpn = &out.plan.alpn;
+    let handshake = tokio::time::timeout(
+        timeout,
+        alpn_matrix::fetch(ctx, &url, alpn, Layer::Handshake, true, jobs),
+    )
+    .await
+    .map_err(|_| anyhow!("timed out"))??;
+    let Some(tls) = handshake.tls.as_ref().filter(|tls| tls.version.is_some()) else {
+        if let Some(e) = handshake.tls.as_ref().and_then(|tls| tls.errors.first()) {
+            out.errors.push(format!("tls: {}", e.message));
+        }
+        return Ok(false);
+    };
+    out.tls = true;
+    out.tls_version = tls.version.clone();
+    out.alpn = tls.alpn.as_ref().map(ToString::to_string);
+    out.verdict = "tls".to_owned();
+
+    let (layer, verdict) = match out.alpn.as_deref() {
+        Some("h2") => (Layer::Http2, "h2"),
+        // Servers that don't select a protocol may still speak HTTP/1.1.
+        Some("http/1.1") | None => (Layer::Http1, "h1"),
+        Some(_) => return Ok(true),
+    };
+    let alpn = &out.plan.alpn;
+    let response = tokio::time::timeout(
+        timeout,
+        alpn_matrix::fetch(ctx, &url, alpn, layer, true, jobs),
+    )
+    .await;
+    match response {
+        Ok(Ok(job)) => {
+            out.status_code = job.response_summary().0;
+            if out.status_code.is_some() {
+                out.verdict = verdict.to_owned();
+            }
+        }
+        Ok(Err(e)) => out.errors.push(format!("{verdict}: {e:#}")),
+        Err(_) => out.errors.push(format!("{verdict}: timed out")),
+    }
+    Ok(true)
+}
+
+/// Connect, send `request` if there is one, and read what comes back until the server closes
+/// the connection, MAX_BYTES have been read, or nothing more arrives within `wait`.
+async fn exchange(
+    ctx: &Context,
+    plan: &DetectPlanOutput,
+    request: Option<&[u8]>,
+    wait: Duration,
+    timeout: Duration,
+) -> anyhow::Result<Vec<u8>> {
+    let mut stream =
+        tokio::time::timeout(timeout, grpc_reflect::connect(ctx, &plan.host, plan.port))
+            .await
+            .map_err(|_| anyhow!("connect timed out"))??;
+    if let Some(request) = request {
+        stream.write_all(request).await?;
+        stream.flush().await?;
+    }
+    let mut received = Vec::new();
+    let mut buf = [0; MAX_BYTES];
+    while received.len() < MAX_BYTES {
+        match tokio::time::timeout(wait, stream.read(&mut buf)).await {
+            Ok(Ok(0)) | Err(_) => break,
+            Ok(Ok(n)) => received.extend_from_slice(&buf[..n]),
+            // A reset after some data still leaves something to classify.
+            Ok(Err(_)) if !received.is_empty() => break,
+            Ok(Err(e)) => return Err(e.into()),
+        }
+    }
+    received.truncate(MAX_BYTES);
+    Ok(received)
+}
+
+/// Name the protocol a banner or reply came from, if it's recognized.
+fn classify(data: &[u8]) -> Option<&'static str> {
+    let upper = String::from_utf8_lossy(&data[..data.len().min(256)]).to_ascii_uppercase();
+    if data.starts_with(b"HTTP/") {
+        Some("h1c")
+    } else if data.starts_with(b"SSH-") {
+        Some("ssh")
+    } else if data.starts_with(b"220") && upper.contains("FTP") {
+        Some("ftp")
+    } else if data.starts_with(b"220") {
+        Some("smtp")
+    } else if data.starts_with(b"+OK") {
+        Some("pop3")
+    } else if data.starts_with(b"* OK") {
+        Some("imap")
+    } else if data.starts_with(b"-ERR") || data.starts_with(b"-NOAUTH") {
+        Some("redis")
+    // A MySQL handshake packet: a 3 byte length, sequence number 0, then protocol version 10.
+    } else if data.len() > 5 && data[3] == 0 && data[4] == 10 {
+        Some("mysql")
+    } else {
+        None
+    }
+}
+
+fn authority(plan: &DetectPlanOutput) -> String {
+    if plan.host.contains(':') {
+        format!("[{}]:{}", plan.host, plan.port)
+    } else {
+        format!("{}:{}", plan.host, plan.port)
+    }
+}
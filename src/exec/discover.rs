@@ -0,0 +1,181 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, OnceLock};
+
+use regex::Regex;
+use url::Url;
+
+use crate::{DiscoverOutput, DiscoverPlanOutput, IterableKey, JobOutput};
+
+use super::crawl::fetch;
+use super::Context;
+
+/// Fetch the site's robots.txt and sitemaps, collecting the paths they list. Returns the
+/// discovered paths and the job for each document fetched.
+pub(super) async fn discover(
+    ctx: &Context,
+    plan: DiscoverPlanOutput,
+) -> (DiscoverOutput, Vec<(IterableKey, Arc<JobOutput>)>) {
+    let mut out = DiscoverOutput {
+        robots_paths: Vec::new(),
+        sitemaps: Vec::new(),
+        urls: Vec::new(),
+        paths: Vec::new(),
+        plan,
+    };
+    let mut jobs = Vec::new();
+    let mut paths = HashSet::new();
+    let mut add_path = |out: &mut DiscoverOutput, path: String| {
+        if paths.insert(path.clone()) {
+            out.paths.push(path);
+        }
+    };
+    let origin = out
+        .plan
+        .url
+        .join("/")
+        .expect("root path should always be valid");
+    let mut sitemaps = VecDeque::new();
+
+    if out.plan.robots {
+        let url = origin
+            .join("robots.txt")
+            .expect("robots path should always be valid");
+        if let Some(body) = get(ctx, url, &mut jobs).await {
+            let robots = parse_robots(&body, &origin);
+            for path in robots.paths {
+                out.robots_paths.push(path.clone());
+                add_path(&mut out, path);
+            }
+            sitemaps.extend(robots.sitemaps);
+        }
+    }
+    if out.plan.sitemaps {
+        // Most sites that have a sitemap put it here even if robots.txt doesn't say so.
+        let default = origin
+            .join("sitemap.xml")
+            .expect("sitemap path should always be valid");
+        if !sitemaps.contains(&default) {
+            sitemaps.push_back(default);
+        }
+    } else {
+        sitemaps.clear();
+    }
+
+    let mut seen = HashSet::new();
+    while let Some(url) = sitemaps.pop_front() {
+        if u64::try_from(out.sitemaps.len()).unwrap_or(u64::MAX) >= out.plan.max_sitemaps {
+            break;
+        }
+        if !seen.insert(url.clone()) {
+            continue;
+        }
+        let Some(body) = get(ctx, url.clone(), &mut jobs).await else {
+            continue;
+        };
+        out.sitemaps.push(url);
+        let (index, locs) = parse_sitemap(&body);
+        for loc in locs {
+            let Ok(loc) = origin.join(&loc) else {
+                continue;
+            };
+            if index {
+                sitemaps.push_back(loc);
+                continue;
+            }
+            if loc.origin() == origin.origin() {
+                let mut path = loc.path().to_owned();
+                if let Some(query) = loc.query() {
+                    path = path + "?" + query;
+                }
+                add_path(&mut out, path);
+            }
+            if !out.urls.contains(&loc) {
+                out.urls.push(loc);
+            }
+        }
+    }
+    (out, jobs)
+}
+
+/// Fetch a document, returning its body if the server responded successfully.
+async fn get(
+    ctx: &Context,
+    url: Url,
+    jobs: &mut Vec<(IterableKey, Arc<JobOutput>)>,
+) -> Option<String> {
+    let key = IterableKey::Uint(jobs.len().try_into().unwrap_or(u64::MAX));
+    let mut job_name = ctx.job_name.clone();
+    job_name.job = key.clone();
+    let job = match fetch(ctx, url.clone(), job_name).await {
+        Ok(job) => job,
+        Err(e) => {
+            tracing::debug!(%url, ?e, "discover fetch failed");
+            return None;
+        }
+    };
+    let response = job.http.as_ref().and_then(|h| h.response.clone());
+    jobs.push((key, Arc::new(job)));
+    let response = response?;
+    if !matches!(response.status_code, Some(200..=299)) {
+        return None;
+    }
+    Some(response.text.clone().unwrap_or_else(|| {
+        String::from_utf8_lossy(response.body.as_deref().unwrap_or_default()).into_owned()
+    }))
+}
+
+struct Robots {
+    paths: Vec<String>,
+    sitemaps: Vec<Url>,
+}
+
+fn parse_robots(body: &str, origin: &Url) -> Robots {
+    let mut robots = Robots {
+        paths: Vec::new(),
+        sitemaps: Vec::new(),
+    };
+    for line in body.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim().to_ascii_lowercase().as_str() {
+            "allow" | "disallow" => {
+                // Wildcards match anything, so keep only the literal prefix.
+                let path = value.split('*').next().unwrap_or_default();
+                let path = path.strip_suffix('$').unwrap_or(path);
+                if path.starts_with('/') && !robots.paths.iter().any(|p| p == path) {
+                    robots.paths.push(path.to_owned());
+                }
+            }
+            "sitemap" => {
+                if let Ok(url) = origin.join(value) {
+                    robots.sitemaps.push(url);
+                }
+            }
+            _ => {}
+        }
+    }
+    robots
+}
+
+/// Returns whether the document is a sitemap index and the locations it lists.
+fn parse_sitemap(body: &str) -> (bool, Vec<String>) {
+    static LOC: OnceLock<Regex> = OnceLock::new();
+    let loc = LOC.get_or_init(|| {
+        Regex::new(r"(?is)<loc>\s*(.*?)\s*</loc>").expect("sitemap regex should be valid")
+    });
+    let index = body.contains("<sitemapindex");
+    let locs = loc
+        .captures_iter(body)
+        .map(|c| {
+            c[1].replace("&amp;", "&")
+                .replace("&lt;", "<")
+                .replace("&gt;", ">")
+                .replace("&quot;", "\"")
+                .replace("&apos;", "'")
+        })
+        .collect();
+    (index, locs)
+}
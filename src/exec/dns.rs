@@ -0,0 +1,591 @@
+use std::fmt::Write;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail};
+use bytes::Bytes;
+use chrono::TimeDelta;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::{DnsOutput, DnsPlanOutput, DnsRecordOutput, DnsTransport, MaybeUtf8};
+
+use super::Context;
+
+pub(super) const TYPE_A: u16 = 1;
+pub(super) const TYPE_NS: u16 = 2;
+pub(super) const TYPE_CNAME: u16 = 5;
+pub(super) const TYPE_PTR: u16 = 12;
+pub(super) const TYPE_MX: u16 = 15;
+pub(super) const TYPE_TXT: u16 = 16;
+pub(super) const TYPE_AAAA: u16 = 28;
+pub(super) const TYPE_SRV: u16 = 33;
+
+pub(super) const RCODE_NXDOMAIN: u8 = 3;
+
+/// The resolver from /etc/resolv.conf, falling back to a public resolver if there isn't one.
+pub(super) fn system_resolver() -> SocketAddr {
+    std::fs::read_to_string("/etc/resolv.conf")
+        .ok()
+        .and_then(|conf| {
+            conf.lines().find_map(|line| {
+                let addr = line.trim().strip_prefix("nameserver")?.trim();
+                addr.parse::<IpAddr>().ok()
+            })
+        })
+        .map_or_else(
+            || SocketAddr::new(IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1)), 53),
+            |ip| SocketAddr::new(ip, 53),
+        )
+}
+
+/// Resolve a host to socket addresses, using the step's egress resolver if it has one and the
+/// socket provider's otherwise.
+pub(super) async fn lookup(
+    ctx: &Context,
+    host: &str,
+    port: u16,
+) -> anyhow::Result<Vec<SocketAddr>> {
+    let trusted = match &ctx.scope {
+        Some(scope) => scope.check_host(host)?,
+        None => false,
+    };
+    let addrs = resolve(ctx, host, port).await?;
+    if let Some(scope) = ctx.scope.as_ref().filter(|_| trusted) {
+        scope.trust(host, &addrs);
+    }
+    Ok(addrs)
+}
+
+async fn resolve(ctx: &Context, host: &str, port: u16) -> anyhow::Result<Vec<SocketAddr>> {
+    let bare = host.trim_start_matches('[').trim_end_matches(']');
+    if let Ok(ip) = bare.parse::<IpAddr>() {
+        return Ok(vec![SocketAddr::new(ip, port)]);
+    }
+    let Some(server) = ctx.egress.as_ref().and_then(|egress| egress.resolver) else {
+        return ctx
+            .sockets
+            .lookup_host(host.to_owned(), port)
+            .await
+            .map_err(|e| anyhow!("lookup host '{host}:{port}': {e}"));
+    };
+    let mut addrs = Vec::new();
+    for qtype in [TYPE_A, TYPE_AAAA] {
+        let response = query(ctx, server, host, qtype, Duration::from_secs(5)).await?;
+        addrs.extend(
+            response
+                .answers
+                .into_iter()
+                .filter_map(|record| match record.data {
+                    RecordData::Address(ip) => Some(SocketAddr::new(ip, port)),
+                    _ => None,
+                }),
+        );
+        if !addrs.is_empty() {
+            break;
+        }
+    }
+    Ok(addrs)
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct Response {
+    pub rcode: u8,
+    pub authoritative: bool,
+    pub truncated: bool,
+    pub recursion_available: bool,
+    pub answers: Vec<Record>,
+    pub authority: Vec<Record>,
+    pub additional: Vec<Record>,
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct Record {
+    pub name: String,
+    pub kind: u16,
+    pub class: u16,
+    pub ttl: u32,
+    pub rdata: Vec<u8>,
+    pub data: RecordData,
+}
+
+#[derive(Debug, Clone)]
+pub(super) enum RecordData {
+    Cname(String),
+    Address(IpAddr),
+    /// A single name, as in NS and PTR records.
+    Name(String),
+    Mx {
+        preference: u16,
+        exchange: String,
+    },
+    Txt(Vec<Vec<u8>>),
+    Srv {
+        priority: u16,
+        weight: u16,
+        port: u16,
+        target: String,
+    },
+    Other(u16),
+}
+
+/// Send a recursive query to the resolver over TCP, which works with any socket provider.
+pub(super) async fn query(
+    ctx: &Context,
+    server: SocketAddr,
+    name: &str,
+    qtype: u16,
+    timeout: Duration,
+) -> anyhow::Result<Response> {
+    let id: u16 = rand::random();
+    let message = encode_query(id, name, qtype, true)?;
+    let reply = tokio::time::timeout(timeout, exchange_tcp(ctx, server, &message))
+        .await
+        .map_err(|_| anyhow!("dns query for {name} timed out"))??;
+    parse(&reply, id)
+}
+
+/// Send the planned query and record the raw exchange and parsed reply.
+///
+/// Both transports go through the socket provider, but network profiles only apply over TCP.
+/// Truncated UDP replies aren't retried over TCP.
+pub(super) async fn dns(ctx: &Context, plan: DnsPlanOutput) -> DnsOutput {
+    let start = Instant::now();
+    let mut out = DnsOutput {
+        server: None,
+        query: None,
+        reply: None,
+        rcode: None,
+        authoritative: false,
+        truncated: false,
+        recursion_available: false,
+        answers: Vec::new(),
+        authority: Vec::new(),
+        additional: Vec::new(),
+        latency: None,
+        error: None,
+        duration: TimeDelta::zero().into(),
+        plan,
+    };
+    if let Err(e) = run(ctx, &mut out).await {
+        out.error = Some(format!("{e:#}"));
+    }
+    out.duration = to_duration(start.elapsed());
+    out
+}
+
+async fn run(ctx: &Context, out: &mut DnsOutput) -> anyhow::Result<()> {
+    let server = match &out.plan.server {
+        Some(server) => resolve_server(ctx, server).await?,
+        None => ctx
+            .egress
+            .as_ref()
+            .and_then(|egress| egress.resolver)
+            .unwrap_or_else(system_resolver),
+    };
+    out.server = Some(server.to_string());
+
+    let id: u16 = rand::random();
+    let message = encode_query(
+        id,
+        &out.plan.name,
+        out.plan.record_type.raw,
+        out.plan.recursion_desired,
+    )?;
+    out.query = Some(MaybeUtf8(Bytes::copy_from_slice(&message).into()));
+
+    let timeout = out.plan.timeout.0.to_std().unwrap_or_default();
+    let transport = out.plan.transport;
+    let sent = Instant::now();
+    let exchange = async {
+        match transport {
+            DnsTransport::Udp => exchange_udp(ctx, server, &message).await,
+            DnsTransport::Tcp => exchange_tcp(ctx, server, &message).await,
+        }
+    };
+    let reply = tokio::time::timeout(timeout, exchange)
+        .await
+        .map_err(|_| anyhow!("dns query for {} timed out", out.plan.name))??;
+    out.latency = Some(to_duration(sent.elapsed()));
+    out.reply = Some(MaybeUtf8(Bytes::copy_from_slice(&reply).into()));
+
+    let response = parse(&reply, id)?;
+    out.rcode = Some(response.rcode);
+    out.authoritative = response.authoritative;
+    out.truncated = response.truncated;
+    out.recursion_available = response.recursion_available;
+    out.answers = response.answers.into_iter().map(record_output).collect();
+    out.authority = response.authority.into_iter().map(record_output).collect();
+    out.additional = response.additional.into_iter().map(record_output).collect();
+    Ok(())
+}
+
+/// Parse a server given as an IP address with an optional port, or a host name to look up.
+async fn resolve_server(ctx: &Context, server: &str) -> anyhow::Result<SocketAddr> {
+    if let Ok(addr) = server.parse::<SocketAddr>() {
+        return Ok(addr);
+    }
+    if let Ok(ip) = server.parse::<IpAddr>() {
+        return Ok(SocketAddr::new(ip, 53));
+    }
+    let (host, port) = server.rsplit_once(':').unwrap_or((server, "53"));
+    let port = port
+        .parse()
+        .map_err(|_| anyhow!("invalid port in dns.server '{server}'"))?;
+    lookup(ctx, host, port)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("no addresses found for dns.server '{server}'"))
+}
+
+fn encode_query(
+    id: u16,
+    name: &str,
+    qtype: u16,
+    recursion_desired: bool,
+) -> anyhow::Result<Vec<u8>> {
+    let mut message = Vec::with_capacity(name.len() + 18);
+    message.extend_from_slice(&id.to_be_bytes());
+    // Optionally recursion desired, one question.
+    message.extend_from_slice(&[u8::from(recursion_desired), 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    // The root name is encoded as just the terminating empty label.
+    for label in name
+        .trim_end_matches('.')
+        .split('.')
+        .filter(|l| !l.is_empty())
+    {
+        message.push(u8::try_from(label.len()).map_err(|_| anyhow!("dns label too long"))?);
+        message.extend_from_slice(label.as_bytes());
+    }
+    message.push(0);
+    message.extend_from_slice(&qtype.to_be_bytes());
+    // Class IN.
+    message.extend_from_slice(&1u16.to_be_bytes());
+    Ok(message)
+}
+
+fn unspecified(server: SocketAddr) -> SocketAddr {
+    if server.is_ipv4() {
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0)
+    } else {
+        SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0)
+    }
+}
+
+async fn exchange_tcp(
+    ctx: &Context,
+    server: SocketAddr,
+    message: &[u8],
+) -> anyhow::Result<Vec<u8>> {
+    let mut stream = ctx.sockets.connect_tcp(unspecified(server), server).await?;
+    stream
+        .write_all(&u16::try_from(message.len())?.to_be_bytes())
+        .await?;
+    stream.write_all(message).await?;
+    stream.flush().await?;
+    let len = stream.read_u16().await?;
+    let mut reply = vec![0; usize::from(len)];
+    stream.read_exact(&mut reply).await?;
+    Ok(reply)
+}
+
+async fn exchange_udp(
+    ctx: &Context,
+    server: SocketAddr,
+    message: &[u8],
+) -> anyhow::Result<Vec<u8>> {
+    let local = ctx
+        .egress
+        .as_ref()
+        .and_then(|egress| egress.local_addr(server))
+        .unwrap_or_else(|| unspecified(server));
+    if let Some(egress) = &ctx.egress {
+        egress.acquire().await;
+    }
+    // Connecting filters out datagrams from anyone but the server.
+    let socket = ctx.sockets.connect_udp(local, server).await?;
+    socket.send(message).await?;
+    let mut reply = vec![0; 65535];
+    let len = socket.recv(&mut reply).await?;
+    reply.truncate(len);
+    Ok(reply)
+}
+
+fn parse(reply: &[u8], id: u16) -> anyhow::Result<Response> {
+    if reply.len() < 12 {
+        bail!("dns reply too short");
+    }
+    if u16::from_be_bytes([reply[0], reply[1]]) != id {
+        bail!("dns reply id mismatch");
+    }
+    let rcode = reply[3] & 0x0f;
+    let questions = u16::from_be_bytes([reply[4], reply[5]]);
+    let mut pos = 12;
+    for _ in 0..questions {
+        (_, pos) = read_name(reply, pos)?;
+        pos += 4;
+    }
+    let mut sections = [Vec::new(), Vec::new(), Vec::new()];
+    for (i, records) in sections.iter_mut().enumerate() {
+        let count = u16::from_be_bytes([reply[6 + i * 2], reply[7 + i * 2]]);
+        for _ in 0..count {
+            let (record, next) = read_record(reply, pos)?;
+            records.push(record);
+            pos = next;
+        }
+    }
+    let [answers, authority, additional] = sections;
+    Ok(Response {
+        rcode,
+        authoritative: reply[2] & 0x04 != 0,
+        truncated: reply[2] & 0x02 != 0,
+        recursion_available: reply[3] & 0x80 != 0,
+        answers,
+        authority,
+        additional,
+    })
+}
+
+/// Read a resource record, returning it and the position after it.
+fn read_record(reply: &[u8], pos: usize) -> anyhow::Result<(Record, usize)> {
+    let (name, next) = read_name(reply, pos)?;
+    let header = reply
+        .get(next..next + 10)
+        .ok_or_else(|| anyhow!("dns record truncated"))?;
+    let kind = u16::from_be_bytes([header[0], header[1]]);
+    let class = u16::from_be_bytes([header[2], header[3]]);
+    let ttl = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+    let len = usize::from(u16::from_be_bytes([header[8], header[9]]));
+    let start = next + 10;
+    let rdata = reply
+        .get(start..start + len)
+        .ok_or_else(|| anyhow!("dns record data truncated"))?;
+    let data = match (kind, rdata.len()) {
+        (TYPE_A, 4) => RecordData::Address(IpAddr::from(<[u8; 4]>::try_from(rdata)?)),
+        (TYPE_AAAA, 16) => RecordData::Address(IpAddr::from(<[u8; 16]>::try_from(rdata)?)),
+        (TYPE_CNAME, _) => RecordData::Cname(read_name(reply, start)?.0),
+        (TYPE_NS | TYPE_PTR, _) => RecordData::Name(read_name(reply, start)?.0),
+        (TYPE_MX, 3..) => RecordData::Mx {
+            preference: u16::from_be_bytes([rdata[0], rdata[1]]),
+            exchange: read_name(reply, start + 2)?.0,
+        },
+        (TYPE_TXT, _) => {
+            let mut strings = Vec::new();
+            let mut rest = rdata;
+            while let Some((&len, tail)) = rest.split_first() {
+                let len = usize::from(len);
+                let string = tail
+                    .get(..len)
+                    .ok_or_else(|| anyhow!("dns txt string truncated"))?;
+                strings.push(string.to_vec());
+                rest = &tail[len..];
+            }
+            RecordData::Txt(strings)
+        }
+        (TYPE_SRV, 7..) => RecordData::Srv {
+            priority: u16::from_be_bytes([rdata[0], rdata[1]]),
+            weight: u16::from_be_bytes([rdata[2], rdata[3]]),
+            port: u16::from_be_bytes([rdata[4], rdata[5]]),
+            target: read_name(reply, start + 6)?.0,
+        },
+        (kind, _) => RecordData::Other(kind),
+    };
+    let record = Record {
+        name,
+        kind,
+        class,
+        ttl,
+        rdata: rdata.to_vec(),
+        data,
+    };
+    Ok((record, start + len))
+}
+
+fn record_output(record: Record) -> DnsRecordOutput {
+    let data = match &record.data {
+        RecordData::Address(ip) => ip.to_string(),
+        RecordData::Cname(name) | RecordData::Name(name) => format!("{name}."),
+        RecordData::Mx {
+            preference,
+            exchange,
+        } => format!("{preference} {exchange}."),
+        RecordData::Txt(strings) => strings
+            .iter()
+            .map(|string| format!("{:?}", String::from_utf8_lossy(string)))
+            .collect::<Vec<_>>()
+            .join(" "),
+        RecordData::Srv {
+            priority,
+            weight,
+            port,
+            target,
+        } => format!("{priority} {weight} {port} {target}."),
+        RecordData::Other(_) => {
+            // The RFC 3597 notation for unknown record data.
+            let mut hex = format!("\\# {}", record.rdata.len());
+            if !record.rdata.is_empty() {
+                hex.push(' ');
+                for byte in &record.rdata {
+                    let _ = write!(hex, "{byte:02x}");
+                }
+            }
+            hex
+        }
+    };
+    DnsRecordOutput {
+        name: record.name,
+        record_type: record.kind.into(),
+        class: record.class,
+        ttl: record.ttl,
+        data,
+        raw: MaybeUtf8(Bytes::from(record.rdata).into()),
+    }
+}
+
+/// Read a possibly compressed name, returning it and the position after it.
+fn read_name(message: &[u8], mut pos: usize) -> anyhow::Result<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    // Bound pointer jumps so malicious replies can't loop forever.
+    for _ in 0..128 {
+        let len = *message
+            .get(pos)
+            .ok_or_else(|| anyhow!("dns name truncated"))?;
+        match len {
+            0 => {
+                return Ok((labels.join("."), end.unwrap_or(pos + 1)));
+            }
+            len if len & 0xc0 == 0xc0 => {
+                let low = *message
+                    .get(pos + 1)
+                    .ok_or_else(|| anyhow!("dns name pointer truncated"))?;
+                end.get_or_insert(pos + 2);
+                pos = usize::from(u16::from_be_bytes([len & 0x3f, low]));
+            }
+            len => {
+                let label = message
+                    .get(pos + 1..pos + 1 + usize::from(len))
+                    .ok_or_else(|| anyhow!("dns label truncated"))?;
+                labels.push(String::from_utf8_lossy(label).to_ascii_lowercase());
+                pos += 1 + usize::from(len);
+            }
+        }
+    }
+    bail!("dns name has too many compression pointers")
+}
+
+fn to_duration(duration: Duration) -> cel_interpreter::Duration {
+    TimeDelta::from_std(duration)
+        .unwrap_or(TimeDelta::MAX)
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Append a record with a compressed name pointing at `name`.
+    fn push_record(reply: &mut Vec<u8>, name: u16, kind: u16, rdata: &[u8]) {
+        reply.extend_from_slice(&(0xc000 | name).to_be_bytes());
+        reply.extend_from_slice(&kind.to_be_bytes());
+        reply.extend_from_slice(&1u16.to_be_bytes());
+        reply.extend_from_slice(&300u32.to_be_bytes());
+        reply.extend_from_slice(&u16::try_from(rdata.len()).unwrap().to_be_bytes());
+        reply.extend_from_slice(rdata);
+    }
+
+    #[test]
+    fn test_encode_query() {
+        assert_eq!(
+            encode_query(0x1234, "www.example.com.", TYPE_A, true).unwrap(),
+            b"\x12\x34\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\
+              \x03www\x07example\x03com\x00\x00\x01\x00\x01",
+        );
+        // The root is just the empty label.
+        assert_eq!(
+            encode_query(1, ".", TYPE_NS, false).unwrap()[12..],
+            [0u8, 0, 2, 0, 1],
+        );
+        assert!(encode_query(1, &"a".repeat(256), TYPE_A, true).is_err());
+    }
+
+    #[test]
+    fn test_parse_compressed_reply() {
+        let mut reply = encode_query(0x1234, "www.example.com", TYPE_A, true).unwrap();
+        // A recursive answer with two answers and one additional record.
+        reply[2..12].copy_from_slice(&[0x81, 0x80, 0, 1, 0, 2, 0, 0, 0, 1]);
+        // www.example.com CNAME web.example.com, pointing into the question for example.com.
+        push_record(&mut reply, 12, TYPE_CNAME, b"\x03web\xc0\x10");
+        // web.example.com A, pointing at the CNAME's data, which itself ends in a pointer.
+        let cname_data = u16::try_from(reply.len() - 6).unwrap();
+        push_record(&mut reply, cname_data, TYPE_A, &[93, 184, 216, 34]);
+        // TXT strings may be empty and aren't joined.
+        push_record(&mut reply, 16, TYPE_TXT, b"\x0bv=spf1 -all\x00\x05Hello");
+
+        let response = parse(&reply, 0x1234).unwrap();
+        assert_eq!(response.rcode, 0);
+        assert!(response.recursion_available);
+        assert!(!response.authoritative);
+        assert!(!response.truncated);
+        assert_eq!(response.answers.len(), 2);
+        assert!(response.authority.is_empty());
+
+        let cname = &response.answers[0];
+        assert_eq!(cname.name, "www.example.com");
+        assert_eq!(cname.ttl, 300);
+        assert!(matches!(&cname.data, RecordData::Cname(name) if name == "web.example.com"));
+
+        let a = &response.answers[1];
+        assert_eq!(a.name, "web.example.com");
+        assert!(matches!(
+            a.data,
+            RecordData::Address(IpAddr::V4(ip)) if ip == Ipv4Addr::new(93, 184, 216, 34)
+        ));
+
+        let txt = &response.additional[0];
+        assert_eq!(txt.name, "example.com");
+        let RecordData::Txt(strings) = &txt.data else {
+            panic!("expected TXT data, got {:?}", txt.data);
+        };
+        assert_eq!(
+            strings,
+            &[b"v=spf1 -all".to_vec(), Vec::new(), b"Hello".to_vec()],
+        );
+        assert_eq!(
+            record_output(txt.clone()).data,
+            r#""v=spf1 -all" "" "Hello""#,
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_replies() {
+        let mut reply = encode_query(7, "example.com", TYPE_TXT, true).unwrap();
+        reply[2..12].copy_from_slice(&[0x81, 0x80, 0, 1, 0, 1, 0, 0, 0, 0]);
+        let valid = reply.len();
+
+        // A TXT string longer than the record's data.
+        push_record(&mut reply, 12, TYPE_TXT, b"\x05abc");
+        assert!(parse(&reply, 7).is_err());
+        assert!(
+            parse(&reply, 8).is_err(),
+            "mismatched IDs should be rejected"
+        );
+
+        // A name which points at itself.
+        reply.truncate(valid);
+        push_record(
+            &mut reply,
+            u16::try_from(valid).unwrap(),
+            TYPE_A,
+            &[127, 0, 0, 1],
+        );
+        let err = parse(&reply, 7).unwrap_err();
+        assert!(err.to_string().contains("compression pointers"), "{err}");
+
+        // Record data past the end of the reply.
+        reply.truncate(valid);
+        push_record(&mut reply, 12, TYPE_A, &[127, 0, 0, 1]);
+        reply.truncate(reply.len() - 1);
+        assert!(parse(&reply, 7).is_err());
+    }
+}
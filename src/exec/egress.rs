@@ -0,0 +1,80 @@
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use anyhow::anyhow;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use crate::EgressPlanOutput;
+
+/// An evaluated egress profile shared by every step that selects it.
+#[derive(Debug)]
+pub(super) struct Egress {
+    pub plan: EgressPlanOutput,
+    pub bind: Option<IpAddr>,
+    pub resolver: Option<SocketAddr>,
+    /// When the next connection may start, if rate limited.
+    next: Option<Mutex<Instant>>,
+}
+
+impl Egress {
+    pub fn new(plan: EgressPlanOutput) -> anyhow::Result<Self> {
+        let bind = plan
+            .bind
+            .as_ref()
+            .map(|bind| {
+                bind.parse().map_err(|_| {
+                    anyhow!(
+                        "devil.egress.{}.bind {bind:?} is not an IP address",
+                        plan.name
+                    )
+                })
+            })
+            .transpose()?;
+        let resolver = plan
+            .resolver
+            .as_ref()
+            .map(|addr| {
+                addr.parse::<SocketAddr>()
+                    .or_else(|_| addr.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, 53)))
+                    .map_err(|_| {
+                        anyhow!(
+                            "devil.egress.{}.resolver {addr:?} is not an address",
+                            plan.name
+                        )
+                    })
+            })
+            .transpose()?;
+        Ok(Self {
+            next: plan
+                .rate
+                .filter(|rate| *rate > 0)
+                .map(|_| Mutex::new(Instant::now())),
+            bind,
+            resolver,
+            plan,
+        })
+    }
+
+    /// Wait until the profile's rate limit allows another connection.
+    pub async fn acquire(&self) {
+        let (Some(next), Some(rate)) = (&self.next, self.plan.rate) else {
+            return;
+        };
+        let interval = Duration::from_secs(1) / u32::try_from(rate).unwrap_or(u32::MAX);
+        let start = {
+            let mut next = next.lock().await;
+            let start = (*next).max(Instant::now());
+            *next = start + interval;
+            start
+        };
+        tokio::time::sleep_until(start).await;
+    }
+
+    /// The local address to connect from for a remote address of the same family.
+    pub fn local_addr(&self, remote: SocketAddr) -> Option<SocketAddr> {
+        self.bind
+            .filter(|bind| bind.is_ipv4() == remote.is_ipv4())
+            .map(|bind| SocketAddr::new(bind, 0))
+    }
+}
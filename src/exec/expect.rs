@@ -0,0 +1,263 @@
+use std::future::Future;
+use std::pin::{pin, Pin};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{ready, Poll};
+use std::time::Instant;
+
+use bytes::Bytes;
+use chrono::TimeDelta;
+use derivative::Derivative;
+use regex::bytes::Regex;
+use tokio::io::{self, AsyncRead, AsyncWrite};
+use tokio::time::Sleep;
+
+use crate::{ExpectOutput, ExpectPlanOutput, ExpectTeardown, MaybeUtf8};
+
+/// Checks expectations against bytes as each read arrives, without waiting for the stream to end.
+///
+/// Once an expectation with abort set passes, or one fails with on_fail asking to stop, the stream
+/// reports EOF so the layers above finish with whatever was received. A reset teardown also sets
+/// the shared flag which makes the connection close with a TCP reset.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct ExpectReader<T: AsyncRead + Unpin + Send> {
+    #[derivative(Debug = "ignore")]
+    inner: T,
+    start: Instant,
+    received: Vec<u8>,
+    expectations: Vec<Expectation>,
+    #[derivative(Debug = "ignore")]
+    deadline: Option<Pin<Box<Sleep>>>,
+    aborted: bool,
+    reset: Arc<AtomicBool>,
+}
+
+#[derive(Debug)]
+struct Expectation {
+    pattern: Regex,
+    deadline: Option<Instant>,
+    abort: bool,
+    on_fail: ExpectTeardown,
+    expired: bool,
+    out: ExpectOutput,
+}
+
+impl Expectation {
+    fn pending(&self) -> bool {
+        self.out.matched.is_none() && !self.expired
+    }
+}
+
+impl<T: AsyncRead + Unpin + Send> ExpectReader<T> {
+    /// Wrap a stream with expectations timed from start, usually when the connection opened.
+    pub fn new(wrap: T, start: Instant, plan: &[ExpectPlanOutput], reset: Arc<AtomicBool>) -> Self {
+        let expectations = plan
+            .iter()
+            .map(|expect| Expectation {
+                pattern: Regex::new(&expect.pattern)
+                    .expect("expect patterns should be checked when the plan is evaluated"),
+                deadline: expect
+                    .within
+                    .map(|within| start + within.0.to_std().unwrap_or_default()),
+                abort: expect.abort,
+                on_fail: expect.on_fail,
+                expired: false,
+                out: ExpectOutput {
+                    pattern: expect.pattern.clone(),
+                    passed: false,
+                    matched: None,
+                    offset: None,
+                    time: None,
+                    aborted: false,
+                    teardown: None,
+                },
+            })
+            .collect();
+        let mut reader = Self {
+            inner: wrap,
+            start,
+            received: Vec::new(),
+            expectations,
+            deadline: None,
+            aborted: false,
+            reset,
+        };
+        reader.reset_deadline();
+        reader
+    }
+    pub fn into_parts(self) -> (T, Vec<ExpectOutput>) {
+        (
+            self.inner,
+            self.expectations.into_iter().map(|e| e.out).collect(),
+        )
+    }
+
+    /// Search the received bytes for each pending pattern.
+    fn check(&mut self, now: Instant) {
+        let time = to_duration(now - self.start);
+        for expect in &mut self.expectations {
+            if expect.out.matched.is_some() {
+                continue;
+            }
+            let Some(found) = expect.pattern.find(&self.received) else {
+                continue;
+            };
+            expect.out.matched = Some(MaybeUtf8(Bytes::copy_from_slice(found.as_bytes()).into()));
+            expect.out.offset = Some(found.start() as u64);
+            expect.out.time = Some(time);
+            expect.out.passed = !expect.expired && expect.deadline.map_or(true, |d| now <= d);
+            // Expired expectations already failed and chose how to tear down.
+            if expect.expired {
+                continue;
+            }
+            let teardown = if !expect.out.passed {
+                expect.on_fail
+            } else if expect.abort {
+                ExpectTeardown::HalfClose
+            } else {
+                continue;
+            };
+            tear_down(&mut self.aborted, &self.reset, &mut expect.out, teardown);
+        }
+        self.reset_deadline();
+    }
+
+    /// Fail pending expectations whose deadlines have passed.
+    fn expire(&mut self, now: Instant) {
+        for expect in &mut self.expectations {
+            if !expect.pending() || expect.deadline.map_or(true, |d| now < d) {
+                continue;
+            }
+            expect.expired = true;
+            tear_down(
+                &mut self.aborted,
+                &self.reset,
+                &mut expect.out,
+                expect.on_fail,
+            );
+        }
+        self.reset_deadline();
+    }
+
+    fn reset_deadline(&mut self) {
+        self.deadline = self
+            .expectations
+            .iter()
+            .filter(|e| e.pending())
+            .filter_map(|e| e.deadline)
+            .min()
+            .map(|d| Box::pin(tokio::time::sleep_until(d.into())));
+    }
+}
+
+impl<T: AsyncRead + Unpin + Send> AsyncRead for ExpectReader<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut io::ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        // Report EOF once an aborting expectation was decided.
+        if self.aborted {
+            return Poll::Ready(Ok(()));
+        }
+        let old_len = buf.filled().len();
+        if let Poll::Ready(result) = pin!(&mut self.inner).poll_read(cx, buf) {
+            result?;
+            let now = Instant::now();
+            let this = &mut *self;
+            // Only keep a copy of the stream while there's a pattern left to search for.
+            if this.expectations.iter().any(|e| e.out.matched.is_none()) {
+                this.received.extend_from_slice(&buf.filled()[old_len..]);
+                this.check(now);
+            }
+            return Poll::Ready(Ok(()));
+        }
+        // Nothing arrived yet, so wake up at the next deadline to fail expectations in time.
+        while let Some(deadline) = &mut self.deadline {
+            ready!(deadline.as_mut().poll(cx));
+            self.expire(Instant::now());
+            if self.aborted {
+                return Poll::Ready(Ok(()));
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> AsyncWrite for ExpectReader<T> {
+    #[inline]
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        pin!(&mut self.inner).poll_write(cx, buf)
+    }
+    #[inline]
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Result<(), std::io::Error>> {
+        pin!(&mut self.inner).poll_flush(cx)
+    }
+    #[inline]
+    fn poll_shutdown(
+        mut self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Result<(), std::io::Error>> {
+        pin!(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Record how a decided expectation ends the connection, unless an earlier one already did.
+fn tear_down(
+    aborted: &mut bool,
+    reset: &AtomicBool,
+    out: &mut ExpectOutput,
+    teardown: ExpectTeardown,
+) {
+    if *aborted {
+        return;
+    }
+    out.teardown = Some(teardown);
+    match teardown {
+        ExpectTeardown::Finish => return,
+        ExpectTeardown::HalfClose => {}
+        ExpectTeardown::Reset => reset.store(true, Ordering::Relaxed),
+    }
+    out.aborted = true;
+    *aborted = true;
+}
+
+fn to_duration(duration: std::time::Duration) -> cel_interpreter::Duration {
+    TimeDelta::from_std(duration)
+        .unwrap_or(TimeDelta::MAX)
+        .into()
+}
+
+/// Describe each expectation which didn't pass.
+pub fn failures<'a>(
+    plan: &'a [ExpectPlanOutput],
+    out: &'a [ExpectOutput],
+) -> impl Iterator<Item = String> + 'a {
+    plan.iter()
+        .zip(out)
+        .filter(|(_, out)| !out.passed)
+        .map(|(plan, out)| {
+            let within = plan
+                .within
+                .map(|within| within.0.to_std().unwrap_or_default());
+            match (out.time, within) {
+                (Some(time), Some(within)) => format!(
+                    "pattern {:?} matched after {:?}, later than the {within:?} deadline",
+                    plan.pattern,
+                    time.0.to_std().unwrap_or_default(),
+                ),
+                (None, Some(within)) => {
+                    format!("pattern {:?} didn't match within {within:?}", plan.pattern)
+                }
+                (_, None) => format!("pattern {:?} didn't match", plan.pattern),
+            }
+        })
+}
@@ -0,0 +1,132 @@
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::TcpFaultPlanOutput;
+
+/// Injects the planned faults into the bytes written to a stream. Offsets count the bytes the
+/// caller writes, not including duplicates.
+#[derive(Debug)]
+pub(super) struct FaultStream<S> {
+    inner: S,
+    plan: TcpFaultPlanOutput,
+    written: u64,
+    /// Bytes already written once which still need to be written again.
+    duplicate: Vec<u8>,
+    dropped: bool,
+}
+
+impl<S> FaultStream<S> {
+    pub(super) fn new(inner: S, plan: TcpFaultPlanOutput) -> Self {
+        Self {
+            inner,
+            plan,
+            written: 0,
+            duplicate: Vec::new(),
+            dropped: false,
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> FaultStream<S> {
+    /// Shut down the connection once drop_after bytes were written, failing every later call.
+    fn poll_drop(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if !self.dropped
+            && self
+                .plan
+                .drop_after
+                .is_some_and(|limit| self.written >= limit)
+        {
+            // The connection is being abandoned, so errors closing it don't matter.
+            let _ = ready!(Pin::new(&mut self.inner).poll_shutdown(cx));
+            self.dropped = true;
+        }
+        if self.dropped {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                format!(
+                    "connection dropped by fault injection after {} bytes",
+                    self.written
+                ),
+            )));
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_duplicate(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !self.duplicate.is_empty() {
+            let n = ready!(Pin::new(&mut self.inner).poll_write(cx, &self.duplicate))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.duplicate.drain(..n);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncRead for FaultStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drop(cx))?;
+        Pin::new(&mut this.inner).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for FaultStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_drop(cx))?;
+        ready!(this.poll_duplicate(cx))?;
+        let mut data = buf.to_vec();
+        if let Some(limit) = this.plan.drop_after {
+            data.truncate(usize::try_from(limit - this.written).unwrap_or(usize::MAX));
+        }
+        if let Some(offset) = this.plan.corrupt_offset {
+            let end = offset.saturating_add(this.plan.corrupt_length);
+            let len = u64::try_from(data.len()).unwrap_or(u64::MAX);
+            for i in this.written.max(offset)..end.min(this.written + len) {
+                data[usize::try_from(i - this.written).unwrap()] ^= 0xff;
+            }
+        }
+        let n = ready!(Pin::new(&mut this.inner).poll_write(cx, &data))?;
+        this.written += u64::try_from(n).unwrap_or(u64::MAX);
+        if this.plan.duplicate_writes {
+            this.duplicate.extend_from_slice(&data[..n]);
+        }
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drop(cx))?;
+        ready!(this.poll_duplicate(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.dropped
+            || this
+                .plan
+                .drop_after
+                .is_some_and(|limit| this.written >= limit)
+        {
+            // Dropping already shuts the connection down, and only fails to report the drop.
+            let _ = ready!(this.poll_drop(cx));
+            return Poll::Ready(Ok(()));
+        }
+        ready!(this.poll_duplicate(cx))?;
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
@@ -0,0 +1,57 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::{Evaluate, Follow, PlanData};
+
+use super::State;
+
+/// Tracks the values queued by run.follow for a step.
+#[derive(Debug)]
+pub(super) struct Follower<'a> {
+    plan: &'a Follow,
+    max_depth: u64,
+    max_jobs: u64,
+    queued: u64,
+    // CEL values aren't hashable, so they're compared by their debug representation.
+    seen: HashSet<String>,
+    queue: VecDeque<(PlanData, u64)>,
+}
+
+impl<'a> Follower<'a> {
+    pub fn new(plan: &'a Follow, state: &State<'_>) -> crate::Result<Self> {
+        Ok(Self {
+            plan,
+            max_depth: plan.max_depth.evaluate(state)?,
+            max_jobs: plan.max_jobs.evaluate(state)?,
+            queued: 0,
+            seen: HashSet::new(),
+            queue: VecDeque::new(),
+        })
+    }
+
+    /// Mark a value as already run so it won't be followed.
+    pub fn visit(&mut self, value: &PlanData) {
+        self.seen.insert(format!("{:?}", value.0));
+    }
+
+    /// The next value to run along with its depth.
+    pub fn next(&mut self) -> Option<(PlanData, u64)> {
+        self.queue.pop_front()
+    }
+
+    /// Queue any new values found by a job which ran at depth.
+    pub fn push(&mut self, state: &State<'_>, depth: u64) -> crate::Result<()> {
+        if depth >= self.max_depth {
+            return Ok(());
+        }
+        for (_, value) in self.plan.values.evaluate(state)? {
+            if self.queued >= self.max_jobs {
+                break;
+            }
+            if self.seen.insert(format!("{:?}", value.0)) {
+                self.queue.push_back((value, depth + 1));
+                self.queued += 1;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use futures::{stream, StreamExt};
+use rand::distributions::{Alphanumeric, DistString};
+use url::Url;
+
+use crate::{
+    ForcedBrowseCalibrationOutput, ForcedBrowseOutput, ForcedBrowsePlanOutput,
+    ForcedBrowseResourceOutput, IterableKey, JobOutput,
+};
+
+use super::crawl::fetch;
+use super::Context;
+
+/// Request each word under the plan's URL, recursing into discovered directories. Responses are
+/// compared against each directory's response to random names so soft 404s are ignored.
+pub(super) async fn forced_browse(
+    ctx: &Context,
+    plan: ForcedBrowsePlanOutput,
+) -> (ForcedBrowseOutput, Vec<(IterableKey, Arc<JobOutput>)>) {
+    let mut out = ForcedBrowseOutput {
+        calibration: Vec::new(),
+        resources: Vec::new(),
+        requests: 0,
+        plan,
+    };
+    let mut jobs = Vec::new();
+    let extensions: Vec<Option<String>> = std::iter::once(None)
+        .chain(out.plan.extensions.iter().cloned().map(Some))
+        .collect();
+    let words = out.plan.words.clone();
+    let mut dirs = VecDeque::from([(out.plan.url.clone(), 0)]);
+
+    while let Some((dir, depth)) = dirs.pop_front() {
+        let mut baselines = Vec::new();
+        for ext in &extensions {
+            // Two probes show how much the response varies between requests on its own.
+            let probes: Vec<_> = (0..2)
+                .map(|_| {
+                    name(
+                        &Alphanumeric.sample_string(&mut rand::thread_rng(), 16),
+                        ext,
+                    )
+                })
+                .collect();
+            let mut responses = Vec::new();
+            for probe in probes {
+                if out.requests >= out.plan.max_requests {
+                    break;
+                }
+                let Ok(url) = dir.join(&probe) else {
+                    continue;
+                };
+                if let Some(response) = request(ctx, url, &probe, &mut out, &mut jobs).await {
+                    responses.push(response);
+                }
+            }
+            let Some(first) = responses.first() else {
+                continue;
+            };
+            let variation = responses
+                .iter()
+                .map(|r| r.length.abs_diff(first.length))
+                .max();
+            let calibration = ForcedBrowseCalibrationOutput {
+                directory: dir.clone(),
+                extension: ext.clone(),
+                status_code: first.status_code,
+                length: first.length,
+                tolerance: out.plan.length_tolerance.max(variation.unwrap_or_default()),
+            };
+            baselines.push(calibration.clone());
+            out.calibration.push(calibration);
+        }
+
+        let remaining = out.plan.max_requests.saturating_sub(out.requests);
+        let candidates: Vec<_> = words
+            .iter()
+            .flat_map(|word| extensions.iter().map(move |ext| (word, ext)))
+            .filter_map(|(word, ext)| {
+                let name = name(word.trim_matches('/'), ext);
+                Some((dir.join(&name).ok()?, name, word, ext))
+            })
+            .take(usize::try_from(remaining).unwrap_or(usize::MAX))
+            .collect();
+        // Key jobs by request number so they stay unique even when some requests fail.
+        let offset = out.requests;
+        let concurrency = usize::try_from(out.plan.concurrency).unwrap_or(usize::MAX);
+        let mut results = stream::iter(candidates.into_iter().enumerate())
+            .map(|(i, (url, name, word, ext))| async move {
+                let mut job_name = ctx.job_name.clone();
+                job_name.job = IterableKey::Uint(offset + u64::try_from(i).unwrap_or(u64::MAX));
+                let result = fetch(ctx, url.clone(), job_name).await;
+                (url, name, word, ext, result)
+            })
+            .buffered(concurrency);
+
+        while let Some((url, name, word, ext, result)) = results.next().await {
+            out.requests += 1;
+            let job = match result {
+                Ok(job) => job,
+                Err(e) => {
+                    tracing::debug!(%url, ?e, "forced browse fetch failed");
+                    continue;
+                }
+            };
+            let response = Response::new(&job, &name);
+            jobs.push((job.name.job.clone(), Arc::new(job)));
+            let baseline = baselines.iter().find(|b| &b.extension == ext);
+            if !response.found(baseline) {
+                continue;
+            }
+            if ext.is_none() && depth < out.plan.max_depth {
+                if let Ok(child) = dir.join(&format!("{}/", word.trim_matches('/'))) {
+                    dirs.push_back((child, depth + 1));
+                }
+            }
+            out.resources.push(ForcedBrowseResourceOutput {
+                url,
+                depth,
+                status_code: response.status_code,
+                length: response.length,
+                location: response.location,
+            });
+        }
+        if out.requests >= out.plan.max_requests {
+            break;
+        }
+    }
+    (out, jobs)
+}
+
+fn name(word: &str, ext: &Option<String>) -> String {
+    match ext {
+        Some(ext) => format!("{word}.{ext}"),
+        None => word.to_owned(),
+    }
+}
+
+async fn request(
+    ctx: &Context,
+    url: Url,
+    name: &str,
+    out: &mut ForcedBrowseOutput,
+    jobs: &mut Vec<(IterableKey, Arc<JobOutput>)>,
+) -> Option<Response> {
+    let key = IterableKey::Uint(out.requests);
+    let mut job_name = ctx.job_name.clone();
+    job_name.job = key.clone();
+    out.requests += 1;
+    let job = match fetch(ctx, url.clone(), job_name).await {
+        Ok(job) => job,
+        Err(e) => {
+            tracing::debug!(%url, ?e, "forced browse calibration failed");
+            return None;
+        }
+    };
+    let response = Response::new(&job, name);
+    jobs.push((key, Arc::new(job)));
+    Some(response)
+}
+
+struct Response {
+    status_code: Option<u16>,
+    length: u64,
+    location: Option<String>,
+}
+
+impl Response {
+    fn new(job: &JobOutput, name: &str) -> Self {
+        let (status_code, body) = job.response_summary();
+        // Servers often echo the requested path, so remove it to make lengths comparable.
+        let length = String::from_utf8_lossy(body).replace(name, "").len();
+        let location = job
+            .http
+            .as_ref()
+            .and_then(|h| h.response.as_ref())
+            .and_then(|r| r.headers.as_ref())
+            .and_then(|headers| {
+                headers.iter().find(|h| {
+                    h.key
+                        .as_ref()
+                        .is_some_and(|k| k.eq_ignore_ascii_case(b"location"))
+                })
+            })
+            .map(|h| String::from_utf8_lossy(&h.value).into_owned());
+        Self {
+            status_code,
+            length: length.try_into().unwrap_or(u64::MAX),
+            location,
+        }
+    }
+
+    fn found(&self, baseline: Option<&ForcedBrowseCalibrationOutput>) -> bool {
+        if self.status_code.is_none() {
+            return false;
+        }
+        let Some(baseline) = baseline else {
+            // Without calibration fall back to trusting the status code.
+            return self.status_code != Some(404);
+        };
+        self.status_code != baseline.status_code
+            || self.length.abs_diff(baseline.length) > baseline.tolerance
+    }
+}
@@ -152,6 +152,16 @@ impl<'a> GraphqlRunner {
         };
 
         if let Some(resp_body) = resp_body {
+            let errors = match resp_body.get("errors") {
+                None | Some(serde_json::Value::Null) => Vec::new(),
+                Some(errors) => serde_json::from_value(errors.clone()).unwrap_or_else(|e| {
+                    self.out.errors.push(GraphqlError {
+                        kind: "json response errors deserialize".to_owned(),
+                        message: e.to_string(),
+                    });
+                    Vec::new()
+                }),
+            };
             self.out.response = Some(Arc::new(crate::GraphqlResponse {
                 name: PduName::with_job(
                     self.ctx.job_name.clone(),
@@ -163,11 +173,7 @@ impl<'a> GraphqlRunner {
                     .unwrap_or(&serde_json::Value::Null)
                     .clone()
                     .into(),
-                errors: resp_body
-                    .get("errors")
-                    .unwrap_or(&serde_json::Value::Null)
-                    .clone()
-                    .into(),
+                errors,
                 full: resp_body.into(),
                 duration: chrono::Duration::from_std(
                     end_time
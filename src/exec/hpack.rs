@@ -0,0 +1,692 @@
+//! Just enough HPACK (RFC 7541) to build header blocks by hand, including ones a real encoder
+//! would refuse to produce, and a decoder that reports what a peer would make of them.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use url::{Position, Url};
+
+use crate::{
+    HpackBlockOutput, HpackEntryOutput, HpackFieldOutput, HpackOutput, Http2FrameOutput,
+    Http2FramePayloadOutput, Http2SettingsParameterId, MaybeUtf8,
+};
+
+/// The initial SETTINGS_HEADER_TABLE_SIZE.
+const DEFAULT_TABLE_SIZE: u64 = 4096;
+/// Octets each dynamic table entry counts for beyond its name and value.
+const ENTRY_OVERHEAD: u64 = 32;
+
+/// Append an integer with an N bit prefix, keeping the bits of `flags` above the prefix.
+pub(super) fn integer(out: &mut Vec<u8>, prefix: u8, flags: u8, value: u64) {
+    let max = (1u8 << prefix) - 1;
+    if value < u64::from(max) {
+        out.push(flags | value as u8);
+        return;
+    }
+    out.push(flags | max);
+    let mut rest = value - u64::from(max);
+    while rest >= 0x80 {
+        out.push(rest as u8 | 0x80);
+        rest >>= 7;
+    }
+    out.push(rest as u8);
+}
+
+/// Append a literal field without indexing, with both name and value sent as raw strings.
+pub(super) fn literal(out: &mut Vec<u8>, name: &[u8], value: &[u8]) {
+    out.push(0);
+    for s in [name, value] {
+        integer(out, 7, 0, s.len().try_into().unwrap_or(u64::MAX));
+        out.extend_from_slice(s);
+    }
+}
+
+/// Append a dynamic table size update, which is only valid at the start of a block.
+pub(super) fn size_update(out: &mut Vec<u8>, size: u64) {
+    integer(out, 5, 0x20, size);
+}
+
+/// A header block for a GET of the URL.
+pub(super) fn request(url: &Url) -> Vec<u8> {
+    let mut out = Vec::new();
+    literal(&mut out, b":method", b"GET");
+    literal(&mut out, b":scheme", url.scheme().as_bytes());
+    literal(
+        &mut out,
+        b":authority",
+        url[Position::BeforeHost..Position::AfterPort].as_bytes(),
+    );
+    literal(
+        &mut out,
+        b":path",
+        url[Position::BeforePath..Position::AfterQuery].as_bytes(),
+    );
+    out
+}
+
+/// Decode every header block in the frames, in order, as the receiving peer would.
+/// `header_table_size` is the limit the receiver advertised.
+pub(super) fn inspect(frames: &[Arc<Http2FrameOutput>], header_table_size: u64) -> HpackOutput {
+    let mut decoder = Decoder::new(header_table_size);
+    let mut out = HpackOutput::default();
+    let mut pending: Option<(u32, Vec<u8>)> = None;
+    let mut failed = false;
+    for frame in frames {
+        let (fragment, end_headers) = match &frame.payload {
+            Http2FramePayloadOutput::Headers(headers) => {
+                if let Some((id, _)) = pending.take() {
+                    out.anomalies.push(format!(
+                        "HEADERS on stream {} interrupted the header block on stream {id}",
+                        frame.stream_id,
+                    ));
+                }
+                pending = Some((frame.stream_id, Vec::new()));
+                (&headers.header_block_fragment, headers.end_headers)
+            }
+            Http2FramePayloadOutput::PushPromise(promise) => {
+                if let Some((id, _)) = pending.take() {
+                    out.anomalies.push(format!(
+                        "PUSH_PROMISE on stream {} interrupted the header block on stream {id}",
+                        frame.stream_id,
+                    ));
+                }
+                pending = Some((frame.stream_id, Vec::new()));
+                (&promise.header_block_fragment, promise.end_headers)
+            }
+            Http2FramePayloadOutput::Continuation(continuation) => match &pending {
+                Some((id, _)) if *id == frame.stream_id => (
+                    &continuation.header_block_fragment,
+                    continuation.end_headers,
+                ),
+                _ => {
+                    out.anomalies.push(format!(
+                        "CONTINUATION on stream {} without an open header block",
+                        frame.stream_id,
+                    ));
+                    continue;
+                }
+            },
+            payload => {
+                if let Some((id, _)) = &pending {
+                    out.anomalies.push(format!(
+                        "{} on stream {} interrupted the header block on stream {id}",
+                        payload.r#type(),
+                        frame.stream_id,
+                    ));
+                }
+                continue;
+            }
+        };
+        let Some((stream_id, block)) = &mut pending else {
+            continue;
+        };
+        block.extend_from_slice(fragment);
+        if !end_headers {
+            continue;
+        }
+        let (stream_id, block) = (*stream_id, std::mem::take(block));
+        pending = None;
+        if failed {
+            continue;
+        }
+        let mut decoded = HpackBlockOutput {
+            stream_id,
+            fields: Vec::new(),
+            table_size_updates: Vec::new(),
+            anomalies: Vec::new(),
+        };
+        if let Err(e) = decoder.decode(&block, &mut decoded) {
+            decoded.anomalies.push(e);
+            out.anomalies
+                .push("later blocks weren't decoded after a compression error".to_owned());
+            failed = true;
+        }
+        out.blocks.push(decoded);
+    }
+    if let Some((id, _)) = pending {
+        out.anomalies
+            .push(format!("header block on stream {id} never ended"));
+    }
+    out.dynamic_table = decoder
+        .table
+        .iter()
+        .map(|(name, value)| HpackEntryOutput {
+            name: MaybeUtf8(name.clone().into()),
+            value: MaybeUtf8(value.clone().into()),
+        })
+        .collect();
+    out.table_size = decoder.size;
+    out.max_table_size = decoder.max_size;
+    out
+}
+
+/// The last header table size the frames' sender advertised, which limits the dynamic table of
+/// the blocks it receives.
+pub(super) fn header_table_size(frames: &[Arc<Http2FrameOutput>]) -> u64 {
+    frames
+        .iter()
+        .filter_map(|frame| match &frame.payload {
+            Http2FramePayloadOutput::Settings(settings) if !settings.ack => settings
+                .parameters
+                .iter()
+                .rev()
+                .find(|p| matches!(p.id, Http2SettingsParameterId::HeaderTableSize))
+                .map(|p| u64::from(p.value)),
+            _ => None,
+        })
+        .last()
+        .unwrap_or(DEFAULT_TABLE_SIZE)
+}
+
+struct Decoder {
+    /// Newest entry first, matching the order of dynamic table indexes.
+    table: VecDeque<(Bytes, Bytes)>,
+    size: u64,
+    max_size: u64,
+    limit: u64,
+    huffman: Huffman,
+}
+
+impl Decoder {
+    fn new(limit: u64) -> Self {
+        Self {
+            table: VecDeque::new(),
+            size: 0,
+            max_size: limit,
+            limit,
+            huffman: Huffman::new(),
+        }
+    }
+
+    /// Decode the fields of a block into `out`. Errors are compression errors, after which the
+    /// table can't be trusted.
+    fn decode(&mut self, mut buf: &[u8], out: &mut HpackBlockOutput) -> Result<(), String> {
+        let mut regular = false;
+        while let Some(&first) = buf.first() {
+            let (name, value, representation, index, huffman) = if first & 0x80 != 0 {
+                let index = integer(&mut buf, 7)?;
+                let (name, value) = self.get(index)?;
+                (name, value, "indexed", Some(index), false)
+            } else if first & 0x40 != 0 {
+                let (name, value, index, huffman) = self.literal(&mut buf, 6)?;
+                self.insert(name.clone(), value.clone());
+                (name, value, "incremental", index, huffman)
+            } else if first & 0x20 != 0 {
+                let size = integer(&mut buf, 5)?;
+                if !out.fields.is_empty() {
+                    return Err("dynamic table size update after a header field".to_owned());
+                }
+                if size > self.limit {
+                    return Err(format!(
+                        "dynamic table size update to {size} exceeds the limit of {}",
+                        self.limit,
+                    ));
+                }
+                self.max_size = size;
+                self.evict(0);
+                out.table_size_updates.push(size);
+                continue;
+            } else {
+                let representation = if first & 0x10 != 0 {
+                    "never_indexed"
+                } else {
+                    "without_indexing"
+                };
+                let (name, value, index, huffman) = self.literal(&mut buf, 4)?;
+                (name, value, representation, index, huffman)
+            };
+
+            // Requests a server forwards as HTTP/1.1 can smuggle these past its parser.
+            let escaped = name.escape_ascii();
+            if name.starts_with(b":") {
+                if regular {
+                    out.anomalies
+                        .push(format!("pseudo-header {escaped} after a regular field"));
+                }
+            } else {
+                regular = true;
+            }
+            if name.iter().any(u8::is_ascii_uppercase) {
+                out.anomalies
+                    .push(format!("uppercase field name {escaped}"));
+            }
+            if value.iter().any(|b| matches!(b, b'\r' | b'\n' | 0)) {
+                out.anomalies
+                    .push(format!("{escaped} value contains CR, LF, or NUL"));
+            }
+            let connection_specific = matches!(
+                &name[..],
+                b"connection"
+                    | b"keep-alive"
+                    | b"proxy-connection"
+                    | b"transfer-encoding"
+                    | b"upgrade"
+            );
+            if connection_specific || (&name[..] == b"te" && &value[..] != b"trailers") {
+                out.anomalies
+                    .push(format!("connection-specific field {escaped}"));
+            }
+            out.fields.push(HpackFieldOutput {
+                name: MaybeUtf8(name.into()),
+                value: MaybeUtf8(value.into()),
+                representation: representation.to_owned(),
+                index,
+                huffman,
+            });
+        }
+        Ok(())
+    }
+
+    /// Decode a literal field whose name index has an N bit prefix.
+    fn literal(
+        &self,
+        buf: &mut &[u8],
+        prefix: u8,
+    ) -> Result<(Bytes, Bytes, Option<u64>, bool), String> {
+        let index = integer(buf, prefix)?;
+        let (name, index, name_huffman) = if index == 0 {
+            let (name, huffman) = self.string(buf)?;
+            (name, None, huffman)
+        } else {
+            (self.get(index)?.0, Some(index), false)
+        };
+        let (value, value_huffman) = self.string(buf)?;
+        Ok((name, value, index, name_huffman || value_huffman))
+    }
+
+    fn string(&self, buf: &mut &[u8]) -> Result<(Bytes, bool), String> {
+        let huffman = buf.first().ok_or("truncated string")? & 0x80 != 0;
+        let len = integer(buf, 7)?;
+        let len = usize::try_from(len)
+            .ok()
+            .filter(|len| *len <= buf.len())
+            .ok_or("truncated string")?;
+        let (raw, rest) = buf.split_at(len);
+        *buf = rest;
+        if huffman {
+            Ok((self.huffman.decode(raw)?.into(), true))
+        } else {
+            Ok((Bytes::copy_from_slice(raw), false))
+        }
+    }
+
+    fn get(&self, index: u64) -> Result<(Bytes, Bytes), String> {
+        let Some(i) = usize::try_from(index).ok().and_then(|i| i.checked_sub(1)) else {
+            return Err("index 0 is not valid".to_owned());
+        };
+        if let Some(&(name, value)) = STATIC_TABLE.get(i) {
+            return Ok((Bytes::from_static(name), Bytes::from_static(value)));
+        }
+        self.table
+            .get(i - STATIC_TABLE.len())
+            .cloned()
+            .ok_or_else(|| format!("index {index} is past the end of the table"))
+    }
+
+    fn insert(&mut self, name: Bytes, value: Bytes) {
+        let size = entry_size(&name, &value);
+        // An entry larger than the whole table empties it without being added.
+        self.evict(size);
+        if size <= self.max_size {
+            self.size += size;
+            self.table.push_front((name, value));
+        }
+    }
+
+    /// Drop the oldest entries until `incoming` more octets fit.
+    fn evict(&mut self, incoming: u64) {
+        while self.size + incoming > self.max_size {
+            let Some((name, value)) = self.table.pop_back() else {
+                break;
+            };
+            self.size -= entry_size(&name, &value);
+        }
+    }
+}
+
+fn entry_size(name: &[u8], value: &[u8]) -> u64 {
+    u64::try_from(name.len() + value.len()).unwrap_or(u64::MAX) + ENTRY_OVERHEAD
+}
+
+fn integer(buf: &mut &[u8], prefix: u8) -> Result<u64, String> {
+    let (&first, rest) = buf.split_first().ok_or("truncated integer")?;
+    *buf = rest;
+    let max = (1u64 << prefix) - 1;
+    let mut value = u64::from(first) & max;
+    if value < max {
+        return Ok(value);
+    }
+    let mut shift = 0;
+    loop {
+        let (&byte, rest) = buf.split_first().ok_or("truncated integer")?;
+        *buf = rest;
+        if shift > 56 {
+            return Err("integer overflows 64 bits".to_owned());
+        }
+        value = value
+            .checked_add(u64::from(byte & 0x7f) << shift)
+            .ok_or("integer overflows 64 bits")?;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+        shift += 7;
+    }
+}
+
+/// A canonical Huffman decoder for the code in RFC 7541 Appendix B.
+struct Huffman {
+    /// Symbols sorted by code length and then value.
+    symbols: Vec<u16>,
+    /// For each code length, the first code, its position in symbols, and how many codes have
+    /// that length.
+    lengths: [(u32, usize, usize); 31],
+}
+
+impl Huffman {
+    fn new() -> Self {
+        let mut symbols: Vec<u16> = (0..=256).collect();
+        symbols.sort_by_key(|&s| HUFFMAN_LENGTHS[usize::from(s)]);
+        let mut lengths = [(0, 0, 0); 31];
+        let (mut code, mut position) = (0, 0);
+        for (len, entry) in lengths.iter_mut().enumerate().skip(1) {
+            let count = HUFFMAN_LENGTHS
+                .iter()
+                .filter(|&&l| usize::from(l) == len)
+                .count();
+            *entry = (code, position, count);
+            code = (code + u32::try_from(count).unwrap()) << 1;
+            position += count;
+        }
+        Self { symbols, lengths }
+    }
+
+    fn decode(&self, raw: &[u8]) -> Result<Vec<u8>, String> {
+        let mut out = Vec::with_capacity(raw.len() * 8 / 5);
+        let (mut code, mut len) = (0u32, 0);
+        for bit in raw
+            .iter()
+            .flat_map(|b| (0..8).rev().map(move |i| b >> i & 1))
+        {
+            code = code << 1 | u32::from(bit);
+            len += 1;
+            let Some(&(first, position, count)) = self.lengths.get(len) else {
+                return Err("invalid Huffman code".to_owned());
+            };
+            let offset = code.wrapping_sub(first);
+            if code >= first && usize::try_from(offset).unwrap() < count {
+                match self.symbols[position + usize::try_from(offset).unwrap()] {
+                    256 => return Err("Huffman string contains EOS".to_owned()),
+                    symbol => out.push(u8::try_from(symbol).unwrap()),
+                }
+                (code, len) = (0, 0);
+            }
+        }
+        // Padding must be the high bits of EOS, which are all ones, and shorter than a byte.
+        if len > 7 || code != (1 << len) - 1 {
+            return Err("invalid Huffman padding".to_owned());
+        }
+        Ok(out)
+    }
+}
+
+/// Code lengths for each symbol, from which the canonical codes follow.
+#[rustfmt::skip]
+const HUFFMAN_LENGTHS: [u8; 257] = [
+    13, 23, 28, 28, 28, 28, 28, 28, 28, 24, 30, 28, 28, 30, 28, 28,
+    28, 28, 28, 28, 28, 28, 30, 28, 28, 28, 28, 28, 28, 28, 28, 28,
+    6, 10, 10, 12, 13, 6, 8, 11, 10, 10, 8, 11, 8, 6, 6, 6,
+    5, 5, 5, 6, 6, 6, 6, 6, 6, 6, 7, 8, 15, 6, 12, 10,
+    13, 6, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7,
+    7, 7, 7, 7, 7, 7, 7, 7, 8, 7, 8, 13, 19, 13, 14, 6,
+    15, 5, 6, 5, 6, 5, 6, 6, 6, 5, 7, 7, 6, 6, 6, 5,
+    6, 7, 6, 5, 5, 6, 7, 7, 7, 7, 7, 15, 11, 14, 13, 28,
+    20, 22, 20, 20, 22, 22, 22, 23, 22, 23, 23, 23, 23, 23, 24, 23,
+    24, 24, 22, 23, 24, 23, 23, 23, 23, 21, 22, 23, 22, 23, 23, 24,
+    22, 21, 20, 22, 22, 23, 23, 21, 23, 22, 22, 24, 21, 22, 23, 23,
+    21, 21, 22, 21, 23, 22, 23, 23, 20, 22, 22, 22, 23, 22, 22, 23,
+    26, 26, 20, 19, 22, 23, 22, 25, 26, 26, 26, 27, 27, 26, 24, 25,
+    19, 21, 26, 27, 27, 26, 27, 24, 21, 21, 26, 26, 28, 27, 27, 27,
+    20, 24, 20, 21, 22, 21, 21, 23, 22, 22, 25, 25, 24, 24, 26, 23,
+    26, 27, 26, 26, 27, 27, 27, 27, 27, 28, 27, 27, 27, 27, 27, 26,
+    30,
+];
+
+const STATIC_TABLE: [(&[u8], &[u8]); 61] = [
+    (b":authority", b""),
+    (b":method", b"GET"),
+    (b":method", b"POST"),
+    (b":path", b"/"),
+    (b":path", b"/index.html"),
+    (b":scheme", b"http"),
+    (b":scheme", b"https"),
+    (b":status", b"200"),
+    (b":status", b"204"),
+    (b":status", b"206"),
+    (b":status", b"304"),
+    (b":status", b"400"),
+    (b":status", b"404"),
+    (b":status", b"500"),
+    (b"accept-charset", b""),
+    (b"accept-encoding", b"gzip, deflate"),
+    (b"accept-language", b""),
+    (b"accept-ranges", b""),
+    (b"accept", b""),
+    (b"access-control-allow-origin", b""),
+    (b"age", b""),
+    (b"allow", b""),
+    (b"authorization", b""),
+    (b"cache-control", b""),
+    (b"content-disposition", b""),
+    (b"content-encoding", b""),
+    (b"content-language", b""),
+    (b"content-length", b""),
+    (b"content-location", b""),
+    (b"content-range", b""),
+    (b"content-type", b""),
+    (b"cookie", b""),
+    (b"date", b""),
+    (b"etag", b""),
+    (b"expect", b""),
+    (b"expires", b""),
+    (b"from", b""),
+    (b"host", b""),
+    (b"if-match", b""),
+    (b"if-modified-since", b""),
+    (b"if-none-match", b""),
+    (b"if-range", b""),
+    (b"if-unmodified-since", b""),
+    (b"last-modified", b""),
+    (b"link", b""),
+    (b"location", b""),
+    (b"max-forwards", b""),
+    (b"proxy-authenticate", b""),
+    (b"proxy-authorization", b""),
+    (b"range", b""),
+    (b"referer", b""),
+    (b"refresh", b""),
+    (b"retry-after", b""),
+    (b"server", b""),
+    (b"set-cookie", b""),
+    (b"strict-transport-security", b""),
+    (b"transfer-encoding", b""),
+    (b"user-agent", b""),
+    (b"vary", b""),
+    (b"via", b""),
+    (b"www-authenticate", b""),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        let s = s.replace(' ', "");
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    /// Decode a block, returning its fields and whether any were Huffman coded.
+    fn decode(decoder: &mut Decoder, block: &str) -> (Vec<(String, String)>, bool) {
+        let mut out = HpackBlockOutput {
+            stream_id: 1,
+            fields: Vec::new(),
+            table_size_updates: Vec::new(),
+            anomalies: Vec::new(),
+        };
+        decoder.decode(&hex(block), &mut out).unwrap();
+        let fields = out
+            .fields
+            .iter()
+            .map(|f| {
+                (
+                    String::from_utf8(f.name.to_vec()).unwrap(),
+                    String::from_utf8(f.value.to_vec()).unwrap(),
+                )
+            })
+            .collect();
+        (fields, out.fields.iter().any(|f| f.huffman))
+    }
+
+    fn fields(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_requests_with_huffman() {
+        // RFC 7541 C.4
+        let mut decoder = Decoder::new(DEFAULT_TABLE_SIZE);
+        assert_eq!(
+            decode(&mut decoder, "8286 8441 8cf1 e3c2 e5f2 3a6b a0ab 90f4 ff"),
+            (
+                fields(&[
+                    (":method", "GET"),
+                    (":scheme", "http"),
+                    (":path", "/"),
+                    (":authority", "www.example.com"),
+                ]),
+                true,
+            ),
+        );
+        assert_eq!(decoder.size, 57);
+
+        assert_eq!(
+            decode(&mut decoder, "8286 84be 5886 a8eb 1064 9cbf"),
+            (
+                fields(&[
+                    (":method", "GET"),
+                    (":scheme", "http"),
+                    (":path", "/"),
+                    (":authority", "www.example.com"),
+                    ("cache-control", "no-cache"),
+                ]),
+                true,
+            ),
+        );
+        assert_eq!(decoder.size, 110);
+
+        assert_eq!(
+            decode(
+                &mut decoder,
+                "8287 85bf 4088 25a8 49e9 5ba9 7d7f 8925 a849 e95b b8e8 b4bf",
+            ),
+            (
+                fields(&[
+                    (":method", "GET"),
+                    (":scheme", "https"),
+                    (":path", "/index.html"),
+                    (":authority", "www.example.com"),
+                    ("custom-key", "custom-value"),
+                ]),
+                true,
+            ),
+        );
+        assert_eq!(decoder.size, 164);
+        assert_eq!(
+            decoder.table,
+            [
+                (Bytes::from("custom-key"), Bytes::from("custom-value")),
+                (Bytes::from("cache-control"), Bytes::from("no-cache")),
+                (Bytes::from(":authority"), Bytes::from("www.example.com")),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_responses_with_huffman() {
+        // RFC 7541 C.6, with the table limited to 256 octets so entries are evicted.
+        let mut decoder = Decoder::new(256);
+        assert_eq!(
+            decode(
+                &mut decoder,
+                "4882 6402 5885 aec3 771a 4b61 96d0 7abe 9410 54d4 44a8 2005 9504 0b81 66e0 82a6 \
+                 2d1b ff6e 919d 29ad 1718 63c7 8f0b 97c8 e9ae 82ae 43d3",
+            ),
+            (
+                fields(&[
+                    (":status", "302"),
+                    ("cache-control", "private"),
+                    ("date", "Mon, 21 Oct 2013 20:13:21 GMT"),
+                    ("location", "https://www.example.com"),
+                ]),
+                true,
+            ),
+        );
+        assert_eq!(decoder.size, 222);
+
+        assert_eq!(
+            decode(&mut decoder, "4883 640e ffc1 c0bf"),
+            (
+                fields(&[
+                    (":status", "307"),
+                    ("cache-control", "private"),
+                    ("date", "Mon, 21 Oct 2013 20:13:21 GMT"),
+                    ("location", "https://www.example.com"),
+                ]),
+                true,
+            ),
+        );
+        assert_eq!(decoder.size, 222);
+
+        assert_eq!(
+            decode(
+                &mut decoder,
+                "88c1 6196 d07a be94 1054 d444 a820 0595 040b 8166 e084 a62d 1bff c05a 839b d9ab \
+                 77ad 94e7 821d d7f2 e6c7 b335 dfdf cd5b 3960 d5af 2708 7f36 72c1 ab27 0fb5 291f \
+                 9587 3160 65c0 03ed 4ee5 b106 3d50 07",
+            ),
+            (
+                fields(&[
+                    (":status", "200"),
+                    ("cache-control", "private"),
+                    ("date", "Mon, 21 Oct 2013 20:13:22 GMT"),
+                    ("location", "https://www.example.com"),
+                    ("content-encoding", "gzip"),
+                    (
+                        "set-cookie",
+                        "foo=ASDJKHQKBZXOQWEOPIUAXQWEOIU; max-age=3600; version=1",
+                    ),
+                ]),
+                true,
+            ),
+        );
+        assert_eq!(decoder.size, 215);
+        assert_eq!(decoder.table.len(), 3);
+    }
+
+    #[test]
+    fn test_huffman_rejects_bad_padding() {
+        let huffman = Huffman::new();
+        // "a" is 00011, padded with ones.
+        assert_eq!(huffman.decode(&[0x1f]).unwrap(), b"a");
+        // Padded with zeros instead.
+        assert!(huffman.decode(&[0x18]).is_err());
+        // A whole byte of padding.
+        assert!(huffman.decode(&[0x1f, 0xff]).is_err());
+    }
+}
@@ -1,24 +1,40 @@
 use std::mem;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::task::Poll;
 
 use anyhow::{anyhow, bail};
+use bytes::Bytes;
+use indexmap::IndexMap;
 use tokio::io::{AsyncRead, AsyncWrite};
 
+use super::charset;
+use super::content_encoding;
+use super::http2::Http2Runner;
+#[cfg(feature = "native")]
+use super::http3::Http3Runner;
+#[cfg(feature = "native")]
+use super::quic::QuicRunner;
+use super::raw_http2::RawHttp2Runner;
 use super::raw_tcp::RawTcpRunner;
 use super::runner::Runner;
+use super::similarity;
 use super::tcp::TcpRunner;
 use super::tls::TlsRunner;
 use super::{http1::Http1Runner, Context};
 use crate::{
-    HttpOutput, HttpPlanOutput, HttpRequestOutput, HttpResponse, MaybeUtf8, ProtocolDiscriminants,
-    RawTcpPlanOutput, TcpPlanOutput, TlsPlanOutput,
+    Http2PlanOutput, HttpHeader, HttpOutput, HttpPlanOutput, HttpRequestOutput, HttpResponse,
+    MaybeUtf8, ProtocolDiscriminants, RawHttp2PlanOutput, RawTcpPlanOutput, SimilarityHash,
+    TcpPlanOutput, TlsPlanOutput,
 };
+#[cfg(feature = "native")]
+use crate::{Http3PlanOutput, QuicPlanOutput};
 
 #[derive(Debug)]
 pub(super) struct HttpRunner {
     inner: HttpProtocol,
     state: State,
+    similarity: Vec<SimilarityHash>,
 }
 
 #[derive(Debug)]
@@ -30,7 +46,26 @@ enum State {
 
 #[derive(Debug)]
 enum HttpProtocol {
+    /// Waiting for TLS to negotiate which version to use.
+    Negotiating {
+        http1: Box<Http1Runner>,
+        http2: Box<Http2Runner>,
+        raw: Box<RawHttp2Runner>,
+    },
     Http1(Http1Runner),
+    Http2(Box<Http2Runner>),
+    #[cfg(feature = "native")]
+    Http3(Box<Http3Runner>),
+    Invalid,
+}
+
+fn not_negotiated() -> std::io::Error {
+    std::io::Error::other(anyhow!("http version has not been negotiated"))
+}
+
+#[cfg(feature = "native")]
+fn not_stream() -> std::io::Error {
+    std::io::Error::other(anyhow!("http3 can't be used as a stream transport"))
 }
 
 impl AsyncRead for HttpRunner {
@@ -41,6 +76,12 @@ impl AsyncRead for HttpRunner {
     ) -> std::task::Poll<std::io::Result<()>> {
         match self.inner {
             HttpProtocol::Http1(ref mut r) => Pin::new(r).poll_read(cx, buf),
+            HttpProtocol::Http2(ref mut r) => Pin::new(r.as_mut()).poll_read(cx, buf),
+            #[cfg(feature = "native")]
+            HttpProtocol::Http3(_) => Poll::Ready(Err(not_stream())),
+            HttpProtocol::Negotiating { .. } | HttpProtocol::Invalid => {
+                Poll::Ready(Err(not_negotiated()))
+            }
         }
     }
 }
@@ -53,6 +94,12 @@ impl AsyncWrite for HttpRunner {
     ) -> std::task::Poll<Result<usize, std::io::Error>> {
         match self.inner {
             HttpProtocol::Http1(ref mut s) => Pin::new(s).poll_write(cx, buf),
+            HttpProtocol::Http2(ref mut s) => Pin::new(s.as_mut()).poll_write(cx, buf),
+            #[cfg(feature = "native")]
+            HttpProtocol::Http3(_) => Poll::Ready(Err(not_stream())),
+            HttpProtocol::Negotiating { .. } | HttpProtocol::Invalid => {
+                Poll::Ready(Err(not_negotiated()))
+            }
         }
     }
     fn poll_flush(
@@ -61,6 +108,12 @@ impl AsyncWrite for HttpRunner {
     ) -> std::task::Poll<Result<(), std::io::Error>> {
         match self.inner {
             HttpProtocol::Http1(ref mut s) => Pin::new(s).poll_flush(cx),
+            HttpProtocol::Http2(ref mut s) => Pin::new(s.as_mut()).poll_flush(cx),
+            #[cfg(feature = "native")]
+            HttpProtocol::Http3(_) => Poll::Ready(Err(not_stream())),
+            HttpProtocol::Negotiating { .. } | HttpProtocol::Invalid => {
+                Poll::Ready(Err(not_negotiated()))
+            }
         }
     }
     fn poll_shutdown(
@@ -69,20 +122,35 @@ impl AsyncWrite for HttpRunner {
     ) -> std::task::Poll<Result<(), std::io::Error>> {
         match self.inner {
             HttpProtocol::Http1(ref mut s) => Pin::new(s).poll_shutdown(cx),
+            HttpProtocol::Http2(ref mut s) => Pin::new(s.as_mut()).poll_shutdown(cx),
+            #[cfg(feature = "native")]
+            HttpProtocol::Http3(_) => Poll::Ready(Err(not_stream())),
+            HttpProtocol::Negotiating { .. } | HttpProtocol::Invalid => {
+                Poll::Ready(Err(not_negotiated()))
+            }
         }
     }
 }
 
 impl HttpRunner {
     pub(super) fn new(ctx: Arc<Context>, plan: HttpPlanOutput) -> crate::Result<Self> {
+        if plan.http3 {
+            #[cfg(feature = "native")]
+            return Self::new_http3(ctx, plan);
+            #[cfg(not(feature = "native"))]
+            bail!("http.http3 requires the native feature");
+        }
+        let similarity = plan.similarity.clone();
+
+        // Only offer h2 for requests it can represent, so anything else still goes out as
+        // written over HTTP/1.1.
+        let offer_http2 = plan.url.scheme() == "https" && http2_compatible(&plan);
         let mut transports = if plan.url.scheme() == "https" {
             Vec::with_capacity(2)
         } else {
             Vec::with_capacity(1)
         };
 
-        // For now we always use TCP and possibly TLS. To support HTTP/3 we'll need to decide
-        // whether to use UPD and QUIC instead.
         transports.push(Runner::RawTcp(Box::new(RawTcpRunner::new(
             ctx.clone(),
             RawTcpPlanOutput {
@@ -119,6 +187,10 @@ impl HttpRunner {
                     .port_or_known_default()
                     .ok_or_else(|| anyhow!("url is missing port"))?,
                 body: MaybeUtf8::default(),
+                proxies: Vec::new(),
+                proxy_protocol: None,
+                fault: None,
+                expect: Vec::new(),
                 //close: TcpPlanCloseOutput::default(),
             },
         ))));
@@ -136,26 +208,140 @@ impl HttpRunner {
                         .url
                         .port_or_known_default()
                         .ok_or_else(|| anyhow!("url is missing port"))?,
-                    alpn: vec![MaybeUtf8("http/1.1".into()) /*, b"h2".to_vec()*/],
+                    server_name: None,
+                    alpn: if offer_http2 {
+                        vec![MaybeUtf8("h2".into()), MaybeUtf8("http/1.1".into())]
+                    } else {
+                        vec![MaybeUtf8("http/1.1".into())]
+                    },
                     body: MaybeUtf8::default(),
+                    min_version: None,
+                    max_version: None,
+                    cipher_suites: Vec::new(),
+                    pin: None,
+                    client: None,
+                    ca: None,
+                    ca_file: None,
+                    insecure: false,
+                    resume: None,
+                    session_tickets: true,
+                    expect: Vec::new(),
                 },
-            ))))
+            )?)))
         }
 
-        Ok(HttpRunner {
-            state: State::Pending { transports },
-            inner: HttpProtocol::Http1(Http1Runner::new(
+        let http1 = Http1Runner::new(
+            ctx.clone(),
+            crate::Http1PlanOutput {
+                url: plan.url.clone(),
+                method: plan.method.clone(),
+                version_string: Some(MaybeUtf8("HTTP/1.1".into())),
+                add_content_length: plan.add_content_length,
+                headers: plan.headers.clone(),
+                body: plan.body.clone(),
+                sign: None,
+                cache: crate::CacheMode::Off,
+                chunked: None,
+                expect_continue: None,
+                head: None,
+                read: None,
+                permissive: false,
+            },
+            ProtocolDiscriminants::Http,
+        );
+        let inner = if offer_http2 {
+            let raw = RawHttp2Runner::new(
+                ctx.clone(),
+                RawHttp2PlanOutput {
+                    host: plan
+                        .url
+                        .host()
+                        .ok_or_else(|| anyhow!("url is missing host"))?
+                        .to_string(),
+                    port: plan
+                        .url
+                        .port_or_known_default()
+                        .ok_or_else(|| anyhow!("url is missing port"))?,
+                    preamble: None,
+                    frames: Vec::new(),
+                },
+                ProtocolDiscriminants::RawH2,
+                false,
+            );
+            let http2 = Http2Runner::new(
                 ctx,
-                crate::Http1PlanOutput {
+                Http2PlanOutput {
                     url: plan.url,
-                    method: plan.method,
-                    version_string: Some(MaybeUtf8("HTTP/1.1".into())),
+                    method: Some(plan.method.unwrap_or_else(|| MaybeUtf8("GET".into()))),
                     add_content_length: plan.add_content_length,
                     headers: plan.headers,
+                    trailers: Vec::new(),
                     body: plan.body,
                 },
                 ProtocolDiscriminants::Http,
-            )),
+            )?;
+            HttpProtocol::Negotiating {
+                http1: Box::new(http1),
+                http2: Box::new(http2),
+                raw: Box::new(raw),
+            }
+        } else {
+            HttpProtocol::Http1(http1)
+        };
+
+        Ok(HttpRunner {
+            state: State::Pending { transports },
+            inner,
+            similarity,
+        })
+    }
+
+    /// HTTP/3 isn't negotiated over TCP, so requesting it swaps the whole stack for QUIC.
+    #[cfg(feature = "native")]
+    fn new_http3(ctx: Arc<Context>, plan: HttpPlanOutput) -> crate::Result<Self> {
+        if plan.url.scheme() != "https" {
+            bail!("http.http3 requires an https url");
+        }
+        if !http2_compatible(&plan) {
+            bail!(
+                "http.http3 requires a UTF-8 method, valid header names, and no \
+                connection-specific headers"
+            );
+        }
+        let similarity = plan.similarity.clone();
+        let quic = QuicRunner::new(
+            ctx.clone(),
+            QuicPlanOutput {
+                host: plan
+                    .url
+                    .host()
+                    .ok_or_else(|| anyhow!("url is missing host"))?
+                    .to_string(),
+                port: plan
+                    .url
+                    .port_or_known_default()
+                    .ok_or_else(|| anyhow!("url is missing port"))?,
+                alpn: vec![MaybeUtf8("h3".into())],
+                tls_version: None,
+            },
+        );
+        let http3 = Http3Runner::new(
+            ctx,
+            Http3PlanOutput {
+                url: plan.url,
+                method: Some(plan.method.unwrap_or_else(|| MaybeUtf8("GET".into()))),
+                add_content_length: plan.add_content_length,
+                headers: plan.headers,
+                body: plan.body,
+            },
+            ProtocolDiscriminants::Http,
+        );
+        Ok(HttpRunner {
+            state: State::Pending {
+                transports: vec![Runner::Quic(Box::new(quic))],
+            },
+            inner: HttpProtocol::Http3(Box::new(http3)),
+            similarity,
         })
     }
 
@@ -165,6 +351,16 @@ impl HttpRunner {
         };
         let mut size_hint = match &mut self.inner {
             HttpProtocol::Http1(p) => p.size_hint(size_hint),
+            HttpProtocol::Http2(p) => p.size_hint(size_hint),
+            #[cfg(feature = "native")]
+            HttpProtocol::Http3(p) => p.size_hint(size_hint),
+            // Either version may be used, so both need the hint. Only HTTP/1.1 streams directly
+            // over the transports.
+            HttpProtocol::Negotiating { http1, http2, .. } => {
+                http2.size_hint(size_hint);
+                http1.size_hint(size_hint)
+            }
+            HttpProtocol::Invalid => panic!("invalid protocol to call size_hint"),
         };
         for t in transports.iter_mut().rev() {
             size_hint = t.size_hint(size_hint);
@@ -175,6 +371,11 @@ impl HttpRunner {
     pub fn executor_size_hint(&self) -> Option<usize> {
         match &self.inner {
             HttpProtocol::Http1(r) => r.executor_size_hint(),
+            HttpProtocol::Http2(r) => r.executor_size_hint(),
+            #[cfg(feature = "native")]
+            HttpProtocol::Http3(r) => r.executor_size_hint(),
+            HttpProtocol::Negotiating { http1, .. } => http1.executor_size_hint(),
+            HttpProtocol::Invalid => None,
         }
     }
 
@@ -191,22 +392,73 @@ impl HttpRunner {
         }
         let transport = transport.expect("http should always provide a transport");
 
+        // Use whichever version TLS negotiated.
+        match mem::replace(&mut self.inner, HttpProtocol::Invalid) {
+            HttpProtocol::Negotiating {
+                http1,
+                mut http2,
+                mut raw,
+            } => {
+                let negotiated = match &transport {
+                    Runner::Tls(tls) => tls.alpn().is_some_and(|alpn| alpn.as_bytes() == b"h2"),
+                    _ => false,
+                };
+                if !negotiated {
+                    self.inner = HttpProtocol::Http1(*http1);
+                } else {
+                    let result = match raw.start(transport, 1).await {
+                        Ok(()) => http2.start(*raw).await,
+                        Err(e) => {
+                            http2.start_failed(*raw);
+                            Err(e)
+                        }
+                    };
+                    self.inner = HttpProtocol::Http2(http2);
+                    return result;
+                }
+            }
+            inner => self.inner = inner,
+        }
         match &mut self.inner {
             HttpProtocol::Http1(r) => r.start(transport).await,
+            #[cfg(feature = "native")]
+            HttpProtocol::Http3(r) => match transport {
+                Runner::Quic(transport) => r.start(*transport).await,
+                _ => bail!("http3 requires a quic transport"),
+            },
+            HttpProtocol::Http2(_) | HttpProtocol::Negotiating { .. } | HttpProtocol::Invalid => {
+                bail!("invalid protocol to call start")
+            }
         }
     }
 
     pub async fn execute(&mut self) {
         match &mut self.inner {
             HttpProtocol::Http1(r) => r.execute().await,
+            HttpProtocol::Http2(r) => r.execute().await,
+            #[cfg(feature = "native")]
+            HttpProtocol::Http3(r) => r.execute().await,
+            HttpProtocol::Negotiating { .. } | HttpProtocol::Invalid => {}
         }
     }
 
-    pub fn finish(self) -> (HttpOutput, Option<Runner>) {
-        let protocol = "HTTP/1.1";
+    pub async fn finish(self) -> (HttpOutput, Option<Runner>) {
+        let similarity = self.similarity;
         match self.inner {
-            HttpProtocol::Http1(r) => {
-                let (out, inner) = r.finish();
+            HttpProtocol::Http1(r) => finish_http1(r, similarity),
+            // The transports failed to start before a version was negotiated.
+            HttpProtocol::Negotiating { http1, .. } => finish_http1(*http1, similarity),
+            HttpProtocol::Http2(r) => {
+                let protocol = "HTTP/2";
+                let (out, inner) = r.finish().await;
+                let mut errors: Vec<_> = out
+                    .errors
+                    .into_iter()
+                    .map(|e| crate::HttpError {
+                        kind: e.kind,
+                        message: e.message,
+                    })
+                    .collect();
                 (
                     HttpOutput {
                         name: out.name,
@@ -216,6 +468,8 @@ impl HttpRunner {
                             add_content_length: out.plan.add_content_length,
                             headers: out.plan.headers,
                             body: out.plan.body,
+                            http3: false,
+                            similarity: similarity.clone(),
                         },
                         request: out.request.map(|req| {
                             let req = Arc::unwrap_or_clone(req);
@@ -233,31 +487,268 @@ impl HttpRunner {
                         }),
                         response: out.response.map(|resp| {
                             let resp = Arc::unwrap_or_clone(resp);
+                            let (body, encoded_body, content_encoding) =
+                                decompress(&resp.headers, resp.body, &mut errors);
+                            let (charset, text) = decode(&resp.headers, &body);
+                            let (simhash, ssdeep) = hash(&similarity, &body);
                             Arc::new(HttpResponse {
                                 name: resp.name,
-                                protocol: resp.protocol,
+                                protocol: Some(MaybeUtf8(protocol.into())),
                                 status_code: resp.status_code,
+                                header_map: header_map(&resp.headers),
                                 headers: resp.headers,
-                                body: resp.body,
+                                raw_header: None,
+                                body,
+                                raw_body: None,
+                                encoded_body,
+                                content_encoding,
+                                charset,
+                                text,
+                                simhash,
+                                ssdeep,
                                 duration: resp.duration,
                                 header_duration: resp.header_duration,
                                 time_to_first_byte: resp.time_to_first_byte,
                             })
                         }),
-                        errors: out
-                            .errors
-                            .into_iter()
-                            .map(|e| crate::HttpError {
-                                kind: e.kind,
-                                message: e.message,
+                        errors,
+                        protocol: Some(protocol.to_string()),
+                        duration: out.duration,
+                    },
+                    inner.map(|inner| Runner::RawH2(Box::new(inner))),
+                )
+            }
+            #[cfg(feature = "native")]
+            HttpProtocol::Http3(r) => {
+                let protocol = "HTTP/3";
+                let (out, inner) = r.finish().await;
+                let mut errors: Vec<_> = out
+                    .errors
+                    .into_iter()
+                    .map(|e| crate::HttpError {
+                        kind: e.kind,
+                        message: e.message,
+                    })
+                    .collect();
+                (
+                    HttpOutput {
+                        name: out.name,
+                        plan: HttpPlanOutput {
+                            url: out.plan.url,
+                            method: out.plan.method,
+                            add_content_length: out.plan.add_content_length,
+                            headers: out.plan.headers,
+                            body: out.plan.body,
+                            http3: true,
+                            similarity: similarity.clone(),
+                        },
+                        request: out.request.map(|req| {
+                            let req = Arc::unwrap_or_clone(req);
+                            Arc::new(HttpRequestOutput {
+                                name: req.name,
+                                url: req.url,
+                                protocol: MaybeUtf8(protocol.into()),
+                                method: req.method,
+                                headers: req.headers,
+                                body: req.body,
+                                duration: req.duration,
+                                body_duration: req.body_duration,
+                                time_to_first_byte: req.time_to_first_byte,
                             })
-                            .collect(),
+                        }),
+                        response: out.response.map(|resp| {
+                            let resp = Arc::unwrap_or_clone(resp);
+                            let (body, encoded_body, content_encoding) =
+                                decompress(&resp.headers, resp.body, &mut errors);
+                            let (charset, text) = decode(&resp.headers, &body);
+                            let (simhash, ssdeep) = hash(&similarity, &body);
+                            Arc::new(HttpResponse {
+                                name: resp.name,
+                                protocol: Some(MaybeUtf8(protocol.into())),
+                                status_code: resp.status_code,
+                                header_map: header_map(&resp.headers),
+                                headers: resp.headers,
+                                raw_header: None,
+                                body,
+                                raw_body: None,
+                                encoded_body,
+                                content_encoding,
+                                charset,
+                                text,
+                                simhash,
+                                ssdeep,
+                                duration: resp.duration,
+                                header_duration: resp.header_duration,
+                                time_to_first_byte: resp.time_to_first_byte,
+                            })
+                        }),
+                        errors,
                         protocol: Some(protocol.to_string()),
                         duration: out.duration,
                     },
-                    inner,
+                    inner.map(|inner| Runner::Quic(Box::new(inner))),
                 )
             }
+            HttpProtocol::Invalid => panic!("invalid protocol to finish http"),
+        }
+    }
+}
+
+fn finish_http1(r: Http1Runner, similarity: Vec<SimilarityHash>) -> (HttpOutput, Option<Runner>) {
+    let protocol = "HTTP/1.1";
+    let (out, inner) = r.finish();
+    (
+        HttpOutput {
+            name: out.name,
+            plan: HttpPlanOutput {
+                url: out.plan.url,
+                method: out.plan.method,
+                add_content_length: out.plan.add_content_length,
+                headers: out.plan.headers,
+                body: out.plan.body,
+                http3: false,
+                similarity: similarity.clone(),
+            },
+            request: out.request.map(|req| {
+                let req = Arc::unwrap_or_clone(req);
+                Arc::new(HttpRequestOutput {
+                    name: req.name,
+                    url: req.url,
+                    protocol: MaybeUtf8(protocol.into()),
+                    method: req.method,
+                    headers: req.headers,
+                    body: req.body,
+                    duration: req.duration,
+                    body_duration: req.body_duration,
+                    time_to_first_byte: req.time_to_first_byte,
+                })
+            }),
+            response: out.response.map(|resp| {
+                let resp = Arc::unwrap_or_clone(resp);
+                let (charset, text) = decode(&resp.headers, &resp.body);
+                let (simhash, ssdeep) = hash(&similarity, &resp.body);
+                Arc::new(HttpResponse {
+                    name: resp.name,
+                    protocol: resp.protocol,
+                    status_code: resp.status_code,
+                    header_map: header_map(&resp.headers),
+                    headers: resp.headers,
+                    raw_header: resp.raw_header,
+                    body: resp.body,
+                    raw_body: resp.raw_body,
+                    encoded_body: resp.encoded_body,
+                    content_encoding: resp.content_encoding,
+                    charset,
+                    text,
+                    simhash,
+                    ssdeep,
+                    duration: resp.duration,
+                    header_duration: resp.header_duration,
+                    time_to_first_byte: resp.time_to_first_byte,
+                })
+            }),
+            errors: out
+                .errors
+                .into_iter()
+                .map(|e| crate::HttpError {
+                    kind: e.kind,
+                    message: e.message,
+                })
+                .collect(),
+            protocol: Some(protocol.to_string()),
+            duration: out.duration,
+        },
+        inner,
+    )
+}
+
+/// Decode a response body using its detected charset.
+fn decode(
+    headers: &Option<Vec<HttpHeader>>,
+    body: &Option<MaybeUtf8>,
+) -> (Option<String>, Option<String>) {
+    body.as_ref()
+        .and_then(|body| charset::decode(headers.as_deref().unwrap_or_default(), body))
+        .unzip()
+}
+
+/// Remove the content codings from an HTTP/2 or HTTP/3 response body. Returns the body, the
+/// body as received if it was decoded, and the codings.
+fn decompress(
+    headers: &Option<Vec<HttpHeader>>,
+    body: Option<MaybeUtf8>,
+    errors: &mut Vec<crate::HttpError>,
+) -> (Option<MaybeUtf8>, Option<MaybeUtf8>, Option<String>) {
+    let Some((coding, decoded)) = body.as_ref().and_then(|body| {
+        content_encoding::decompress(headers.as_deref().unwrap_or_default(), body.as_bytes())
+    }) else {
+        return (body, None, None);
+    };
+    match decoded {
+        Ok(decoded) => (
+            Some(MaybeUtf8(Bytes::from(decoded).into())),
+            body,
+            Some(coding),
+        ),
+        Err(e) => {
+            errors.push(crate::HttpError {
+                kind: "decompress".to_owned(),
+                message: format!("{e:#}"),
+            });
+            (body, None, Some(coding))
         }
     }
 }
+
+/// Group header values by lowercase name, in the order each name was first received.
+fn header_map(headers: &Option<Vec<HttpHeader>>) -> Option<IndexMap<String, Vec<MaybeUtf8>>> {
+    let mut map = IndexMap::<_, Vec<_>>::new();
+    for header in headers.as_ref()? {
+        let Some(key) = &header.key else {
+            continue;
+        };
+        map.entry(String::from_utf8_lossy(key).to_ascii_lowercase())
+            .or_default()
+            .push(header.value.clone());
+    }
+    Some(map)
+}
+
+/// Compute the simhash and ssdeep hash of a response body, for those the plan asks for.
+fn hash(hashes: &[SimilarityHash], body: &Option<MaybeUtf8>) -> (Option<String>, Option<String>) {
+    let body = body
+        .as_ref()
+        .map(|body| body.as_bytes())
+        .unwrap_or_default();
+    (
+        hashes
+            .contains(&SimilarityHash::Simhash)
+            .then(|| similarity::simhash(body)),
+        hashes
+            .contains(&SimilarityHash::Ssdeep)
+            .then(|| similarity::ssdeep(body)),
+    )
+}
+
+/// Whether h2 or h3 can send the request as planned. HTTP/2 and HTTP/3 require a UTF-8 method,
+/// valid lowercase header names, and no connection-specific headers.
+fn http2_compatible(plan: &HttpPlanOutput) -> bool {
+    plan.method
+        .as_ref()
+        .map_or(true, |method| method.as_str().is_some())
+        && plan.headers.iter().all(|header| {
+            let Some(key) = &header.key else {
+                return false;
+            };
+            http::HeaderName::from_bytes(key.as_bytes()).is_ok_and(|name| {
+                !matches!(
+                    name.as_str(),
+                    "connection"
+                        | "keep-alive"
+                        | "proxy-connection"
+                        | "transfer-encoding"
+                        | "upgrade"
+                )
+            }) && http::HeaderValue::from_bytes(header.value.as_bytes()).is_ok()
+        })
+}
@@ -6,7 +6,6 @@ use std::time::Instant;
 
 use anyhow::anyhow;
 use anyhow::bail;
-use bytes::Buf;
 use bytes::BufMut;
 use bytes::Bytes;
 use bytes::BytesMut;
@@ -17,15 +16,27 @@ use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tracing::debug;
 use tracing::instrument;
 
+use super::cache::HttpCache;
+use super::content_encoding;
+use super::cookies::CookieJar;
 use super::pause;
 use super::pause::PauseSpec;
 use super::pause::PauseStream;
+use super::permissive;
 use super::runner::Runner;
+use super::sign;
 use super::Context;
 use crate::AddContentLength;
+use crate::CacheMode;
+use crate::ExpectContinueOutcome;
+use crate::Http1ChunkedPlanOutput;
+use crate::Http1Deviation;
 use crate::Http1Error;
+use crate::Http1InformationalResponse;
+use crate::Http1PipelineOutput;
 use crate::Http1PlanOutput;
 use crate::Http1RequestOutput;
+use crate::HttpCacheOutput;
 use crate::HttpHeader;
 use crate::MaybeUtf8;
 use crate::PduName;
@@ -35,6 +46,7 @@ use crate::{Http1Output, Http1Response};
 
 #[derive(Debug)]
 pub(super) struct Http1Runner {
+    ctx: Arc<Context>,
     out: Http1Output,
     state: State,
     start_time: Option<Instant>,
@@ -48,20 +60,47 @@ pub(super) struct Http1Runner {
     resp_header_buf: BytesMut,
     req_body_buf: BytesMut,
     resp_body_buf: BytesMut,
+    /// How far resp_body_buf has been scanned for the end of a chunked body.
+    chunked_scan: ChunkedScan,
+    /// Where the next search of resp_body_buf for the plan's read pattern starts.
+    pattern_from: usize,
     size_hint: Option<usize>,
     send_headers: Vec<HttpHeader>,
+    /// The body as written, after any chunked encoding.
+    send_body: MaybeUtf8,
+    cache: Arc<HttpCache>,
+    cookies: Option<Arc<CookieJar>>,
 }
 
 #[derive(Debug)]
 enum State {
-    Pending { ctx: Arc<Context> },
-    Ready { ctx: Arc<Context>, header: BytesMut },
-    StartFailed { transport: Runner },
-    SendingHeader { transport: PauseStream<Runner> },
-    SendingBody { transport: PauseStream<Runner> },
-    ReceivingHeader { transport: PauseStream<Runner> },
-    ReceivingBody { transport: PauseStream<Runner> },
-    Complete { transport: Option<Runner> },
+    Pending {
+        ctx: Arc<Context>,
+    },
+    Ready {
+        ctx: Arc<Context>,
+        header: BytesMut,
+    },
+    StartFailed {
+        transport: Runner,
+    },
+    SendingHeader {
+        transport: PauseStream<Runner>,
+    },
+    SendingBody {
+        transport: PauseStream<Runner>,
+    },
+    /// Sent as part of a pipeline, with the connection lent to the requests after it.
+    Pipelined,
+    ReceivingHeader {
+        transport: PauseStream<Runner>,
+    },
+    ReceivingBody {
+        transport: PauseStream<Runner>,
+    },
+    Complete {
+        transport: Option<Runner>,
+    },
     Invalid,
 }
 
@@ -125,10 +164,24 @@ impl AsyncRead for Http1Runner {
                     }
                     _ => State::ReceivingHeader { transport },
                 };
+                // A header read alone would look like the end of the stream, so keep reading the
+                // body unless it's already complete.
+                if matches!(poll, Poll::Ready(Ok(())))
+                    && buf.filled().is_empty()
+                    && self.body_end().is_none()
+                {
+                    return self.poll_read(cx, buf);
+                }
                 poll
             }
 
             State::ReceivingBody { mut transport } => {
+                // End the stream once the body's framing is complete so the connection can be
+                // reused for later requests.
+                if self.body_end().is_some() {
+                    self.state = State::ReceivingBody { transport };
+                    return Poll::Ready(Ok(()));
+                }
                 let old_len = buf.filled().len();
                 let poll = pin!(&mut transport).poll_read(cx, buf);
                 self.resp_body_buf
@@ -208,16 +261,27 @@ impl Http1Runner {
     ) -> Self {
         Self {
             send_headers: plan.headers.clone(),
+            send_body: match &plan.chunked {
+                Some(chunked) => MaybeUtf8(encode_chunked(&plan.body, chunked).freeze().into()),
+                None => plan.body.clone(),
+            },
+            cache: ctx.cache.clone(),
+            cookies: ctx.cookies.clone(),
             out: Http1Output {
                 name: ProtocolName::with_job(ctx.job_name.clone(), protocol),
                 request: None,
                 response: None,
+                informational_responses: Vec::new(),
+                expect_continue: None,
+                pipeline: None,
+                cache: None,
                 errors: Vec::new(),
                 duration: TimeDelta::zero().into(),
                 //pause: crate::Http1PauseOutput::with_planned_capacity(&plan.pause),
                 plan,
             },
-            state: State::Pending { ctx },
+            state: State::Pending { ctx: ctx.clone() },
+            ctx,
             start_time: None,
             req_header_start_time: None,
             req_body_start_time: None,
@@ -229,6 +293,8 @@ impl Http1Runner {
             resp_header_buf: BytesMut::new(),
             req_body_buf: BytesMut::new(),
             resp_body_buf: BytesMut::new(),
+            chunked_scan: ChunkedScan::default(),
+            pattern_from: 0,
             size_hint: None,
         }
     }
@@ -292,27 +358,37 @@ impl Http1Runner {
         // reading the header.
         // TODO: optimize this to avoid the intermediate allocation and write.
         let mut header_vec = vec![0; buf.remaining() + 1];
+        // Bytes read while waiting for 100 Continue may already hold the response.
+        let mut read = self.resp_header_buf.is_empty();
         loop {
-            let mut header_buf = ReadBuf::new(header_vec.as_mut());
-            let poll = pin!(&mut *transport).poll_read(cx, &mut header_buf);
-            // Record when we first get any response data.
-            if poll.is_ready() && self.first_read.is_none() {
-                self.first_read = Some(Instant::now());
-            }
-            self.resp_header_buf.put_slice(header_buf.filled());
-            match poll {
-                Poll::Pending => return Poll::Pending,
-                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
-                // If no data was read then the stream has ended.
-                Poll::Ready(Ok(())) => {
-                    if header_buf.filled().len() == 0 {
-                        return Poll::Ready(Err(std::io::Error::new(
-                            std::io::ErrorKind::UnexpectedEof,
-                            "header incomplete".to_owned(),
-                        )));
+            if read {
+                let mut header_buf = ReadBuf::new(header_vec.as_mut());
+                let poll = pin!(&mut *transport).poll_read(cx, &mut header_buf);
+                // Record when we first get any response data.
+                if poll.is_ready() && self.first_read.is_none() {
+                    self.first_read = Some(Instant::now());
+                }
+                self.resp_header_buf.put_slice(header_buf.filled());
+                match poll {
+                    Poll::Pending => return Poll::Pending,
+                    Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                    // If no data was read then the stream has ended.
+                    Poll::Ready(Ok(())) => {
+                        if header_buf.filled().len() == 0 {
+                            return Poll::Ready(Err(std::io::Error::new(
+                                std::io::ErrorKind::UnexpectedEof,
+                                "header incomplete".to_owned(),
+                            )));
+                        }
                     }
                 }
             }
+            read = true;
+            // Informational responses come before the final one.
+            self.take_informational();
+            if self.resp_header_buf.is_empty() {
+                continue;
+            }
             // Data was read - try to process it.
             match self.receive_header() {
                 // Not enough data, let's read some more.
@@ -321,7 +397,8 @@ impl Http1Runner {
                 Poll::Ready(Ok(remaining)) => {
                     self.resp_header_end_time = Some(Instant::now());
                     self.resp_body_buf.extend_from_slice(&remaining);
-                    buf.put(remaining);
+                    // Bytes buffered while waiting for 100 Continue may not all fit.
+                    buf.put_slice(&remaining[..remaining.len().min(buf.remaining())]);
                     return Poll::Ready(Ok(()));
                 }
                 Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
@@ -329,83 +406,146 @@ impl Http1Runner {
         }
     }
 
-    #[inline]
-    fn receive_header(&mut self) -> Poll<std::io::Result<BytesMut>> {
-        // TODO: Write our own extra-permissive parser.
-        let mut headers = [httparse::EMPTY_HEADER; 64];
-        let mut resp = httparse::Response::new(&mut headers);
-        match resp.parse(&self.resp_header_buf) {
-            Ok(result) => {
-                let header_complete_time = Instant::now();
-                // Set the header fields in our response.
-                self.out.response = Some(Arc::new(Http1Response {
-                    name: PduName::with_protocol(self.out.name.clone(), 1),
-                    protocol: resp
-                        .version
-                        .map(|v| MaybeUtf8(format!("HTTP/1.{}", v).into())),
-                    status_code: resp.code,
-                    // Use the first valid Content-Length header as the content length, if any.
-                    content_length: resp
-                        .headers
-                        .iter()
-                        .filter(|h| h.name.eq_ignore_ascii_case("content-length"))
-                        .find_map(|h| atoi::atoi(h.value)),
-                    // If the reason hasn't been read yet then also no headers were parsed.
-                    headers: resp.reason.as_ref().map(|_| {
-                        resp.headers
-                            .into_iter()
-                            .map(|h| {
-                                HttpHeader {
-                                    // TODO: We could probably avoid extra copies here since these
-                                    // are backed by a BytesMut, but the current approach reparses
-                                    // the whole buffer so it's not trivial.
-                                    key: Some(MaybeUtf8(Arc::new(h.name.to_owned()).into())),
-                                    value: MaybeUtf8(Bytes::copy_from_slice(h.value).into()),
-                                }
-                            })
-                            .collect()
-                    }),
-                    status_reason: resp
-                        .reason
-                        .map(|r| MaybeUtf8(Arc::new(r.to_owned()).into())),
-                    body: None,
-                    duration: TimeDelta::zero().into(),
-                    header_duration: None,
-                    time_to_first_byte: self
-                        .first_read
-                        .map(|first_read| {
-                            self.resp_start_time
-                                .map(|start| first_read - start)
-                                .unwrap_or_default()
-                        })
-                        .map(TimeDelta::from_std)
-                        .transpose()
-                        .expect("durations should fit in std")
-                        .map(Duration),
-                }));
-                match result {
-                    httparse::Status::Partial => Poll::Pending,
-                    httparse::Status::Complete(body_start) => {
-                        Arc::make_mut(self.out.response.as_mut().unwrap()).header_duration = Some(
-                            TimeDelta::from_std(header_complete_time - self.start_time.unwrap())
-                                .unwrap()
-                                .into(),
-                        );
-                        // Return the bytes we didn't read.
-                        self.resp_header_buf.advance(body_start);
-                        Poll::Ready(Ok(std::mem::take(&mut self.resp_header_buf)))
-                    }
+    /// Record the complete informational responses at the start of the response buffer, removing
+    /// them so the final response can be parsed. Returns whether there were any.
+    fn take_informational(&mut self) -> bool {
+        let mut found = false;
+        loop {
+            let Ok(head) = self.parse_head() else {
+                return found;
+            };
+            let Some(len) = head.len else {
+                return found;
+            };
+            // 101 Switching Protocols ends the exchange, so it counts as a final response.
+            let Some(status_code) = head
+                .status_code
+                .filter(|code| code / 100 == 1 && *code != 101)
+            else {
+                return found;
+            };
+            let time = self
+                .req_header_start_time
+                .map(|start| start.elapsed())
+                .map(TimeDelta::from_std)
+                .transpose()
+                .expect("durations should fit in std")
+                .unwrap_or_default();
+            self.out
+                .informational_responses
+                .push(Http1InformationalResponse {
+                    status_code,
+                    status_reason: head.reason,
+                    headers: head.headers.unwrap_or_default(),
+                    time: Duration(time),
+                });
+            let _ = self.resp_header_buf.split_to(len);
+            found = true;
+        }
+    }
+
+    /// Wait for the server to answer the request's header before sending the body. Informational
+    /// responses are recorded as they arrive, and a final response is left to be read as usual.
+    async fn await_continue(&mut self, timeout: TimeDelta) -> ExpectContinueOutcome {
+        let deadline = tokio::time::Instant::now() + timeout.to_std().unwrap_or_default();
+        loop {
+            let State::SendingBody { transport } = &mut self.state else {
+                panic!("unexpected state while waiting for 100 Continue");
+            };
+            let mut chunk = [0; 4096];
+            let len = match tokio::time::timeout_at(deadline, transport.read(&mut chunk)).await {
+                Err(_) => return ExpectContinueOutcome::Timeout,
+                Ok(Ok(0)) => return ExpectContinueOutcome::Closed,
+                Ok(Ok(len)) => len,
+                Ok(Err(e)) => {
+                    self.out.errors.push(Http1Error {
+                        kind: e.kind().to_string(),
+                        message: e.to_string(),
+                    });
+                    return ExpectContinueOutcome::Closed;
                 }
+            };
+            self.resp_header_buf.extend_from_slice(&chunk[..len]);
+            if self.take_informational() {
+                return ExpectContinueOutcome::Continue;
             }
-            Err(e) => {
-                return Poll::Ready(Err(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    anyhow!(e),
-                )))
+            // Anything else complete, or unparsable, means the server won't wait for the body.
+            if !matches!(self.parse_head(), Ok(ResponseHead { len: None, .. })) {
+                return ExpectContinueOutcome::FinalResponse;
             }
         }
     }
 
+    /// Parse the response head read so far, with the permissive parser if the plan set it.
+    fn parse_head(&self) -> std::io::Result<ResponseHead> {
+        if self.out.plan.permissive {
+            return Ok(permissive::parse(&self.resp_header_buf));
+        }
+        parse_strict(&self.resp_header_buf)
+    }
+
+    #[inline]
+    fn receive_header(&mut self) -> Poll<std::io::Result<BytesMut>> {
+        let head = match self.parse_head() {
+            Ok(head) => head,
+            Err(e) => return Poll::Ready(Err(e)),
+        };
+        let header_complete_time = Instant::now();
+        // Use the first valid Content-Length header as the content length, if any.
+        let content_length = head
+            .headers
+            .iter()
+            .flatten()
+            .filter(|h| {
+                h.key
+                    .as_ref()
+                    .is_some_and(|k| k.eq_ignore_ascii_case(b"content-length"))
+            })
+            .find_map(|h| atoi::atoi(h.value.as_slice()));
+        // Set the header fields in our response.
+        self.out.response = Some(Arc::new(Http1Response {
+            name: PduName::with_protocol(self.out.name.clone(), 1),
+            protocol: head.protocol,
+            status_code: head.status_code,
+            content_length,
+            headers: head.headers,
+            status_reason: head.reason,
+            raw_header: None,
+            body: None,
+            raw_body: None,
+            encoded_body: None,
+            content_encoding: None,
+            deviations: head.deviations,
+            duration: TimeDelta::zero().into(),
+            header_duration: None,
+            time_to_first_byte: self
+                .first_read
+                .map(|first_read| {
+                    self.resp_start_time
+                        .map(|start| first_read - start)
+                        .unwrap_or_default()
+                })
+                .map(TimeDelta::from_std)
+                .transpose()
+                .expect("durations should fit in std")
+                .map(Duration),
+        }));
+        let Some(body_start) = head.len else {
+            return Poll::Pending;
+        };
+        let response = Arc::make_mut(self.out.response.as_mut().unwrap());
+        response.header_duration = Some(
+            TimeDelta::from_std(header_complete_time - self.start_time.unwrap())
+                .unwrap()
+                .into(),
+        );
+        response.raw_header = Some(MaybeUtf8(
+            self.resp_header_buf.split_to(body_start).freeze().into(),
+        ));
+        // Return the bytes we didn't read.
+        Poll::Ready(Ok(std::mem::take(&mut self.resp_header_buf)))
+    }
+
     pub fn size_hint(&mut self, size_hint: Option<usize>) -> Option<usize> {
         let State::Pending { ctx } = mem::replace(&mut self.state, State::Invalid) else {
             panic!("invalid state {:?}", self.state);
@@ -413,10 +553,54 @@ impl Http1Runner {
 
         self.size_hint = size_hint;
 
+        // A raw head is sent exactly as written, so nothing is added to it or computed from it.
+        if let Some(head) = &self.out.plan.head {
+            let header = BytesMut::from(head.as_slice());
+            let header_len = header.len();
+            self.send_headers.clear();
+            self.state = State::Ready { ctx, header };
+            return size_hint.map(|hint| header_len + hint);
+        }
+
+        // Declare chunked encoding unless the plan sets its own Transfer-Encoding, which may be
+        // deliberately obfuscated.
+        if self.out.plan.chunked.is_some()
+            && !self.send_headers.iter().any(|h| {
+                h.key
+                    .as_ref()
+                    .is_some_and(|k| k.eq_ignore_ascii_case(b"transfer-encoding"))
+            })
+        {
+            self.send_headers.push(HttpHeader {
+                key: Some(MaybeUtf8("Transfer-Encoding".into())),
+                value: MaybeUtf8("chunked".into()),
+            });
+        }
+
+        // Ask the server to accept the request before the body is sent, unless the plan sets its
+        // own Expect header.
+        if self
+            .out
+            .plan
+            .expect_continue
+            .as_ref()
+            .is_some_and(|expect| expect.header)
+            && !self.send_headers.iter().any(|h| {
+                h.key
+                    .as_ref()
+                    .is_some_and(|k| k.eq_ignore_ascii_case(b"expect"))
+            })
+        {
+            self.send_headers.push(HttpHeader {
+                key: Some(MaybeUtf8("Expect".into())),
+                value: MaybeUtf8("100-continue".into()),
+            });
+        }
+
         // Add a Content-Length header if the size_hint has a value and either:
         //   automatic_content_length is auto (the default),
         //   we don't have a content length header specified,
-        //   and TODO: we aren't using chunked transport encoding
+        //   and we aren't using chunked transfer encoding
         // or
         //   automatic_content_length is force
         if let Some(size_hint) = size_hint {
@@ -431,7 +615,7 @@ impl Http1Runner {
                                 .is_some_and(|k| k.eq_ignore_ascii_case(b"content-length"))
                         })
                         .is_none()
-            //&& self.out.plan.chunked_transfer_encoding != ChunkedTransferEncoding::Force
+                    && self.out.plan.chunked.is_none()
             {
                 self.send_headers.push(HttpHeader {
                     key: Some(MaybeUtf8("Content-Length".into())),
@@ -440,6 +624,63 @@ impl Http1Runner {
             }
         }
 
+        // Headers set explicitly in the plan take precedence over the jar.
+        if let Some(cookies) = &self.cookies {
+            let explicit = self.send_headers.iter().any(|h| {
+                h.key
+                    .as_ref()
+                    .is_some_and(|k| k.eq_ignore_ascii_case(b"cookie"))
+            });
+            if let (false, Some(value)) = (explicit, cookies.header(&self.out.plan.url)) {
+                self.send_headers.push(HttpHeader {
+                    key: Some(MaybeUtf8("Cookie".into())),
+                    value: MaybeUtf8(Arc::new(value).into()),
+                });
+            }
+            for (name, value) in cookies.headers() {
+                let explicit = self.send_headers.iter().any(|h| {
+                    h.key
+                        .as_ref()
+                        .is_some_and(|k| k.eq_ignore_ascii_case(name.as_bytes()))
+                });
+                if !explicit {
+                    self.send_headers.push(HttpHeader {
+                        key: Some(MaybeUtf8(name.into())),
+                        value: MaybeUtf8(value.into()),
+                    });
+                }
+            }
+        }
+
+        if self.out.plan.cache != CacheMode::Off {
+            let mut cache = HttpCacheOutput::default();
+            if self.out.plan.cache == CacheMode::Conditional {
+                let conditional = self.cache.conditional_headers(
+                    self.out.plan.method.as_deref().unwrap_or_default(),
+                    &self.out.plan.url,
+                );
+                cache.conditional = !conditional.is_empty();
+                self.send_headers.extend(conditional);
+            }
+            self.out.cache = Some(cache);
+        }
+
+        // Sign after all other headers are finalized so the signature can cover them.
+        if let Some(plan) = &self.out.plan.sign {
+            if let Err(e) = sign::sign(
+                plan,
+                self.out.plan.method.as_deref().unwrap_or_default(),
+                &self.out.plan.url,
+                &mut self.send_headers,
+                &self.out.plan.body,
+            ) {
+                self.out.errors.push(Http1Error {
+                    kind: "sign".to_owned(),
+                    message: format!("{e:#}"),
+                });
+            }
+        }
+
         let header = Self::compute_header(&self.out.plan, &self.send_headers);
         let header_len = header.len();
         self.state = State::Ready { ctx, header };
@@ -516,6 +757,7 @@ impl Http1Runner {
             headers: self.send_headers.clone(),
             method: self.out.plan.method.clone(),
             version_string: self.out.plan.version_string.clone(),
+            raw_header: self.out.plan.head.clone(),
             body: MaybeUtf8::default(),
             duration: TimeDelta::zero().into(),
             body_duration: None,
@@ -525,34 +767,126 @@ impl Http1Runner {
     }
 
     pub fn executor_size_hint(&self) -> Option<usize> {
-        Some(self.out.plan.body.len())
+        Some(self.send_body.len())
     }
 
     #[instrument]
     pub async fn execute(&mut self) {
         debug!("executing http1");
-        if !self.out.plan.body.is_empty() {
-            let body = std::mem::take(&mut self.out.plan.body);
+        if self.send_body().await {
+            self.receive().await;
+        }
+    }
+
+    /// Write the request's body and hand back the connection without reading the response, so
+    /// the requests after it can be written first. The response is read by [`Self::resume`].
+    pub async fn send_pipelined(&mut self) -> anyhow::Result<Runner> {
+        if self.out.plan.expect_continue.is_some() {
+            bail!("http1.expect_continue can't be used with pipelined requests");
+        }
+        self.send_body().await;
+        let State::SendingBody { transport } = mem::replace(&mut self.state, State::Invalid) else {
+            bail!("attempt to pipeline Http1Runner from invalid state");
+        };
+        self.req_end_time = Some(Instant::now());
+        let (transport, _, _) = transport.finish_stream();
+        self.state = State::Pipelined;
+        Ok(transport)
+    }
+
+    /// Read the response to a pipelined request. `buffered` holds the bytes read past the end of
+    /// the previous response on the connection, and `index` is the request's position on it.
+    pub async fn resume(&mut self, transport: Runner, buffered: Bytes, index: u64) {
+        let State::Pipelined = self.state else {
+            panic!("attempt to resume Http1Runner from invalid state");
+        };
+        let now = Instant::now();
+        self.resp_start_time = Some(now);
+        // A buffered response arrived before it was waited for.
+        if !buffered.is_empty() {
+            self.first_read = Some(now);
+        }
+        self.out.pipeline = Some(Http1PipelineOutput {
+            index,
+            buffered_bytes: buffered.len() as u64,
+        });
+        self.resp_header_buf.extend_from_slice(&buffered);
+        self.state = State::ReceivingHeader {
+            transport: pause::new_stream(self.ctx.clone(), transport, [], []),
+        };
+        self.receive().await;
+    }
+
+    /// Remove the bytes read past the end of the response's body, which belong to the responses
+    /// after it on a pipelined connection.
+    pub fn take_overflow(&mut self) -> Bytes {
+        match self.body_end() {
+            Some(end) if end < self.resp_body_buf.len() => {
+                self.resp_body_buf.split_off(end).freeze()
+            }
+            _ => Bytes::new(),
+        }
+    }
+
+    /// Send the body unless waiting for 100 Continue showed the server won't read it, then flush
+    /// the request. Returns false if writing failed.
+    async fn send_body(&mut self) -> bool {
+        let timeout = self.out.plan.expect_continue.as_ref().map(|e| e.timeout.0);
+        let send_body = match timeout {
+            Some(timeout) if !self.send_body.is_empty() => {
+                let outcome = self.await_continue(timeout).await;
+                self.out.expect_continue = Some(outcome);
+                matches!(
+                    outcome,
+                    ExpectContinueOutcome::Continue | ExpectContinueOutcome::Timeout
+                )
+            }
+            _ => true,
+        };
+        if send_body && !self.send_body.is_empty() {
+            let body = self.send_body.clone();
             if let Err(e) = self.write_all(body.as_slice()).await {
                 self.out.errors.push(Http1Error {
                     kind: e.kind().to_string(),
                     message: e.to_string(),
                 });
-                return;
+                return false;
             }
             debug!("wrote body: {body}");
-            self.out.plan.body = body;
         }
         if let Err(e) = self.flush().await {
             self.out.errors.push(Http1Error {
                 kind: e.kind().to_string(),
                 message: e.to_string(),
             });
-            return;
+            return false;
         }
         debug!("flushed");
+        true
+    }
+
+    async fn receive(&mut self) {
+        let idle = self
+            .out
+            .plan
+            .read
+            .as_ref()
+            .and_then(|read| read.idle.as_ref())
+            .and_then(|idle| idle.0.to_std().ok());
         let mut response = Vec::new();
-        if let Err(e) = self.read_to_end(&mut response).await {
+        let result = match idle {
+            Some(idle) => loop {
+                match tokio::time::timeout(idle, self.read_buf(&mut response)).await {
+                    Ok(Ok(0)) => break Ok(()),
+                    Ok(Ok(_)) => {}
+                    Ok(Err(e)) => break Err(e),
+                    // Nothing arrived for the idle time, so take the response as complete.
+                    Err(_) => break Ok(()),
+                }
+            },
+            None => self.read_to_end(&mut response).await.map(|_| ()),
+        };
+        if let Err(e) = result {
             self.out.errors.push(Http1Error {
                 kind: e.kind().to_string(),
                 message: e.to_string(),
@@ -562,6 +896,87 @@ impl Http1Runner {
         debug!("got response: {:?}", String::from_utf8_lossy(&response));
     }
 
+    /// How the response's body is delimited, once its header has been read.
+    fn framing(&self) -> Option<Framing> {
+        let resp = self.out.response.as_ref()?;
+        resp.headers.as_ref()?;
+        let head = self
+            .out
+            .plan
+            .method
+            .as_ref()
+            .is_some_and(|m| m.eq_ignore_ascii_case(b"HEAD"));
+        let bodiless = resp
+            .status_code
+            .is_some_and(|code| code / 100 == 1 || code == 204 || code == 304);
+        if head || bodiless {
+            return Some(Framing::Empty);
+        }
+        if let (true, Some(length)) = (
+            self.out
+                .plan
+                .read
+                .as_ref()
+                .is_some_and(|r| r.content_length),
+            resp.content_length,
+        ) {
+            return Some(Framing::Length(
+                usize::try_from(length).unwrap_or(usize::MAX),
+            ));
+        }
+        // Transfer-Encoding overrides Content-Length, per RFC 9112 section 6.3.
+        let chunked = resp.headers.iter().flatten().any(|h| {
+            h.key
+                .as_ref()
+                .is_some_and(|k| k.eq_ignore_ascii_case(b"transfer-encoding"))
+                && String::from_utf8_lossy(&h.value)
+                    .to_ascii_lowercase()
+                    .contains("chunked")
+        });
+        if chunked {
+            return Some(Framing::Chunked);
+        }
+        Some(match resp.content_length {
+            Some(length) => Framing::Length(usize::try_from(length).unwrap_or(usize::MAX)),
+            None => Framing::Close,
+        })
+    }
+
+    /// The length of the raw body once all of it has been read. Scanning picks up where the last
+    /// call left off, so checking after each read doesn't rescan the whole body.
+    fn body_end(&mut self) -> Option<usize> {
+        let framed = match self.framing()? {
+            Framing::Empty => Some(0),
+            Framing::Chunked => self.chunked_scan.scan(&self.resp_body_buf),
+            Framing::Length(length) => (self.resp_body_buf.len() >= length).then_some(length),
+            Framing::Close => None,
+        };
+        // The plan's read conditions can end the body before its framing does.
+        let Some(read) = &self.out.plan.read else {
+            return framed;
+        };
+        let bytes = read
+            .bytes
+            .map(|bytes| usize::try_from(bytes).unwrap_or(usize::MAX))
+            .filter(|bytes| self.resp_body_buf.len() >= *bytes);
+        let pattern = match read.pattern.as_ref().filter(|pattern| !pattern.is_empty()) {
+            Some(pattern) => {
+                let from = self.pattern_from.min(self.resp_body_buf.len());
+                let found = self.resp_body_buf[from..]
+                    .windows(pattern.len())
+                    .position(|window| window == pattern.as_slice());
+                // Start the next search at the match, or at the first window not yet checked.
+                self.pattern_from = match found {
+                    Some(i) => from + i,
+                    None => from.max(self.resp_body_buf.len().saturating_sub(pattern.len() - 1)),
+                };
+                found.map(|i| from + i + pattern.len())
+            }
+            None => None,
+        };
+        [framed, bytes, pattern].into_iter().flatten().min()
+    }
+
     pub fn finish(mut self) -> (Http1Output, Option<Runner>) {
         self.complete();
         let State::Complete { transport } = self.state else {
@@ -591,7 +1006,7 @@ impl Http1Runner {
                 };
                 return;
             }
-            State::Pending { .. } | State::Ready { .. } => {
+            State::Pending { .. } | State::Ready { .. } | State::Pipelined => {
                 self.state = State::Complete { transport: None };
                 return;
             }
@@ -636,8 +1051,32 @@ impl Http1Runner {
         }
 
         // The response should be set if the header has been read.
+        let framing = self.framing();
+        let body_end = self.body_end();
         if let Some(resp) = self.out.response.as_mut().map(Arc::make_mut) {
-            resp.body = Some(MaybeUtf8(self.resp_body_buf.split().freeze().into()));
+            let raw = self.resp_body_buf.split().freeze();
+            let mut body = match framing {
+                Some(Framing::Empty) => Bytes::new(),
+                Some(Framing::Chunked) => decode_chunked(&raw).0.freeze(),
+                Some(Framing::Length(length)) => raw.slice(..length.min(raw.len())),
+                Some(Framing::Close) | None => raw.slice(..body_end.unwrap_or(raw.len())),
+            };
+            let headers = resp.headers.as_deref().unwrap_or_default();
+            if let Some((coding, decoded)) = content_encoding::decompress(headers, &body) {
+                resp.content_encoding = Some(coding);
+                match decoded {
+                    Ok(decoded) => {
+                        let encoded = mem::replace(&mut body, decoded.into());
+                        resp.encoded_body = Some(MaybeUtf8(encoded.into()));
+                    }
+                    Err(e) => self.out.errors.push(Http1Error {
+                        kind: "decompress".to_owned(),
+                        message: format!("{e:#}"),
+                    }),
+                }
+            }
+            resp.body = Some(MaybeUtf8(body.into()));
+            resp.raw_body = Some(MaybeUtf8(raw.into()));
             resp.duration = TimeDelta::from_std(
                 self.resp_start_time
                     .map(|start| end_time - start)
@@ -658,9 +1097,353 @@ impl Http1Runner {
                 .map(Duration);
         }
 
+        if let (Some(cookies), Some(headers)) = (
+            &self.cookies,
+            self.out.response.as_ref().and_then(|r| r.headers.as_ref()),
+        ) {
+            cookies.store(&self.out.plan.url, headers);
+        }
+
+        if let (Some(cache), Some(resp)) = (&mut self.out.cache, &self.out.response) {
+            cache.not_modified = resp.status_code == Some(304);
+            cache.stored = self.cache.store(
+                self.out.plan.method.as_deref().unwrap_or_default(),
+                &self.out.plan.url,
+                resp,
+            );
+        }
+
         self.state = State::Complete {
             transport: Some(inner),
         };
         self.out.duration = TimeDelta::from_std(end_time - start_time).unwrap().into();
     }
 }
+
+/// The parts of a response head read so far.
+#[derive(Debug, Default)]
+pub(super) struct ResponseHead {
+    pub protocol: Option<MaybeUtf8>,
+    pub status_code: Option<u16>,
+    pub reason: Option<MaybeUtf8>,
+    /// Set once the headers have been read.
+    pub headers: Option<Vec<HttpHeader>>,
+    pub deviations: Vec<Http1Deviation>,
+    /// The length of the head, once it has all been read.
+    pub len: Option<usize>,
+}
+
+/// Parse a response head with httparse, failing if it's malformed.
+fn parse_strict(buf: &[u8]) -> std::io::Result<ResponseHead> {
+    // Start with room for typical responses and grow for any with more headers, which are only
+    // bounded by the size of the head.
+    let mut headers = vec![httparse::EMPTY_HEADER; 64];
+    loop {
+        let mut resp = httparse::Response::new(&mut headers);
+        let status = match resp.parse(buf) {
+            Ok(status) => status,
+            Err(httparse::Error::TooManyHeaders) => {
+                let len = headers.len() * 2;
+                headers.resize(len, httparse::EMPTY_HEADER);
+                continue;
+            }
+            Err(e) => return Err(std::io::Error::new(std::io::ErrorKind::Other, anyhow!(e))),
+        };
+        return Ok(ResponseHead {
+            protocol: resp
+                .version
+                .map(|v| MaybeUtf8(format!("HTTP/1.{}", v).into())),
+            status_code: resp.code,
+            reason: resp
+                .reason
+                .map(|r| MaybeUtf8(Arc::new(r.to_owned()).into())),
+            // If the reason hasn't been read yet then also no headers were parsed.
+            headers: resp.reason.map(|_| {
+                resp.headers
+                    .iter()
+                    .map(|h| HttpHeader {
+                        // TODO: We could probably avoid extra copies here since these are backed
+                        // by a BytesMut, but the current approach reparses the whole buffer so
+                        // it's not trivial.
+                        key: Some(MaybeUtf8(Arc::new(h.name.to_owned()).into())),
+                        value: MaybeUtf8(Bytes::copy_from_slice(h.value).into()),
+                    })
+                    .collect()
+            }),
+            deviations: Vec::new(),
+            len: match status {
+                httparse::Status::Partial => None,
+                httparse::Status::Complete(len) => Some(len),
+            },
+        });
+    }
+}
+
+/// How a response's body is delimited.
+#[derive(Debug, Clone, Copy)]
+enum Framing {
+    Empty,
+    Chunked,
+    Length(usize),
+    /// Until the server closes the connection.
+    Close,
+}
+
+/// Remove chunked framing from a body, returning the data of each chunk received so far and, if
+/// the final chunk and trailers were all received, the offset just past them.
+pub(super) fn decode_chunked(raw: &[u8]) -> (BytesMut, Option<usize>) {
+    let line_end = |from: usize| {
+        raw.get(from..)?
+            .windows(2)
+            .position(|w| w == b"\r\n")
+            .map(|i| from + i)
+    };
+    let mut body = BytesMut::new();
+    let mut pos = 0;
+    loop {
+        let Some(end) = line_end(pos) else {
+            return (body, None);
+        };
+        let Some(size) = chunk_size(&raw[pos..end]) else {
+            return (body, None);
+        };
+        pos = end + 2;
+        if size == 0 {
+            break;
+        }
+        body.extend_from_slice(&raw[pos..pos.saturating_add(size).min(raw.len())]);
+        match pos.checked_add(size).and_then(|end| end.checked_add(2)) {
+            Some(next) if next <= raw.len() => pos = next,
+            _ => return (body, None),
+        }
+    }
+    // Trailers end with an empty line.
+    loop {
+        let Some(end) = line_end(pos) else {
+            return (body, None);
+        };
+        if end == pos {
+            return (body, Some(end + 2));
+        }
+        pos = end + 2;
+    }
+}
+
+/// Parse a chunk size line, ignoring any extensions.
+fn chunk_size(line: &[u8]) -> Option<usize> {
+    let size = line.split(|b| *b == b';').next().unwrap_or_default();
+    std::str::from_utf8(size)
+        .ok()
+        .and_then(|size| usize::from_str_radix(size.trim(), 16).ok())
+}
+
+/// Finds the end of a chunked body as it's read, like [`decode_chunked`] but without copying the
+/// data or rescanning the chunks already seen.
+#[derive(Debug, Default)]
+struct ChunkedScan {
+    /// The start of the next size or trailer line, or the end of the chunk being read.
+    pos: usize,
+    /// Where the search for the end of the line at pos resumes.
+    searched: usize,
+    /// Whether pos is past the data of a chunk which hasn't all been read yet.
+    in_data: bool,
+    trailers: bool,
+    invalid: bool,
+    end: Option<usize>,
+}
+
+impl ChunkedScan {
+    /// Advance over what's complete in raw, which must start with the bytes passed before.
+    fn scan(&mut self, raw: &[u8]) -> Option<usize> {
+        if self.invalid || self.end.is_some() {
+            return self.end;
+        }
+        loop {
+            if self.in_data {
+                if self.pos > raw.len() {
+                    return None;
+                }
+                self.in_data = false;
+            }
+            let from = self.pos.max(self.searched);
+            let Some(end) = raw[from..]
+                .windows(2)
+                .position(|w| w == b"\r\n")
+                .map(|i| from + i)
+            else {
+                // A CR at the end may be completed by the next read.
+                self.searched = from.max(raw.len().saturating_sub(1));
+                return None;
+            };
+            let line = &raw[self.pos..end];
+            self.pos = end + 2;
+            if self.trailers {
+                // Trailers end with an empty line.
+                if line.is_empty() {
+                    self.end = Some(self.pos);
+                    return self.end;
+                }
+                continue;
+            }
+            match chunk_size(line) {
+                Some(0) => self.trailers = true,
+                Some(size) => match self
+                    .pos
+                    .checked_add(size)
+                    .and_then(|end| end.checked_add(2))
+                {
+                    Some(next) => {
+                        self.pos = next;
+                        self.in_data = true;
+                    }
+                    None => self.invalid = true,
+                },
+                None => self.invalid = true,
+            }
+            if self.invalid {
+                return None;
+            }
+        }
+    }
+}
+
+/// Frame a body with chunked transfer encoding as the plan describes.
+fn encode_chunked(body: &[u8], plan: &Http1ChunkedPlanOutput) -> BytesMut {
+    let mut buf = BytesMut::with_capacity(body.len() + 16);
+    let mut rest = body;
+    let put_size_line = |buf: &mut BytesMut, i: usize, size: usize| {
+        buf.put_slice(format!("{size:x}").as_bytes());
+        if let Some(extension) = plan.extensions.get(i) {
+            buf.put_u8(b';');
+            buf.put_slice(extension);
+        }
+        buf.put_slice(b"\r\n");
+    };
+    let mut i = 0;
+    while !rest.is_empty() {
+        let size = plan
+            .sizes
+            .get(i)
+            .or(plan.sizes.last())
+            .map_or(rest.len(), |size| {
+                usize::try_from(*size).unwrap_or(usize::MAX)
+            });
+        if size == 0 {
+            break;
+        }
+        let (chunk, remaining) = rest.split_at(size.min(rest.len()));
+        put_size_line(&mut buf, i, chunk.len());
+        buf.put_slice(chunk);
+        buf.put_slice(b"\r\n");
+        rest = remaining;
+        i += 1;
+    }
+    put_size_line(&mut buf, i, 0);
+    for trailer in &plan.trailers {
+        if let Some(key) = &trailer.key {
+            buf.put_slice(key.as_slice());
+            buf.put_slice(b": ");
+        }
+        buf.put_slice(trailer.value.as_slice());
+        buf.put_slice(b"\r\n");
+    }
+    buf.put_slice(b"\r\n");
+    // Whatever follows an early final chunk is sent unframed.
+    buf.put_slice(rest);
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn maybe_utf8(s: &'static str) -> MaybeUtf8 {
+        MaybeUtf8(Bytes::from_static(s.as_bytes()).into())
+    }
+
+    #[test]
+    fn test_decode_chunked() {
+        let raw = b"4\r\nWiki\r\n5\r\npedia\r\n0\r\n\r\n";
+        let (body, end) = decode_chunked(raw);
+        assert_eq!(&body[..], b"Wikipedia");
+        assert_eq!(end, Some(raw.len()));
+    }
+
+    #[test]
+    fn test_decode_chunked_extensions_and_trailers() {
+        let raw = b"4;name=value\r\nWiki\r\nA ; quoted=\"a;b\"\r\n in chunks\r\n\
+                    0;last\r\nExpires: never\r\nX-Checksum: abc\r\n\r\nHTTP/1.1 200 OK\r\n";
+        let (body, end) = decode_chunked(raw);
+        assert_eq!(&body[..], b"Wiki in chunks");
+        // Anything after the trailers belongs to the next response.
+        assert_eq!(&raw[end.unwrap()..], b"HTTP/1.1 200 OK\r\n");
+    }
+
+    #[test]
+    fn test_decode_chunked_split_reads() {
+        let raw = b"4\r\nWiki\r\n5\r\npedia\r\n0\r\nX-Trailer: 1\r\n\r\n";
+        for len in 0..raw.len() {
+            let (body, end) = decode_chunked(&raw[..len]);
+            assert_eq!(end, None, "complete after {len} bytes");
+            assert!(
+                b"Wikipedia".starts_with(&body),
+                "{body:?} after {len} bytes"
+            );
+        }
+        // Partial chunk data is returned as it arrives.
+        assert_eq!(&decode_chunked(&raw[..13]).0[..], b"Wikip");
+        assert_eq!(decode_chunked(raw).1, Some(raw.len()));
+    }
+
+    #[test]
+    fn test_decode_chunked_invalid_size() {
+        let (body, end) = decode_chunked(b"4\r\nWiki\r\nzz\r\npedia\r\n0\r\n\r\n");
+        assert_eq!(&body[..], b"Wiki");
+        assert_eq!(end, None);
+    }
+
+    #[test]
+    fn test_chunked_scan_matches_decode() {
+        let inputs: [&[u8]; 3] = [
+            b"4;name=value\r\nWiki\r\n5\r\npedia\r\n0\r\nX-Trailer: 1\r\n\r\nHTTP/1.1",
+            b"4\r\nWiki\r\nzz\r\npedia\r\n0\r\n\r\n",
+            b"0\r\n\r\n",
+        ];
+        for raw in inputs {
+            // Feed the scanner every split of the body into two reads and then the rest.
+            for first in 0..=raw.len() {
+                for second in first..=raw.len() {
+                    let mut scan = ChunkedScan::default();
+                    for len in [first, second, raw.len()] {
+                        assert_eq!(
+                            scan.scan(&raw[..len]),
+                            decode_chunked(&raw[..len]).1,
+                            "{:?} after reads ending at {first}, {second}, {len}",
+                            String::from_utf8_lossy(raw),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_encode_chunked_round_trip() {
+        let plan = Http1ChunkedPlanOutput {
+            sizes: vec![3, 2],
+            extensions: vec![maybe_utf8("a=1")],
+            trailers: vec![HttpHeader {
+                key: Some(maybe_utf8("X-Trailer")),
+                value: maybe_utf8("done"),
+            }],
+        };
+        let raw = encode_chunked(b"Wikipedia", &plan);
+        assert_eq!(
+            &raw[..],
+            b"3;a=1\r\nWik\r\n2\r\nip\r\n2\r\ned\r\n2\r\nia\r\n0\r\nX-Trailer: done\r\n\r\n",
+        );
+        let (body, end) = decode_chunked(&raw);
+        assert_eq!(&body[..], b"Wikipedia");
+        assert_eq!(end, Some(raw.len()));
+    }
+}
@@ -212,6 +212,13 @@ impl Http2Runner {
             .await
     }
 
+    /// Keep a transport which failed to start, so its output is still recorded.
+    pub(super) fn start_failed(&mut self, transport: RawHttp2Runner) {
+        self.transport = Some(transport);
+        self.read_state = ReadState::StartFailed;
+        self.write_state = WriteState::StartFailed;
+    }
+
     pub async fn start_shared(&mut self, transport: SendRequest<Bytes>) -> anyhow::Result<()> {
         let state = mem::replace(&mut self.write_state, WriteState::Invalid);
         let WriteState::Ready {
@@ -317,65 +324,65 @@ impl Http2Runner {
         let end_time = self.shutdown_end.unwrap_or_else(Instant::now);
 
         let mut read_state = mem::replace(&mut self.read_state, ReadState::Invalid);
-        let ReadState::Body { ref mut body, .. } = read_state else {
-            panic!("invalid read state for finish: {:?}", self.read_state);
-        };
-        // Read and write the trailers in parallel.
-        let (read_trailers, _) = join!(body.inner_mut().inner_mut().trailers(), async {
-            if !self.out.plan.trailers.is_empty() {
-                // Send the trailers.
-                if let WriteState::Body { stream, .. } = &mut self.write_state {
-                    let trailers = self
-                        .out
-                        .plan
-                        .trailers
-                        .clone()
-                        .into_iter()
-                        .map(|header| {
-                            (
-                                header
-                                    .key
-                                    .as_ref()
-                                    .map(|key| http::HeaderName::from_bytes(&key))
-                                    .expect("http2 trailer names are required")
-                                    .expect(
-                                        "out-of-spec http2 trailer names are not supported yet",
+        // Without a response body there's no stream left to exchange trailers on.
+        if let ReadState::Body { ref mut body, .. } = read_state {
+            // Read and write the trailers in parallel.
+            let (read_trailers, _) = join!(body.inner_mut().inner_mut().trailers(), async {
+                if !self.out.plan.trailers.is_empty() {
+                    // Send the trailers.
+                    if let WriteState::Body { stream, .. } = &mut self.write_state {
+                        let trailers = self
+                            .out
+                            .plan
+                            .trailers
+                            .clone()
+                            .into_iter()
+                            .map(|header| {
+                                (
+                                    header
+                                        .key
+                                        .as_ref()
+                                        .map(|key| http::HeaderName::from_bytes(&key))
+                                        .expect("http2 trailer names are required")
+                                        .expect(
+                                            "out-of-spec http2 trailer names are not supported yet",
+                                        ),
+                                    http::HeaderValue::from_bytes(header.value.as_bytes()).expect(
+                                        "out-of-spec http2 trailer values are not yet supported",
                                     ),
-                                http::HeaderValue::from_bytes(header.value.as_bytes()).expect(
-                                    "out-of-spec http2 trailer values are not yet supported",
-                                ),
-                            )
-                        })
-                        .collect();
-
-                    self.trailer_send_start = Some(Instant::now());
-                    match stream.inner_mut().inner_mut().send_trailers(trailers) {
-                        Ok(()) => {
-                            let result =
-                                future::poll_fn(|cx| stream.inner_mut().inner_mut().poll_reset(cx))
-                                    .await;
-                            self.trailer_send_end = Some(Instant::now());
-                            match result {
-                                Ok(reason) => {}
-                                Err(e) => self.set_error("sending trailers", e),
+                                )
+                            })
+                            .collect();
+
+                        self.trailer_send_start = Some(Instant::now());
+                        match stream.inner_mut().inner_mut().send_trailers(trailers) {
+                            Ok(()) => {
+                                let result = future::poll_fn(|cx| {
+                                    stream.inner_mut().inner_mut().poll_reset(cx)
+                                })
+                                .await;
+                                self.trailer_send_end = Some(Instant::now());
+                                match result {
+                                    Ok(reason) => {}
+                                    Err(e) => self.set_error("sending trailers", e),
+                                }
                             }
+                            Err(e) => self.set_error("start sending trailers", e),
                         }
-                        Err(e) => self.set_error("start sending trailers", e),
+                    }
+                } else {
+                    // No trailers, so manually close the write stream.
+                    if let Err(e) = self.shutdown().await {
+                        self.set_error("shutdown write stream", e);
                     }
                 }
-            } else {
-                // No trailers, so manually close the write stream.
-                if let Err(e) = self.shutdown().await {
-                    self.set_error("shutdown write stream", e);
-                }
-            }
-        });
+            });
 
-        match read_trailers {
-            Ok(trailers) => self.receive_trailers = trailers,
-            Err(e) => self.set_error("read trailers", e),
+            match read_trailers {
+                Ok(trailers) => self.receive_trailers = trailers,
+                Err(e) => self.set_error("read trailers", e),
+            }
         }
-
         self.read_state = read_state;
 
         let (resp_head, resp_body) = match mem::replace(&mut self.read_state, ReadState::Invalid) {
@@ -0,0 +1,302 @@
+use std::{future, mem, sync::Arc, time::Instant};
+
+use anyhow::bail;
+use bytes::{Buf, Bytes, BytesMut};
+use chrono::TimeDelta;
+use derivative::Derivative;
+use h3::client::{RequestStream, SendRequest};
+use http::{HeaderMap, HeaderName, HeaderValue, Request, Uri};
+use tokio::task::JoinHandle;
+
+use crate::{
+    AddContentLength, Http3Error, Http3Output, Http3PlanOutput, Http3RequestOutput, Http3Response,
+    HttpHeader, MaybeUtf8, PduName, ProtocolDiscriminants, ProtocolName,
+};
+
+use super::{quic::QuicRunner, Context};
+
+#[derive(Debug)]
+pub(super) struct Http3Runner {
+    ctx: Arc<Context>,
+    out: Http3Output,
+    state: State,
+    transport: Option<QuicRunner>,
+    protocol: ProtocolDiscriminants,
+    send_headers: HeaderMap,
+    start_time: Option<Instant>,
+}
+
+#[derive(Derivative)]
+#[derivative(Debug)]
+enum State {
+    Pending,
+    Open {
+        #[derivative(Debug = "ignore")]
+        driver: JoinHandle<Result<(), h3::Error>>,
+        #[derivative(Debug = "ignore")]
+        send_request: SendRequest<h3_quinn::OpenStreams, Bytes>,
+        #[derivative(Debug = "ignore")]
+        stream: RequestStream<h3_quinn::BidiStream<Bytes>, Bytes>,
+        request_out: Http3RequestOutput,
+    },
+    Completed,
+}
+
+impl Http3Runner {
+    pub(super) fn new(
+        ctx: Arc<Context>,
+        plan: Http3PlanOutput,
+        protocol: ProtocolDiscriminants,
+    ) -> Self {
+        Self {
+            out: Http3Output {
+                name: ProtocolName::with_job(ctx.job_name.clone(), protocol),
+                plan,
+                request: None,
+                response: None,
+                errors: Vec::new(),
+                duration: TimeDelta::zero().into(),
+            },
+            ctx,
+            state: State::Pending,
+            transport: None,
+            protocol,
+            send_headers: HeaderMap::new(),
+            start_time: None,
+        }
+    }
+
+    pub(super) fn size_hint(&mut self, hint: Option<usize>) -> Option<usize> {
+        // Follow the same Content-Length rules as http2.
+        if let Some(size_hint) = hint {
+            if self.out.plan.add_content_length == AddContentLength::Force
+                || self.out.plan.add_content_length == AddContentLength::Auto
+                    && !self.out.plan.headers.iter().any(|header| {
+                        header.key.as_ref().is_some_and(|key| {
+                            key.as_slice().eq_ignore_ascii_case(b"content-length")
+                        })
+                    })
+            {
+                self.send_headers.append(
+                    HeaderName::from_static("content-length"),
+                    HeaderValue::from_str(&size_hint.to_string())
+                        .expect("u64::to_string should always produce a valid header value"),
+                );
+            }
+        }
+        None
+    }
+
+    pub(super) fn executor_size_hint(&self) -> Option<usize> {
+        Some(self.out.plan.body.len())
+    }
+
+    fn build_request(&self) -> anyhow::Result<Request<()>> {
+        let uri: Uri = self.out.plan.url.as_str().parse()?;
+        let method = match &self.out.plan.method {
+            Some(method) => method
+                .as_str()
+                .ok_or_else(|| anyhow::anyhow!("http3 method must be valid UTF-8"))?,
+            None => "GET",
+        };
+        let mut req = Request::builder().uri(uri).method(method);
+        for header in &self.out.plan.headers {
+            let Some(key) = &header.key else {
+                bail!("http3 headers require a key");
+            };
+            req = req.header(key.as_slice(), header.value.as_slice());
+        }
+        let mut req = req.body(())?;
+        req.headers_mut().extend(self.send_headers.clone());
+        Ok(req)
+    }
+
+    pub async fn start(&mut self, transport: QuicRunner) -> anyhow::Result<()> {
+        let connection = transport.connection();
+        self.transport = Some(transport);
+        let Some(connection) = connection else {
+            bail!("attempt to start Http3Runner without an open quic connection");
+        };
+        if let Err(e) = self.open(connection).await {
+            self.out.errors.push(Http3Error {
+                kind: "open stream".to_owned(),
+                message: format!("{e:#}"),
+            });
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    async fn open(&mut self, connection: quinn::Connection) -> anyhow::Result<()> {
+        let request = self.build_request()?;
+
+        let start = Instant::now();
+        self.start_time = Some(start);
+
+        let (mut driver, mut send_request) =
+            h3::client::new(h3_quinn::Connection::new(connection)).await?;
+        // The connection driver handles control streams and must run for as long as we use the
+        // connection.
+        let driver = tokio::spawn(async move { future::poll_fn(|cx| driver.poll_close(cx)).await });
+
+        let stream = send_request.send_request(request).await?;
+        let request_out = Http3RequestOutput {
+            name: PduName::with_job(self.ctx.job_name.clone(), self.protocol, 0),
+            url: self.out.plan.url.clone(),
+            method: self.out.plan.method.clone(),
+            headers: self.out.plan.headers.clone(),
+            body: self.out.plan.body.clone(),
+            stream_id: Some(stream.id().into()),
+            duration: TimeDelta::zero().into(),
+            headers_duration: Some(
+                TimeDelta::from_std(start.elapsed())
+                    .unwrap_or(TimeDelta::MAX)
+                    .into(),
+            ),
+            body_duration: None,
+            time_to_first_byte: None,
+        };
+        self.state = State::Open {
+            driver,
+            send_request,
+            stream,
+            request_out,
+        };
+        Ok(())
+    }
+
+    pub async fn execute(&mut self) {
+        let State::Open {
+            stream,
+            request_out,
+            ..
+        } = &mut self.state
+        else {
+            return;
+        };
+        let start = self
+            .start_time
+            .expect("start time should be set for open stream");
+
+        if !self.out.plan.body.is_empty() {
+            let body_start = Instant::now();
+            let body = Bytes::copy_from_slice(self.out.plan.body.as_slice());
+            if let Err(e) = stream.send_data(body).await {
+                self.out.errors.push(Http3Error {
+                    kind: "send body".to_owned(),
+                    message: e.to_string(),
+                });
+                return;
+            }
+            request_out.body_duration = Some(
+                TimeDelta::from_std(body_start.elapsed())
+                    .unwrap_or(TimeDelta::MAX)
+                    .into(),
+            );
+        }
+        let finish_result = stream.finish().await;
+        let request_end = Instant::now();
+        request_out.duration = TimeDelta::from_std(request_end - start)
+            .unwrap_or(TimeDelta::MAX)
+            .into();
+        self.out.request = Some(Arc::new(request_out.clone()));
+        if let Err(e) = finish_result {
+            self.out.errors.push(Http3Error {
+                kind: "finish request".to_owned(),
+                message: e.to_string(),
+            });
+            return;
+        }
+
+        let stream_id = request_out.stream_id;
+        let mut response_out = Http3Response {
+            name: PduName::with_protocol(self.out.name.clone(), 1),
+            stream_id,
+            status_code: None,
+            headers: None,
+            trailers: None,
+            body: None,
+            duration: TimeDelta::zero().into(),
+            header_duration: None,
+            time_to_first_byte: None,
+        };
+        let result = async {
+            let response = stream.recv_response().await?;
+            let headers_end = Instant::now();
+            response_out.time_to_first_byte = Some(
+                TimeDelta::from_std(headers_end - start)
+                    .unwrap_or(TimeDelta::MAX)
+                    .into(),
+            );
+            response_out.header_duration = Some(
+                TimeDelta::from_std(headers_end - request_end)
+                    .unwrap_or(TimeDelta::MAX)
+                    .into(),
+            );
+            response_out.status_code = Some(response.status().into());
+            response_out.headers = Some(to_headers(response.headers()));
+
+            let mut body = BytesMut::new();
+            while let Some(mut chunk) = stream.recv_data().await? {
+                while chunk.has_remaining() {
+                    let bytes = chunk.chunk();
+                    body.extend_from_slice(bytes);
+                    let len = bytes.len();
+                    chunk.advance(len);
+                }
+            }
+            response_out.body = Some(MaybeUtf8(body.freeze().into()));
+            response_out.trailers = stream
+                .recv_trailers()
+                .await?
+                .map(|trailers| to_headers(&trailers));
+            Ok::<_, h3::Error>(())
+        }
+        .await;
+        response_out.duration = TimeDelta::from_std(start.elapsed())
+            .unwrap_or(TimeDelta::MAX)
+            .into();
+        self.out.response = Some(Arc::new(response_out));
+        if let Err(e) = result {
+            self.out.errors.push(Http3Error {
+                kind: "receive response".to_owned(),
+                message: e.to_string(),
+            });
+        }
+    }
+
+    pub async fn finish(mut self) -> (Http3Output, Option<QuicRunner>) {
+        if let State::Open {
+            driver,
+            send_request,
+            stream,
+            request_out,
+        } = mem::replace(&mut self.state, State::Completed)
+        {
+            if self.out.request.is_none() {
+                self.out.request = Some(Arc::new(request_out));
+            }
+            // Closing the QUIC connection below ends the session, so the driver has nothing
+            // left to do.
+            drop(stream);
+            drop(send_request);
+            driver.abort();
+        }
+        if let Some(start) = self.start_time {
+            self.out.duration = TimeDelta::from_std(start.elapsed())
+                .unwrap_or(TimeDelta::MAX)
+                .into();
+        }
+        (self.out, self.transport)
+    }
+}
+
+fn to_headers(headers: &HeaderMap) -> Vec<HttpHeader> {
+    headers
+        .iter()
+        .map(|(k, v)| HttpHeader {
+            key: Some(MaybeUtf8(k.to_string().into())),
+            value: MaybeUtf8(Bytes::copy_from_slice(v.as_bytes()).into()),
+        })
+        .collect()
+}
@@ -0,0 +1,185 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail};
+use chrono::TimeDelta;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+use url::{Position, Url};
+
+use crate::{HeldConnectionOutput, KeepAliveOutput, KeepAlivePlanOutput};
+
+use super::socket::BoxStream;
+use super::{crawl, grpc_reflect, Context};
+
+/// The most connections a step may hold, to stay within typical file descriptor limits.
+const MAX_CONNECTIONS: u64 = 10_000;
+/// The most response header bytes to buffer before giving up on a connection.
+const MAX_HEAD: usize = 64 * 1024;
+/// Servers ignore empty lines before a request line (RFC 9112 section 2.2), so a bare CRLF
+/// keeps a connection active without starting a request.
+const NOOP: &[u8] = b"\r\n";
+
+/// Open connections one after another and hold them all open, recording when the server
+/// starts refusing new connections or closing held ones.
+pub(super) async fn keep_alive(ctx: &Context, plan: KeepAlivePlanOutput) -> KeepAliveOutput {
+    let start = Instant::now();
+    let mut out = KeepAliveOutput {
+        connections: Vec::new(),
+        max_open: 0,
+        limit: None,
+        recovered: false,
+        error: None,
+        duration: TimeDelta::zero().into(),
+        plan,
+    };
+    if let Err(e) = hold(ctx, &mut out, start).await {
+        out.error = Some(format!("{e:#}"));
+    }
+    out.duration = since(start);
+    out
+}
+
+async fn hold(ctx: &Context, out: &mut KeepAliveOutput, start: Instant) -> anyhow::Result<()> {
+    if out.plan.connections > MAX_CONNECTIONS {
+        bail!("keep_alive.connections must be at most {MAX_CONNECTIONS}");
+    }
+    let interval = out.plan.interval.0.to_std().unwrap_or_default();
+    if interval.is_zero() {
+        bail!("keep_alive.interval must be positive");
+    }
+    let timeout = out.plan.timeout.0.to_std().unwrap_or_default();
+
+    let open = Arc::new(AtomicU64::new(0));
+    let (release, released) = watch::channel(false);
+    let mut held = JoinSet::new();
+    let mut connections = Vec::new();
+    for index in 0..out.plan.connections {
+        let mut conn = HeldConnectionOutput {
+            index,
+            status_code: None,
+            opened_at: None,
+            closed_at: None,
+            open_at_close: None,
+            noops_sent: 0,
+            error: None,
+        };
+        match connect(ctx, &out.plan.url, timeout).await {
+            Ok((stream, status_code)) => {
+                conn.status_code = Some(status_code);
+                conn.opened_at = Some(since(start));
+                out.max_open = out.max_open.max(open.fetch_add(1, Ordering::SeqCst) + 1);
+                held.spawn(hold_one(
+                    stream,
+                    conn,
+                    interval,
+                    start,
+                    open.clone(),
+                    released.clone(),
+                ));
+            }
+            Err(e) => {
+                conn.closed_at = Some(since(start));
+                conn.open_at_close = Some(open.load(Ordering::SeqCst));
+                conn.error = Some(format!("{e:#}"));
+                connections.push(conn);
+            }
+        }
+    }
+
+    tokio::time::sleep(out.plan.hold.0.to_std().unwrap_or_default()).await;
+    // Sending only fails if every connection already closed, which is fine.
+    let _ = release.send(true);
+    while let Some(conn) = held.join_next().await {
+        connections.push(conn?);
+    }
+    connections.sort_by_key(|conn| conn.index);
+    out.limit = connections
+        .iter()
+        .filter(|conn| conn.closed_at.is_some())
+        .min_by_key(|conn| conn.closed_at.as_ref().map(|at| at.0))
+        .and_then(|conn| conn.open_at_close);
+    out.connections = connections;
+
+    out.recovered = connect(ctx, &out.plan.url, timeout).await.is_ok();
+    Ok(())
+}
+
+/// Connect and send a HEAD request, leaving the connection idle between requests. HEAD
+/// responses never have a body, so the head is all there is to read.
+async fn connect(ctx: &Context, url: &Url, timeout: Duration) -> anyhow::Result<(BoxStream, u16)> {
+    tokio::time::timeout(timeout, async {
+        let mut stream = grpc_reflect::dial_alpn(ctx, url, b"http/1.1").await?;
+        let request = format!(
+            "HEAD {} HTTP/1.1\r\nHost: {}\r\nConnection: keep-alive\r\n\r\n",
+            &url[Position::BeforePath..Position::AfterQuery],
+            crawl::authority(url)?,
+        );
+        stream.write_all(request.as_bytes()).await?;
+
+        let mut head = Vec::new();
+        let mut buf = [0; 4096];
+        while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+            if head.len() > MAX_HEAD {
+                bail!("response head exceeded {MAX_HEAD} bytes");
+            }
+            let n = stream.read(&mut buf).await?;
+            if n == 0 {
+                bail!("connection closed before response");
+            }
+            head.extend_from_slice(&buf[..n]);
+        }
+        let status_code = head
+            .split(|b| *b == b' ')
+            .nth(1)
+            .and_then(|code| std::str::from_utf8(code).ok())
+            .and_then(|code| code.parse().ok())
+            .ok_or_else(|| anyhow!("invalid response status line"))?;
+        Ok::<_, anyhow::Error>((stream, status_code))
+    })
+    .await
+    .unwrap_or_else(|_| Err(anyhow!("timed out")))
+}
+
+/// Keep a connection active until it's released, returning early if the server closes it.
+async fn hold_one(
+    stream: BoxStream,
+    mut conn: HeldConnectionOutput,
+    interval: Duration,
+    start: Instant,
+    open: Arc<AtomicU64>,
+    mut released: watch::Receiver<bool>,
+) -> HeldConnectionOutput {
+    let (mut reader, mut writer) = tokio::io::split(stream);
+    let mut ticks = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+    let mut buf = [0; 1024];
+    let error = loop {
+        tokio::select! {
+            _ = released.changed() => return conn,
+            _ = ticks.tick() => {
+                if let Err(e) = writer.write_all(NOOP).await {
+                    break e.to_string();
+                }
+                conn.noops_sent += 1;
+            }
+            // Anything the server sends unprompted, like a 408, is followed by it hanging up.
+            read = reader.read(&mut buf) => match read {
+                Ok(0) => break "closed by server".to_owned(),
+                Ok(_) => {}
+                Err(e) => break e.to_string(),
+            },
+        }
+    };
+    conn.closed_at = Some(since(start));
+    conn.open_at_close = Some(open.fetch_sub(1, Ordering::SeqCst) - 1);
+    conn.error = Some(error);
+    conn
+}
+
+fn since(start: Instant) -> cel_interpreter::Duration {
+    TimeDelta::from_std(start.elapsed())
+        .unwrap_or(TimeDelta::MAX)
+        .into()
+}
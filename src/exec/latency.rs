@@ -0,0 +1,182 @@
+use std::collections::BTreeMap;
+
+use cel_interpreter::Duration;
+use chrono::TimeDelta;
+
+use crate::{HistogramOutput, JobOutput, LatencyOutput};
+
+/// Values below this are recorded exactly and larger ones keep this many bits of precision, which
+/// is about three significant digits.
+const SUB_BUCKET_BITS: u32 = 11;
+const SUB_BUCKETS: u64 = 1 << SUB_BUCKET_BITS;
+const HALF_SUB_BUCKETS: u64 = SUB_BUCKETS / 2;
+
+/// An HDR-style histogram of durations with microsecond resolution, using log-linear buckets so
+/// memory stays small regardless of how many samples are recorded.
+#[derive(Debug, Default)]
+struct Histogram {
+    counts: BTreeMap<u64, u64>,
+    count: u64,
+    sum: u128,
+    min: u64,
+    max: u64,
+}
+
+impl Histogram {
+    fn record(&mut self, duration: TimeDelta) {
+        let value = u64::try_from(duration.num_microseconds().unwrap_or(i64::MAX)).unwrap_or(0);
+        *self.counts.entry(bucket(value)).or_default() += 1;
+        self.min = if self.count == 0 {
+            value
+        } else {
+            self.min.min(value)
+        };
+        self.max = self.max.max(value);
+        self.count += 1;
+        self.sum += u128::from(value);
+    }
+
+    /// The highest value equivalent to the sample at the percentile, capped at the recorded max.
+    fn percentile(&self, percentile: f64) -> u64 {
+        let target = ((percentile / 100.0 * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, count) in &self.counts {
+            seen += count;
+            if seen >= target {
+                return highest_equivalent(*bucket).min(self.max);
+            }
+        }
+        self.max
+    }
+
+    fn output(&self) -> Option<HistogramOutput> {
+        if self.count == 0 {
+            return None;
+        }
+        let micros = |value: u64| -> Duration { TimeDelta::microseconds(value as i64).into() };
+        Some(HistogramOutput {
+            count: self.count,
+            min: micros(self.min),
+            max: micros(self.max),
+            mean: micros((self.sum / u128::from(self.count)) as u64),
+            p50: micros(self.percentile(50.0)),
+            p90: micros(self.percentile(90.0)),
+            p95: micros(self.percentile(95.0)),
+            p99: micros(self.percentile(99.0)),
+            p999: micros(self.percentile(99.9)),
+        })
+    }
+}
+
+fn bucket(value: u64) -> u64 {
+    if value < SUB_BUCKETS {
+        return value;
+    }
+    let shift = u64::from(63 - value.leading_zeros() - (SUB_BUCKET_BITS - 1));
+    SUB_BUCKETS + (shift - 1) * HALF_SUB_BUCKETS + ((value >> shift) - HALF_SUB_BUCKETS)
+}
+
+fn highest_equivalent(bucket: u64) -> u64 {
+    if bucket < SUB_BUCKETS {
+        return bucket;
+    }
+    let shift = (bucket - SUB_BUCKETS) / HALF_SUB_BUCKETS + 1;
+    let sub = (bucket - SUB_BUCKETS) % HALF_SUB_BUCKETS + HALF_SUB_BUCKETS;
+    (sub << shift) + (1 << shift) - 1
+}
+
+/// Fold the timings of each job into per-phase percentiles.
+pub(super) fn aggregate<'a>(jobs: impl IntoIterator<Item = &'a JobOutput>) -> LatencyOutput {
+    let mut connect = Histogram::default();
+    let mut time_to_first_byte = Histogram::default();
+    let mut total = Histogram::default();
+    for job in jobs {
+        if let Some(d) = job
+            .raw_tcp
+            .as_ref()
+            .and_then(|raw| raw.handshake_duration.as_ref())
+            .or_else(|| job.tcp.as_ref()?.handshake_duration.as_ref())
+            .or_else(|| job.quic.as_ref()?.handshake_duration.as_ref())
+        {
+            connect.record(d.0);
+        }
+        // Prefer the response of the top protocol, falling back to the first byte off the wire.
+        let ttfb = [&job.h1, &job.h1c]
+            .into_iter()
+            .flatten()
+            .find_map(|h| h.response.as_ref()?.time_to_first_byte.clone())
+            .or_else(|| {
+                [&job.h2, &job.h2c]
+                    .into_iter()
+                    .flatten()
+                    .find_map(|h| h.response.as_ref()?.time_to_first_byte.clone())
+            })
+            .or_else(|| {
+                job.h3
+                    .as_ref()?
+                    .response
+                    .as_ref()?
+                    .time_to_first_byte
+                    .clone()
+            })
+            .or_else(|| {
+                job.http
+                    .as_ref()?
+                    .response
+                    .as_ref()?
+                    .time_to_first_byte
+                    .clone()
+            })
+            .or_else(|| {
+                job.tls
+                    .as_ref()?
+                    .received
+                    .as_ref()?
+                    .time_to_first_byte
+                    .clone()
+            })
+            .or_else(|| {
+                job.tcp
+                    .as_ref()?
+                    .received
+                    .as_ref()?
+                    .time_to_first_byte
+                    .clone()
+            });
+        if let Some(d) = ttfb {
+            time_to_first_byte.record(d.0);
+        }
+        let duration = job
+            .graphql
+            .as_ref()
+            .map(|p| &p.duration)
+            .or_else(|| job.http.as_ref().map(|p| &p.duration))
+            .or_else(|| {
+                [&job.h1, &job.h1c]
+                    .into_iter()
+                    .flatten()
+                    .next()
+                    .map(|p| &p.duration)
+            })
+            .or_else(|| {
+                [&job.h2, &job.h2c]
+                    .into_iter()
+                    .flatten()
+                    .next()
+                    .map(|p| &p.duration)
+            })
+            .or_else(|| job.h3.as_ref().map(|p| &p.duration))
+            .or_else(|| job.tls.as_ref().map(|p| &p.duration))
+            .or_else(|| job.tcp.as_ref().map(|p| &p.duration))
+            .or_else(|| job.raw_tcp.as_ref().map(|p| &p.duration))
+            .or_else(|| job.quic.as_ref().map(|p| &p.duration));
+        if let Some(d) = duration {
+            total.record(d.0);
+        }
+    }
+    LatencyOutput {
+        connect: connect.output(),
+        time_to_first_byte: time_to_first_byte.output(),
+        total: total.output(),
+    }
+}
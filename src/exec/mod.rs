@@ -1,23 +1,74 @@
+mod adaptive;
+mod alpn_matrix;
+mod auth;
+mod banner;
+mod budget;
 mod buffer;
+mod cache;
+mod charset;
+mod command;
+mod conditional;
+mod content_encoding;
+mod cookies;
+mod crawl;
+mod desync;
+mod detect;
+mod discover;
+mod dns;
+mod egress;
+mod expect;
 mod extract;
+mod fault;
+mod follow;
+mod forced_browse;
 pub mod graphql;
+pub mod grpc;
+mod grpc_reflect;
+mod h2_attack;
+mod hpack;
 pub mod http;
 pub mod http1;
 pub mod http2;
+#[cfg(feature = "native")]
+pub mod http3;
+mod keep_alive;
+mod latency;
+mod network;
 mod pause;
+mod permissive;
+mod pipeline;
+mod proxy;
+#[cfg(feature = "native")]
+pub mod quic;
+mod range;
 pub mod raw_http2;
 pub mod raw_tcp;
+mod reflect;
 mod runner;
+mod scope;
+mod script;
+mod session;
+mod sign;
+mod similarity;
+pub mod socket;
 mod sync;
+mod takeover;
 pub mod tcp;
+mod tcp_burst;
 mod tee;
 mod timing;
 pub mod tls;
+mod vhost;
+pub mod websocket;
+mod x509;
 
 use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Instant;
 
-use anyhow::bail;
+use anyhow::{anyhow, bail};
+use chrono::TimeDelta;
 use futures::future::try_join_all;
 use indexmap::IndexMap;
 use itertools::{Either, Itertools, Position};
@@ -25,26 +76,117 @@ use svix_ksuid::{KsuidLike, KsuidMs};
 use tokio_task_pool::Pool;
 use tracing::debug;
 
+use crate::cel_functions::CelState;
 use crate::{
-    location, Evaluate, IterableKey, JobName, JobOutput, Parallelism, Plan, PlanWrapper, Protocol,
-    ProtocolField, ProtocolName, RunName, Step, StepOutput, StepPlanOutput, StepPlanOutputs,
+    location, AuthOutput, Evaluate, IterableKey, JobName, JobOutput, MirrorMode, MirrorOutput,
+    MirrorPlanOutput, MirrorRequest, ModuleOutput, ModulePlanOutput, NetworkPlanOutput, OnError,
+    Parallelism, Plan, PlanWrapper, Protocol, ProtocolField, ProtocolName, RunName, RunOutput,
+    Step, StepError, StepName, StepOutput, StepPlanOutput, StepPlanOutputs, StepProtocols,
+    UsageOutput,
 };
 
+use self::auth::Login;
+use self::budget::{Meter, MeteredSocketProvider};
+use self::cache::HttpCache;
+use self::cookies::CookieJar;
+use self::egress::Egress;
+use self::follow::Follower;
+use self::reflect::Reflections;
 use self::runner::Runner;
+use self::scope::{Guard, ScopedSocketProvider};
+use self::socket::SocketProvider;
+use self::tls::{TlsSessions, VerifierProvider};
 use sync::*;
 
+/// Decides whether a step marked destructive may run, given the step's name.
+pub type ConfirmDestructive = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
 pub struct Executor {
     locals: HashMap<cel_interpreter::objects::Key, cel_interpreter::Value>,
+    names: Vec<Arc<String>>,
     steps: VecDeque<(Arc<String>, Step)>,
     outputs: HashMap<Arc<String>, StepOutput>,
     run: RunName,
+    sockets: Arc<dyn SocketProvider>,
+    cache: Arc<HttpCache>,
+    /// TLS sessions from each step's handshakes, for later steps to resume.
+    tls_sessions: Arc<TlsSessions>,
+    /// Overrides certificate verification for steps it returns a verifier for.
+    verifiers: Option<Arc<dyn VerifierProvider>>,
+    mirror: Option<MirrorRequest>,
+    egress: HashMap<String, Arc<Egress>>,
+    network: HashMap<String, Arc<NetworkPlanOutput>>,
+    /// The network profile for steps which don't set run.network.
+    default_network: Option<String>,
+    cookies: Option<Arc<CookieJar>>,
+    shard: Option<Shard>,
+    scope: Option<Arc<Guard>>,
+    /// Asked before each destructive step. Without it, destructive steps are refused.
+    confirm_destructive: Option<ConfirmDestructive>,
+    /// Whether command steps may run local programs.
+    allow_commands: bool,
+    /// Whether script steps may run.
+    allow_scripts: bool,
+    reflections: Reflections,
+    /// Counts the run's traffic against the plan's budget.
+    meter: Arc<Meter>,
+    /// When the first step started.
+    started: Option<Instant>,
+    /// The jobs run so far.
+    attempts: u64,
+    /// Logins from devil.auth, by name.
+    auth: HashMap<String, Login>,
+    /// The files of the plans which imported this one as a module, outermost first, ending with
+    /// this plan's own file if it was read from one. Module paths are relative to the last.
+    imports: Vec<PathBuf>,
+    /// State kept by CEL functions, like the random generator seeded by devil.seed.
+    cel: Arc<CelState>,
+}
+
+/// A slice of each step's jobs for one of several workers running the same plan.
+#[derive(Debug, Clone, Copy)]
+pub struct Shard {
+    pub index: u64,
+    pub count: u64,
+}
+
+impl Shard {
+    fn skips(&self, job: u64) -> bool {
+        job % self.count != self.index
+    }
 }
 
 impl<'a> Executor {
     pub fn new(plan: &'a Plan, run_name: RunName) -> Result<Self, crate::Error> {
+        Self::with_params(plan, run_name, IndexMap::new())
+    }
+
+    /// Creates an executor with params which replace the plan's locals of the same name. Params
+    /// without a matching local are added as locals.
+    pub fn with_params(
+        plan: &'a Plan,
+        run_name: RunName,
+        params: IndexMap<String, cel_interpreter::Value>,
+    ) -> Result<Self, crate::Error> {
+        let cel = Arc::new(CelState::new(plan.seed, plan.now));
+        Self::with_cel_state(plan, run_name, params, cel)
+    }
+
+    /// Creates an executor whose CEL functions keep their state in `cel`, for modules, sessions
+    /// and logins to share with the plan running them.
+    fn with_cel_state(
+        plan: &'a Plan,
+        run_name: RunName,
+        mut params: IndexMap<String, cel_interpreter::Value>,
+        cel: Arc<CelState>,
+    ) -> Result<Self, crate::Error> {
         let mut locals = HashMap::new();
         // Evaluate the locals in order.
         for (k, v) in plan.locals.iter() {
+            if let Some(param) = params.shift_remove(k) {
+                locals.insert(k.clone().into(), param);
+                continue;
+            }
             let inputs = State {
                 data: &HashMap::new(),
                 locals: &mut locals,
@@ -52,13 +194,50 @@ impl<'a> Executor {
                 run_while: None,
                 run_for: None,
                 run_count: None,
+                previous: None,
                 run_name: &run_name,
                 job_name: None,
+                cel: &cel,
             };
             let out = v.evaluate(&inputs)?;
             locals.insert(k.clone().into(), out.0);
         }
+        locals.extend(params.into_iter().map(|(k, v)| (k.into(), v)));
+        let inputs = State {
+            data: &HashMap::new(),
+            locals: &mut locals,
+            current: StepPlanOutputs::default(),
+            run_while: None,
+            run_for: None,
+            run_count: None,
+            previous: None,
+            run_name: &run_name,
+            job_name: None,
+            cel: &cel,
+        };
+        let egress = plan
+            .egress
+            .iter()
+            .map(|(name, egress)| {
+                let egress = Egress::new(egress.evaluate(name, &inputs)?)?;
+                Ok::<_, crate::Error>((name.clone(), Arc::new(egress)))
+            })
+            .try_collect()?;
+        let mut network: HashMap<_, _> = network::presets()
+            .into_iter()
+            .map(|network| (network.name.clone(), Arc::new(network)))
+            .collect();
+        for (name, request) in &plan.network {
+            network.insert(name.clone(), Arc::new(request.evaluate(name, &inputs)?));
+        }
+        let scope = plan
+            .scope
+            .clone()
+            .map(|scope| Arc::new(Guard::new(scope, &egress)));
+        let meter = Arc::new(Meter::new(plan.budget.clone().unwrap_or_default()));
+        let sockets = MeteredSocketProvider::wrap(socket::default_provider(), &meter);
         Ok(Executor {
+            names: plan.steps.keys().cloned().collect(),
             steps: plan
                 .steps
                 .iter()
@@ -67,13 +246,566 @@ impl<'a> Executor {
             outputs: HashMap::with_capacity(plan.steps.len()),
             run: run_name,
             locals: locals.into(),
+            sockets: ScopedSocketProvider::wrap(sockets, scope.as_ref()),
+            cache: Arc::default(),
+            tls_sessions: Arc::default(),
+            verifiers: None,
+            mirror: plan.mirror.clone(),
+            egress,
+            network,
+            default_network: None,
+            cookies: None,
+            shard: None,
+            scope,
+            confirm_destructive: None,
+            allow_commands: false,
+            allow_scripts: false,
+            reflections: Reflections::default(),
+            meter,
+            started: None,
+            attempts: 0,
+            auth: plan
+                .auth
+                .iter()
+                .map(|(name, request)| (name.clone(), Login::new(request.clone())))
+                .collect(),
+            imports: Vec::new(),
+            cel,
         })
     }
 
+    /// Resolves module paths relative to the directory of `path`, the file the plan was read
+    /// from, instead of the working directory.
+    pub fn with_plan_path(mut self, path: impl AsRef<Path>) -> Self {
+        self.imports = vec![absolute(path.as_ref())];
+        self
+    }
+
+    /// Replaces the provider used to open connections for subsequent steps.
+    pub fn with_socket_provider(mut self, sockets: Arc<dyn SocketProvider>) -> Self {
+        let sockets = MeteredSocketProvider::wrap(sockets, &self.meter);
+        self.sockets = ScopedSocketProvider::wrap(sockets, self.scope.as_ref());
+        self
+    }
+
+    /// Asks `verifiers` for the certificate verifier of each step's TLS handshakes. Steps it
+    /// returns None for keep the default webpki verification.
+    pub fn with_verifier_provider(mut self, verifiers: Arc<dyn VerifierProvider>) -> Self {
+        self.verifiers = Some(verifiers);
+        self
+    }
+
+    /// Emulates the named network profile for every step that doesn't select its own with
+    /// run.network.
+    pub fn with_network(mut self, name: String) -> Result<Self, crate::Error> {
+        if !self.network.contains_key(&name) {
+            bail!("network profile {name:?} is not built in or defined in devil.network");
+        }
+        self.default_network = Some(name);
+        Ok(self)
+    }
+
+    /// Also searches responses for the markers sent in `run` of the SQLite output at `db`, to
+    /// check whether injections from that run were stored.
+    #[cfg(feature = "native")]
+    pub fn with_markers_from(mut self, db: &str, run: &str) -> Result<Self, crate::Error> {
+        self.reflections.import(db, run)?;
+        Ok(self)
+    }
+
+    /// Makes the mean response timings of each step in `run` of the SQLite output at `db`
+    /// available to baseline_timing() in CEL, to check for timing regressions across runs.
+    #[cfg(feature = "native")]
+    pub fn with_timing_baseline(self, db: &str, run: &str) -> Result<Self, crate::Error> {
+        let timings = crate::compare::phase_timings(db, run)?
+            .into_iter()
+            .map(|(key, ms)| (key, TimeDelta::nanoseconds((ms * 1_000_000.0) as i64)))
+            .collect();
+        self.cel.set_baseline(timings);
+        Ok(self)
+    }
+
+    /// Only runs this shard's jobs for steps with more than one independent job. Other steps,
+    /// including single job steps and run.while or run.follow loops, run whole in shard 0 and are
+    /// skipped by the rest, so their outputs aren't available to later steps in other shards.
+    ///
+    /// Panics if `shard.count` is zero.
+    pub fn with_shard(mut self, shard: Shard) -> Self {
+        self.shard = Some(shard);
+        self
+    }
+
+    /// Runs steps marked destructive without asking.
+    pub fn allow_destructive(self) -> Self {
+        self.with_destructive_confirmation(Arc::new(|_: &str| true))
+    }
+
+    /// Runs command steps, which start local programs with the plan's arguments. Without this
+    /// they're refused, so plans from elsewhere can't run code on this host.
+    pub fn allow_commands(mut self) -> Self {
+        self.allow_commands = true;
+        self
+    }
+
+    /// Runs script steps. Scripts are sandboxed but still use this host's CPU up to their
+    /// operation limit, so they're refused unless allowed.
+    pub fn allow_scripts(mut self) -> Self {
+        self.allow_scripts = true;
+        self
+    }
+
+    /// Asks `confirm` before running each step marked destructive, refusing the step if it
+    /// returns false.
+    pub fn with_destructive_confirmation(mut self, confirm: ConfirmDestructive) -> Self {
+        self.confirm_destructive = Some(confirm);
+        self
+    }
+
+    /// Sends cookies set by earlier responses with later HTTP/1 requests, like a browser would.
+    fn with_cookie_jar(mut self, cookies: Arc<CookieJar>) -> Self {
+        self.cookies = Some(cookies);
+        self
+    }
+
+    /// The names of all steps in the plan in execution order.
+    pub fn steps(&self) -> &[Arc<String>] {
+        &self.names
+    }
+
+    /// The names of steps which haven't been started yet.
+    pub fn remaining(&self) -> impl Iterator<Item = &Arc<String>> {
+        self.steps.iter().map(|(name, _)| name)
+    }
+
+    /// The index and name of the step which will run on the next call to [`Executor::next`], or
+    /// None if all steps have run.
+    pub fn current(&self) -> Option<(usize, &Arc<String>)> {
+        let (name, _) = self.steps.front()?;
+        Some((self.names.len() - self.steps.len(), name))
+    }
+
+    /// Whether the named step has already been run, including steps skipped by run.if.
+    pub fn has_run(&self, name: &str) -> bool {
+        let done = self.names.len() - self.steps.len();
+        self.names[..done].iter().any(|n| n.as_str() == name)
+    }
+
+    /// The resources used by the steps run so far.
+    pub fn usage(&self) -> UsageOutput {
+        let duration = self
+            .started
+            .map(|started| started.elapsed())
+            .unwrap_or_default();
+        self.meter.run_usage(self.attempts, time_delta(duration))
+    }
+
+    /// The output of a previously run step.
+    pub fn output(&self, name: &str) -> Option<&StepOutput> {
+        self.outputs.get(&Arc::new(name.to_owned()))
+    }
+
+    /// Inserts a step to run next, such as one generated from an earlier step's output. Step
+    /// names must be unique within a run.
+    pub fn enqueue(&mut self, name: String, step: Step) -> anyhow::Result<()> {
+        if self.names.iter().any(|n| **n == name) {
+            bail!("step {name} already exists");
+        }
+        let name = Arc::new(name);
+        let done = self.names.len() - self.steps.len();
+        self.names.insert(done, name.clone());
+        self.steps.push_front((name, step));
+        Ok(())
+    }
+
+    /// Remove the steps enqueued since `pending` steps were left to run.
+    fn dequeue_generated(&mut self, pending: usize) {
+        let generated = self.steps.len() - pending;
+        let done = self.names.len() - self.steps.len();
+        self.steps.drain(..generated);
+        self.names.drain(done..done + generated);
+    }
+
     pub async fn next(&mut self) -> anyhow::Result<StepOutput> {
         let Some((name, step)) = self.steps.pop_front() else {
             bail!(Error::Done);
         };
+        let step_name = StepName::with_run(self.run.clone(), name.clone());
+        let on_error = step.run.on_error.evaluate(&State {
+            data: &self.outputs,
+            locals: &self.locals,
+            current: StepPlanOutputs::default(),
+            run_while: None,
+            run_for: None,
+            run_count: None,
+            previous: None,
+            run_name: &self.run,
+            job_name: None,
+            cel: &self.cel,
+        })?;
+
+        let started = Instant::now();
+        self.started.get_or_insert(started);
+        self.meter.start_step();
+        let err = match self.run_authenticated(name.clone(), step).await {
+            Ok(mut output) => {
+                let blocked = self.record_violations(&mut output);
+                let exceeded = self.record_usage(&mut output, started);
+                // Steps skipped by run.if aren't stored unless something was recorded for them.
+                if self
+                    .reflections
+                    .record(self.cel.markers(), &name, &mut output)
+                    || blocked
+                    || exceeded
+                    || self.outputs.contains_key(&name)
+                {
+                    self.outputs.insert(name, output.clone());
+                }
+                return Ok(output);
+            }
+            Err(e) => e,
+        };
+        if on_error == OnError::Abort {
+            return Err(err);
+        }
+
+        // Record the error as the step's output and move on.
+        let mut output = StepOutput::new(step_name);
+        output.errors.push(StepError {
+            kind: "execution".to_owned(),
+            message: format!("{err:#}"),
+        });
+        self.record_violations(&mut output);
+        self.record_usage(&mut output, started);
+        self.outputs.insert(name, output.clone());
+        if let OnError::Jump(target) = on_error {
+            let Some(i) = self.steps.iter().position(|(n, _)| **n == target) else {
+                bail!("run.on_error jump target {target} is not a later step: {err:#}");
+            };
+            self.steps.drain(..i);
+        }
+        Ok(output)
+    }
+
+    /// Add the traffic the scope blocked during a step to its errors, returning whether there
+    /// was any.
+    fn record_violations(&self, output: &mut StepOutput) -> bool {
+        let Some(scope) = &self.scope else {
+            return false;
+        };
+        let violations = scope.take_violations();
+        let blocked = !violations.is_empty();
+        output
+            .errors
+            .extend(violations.into_iter().map(|message| StepError {
+                kind: "scope".to_owned(),
+                message,
+            }));
+        blocked
+    }
+
+    /// Set the resources a step used on its output, and skip the rest of the run if the step or
+    /// run went over budget. Returns whether it did.
+    fn record_usage(&mut self, output: &mut StepOutput, started: Instant) -> bool {
+        let attempts = output.jobs.len() as u64;
+        self.attempts += attempts;
+        let usage = self
+            .meter
+            .step_usage(attempts, time_delta(started.elapsed()));
+        let budget = self.meter.budget();
+        let exceeded = budget
+            .step
+            .exceeded(&usage)
+            .map(|reason| format!("step used {reason}"))
+            .or_else(|| {
+                budget
+                    .run
+                    .exceeded(&self.usage())
+                    .map(|reason| format!("run used {reason}"))
+            });
+        output.usage = Some(usage);
+        let Some(reason) = exceeded else {
+            return false;
+        };
+        output.errors.push(StepError {
+            kind: "budget".to_owned(),
+            message: format!("{reason} in devil.budget, skipping the remaining steps"),
+        });
+        self.steps.clear();
+        true
+    }
+
+    /// Run a step with the credentials of the login named in its run.auth, logging in first if
+    /// needed. If any of its responses are 401 Unauthorized, log in again and replay the step.
+    async fn run_authenticated(
+        &mut self,
+        name: Arc<String>,
+        step: Step,
+    ) -> anyhow::Result<StepOutput> {
+        let auth = step.run.auth.evaluate(&State {
+            data: &self.outputs,
+            locals: &self.locals,
+            current: StepPlanOutputs::default(),
+            run_while: None,
+            run_for: None,
+            run_count: None,
+            previous: None,
+            run_name: &self.run,
+            job_name: None,
+            cel: &self.cel,
+        })?;
+        let Some(auth) = auth else {
+            return self.run_step(name, step).await;
+        };
+        if !self.auth.contains_key(&auth) {
+            bail!("run.auth {auth:?} is not defined in devil.auth");
+        }
+        let mut logins = Vec::new();
+        if self.auth[&auth].jar.is_none() {
+            logins.push(self.login(&auth).await?);
+        }
+        let pending = self.steps.len();
+        let mut output = self.run_as(&auth, name.clone(), step.clone()).await?;
+        let replayed = auth::unauthorized(&output);
+        if replayed {
+            // The replay generates its steps, like a crawl's form steps, again.
+            self.dequeue_generated(pending);
+            logins.push(self.login(&auth).await?);
+            // A destructive step was already allowed to run.
+            let confirm = self.confirm_destructive.replace(Arc::new(|_: &str| true));
+            let replay = self.run_as(&auth, name, step).await;
+            self.confirm_destructive = confirm;
+            output = replay?;
+        }
+        output.auth = Some(AuthOutput {
+            name: auth,
+            logins,
+            replayed,
+        });
+        Ok(output)
+    }
+
+    /// Run a login again, replacing the credentials sent by steps tagged with it.
+    async fn login(&mut self, name: &str) -> anyhow::Result<ModuleOutput> {
+        let login = &self.auth[name];
+        let plan = login.request.evaluate(&State {
+            data: &self.outputs,
+            locals: &self.locals,
+            current: StepPlanOutputs::default(),
+            run_while: None,
+            run_for: None,
+            run_count: None,
+            previous: None,
+            run_name: &self.run,
+            job_name: None,
+            cel: &self.cel,
+        })?;
+        let (output, jar) = Box::pin(auth::login(
+            plan,
+            login.request.token.as_ref(),
+            self.run.run,
+            self.sockets.clone(),
+            self.confirm_destructive.clone(),
+            self.cel.clone(),
+        ))
+        .await
+        .map_err(|e| anyhow!("log in with devil.auth.{name}: {e:#}"))?;
+        self.auth.get_mut(name).unwrap().jar = Some(jar);
+        Ok(output)
+    }
+
+    /// Run a step sending the cookies and token of a login instead of any other cookie jar.
+    async fn run_as(
+        &mut self,
+        auth: &str,
+        name: Arc<String>,
+        step: Step,
+    ) -> anyhow::Result<StepOutput> {
+        let cookies = std::mem::replace(&mut self.cookies, self.auth[auth].jar.clone());
+        let output = self.run_step(name, step).await;
+        self.cookies = cookies;
+        output
+    }
+
+    /// The state to evaluate a composite step's plan with.
+    fn composite_inputs(&self, job_name: &JobName) -> State<'_> {
+        State {
+            data: &self.outputs,
+            locals: &self.locals,
+            current: StepPlanOutputs::default(),
+            run_while: None,
+            run_for: None,
+            run_count: None,
+            previous: None,
+            run_name: &self.run,
+            job_name: Some(job_name.clone()),
+            cel: &self.cel,
+        }
+    }
+
+    /// Run a step which the executor runs itself rather than through a protocol stack. These make
+    /// their own jobs, so plan validation rejects the run controls for repeating a step's jobs.
+    async fn run_composite(
+        &mut self,
+        name: Arc<String>,
+        job_name: &JobName,
+        protocols: &StepProtocols,
+        ctx: &Context,
+    ) -> anyhow::Result<StepOutput> {
+        let mut output = StepOutput::new(job_name.step_name());
+        match protocols {
+            StepProtocols::Session { session: request } => {
+                let plan = request.evaluate(&self.composite_inputs(job_name))?;
+                let run = session::session(
+                    plan,
+                    self.run.run,
+                    self.sockets.clone(),
+                    self.confirm_destructive.clone(),
+                    self.cel.clone(),
+                )
+                .await?;
+                output.session = Some(Arc::new(run));
+            }
+            StepProtocols::Command { command: request } => {
+                if !self.allow_commands {
+                    bail!("step {name} runs a local command, which isn't allowed in this run");
+                }
+                let plan = request.evaluate(&self.composite_inputs(job_name))?;
+                output.command = Some(Arc::new(command::command(plan).await?));
+            }
+            StepProtocols::Script { script: request } => {
+                if !self.allow_scripts {
+                    bail!("step {name} runs a script, which isn't allowed in this run");
+                }
+                let plan = request.evaluate(&self.composite_inputs(job_name))?;
+                let run = script::script(plan, &self.outputs, &mut self.locals).await?;
+                output.script = Some(Arc::new(run));
+            }
+            StepProtocols::Module { module } => {
+                let module = module.evaluate(&self.composite_inputs(job_name))?;
+                let run = self.run_module(module).await?;
+                output.module = Some(ModuleOutput(Arc::new(run)));
+            }
+            StepProtocols::Crawl { crawl: request } => {
+                let plan = request.evaluate(&self.composite_inputs(job_name))?;
+                let (mut crawl, jobs) = crawl::crawl(ctx, plan).await;
+                output.jobs.extend(jobs);
+                if crawl.plan.form_steps {
+                    // Generated steps run next, in the order their forms were found.
+                    for (i, form) in crawl.forms.iter().enumerate().rev() {
+                        let step_name = format!("{name}_form_{i}");
+                        self.enqueue(step_name.clone(), crawl::form_step(form))?;
+                        crawl.form_steps.insert(0, step_name);
+                    }
+                }
+                output.crawl = Some(Arc::new(crawl));
+            }
+            StepProtocols::Discover { discover: request } => {
+                let plan = request.evaluate(&self.composite_inputs(job_name))?;
+                let (discover, jobs) = discover::discover(ctx, plan).await;
+                output.jobs.extend(jobs);
+                output.discover = Some(Arc::new(discover));
+            }
+            StepProtocols::ForcedBrowse { forced_browse } => {
+                let plan = forced_browse.evaluate(&self.composite_inputs(job_name))?;
+                let (browse, jobs) = forced_browse::forced_browse(ctx, plan).await;
+                output.jobs.extend(jobs);
+                output.forced_browse = Some(Arc::new(browse));
+            }
+            StepProtocols::Vhost { vhost: request } => {
+                let plan = request.evaluate(&self.composite_inputs(job_name))?;
+                let (vhost, jobs) = vhost::vhost(ctx, plan).await;
+                output.jobs.extend(jobs);
+                output.vhost = Some(Arc::new(vhost));
+            }
+            StepProtocols::Range { range: request } => {
+                let plan = request.evaluate(&self.composite_inputs(job_name))?;
+                let (range, jobs) = range::range(ctx, plan).await;
+                output.jobs.extend(jobs);
+                output.range = Some(Arc::new(range));
+            }
+            StepProtocols::Conditional {
+                conditional: request,
+            } => {
+                let plan = request.evaluate(&self.composite_inputs(job_name))?;
+                let (conditional, jobs) = conditional::conditional(ctx, plan).await;
+                output.jobs.extend(jobs);
+                output.conditional = Some(Arc::new(conditional));
+            }
+            StepProtocols::H2Attack { h2_attack: request } => {
+                let plan = request.evaluate(&self.composite_inputs(job_name))?;
+                output.h2_attack = Some(Arc::new(h2_attack::h2_attack(ctx, plan).await));
+            }
+            StepProtocols::AlpnMatrix {
+                alpn_matrix: request,
+            } => {
+                let plan = request.evaluate(&self.composite_inputs(job_name))?;
+                let (matrix, jobs) = alpn_matrix::alpn_matrix(ctx, plan).await;
+                output.jobs.extend(jobs);
+                output.alpn_matrix = Some(Arc::new(matrix));
+            }
+            StepProtocols::KeepAlive {
+                keep_alive: request,
+            } => {
+                let plan = request.evaluate(&self.composite_inputs(job_name))?;
+                output.keep_alive = Some(Arc::new(keep_alive::keep_alive(ctx, plan).await));
+            }
+            StepProtocols::TcpBurst { tcp_burst: request } => {
+                let plan = request.evaluate(&self.composite_inputs(job_name))?;
+                output.tcp_burst = Some(Arc::new(tcp_burst::tcp_burst(ctx, plan).await));
+            }
+            StepProtocols::Dns { dns: request } => {
+                let plan = request.evaluate(&self.composite_inputs(job_name))?;
+                output.dns = Some(Arc::new(dns::dns(ctx, plan).await));
+            }
+            StepProtocols::Detect { detect: request } => {
+                let plan = request.evaluate(&self.composite_inputs(job_name))?;
+                let (detected, jobs) = detect::detect(ctx, plan).await;
+                // Later steps can choose a protocol stack by the verdict.
+                if let Some(local) = &detected.plan.local {
+                    self.locals.insert(
+                        local.clone().into(),
+                        cel_interpreter::Value::String(Arc::new(detected.verdict.clone())),
+                    );
+                }
+                output.jobs.extend(jobs);
+                output.detect = Some(Arc::new(detected));
+            }
+            StepProtocols::Takeover { takeover: request } => {
+                let plan = request.evaluate(&self.composite_inputs(job_name))?;
+                let (takeover, jobs) = takeover::takeover(ctx, plan).await?;
+                output.jobs.extend(jobs);
+                output.takeover = Some(Arc::new(takeover));
+            }
+            StepProtocols::Banner { banner: request } => {
+                let plan = request.evaluate(&self.composite_inputs(job_name))?;
+                output.banner = Some(Arc::new(banner::banner(ctx, plan).await));
+            }
+            StepProtocols::GrpcReflect {
+                grpc_reflect: request,
+            } => {
+                let plan = request.evaluate(&self.composite_inputs(job_name))?;
+                let run = grpc_reflect::grpc_reflect(ctx, plan).await;
+                output.grpc_reflect = Some(Arc::new(run));
+            }
+            _ => unreachable!("step {name} runs through a protocol stack"),
+        }
+        // Local steps don't connect through the step's context, so its egress and network don't
+        // apply to them.
+        if !matches!(
+            protocols,
+            StepProtocols::Session { .. }
+                | StepProtocols::Command { .. }
+                | StepProtocols::Script { .. }
+                | StepProtocols::Module { .. }
+        ) {
+            output.egress = ctx.egress.as_ref().map(|e| e.plan.name.clone());
+            output.network = ctx.network.as_ref().map(|n| n.as_ref().clone());
+        }
+        self.outputs.insert(name, output.clone());
+        Ok(output)
+    }
+
+    async fn run_step(&mut self, name: Arc<String>, step: Step) -> anyhow::Result<StepOutput> {
         let job_name = JobName::with_run(self.run.clone(), name.clone(), IterableKey::Uint(0));
         let mut inputs = State {
             data: &self.outputs,
@@ -82,8 +814,10 @@ impl<'a> Executor {
             run_while: None,
             run_for: None,
             run_count: None,
+            previous: None,
             run_name: &job_name.run_name(),
             job_name: Some(job_name.clone()),
+            cel: &self.cel,
         };
 
         // Check the if condition only before the first iteration.
@@ -91,11 +825,67 @@ impl<'a> Executor {
             return Ok(StepOutput::new(job_name.into_step_name()));
         }
 
+        // Steps which can't be split run whole on the first shard, so workers don't repeat them.
+        let sharded = self.shard.is_some() && Self::shardable(&step, &inputs)?;
+        if self.shard.is_some_and(|shard| shard.index != 0) && !sharded {
+            return Ok(StepOutput::new(job_name.into_step_name()));
+        }
+
+        if step.destructive
+            && !self
+                .confirm_destructive
+                .as_ref()
+                .is_some_and(|confirm| confirm(&name))
+        {
+            bail!("step {name} is destructive and wasn't allowed to run");
+        }
+
+        let egress =
+            step.run
+                .egress
+                .evaluate(&inputs)?
+                .map(|name| {
+                    self.egress.get(&name).cloned().ok_or_else(|| {
+                        anyhow!("run.egress {name:?} is not defined in devil.egress")
+                    })
+                })
+                .transpose()?;
+        let network = step
+            .run
+            .network
+            .evaluate(&inputs)?
+            .or_else(|| self.default_network.clone())
+            .map(|name| {
+                self.network.get(&name).cloned().ok_or_else(|| {
+                    anyhow!("run.network {name:?} is not built in or defined in devil.network")
+                })
+            })
+            .transpose()?;
+        let ctx = self.context(job_name.clone(), egress.clone(), network.clone());
+
+        if step.protocols.is_composite() {
+            return self
+                .run_composite(name, &job_name, &step.protocols, &ctx)
+                .await;
+        }
+
         let parallel = step.run.parallel.evaluate(&inputs)?;
         // Don't allow parallel execution with while (for now at least).
         if step.run.run_while.is_some() && !matches!(parallel, crate::Parallelism::Serial) {
             bail!("run.while cannot be used with run.parallel");
         }
+        if step.run.follow.is_some() && !matches!(parallel, crate::Parallelism::Serial) {
+            bail!("run.follow cannot be used with run.parallel");
+        }
+        let adaptive = step
+            .run
+            .adaptive
+            .as_ref()
+            .map(|adaptive| adaptive.evaluate(&inputs))
+            .transpose()?;
+        if adaptive.is_some() && !matches!(parallel, crate::Parallelism::Parallel(_)) {
+            bail!("run.adaptive requires run.parallel");
+        }
 
         let for_pairs = step.run.run_for.map(|f| f.evaluate(&inputs)).transpose()?;
 
@@ -104,9 +894,12 @@ impl<'a> Executor {
             count = count.min(pairs.len().try_into()?)
         }
         let count_usize: usize = count.try_into()?;
+        let shard = self.shard.filter(|_| sharded);
 
         // Preallocate space when able.
         let mut output = StepOutput::new(job_name.step_name());
+        output.egress = egress.as_ref().map(|e| e.plan.name.clone());
+        output.network = network.as_ref().map(|n| n.as_ref().clone());
         if step.run.run_while.is_none() {
             output.jobs.try_reserve(count_usize)?;
         }
@@ -115,24 +908,29 @@ impl<'a> Executor {
 
         // Compute the shared and duplicated protocol stacks.
         let mut stack = step.protocols.into_stack();
+
+        // Mirrored requests get their own connection, so they use the full stack.
+        let mirror = match &self.mirror {
+            Some(mirror) if is_http(&stack) => Some(mirror.evaluate(&inputs)?),
+            _ => None,
+        };
+        let mirror_stack = if mirror.is_some() {
+            stack.clone()
+        } else {
+            Vec::new()
+        };
         let shared = step.run.share.evaluate(&inputs)?;
         let shared_stack = shared
-            .map(|share| {
-                stack
-                    .iter()
-                    .enumerate()
-                    .find(|(_, proto)| proto.field() != share)
-                    .map(|(i, _)| i)
-            })
-            .flatten()
+            .and_then(|share| stack.iter().position(|proto| proto.field() == share))
             .map(|i| stack.split_off(i))
             .unwrap_or_default();
 
         // Create the runners for the shared stack in advance.
         let shared_runners = Self::prepare_runners(
-            &Arc::new(Context::new(job_name.clone())),
+            &Arc::new(ctx.for_job(job_name.clone())),
             &shared_stack,
             &mut inputs,
+            None,
         )?;
         let syncs = step
             .sync
@@ -157,7 +955,7 @@ impl<'a> Executor {
             Parallelism::Parallel(max_parallel) => {
                 let ctx = Arc::new(Context {
                     sync_locations: StepLocations::new(syncs, &signals, &pauses),
-                    job_name,
+                    ..ctx
                 });
 
                 let states: Vec<_> = (0..count)
@@ -183,10 +981,22 @@ impl<'a> Executor {
                         }
 
                         inputs.run_count = Some(crate::RunCountOutput { index: i });
+                        let mirror_runners = mirror
+                            .as_ref()
+                            .map(|m| {
+                                Self::prepare_runners(
+                                    &ctx,
+                                    &mirror_stack,
+                                    &mut inputs.clone(),
+                                    Some(&m.url),
+                                )
+                            })
+                            .transpose()?;
                         Ok((
                             key.unwrap_or(IterableKey::Uint(i)),
-                            Self::prepare_runners(&ctx, &stack, &mut inputs.clone())?,
+                            Self::prepare_runners(&ctx, &stack, &mut inputs.clone(), None)?,
                             shared,
+                            mirror_runners,
                         ))
                     })
                     .collect::<crate::Result<_>>()?;
@@ -220,58 +1030,106 @@ impl<'a> Executor {
 
                 // Start the parallel runners and execute.
                 let task_pool = Pool::bounded(max_parallel);
+                let controller =
+                    adaptive.map(|plan| Arc::new(adaptive::Controller::new(plan, max_parallel)));
                 let mut ops = Vec::with_capacity(states.len());
-                for ((key, runners, shared), shared_transport) in
-                    states.into_iter().zip(shared_transports)
+                let mut mirrors = Vec::with_capacity(states.len());
+                for (i, ((key, runners, shared, mirror_runners), shared_transport)) in
+                    states.into_iter().zip(shared_transports).enumerate()
                 {
+                    if shard.is_some_and(|shard| shard.skips(i as u64)) {
+                        continue;
+                    }
+                    mirrors.push(mirror_runners);
                     let job_name = inputs.job_name.clone().unwrap();
+                    if let Some(controller) = &controller {
+                        controller.acquire().await;
+                    }
+                    let controller = controller.clone();
                     let op = task_pool
                         .spawn(async move {
-                            anyhow::Ok((
-                                key,
-                                Executor::iteration(
-                                    Executor::start_runners(shared_transport, runners, 1)
-                                        .await?
-                                        .expect("any stack should have at least one protocol"),
-                                    shared,
-                                    job_name,
-                                )
-                                .await?,
-                            ))
+                            let start = Instant::now();
+                            let result = async {
+                                anyhow::Ok((
+                                    key,
+                                    Executor::iteration(
+                                        Executor::start_runners(shared_transport, runners, 1)
+                                            .await?
+                                            .expect("any stack should have at least one protocol"),
+                                        shared,
+                                        job_name,
+                                    )
+                                    .await?,
+                                ))
+                            }
+                            .await;
+                            if let Some(controller) = controller {
+                                let failed = result
+                                    .as_ref()
+                                    .map_or(true, |(_, (job, _))| adaptive::failed(job));
+                                controller.release(start.elapsed(), failed);
+                            }
+                            result
                         })
                         // Wait for our turn in the pool.
                         .await?;
                     ops.push(op);
                 }
 
-                output.jobs.extend(
-                    try_join_all(ops)
-                        .await?
-                        .into_iter()
-                        .collect::<Result<anyhow::Result<Vec<_>>, _>>()??
-                        .into_iter()
-                        .map(|(key, (out, _))| (key.to_owned(), Arc::new(out))),
-                );
+                let jobs = try_join_all(ops)
+                    .await?
+                    .into_iter()
+                    .collect::<Result<anyhow::Result<Vec<_>>, _>>()??;
+                for ((key, (out, _)), mirror_runners) in jobs.into_iter().zip(mirrors) {
+                    let out = Arc::new(out);
+                    if let (Some(plan), Some(runners)) = (&mirror, mirror_runners) {
+                        Self::mirror(runners, plan, &out, &key, &mut output).await;
+                    }
+                    output.jobs.insert(key, out);
+                }
+                output.adaptive = controller.map(|controller| controller.output());
             }
             Parallelism::Serial => {
-                let ctx = Arc::new(Context::new(job_name));
+                let ctx = Arc::new(ctx);
 
                 // Start the shared runners.
                 let mut shared_transport = Executor::start_runners(None, shared_runners, 1).await?;
 
+                let mut follow = step
+                    .run
+                    .follow
+                    .as_ref()
+                    .map(|f| Follower::new(f, &inputs))
+                    .transpose()?;
+
                 // Iteratively start and execute the independant runners.
-                for i in 0..count {
+                for i in 0.. {
                     // Process current item if for is used.
                     let mut key = None;
-                    if let Some(pairs) = for_iterator.as_mut() {
-                        let (k, v) = pairs.next().expect(
-                            "iteration count should be limited by the length of the for iterable",
-                        );
+                    let mut depth = 0;
+                    if i < count {
+                        if let Some(pairs) = for_iterator.as_mut() {
+                            let (k, v) = pairs.next().expect(
+                                "iteration count should be limited by the length of the for iterable",
+                            );
+                            if let Some(follow) = &mut follow {
+                                follow.visit(&v);
+                            }
+                            inputs.run_for = Some(crate::RunForOutput {
+                                key: k.clone(),
+                                value: v.0.try_into()?,
+                            });
+                            key = Some(k.clone());
+                        }
+                    } else if let Some((v, d)) = follow.as_mut().and_then(Follower::next) {
+                        // Followed values are exposed as for values after the original ones.
                         inputs.run_for = Some(crate::RunForOutput {
-                            key: k.clone(),
+                            key: IterableKey::Uint(i),
                             value: v.0.try_into()?,
                         });
-                        key = Some(k.clone());
+                        depth = d;
+                    } else {
+                        break;
                     }
                     let key = key.unwrap_or(IterableKey::Uint(i));
 
@@ -283,9 +1141,23 @@ impl<'a> Executor {
                         }
                         output.jobs.try_reserve(1)?;
                     }
+                    if shard.is_some_and(|shard| shard.skips(i)) {
+                        continue;
+                    }
 
                     inputs.run_count = Some(crate::RunCountOutput { index: i });
-                    let runners = Self::prepare_runners(&ctx, &stack, &mut inputs.clone())?;
+                    let runners = Self::prepare_runners(&ctx, &stack, &mut inputs.clone(), None)?;
+                    let mirror_runners = mirror
+                        .as_ref()
+                        .map(|m| {
+                            Self::prepare_runners(
+                                &ctx,
+                                &mirror_stack,
+                                &mut inputs.clone(),
+                                Some(&m.url),
+                            )
+                        })
+                        .transpose()?;
                     let out;
                     (out, shared_transport) = Self::iteration(
                         Self::start_runners(shared_transport, runners, 1)
@@ -295,33 +1167,134 @@ impl<'a> Executor {
                         inputs.job_name.as_ref().unwrap().clone(),
                     )
                     .await?;
-                    output.jobs.insert(key, Arc::new(out));
+                    let out = Arc::new(out);
+                    if let (Some(plan), Some(runners)) = (&mirror, mirror_runners) {
+                        Self::mirror(runners, plan, &out, &key, &mut output).await;
+                    }
+                    // Later jobs can build on this one, like for multi-leg auth handshakes over a
+                    // shared connection.
+                    inputs.previous = Some(out.clone());
+                    output.jobs.insert(key, out);
+                    if let Some(follow) = &mut follow {
+                        follow.push(&inputs, depth)?;
+                    }
                 }
             }
             Parallelism::Pipelined => {
-                bail!("pipelining support is not yet implemented")
+                let Some(shared) = shared else {
+                    bail!("run.parallel = \"pipelined\" requires run.share");
+                };
+                let ctx = Arc::new(ctx);
+                let mut transport = Executor::start_runners(None, shared_runners, 1)
+                    .await?
+                    .ok_or_else(|| anyhow!("run.share must name a protocol used by the step"))?;
+
+                // Write every request before reading any responses.
+                let mut sent = Vec::with_capacity(count_usize);
+                for i in 0..count {
+                    let mut key = IterableKey::Uint(i);
+                    if let Some(pairs) = for_iterator.as_mut() {
+                        let (k, v) = pairs.next().expect(
+                            "iteration count should be limited by the length of the for iterable",
+                        );
+                        inputs.run_for = Some(crate::RunForOutput {
+                            key: k.clone(),
+                            value: v.0.try_into()?,
+                        });
+                        key = k;
+                    }
+                    if shard.is_some_and(|shard| shard.skips(i)) {
+                        continue;
+                    }
+                    if let Some(job_name) = &mut inputs.job_name {
+                        job_name.job = key.clone();
+                    }
+                    inputs.run_count = Some(crate::RunCountOutput { index: i });
+                    let runners = Self::prepare_runners(&ctx, &stack, &mut inputs.clone(), None)?;
+                    let job_name = inputs.job_name.clone().unwrap();
+                    let job;
+                    (job, transport) = pipeline::send(key, job_name, runners, transport).await?;
+                    sent.push(job);
+                }
+                for (key, out) in pipeline::receive(sent, transport, shared).await? {
+                    output.jobs.insert(key, Arc::new(out));
+                }
             }
         }
 
+        if output.jobs.len() > 1 {
+            output.latency = Some(latency::aggregate(output.jobs.values().map(Arc::as_ref)));
+        }
+        // Jobs only follow one another on a connection when they share one.
+        if shared.is_some() {
+            desync::detect(&mut output);
+        }
         self.outputs.insert(name, output.clone());
         Ok(output)
     }
 
+    /// Whether a step's jobs can be divided between shards: they come from a protocol stack,
+    /// there is more than one, and none of them depends on an earlier one.
+    fn shardable(step: &Step, inputs: &State) -> anyhow::Result<bool> {
+        if step.run.run_while.is_some()
+            || step.run.follow.is_some()
+            || step.protocols.clone().into_stack().is_empty()
+        {
+            return Ok(false);
+        }
+        let mut count = step.run.count.evaluate(inputs)?;
+        if let Some(run_for) = &step.run.run_for {
+            count = count.min(run_for.evaluate(inputs)?.len().try_into()?);
+        }
+        Ok(count > 1)
+    }
+
+    /// The connection settings shared by every job in a step.
+    fn context(
+        &self,
+        job_name: JobName,
+        egress: Option<Arc<Egress>>,
+        network: Option<Arc<NetworkPlanOutput>>,
+    ) -> Context {
+        Context::new(
+            job_name,
+            self.sockets.clone(),
+            self.cache.clone(),
+            self.tls_sessions.clone(),
+            self.verifiers.clone(),
+            egress,
+            self.cookies.clone(),
+            network,
+            self.scope.clone(),
+            self.cel.clone(),
+        )
+    }
+
     fn prepare_runners<'p>(
         ctx: &Arc<Context>,
         stack: impl IntoIterator<Item = &'p Protocol>,
         inputs: &mut State<'_>,
+        rebase: Option<&url::Url>,
     ) -> Result<Vec<Runner>, crate::Error> {
         // Reverse iterate the protocol stack for evaluation so that protocols below can access
         // request fields from higher protocols.
         let requests = stack
             .into_iter()
-            .map(|proto| {
-                let req = proto.evaluate(inputs)?;
+            .enumerate()
+            .map(|(i, proto)| {
+                let mut req = proto.evaluate(inputs)?;
+                // Rebase the top request before lower protocols derive their targets from it.
+                if let (0, Some(base)) = (i, rebase) {
+                    req.rebase_url(base);
+                }
+                if let (Some(scope), Some(url)) = (&ctx.scope, req.url()) {
+                    scope.check_url(url)?;
+                }
                 match req.clone() {
                     StepPlanOutput::Graphql(req) => {
                         inputs.current.graphql = Some(PlanWrapper::new(req))
                     }
+                    StepPlanOutput::Grpc(req) => inputs.current.grpc = Some(PlanWrapper::new(req)),
                     StepPlanOutput::Http(req) => inputs.current.http = Some(PlanWrapper::new(req)),
                     StepPlanOutput::H1c(req) => inputs.current.h1c = Some(PlanWrapper::new(req)),
                     StepPlanOutput::H1(req) => inputs.current.h1 = Some(PlanWrapper::new(req)),
@@ -333,11 +1306,14 @@ impl<'a> Executor {
                     StepPlanOutput::RawH2(req) => {
                         inputs.current.raw_h2 = Some(PlanWrapper::new(req))
                     }
+                    StepPlanOutput::H3(req) => inputs.current.h3 = Some(PlanWrapper::new(req)),
                     StepPlanOutput::Tls(req) => inputs.current.tls = Some(PlanWrapper::new(req)),
                     StepPlanOutput::Tcp(req) => inputs.current.tcp = Some(PlanWrapper::new(req)),
                     StepPlanOutput::RawTcp(req) => {
                         inputs.current.raw_tcp = Some(PlanWrapper::new(req))
                     }
+                    StepPlanOutput::Quic(req) => inputs.current.quic = Some(PlanWrapper::new(req)),
+                    StepPlanOutput::Ws(req) => inputs.current.ws = Some(PlanWrapper::new(req)),
                 }
                 Ok(req)
             })
@@ -367,6 +1343,117 @@ impl<'a> Executor {
         Ok(runners)
     }
 
+    /// Run a plan file as part of the current step, with the same connections, scope, budget and
+    /// permissions as this plan.
+    async fn run_module(&self, module: ModulePlanOutput) -> anyhow::Result<RunOutput> {
+        let path = match self.imports.last().and_then(|file| file.parent()) {
+            Some(dir) => absolute(&dir.join(&module.path)),
+            None => absolute(Path::new(&module.path)),
+        };
+        if self.imports.contains(&path) {
+            let chain = self
+                .imports
+                .iter()
+                .chain([&path])
+                .map(|file| file.display())
+                .join(" -> ");
+            bail!("module {} imports itself: {chain}", module.path);
+        }
+        let text = read_to_string(&path)
+            .await
+            .map_err(|e| anyhow::anyhow!("read module {}: {e}", path.display()))?;
+        let plan = Plan::parse(&text)?;
+        // Modules share the parent's run ID so their outputs can be correlated.
+        let mut output = RunOutput::new(RunName {
+            plan: plan.name.clone(),
+            run: self.run.run,
+        });
+        // Modules share the parent's CEL state, so their own devil.seed and devil.now don't apply.
+        let mut executor =
+            Executor::with_cel_state(&plan, output.name.clone(), module.params, self.cel.clone())?;
+        // The parent's sockets already count against its budget and check its scope, so the
+        // module's own budget and scope don't apply.
+        executor.sockets = self.sockets.clone();
+        executor.meter = self.meter.clone();
+        executor.scope = self.scope.clone();
+        executor.cache = self.cache.clone();
+        executor.tls_sessions = self.tls_sessions.clone();
+        executor.verifiers = self.verifiers.clone();
+        executor.cookies = self.cookies.clone();
+        executor.shard = self.shard;
+        if let Some(name) = &self.default_network {
+            executor
+                .network
+                .entry(name.clone())
+                .or_insert_with(|| self.network[name].clone());
+            executor.default_network = Some(name.clone());
+        }
+        // Modules are run with the parent's permission to run destructive steps.
+        executor.confirm_destructive = self.confirm_destructive.clone();
+        executor.allow_commands = self.allow_commands;
+        executor.allow_scripts = self.allow_scripts;
+        executor.imports = self.imports.iter().cloned().chain([path]).collect();
+
+        // Each of the module's steps counts towards its own usage, so restore the parent step's
+        // start afterwards.
+        let step_start = self.meter.step_start();
+        let result = async {
+            while executor.current().is_some() {
+                // Boxed since modules may recursively run other modules.
+                let step = Box::pin(executor.next()).await?;
+                output.steps.insert(step.name.step.clone(), Arc::new(step));
+            }
+            anyhow::Ok(())
+        }
+        .await;
+        self.meter.restore_step_start(step_start);
+        result?;
+        output.usage = Some(executor.usage());
+        Ok(output)
+    }
+
+    /// Send a job's requests to the mirror target, recording the result in compare mode.
+    async fn mirror(
+        runners: Vec<Runner>,
+        plan: &MirrorPlanOutput,
+        original: &JobOutput,
+        key: &IterableKey,
+        output: &mut StepOutput,
+    ) {
+        let name = original.name.clone();
+        let run = async move {
+            let runner = Self::start_runners(None, runners, 1)
+                .await?
+                .expect("any stack should have at least one protocol");
+            anyhow::Ok(Self::iteration(runner, None, name).await?.0)
+        };
+        match plan.mode {
+            MirrorMode::FireAndForget => {
+                tokio::spawn(async move {
+                    if let Err(e) = run.await {
+                        debug!(?e, "mirrored job failed");
+                    }
+                });
+            }
+            MirrorMode::Compare => match run.await {
+                Ok(job) => {
+                    output.mirror.insert(
+                        key.clone(),
+                        Arc::new(MirrorOutput::compare(
+                            plan.url.clone(),
+                            original,
+                            Arc::new(job),
+                        )),
+                    );
+                }
+                Err(e) => output.errors.push(StepError {
+                    kind: "mirror".to_owned(),
+                    message: format!("{e:#}"),
+                }),
+            },
+        }
+    }
+
     async fn start_runners(
         shared_transport: Option<Runner>,
         runners: Vec<Runner>,
@@ -391,6 +1478,15 @@ impl<'a> Executor {
         name: JobName,
     ) -> anyhow::Result<(JobOutput, Option<Runner>)> {
         runner.execute().await;
+        Self::unwind(runner, shared, name).await
+    }
+
+    /// Finish an executed runner and those below it, down to the shared one if any.
+    async fn unwind(
+        runner: Runner,
+        shared: Option<ProtocolField>,
+        name: JobName,
+    ) -> anyhow::Result<(JobOutput, Option<Runner>)> {
         let mut output = JobOutput::empty(name);
         let mut current = Some(runner);
         while let Some(r) = current {
@@ -407,6 +1503,23 @@ impl<'a> Executor {
     }
 }
 
+/// Whether a stack is topped by a protocol which can be mirrored.
+fn is_http(stack: &[Protocol]) -> bool {
+    matches!(
+        stack.first(),
+        Some(
+            Protocol::Graphql(_)
+                | Protocol::Grpc(_)
+                | Protocol::Http(_)
+                | Protocol::H1c(_)
+                | Protocol::H1(_)
+                | Protocol::H2c(_)
+                | Protocol::H2(_)
+                | Protocol::H3(_)
+        )
+    )
+}
+
 #[derive(Debug, Clone)]
 struct State<'a> {
     data: &'a HashMap<Arc<String>, StepOutput>,
@@ -414,9 +1527,11 @@ struct State<'a> {
     run_while: Option<crate::RunWhileOutput>,
     run_for: Option<crate::RunForOutput>,
     run_count: Option<crate::RunCountOutput>,
+    previous: Option<Arc<JobOutput>>,
     locals: &'a HashMap<cel_interpreter::objects::Key, cel_interpreter::Value>,
     run_name: &'a RunName,
     job_name: Option<JobName>,
+    cel: &'a Arc<CelState>,
 }
 
 impl<'a> crate::State<'a, &'a Arc<String>, StateIterator<'a>> for State<'a> {
@@ -435,6 +1550,9 @@ impl<'a> crate::State<'a, &'a Arc<String>, StateIterator<'a>> for State<'a> {
     fn run_count(&self) -> &Option<crate::RunCountOutput> {
         &self.run_count
     }
+    fn previous(&self) -> Option<&JobOutput> {
+        self.previous.as_deref()
+    }
     fn locals(&self) -> cel_interpreter::objects::Map {
         self.locals.clone().into()
     }
@@ -449,6 +1567,9 @@ impl<'a> crate::State<'a, &'a Arc<String>, StateIterator<'a>> for State<'a> {
     fn job_name(&self) -> Option<&crate::JobName> {
         self.job_name.as_ref()
     }
+    fn cel_state(&self) -> &Arc<CelState> {
+        self.cel
+    }
 }
 
 struct StateIterator<'a> {
@@ -472,17 +1593,90 @@ pub enum Error {
 pub(super) struct Context {
     sync_locations: sync::StepLocations,
     pub job_name: JobName,
+    pub sockets: Arc<dyn SocketProvider>,
+    pub cache: Arc<HttpCache>,
+    pub tls_sessions: Arc<TlsSessions>,
+    pub verifiers: Option<Arc<dyn VerifierProvider>>,
+    pub egress: Option<Arc<Egress>>,
+    pub cookies: Option<Arc<CookieJar>>,
+    pub network: Option<Arc<NetworkPlanOutput>>,
+    pub scope: Option<Arc<Guard>>,
+    pub cel: Arc<CelState>,
 }
 
 impl Context {
-    fn new(job_name: JobName) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        job_name: JobName,
+        sockets: Arc<dyn SocketProvider>,
+        cache: Arc<HttpCache>,
+        tls_sessions: Arc<TlsSessions>,
+        verifiers: Option<Arc<dyn VerifierProvider>>,
+        egress: Option<Arc<Egress>>,
+        cookies: Option<Arc<CookieJar>>,
+        network: Option<Arc<NetworkPlanOutput>>,
+        scope: Option<Arc<Guard>>,
+        cel: Arc<CelState>,
+    ) -> Self {
         Self {
             sync_locations: sync::StepLocations::default(),
             job_name,
+            sockets,
+            cache,
+            tls_sessions,
+            verifiers,
+            egress,
+            cookies,
+            network,
+            scope,
+            cel,
         }
     }
+
+    /// A context for another job in the same step, sharing its connections settings.
+    pub(super) fn for_job(&self, job_name: JobName) -> Self {
+        Self::new(
+            job_name,
+            self.sockets.clone(),
+            self.cache.clone(),
+            self.tls_sessions.clone(),
+            self.verifiers.clone(),
+            self.egress.clone(),
+            self.cookies.clone(),
+            self.network.clone(),
+            self.scope.clone(),
+            self.cel.clone(),
+        )
+    }
+
     pub(super) fn next_sync_location(&self, loc: location::Location) -> Option<StepLocation> {
         // TODO: implement
         None
     }
 }
+
+/// The canonical form of a plan file's path, so imports of the same file compare equal.
+fn absolute(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_owned())
+}
+
+/// Read a file a plan refers to. Without the native feature tokio has no filesystem support, so
+/// this reads it directly from the host.
+async fn read_to_string(path: impl AsRef<Path>) -> std::io::Result<String> {
+    #[cfg(feature = "native")]
+    return tokio::fs::read_to_string(path).await;
+    #[cfg(not(feature = "native"))]
+    return std::fs::read_to_string(path);
+}
+
+/// Write a file a plan asked for, like [`read_to_string`].
+async fn write(path: impl AsRef<Path>, contents: Vec<u8>) -> std::io::Result<()> {
+    #[cfg(feature = "native")]
+    return tokio::fs::write(path, contents).await;
+    #[cfg(not(feature = "native"))]
+    return std::fs::write(path, contents);
+}
+
+fn time_delta(duration: std::time::Duration) -> TimeDelta {
+    TimeDelta::from_std(duration).expect("durations should fit in chrono")
+}
@@ -0,0 +1,263 @@
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+
+use chrono::TimeDelta;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{Instant, Sleep};
+
+use crate::NetworkPlanOutput;
+
+use super::socket::{BoxStream, SocketOptions};
+
+/// The most bytes delayed as one chunk, so large writes and reads are spread out like packets.
+const CHUNK: usize = 16 * 1024;
+/// The most bytes held back in each direction before the caller has to wait.
+const MAX_QUEUED: usize = 256 * 1024;
+/// The shortest delay for a lost chunk, like TCP's minimum retransmission timeout.
+const MIN_RETRANSMIT: Duration = Duration::from_millis(200);
+
+/// The profiles available without defining them in devil.network. A plan can replace one by
+/// defining a profile of the same name.
+pub(super) fn presets() -> Vec<NetworkPlanOutput> {
+    let profile = |name: &str, bandwidth, latency, jitter, loss| NetworkPlanOutput {
+        name: name.to_owned(),
+        bandwidth: Some(bandwidth),
+        latency: Some(TimeDelta::milliseconds(latency).into()),
+        jitter: Some(TimeDelta::milliseconds(jitter).into()),
+        loss: Some(loss),
+        fault: None,
+    };
+    vec![
+        profile("3g", 1_600_000, 150, 40, 0.01),
+        profile("satellite", 5_000_000, 300, 20, 0.005),
+        profile("lossy-wifi", 20_000_000, 5, 30, 0.05),
+    ]
+}
+
+/// Connect, waiting a round trip first if the network has latency, then emulate the network's
+/// conditions on everything sent and received.
+pub(super) async fn connect(
+    ctx: &super::Context,
+    local_addr: SocketAddr,
+    remote_addr: SocketAddr,
+    options: SocketOptions,
+) -> io::Result<BoxStream> {
+    let Some(plan) = &ctx.network else {
+        return ctx
+            .sockets
+            .connect_tcp_with_options(local_addr, remote_addr, options)
+            .await;
+    };
+    let conditions = Conditions::new(plan);
+    tokio::time::sleep(conditions.latency * 2).await;
+    let stream = ctx
+        .sockets
+        .connect_tcp_with_options(local_addr, remote_addr, options)
+        .await?;
+    // Seeded from the run's generator so devil.seed makes the conditions repeatable.
+    let rng = ctx.cel.with_rng(|rng| StdRng::seed_from_u64(rng.gen()));
+    Ok(Box::new(NetworkStream::new(stream, conditions, rng)))
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Conditions {
+    bandwidth: Option<u64>,
+    latency: Duration,
+    jitter: Duration,
+    loss: f64,
+}
+
+impl Conditions {
+    fn new(plan: &NetworkPlanOutput) -> Self {
+        let to_std = |d: &Option<cel_interpreter::Duration>| {
+            d.as_ref()
+                .and_then(|d| d.0.to_std().ok())
+                .unwrap_or_default()
+        };
+        Self {
+            bandwidth: plan.bandwidth,
+            latency: to_std(&plan.latency),
+            jitter: to_std(&plan.jitter),
+            loss: plan.loss.unwrap_or_default(),
+        }
+    }
+}
+
+/// Bytes travelling in one direction, each chunk held until it would have arrived.
+#[derive(Debug)]
+struct Link {
+    queue: VecDeque<(Instant, Vec<u8>)>,
+    queued: usize,
+    /// When the link finishes transmitting the chunks already queued.
+    free: Instant,
+    sleep: Pin<Box<Sleep>>,
+}
+
+impl Link {
+    fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            queue: VecDeque::new(),
+            queued: 0,
+            free: now,
+            sleep: Box::pin(tokio::time::sleep_until(now)),
+        }
+    }
+
+    fn push(&mut self, conditions: &Conditions, rng: &mut StdRng, data: Vec<u8>) {
+        let now = Instant::now();
+        let bits = u32::try_from(data.len() * 8).unwrap_or(u32::MAX);
+        let transmit = conditions.bandwidth.map_or(Duration::ZERO, |bandwidth| {
+            Duration::from_secs(1) * bits / u32::try_from(bandwidth).unwrap_or(u32::MAX)
+        });
+        self.free = self.free.max(now) + transmit;
+        let mut delay = conditions.latency + rng.gen_range(Duration::ZERO..=conditions.jitter);
+        if conditions.loss > 0.0 && rng.gen_bool(conditions.loss) {
+            delay += MIN_RETRANSMIT.max(conditions.latency * 2);
+        }
+        // Chunks can't overtake each other, so a lost one holds up the rest like in TCP.
+        let last = self.queue.back().map_or(now, |(deadline, _)| *deadline);
+        self.queued += data.len();
+        self.queue.push_back(((self.free + delay).max(last), data));
+    }
+
+    /// The first chunk once it has arrived, or None if nothing is queued.
+    fn poll_front(&mut self, cx: &mut Context<'_>) -> Poll<Option<&[u8]>> {
+        let Some((deadline, _)) = self.queue.front() else {
+            return Poll::Ready(None);
+        };
+        let deadline = *deadline;
+        if self.sleep.deadline() != deadline {
+            self.sleep.as_mut().reset(deadline);
+        }
+        ready!(self.sleep.as_mut().poll(cx));
+        Poll::Ready(self.queue.front().map(|(_, data)| data.as_slice()))
+    }
+
+    fn consume(&mut self, n: usize) {
+        let (_, data) = self
+            .queue
+            .front_mut()
+            .expect("consumed bytes should be queued");
+        data.drain(..n);
+        self.queued -= n;
+        if data.is_empty() {
+            self.queue.pop_front();
+        }
+    }
+}
+
+/// Delays, throttles, and randomly holds back chunks in both directions of a stream.
+#[derive(Debug)]
+struct NetworkStream<S> {
+    inner: S,
+    conditions: Conditions,
+    rng: StdRng,
+    sent: Link,
+    received: Link,
+    eof: bool,
+}
+
+impl<S> NetworkStream<S> {
+    fn new(inner: S, conditions: Conditions, rng: StdRng) -> Self {
+        Self {
+            inner,
+            conditions,
+            rng,
+            sent: Link::new(),
+            received: Link::new(),
+            eof: false,
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> NetworkStream<S> {
+    /// Write every sent chunk which has arrived.
+    fn poll_send(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while let Some(data) = ready!(self.sent.poll_front(cx)) {
+            let n = ready!(Pin::new(&mut self.inner).poll_write(cx, data))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.sent.consume(n);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncRead for NetworkStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        // Callers may wait for a response without flushing, so keep sending here too.
+        if let Poll::Ready(Err(e)) = this.poll_send(cx) {
+            return Poll::Ready(Err(e));
+        }
+        // Receive everything available so it's delayed from when it arrived, not when it's read.
+        while !this.eof && this.received.queued < MAX_QUEUED {
+            let mut chunk = vec![0; CHUNK];
+            let mut chunk_buf = ReadBuf::new(&mut chunk);
+            match Pin::new(&mut this.inner).poll_read(cx, &mut chunk_buf) {
+                Poll::Pending => break,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Ready(Ok(())) => {
+                    let n = chunk_buf.filled().len();
+                    chunk.truncate(n);
+                    // An empty chunk marks the end of the stream, which is delayed too.
+                    this.eof = n == 0;
+                    this.received.push(&this.conditions, &mut this.rng, chunk);
+                }
+            }
+        }
+        let Some(data) = ready!(this.received.poll_front(cx)) else {
+            return Poll::Pending;
+        };
+        let n = data.len().min(buf.remaining());
+        buf.put_slice(&data[..n]);
+        if n > 0 {
+            this.received.consume(n);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for NetworkStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        match this.poll_send(cx) {
+            Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+            Poll::Pending if this.sent.queued >= MAX_QUEUED => return Poll::Pending,
+            _ => {}
+        }
+        let n = buf.len().min(CHUNK);
+        this.sent
+            .push(&this.conditions, &mut this.rng, buf[..n].to_vec());
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_send(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_send(cx))?;
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
@@ -0,0 +1,290 @@
+//! A parser for HTTP/1 response heads which accepts whatever a server sends, recording each way it
+//! strays from RFC 9112 instead of rejecting the response. It's called again as more of the head
+//! arrives, so it only needs the bytes read so far.
+
+use bytes::Bytes;
+
+use super::http1::ResponseHead;
+use crate::{Http1Deviation, Http1DeviationKind, HttpHeader, MaybeUtf8};
+
+/// Parse the status line and headers at the start of `buf`. The status line is parsed once it's
+/// complete, and the headers once the empty line ending the head has been read.
+pub(super) fn parse(buf: &[u8]) -> ResponseHead {
+    let mut head = ResponseHead::default();
+    let mut deviations = Vec::new();
+    let mut lines = Lines { buf, pos: 0 };
+
+    // Skip empty lines before the status line.
+    let (offset, status_line) = loop {
+        let Some((offset, line)) = lines.next(&mut deviations) else {
+            return head;
+        };
+        if !line.is_empty() {
+            break (offset, line);
+        }
+        deviations.push(deviation(
+            Http1DeviationKind::LeadingEmptyLine,
+            offset,
+            line,
+        ));
+    };
+    parse_status_line(&mut head, &mut deviations, offset, status_line);
+
+    let mut headers: Vec<HttpHeader> = Vec::new();
+    loop {
+        let Some((offset, line)) = lines.next(&mut deviations) else {
+            // Report the status line while waiting for the rest of the head.
+            head.deviations = deviations;
+            return head;
+        };
+        if line.is_empty() {
+            break;
+        }
+        if line.starts_with(b" ") || line.starts_with(b"\t") {
+            deviations.push(deviation(Http1DeviationKind::ObsFold, offset, line));
+            let continuation = trim(line);
+            // Unfold into the previous value with a single space, as RFC 9112 section 5.2
+            // suggests. A fold with nothing to continue is kept as a line without a name.
+            match headers.last_mut() {
+                Some(header) => {
+                    let mut value = header.value.to_vec();
+                    if !value.is_empty() && !continuation.is_empty() {
+                        value.push(b' ');
+                    }
+                    value.extend_from_slice(continuation);
+                    header.value = MaybeUtf8(Bytes::from(value).into());
+                }
+                None => headers.push(HttpHeader {
+                    key: None,
+                    value: MaybeUtf8(Bytes::copy_from_slice(continuation).into()),
+                }),
+            }
+            continue;
+        }
+        let Some(colon) = line.iter().position(|b| *b == b':') else {
+            deviations.push(deviation(Http1DeviationKind::MissingColon, offset, line));
+            headers.push(HttpHeader {
+                key: None,
+                value: MaybeUtf8(Bytes::copy_from_slice(line).into()),
+            });
+            continue;
+        };
+        let mut name = &line[..colon];
+        if name.ends_with(b" ") || name.ends_with(b"\t") {
+            deviations.push(deviation(
+                Http1DeviationKind::SpaceBeforeColon,
+                offset,
+                line,
+            ));
+            name = trim(name);
+        }
+        if name.is_empty() || !name.iter().copied().all(is_token) {
+            deviations.push(deviation(Http1DeviationKind::HeaderName, offset, line));
+        }
+        let value = trim(&line[colon + 1..]);
+        if value.iter().copied().any(is_control) {
+            deviations.push(deviation(
+                Http1DeviationKind::ControlCharacter,
+                offset,
+                line,
+            ));
+        }
+        headers.push(HttpHeader {
+            key: Some(MaybeUtf8(Bytes::copy_from_slice(name).into())),
+            value: MaybeUtf8(Bytes::copy_from_slice(value).into()),
+        });
+    }
+
+    head.headers = Some(headers);
+    head.deviations = deviations;
+    head.len = Some(lines.pos);
+    head
+}
+
+fn parse_status_line(
+    head: &mut ResponseHead,
+    deviations: &mut Vec<Http1Deviation>,
+    offset: usize,
+    line: &[u8],
+) {
+    let (protocol, rest) = split_space(line);
+    if protocol != b"HTTP/1.1" && protocol != b"HTTP/1.0" {
+        deviations.push(deviation(Http1DeviationKind::Protocol, offset, line));
+    }
+    head.protocol = Some(MaybeUtf8(Bytes::copy_from_slice(protocol).into()));
+
+    let Some(rest) = rest else {
+        deviations.push(deviation(Http1DeviationKind::StatusCode, offset, line));
+        deviations.push(deviation(Http1DeviationKind::MissingReason, offset, line));
+        return;
+    };
+    let (code, reason) = split_space(rest);
+    if code.len() != 3 || !code.iter().all(u8::is_ascii_digit) {
+        deviations.push(deviation(Http1DeviationKind::StatusCode, offset, line));
+    }
+    // Keep codes which are numeric but the wrong length, like 2000.
+    head.status_code = std::str::from_utf8(code).ok().and_then(|c| c.parse().ok());
+
+    let Some(reason) = reason else {
+        deviations.push(deviation(Http1DeviationKind::MissingReason, offset, line));
+        return;
+    };
+    if reason.iter().copied().any(is_control) {
+        deviations.push(deviation(
+            Http1DeviationKind::ControlCharacter,
+            offset,
+            line,
+        ));
+    }
+    head.reason = Some(MaybeUtf8(Bytes::copy_from_slice(reason).into()));
+}
+
+/// The complete lines at the start of a buffer.
+struct Lines<'a> {
+    buf: &'a [u8],
+    /// The start of the next line.
+    pos: usize,
+}
+
+impl<'a> Lines<'a> {
+    /// The next line's offset and contents without its line ending, or None if it hasn't been
+    /// fully read. Lines ending in a bare LF are recorded in `deviations`.
+    fn next(&mut self, deviations: &mut Vec<Http1Deviation>) -> Option<(usize, &'a [u8])> {
+        let start = self.pos;
+        let len = self.buf[start..].iter().position(|b| *b == b'\n')?;
+        self.pos = start + len + 1;
+        let line = &self.buf[start..start + len];
+        match line.strip_suffix(b"\r") {
+            Some(line) => Some((start, line)),
+            None => {
+                deviations.push(deviation(Http1DeviationKind::BareLf, start, line));
+                Some((start, line))
+            }
+        }
+    }
+}
+
+fn deviation(kind: Http1DeviationKind, offset: usize, line: &[u8]) -> Http1Deviation {
+    Http1Deviation {
+        kind,
+        offset: offset as u64,
+        line: MaybeUtf8(Bytes::copy_from_slice(line).into()),
+    }
+}
+
+/// Split at the first run of spaces, returning None for the rest if there were none.
+fn split_space(line: &[u8]) -> (&[u8], Option<&[u8]>) {
+    match line.iter().position(|b| *b == b' ') {
+        Some(i) => {
+            let rest = &line[i..];
+            let skip = rest.iter().take_while(|b| **b == b' ').count();
+            (&line[..i], Some(&rest[skip..]))
+        }
+        None => (line, None),
+    }
+}
+
+/// Remove optional whitespace, meaning spaces and tabs, from both ends.
+fn trim(mut value: &[u8]) -> &[u8] {
+    while let [b' ' | b'\t', rest @ ..] = value {
+        value = rest;
+    }
+    while let [rest @ .., b' ' | b'\t'] = value {
+        value = rest;
+    }
+    value
+}
+
+/// Whether a byte is allowed in a token, per RFC 9110 section 5.6.2.
+fn is_token(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+fn is_control(b: u8) -> bool {
+    b.is_ascii_control() && b != b'\t'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(head: &ResponseHead) -> Vec<(Http1DeviationKind, u64)> {
+        head.deviations.iter().map(|d| (d.kind, d.offset)).collect()
+    }
+
+    fn headers(head: &ResponseHead) -> Vec<(Option<&[u8]>, &[u8])> {
+        head.headers
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|h| (h.key.as_deref(), &*h.value))
+            .collect()
+    }
+
+    #[test]
+    fn test_bare_lf() {
+        let head = parse(b"HTTP/1.1 200 OK\nContent-Length: 0\n\nbody");
+        assert_eq!(head.status_code, Some(200));
+        assert_eq!(head.reason.as_deref(), Some(&b"OK"[..]));
+        assert_eq!(headers(&head), [(Some(&b"Content-Length"[..]), &b"0"[..])]);
+        assert_eq!(
+            kinds(&head),
+            [
+                (Http1DeviationKind::BareLf, 0),
+                (Http1DeviationKind::BareLf, 16),
+                (Http1DeviationKind::BareLf, 34),
+            ]
+        );
+        assert_eq!(head.len, Some(35));
+    }
+
+    #[test]
+    fn test_obs_fold() {
+        let head = parse(b"HTTP/1.1 200 OK\r\nX-A: one\r\n  two\r\n\tthree \r\n\r\n");
+        assert_eq!(headers(&head), [(Some(&b"X-A"[..]), &b"one two three"[..])]);
+        assert_eq!(
+            kinds(&head),
+            [
+                (Http1DeviationKind::ObsFold, 27),
+                (Http1DeviationKind::ObsFold, 34),
+            ]
+        );
+        assert_eq!(head.len, Some(45));
+
+        // A fold before any header has nothing to continue.
+        let head = parse(b"HTTP/1.1 200 OK\r\n folded\r\n\r\n");
+        assert_eq!(headers(&head), [(None, &b"folded"[..])]);
+        assert_eq!(kinds(&head), [(Http1DeviationKind::ObsFold, 17)]);
+    }
+
+    #[test]
+    fn test_missing_colon() {
+        let head = parse(b"HTTP/1.1 200 OK\r\nnot a header\r\nName : value\r\n\r\n");
+        assert_eq!(
+            headers(&head),
+            [
+                (None, &b"not a header"[..]),
+                (Some(&b"Name"[..]), &b"value"[..]),
+            ]
+        );
+        assert_eq!(
+            kinds(&head),
+            [
+                (Http1DeviationKind::MissingColon, 17),
+                (Http1DeviationKind::SpaceBeforeColon, 31),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_partial_head() {
+        let head = parse(b"\r\nHTTP/1.1 200 OK\r\nX-A");
+        assert_eq!(head.status_code, Some(200));
+        assert!(head.headers.is_none());
+        assert!(head.len.is_none());
+        assert_eq!(kinds(&head), [(Http1DeviationKind::LeadingEmptyLine, 0)]);
+
+        let head = parse(b"HTTP/1.1 200");
+        assert!(head.status_code.is_none());
+    }
+}
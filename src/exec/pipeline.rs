@@ -0,0 +1,70 @@
+//! Pipelined HTTP/1, where every request in a step is written to a shared connection before any
+//! response is read. Responses are matched to requests by their order on the connection.
+
+use anyhow::{anyhow, bail};
+use bytes::Bytes;
+
+use super::runner::Runner;
+use super::Executor;
+use crate::{IterableKey, JobName, JobOutput, ProtocolField};
+
+/// A job whose request was written but whose response hasn't been read.
+pub(super) struct Sent {
+    key: IterableKey,
+    job_name: JobName,
+    runner: Runner,
+}
+
+/// Write a job's request, returning the job to read the response for later along with the
+/// connection for the next request.
+pub(super) async fn send(
+    key: IterableKey,
+    job_name: JobName,
+    runners: Vec<Runner>,
+    transport: Runner,
+) -> anyhow::Result<(Sent, Runner)> {
+    let mut runner = match <[Runner; 1]>::try_from(runners) {
+        Ok([runner @ (Runner::H1(_) | Runner::H1c(_))]) => runner,
+        _ => bail!("pipelining requires run.share to be the protocol directly below h1 or h1c"),
+    };
+    runner.start(Some(transport), 1).await?;
+    let (Runner::H1(http1) | Runner::H1c(http1)) = &mut runner else {
+        unreachable!();
+    };
+    let transport = http1.send_pipelined().await?;
+    Ok((
+        Sent {
+            key,
+            job_name,
+            runner,
+        },
+        transport,
+    ))
+}
+
+/// Read the responses to the sent jobs in the order their requests were written.
+pub(super) async fn receive(
+    sent: Vec<Sent>,
+    mut transport: Runner,
+    shared: ProtocolField,
+) -> anyhow::Result<Vec<(IterableKey, JobOutput)>> {
+    let last = sent.len().saturating_sub(1);
+    let mut buffered = Bytes::new();
+    let mut outputs = Vec::with_capacity(sent.len());
+    for (i, mut job) in sent.into_iter().enumerate() {
+        let (Runner::H1(http1) | Runner::H1c(http1)) = &mut job.runner else {
+            unreachable!();
+        };
+        http1.resume(transport, buffered, i as u64).await;
+        // Bytes after the last response stay in its body, where they show as desync indicators.
+        buffered = if i < last {
+            http1.take_overflow()
+        } else {
+            Bytes::new()
+        };
+        let (output, inner) = Executor::unwind(job.runner, Some(shared), job.job_name).await?;
+        transport = inner.ok_or_else(|| anyhow!("pipelined connection closed"))?;
+        outputs.push((job.key, output));
+    }
+    Ok(outputs)
+}
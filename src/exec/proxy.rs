@@ -0,0 +1,496 @@
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail};
+use base64::Engine;
+use bytes::Bytes;
+use chrono::TimeDelta;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::secret::REDACTED;
+use crate::{
+    HttpHeader, MaybeUtf8, ProxyKind, TcpProxyOutput, TcpProxyPlanOutput,
+    TcpProxyProtocolPlanOutput,
+};
+
+/// How long a proxy has to finish the handshake. TCP steps have no timeout of their own, so
+/// without one a proxy which never replies would hang the step.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The longest CONNECT reply header read before giving up on the proxy.
+const MAX_CONNECT_REPLY: usize = 16 * 1024;
+
+/// Ask a proxy hop to connect to the target, recording the bytes exchanged. The stream is only
+/// read up to the end of the proxy's reply so any following data is left for the next layer.
+pub(super) async fn handshake<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    hop: &TcpProxyPlanOutput,
+    target_host: &str,
+    target_port: u16,
+) -> TcpProxyOutput {
+    let start = Instant::now();
+    let mut sent = Vec::new();
+    let mut received = Vec::new();
+    let exchange = async {
+        match hop.kind {
+            ProxyKind::Http => {
+                http_connect(
+                    stream,
+                    hop,
+                    target_host,
+                    target_port,
+                    &mut sent,
+                    &mut received,
+                )
+                .await
+            }
+            ProxyKind::Socks5 => {
+                socks5_connect(
+                    stream,
+                    hop,
+                    target_host,
+                    target_port,
+                    &mut sent,
+                    &mut received,
+                )
+                .await
+            }
+        }
+    };
+    let result = tokio::time::timeout(HANDSHAKE_TIMEOUT, exchange)
+        .await
+        .unwrap_or_else(|_| {
+            Err(anyhow!(
+                "proxy {}:{} didn't complete the handshake within {HANDSHAKE_TIMEOUT:?}",
+                hop.host,
+                hop.port
+            ))
+        });
+    let (status_code, headers) = match hop.kind {
+        ProxyKind::Http => connect_reply(&received),
+        ProxyKind::Socks5 => (None, Vec::new()),
+    };
+    TcpProxyOutput {
+        kind: hop.kind,
+        host: hop.host.clone(),
+        port: hop.port,
+        target_host: target_host.to_owned(),
+        target_port,
+        sent: MaybeUtf8(Bytes::from(sent).into()),
+        received: MaybeUtf8(Bytes::from(received).into()),
+        status_code,
+        headers,
+        duration: TimeDelta::from_std(start.elapsed()).unwrap().into(),
+        error: result.err().map(|e| format!("{e:#}")),
+    }
+}
+
+const PROXY_V2_SIGNATURE: &[u8; 12] = b"\r\n\r\n\0\r\nQUIT\n";
+
+/// Build a PROXY protocol header claiming the planned source address. The destination defaults to
+/// the connected peer.
+pub(super) fn proxy_protocol_header(
+    plan: &TcpProxyProtocolPlanOutput,
+    peer: SocketAddr,
+) -> anyhow::Result<Vec<u8>> {
+    let source_ip: IpAddr = plan.source_host.parse().map_err(|_| {
+        anyhow!(
+            "tcp.proxy_protocol.source_host {:?} is not an IP address",
+            plan.source_host
+        )
+    })?;
+    let dest_ip = match &plan.dest_host {
+        Some(host) => host
+            .parse()
+            .map_err(|_| anyhow!("tcp.proxy_protocol.dest_host {host:?} is not an IP address"))?,
+        None => peer.ip(),
+    };
+    let dest_port = plan.dest_port.unwrap_or(peer.port());
+    // Both addresses must be in the same family, so upgrade to IPv6 if they're mixed.
+    let (source_ip, dest_ip) = match (source_ip, dest_ip) {
+        (IpAddr::V4(s), IpAddr::V6(d)) => (IpAddr::V6(s.to_ipv6_mapped()), IpAddr::V6(d)),
+        (IpAddr::V6(s), IpAddr::V4(d)) => (IpAddr::V6(s), IpAddr::V6(d.to_ipv6_mapped())),
+        addrs => addrs,
+    };
+
+    if plan.version == 1 {
+        let family = if source_ip.is_ipv4() { "TCP4" } else { "TCP6" };
+        return Ok(format!(
+            "PROXY {family} {source_ip} {dest_ip} {} {dest_port}\r\n",
+            plan.source_port
+        )
+        .into_bytes());
+    }
+
+    let mut header = PROXY_V2_SIGNATURE.to_vec();
+    // Version 2, PROXY command.
+    header.push(0x21);
+    match (source_ip, dest_ip) {
+        (IpAddr::V4(s), IpAddr::V4(d)) => {
+            // AF_INET, STREAM.
+            header.push(0x11);
+            header.extend_from_slice(&12u16.to_be_bytes());
+            header.extend_from_slice(&s.octets());
+            header.extend_from_slice(&d.octets());
+        }
+        (IpAddr::V6(s), IpAddr::V6(d)) => {
+            // AF_INET6, STREAM.
+            header.push(0x21);
+            header.extend_from_slice(&36u16.to_be_bytes());
+            header.extend_from_slice(&s.octets());
+            header.extend_from_slice(&d.octets());
+        }
+        _ => unreachable!("address families were unified above"),
+    }
+    header.extend_from_slice(&plan.source_port.to_be_bytes());
+    header.extend_from_slice(&dest_port.to_be_bytes());
+    Ok(header)
+}
+
+async fn http_connect<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    hop: &TcpProxyPlanOutput,
+    target_host: &str,
+    target_port: u16,
+    sent: &mut Vec<u8>,
+    received: &mut Vec<u8>,
+) -> anyhow::Result<()> {
+    let authority = match target_host.parse::<IpAddr>() {
+        Ok(IpAddr::V6(ip)) => format!("[{ip}]:{target_port}"),
+        _ => format!("{target_host}:{target_port}"),
+    };
+    let mut request = format!("CONNECT {authority} HTTP/1.1\r\nHost: {authority}\r\n");
+    // What's recorded as sent, which has the credentials redacted.
+    let mut recorded = request.clone();
+    if let Some(username) = &hop.username {
+        let password = hop
+            .password
+            .as_ref()
+            .map(|p| p.expose())
+            .unwrap_or_default();
+        let credentials = base64::prelude::BASE64_STANDARD.encode(format!("{username}:{password}"));
+        crate::secret::register(password);
+        crate::secret::register(credentials.clone());
+        request += &format!("Proxy-Authorization: Basic {credentials}\r\n");
+        recorded += &format!("Proxy-Authorization: Basic {REDACTED}\r\n");
+    }
+    request += "\r\n";
+    recorded += "\r\n";
+    sent.extend_from_slice(recorded.as_bytes());
+    stream.write_all(request.as_bytes()).await?;
+    stream.flush().await?;
+
+    // Read a byte at a time so we don't consume anything past the end of the reply header.
+    while !received.ends_with(b"\r\n\r\n") {
+        if received.len() >= MAX_CONNECT_REPLY {
+            bail!(
+                "CONNECT reply from proxy {}:{} is longer than {MAX_CONNECT_REPLY} bytes",
+                hop.host,
+                hop.port
+            );
+        }
+        received.push(stream.read_u8().await?);
+    }
+    let mut headers = [httparse::EMPTY_HEADER; 64];
+    let mut response = httparse::Response::new(&mut headers);
+    response.parse(received)?;
+    match response.code {
+        Some(200..=299) => Ok(()),
+        Some(code) => bail!(
+            "proxy {}:{} refused CONNECT with status {code}",
+            hop.host,
+            hop.port
+        ),
+        None => bail!(
+            "invalid CONNECT response from proxy {}:{}",
+            hop.host,
+            hop.port
+        ),
+    }
+}
+
+/// The status and headers of a CONNECT reply, as far as it was read.
+fn connect_reply(received: &[u8]) -> (Option<u16>, Vec<HttpHeader>) {
+    let mut headers = [httparse::EMPTY_HEADER; 64];
+    let mut response = httparse::Response::new(&mut headers);
+    // A partial reply still has whatever was parsed before it ended.
+    let _ = response.parse(received);
+    let headers = response
+        .headers
+        .iter()
+        .take_while(|h| !h.name.is_empty())
+        .map(|h| HttpHeader {
+            key: Some(MaybeUtf8(h.name.to_owned().into())),
+            value: MaybeUtf8(h.value.to_vec().into()),
+        })
+        .collect();
+    (response.code, headers)
+}
+
+async fn socks5_connect<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    hop: &TcpProxyPlanOutput,
+    target_host: &str,
+    target_port: u16,
+    sent: &mut Vec<u8>,
+    received: &mut Vec<u8>,
+) -> anyhow::Result<()> {
+    // Method selection, offering username/password auth only if credentials were given.
+    let greeting: &[u8] = if hop.username.is_some() {
+        &[5, 2, 0, 2]
+    } else {
+        &[5, 1, 0]
+    };
+    write(stream, sent, greeting).await?;
+    let reply = read(stream, received, 2).await?;
+    match reply[..] {
+        [5, 0] => {}
+        [5, 2] => {
+            let username = hop.username.as_deref().unwrap_or_default().as_bytes();
+            let password = hop
+                .password
+                .as_ref()
+                .map(|p| p.expose())
+                .unwrap_or_default()
+                .as_bytes();
+            crate::secret::register(String::from_utf8_lossy(password));
+            let mut auth = vec![1, u8::try_from(username.len())?];
+            auth.extend_from_slice(username);
+            auth.push(u8::try_from(password.len())?);
+            auth.extend_from_slice(password);
+            stream.write_all(&auth).await?;
+            stream.flush().await?;
+            // Record the credentials without the password.
+            sent.extend_from_slice(&auth[..auth.len() - password.len()]);
+            if !password.is_empty() {
+                sent.extend_from_slice(REDACTED.as_bytes());
+            }
+            if read(stream, received, 2).await?[1] != 0 {
+                bail!(
+                    "socks5 proxy {}:{} rejected credentials",
+                    hop.host,
+                    hop.port
+                );
+            }
+        }
+        [5, 0xff] => bail!(
+            "socks5 proxy {}:{} accepted no auth methods",
+            hop.host,
+            hop.port
+        ),
+        _ => bail!("invalid socks5 method reply from {}:{}", hop.host, hop.port),
+    }
+
+    let mut request = vec![5, 1, 0];
+    match target_host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            request.push(1);
+            request.extend_from_slice(&ip.octets());
+        }
+        Ok(IpAddr::V6(ip)) => {
+            request.push(4);
+            request.extend_from_slice(&ip.octets());
+        }
+        Err(_) => {
+            request.push(3);
+            request.push(u8::try_from(target_host.len())?);
+            request.extend_from_slice(target_host.as_bytes());
+        }
+    }
+    request.extend_from_slice(&target_port.to_be_bytes());
+    write(stream, sent, &request).await?;
+
+    let reply = read(stream, received, 4).await?;
+    if reply[1] != 0 {
+        bail!(
+            "socks5 proxy {}:{} failed to connect with reply code {}",
+            hop.host,
+            hop.port,
+            reply[1]
+        );
+    }
+    // Skip the bound address and port.
+    let addr_len = match reply[3] {
+        1 => 4,
+        4 => 16,
+        3 => usize::from(read(stream, received, 1).await?[0]),
+        atyp => bail!("invalid socks5 address type {atyp}"),
+    };
+    read(stream, received, addr_len + 2).await?;
+    Ok(())
+}
+
+async fn write<S: AsyncWrite + Unpin>(
+    stream: &mut S,
+    sent: &mut Vec<u8>,
+    buf: &[u8],
+) -> std::io::Result<()> {
+    sent.extend_from_slice(buf);
+    stream.write_all(buf).await?;
+    stream.flush().await
+}
+
+async fn read<S: AsyncRead + Unpin>(
+    stream: &mut S,
+    received: &mut Vec<u8>,
+    len: usize,
+) -> std::io::Result<Vec<u8>> {
+    let mut buf = vec![0; len];
+    stream.read_exact(&mut buf).await?;
+    received.extend_from_slice(&buf);
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::duplex;
+
+    use super::*;
+    use crate::Secret;
+
+    fn hop(kind: ProxyKind) -> TcpProxyPlanOutput {
+        TcpProxyPlanOutput {
+            kind,
+            host: "proxy.test".to_owned(),
+            port: 3128,
+            username: Some("user".to_owned()),
+            password: Some(Secret("hunter2".to_owned())),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_http_connect_redacts_credentials() {
+        let (mut client, mut server) = duplex(1024);
+        tokio::spawn(async move {
+            let mut request = Vec::new();
+            while !request.ends_with(b"\r\n\r\n") {
+                request.push(server.read_u8().await.unwrap());
+            }
+            server
+                .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+                .await
+                .unwrap();
+        });
+        let out = handshake(&mut client, &hop(ProxyKind::Http), "example.com", 443).await;
+        assert_eq!(out.error, None);
+        assert_eq!(out.status_code, Some(200));
+        assert_eq!(
+            out.sent.as_str(),
+            Some(
+                "CONNECT example.com:443 HTTP/1.1\r\nHost: example.com:443\r\n\
+                 Proxy-Authorization: Basic [redacted]\r\n\r\n"
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_http_connect_limits_reply() {
+        let (mut client, mut server) = duplex(MAX_CONNECT_REPLY * 2);
+        server
+            .write_all(&vec![b'a'; MAX_CONNECT_REPLY + 1])
+            .await
+            .unwrap();
+        let out = handshake(&mut client, &hop(ProxyKind::Http), "example.com", 443).await;
+        assert!(out.error.unwrap().contains("longer than"));
+        assert_eq!(out.received.len(), MAX_CONNECT_REPLY);
+    }
+
+    #[tokio::test]
+    async fn test_socks5_redacts_password() {
+        let (mut client, mut server) = duplex(1024);
+        tokio::spawn(async move {
+            let mut buf = [0; 4];
+            server.read_exact(&mut buf).await.unwrap();
+            server.write_all(&[5, 2]).await.unwrap();
+            let mut auth = [0; 1 + 1 + 4 + 1 + 7];
+            server.read_exact(&mut auth).await.unwrap();
+            server.write_all(&[1, 0]).await.unwrap();
+            let mut request = [0; 4 + 4 + 2];
+            server.read_exact(&mut request).await.unwrap();
+            server
+                .write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0])
+                .await
+                .unwrap();
+        });
+        let out = handshake(&mut client, &hop(ProxyKind::Socks5), "192.0.2.1", 80).await;
+        assert_eq!(out.error, None);
+        let mut expected = vec![5, 2, 0, 2, 1, 4];
+        expected.extend_from_slice(b"user");
+        expected.push(7);
+        expected.extend_from_slice(b"[redacted]");
+        expected.extend_from_slice(&[5, 1, 0, 1, 192, 0, 2, 1, 0, 80]);
+        assert_eq!(out.sent.as_bytes(), expected);
+    }
+
+    fn proxy_protocol(version: u8, source: &str, dest: Option<&str>) -> TcpProxyProtocolPlanOutput {
+        TcpProxyProtocolPlanOutput {
+            version,
+            source_host: source.to_owned(),
+            source_port: 0xABCD,
+            dest_host: dest.map(str::to_owned),
+            dest_port: None,
+        }
+    }
+
+    #[test]
+    fn test_proxy_protocol_v2_ipv4() {
+        let peer = SocketAddr::from(([198, 51, 100, 7], 443));
+        let header = proxy_protocol_header(&proxy_protocol(2, "192.0.2.1", None), peer).unwrap();
+        let mut expected = PROXY_V2_SIGNATURE.to_vec();
+        expected.extend_from_slice(&[
+            0x21, // version 2, PROXY
+            0x11, // AF_INET, STREAM
+            0x00, 0x0C, // 12 address bytes and no TLVs
+            192, 0, 2, 1, // source
+            198, 51, 100, 7, // destination
+            0xAB, 0xCD, // source port
+            0x01, 0xBB, // destination port
+        ]);
+        assert_eq!(header, expected);
+        assert_eq!(
+            usize::from(u16::from_be_bytes([header[14], header[15]])),
+            header.len() - 16
+        );
+    }
+
+    #[test]
+    fn test_proxy_protocol_v2_ipv6() {
+        let peer = SocketAddr::from(([198, 51, 100, 7], 443));
+        let header =
+            proxy_protocol_header(&proxy_protocol(2, "2001:db8::1", Some("2001:db8::2")), peer)
+                .unwrap();
+        let mut expected = PROXY_V2_SIGNATURE.to_vec();
+        expected.extend_from_slice(&[0x21, 0x21, 0x00, 0x24]);
+        expected.extend_from_slice(&[0x20, 0x01, 0x0D, 0xB8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+        expected.extend_from_slice(&[0x20, 0x01, 0x0D, 0xB8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2]);
+        expected.extend_from_slice(&[0xAB, 0xCD, 0x01, 0xBB]);
+        assert_eq!(header, expected);
+        assert_eq!(
+            usize::from(u16::from_be_bytes([header[14], header[15]])),
+            header.len() - 16
+        );
+
+        // An IPv4 source is mapped into IPv6 when the destination is IPv6.
+        let header =
+            proxy_protocol_header(&proxy_protocol(2, "192.0.2.1", Some("2001:db8::2")), peer)
+                .unwrap();
+        assert_eq!(header[13], 0x21);
+        assert_eq!(
+            header[16..32],
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xFF, 0xFF, 192, 0, 2, 1]
+        );
+    }
+
+    #[test]
+    fn test_proxy_protocol_v1() {
+        let peer = SocketAddr::from(([198, 51, 100, 7], 443));
+        let header = proxy_protocol_header(&proxy_protocol(1, "192.0.2.1", None), peer).unwrap();
+        assert_eq!(header, b"PROXY TCP4 192.0.2.1 198.51.100.7 43981 443\r\n");
+        let header = proxy_protocol_header(&proxy_protocol(1, "2001:db8::1", None), peer).unwrap();
+        assert_eq!(
+            header,
+            b"PROXY TCP6 2001:db8::1 ::ffff:198.51.100.7 43981 443\r\n"
+        );
+        assert!(proxy_protocol_header(&proxy_protocol(1, "example.com", None), peer).is_err());
+    }
+}
@@ -0,0 +1,265 @@
+use std::{
+    io::{self, IoSliceMut},
+    mem,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    pin::Pin,
+    sync::Arc,
+    task::{self, Poll},
+    time::Instant,
+};
+
+use anyhow::{anyhow, bail};
+use bytes::Bytes;
+use chrono::TimeDelta;
+use quinn::{
+    crypto::rustls::{HandshakeData, QuicClientConfig},
+    rustls,
+    udp::{RecvMeta, Transmit},
+    AsyncUdpSocket, ConnectionError, Endpoint, EndpointConfig, TokioRuntime, UdpPoller, VarInt,
+};
+use tokio::io::ReadBuf;
+
+use crate::{
+    MaybeUtf8, ProtocolDiscriminants, ProtocolName, QuicError, QuicOutput, QuicPlanOutput,
+};
+
+use super::{dns, socket::BoxDatagram, Context};
+
+/// How long to wait for the server to acknowledge the connection closing before giving up.
+const CLOSE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(1);
+
+#[derive(Debug)]
+pub(super) struct QuicRunner {
+    ctx: Arc<Context>,
+    out: QuicOutput,
+    state: State,
+    start: Option<Instant>,
+}
+
+#[derive(Debug)]
+enum State {
+    Pending,
+    Open {
+        endpoint: Endpoint,
+        connection: quinn::Connection,
+    },
+    Completed,
+}
+
+impl QuicRunner {
+    pub(super) fn new(ctx: Arc<Context>, plan: QuicPlanOutput) -> Self {
+        Self {
+            out: QuicOutput {
+                name: ProtocolName::with_job(ctx.job_name.clone(), ProtocolDiscriminants::Quic),
+                plan,
+                local_addr: None,
+                remote_addr: None,
+                alpn: None,
+                rtt: None,
+                sent_packets: 0,
+                lost_packets: 0,
+                errors: Vec::new(),
+                duration: TimeDelta::zero().into(),
+                handshake_duration: None,
+            },
+            ctx,
+            state: State::Pending,
+            start: None,
+        }
+    }
+
+    pub(super) fn size_hint(&mut self, _hint: Option<usize>) -> Option<usize> {
+        // QUIC carries its own framing and sits directly on UDP, so there's nothing below us to
+        // pass a hint to.
+        None
+    }
+
+    pub(super) fn executor_size_hint(&self) -> Option<usize> {
+        None
+    }
+
+    /// The established connection, for layering application streams on top of.
+    pub(super) fn connection(&self) -> Option<quinn::Connection> {
+        match &self.state {
+            State::Open { connection, .. } => Some(connection.clone()),
+            _ => None,
+        }
+    }
+
+    pub async fn start(&mut self) -> anyhow::Result<()> {
+        self.start = Some(Instant::now());
+        if let Err(e) = self.connect().await {
+            self.out.errors.push(QuicError {
+                kind: "connect".to_owned(),
+                message: format!("{e:#}"),
+            });
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    async fn connect(&mut self) -> anyhow::Result<()> {
+        if let Some(version) = &self.out.plan.tls_version {
+            if version.raw != 0x0304 {
+                bail!("quic only supports tls1.3");
+            }
+        }
+
+        let host = self.out.plan.host.as_str();
+        let remote = dns::lookup(&self.ctx, host, self.out.plan.port)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("no addresses found for quic.host '{host}'"))?;
+        let local = self
+            .ctx
+            .egress
+            .as_ref()
+            .and_then(|egress| egress.local_addr(remote))
+            .unwrap_or_else(|| match remote {
+                SocketAddr::V4(_) => SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0),
+                SocketAddr::V6(_) => SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), 0),
+            });
+
+        // quinn builds on a newer rustls than the tls step, so configure it separately.
+        let mut tls_config = rustls::ClientConfig::builder_with_provider(Arc::new(
+            rustls::crypto::ring::default_provider(),
+        ))
+        .with_protocol_versions(&[&rustls::version::TLS13])?
+        .with_root_certificates(rustls::RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.into(),
+        })
+        .with_no_client_auth();
+        tls_config.alpn_protocols = self
+            .out
+            .plan
+            .alpn
+            .iter()
+            .map(|alpn| alpn.to_vec())
+            .collect();
+
+        let socket = self.ctx.sockets.connect_udp(local, remote).await?;
+        let mut endpoint = Endpoint::new_with_abstract_socket(
+            EndpointConfig::default(),
+            None,
+            Arc::new(ProviderUdpSocket { socket, remote }),
+            Arc::new(TokioRuntime),
+        )?;
+        endpoint.set_default_client_config(quinn::ClientConfig::new(Arc::new(
+            QuicClientConfig::try_from(tls_config)?,
+        )));
+        self.out.local_addr = endpoint.local_addr().ok().map(|addr| addr.to_string());
+        self.out.remote_addr = Some(remote.to_string());
+
+        let handshake_start = Instant::now();
+        let connection = endpoint
+            .connect(remote, host.trim_start_matches('[').trim_end_matches(']'))?
+            .await?;
+        self.out.handshake_duration = Some(
+            TimeDelta::from_std(handshake_start.elapsed())
+                .unwrap_or(TimeDelta::MAX)
+                .into(),
+        );
+        self.out.alpn = connection
+            .handshake_data()
+            .and_then(|data| data.downcast::<HandshakeData>().ok())
+            .and_then(|data| data.protocol)
+            .map(|alpn| MaybeUtf8(Bytes::from(alpn).into()));
+
+        self.state = State::Open {
+            endpoint,
+            connection,
+        };
+        Ok(())
+    }
+
+    pub async fn finish(mut self) -> QuicOutput {
+        if let State::Open {
+            endpoint,
+            connection,
+        } = mem::replace(&mut self.state, State::Completed)
+        {
+            // A server closing the connection at the application layer is a normal shutdown, but
+            // anything else means the connection died under the step.
+            match connection.close_reason() {
+                None | Some(ConnectionError::ApplicationClosed(_)) => {}
+                Some(e) => self.out.errors.push(QuicError {
+                    kind: "connection".to_owned(),
+                    message: e.to_string(),
+                }),
+            }
+            connection.close(VarInt::from_u32(0), b"");
+            // Don't hold the job open for a server that never acknowledges the close.
+            let _ = tokio::time::timeout(CLOSE_TIMEOUT, endpoint.wait_idle()).await;
+
+            let stats = connection.stats();
+            self.out.rtt = Some(
+                TimeDelta::from_std(stats.path.rtt)
+                    .unwrap_or(TimeDelta::MAX)
+                    .into(),
+            );
+            self.out.sent_packets = stats.path.sent_packets;
+            self.out.lost_packets = stats.path.lost_packets;
+        }
+        if let Some(start) = self.start {
+            self.out.duration = TimeDelta::from_std(start.elapsed())
+                .unwrap_or(TimeDelta::MAX)
+                .into();
+        }
+        self.out
+    }
+}
+
+/// Lets quinn send over a datagram socket from the socket provider. The socket is connected, so
+/// every datagram goes to and comes from the step's remote address.
+#[derive(Debug)]
+struct ProviderUdpSocket {
+    socket: BoxDatagram,
+    remote: SocketAddr,
+}
+
+impl AsyncUdpSocket for ProviderUdpSocket {
+    fn create_io_poller(self: Arc<Self>) -> Pin<Box<dyn UdpPoller>> {
+        Box::pin(ProviderUdpPoller(self))
+    }
+
+    fn try_send(&self, transmit: &Transmit) -> io::Result<()> {
+        // quinn calls poll_writable to wait for readiness when this would block.
+        let mut cx = task::Context::from_waker(futures::task::noop_waker_ref());
+        match self.socket.poll_send(&mut cx, transmit.contents) {
+            Poll::Ready(result) => result.map(|_| ()),
+            Poll::Pending => Err(io::ErrorKind::WouldBlock.into()),
+        }
+    }
+
+    fn poll_recv(
+        &self,
+        cx: &mut task::Context,
+        bufs: &mut [IoSliceMut<'_>],
+        meta: &mut [RecvMeta],
+    ) -> Poll<io::Result<usize>> {
+        let mut buf = ReadBuf::new(&mut bufs[0][..]);
+        task::ready!(self.socket.poll_recv(cx, &mut buf))?;
+        let len = buf.filled().len();
+        meta[0] = RecvMeta {
+            addr: self.remote,
+            len,
+            stride: len,
+            ..RecvMeta::default()
+        };
+        Poll::Ready(Ok(1))
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+}
+
+#[derive(Debug)]
+struct ProviderUdpPoller(Arc<ProviderUdpSocket>);
+
+impl UdpPoller for ProviderUdpPoller {
+    fn poll_writable(self: Pin<&mut Self>, cx: &mut task::Context) -> Poll<io::Result<()>> {
+        self.0.socket.poll_send_ready(cx)
+    }
+}
@@ -0,0 +1,440 @@
+use std::sync::Arc;
+
+use itertools::Itertools;
+use url::Url;
+
+use crate::{
+    AddContentLength, HttpHeader, HttpPlanOutput, IterableKey, JobOutput, MaybeUtf8,
+    RangeCaseOutput, RangeOutput, RangePlanOutput, StepPlanOutput,
+};
+
+use super::runner::Runner;
+use super::{Context, Executor};
+
+/// What a correct server may do with a Range header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Expect {
+    /// A 206 with exactly the requested bytes, or the full resource with a 200.
+    Satisfiable,
+    /// Ranges that all overlap the first one, which should be coalesced or refused.
+    Overlapping,
+    /// More ranges than any client needs, which should be coalesced or refused.
+    ManyParts,
+    /// A syntactically invalid header, which must be ignored.
+    Invalid,
+    /// Ranges past the end, which should get a 416.
+    Unsatisfiable,
+}
+
+/// Fetch the full resource, send each Range case, then fetch it again to check whether a cache
+/// stored a partial response.
+pub(super) async fn range(
+    ctx: &Context,
+    plan: RangePlanOutput,
+) -> (RangeOutput, Vec<(IterableKey, Arc<JobOutput>)>) {
+    let mut out = RangeOutput {
+        status_code: None,
+        length: 0,
+        accept_ranges: None,
+        cases: Vec::new(),
+        cache_consistent: None,
+        vulnerable: false,
+        error: None,
+        plan,
+    };
+    let mut jobs = Vec::new();
+    let full = match request(ctx, &out.plan.url, &[], &mut jobs).await {
+        Ok(job) => job,
+        Err(e) => {
+            out.error = Some(format!("{e:#}"));
+            return (out, jobs);
+        }
+    };
+    let (status_code, body) = full.response_summary();
+    out.status_code = status_code;
+    out.length = body.len().try_into().unwrap_or(u64::MAX);
+    out.accept_ranges = header(&full, "accept-ranges");
+    if status_code != Some(200) || body.is_empty() {
+        out.error = Some("the full resource must be a non-empty 200 response".to_owned());
+        return (out, jobs);
+    }
+    let body = body.to_vec();
+
+    for (name, range, expect) in cases(out.length, out.plan.max_parts) {
+        let mut case = RangeCaseOutput {
+            name: name.to_owned(),
+            range,
+            status_code: None,
+            content_range: None,
+            parts: 0,
+            issues: Vec::new(),
+            error: None,
+        };
+        match request(
+            ctx,
+            &out.plan.url,
+            &[("Range", case.range.as_str())],
+            &mut jobs,
+        )
+        .await
+        {
+            Ok(job) => check(&mut case, &job, &body, expect, out.plan.max_parts),
+            Err(e) => case.error = Some(format!("{e:#}")),
+        }
+        out.cases.push(case);
+    }
+
+    if let Ok(job) = request(ctx, &out.plan.url, &[], &mut jobs).await {
+        let (status_code, again) = job.response_summary();
+        out.cache_consistent = Some(status_code == out.status_code && again == body);
+    }
+    out.vulnerable =
+        out.cache_consistent == Some(false) || out.cases.iter().any(|case| !case.issues.is_empty());
+    (out, jobs)
+}
+
+/// The name, Range header, and expected handling of each case for a resource of `len` bytes.
+fn cases(len: u64, max_parts: u64) -> Vec<(&'static str, String, Expect)> {
+    let last = len - 1;
+    let end = last.min(9);
+    let many = (0..max_parts)
+        .map(|i| {
+            let byte = (i * 2) % len;
+            format!("{byte}-{byte}")
+        })
+        .join(",");
+    vec![
+        ("single", format!("bytes=0-{end}"), Expect::Satisfiable),
+        ("suffix", format!("bytes=-{}", end + 1), Expect::Satisfiable),
+        (
+            "open_ended",
+            format!("bytes={}-", last - end),
+            Expect::Satisfiable,
+        ),
+        (
+            "multiple",
+            format!("bytes=0-0,{last}-{last}"),
+            Expect::Satisfiable,
+        ),
+        (
+            "end_past_length",
+            format!("bytes=0-{}", u64::MAX),
+            Expect::Satisfiable,
+        ),
+        (
+            "overlapping",
+            format!("bytes=0-{end},0-{end},0-"),
+            Expect::Overlapping,
+        ),
+        ("many_parts", format!("bytes={many}"), Expect::ManyParts),
+        (
+            "reversed",
+            format!("bytes={}-0", end.max(1)),
+            Expect::Invalid,
+        ),
+        ("malformed", "bytes=0-1-2".to_owned(), Expect::Invalid),
+        ("wrong_unit", format!("lines=0-{end}"), Expect::Invalid),
+        (
+            "unsatisfiable",
+            format!("bytes={}-{}", len + 100, len + 200),
+            Expect::Unsatisfiable,
+        ),
+    ]
+}
+
+fn check(case: &mut RangeCaseOutput, job: &JobOutput, full: &[u8], expect: Expect, max: u64) {
+    let (status_code, body) = job.response_summary();
+    case.status_code = status_code;
+    case.content_range = header(job, "content-range");
+    match status_code {
+        Some(206) => {}
+        Some(200) => {
+            case.parts = 1;
+            // Ignoring the header is always allowed, even for unsatisfiable ranges.
+            if body != full {
+                case.issues
+                    .push("200 response differs from the full resource".to_owned());
+            }
+            return;
+        }
+        Some(416) => {
+            if expect == Expect::Satisfiable {
+                case.issues
+                    .push("satisfiable range was refused with 416".to_owned());
+            }
+            return;
+        }
+        Some(code) if code >= 500 => {
+            case.issues.push(format!("server error {code}"));
+            return;
+        }
+        Some(code) => {
+            case.issues.push(format!("unexpected status {code}"));
+            return;
+        }
+        None => {
+            case.error = Some("no response".to_owned());
+            return;
+        }
+    }
+
+    match expect {
+        Expect::Invalid => case
+            .issues
+            .push("invalid Range header was honored".to_owned()),
+        Expect::Unsatisfiable => case
+            .issues
+            .push("unsatisfiable range got a partial response".to_owned()),
+        _ => {}
+    }
+    let content_type = header(job, "content-type").unwrap_or_default();
+    let parts = if content_type
+        .to_ascii_lowercase()
+        .starts_with("multipart/byteranges")
+    {
+        let Some(boundary) = boundary(&content_type) else {
+            case.issues
+                .push("multipart response without a boundary".to_owned());
+            return;
+        };
+        multipart(body, &boundary)
+    } else {
+        vec![(case.content_range.clone(), body)]
+    };
+    case.parts = parts.len().try_into().unwrap_or(u64::MAX);
+    let mut served = Vec::new();
+    for (content_range, part) in parts {
+        let Some(content_range) = content_range else {
+            case.issues
+                .push("partial content without Content-Range".to_owned());
+            continue;
+        };
+        match parse_content_range(&content_range) {
+            Some((start, end, total)) => {
+                if total.is_some_and(|total| usize::try_from(total).ok() != Some(full.len())) {
+                    case.issues
+                        .push(format!("{content_range} has the wrong total length"));
+                }
+                let expected = usize::try_from(start)
+                    .ok()
+                    .zip(usize::try_from(end).ok())
+                    .and_then(|(start, end)| full.get(start..=end));
+                if expected != Some(part) {
+                    case.issues.push(format!(
+                        "bytes for {content_range} don't match the resource"
+                    ));
+                }
+                served.push((start, end));
+            }
+            None => case
+                .issues
+                .push(format!("unparsable Content-Range {content_range:?}")),
+        }
+    }
+    if expect == Expect::Overlapping
+        && served
+            .iter()
+            .tuple_combinations()
+            .any(|(a, b)| a.0 <= b.1 && b.0 <= a.1)
+    {
+        case.issues
+            .push("overlapping ranges were served without coalescing".to_owned());
+    }
+    if expect == Expect::ManyParts && case.parts >= max.min(50) {
+        case.issues.push(format!(
+            "served {} parts, which amplifies small requests into large responses",
+            case.parts
+        ));
+    }
+}
+
+/// Parse `bytes START-END/TOTAL`, where TOTAL may be `*`.
+fn parse_content_range(value: &str) -> Option<(u64, u64, Option<u64>)> {
+    let (range, total) = value.trim().strip_prefix("bytes ")?.split_once('/')?;
+    let (start, end) = range.split_once('-')?;
+    let total = match total {
+        "*" => None,
+        total => Some(total.parse().ok()?),
+    };
+    Some((start.parse().ok()?, end.parse().ok()?, total))
+}
+
+fn boundary(content_type: &str) -> Option<String> {
+    content_type.split(';').find_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        key.trim()
+            .eq_ignore_ascii_case("boundary")
+            .then(|| value.trim().trim_matches('"').to_owned())
+    })
+}
+
+/// Split a multipart/byteranges body into each part's Content-Range and bytes.
+fn multipart<'a>(body: &'a [u8], boundary: &str) -> Vec<(Option<String>, &'a [u8])> {
+    let delimiter = format!("--{boundary}");
+    let delimiter = delimiter.as_bytes();
+    let mut parts = Vec::new();
+    let mut rest = body;
+    while let Some(start) = find(rest, delimiter) {
+        rest = &rest[start + delimiter.len()..];
+        if rest.starts_with(b"--") {
+            break;
+        }
+        let Some(headers_end) = find(rest, b"\r\n\r\n") else {
+            break;
+        };
+        let headers = String::from_utf8_lossy(&rest[..headers_end]);
+        let content_range = headers.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.trim()
+                .eq_ignore_ascii_case("content-range")
+                .then(|| value.trim().to_owned())
+        });
+        rest = &rest[headers_end + 4..];
+        let end = find(rest, delimiter).unwrap_or(rest.len());
+        let part = rest[..end].strip_suffix(b"\r\n").unwrap_or(&rest[..end]);
+        parts.push((content_range, part));
+        rest = &rest[end..];
+    }
+    parts
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+pub(super) fn header(job: &JobOutput, name: &str) -> Option<String> {
+    job.http
+        .as_ref()
+        .and_then(|h| h.response.as_ref())
+        .and_then(|r| r.headers.as_ref())
+        .and_then(|headers| {
+            headers.iter().find(|h| {
+                h.key
+                    .as_ref()
+                    .is_some_and(|k| k.eq_ignore_ascii_case(name.as_bytes()))
+            })
+        })
+        .map(|h| String::from_utf8_lossy(&h.value).into_owned())
+}
+
+/// GET the URL with extra headers, recording the job.
+pub(super) async fn request(
+    ctx: &Context,
+    url: &Url,
+    extra: &[(&str, &str)],
+    jobs: &mut Vec<(IterableKey, Arc<JobOutput>)>,
+) -> anyhow::Result<Arc<JobOutput>> {
+    let key = IterableKey::Uint(jobs.len().try_into().unwrap_or(u64::MAX));
+    let mut job_name = ctx.job_name.clone();
+    job_name.job = key.clone();
+    let ctx = Arc::new(ctx.for_job(job_name.clone()));
+    let mut headers = vec![HttpHeader {
+        key: Some(MaybeUtf8("Host".into())),
+        value: MaybeUtf8(super::crawl::authority(url)?.into()),
+    }];
+    for (key, value) in extra {
+        headers.push(HttpHeader {
+            key: Some(MaybeUtf8(key.to_string().into())),
+            value: MaybeUtf8(value.to_string().into()),
+        });
+    }
+    let mut runner = Runner::new(
+        ctx,
+        StepPlanOutput::Http(HttpPlanOutput {
+            method: Some(MaybeUtf8("GET".into())),
+            add_content_length: AddContentLength::Auto,
+            headers,
+            body: MaybeUtf8::default(),
+            url: url.clone(),
+            http3: false,
+            similarity: Vec::new(),
+        }),
+        true,
+    )?;
+    let hint = runner.executor_size_hint();
+    runner.size_hint(hint);
+    let runner = Executor::start_runners(None, vec![runner], 1)
+        .await?
+        .expect("any stack should have at least one protocol");
+    let job = Arc::new(Executor::iteration(runner, None, job_name).await?.0);
+    jobs.push((key, job.clone()));
+    Ok(job)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range_for<'a>(cases: &'a [(&str, String, Expect)], name: &str) -> &'a str {
+        &cases.iter().find(|(n, _, _)| *n == name).unwrap().1
+    }
+
+    #[test]
+    fn test_cases() {
+        let large = cases(100, 4);
+        assert_eq!(range_for(&large, "single"), "bytes=0-9");
+        assert_eq!(range_for(&large, "suffix"), "bytes=-10");
+        assert_eq!(range_for(&large, "open_ended"), "bytes=90-");
+        assert_eq!(range_for(&large, "multiple"), "bytes=0-0,99-99");
+        assert_eq!(range_for(&large, "overlapping"), "bytes=0-9,0-9,0-");
+        assert_eq!(range_for(&large, "many_parts"), "bytes=0-0,2-2,4-4,6-6");
+        assert_eq!(range_for(&large, "reversed"), "bytes=9-0");
+        assert_eq!(range_for(&large, "unsatisfiable"), "bytes=200-300");
+
+        // Ranges stay within a one byte resource, except the ones meant to be invalid.
+        let small = cases(1, 3);
+        assert_eq!(range_for(&small, "single"), "bytes=0-0");
+        assert_eq!(range_for(&small, "suffix"), "bytes=-1");
+        assert_eq!(range_for(&small, "open_ended"), "bytes=0-");
+        assert_eq!(range_for(&small, "multiple"), "bytes=0-0,0-0");
+        assert_eq!(range_for(&small, "many_parts"), "bytes=0-0,0-0,0-0");
+        assert_eq!(range_for(&small, "reversed"), "bytes=1-0");
+    }
+
+    #[test]
+    fn test_parse_content_range() {
+        assert_eq!(
+            parse_content_range("bytes 0-9/100"),
+            Some((0, 9, Some(100)))
+        );
+        assert_eq!(parse_content_range(" bytes 90-99/* "), Some((90, 99, None)));
+        assert_eq!(parse_content_range("bytes */100"), None);
+        assert_eq!(parse_content_range("bytes 0-9"), None);
+        assert_eq!(parse_content_range("bytes=0-9/100"), None);
+        assert_eq!(parse_content_range("bytes -9/100"), None);
+        assert_eq!(parse_content_range("bytes 0-9/x"), None);
+    }
+
+    #[test]
+    fn test_boundary() {
+        assert_eq!(
+            boundary(r#"multipart/byteranges; boundary="3d6b6a416f9b5""#).as_deref(),
+            Some("3d6b6a416f9b5")
+        );
+        assert_eq!(
+            boundary("multipart/byteranges; charset=utf-8; BOUNDARY=abc").as_deref(),
+            Some("abc")
+        );
+        assert_eq!(boundary("multipart/byteranges"), None);
+    }
+
+    #[test]
+    fn test_multipart() {
+        let body = b"preamble\r\n\
+            --abc\r\nContent-Type: text/plain\r\nContent-Range: bytes 0-1/10\r\n\r\nab\r\n\
+            --abc\r\ncontent-range:bytes 8-9/10\r\n\r\n\r\nj\r\n\
+            --abc\r\nContent-Type: text/plain\r\n\r\nz\r\n\
+            --abc--\r\nepilogue";
+        assert_eq!(
+            multipart(body, "abc"),
+            [
+                (Some("bytes 0-1/10".to_owned()), b"ab".as_slice()),
+                // Only the CRLF before the next delimiter is stripped from a part.
+                (Some("bytes 8-9/10".to_owned()), b"\r\nj".as_slice()),
+                (None, b"z".as_slice()),
+            ]
+        );
+        // A body cut off before a part's headers end stops at the complete parts.
+        assert!(multipart(b"--abc\r\nContent-Range: bytes 0-0/1", "abc").is_empty());
+    }
+}
@@ -16,11 +16,11 @@ use tokio::task::JoinHandle;
 use tracing::{debug, debug_span, Instrument};
 
 use crate::{
-    Direction, Http2FrameOutput, Http2FrameType, MaybeUtf8, PduName, ProtocolDiscriminants,
-    ProtocolName, RawHttp2Error, RawHttp2Output, RawHttp2PlanOutput,
+    Direction, HpackOutput, Http2FrameOutput, Http2FrameType, MaybeUtf8, PduName,
+    ProtocolDiscriminants, ProtocolName, RawHttp2Error, RawHttp2Output, RawHttp2PlanOutput,
 };
 
-use super::extract;
+use super::{extract, hpack};
 use super::{runner::Runner, Context};
 
 #[derive(Debug)]
@@ -74,6 +74,8 @@ impl RawHttp2Runner {
                 duration: TimeDelta::zero().into(),
                 received: Vec::new(),
                 sent: plan.frames.clone(),
+                sent_hpack: HpackOutput::default(),
+                received_hpack: HpackOutput::default(),
                 plan,
             },
             ctx,
@@ -105,12 +107,14 @@ impl RawHttp2Runner {
         let preface = mem::take(&mut self.send_preface);
         let counter = Arc::new(AtomicU64::new(0));
         let proto = self.out.name.clone();
+        let start = self.start_time.unwrap_or_else(Instant::now);
         let (send_result, (received, recv_err)) = join!(
             async {
                 send.write_all(&preface).await?;
                 for frame in &mut frames {
                     frame.write(&mut send).await?;
                     frame.name.pdu = counter.fetch_add(1, Ordering::Relaxed);
+                    frame.time = TimeDelta::from_std(start.elapsed()).ok().map(Into::into);
                 }
                 send.shutdown().await
             },
@@ -120,6 +124,7 @@ impl RawHttp2Runner {
                     Direction::Recv,
                     proto,
                     counter.clone(),
+                    start,
                 );
                 let mut buf = [0; 2048];
                 loop {
@@ -156,7 +161,8 @@ impl RawHttp2Runner {
     }
 
     pub(super) async fn start(&mut self, transport: Runner, streams: usize) -> anyhow::Result<()> {
-        self.start_time = Some(Instant::now());
+        let start = Instant::now();
+        self.start_time = Some(start);
         let state = mem::replace(&mut self.state, State::Invalid);
         let State::Pending { executor } = state else {
             bail!("state {state:?} not valid for open");
@@ -165,8 +171,11 @@ impl RawHttp2Runner {
         self.state = if executor {
             State::Executing { transport }
         } else {
-            let (extractor, transport) =
-                extract::new(FrameParserStream::new(transport, self.out.name.clone()));
+            let (extractor, transport) = extract::new(FrameParserStream::new(
+                transport,
+                self.out.name.clone(),
+                start,
+            ));
 
             let (stream, connection) = handshake(transport).await.inspect_err(|e| {
                 self.out.errors.push(crate::RawHttp2Error {
@@ -247,6 +256,11 @@ impl RawHttp2Runner {
             }
             State::Invalid => panic!(),
         };
+        // Each side's encoder is limited by the header table size the other side advertised.
+        self.out.sent_hpack =
+            hpack::inspect(&self.out.sent, hpack::header_table_size(&self.out.received));
+        self.out.received_hpack =
+            hpack::inspect(&self.out.received, hpack::header_table_size(&self.out.sent));
     }
 }
 
@@ -260,6 +274,8 @@ struct FrameParser {
     direction: Direction,
     proto: ProtocolName,
     counter: Arc<AtomicU64>,
+    /// When the connection started, for timing each frame.
+    start: Instant,
 }
 
 impl FrameParser {
@@ -268,6 +284,7 @@ impl FrameParser {
         direction: Direction,
         proto: ProtocolName,
         counter: Arc<AtomicU64>,
+        start: Instant,
     ) -> Self {
         Self {
             buf: BytesMut::new(),
@@ -276,6 +293,7 @@ impl FrameParser {
             direction,
             proto,
             counter,
+            start,
         }
     }
 
@@ -337,7 +355,7 @@ impl FrameParser {
                         break;
                     }
                     buf = &buf[to_copy..];
-                    let out = Arc::new(Http2FrameOutput::new(
+                    let mut out = Http2FrameOutput::new(
                         PduName::with_protocol(
                             self.proto.clone(),
                             self.counter.fetch_add(1, Ordering::Relaxed),
@@ -348,7 +366,11 @@ impl FrameParser {
                         *stream_id,
                         self.buf.split().freeze(),
                         self.direction,
-                    ));
+                    );
+                    out.time = TimeDelta::from_std(self.start.elapsed())
+                        .ok()
+                        .map(Into::into);
+                    let out = Arc::new(out);
                     debug!(out = ?out, "push finished frame");
                     self.out.push(out);
                     self.buf.clear();
@@ -368,7 +390,7 @@ struct FrameParserStream {
 }
 
 impl FrameParserStream {
-    fn new(transport: Runner, proto: ProtocolName) -> Self {
+    fn new(transport: Runner, proto: ProtocolName, start: Instant) -> Self {
         let counter = Arc::new(AtomicU64::new(0));
         Self {
             transport,
@@ -377,12 +399,14 @@ impl FrameParserStream {
                 Direction::Send,
                 proto.clone(),
                 counter.clone(),
+                start,
             ),
             read: FrameParser::new(
                 FrameParserState::FrameHeader,
                 Direction::Recv,
                 proto,
                 counter,
+                start,
             ),
         }
     }
@@ -1,39 +1,45 @@
 use std::mem;
 use std::net::SocketAddr;
-use std::ops::Deref;
-use std::sync::atomic::{AtomicU64, Ordering};
 use std::{io, net::IpAddr, pin::Pin, sync::Arc, time::Instant};
 
 use anyhow::{anyhow, bail};
-use bytes::Bytes;
-use cel_interpreter::Duration;
 use chrono::TimeDelta;
 use futures::Future;
-use itertools::Itertools;
-use pnet::packet::tcp::{self, MutableTcpPacket, TcpOption};
-use pnet::{
-    packet::{ip::IpNextHeaderProtocols, tcp::TcpPacket},
-    transport::{self, TransportChannelType},
-};
-use pnet::{
-    packet::{tcp::TcpOptionNumbers, Packet},
-    transport::TransportReceiver,
-};
 use tokio::join;
-use tokio::net::TcpSocket;
-use tokio::sync::oneshot::error::TryRecvError;
-use tokio::{
-    net,
-    sync::{mpsc, oneshot},
-    task::JoinHandle,
-};
-use tracing::{debug, info};
+use tokio::{sync::oneshot, task::JoinHandle};
 
 use crate::{
-    Direction, PduName, ProtocolDiscriminants, ProtocolName, RawTcpError, RawTcpOutput,
-    RawTcpPlanOutput, TcpSegmentOptionOutput, TcpSegmentOutput,
+    ProtocolDiscriminants, ProtocolName, RawTcpError, RawTcpOutput, RawTcpPlanOutput,
+    TcpSegmentOptionOutput, TcpSegmentOutput,
 };
 
+#[cfg(feature = "native")]
+use crate::{Direction, PduName};
+#[cfg(feature = "native")]
+use bytes::Bytes;
+#[cfg(feature = "native")]
+use cel_interpreter::Duration;
+#[cfg(feature = "native")]
+use itertools::Itertools;
+#[cfg(feature = "native")]
+use pnet::packet::tcp::{self, MutableTcpPacket, TcpOption, TcpOptionNumbers, TcpPacket};
+#[cfg(feature = "native")]
+use pnet::packet::{ip::IpNextHeaderProtocols, Packet};
+#[cfg(feature = "native")]
+use pnet::transport::{self, TransportChannelType, TransportReceiver};
+#[cfg(feature = "native")]
+use std::ops::Deref;
+#[cfg(feature = "native")]
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "native")]
+use tokio::net::TcpSocket;
+#[cfg(feature = "native")]
+use tokio::sync::mpsc;
+#[cfg(feature = "native")]
+use tokio::sync::oneshot::error::TryRecvError;
+#[cfg(feature = "native")]
+use tracing::{debug, info};
+
 use super::Context;
 
 #[derive(Debug)]
@@ -49,8 +55,10 @@ pub(super) struct RawTcpRunner {
 type OwnedBoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
 
 #[derive(Debug)]
+#[cfg_attr(not(feature = "native"), allow(dead_code))]
 enum State {
     Pending,
+    #[cfg(feature = "native")]
     Open(OpenState),
     Passive {
         writes: JoinHandle<(Vec<Arc<TcpSegmentOutput>>, Option<io::Error>)>,
@@ -67,10 +75,15 @@ enum State {
         reads: JoinHandle<(Vec<Arc<TcpSegmentOutput>>, Option<io::Error>)>,
         writes: JoinHandle<(Vec<Arc<TcpSegmentOutput>>, Option<io::Error>)>,
     },
+    Uncaptured {
+        remote_addr: SocketAddr,
+        local_addr: SocketAddr,
+    },
     CompletedEmpty,
     Invalid,
 }
 
+#[cfg(feature = "native")]
 #[derive(Debug)]
 struct OpenState {
     send_write: mpsc::UnboundedSender<Option<TcpPacket<'static>>>,
@@ -112,71 +125,119 @@ impl RawTcpRunner {
             bail!("attempt to start TcpRunner from unexpected state: {state:?}");
         };
 
-        // DNS lookup for remote address.
-        let Some(remote_addr) = net::lookup_host(format!(
-            "{}:{}",
-            self.out.plan.dest_host, self.out.plan.dest_port,
-        ))
-        .await
-        .map_err(|e| {
-            anyhow!(
-                "lookup host '{}:{}': {e}",
-                self.out.plan.dest_host,
-                self.out.plan.dest_port
-            )
-        })?
-        .next() else {
-            self.out.errors.push(RawTcpError {
-                kind: "dns lookup".to_owned(),
-                message: format!(
-                    "no A records found for raw_tcp.dest_host '{}'",
-                    self.out.plan.dest_host
-                ),
-            });
-            bail!(
-                "no A records found for raw_tcp.dest_host '{}'",
-                self.out.plan.dest_host
+        // Wait for the egress profile's rate limit, and connect through its proxy if it has one.
+        if let Some(egress) = &self.ctx.egress {
+            egress.acquire().await;
+        }
+        let (dest_host, dest_port) = self
+            .ctx
+            .egress
+            .as_ref()
+            .and_then(|egress| egress.plan.proxy.as_ref())
+            .map_or_else(
+                || (self.out.plan.dest_host.clone(), self.out.plan.dest_port),
+                |proxy| (proxy.host.clone(), proxy.port),
             );
-        };
 
-        // DNS lookup for local address.
-        let src_host = self
-            .out
-            .plan
-            .src_host
-            .clone()
-            .unwrap_or_else(|| "localhost".to_owned());
-        let src_port = self.out.plan.src_port.unwrap_or(0);
-        let Some(local_addr) = net::lookup_host(format!("{}:{}", src_host, src_port,))
-            .await
-            .map_err(|e| {
-                anyhow!(
-                    "lookup host '{}:{}': {e}",
-                    self.out.plan.dest_host,
-                    self.out.plan.dest_port
-                )
-            })?
+        // DNS lookup for remote address.
+        let Some(remote_addr) = super::dns::lookup(&self.ctx, &dest_host, dest_port)
+            .await?
+            .into_iter()
             .next()
         else {
             self.out.errors.push(RawTcpError {
                 kind: "dns lookup".to_owned(),
-                message: format!(
-                    "no A records found for raw_tcp.src_host '{}'",
-                    self.out.plan.dest_host
-                ),
+                message: format!("no A records found for raw_tcp.dest_host '{dest_host}'"),
             });
-            bail!(
-                "no A records found for raw_tcp.src_host '{}'",
-                self.out.plan.dest_host
-            );
+            bail!("no A records found for raw_tcp.dest_host '{dest_host}'");
+        };
+
+        // DNS lookup for local address, defaulting to the egress profile's bind address.
+        let egress_bind = self
+            .ctx
+            .egress
+            .as_ref()
+            .filter(|_| self.out.plan.src_host.is_none())
+            .and_then(|egress| egress.local_addr(remote_addr));
+        let local_addr = match egress_bind {
+            Some(addr) => SocketAddr::new(addr.ip(), self.out.plan.src_port.unwrap_or(0)),
+            None => {
+                let src_host = self
+                    .out
+                    .plan
+                    .src_host
+                    .clone()
+                    .unwrap_or_else(|| "localhost".to_owned());
+                let src_port = self.out.plan.src_port.unwrap_or(0);
+                let local_addrs = match src_host.parse::<IpAddr>() {
+                    Ok(ip) => vec![SocketAddr::new(ip, src_port)],
+                    Err(_) => self
+                        .ctx
+                        .sockets
+                        .lookup_host(src_host.clone(), src_port)
+                        .await
+                        .map_err(|e| anyhow!("lookup host '{src_host}:{src_port}': {e}"))?,
+                };
+                let Some(local_addr) = local_addrs.into_iter().next() else {
+                    self.out.errors.push(RawTcpError {
+                        kind: "dns lookup".to_owned(),
+                        message: format!("no A records found for raw_tcp.src_host '{src_host}'"),
+                    });
+                    bail!("no A records found for raw_tcp.src_host '{src_host}'");
+                };
+                local_addr
+            }
         };
 
+        if !self.ctx.sockets.capture_raw_tcp() {
+            if !self.out.plan.segments.is_empty() {
+                self.state = State::CompletedEmpty;
+                bail!("raw_tcp.segments requires a socket provider with raw capture");
+            }
+            // The provider picks the real local address when it connects, so record what was
+            // asked for.
+            self.out.dest_ip = remote_addr.to_string();
+            self.out.src_host = local_addr.ip().to_string();
+            self.out.src_port = local_addr.port();
+            self.start_time = Some(Instant::now());
+            self.state = State::Uncaptured {
+                remote_addr,
+                local_addr,
+            };
+            return Ok(());
+        }
+        self.start_capture(remote_addr, local_addr)
+    }
+
+    /// Open raw sockets to record the connection's segments and send any the plan lists.
+    #[cfg(feature = "native")]
+    fn start_capture(
+        &mut self,
+        remote_addr: SocketAddr,
+        local_addr: SocketAddr,
+    ) -> anyhow::Result<()> {
+        if self.fixed_src_port() {
+            if let Err(e) = check_privileged_port(local_addr.port()) {
+                self.out.errors.push(RawTcpError {
+                    kind: "permission denied".to_owned(),
+                    message: e.to_string(),
+                });
+                self.state = State::CompletedEmpty;
+                return Err(e);
+            }
+        }
+
         // Bind a temporary tcp socket to let the OS resolve our final local device and port.
         let tmp_socket = if local_addr.is_ipv4() {
             TcpSocket::new_v4()
         } else {
             TcpSocket::new_v6()
         }
+        .and_then(|socket| {
+            // Share a fixed port with earlier steps' connections still in TIME_WAIT.
+            socket.set_reuseaddr(self.fixed_src_port())?;
+            Ok(socket)
+        })
         .inspect_err(|e| {
             self.out.errors.push(RawTcpError {
                 kind: e.kind().to_string(),
@@ -193,7 +254,8 @@ impl RawTcpRunner {
                     message: e.to_string(),
                 });
                 self.state = State::CompletedEmpty;
-            })?;
+            })
+            .map_err(|e| anyhow!("bind {local_addr}: {e}"))?;
         // Record the actual resolved destination and source IPs for the output.
         self.out.dest_ip = remote_addr.to_string();
         self.out.src_host = local_addr.ip().to_string();
@@ -303,25 +365,54 @@ impl RawTcpRunner {
         Ok(())
     }
 
+    /// Raw capture needs pnet's raw sockets, which only the native feature builds.
+    #[cfg(not(feature = "native"))]
+    fn start_capture(&mut self, _: SocketAddr, _: SocketAddr) -> anyhow::Result<()> {
+        self.state = State::CompletedEmpty;
+        bail!("raw_tcp capture requires the native feature; use a socket provider without it")
+    }
+
+    /// Whether the step asked for a specific source port rather than any unused one.
+    pub fn fixed_src_port(&self) -> bool {
+        self.out.plan.src_port.is_some_and(|port| port != 0)
+    }
+
     pub fn resolved_addrs(&self) -> (SocketAddr, SocketAddr) {
         match &self.state {
+            #[cfg(feature = "native")]
             State::Open(OpenState {
                 remote_addr,
                 local_addr,
                 ..
-            })
-            | State::Passive {
+            }) => (*local_addr, *remote_addr),
+            State::Passive {
                 remote_addr,
                 local_addr,
                 ..
+            }
+            | State::Uncaptured {
+                remote_addr,
+                local_addr,
             } => (*local_addr, *remote_addr),
             s => panic!("invalid state to get resolved ips: {s:?}"),
         }
     }
 
     pub async fn execute(&mut self) {
+        match self.state {
+            State::Uncaptured { .. } => {}
+            #[cfg(feature = "native")]
+            State::Open(_) => self.send_segments().await,
+            _ => panic!("invalid state to execute raw_tcp: {:?}", self.state),
+        }
+        self.shutdown(0, 0);
+    }
+
+    /// Send the planned segments while recording what comes back.
+    #[cfg(feature = "native")]
+    async fn send_segments(&mut self) {
         let State::Open(state) = &mut self.state else {
-            panic!("invalid state to execute raw_tcp: {:?}", self.state);
+            unreachable!("send_segments is only called on open runners");
         };
         let send_segments = mem::take(&mut state.send_segments);
         let reads = mem::take(&mut state.reads).expect("reads handle should be set on execute");
@@ -348,7 +439,6 @@ impl RawTcpRunner {
                 message: e.to_string(),
             });
         };
-        self.shutdown(0, 0);
     }
 
     pub async fn finish(mut self) -> RawTcpOutput {
@@ -394,6 +484,7 @@ impl RawTcpRunner {
         let end = Instant::now();
 
         match mem::replace(&mut self.state, State::Invalid) {
+            #[cfg(feature = "native")]
             State::Open(OpenState {
                 reads_done, reads, ..
             }) => {
@@ -413,6 +504,7 @@ impl RawTcpRunner {
                 _ = writes_done.send(expect_write_len);
                 self.state = State::CompletedPassive { reads, writes }
             }
+            State::Uncaptured { .. } => self.state = State::CompletedEmpty,
             state => self.state = state,
         }
 
@@ -437,6 +529,7 @@ impl RawTcpRunner {
             .expect("tcp data offset calculation should not exceed 255")
     }
 
+    #[cfg(feature = "native")]
     pub fn send(&mut self, mut segment: TcpSegmentOutput) -> io::Result<()> {
         let State::Open(OpenState {
             send_write,
@@ -518,6 +611,7 @@ impl RawTcpRunner {
     }
 }
 
+#[cfg(feature = "native")]
 pub fn reader(
     mut read: TransportReceiver,
     target_addr: SocketAddr,
@@ -552,7 +646,7 @@ pub fn reader(
                     }
                     debug!("recording packet from {src_ip}:{}", packet.get_source());
                     total_size += packet.payload().len();
-                    if packet.get_flags() & pnet::packet::tcp::TcpFlags::FIN != 0 {
+                    if packet.get_flags() & tcp::TcpFlags::FIN != 0 {
                         info!("got fin packet from {src_ip}:{}", packet.get_source());
                         seen_fin = true;
                     }
@@ -585,7 +679,7 @@ pub fn reader(
                     }
                     debug!("recording packet from {src_ip}:{}", packet.get_source());
                     total_size += packet.payload().len();
-                    if packet.get_flags() & pnet::packet::tcp::TcpFlags::FIN != 0 {
+                    if packet.get_flags() & tcp::TcpFlags::FIN != 0 {
                         info!("got fin packet from {src_ip}:{}", packet.get_source());
                         seen_fin = true;
                     }
@@ -607,6 +701,7 @@ pub fn reader(
     })
 }
 
+#[cfg(feature = "native")]
 fn packet_to_output(
     packet: TcpPacket,
     start: Instant,
@@ -661,3 +756,45 @@ fn packet_to_output(
         payload: Bytes::copy_from_slice(packet.payload()).into(),
     })
 }
+
+/// CAP_NET_BIND_SERVICE's bit in the capability sets of /proc/self/status.
+#[cfg(all(feature = "native", target_os = "linux"))]
+const CAP_NET_BIND_SERVICE: u32 = 10;
+
+#[cfg(all(feature = "native", target_os = "linux"))]
+const UNPRIVILEGED_PORT_START: &str = "/proc/sys/net/ipv4/ip_unprivileged_port_start";
+
+/// Fail with an explanation if binding a source port below the unprivileged range will be
+/// refused, rather than with the bare EACCES from bind.
+#[cfg(all(feature = "native", target_os = "linux"))]
+fn check_privileged_port(port: u16) -> anyhow::Result<()> {
+    let unprivileged_start = std::fs::read_to_string(UNPRIVILEGED_PORT_START)
+        .ok()
+        .and_then(|start| start.trim().parse().ok())
+        .unwrap_or(1024);
+    if port >= unprivileged_start {
+        return Ok(());
+    }
+    let capable = std::fs::read_to_string("/proc/self/status")
+        .ok()
+        .and_then(|status| {
+            let caps = status
+                .lines()
+                .find_map(|line| line.strip_prefix("CapEff:"))?;
+            u64::from_str_radix(caps.trim(), 16).ok()
+        })
+        .is_some_and(|caps| caps & (1 << CAP_NET_BIND_SERVICE) != 0);
+    if !capable {
+        bail!(
+            "raw_tcp.src_port {port} is privileged; run as root, grant CAP_NET_BIND_SERVICE with \
+            `setcap cap_net_bind_service=+ep`, or lower net.ipv4.ip_unprivileged_port_start"
+        );
+    }
+    Ok(())
+}
+
+/// Other platforms either don't reserve low ports or only report it from bind.
+#[cfg(all(feature = "native", not(target_os = "linux")))]
+fn check_privileged_port(_port: u16) -> anyhow::Result<()> {
+    Ok(())
+}
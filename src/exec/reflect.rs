@@ -0,0 +1,200 @@
+//! Traces markers from marker() from the requests that carried them to the responses they
+//! reappear in, as a starting point for XSS and injection triage. Markers found in a later step's
+//! or a later run's responses than the one that sent them point at stored injection.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::{HttpHeader, JobOutput, ReflectionLocation, ReflectionOutput, StepOutput};
+
+/// The markers seen in requests so far, and the first step to send each.
+#[derive(Debug, Default)]
+pub(super) struct Reflections {
+    sources: HashMap<Arc<String>, Source>,
+}
+
+#[derive(Debug)]
+struct Source {
+    step: Arc<String>,
+    /// The earlier run the step belongs to, for imported markers.
+    run: Option<String>,
+}
+
+impl Reflections {
+    /// Search later responses for the markers sent in `run` of a SQLite output, to verify
+    /// stored injections from it.
+    #[cfg(feature = "native")]
+    pub(super) fn import(&mut self, db: &str, run: &str) -> anyhow::Result<()> {
+        let conn =
+            rusqlite::Connection::open_with_flags(db, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let mut stmt = conn.prepare(
+            "SELECT step, marker.value FROM steps, json_each(steps.record, '$.markers') AS marker
+                WHERE run = ?1",
+        )?;
+        let markers = stmt.query_map([run], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        for marker in markers {
+            let (step, marker): (String, String) = marker?;
+            self.sources.entry(Arc::new(marker)).or_insert(Source {
+                step: Arc::new(step),
+                run: Some(run.to_owned()),
+            });
+        }
+        Ok(())
+    }
+
+    /// Note which of the run's `markers` were first sent by `step`, then add every known marker
+    /// found in the step's responses to its output. Returns whether the output changed.
+    pub(super) fn record(
+        &mut self,
+        markers: Vec<Arc<String>>,
+        step: &Arc<String>,
+        output: &mut StepOutput,
+    ) -> bool {
+        let exchanges: Vec<_> = output
+            .jobs
+            .iter()
+            .filter_map(|(key, job)| Some((key, exchange(job)?)))
+            .collect();
+        let mut sent = Vec::new();
+        for marker in markers {
+            if self.sources.contains_key(&marker) {
+                continue;
+            }
+            let found = exchanges
+                .iter()
+                .flat_map(|(_, exchange)| &exchange.sent)
+                .any(|sent| find(sent, marker.as_bytes()).next().is_some());
+            if found {
+                sent.push(marker.to_string());
+                self.sources.insert(
+                    marker,
+                    Source {
+                        step: step.clone(),
+                        run: None,
+                    },
+                );
+            }
+        }
+
+        let mut reflections = Vec::new();
+        for (key, exchange) in &exchanges {
+            for (marker, source) in &self.sources {
+                let reflection =
+                    |location: ReflectionLocation, header: Option<&HttpHeader>, offset: usize| {
+                        ReflectionOutput {
+                            marker: marker.to_string(),
+                            source: source.step.to_string(),
+                            source_run: source.run.clone(),
+                            stored: source.run.is_some() || source.step != *step,
+                            job: key.to_string(),
+                            protocol: exchange.protocol.to_owned(),
+                            location,
+                            header: header
+                                .and_then(|h| h.key.as_ref())
+                                .map(|name| String::from_utf8_lossy(name.as_bytes()).into_owned()),
+                            offset: offset as u64,
+                        }
+                    };
+                let marker = marker.as_bytes();
+                if let Some(reason) = exchange.status_reason {
+                    reflections.extend(
+                        find(reason, marker)
+                            .map(|i| reflection(ReflectionLocation::StatusLine, None, i)),
+                    );
+                }
+                for header in exchange.headers {
+                    reflections.extend(
+                        find(header.value.as_bytes(), marker)
+                            .map(|i| reflection(ReflectionLocation::Header, Some(header), i)),
+                    );
+                }
+                reflections.extend(
+                    find(exchange.body, marker)
+                        .map(|i| reflection(ReflectionLocation::Body, None, i)),
+                );
+            }
+        }
+        let changed = !sent.is_empty() || !reflections.is_empty();
+        output.markers.extend(sent);
+        output.reflections.extend(reflections);
+        changed
+    }
+}
+
+/// The parts of a job's highest level exchange that markers are searched for in.
+struct Exchange<'a> {
+    protocol: &'static str,
+    sent: Vec<&'a [u8]>,
+    status_reason: Option<&'a [u8]>,
+    headers: &'a [HttpHeader],
+    body: &'a [u8],
+}
+
+fn exchange(job: &JobOutput) -> Option<Exchange<'_>> {
+    // Only HTTP/1 has a reason phrase, so take it from the job's h1 output whatever the level.
+    let status_reason = job
+        .http1()
+        .and_then(|h1| h1.response.as_ref()?.status_reason.as_ref())
+        .map(|reason| reason.as_bytes());
+    macro_rules! http {
+        ($protocol:expr, $output:expr) => {
+            if let Some(output) = $output {
+                let response = output.response.as_ref();
+                return Some(Exchange {
+                    protocol: $protocol,
+                    sent: output
+                        .request
+                        .as_ref()
+                        .map(|req| {
+                            let mut sent = vec![req.url.as_str().as_bytes(), req.body.as_bytes()];
+                            sent.extend(req.headers.iter().map(|h| h.value.as_bytes()));
+                            sent
+                        })
+                        .unwrap_or_default(),
+                    status_reason,
+                    headers: response
+                        .and_then(|r| r.headers.as_deref())
+                        .unwrap_or_default(),
+                    body: response.and_then(|r| r.body.as_deref()).unwrap_or_default(),
+                });
+            }
+        };
+    }
+    http!("http", job.http.as_ref());
+    http!(if job.h1.is_some() { "h1" } else { "h1c" }, job.http1());
+    http!(if job.h2.is_some() { "h2" } else { "h2c" }, job.http2());
+    http!("h3", job.h3.as_ref());
+    macro_rules! stream {
+        ($protocol:expr, $output:expr) => {
+            if let Some(output) = $output {
+                return Some(Exchange {
+                    protocol: $protocol,
+                    sent: output
+                        .sent
+                        .iter()
+                        .map(|sent| sent.body.as_bytes())
+                        .collect(),
+                    status_reason: None,
+                    headers: &[],
+                    body: output
+                        .received
+                        .as_ref()
+                        .map(|received| received.body.as_bytes())
+                        .unwrap_or_default(),
+                });
+            }
+        };
+    }
+    stream!("tls", job.tls.as_ref());
+    stream!("tcp", job.tcp.as_ref());
+    None
+}
+
+/// The offsets of each occurrence of needle in haystack.
+fn find<'a>(haystack: &'a [u8], needle: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+    haystack
+        .windows(needle.len())
+        .enumerate()
+        .filter(move |(_, window)| *window == needle)
+        .map(|(i, _)| i)
+}
@@ -1,3 +1,4 @@
+use std::sync::atomic::AtomicBool;
 use std::{pin::pin, sync::Arc};
 
 use futures::future::BoxFuture;
@@ -9,12 +10,16 @@ use super::{http2::Http2Runner, raw_tcp::RawTcpRunner};
 use crate::{JobOutput, ProtocolDiscriminants, ProtocolField, StepPlanOutput};
 
 use super::{
-    graphql::GraphqlRunner, http::HttpRunner, http1::Http1Runner, tcp::TcpRunner, tls::TlsRunner,
+    graphql::GraphqlRunner, grpc::GrpcRunner, http::HttpRunner, http1::Http1Runner, tcp::TcpRunner,
+    tls::TlsRunner, websocket::WebSocketRunner,
 };
+#[cfg(feature = "native")]
+use super::{http3::Http3Runner, quic::QuicRunner};
 
 #[derive(Debug)]
 pub(super) enum Runner {
     Graphql(Box<GraphqlRunner>),
+    Grpc(Box<GrpcRunner>),
     Http(Box<HttpRunner>),
     H1c(Box<Http1Runner>),
     H1(Box<Http1Runner>),
@@ -22,9 +27,14 @@ pub(super) enum Runner {
     RawH2c(Box<RawHttp2Runner>),
     H2(Box<Http2Runner>),
     RawH2(Box<RawHttp2Runner>),
+    #[cfg(feature = "native")]
+    H3(Box<Http3Runner>),
     Tls(Box<TlsRunner>),
     Tcp(Box<TcpRunner>),
     RawTcp(Box<RawTcpRunner>),
+    #[cfg(feature = "native")]
+    Quic(Box<QuicRunner>),
+    Ws(Box<WebSocketRunner>),
     MuxRawH2(h2::client::SendRequest<bytes::Bytes>),
     MuxRawH2c(h2::client::SendRequest<bytes::Bytes>),
     //PipelinedHttp(PipelineRunner<HttpRunner>),
@@ -45,7 +55,7 @@ impl Runner {
                 Self::RawTcp(Box::new(RawTcpRunner::new(ctx, output)))
             }
             StepPlanOutput::Tcp(output) => Self::Tcp(Box::new(TcpRunner::new(ctx, output))),
-            StepPlanOutput::Tls(output) => Self::Tls(Box::new(TlsRunner::new(ctx, output))),
+            StepPlanOutput::Tls(output) => Self::Tls(Box::new(TlsRunner::new(ctx, output)?)),
             StepPlanOutput::Http(output) => Self::Http(Box::new(HttpRunner::new(ctx, output)?)),
             StepPlanOutput::H1c(output) => Runner::H1c(Box::new(Http1Runner::new(
                 ctx,
@@ -79,9 +89,23 @@ impl Runner {
                 ProtocolDiscriminants::RawH2,
                 executor,
             ))),
+            #[cfg(feature = "native")]
+            StepPlanOutput::H3(output) => Self::H3(Box::new(Http3Runner::new(
+                ctx,
+                output,
+                ProtocolDiscriminants::H3,
+            ))),
+            #[cfg(feature = "native")]
+            StepPlanOutput::Quic(output) => Self::Quic(Box::new(QuicRunner::new(ctx, output))),
+            #[cfg(not(feature = "native"))]
+            StepPlanOutput::H3(_) | StepPlanOutput::Quic(_) => {
+                anyhow::bail!("quic and http3 require the native feature")
+            }
+            StepPlanOutput::Ws(output) => Self::Ws(Box::new(WebSocketRunner::new(ctx, output)?)),
             StepPlanOutput::Graphql(output) => {
                 Self::Graphql(Box::new(GraphqlRunner::new(ctx, output)?))
             }
+            StepPlanOutput::Grpc(output) => Self::Grpc(Box::new(GrpcRunner::new(ctx, output)?)),
         })
     }
 
@@ -98,8 +122,14 @@ impl Runner {
             Self::H2(_) => ProtocolField::H2,
             Self::RawH2(_) => ProtocolField::RawH2,
             Self::MuxRawH2(_) => ProtocolField::RawH2,
+            #[cfg(feature = "native")]
+            Self::H3(_) => ProtocolField::H3,
+            #[cfg(feature = "native")]
+            Self::Quic(_) => ProtocolField::Quic,
+            Self::Ws(_) => ProtocolField::Ws,
             Self::Http(_) => ProtocolField::Http,
             Self::Graphql(_) => ProtocolField::Graphql,
+            Self::Grpc(_) => ProtocolField::Grpc,
         }
     }
 
@@ -112,8 +142,24 @@ impl Runner {
             Self::H2c(r) | Self::H2(r) => r.size_hint(hint),
             Self::RawH2c(r) | Self::RawH2(r) => r.size_hint(hint),
             Self::MuxRawH2(_) | Self::MuxRawH2c(_) => None,
+            #[cfg(feature = "native")]
+            Self::H3(r) => r.size_hint(hint),
+            #[cfg(feature = "native")]
+            Self::Quic(r) => r.size_hint(hint),
+            Self::Ws(r) => r.size_hint(hint),
             Self::Http(r) => r.size_hint(hint),
             Self::Graphql(r) => r.size_hint(hint),
+            Self::Grpc(r) => r.size_hint(hint),
+        }
+    }
+
+    /// The flag which makes the connection under this runner close with a TCP reset, if it's
+    /// one that can.
+    pub(super) fn reset_on_close(&self) -> Option<Arc<AtomicBool>> {
+        match self {
+            Self::Tcp(r) => Some(r.reset_on_close()),
+            Self::Tls(r) => Some(r.reset_on_close()),
+            _ => None,
         }
     }
 
@@ -124,8 +170,14 @@ impl Runner {
             Self::Tls(r) => r.executor_size_hint(),
             Self::H1c(r) | Self::H1(r) => r.executor_size_hint(),
             Self::H2c(r) | Self::H2(r) => r.executor_size_hint(),
+            #[cfg(feature = "native")]
+            Self::H3(r) => r.executor_size_hint(),
+            #[cfg(feature = "native")]
+            Self::Quic(r) => r.executor_size_hint(),
+            Self::Ws(r) => r.executor_size_hint(),
             Self::Http(r) => r.executor_size_hint(),
             Self::Graphql(r) => r.executor_size_hint(),
+            Self::Grpc(r) => r.executor_size_hint(),
             Self::RawH2c(_) => None,
             Self::RawH2(_) => None,
             Self::MuxRawH2c(_) => unimplemented!(),
@@ -170,6 +222,21 @@ impl Runner {
                 concurrent_shares,
             )),
             Self::MuxRawH2(_) | Self::MuxRawH2c(_) => Box::pin(async { Ok(()) }),
+            #[cfg(feature = "native")]
+            Self::H3(r) => match transport {
+                Some(Runner::Quic(transport)) => Box::pin(r.start(*transport)),
+                Some(_) => panic!("http3 requires quic transport"),
+                None => panic!("no plan should have http3 as a base protocol"),
+            },
+            #[cfg(feature = "native")]
+            Self::Quic(r) => {
+                assert!(transport.is_none());
+                Box::pin(r.start())
+            }
+            Self::Ws(r) => {
+                assert!(transport.is_none());
+                Box::pin(r.start())
+            }
             Self::Http(r) => {
                 assert!(transport.is_none());
                 Box::pin(r.start())
@@ -177,6 +244,9 @@ impl Runner {
             Self::Graphql(r) => Box::pin(
                 r.start(transport.expect("no plan should have graphql as a base protocol")),
             ),
+            Self::Grpc(r) => {
+                Box::pin(r.start(transport.expect("no plan should have grpc as a base protocol")))
+            }
         }
     }
 
@@ -188,11 +258,17 @@ impl Runner {
             Self::H1c(r) | Self::H1(r) => r.execute().await,
             Self::H2c(r) | Self::H2(r) => r.execute().await,
             Self::RawH2c(r) | Self::RawH2(r) => r.execute().await,
+            #[cfg(feature = "native")]
+            Self::H3(r) => r.execute().await,
+            #[cfg(feature = "native")]
+            Self::Quic(_) => {}
+            Self::Ws(r) => r.execute().await,
             Self::MuxRawH2c(_) | Self::MuxRawH2(_) => {
                 panic!("cannot multiplex and execute at the same layer")
             }
             Self::Http(r) => r.execute().await,
             Self::Graphql(r) => r.execute().await,
+            Self::Grpc(r) => r.execute().await,
         }
     }
 
@@ -213,7 +289,7 @@ impl Runner {
                 Some(inner)
             }
             Self::Http(r) => {
-                let (out, inner) = r.finish();
+                let (out, inner) = r.finish().await;
                 output.http = Some(Arc::new(out));
                 inner
             }
@@ -247,11 +323,41 @@ impl Runner {
                 output.raw_h2 = Some(Arc::new(out));
                 inner
             }
+            #[cfg(feature = "native")]
+            Self::H3(r) => {
+                let (out, inner) = r.finish().await;
+                output.h3 = Some(Arc::new(out));
+                inner.map(|inner| Runner::Quic(Box::new(inner)))
+            }
+            #[cfg(feature = "native")]
+            Self::Quic(r) => {
+                output.quic = Some(Arc::new(r.finish().await));
+                None
+            }
+            Self::Ws(r) => {
+                let (out, inner) = r.finish().await;
+                output.ws = Some(Arc::new(out));
+                inner
+            }
             Self::Graphql(r) => {
                 let (out, inner) = r.finish();
                 output.graphql = Some(Arc::new(out));
                 inner
             }
+            Self::Grpc(r) => {
+                let (mut out, inner) = r.finish();
+                // grpc-status comes in the trailers, which h2 only reads once it's finished.
+                let inner = match inner {
+                    Some(inner @ (Self::H2c(_) | Self::H2(_))) => {
+                        Box::pin(inner.finish(output)).await
+                    }
+                    inner => inner,
+                };
+                let h2 = output.h2.as_ref().or(output.h2c.as_ref());
+                super::grpc::set_status(&mut out, h2.and_then(|h2| h2.response.as_deref()));
+                output.grpc = Some(Arc::new(out));
+                inner
+            }
             Self::MuxRawH2(_) | Self::MuxRawH2c(_) => panic!(),
         }
     }
@@ -278,7 +384,13 @@ impl AsyncRead for Runner {
                 panic!("raw_h2 doesn't support stream reading")
             }
             Self::Http(ref mut r) => pin!(r).poll_read(cx, buf),
+            #[cfg(feature = "native")]
+            Self::H3(_) => panic!("h3 doesn't support stream reading"),
+            #[cfg(feature = "native")]
+            Self::Quic(_) => panic!("quic doesn't support stream reading"),
+            Self::Ws(_) => panic!("ws doesn't support stream reading"),
             Self::Graphql(_) => panic!("graphql cannot be used as a transport"),
+            Self::Grpc(_) => panic!("grpc cannot be used as a transport"),
         }
     }
 }
@@ -304,7 +416,13 @@ impl AsyncWrite for Runner {
                 panic!("raw_h2 doesn't support stream writing")
             }
             Self::Http(ref mut r) => pin!(r).poll_write(cx, buf),
+            #[cfg(feature = "native")]
+            Self::H3(_) => panic!("h3 doesn't support stream writing"),
+            #[cfg(feature = "native")]
+            Self::Quic(_) => panic!("quic doesn't support stream writing"),
+            Self::Ws(_) => panic!("ws doesn't support stream writing"),
             Self::Graphql(_) => panic!("graphql cannot be used as a transport"),
+            Self::Grpc(_) => panic!("grpc cannot be used as a transport"),
         }
     }
     fn poll_flush(
@@ -326,7 +444,13 @@ impl AsyncWrite for Runner {
                 panic!("h2 doesn't support stream writing")
             }
             Self::Http(ref mut r) => pin!(r).poll_flush(cx),
+            #[cfg(feature = "native")]
+            Self::H3(_) => panic!("h3 doesn't support stream writing"),
+            #[cfg(feature = "native")]
+            Self::Quic(_) => panic!("quic doesn't support stream writing"),
+            Self::Ws(_) => panic!("ws doesn't support stream writing"),
             Self::Graphql(_) => panic!("graphql cannot be used as a transport"),
+            Self::Grpc(_) => panic!("grpc cannot be used as a transport"),
         }
     }
     fn poll_shutdown(
@@ -348,7 +472,13 @@ impl AsyncWrite for Runner {
                 panic!("raw_h2 doesn't support stream writing")
             }
             Self::Http(ref mut r) => pin!(r).poll_shutdown(cx),
+            #[cfg(feature = "native")]
+            Self::H3(_) => panic!("h3 doesn't support stream writing"),
+            #[cfg(feature = "native")]
+            Self::Quic(_) => panic!("quic doesn't support stream writing"),
+            Self::Ws(_) => panic!("ws doesn't support stream writing"),
             Self::Graphql(_) => panic!("graphql cannot be used as a transport"),
+            Self::Grpc(_) => panic!("grpc cannot be used as a transport"),
         }
     }
 }
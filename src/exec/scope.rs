@@ -0,0 +1,272 @@
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+
+use anyhow::anyhow;
+use futures::future::BoxFuture;
+use tracing::warn;
+use url::Url;
+
+use super::egress::Egress;
+use super::socket::{BoxDatagram, BoxStream, SocketOptions, SocketProvider};
+use crate::Scope;
+
+/// Enforces a plan's scope on every lookup and connection, recording what it blocks.
+#[derive(Debug)]
+pub(super) struct Guard {
+    scope: Scope,
+    /// Addresses of hosts allowed by name, which pass the address check unless address ranges
+    /// are allowed.
+    trusted: Mutex<HashSet<IpAddr>>,
+    /// Addresses of resolvers and egress proxies, which carry traffic without being targets
+    /// themselves, so they always pass the address check.
+    carriers: Mutex<HashSet<IpAddr>>,
+    /// Egress proxy hosts, which may be looked up even if they're outside the scope.
+    proxies: HashSet<String>,
+    violations: Mutex<Vec<String>>,
+}
+
+impl Guard {
+    pub fn new(scope: Scope, egress: &HashMap<String, Arc<Egress>>) -> Self {
+        let carriers = egress
+            .values()
+            .filter_map(|egress| egress.resolver)
+            .chain([super::dns::system_resolver()])
+            .map(|addr| addr.ip())
+            .collect();
+        let proxies = egress
+            .values()
+            .filter_map(|egress| egress.plan.proxy.as_ref())
+            .map(|proxy| proxy.host.to_ascii_lowercase())
+            .collect();
+        Self {
+            scope,
+            trusted: Mutex::default(),
+            carriers: Mutex::new(carriers),
+            proxies,
+            violations: Mutex::default(),
+        }
+    }
+
+    /// Check a host before looking it up, returning whether the addresses it resolves to should
+    /// be trusted.
+    pub fn check_host(&self, host: &str) -> anyhow::Result<bool> {
+        let bare = host.trim_start_matches('[').trim_end_matches(']');
+        if let Ok(ip) = bare.parse() {
+            self.check_ip(ip)?;
+            return Ok(false);
+        }
+        if self.scope.denies_host(host) {
+            return Err(self.violation(format!("host {host} is denied by devil.scope")));
+        }
+        if self.scope.allows_host(host) || self.proxies.contains(&host.to_ascii_lowercase()) {
+            return Ok(true);
+        }
+        // Leave hosts which may resolve to an allowed range to the address check.
+        if self.scope.limits_hosts() && !self.scope.allows_networks() {
+            return Err(self.violation(format!("host {host} is not in devil.scope.allow")));
+        }
+        Ok(false)
+    }
+
+    /// Let the addresses of a host which passed [`Guard::check_host`] through the address check.
+    pub fn trust(&self, host: &str, addrs: &[SocketAddr]) {
+        let set = if self.proxies.contains(&host.to_ascii_lowercase()) {
+            &self.carriers
+        } else {
+            &self.trusted
+        };
+        set.lock().unwrap().extend(addrs.iter().map(SocketAddr::ip));
+    }
+
+    pub fn check_ip(&self, ip: IpAddr) -> anyhow::Result<()> {
+        if self.scope.denies_ip(ip) {
+            return Err(self.violation(format!("address {ip} is denied by devil.scope")));
+        }
+        if !self.scope.limits_hosts()
+            || self.scope.allows_ip(ip)
+            || self.carriers.lock().unwrap().contains(&ip)
+        {
+            return Ok(());
+        }
+        // With address ranges allowed, a host allowed by name must still resolve into them, so
+        // its DNS can't point requests at other networks.
+        if !self.scope.allows_networks() && self.trusted.lock().unwrap().contains(&ip) {
+            return Ok(());
+        }
+        Err(self.violation(format!("address {ip} is not in devil.scope.allow")))
+    }
+
+    /// Check a destination reached through a proxy. The proxy resolves it, so an unlisted name
+    /// can't be let through on the strength of its addresses.
+    pub fn check_proxied(&self, host: &str) -> anyhow::Result<()> {
+        let bare = host.trim_start_matches('[').trim_end_matches(']');
+        if let Ok(ip) = bare.parse() {
+            return self.check_ip(ip);
+        }
+        if self.scope.denies_host(host) {
+            return Err(self.violation(format!("host {host} is denied by devil.scope")));
+        }
+        if self.scope.limits_hosts() && !self.scope.allows_host(host) {
+            return Err(self.violation(format!("host {host} is not in devil.scope.allow")));
+        }
+        Ok(())
+    }
+
+    pub fn check_url(&self, url: &Url) -> anyhow::Result<()> {
+        if self.scope.denies_url(url) {
+            return Err(self.violation(format!("url {url} is denied by devil.scope")));
+        }
+        if self.scope.limits_urls() && !self.scope.allows_url(url) {
+            return Err(self.violation(format!("url {url} is not in devil.scope.allow")));
+        }
+        Ok(())
+    }
+
+    /// The violations recorded since the last call.
+    pub fn take_violations(&self) -> Vec<String> {
+        std::mem::take(&mut *self.violations.lock().unwrap())
+    }
+
+    fn violation(&self, message: String) -> anyhow::Error {
+        warn!("blocked out of scope traffic: {message}");
+        self.violations.lock().unwrap().push(message.clone());
+        anyhow!(message)
+    }
+}
+
+/// Wraps a socket provider to refuse connections to addresses outside the scope, so nothing
+/// bypasses the guard by connecting without a lookup.
+#[derive(Debug)]
+pub(super) struct ScopedSocketProvider {
+    inner: Arc<dyn SocketProvider>,
+    guard: Arc<Guard>,
+}
+
+impl ScopedSocketProvider {
+    pub fn wrap(
+        inner: Arc<dyn SocketProvider>,
+        guard: Option<&Arc<Guard>>,
+    ) -> Arc<dyn SocketProvider> {
+        match guard {
+            Some(guard) => Arc::new(Self {
+                inner,
+                guard: guard.clone(),
+            }),
+            None => inner,
+        }
+    }
+}
+
+impl SocketProvider for ScopedSocketProvider {
+    fn connect_tcp(
+        &self,
+        local_addr: SocketAddr,
+        remote_addr: SocketAddr,
+    ) -> BoxFuture<'static, io::Result<BoxStream>> {
+        if let Err(e) = self.guard.check_ip(remote_addr.ip()) {
+            let e = io::Error::new(io::ErrorKind::PermissionDenied, e.to_string());
+            return Box::pin(async move { Err(e) });
+        }
+        self.inner.connect_tcp(local_addr, remote_addr)
+    }
+
+    fn connect_tcp_with_options(
+        &self,
+        local_addr: SocketAddr,
+        remote_addr: SocketAddr,
+        options: SocketOptions,
+    ) -> BoxFuture<'static, io::Result<BoxStream>> {
+        if let Err(e) = self.guard.check_ip(remote_addr.ip()) {
+            let e = io::Error::new(io::ErrorKind::PermissionDenied, e.to_string());
+            return Box::pin(async move { Err(e) });
+        }
+        self.inner
+            .connect_tcp_with_options(local_addr, remote_addr, options)
+    }
+
+    fn connect_udp(
+        &self,
+        local_addr: SocketAddr,
+        remote_addr: SocketAddr,
+    ) -> BoxFuture<'static, io::Result<BoxDatagram>> {
+        if let Err(e) = self.guard.check_ip(remote_addr.ip()) {
+            let e = io::Error::new(io::ErrorKind::PermissionDenied, e.to_string());
+            return Box::pin(async move { Err(e) });
+        }
+        self.inner.connect_udp(local_addr, remote_addr)
+    }
+
+    fn lookup_host(
+        &self,
+        host: String,
+        port: u16,
+    ) -> BoxFuture<'static, io::Result<Vec<SocketAddr>>> {
+        self.inner.lookup_host(host, port)
+    }
+
+    fn capture_raw_tcp(&self) -> bool {
+        self.inner.capture_raw_tcp()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guard(allow: &[&str], deny: &[&str]) -> Guard {
+        let parse = |rules: &[&str]| rules.iter().map(|r| r.parse().unwrap()).collect();
+        let scope = Scope {
+            allow: parse(allow),
+            deny: parse(deny),
+        };
+        Guard::new(scope, &HashMap::new())
+    }
+
+    fn addrs(ips: &[&str]) -> Vec<SocketAddr> {
+        ips.iter()
+            .map(|ip| SocketAddr::new(ip.parse().unwrap(), 443))
+            .collect()
+    }
+
+    #[test]
+    fn test_hosts_allowed_by_name() {
+        let guard = guard(&["api.example.com"], &["10.0.0.0/8"]);
+        assert!(guard.check_host("api.example.com").unwrap());
+        assert!(guard.check_host("other.example.com").is_err());
+        guard.trust("api.example.com", &addrs(&["192.0.2.1", "10.0.0.1"]));
+        assert!(guard.check_ip("192.0.2.1".parse().unwrap()).is_ok());
+        // Deny ranges apply even to allowed hosts.
+        assert!(guard.check_ip("10.0.0.1".parse().unwrap()).is_err());
+        assert!(guard.check_ip("192.0.2.2".parse().unwrap()).is_err());
+        assert_eq!(guard.take_violations().len(), 3);
+    }
+
+    #[test]
+    fn test_allowed_hosts_resolve_into_allowed_ranges() {
+        let guard = guard(&["api.example.com", "192.0.2.0/24"], &[]);
+        assert!(guard.check_host("api.example.com").unwrap());
+        // Hosts not named may still resolve into a range.
+        assert!(!guard.check_host("other.example.com").unwrap());
+        guard.trust("api.example.com", &addrs(&["192.0.2.1", "203.0.113.9"]));
+        assert!(guard.check_ip("192.0.2.1".parse().unwrap()).is_ok());
+        assert!(guard.check_ip("203.0.113.9".parse().unwrap()).is_err());
+        assert!(guard.check_ip("198.51.100.1".parse().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_unlimited_hosts() {
+        let guard = guard(&["https://api.example.com/"], &["169.254.0.0/16"]);
+        assert!(!guard.check_host("anything.example.net").unwrap());
+        assert!(guard.check_ip("198.51.100.1".parse().unwrap()).is_ok());
+        assert!(guard.check_ip("169.254.169.254".parse().unwrap()).is_err());
+        assert!(guard
+            .check_url(&Url::parse("https://api.example.com/v1").unwrap())
+            .is_ok());
+        assert!(guard
+            .check_url(&Url::parse("https://api.example.com.attacker.net/").unwrap())
+            .is_err());
+        assert!(guard.check_proxied("169.254.169.254").is_err());
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::{ScriptOutput, ScriptPlanOutput, StepOutput};
+
+type Locals = HashMap<cel_interpreter::objects::Key, cel_interpreter::Value>;
+
+/// Run the script with earlier steps' outputs in `steps` and the plan's locals in `locals`.
+/// Entries the script adds to or changes in `locals` are copied back for later steps.
+///
+/// The engine has no access to the filesystem, network, or environment. Scripts may run for a
+/// while before reaching their operation limit, so they're run off the async runtime's threads.
+#[cfg(feature = "script")]
+pub(super) async fn script(
+    plan: ScriptPlanOutput,
+    steps: &HashMap<Arc<String>, StepOutput>,
+    locals: &mut Locals,
+) -> anyhow::Result<ScriptOutput> {
+    let steps = steps.clone();
+    let mut updated = locals.clone();
+    #[cfg(feature = "native")]
+    let (output, updated) = tokio::task::spawn_blocking(move || {
+        let output = run(plan, &steps, &mut updated);
+        (output, updated)
+    })
+    .await?;
+    // Without native threads there's nowhere else to run it.
+    #[cfg(not(feature = "native"))]
+    let output = run(plan, &steps, &mut updated);
+    *locals = updated;
+    output
+}
+
+#[cfg(feature = "script")]
+fn run(
+    plan: ScriptPlanOutput,
+    steps: &HashMap<Arc<String>, StepOutput>,
+    locals: &mut Locals,
+) -> anyhow::Result<ScriptOutput> {
+    use std::sync::Mutex;
+    use std::time::Instant;
+
+    use anyhow::anyhow;
+    use chrono::TimeDelta;
+    use indexmap::IndexMap;
+    use rhai::serde::{from_dynamic, to_dynamic};
+    use rhai::{Dynamic, Engine, Map, Scope};
+
+    use crate::OutValue;
+
+    let printed = Arc::new(Mutex::new(Vec::new()));
+    let mut engine = Engine::new();
+    engine.set_max_operations(plan.max_operations);
+    let print = printed.clone();
+    engine.on_print(move |s| print.lock().unwrap().push(s.to_owned()));
+    let debug = printed.clone();
+    engine.on_debug(move |s, _, _| debug.lock().unwrap().push(s.to_owned()));
+
+    let mut step_map = Map::new();
+    for (name, output) in steps {
+        step_map.insert(name.as_str().into(), to_dynamic(output)?);
+    }
+    let mut before = IndexMap::new();
+    let mut local_map = Map::new();
+    for (key, value) in locals.iter() {
+        let cel_interpreter::objects::Key::String(key) = key else {
+            continue;
+        };
+        // Locals that don't fit in a script value, like functions, are left out.
+        let Ok(value) = OutValue::try_from(value.clone()) else {
+            continue;
+        };
+        let value = to_dynamic(value)?;
+        before.insert(key.to_string(), from_dynamic::<serde_json::Value>(&value)?);
+        local_map.insert(key.as_str().into(), value);
+    }
+    let mut scope = Scope::new();
+    scope.push_constant("steps", step_map);
+    scope.push("locals", local_map);
+
+    let start = Instant::now();
+    let result = engine
+        .eval_with_scope::<Dynamic>(&mut scope, &plan.source)
+        .map_err(|e| anyhow!("run script: {e}"))?;
+    let duration = TimeDelta::from_std(start.elapsed()).unwrap_or(TimeDelta::MAX);
+
+    let mut changed = IndexMap::new();
+    let local_map = scope
+        .get_value::<Map>("locals")
+        .ok_or_else(|| anyhow!("script replaced locals with a value that isn't a map"))?;
+    for (key, value) in local_map {
+        let value = from_dynamic::<serde_json::Value>(&value)?;
+        if before.get(key.as_str()) == Some(&value) {
+            continue;
+        }
+        let local = cel_interpreter::to_value(value.clone())
+            .map_err(|e| anyhow!("convert local {key}: {e}"))?;
+        locals.insert(key.to_string().into(), local);
+        changed.insert(key.to_string(), value);
+    }
+    let printed = std::mem::take(&mut *printed.lock().unwrap());
+    Ok(ScriptOutput {
+        plan,
+        result: from_dynamic(&result)?,
+        locals: changed,
+        printed,
+        duration: duration.into(),
+    })
+}
+
+#[cfg(not(feature = "script"))]
+pub(super) async fn script(
+    _: ScriptPlanOutput,
+    _: &HashMap<Arc<String>, StepOutput>,
+    _: &mut Locals,
+) -> anyhow::Result<ScriptOutput> {
+    anyhow::bail!("script steps are disabled, build devil with the script feature to enable them")
+}
@@ -0,0 +1,145 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use chrono::TimeDelta;
+use futures::future::join_all;
+
+use super::cookies::CookieJar;
+use super::socket::SocketProvider;
+use super::{ConfirmDestructive, Executor};
+use crate::cel_functions::CelState;
+use crate::{
+    ModuleOutput, Plan, RunName, RunOutput, SessionOutput, SessionPlanOutput, SessionUserOutput,
+    ThinkDistribution, ThinkTimePlanOutput,
+};
+
+/// Run the session's journey for each virtual user concurrently, starting users evenly over the
+/// ramp up period.
+pub(super) async fn session(
+    plan: SessionPlanOutput,
+    run: svix_ksuid::KsuidMs,
+    sockets: Arc<dyn SocketProvider>,
+    confirm_destructive: Option<ConfirmDestructive>,
+    cel: Arc<CelState>,
+) -> anyhow::Result<SessionOutput> {
+    let text = super::read_to_string(&plan.path)
+        .await
+        .map_err(|e| anyhow!("read session {}: {e}", plan.path))?;
+    let journey = Plan::parse(&text)?;
+    let ramp_up = plan
+        .ramp_up
+        .as_ref()
+        .and_then(|r| r.0.to_std().ok())
+        .unwrap_or_default();
+    let users = join_all((0..plan.users).map(|user| {
+        let delay = ramp_up.mul_f64(user as f64 / plan.users as f64);
+        run_user(
+            &plan,
+            &journey,
+            user,
+            delay,
+            run,
+            sockets.clone(),
+            confirm_destructive.clone(),
+            cel.clone(),
+        )
+    }))
+    .await;
+    Ok(SessionOutput {
+        path: plan.path,
+        think_time: plan.think_time,
+        users,
+    })
+}
+
+async fn run_user(
+    plan: &SessionPlanOutput,
+    journey: &Plan,
+    user: u64,
+    delay: std::time::Duration,
+    run: svix_ksuid::KsuidMs,
+    sockets: Arc<dyn SocketProvider>,
+    confirm_destructive: Option<ConfirmDestructive>,
+    cel: Arc<CelState>,
+) -> SessionUserOutput {
+    tokio::time::sleep(delay).await;
+    // Each user keeps its cookies across iterations, like a returning visitor.
+    let cookies = Arc::new(CookieJar::default());
+    let mut think_time = std::time::Duration::ZERO;
+    let mut out = SessionUserOutput {
+        user,
+        iterations: Vec::new(),
+        think_time: TimeDelta::zero().into(),
+        error: None,
+    };
+    for iteration in 0..plan.iterations {
+        let result = async {
+            // Each user's variables are its params plus which user and iteration it is.
+            let mut params = plan.params.clone();
+            params.insert("user".to_owned(), cel_interpreter::Value::UInt(user));
+            params.insert(
+                "iteration".to_owned(),
+                cel_interpreter::Value::UInt(iteration),
+            );
+            let mut output = RunOutput::new(RunName {
+                plan: journey.name.clone(),
+                run,
+            });
+            let mut executor =
+                Executor::with_cel_state(journey, output.name.clone(), params, cel.clone())?
+                    .with_socket_provider(sockets.clone())
+                    .with_cookie_jar(cookies.clone());
+            executor.confirm_destructive = confirm_destructive.clone();
+            while executor.current().is_some() {
+                if iteration > 0 || !output.steps.is_empty() {
+                    think_time += think(plan.think_time.as_ref()).await;
+                }
+                // Boxed since journeys may run other sessions or modules.
+                let step = Box::pin(executor.next()).await?;
+                output.steps.insert(step.name.step.clone(), Arc::new(step));
+            }
+            output.usage = Some(executor.usage());
+            anyhow::Ok(output)
+        }
+        .await;
+        match result {
+            Ok(output) => out.iterations.push(ModuleOutput(Arc::new(output))),
+            Err(e) => {
+                out.error = Some(format!("{e:#}"));
+                break;
+            }
+        }
+    }
+    out.think_time = TimeDelta::from_std(think_time).unwrap_or_default().into();
+    out
+}
+
+/// Pause for a time drawn from the distribution, returning how long it was.
+async fn think(plan: Option<&ThinkTimePlanOutput>) -> std::time::Duration {
+    let Some(plan) = plan else {
+        return std::time::Duration::ZERO;
+    };
+    let to_std = |d: &Option<cel_interpreter::Duration>| {
+        d.as_ref()
+            .and_then(|d| d.0.to_std().ok())
+            .unwrap_or_default()
+    };
+    let min = to_std(&plan.min);
+    let duration = match plan.distribution {
+        ThinkDistribution::Constant => to_std(&plan.mean),
+        ThinkDistribution::Uniform => {
+            let max = to_std(&plan.max).max(min);
+            min + (max - min).mul_f64(rand::random::<f64>())
+        }
+        ThinkDistribution::Exponential => {
+            let sample = -(1.0 - rand::random::<f64>()).ln();
+            let duration = min + to_std(&plan.mean).mul_f64(sample);
+            match plan.max {
+                Some(_) => duration.min(to_std(&plan.max)),
+                None => duration,
+            }
+        }
+    };
+    tokio::time::sleep(duration).await;
+    duration
+}
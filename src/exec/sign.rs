@@ -0,0 +1,405 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail};
+use base64::Engine;
+use hmac::digest::KeyInit;
+use hmac::{Hmac, Mac};
+use itertools::Itertools;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::{HttpHeader, MaybeUtf8, SignKind, SignPlanOutput};
+
+const DEFAULT_COMPONENTS: &str = "method,path,query,body";
+
+/// Sign a request, adding the signature and any headers it covers to `headers`.
+pub(super) fn sign(
+    plan: &SignPlanOutput,
+    method: &[u8],
+    url: &Url,
+    headers: &mut Vec<HttpHeader>,
+    body: &[u8],
+) -> anyhow::Result<()> {
+    match plan.kind {
+        SignKind::AwsSigv4 => sigv4(plan, method, url, headers, body, chrono::Utc::now()),
+        SignKind::Hmac => hmac(plan, method, url, headers, body),
+    }
+}
+
+fn sigv4(
+    plan: &SignPlanOutput,
+    method: &[u8],
+    url: &Url,
+    headers: &mut Vec<HttpHeader>,
+    body: &[u8],
+    now: chrono::DateTime<chrono::Utc>,
+) -> anyhow::Result<()> {
+    let access_key = plan
+        .access_key
+        .as_deref()
+        .ok_or_else(|| anyhow!("sign.access_key is required for aws_sigv4"))?;
+    let secret_key = plan
+        .secret_key
+        .as_ref()
+        .ok_or_else(|| anyhow!("sign.secret_key is required for aws_sigv4"))?;
+    let region = plan
+        .region
+        .as_deref()
+        .ok_or_else(|| anyhow!("sign.region is required for aws_sigv4"))?;
+    let service = plan
+        .service
+        .as_deref()
+        .ok_or_else(|| anyhow!("sign.service is required for aws_sigv4"))?;
+
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = now.format("%Y%m%d").to_string();
+    let payload_hash = hex(&Sha256::digest(body));
+
+    if find_header(headers, "host").is_none() {
+        let host = url
+            .host_str()
+            .ok_or_else(|| anyhow!("url has no host to sign"))?;
+        let host = match url.port() {
+            Some(port) => format!("{host}:{port}"),
+            None => host.to_owned(),
+        };
+        push_header(headers, "Host", host);
+    }
+    push_header(headers, "X-Amz-Date", amz_date.clone());
+    push_header(headers, "X-Amz-Content-Sha256", payload_hash.clone());
+    if let Some(token) = &plan.session_token {
+        crate::secret::register(token.expose());
+        push_header(headers, "X-Amz-Security-Token", token.expose().to_owned());
+    }
+
+    // Headers with the same name are combined, and all names are sorted.
+    let canonical_headers = headers
+        .iter()
+        .filter_map(|h| {
+            Some((
+                String::from_utf8_lossy(h.key.as_ref()?).to_ascii_lowercase(),
+                String::from_utf8_lossy(&h.value)
+                    .split_whitespace()
+                    .join(" "),
+            ))
+        })
+        .into_group_map()
+        .into_iter()
+        .sorted_by(|(a, _), (b, _)| a.cmp(b))
+        .collect_vec();
+    let signed_headers = canonical_headers.iter().map(|(k, _)| k).join(";");
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        String::from_utf8_lossy(method),
+        canonical_uri(url, service),
+        canonical_query(url),
+        canonical_headers
+            .iter()
+            .map(|(k, v)| format!("{k}:{}\n", v.join(",")))
+            .join(""),
+        signed_headers,
+        payload_hash,
+    );
+
+    let scope = format!("{date}/{region}/{service}/aws4_request");
+    let signature = sigv4_signature(secret_key.expose(), &amz_date, &scope, &canonical_request);
+
+    push_header(
+        headers,
+        "Authorization",
+        format!(
+            "AWS4-HMAC-SHA256 Credential={access_key}/{scope}, SignedHeaders={signed_headers}, Signature={signature}"
+        ),
+    );
+    Ok(())
+}
+
+/// Each path segment is encoded once for S3 and twice for every other service.
+fn canonical_uri(url: &Url, service: &str) -> String {
+    let path = if url.path().is_empty() {
+        "/"
+    } else {
+        url.path()
+    };
+    path.split('/')
+        .map(|segment| {
+            let once = uri_encode(&percent_decode(segment));
+            if service == "s3" {
+                once
+            } else {
+                uri_encode(once.as_bytes())
+            }
+        })
+        .join("/")
+}
+
+/// Built from the raw query rather than form decoding it, so a literal `+` is
+/// signed as `%2B` instead of a space.
+fn canonical_query(url: &Url) -> String {
+    url.query()
+        .unwrap_or_default()
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+            (
+                uri_encode(&percent_decode(k)),
+                uri_encode(&percent_decode(v)),
+            )
+        })
+        .sorted()
+        .map(|(k, v)| format!("{k}={v}"))
+        .join("&")
+}
+
+fn sigv4_signature(
+    secret_key: &str,
+    amz_date: &str,
+    scope: &str,
+    canonical_request: &str,
+) -> String {
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+        hex(&Sha256::digest(canonical_request.as_bytes()))
+    );
+    let key = scope
+        .split('/')
+        .fold(format!("AWS4{secret_key}").into_bytes(), |key, part| {
+            mac::<Hmac<Sha256>>(&key, part.as_bytes())
+        });
+    hex(&mac::<Hmac<Sha256>>(&key, string_to_sign.as_bytes()))
+}
+
+fn hmac(
+    plan: &SignPlanOutput,
+    method: &[u8],
+    url: &Url,
+    headers: &mut Vec<HttpHeader>,
+    body: &[u8],
+) -> anyhow::Result<()> {
+    let key = plan
+        .secret_key
+        .as_ref()
+        .ok_or_else(|| anyhow!("sign.secret_key is required for hmac"))?;
+
+    // Build the canonical request from the configured components.
+    let mut canonical = Vec::new();
+    let components = plan.components.as_deref().unwrap_or(DEFAULT_COMPONENTS);
+    for (i, component) in components.split(',').map(str::trim).enumerate() {
+        if i > 0 {
+            canonical.extend_from_slice(plan.separator.as_deref().unwrap_or("\n").as_bytes());
+        }
+        match component {
+            "method" => canonical.extend_from_slice(method),
+            "url" => canonical.extend_from_slice(url.as_str().as_bytes()),
+            "path" => canonical.extend_from_slice(url.path().as_bytes()),
+            "query" => canonical.extend_from_slice(url.query().unwrap_or_default().as_bytes()),
+            "body" => canonical.extend_from_slice(body),
+            "body_sha256" => canonical.extend_from_slice(hex(&Sha256::digest(body)).as_bytes()),
+            c => match c.strip_prefix("header:") {
+                Some(name) => canonical.extend_from_slice(
+                    find_header(headers, name)
+                        .ok_or_else(|| anyhow!("header {name} to sign is missing"))?,
+                ),
+                None => bail!("unrecognized sign.components entry {c}"),
+            },
+        }
+    }
+
+    let signature = match plan.algorithm.as_deref().unwrap_or("sha256") {
+        "sha1" => mac::<Hmac<sha1::Sha1>>(key.expose().as_bytes(), &canonical),
+        "sha256" => mac::<Hmac<Sha256>>(key.expose().as_bytes(), &canonical),
+        "sha512" => mac::<Hmac<sha2::Sha512>>(key.expose().as_bytes(), &canonical),
+        a => bail!("unsupported sign.algorithm {a}"),
+    };
+    let signature = match plan.encoding.as_deref().unwrap_or("base64") {
+        "base64" => base64::prelude::BASE64_STANDARD.encode(signature),
+        "hex" => hex(&signature),
+        e => bail!("unsupported sign.encoding {e}"),
+    };
+    push_header(
+        headers,
+        plan.header.as_deref().unwrap_or("Authorization").to_owned(),
+        format!("{}{signature}", plan.prefix.as_deref().unwrap_or_default()),
+    );
+    Ok(())
+}
+
+fn mac<M: Mac + KeyInit>(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = <M as KeyInit>::new_from_slice(key).expect("hmac accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn find_header<'a>(headers: &'a [HttpHeader], name: &str) -> Option<&'a [u8]> {
+    headers
+        .iter()
+        .find(|h| {
+            h.key
+                .as_ref()
+                .is_some_and(|k| k.eq_ignore_ascii_case(name.as_bytes()))
+        })
+        .map(|h| &*h.value)
+}
+
+fn push_header(headers: &mut Vec<HttpHeader>, key: impl Into<String>, value: String) {
+    headers.push(HttpHeader {
+        key: Some(MaybeUtf8(Arc::new(key.into()).into())),
+        value: MaybeUtf8(Arc::new(value).into()),
+    });
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Percent-encode everything except unreserved characters, as AWS requires.
+fn uri_encode(s: &[u8]) -> String {
+    s.iter()
+        .map(|&b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            b => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// Decode `%XX` escapes, leaving malformed ones and `+` as they are.
+fn percent_decode(s: &str) -> Vec<u8> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| bytes.get(i + 1..i + 3))
+            .flatten()
+            .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(b) => {
+                out.push(b);
+                i += 3;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Secret;
+
+    // Vectors from the AWS Signature Version 4 test suite.
+    const SECRET: &str = "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY";
+    const AMZ_DATE: &str = "20150830T123600Z";
+    const SCOPE: &str = "20150830/us-east-1/service/aws4_request";
+    const EMPTY_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+    fn suite_signature(query: &str) -> (String, String) {
+        let url = Url::parse(&format!("https://example.amazonaws.com/{query}")).unwrap();
+        let canonical_request = format!(
+            "GET\n{}\n{}\nhost:example.amazonaws.com\nx-amz-date:{AMZ_DATE}\n\nhost;x-amz-date\n{EMPTY_SHA256}",
+            canonical_uri(&url, "service"),
+            canonical_query(&url),
+        );
+        let signature = sigv4_signature(SECRET, AMZ_DATE, SCOPE, &canonical_request);
+        (canonical_request, signature)
+    }
+
+    #[test]
+    fn test_sigv4_get_vanilla() {
+        let (canonical_request, signature) = suite_signature("");
+        assert_eq!(
+            canonical_request,
+            format!("GET\n/\n\nhost:example.amazonaws.com\nx-amz-date:{AMZ_DATE}\n\nhost;x-amz-date\n{EMPTY_SHA256}"),
+        );
+        assert_eq!(
+            signature,
+            "5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+    }
+
+    #[test]
+    fn test_sigv4_get_vanilla_query_order_key_case() {
+        let (_, signature) = suite_signature("?Param2=value2&Param1=value1");
+        assert_eq!(
+            signature,
+            "b97d918cfa904a5beff61c982a1b6f458b799221646efd99d3219ec94cdf2500"
+        );
+    }
+
+    #[test]
+    fn test_sigv4_get_vanilla_query_order_value() {
+        let (_, signature) = suite_signature("?Param1=value2&Param1=Value1");
+        assert_eq!(
+            signature,
+            "eedbc4e291e521cf13422ffca22be7d2eb8146eecf653089df300a15b2382bd1"
+        );
+    }
+
+    #[test]
+    fn test_sigv4_get_vanilla_query_unreserved() {
+        let unreserved = "-._~0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+        let (_, signature) = suite_signature(&format!("?{unreserved}={unreserved}"));
+        assert_eq!(
+            signature,
+            "9c3e54bfcdf0b19771a7f523ee5669cdf59bc7cc0884027167c21bb143a40197"
+        );
+    }
+
+    #[test]
+    fn test_canonical_uri() {
+        // The suite's get-space and get-utf8 paths, which S3 encodes once.
+        let url = Url::parse("https://example.amazonaws.com/example space/").unwrap();
+        assert_eq!(canonical_uri(&url, "s3"), "/example%20space/");
+        assert_eq!(canonical_uri(&url, "service"), "/example%2520space/");
+        let url = Url::parse("https://example.amazonaws.com/\u{1234}").unwrap();
+        assert_eq!(canonical_uri(&url, "s3"), "/%E1%88%B4");
+        assert_eq!(canonical_uri(&url, "service"), "/%25E1%2588%25B4");
+    }
+
+    #[test]
+    fn test_canonical_query() {
+        let url = Url::parse("https://example.com/?b=c+d&a=x%20y&e&f=g=h&&").unwrap();
+        assert_eq!(canonical_query(&url), "a=x%20y&b=c%2Bd&e=&f=g%3Dh");
+    }
+
+    #[test]
+    fn test_sigv4_plus_in_query() {
+        let plan = SignPlanOutput {
+            kind: SignKind::AwsSigv4,
+            access_key: Some("AKIDEXAMPLE".to_owned()),
+            secret_key: Some(Secret(SECRET.to_owned())),
+            session_token: None,
+            region: Some("us-east-1".to_owned()),
+            service: Some("service".to_owned()),
+            algorithm: None,
+            components: None,
+            separator: None,
+            encoding: None,
+            header: None,
+            prefix: None,
+        };
+        let url = Url::parse("https://example.amazonaws.com/?Param1=value1+value2").unwrap();
+        let now = "2015-08-30T12:36:00Z".parse().unwrap();
+        let mut headers = Vec::new();
+        sigv4(&plan, b"GET", &url, &mut headers, b"", now).unwrap();
+        assert_eq!(
+            find_header(&headers, "authorization"),
+            Some(
+                format!(
+                    "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/{SCOPE}, \
+                     SignedHeaders=host;x-amz-content-sha256;x-amz-date, \
+                     Signature=0960c0a3b05c7275328fdb9630117e900333e9488bc8e2d611b65b2f375ed60a"
+                )
+                .as_bytes()
+            ),
+        );
+    }
+}
@@ -0,0 +1,110 @@
+//! Similarity hashes of response bodies, which stay close when bodies differ slightly so
+//! responses can be clustered or compared to a baseline without keeping the bodies.
+
+/// A 64 bit simhash of the body's alphanumeric words, in hex. Similar bodies have hashes that
+/// differ in few bits.
+pub(super) fn simhash(body: &[u8]) -> String {
+    let mut weights = [0i64; 64];
+    for word in body
+        .split(|b| !b.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+    {
+        let hash = fnv1a(word);
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            if (hash >> bit) & 1 == 1 {
+                *weight += 1;
+            } else {
+                *weight -= 1;
+            }
+        }
+    }
+    let hash = weights
+        .iter()
+        .enumerate()
+        .filter(|(_, weight)| **weight > 0)
+        .fold(0u64, |hash, (bit, _)| hash | (1 << bit));
+    format!("{hash:016x}")
+}
+
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(0x100000001b3)
+    })
+}
+
+const ROLLING_WINDOW: usize = 7;
+const MIN_BLOCK_SIZE: usize = 3;
+const SIGNATURE_LEN: usize = 64;
+const HASH_INIT: u32 = 0x28021967;
+const HASH_PRIME: u32 = 0x01000193;
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// A context triggered piecewise hash in ssdeep's BLOCKSIZE:HASH:HASH format, from the original
+/// spamsum algorithm.
+pub(super) fn ssdeep(body: &[u8]) -> String {
+    let mut block_size = MIN_BLOCK_SIZE;
+    while block_size * SIGNATURE_LEN < body.len() {
+        block_size *= 2;
+    }
+    loop {
+        let mut roll = RollingHash::default();
+        let (mut h1, mut h2) = (HASH_INIT, HASH_INIT);
+        let (mut sig1, mut sig2) = (String::new(), String::new());
+        for b in body {
+            h1 = h1.wrapping_mul(HASH_PRIME) ^ u32::from(*b);
+            h2 = h2.wrapping_mul(HASH_PRIME) ^ u32::from(*b);
+            let rolled = roll.update(*b) as usize;
+            if rolled % block_size != block_size - 1 {
+                continue;
+            }
+            if sig1.len() < SIGNATURE_LEN - 1 {
+                sig1.push(char::from(ALPHABET[h1 as usize % 64]));
+                h1 = HASH_INIT;
+            }
+            let double = block_size * 2;
+            if rolled % double == double - 1 && sig2.len() < SIGNATURE_LEN / 2 - 1 {
+                sig2.push(char::from(ALPHABET[h2 as usize % 64]));
+                h2 = HASH_INIT;
+            }
+        }
+        if h1 != 0 {
+            sig1.push(char::from(ALPHABET[h1 as usize % 64]));
+        }
+        if h2 != 0 {
+            sig2.push(char::from(ALPHABET[h2 as usize % 64]));
+        }
+        // Retry with a smaller block size if too few pieces were triggered.
+        if block_size > MIN_BLOCK_SIZE && sig1.len() < SIGNATURE_LEN / 2 {
+            block_size /= 2;
+            continue;
+        }
+        return format!("{block_size}:{sig1}:{sig2}");
+    }
+}
+
+#[derive(Default)]
+struct RollingHash {
+    window: [u8; ROLLING_WINDOW],
+    n: usize,
+    h1: u32,
+    h2: u32,
+    h3: u32,
+}
+
+impl RollingHash {
+    fn update(&mut self, b: u8) -> u32 {
+        let b = u32::from(b);
+        self.h2 = self
+            .h2
+            .wrapping_sub(self.h1)
+            .wrapping_add(ROLLING_WINDOW as u32 * b);
+        self.h1 = self
+            .h1
+            .wrapping_add(b)
+            .wrapping_sub(u32::from(self.window[self.n % ROLLING_WINDOW]));
+        self.window[self.n % ROLLING_WINDOW] = b as u8;
+        self.n += 1;
+        self.h3 = (self.h3 << 5) ^ b;
+        self.h1.wrapping_add(self.h2).wrapping_add(self.h3)
+    }
+}
@@ -0,0 +1,299 @@
+use std::fmt::Debug;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures::future::BoxFuture;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// A bidirectional byte stream returned by a [`SocketProvider`].
+pub trait Stream: AsyncRead + AsyncWrite + Debug + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Debug + Send + Unpin> Stream for T {}
+
+pub type BoxStream = Box<dyn Stream>;
+
+/// A connected datagram socket returned by a [`SocketProvider`].
+pub trait Datagram: Debug + Send + Sync {
+    fn local_addr(&self) -> io::Result<SocketAddr>;
+
+    /// Waits until a datagram can be sent without blocking.
+    fn poll_send_ready(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>>;
+
+    /// Sends a single datagram to the connected peer.
+    fn poll_send(&self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>>;
+
+    /// Receives a single datagram from the connected peer, truncating it if buf is too small.
+    fn poll_recv(&self, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>>;
+}
+
+impl dyn Datagram {
+    pub async fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        std::future::poll_fn(|cx| self.poll_send(cx, buf)).await
+    }
+
+    pub async fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        std::future::poll_fn(|cx| {
+            let mut read = ReadBuf::new(&mut *buf);
+            match self.poll_recv(cx, &mut read) {
+                Poll::Ready(Ok(())) => Poll::Ready(Ok(read.filled().len())),
+                Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+                Poll::Pending => Poll::Pending,
+            }
+        })
+        .await
+    }
+}
+
+pub type BoxDatagram = Box<dyn Datagram>;
+
+/// Creates the connections and resolves the hosts used by the runners. Hosts without native
+/// sockets, like wasm32-wasi plugin runtimes or browsers, can build without the native feature and
+/// implement this to supply their own transport.
+pub trait SocketProvider: Debug + Send + Sync {
+    fn connect_tcp(
+        &self,
+        local_addr: SocketAddr,
+        remote_addr: SocketAddr,
+    ) -> BoxFuture<'static, io::Result<BoxStream>>;
+
+    /// Like connect_tcp, but applies socket options to the connection. Providers which can't
+    /// set socket options ignore them.
+    fn connect_tcp_with_options(
+        &self,
+        local_addr: SocketAddr,
+        remote_addr: SocketAddr,
+        options: SocketOptions,
+    ) -> BoxFuture<'static, io::Result<BoxStream>> {
+        let _ = options;
+        self.connect_tcp(local_addr, remote_addr)
+    }
+
+    /// Opens a UDP socket bound to local_addr and connected to remote_addr, for DNS and QUIC.
+    /// Providers without datagram support can leave this unimplemented.
+    fn connect_udp(
+        &self,
+        local_addr: SocketAddr,
+        remote_addr: SocketAddr,
+    ) -> BoxFuture<'static, io::Result<BoxDatagram>> {
+        let _ = local_addr;
+        Box::pin(async move {
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("socket provider can't send datagrams to {remote_addr}"),
+            ))
+        })
+    }
+
+    /// Resolves a host name to the addresses connections to it should use. Providers without a
+    /// resolver can leave this unimplemented, in which case only IP literals and hosts looked up
+    /// through an egress profile's DNS server can be reached.
+    fn lookup_host(
+        &self,
+        host: String,
+        port: u16,
+    ) -> BoxFuture<'static, io::Result<Vec<SocketAddr>>> {
+        Box::pin(async move {
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("socket provider can't resolve {host}:{port}"),
+            ))
+        })
+    }
+
+    /// Whether the raw_tcp layer should open raw sockets to record segments. Providers which don't
+    /// connect over a real network should return false.
+    fn capture_raw_tcp(&self) -> bool {
+        true
+    }
+}
+
+/// Socket options requested by a step.
+#[derive(Debug, Default, Clone)]
+pub struct SocketOptions {
+    /// Disable quick ACKs so the peer sees delayed acknowledgements. Only supported on Linux,
+    /// where the kernel may still re-enable them.
+    pub delay_acks: bool,
+    /// If this is set to true by the time the connection is dropped, close it with a TCP reset
+    /// instead of a FIN.
+    pub reset_on_close: Option<Arc<AtomicBool>>,
+    /// The step asked for this local port. Bind it with SO_REUSEADDR so later steps can reuse it
+    /// while earlier connections from it linger in TIME_WAIT, and fail instead of connecting from
+    /// another port if it can't be bound.
+    pub fixed_local_port: bool,
+}
+
+/// Connects using tokio's native sockets.
+#[cfg(feature = "native")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TokioSocketProvider;
+
+#[cfg(feature = "native")]
+impl SocketProvider for TokioSocketProvider {
+    fn connect_tcp(
+        &self,
+        local_addr: SocketAddr,
+        remote_addr: SocketAddr,
+    ) -> BoxFuture<'static, io::Result<BoxStream>> {
+        self.connect_tcp_with_options(local_addr, remote_addr, SocketOptions::default())
+    }
+
+    fn connect_tcp_with_options(
+        &self,
+        local_addr: SocketAddr,
+        remote_addr: SocketAddr,
+        options: SocketOptions,
+    ) -> BoxFuture<'static, io::Result<BoxStream>> {
+        Box::pin(async move {
+            let socket = if remote_addr.is_ipv4() {
+                tokio::net::TcpSocket::new_v4()
+            } else {
+                tokio::net::TcpSocket::new_v6()
+            }?;
+            if options.fixed_local_port {
+                socket.set_reuseaddr(true)?;
+                socket.bind(local_addr)?;
+            } else {
+                // The raw tcp runner may still hold the exact local address, so binding is best
+                // effort.
+                let _ = socket.bind(local_addr);
+            }
+            let stream = socket.connect(remote_addr).await?;
+            #[cfg(target_os = "linux")]
+            if options.delay_acks {
+                socket2::SockRef::from(&stream).set_quickack(false)?;
+            }
+            if let Some(reset) = options.reset_on_close {
+                return Ok(Box::new(ResetOnDrop { stream, reset }) as BoxStream);
+            }
+            Ok(Box::new(stream) as BoxStream)
+        })
+    }
+
+    fn connect_udp(
+        &self,
+        local_addr: SocketAddr,
+        remote_addr: SocketAddr,
+    ) -> BoxFuture<'static, io::Result<BoxDatagram>> {
+        Box::pin(async move {
+            let socket = tokio::net::UdpSocket::bind(local_addr).await?;
+            socket.connect(remote_addr).await?;
+            Ok(Box::new(socket) as BoxDatagram)
+        })
+    }
+
+    fn lookup_host(
+        &self,
+        host: String,
+        port: u16,
+    ) -> BoxFuture<'static, io::Result<Vec<SocketAddr>>> {
+        Box::pin(async move {
+            Ok(tokio::net::lookup_host((host.as_str(), port))
+                .await?
+                .collect())
+        })
+    }
+}
+
+#[cfg(feature = "native")]
+impl Datagram for tokio::net::UdpSocket {
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        tokio::net::UdpSocket::local_addr(self)
+    }
+
+    fn poll_send_ready(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        tokio::net::UdpSocket::poll_send_ready(self, cx)
+    }
+
+    fn poll_send(&self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        tokio::net::UdpSocket::poll_send(self, cx, buf)
+    }
+
+    fn poll_recv(&self, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        tokio::net::UdpSocket::poll_recv(self, cx, buf)
+    }
+}
+
+/// Sets a zero linger time before the socket closes if asked to, so the kernel sends a RST.
+#[cfg(feature = "native")]
+#[derive(Debug)]
+struct ResetOnDrop {
+    stream: tokio::net::TcpStream,
+    reset: Arc<AtomicBool>,
+}
+
+#[cfg(feature = "native")]
+impl Drop for ResetOnDrop {
+    fn drop(&mut self) {
+        if self.reset.load(std::sync::atomic::Ordering::Relaxed) {
+            let _ =
+                socket2::SockRef::from(&self.stream).set_linger(Some(std::time::Duration::ZERO));
+        }
+    }
+}
+
+#[cfg(feature = "native")]
+impl AsyncRead for ResetOnDrop {
+    fn poll_read(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<io::Result<()>> {
+        std::pin::Pin::new(&mut self.stream).poll_read(cx, buf)
+    }
+}
+
+#[cfg(feature = "native")]
+impl AsyncWrite for ResetOnDrop {
+    fn poll_write(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> std::task::Poll<io::Result<usize>> {
+        std::pin::Pin::new(&mut self.stream).poll_write(cx, buf)
+    }
+    fn poll_flush(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<io::Result<()>> {
+        std::pin::Pin::new(&mut self.stream).poll_flush(cx)
+    }
+    fn poll_shutdown(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<io::Result<()>> {
+        std::pin::Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+}
+
+/// Returns the provider used when the executor isn't given one explicitly.
+pub(super) fn default_provider() -> std::sync::Arc<dyn SocketProvider> {
+    #[cfg(feature = "native")]
+    return std::sync::Arc::new(TokioSocketProvider);
+    #[cfg(not(feature = "native"))]
+    return std::sync::Arc::new(UnsupportedSocketProvider);
+}
+
+/// Fails every connection. Used in builds without the native feature when the host hasn't supplied
+/// a provider.
+#[cfg(not(feature = "native"))]
+#[derive(Debug, Default, Clone, Copy)]
+struct UnsupportedSocketProvider;
+
+#[cfg(not(feature = "native"))]
+impl SocketProvider for UnsupportedSocketProvider {
+    fn connect_tcp(
+        &self,
+        _: SocketAddr,
+        remote_addr: SocketAddr,
+    ) -> BoxFuture<'static, io::Result<BoxStream>> {
+        Box::pin(async move {
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("no socket provider configured to connect to {remote_addr}"),
+            ))
+        })
+    }
+}
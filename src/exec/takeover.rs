@@ -0,0 +1,248 @@
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use url::Url;
+
+use crate::{
+    IterableKey, JobOutput, TakeoverFindingOutput, TakeoverHostOutput, TakeoverOutput,
+    TakeoverPlanOutput,
+};
+
+use super::crawl::fetch;
+use super::dns::{self, RecordData};
+use super::Context;
+
+const DNS_TIMEOUT: Duration = Duration::from_secs(5);
+/// Resolvers include the whole chain in their answer, but stop following it eventually in case
+/// they don't.
+const MAX_CHAIN: usize = 10;
+
+struct Fingerprint {
+    service: &'static str,
+    /// CNAME target suffixes belonging to the service.
+    suffixes: &'static [&'static str],
+    /// Response body text shown for unclaimed resources.
+    body: Option<&'static str>,
+    /// Whether a target that doesn't resolve can be registered by anyone.
+    nxdomain: bool,
+}
+
+const FINGERPRINTS: &[Fingerprint] = &[
+    Fingerprint {
+        service: "github_pages",
+        suffixes: &[".github.io"],
+        body: Some("There isn't a GitHub Pages site here."),
+        nxdomain: false,
+    },
+    Fingerprint {
+        service: "heroku",
+        suffixes: &[".herokuapp.com", ".herokudns.com"],
+        body: Some("No such app"),
+        nxdomain: false,
+    },
+    Fingerprint {
+        service: "aws_s3",
+        suffixes: &[".s3.amazonaws.com", ".s3-website.amazonaws.com"],
+        body: Some("NoSuchBucket"),
+        nxdomain: false,
+    },
+    Fingerprint {
+        service: "azure",
+        suffixes: &[
+            ".azurewebsites.net",
+            ".cloudapp.net",
+            ".cloudapp.azure.com",
+            ".trafficmanager.net",
+            ".blob.core.windows.net",
+            ".azureedge.net",
+        ],
+        body: None,
+        nxdomain: true,
+    },
+    Fingerprint {
+        service: "shopify",
+        suffixes: &[".myshopify.com"],
+        body: Some("Sorry, this shop is currently unavailable."),
+        nxdomain: false,
+    },
+    Fingerprint {
+        service: "fastly",
+        suffixes: &[".fastly.net"],
+        body: Some("Fastly error: unknown domain"),
+        nxdomain: false,
+    },
+    Fingerprint {
+        service: "pantheon",
+        suffixes: &[".pantheonsite.io"],
+        body: Some("The gods are wise, but do not know of the site which you seek."),
+        nxdomain: false,
+    },
+    Fingerprint {
+        service: "tumblr",
+        suffixes: &[".domains.tumblr.com"],
+        body: Some("Whatever you were looking for doesn't currently exist at this address."),
+        nxdomain: false,
+    },
+    Fingerprint {
+        service: "surge",
+        suffixes: &[".surge.sh"],
+        body: Some("project not found"),
+        nxdomain: false,
+    },
+    Fingerprint {
+        service: "bitbucket",
+        suffixes: &[".bitbucket.io"],
+        body: Some("Repository not found"),
+        nxdomain: false,
+    },
+    Fingerprint {
+        service: "zendesk",
+        suffixes: &[".zendesk.com"],
+        body: Some("Help Center Closed"),
+        nxdomain: false,
+    },
+    Fingerprint {
+        service: "readme",
+        suffixes: &[".readme.io"],
+        body: Some("Project doesnt exist... yet!"),
+        nxdomain: false,
+    },
+];
+
+/// Resolve each host's CNAME chain and check targets on known services for signs they're
+/// unclaimed. Returns the results and the job for each fingerprint request.
+pub(super) async fn takeover(
+    ctx: &Context,
+    plan: TakeoverPlanOutput,
+) -> anyhow::Result<(TakeoverOutput, Vec<(IterableKey, Arc<JobOutput>)>)> {
+    let resolver = match &plan.resolver {
+        Some(addr) => addr
+            .parse::<SocketAddr>()
+            .or_else(|_| addr.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, 53)))
+            .map_err(|_| anyhow!("takeover.resolver {addr:?} is not an IP address"))?,
+        None => ctx
+            .egress
+            .as_ref()
+            .and_then(|egress| egress.resolver)
+            .unwrap_or_else(dns::system_resolver),
+    };
+    let mut out = TakeoverOutput {
+        resolver: resolver.to_string(),
+        hosts: Vec::new(),
+        findings: Vec::new(),
+        plan,
+    };
+    let mut jobs = Vec::new();
+
+    for host in out.plan.hosts.clone() {
+        let mut result = TakeoverHostOutput {
+            host: host.clone(),
+            chain: Vec::new(),
+            nxdomain: false,
+            addresses: Vec::new(),
+            service: None,
+            status_code: None,
+            error: None,
+        };
+        if let Err(e) = resolve(ctx, resolver, &mut result).await {
+            result.error = Some(format!("{e:#}"));
+            out.hosts.push(result);
+            continue;
+        }
+        // Hosts that aren't aliases can't dangle.
+        let Some(target) = result.chain.last().cloned() else {
+            out.hosts.push(result);
+            continue;
+        };
+        let fingerprint = FINGERPRINTS
+            .iter()
+            .find(|f| f.suffixes.iter().any(|s| target.ends_with(s)));
+        result.service = fingerprint.map(|f| f.service.to_owned());
+
+        let mut evidence = None;
+        if result.nxdomain {
+            evidence = Some(match fingerprint {
+                Some(f) if f.nxdomain => format!("{target} does not exist and can be registered"),
+                _ => format!("{target} does not exist"),
+            });
+        } else if let Some((f, body)) = fingerprint.and_then(|f| Some((f, f.body?))) {
+            let key = IterableKey::Uint(jobs.len().try_into().unwrap_or(u64::MAX));
+            let mut job_name = ctx.job_name.clone();
+            job_name.job = key.clone();
+            let url = Url::parse(&format!("http://{host}/"))?;
+            match fetch(ctx, url, job_name).await {
+                Ok(job) => {
+                    let (status, response) = job.response_summary();
+                    result.status_code = status;
+                    if String::from_utf8_lossy(response).contains(body) {
+                        evidence = Some(format!("response contains {} text {body:?}", f.service));
+                    }
+                    jobs.push((key, Arc::new(job)));
+                }
+                Err(e) => result.error = Some(format!("{e:#}")),
+            }
+        }
+        if let Some(evidence) = evidence {
+            out.findings.push(TakeoverFindingOutput {
+                host,
+                target,
+                service: result
+                    .service
+                    .clone()
+                    .unwrap_or_else(|| "unknown".to_owned()),
+                evidence,
+            });
+        }
+        out.hosts.push(result);
+    }
+    Ok((out, jobs))
+}
+
+/// Follow the host's CNAME records, recording the chain, whether the final name exists, and its
+/// addresses.
+async fn resolve(
+    ctx: &Context,
+    resolver: SocketAddr,
+    result: &mut TakeoverHostOutput,
+) -> anyhow::Result<()> {
+    let mut name = result.host.trim_end_matches('.').to_ascii_lowercase();
+    while result.chain.len() < MAX_CHAIN {
+        let response = dns::query(ctx, resolver, &name, dns::TYPE_A, DNS_TIMEOUT).await?;
+        result.nxdomain = response.rcode == dns::RCODE_NXDOMAIN;
+        let mut next = None;
+        // Walk the answer from the queried name so unrelated records are ignored.
+        let mut current = name.clone();
+        loop {
+            let cname = response.answers.iter().find_map(|r| match &r.data {
+                RecordData::Cname(target) if r.name == current => Some(target.clone()),
+                _ => None,
+            });
+            let Some(target) = cname else {
+                break;
+            };
+            result.chain.push(target.clone());
+            current = target.clone();
+            next = Some(target);
+            if result.chain.len() >= MAX_CHAIN {
+                break;
+            }
+        }
+        result.addresses = response
+            .answers
+            .iter()
+            .filter(|r| r.name == current)
+            .filter_map(|r| match r.data {
+                RecordData::Address(ip) => Some(ip.to_string()),
+                _ => None,
+            })
+            .collect();
+        // Stop unless the resolver left the end of the chain unresolved.
+        match next {
+            Some(target) if result.addresses.is_empty() && !result.nxdomain => name = target,
+            _ => break,
+        }
+    }
+    Ok(())
+}
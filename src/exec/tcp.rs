@@ -1,3 +1,4 @@
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::task::{ready, Poll};
 use std::time::Instant;
@@ -9,7 +10,6 @@ use cel_interpreter::Duration;
 use chrono::TimeDelta;
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufWriter};
 use tokio::io::{ReadHalf, WriteHalf};
-use tokio::net::{TcpSocket, TcpStream};
 use tokio::spawn;
 
 use crate::{
@@ -17,8 +17,13 @@ use crate::{
     TcpReceivedOutput, TcpSentOutput,
 };
 
+use super::expect::{self, ExpectReader};
+use super::fault::FaultStream;
+use super::network;
 use super::pause::{PauseReader, PauseSpec, PauseWriter};
+use super::proxy;
 use super::raw_tcp::RawTcpRunner;
+use super::socket::{BoxStream, SocketOptions};
 use super::tee::{self, TeeReader, TeeWriter};
 use super::timing::{TimingReader, TimingWriter};
 use super::{Context, Error};
@@ -30,6 +35,7 @@ pub(super) struct TcpRunner {
     state: State,
     size_hint: Option<usize>,
     reader: Option<TcpRunnerReader>,
+    reset: Arc<AtomicBool>,
 }
 
 #[derive(Debug)]
@@ -37,7 +43,7 @@ pub enum State {
     Pending,
     Open {
         start: Instant,
-        writer: PauseWriter<BufWriter<TeeWriter<TimingWriter<WriteHalf<TcpStream>>>>>,
+        writer: PauseWriter<BufWriter<TeeWriter<TimingWriter<WriteHalf<BoxStream>>>>>,
         size_hint: Option<usize>,
         raw: RawTcpRunner,
     },
@@ -59,12 +65,21 @@ impl TcpRunner {
                 errors: Vec::new(),
                 duration: TimeDelta::zero().into(),
                 handshake_duration: None,
+                proxies: Vec::new(),
+                proxy_protocol_header: None,
+                expect: Vec::new(),
             },
             ctx,
             size_hint: None,
+            reset: Arc::new(AtomicBool::new(false)),
         }
     }
 
+    /// Set to true to close the connection with a TCP reset once it's dropped.
+    pub(super) fn reset_on_close(&self) -> Arc<AtomicBool> {
+        self.reset.clone()
+    }
+
     pub fn size_hint(&mut self, hint: Option<usize>) -> Option<usize> {
         self.size_hint = hint;
         None
@@ -93,16 +108,21 @@ impl TcpRunner {
             time_to_last_byte: None,
         }));
 
+        // The step's own faults replace any from its network profile.
+        let fault = self.out.plan.fault.clone().or_else(|| {
+            self.ctx
+                .network
+                .as_ref()
+                .and_then(|network| network.fault.clone())
+        });
+        let options = SocketOptions {
+            delay_acks: fault.as_ref().is_some_and(|f| f.delay_acks),
+            reset_on_close: Some(self.reset.clone()),
+            fixed_local_port: raw.fixed_src_port(),
+        };
         let start = Instant::now();
-        let socket = TcpSocket::new_v4().inspect_err(|e| {
-            self.out.errors.push(TcpError {
-                kind: e.kind().to_string(),
-                message: e.to_string(),
-            });
-            self.state = State::Completed;
-        })?;
-        socket.bind(local_addr);
-        let transport = match socket.connect(remote_addr).await {
+        let connect = network::connect(&self.ctx, local_addr, remote_addr, options);
+        let mut transport = match connect.await {
             Ok(t) => t,
             Err(e) => {
                 self.out.errors.push(TcpError {
@@ -113,8 +133,77 @@ impl TcpRunner {
                 bail!("connect to {remote_addr}: {e}");
             }
         };
+        // Ask each proxy hop to connect to the next, or to the step's destination for the last.
+        // The egress profile's proxy, if any, is always the first hop.
+        let hops: Vec<_> = self
+            .ctx
+            .egress
+            .as_ref()
+            .and_then(|egress| egress.plan.proxy.clone())
+            .into_iter()
+            .chain(self.out.plan.proxies.iter().cloned())
+            .collect();
+        for (i, hop) in hops.iter().enumerate() {
+            let (target_host, target_port) = hops
+                .get(i + 1)
+                .map(|next| (next.host.as_str(), next.port))
+                .unwrap_or((self.out.plan.host.as_str(), self.out.plan.port));
+            // The proxy resolves the target, so the scope can only check it by name.
+            if let Some(Err(e)) = self
+                .ctx
+                .scope
+                .as_ref()
+                .map(|s| s.check_proxied(target_host))
+            {
+                self.out.errors.push(TcpError {
+                    kind: "scope".to_owned(),
+                    message: e.to_string(),
+                });
+                self.state = State::Completed;
+                return Err(e);
+            }
+            let out = proxy::handshake(&mut transport, hop, target_host, target_port).await;
+            let error = out.error.clone();
+            self.out.proxies.push(out);
+            if let Some(message) = error {
+                self.out.errors.push(TcpError {
+                    kind: "proxy".to_owned(),
+                    message: message.clone(),
+                });
+                self.state = State::Completed;
+                bail!("proxy {}:{}: {message}", hop.host, hop.port);
+            }
+        }
+        if let Some(plan) = &self.out.plan.proxy_protocol {
+            let header = match proxy::proxy_protocol_header(plan, remote_addr) {
+                Ok(header) => header,
+                Err(e) => {
+                    self.out.errors.push(TcpError {
+                        kind: "proxy_protocol".to_owned(),
+                        message: e.to_string(),
+                    });
+                    self.state = State::Completed;
+                    return Err(e);
+                }
+            };
+            // Written directly to the stream so it isn't counted as part of the sent body.
+            if let Err(e) = transport.write_all(&header).await {
+                self.out.errors.push(TcpError {
+                    kind: e.kind().to_string(),
+                    message: e.to_string(),
+                });
+                self.state = State::Completed;
+                bail!("send proxy protocol header: {e}");
+            }
+            self.out.proxy_protocol_header = Some(MaybeUtf8(Bytes::from(header).into()));
+        }
+        // Faults only apply to the body, so handshakes with proxies still succeed.
+        if let Some(fault) = fault {
+            transport = Box::new(FaultStream::new(transport, fault));
+        }
         let (reader, writer) = tokio::io::split(transport);
 
+        let reader = ExpectReader::new(reader, start, &self.out.plan.expect, self.reset.clone());
         let tee_reader = TeeReader::new(TimingReader::new(reader));
         //if let Some(limit) = self.out.plan.close.bytes {
         //    tee_reader.set_read_limit(limit.try_into()?);
@@ -223,11 +312,14 @@ impl TcpRunner {
             });
         }
         //is_done.cancelled().await;
-        if let Err(e) = &self.shutdown().await {
-            self.out.errors.push(TcpError {
-                kind: e.kind().to_string(),
-                message: e.to_string(),
-            });
+        // A reset was asked for instead of a FIN, so leave the write half open until it's dropped.
+        if !self.reset.load(Ordering::Relaxed) {
+            if let Err(e) = &self.shutdown().await {
+                self.out.errors.push(TcpError {
+                    kind: e.kind().to_string(),
+                    message: e.to_string(),
+                });
+            }
         }
         let (reader, read_result) = handle.await.expect("tcp reader should not panic");
         if let Err(e) = read_result {
@@ -311,6 +403,14 @@ impl TcpRunner {
                     .map(Duration),
             }));
         }
+        let (_, outcomes) = reader.into_inner().into_parts();
+        self.out.errors.extend(
+            expect::failures(&self.out.plan.expect, &outcomes).map(|message| TcpError {
+                kind: "expectation failed".to_owned(),
+                message,
+            }),
+        );
+        self.out.expect = outcomes;
         self.out.duration = TimeDelta::from_std(end_time - start).unwrap().into();
         self.state = State::Completed;
         (self.out, raw)
@@ -392,13 +492,13 @@ impl AsyncWrite for TcpRunner {
 
 #[derive(Debug)]
 struct TcpRunnerReader {
-    inner: PauseReader<TeeReader<TimingReader<ReadHalf<TcpStream>>>>,
+    inner: PauseReader<TeeReader<TimingReader<ExpectReader<ReadHalf<BoxStream>>>>>,
     recv_max_reached: bool,
     timed_out: bool,
 }
 
 impl TcpRunnerReader {
-    fn new(inner: PauseReader<TeeReader<TimingReader<ReadHalf<TcpStream>>>>) -> Self {
+    fn new(inner: PauseReader<TeeReader<TimingReader<ExpectReader<ReadHalf<BoxStream>>>>>) -> Self {
         Self {
             inner,
             recv_max_reached: false,
@@ -0,0 +1,129 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Instant;
+
+use anyhow::{anyhow, bail};
+use chrono::TimeDelta;
+use futures::future::join_all;
+
+use crate::{BurstConnectionOutput, TcpBurstOutput, TcpBurstPlanOutput};
+
+use super::{dns, Context};
+
+/// The most connections a burst may open, to stay within typical file descriptor limits.
+const MAX_CONNECTIONS: u64 = 10_000;
+
+/// Open every connection at once and record how long each took to be established.
+///
+/// The address is resolved and every connection is requested from the socket provider before any
+/// of them is polled, so the attempts start as close together as a single task can poll them.
+/// Network profiles don't apply, since they would spread the attempts out.
+pub(super) async fn tcp_burst(ctx: &Context, plan: TcpBurstPlanOutput) -> TcpBurstOutput {
+    let start = Instant::now();
+    let mut out = TcpBurstOutput {
+        remote_addr: None,
+        connections: Vec::new(),
+        established: 0,
+        failed: 0,
+        spread: None,
+        connect_min: None,
+        connect_p50: None,
+        connect_p90: None,
+        connect_p99: None,
+        connect_max: None,
+        error: None,
+        duration: TimeDelta::zero().into(),
+        plan,
+    };
+    if let Err(e) = burst(ctx, &mut out).await {
+        out.error = Some(format!("{e:#}"));
+    }
+    out.duration = to_duration(start.elapsed());
+    out
+}
+
+async fn burst(ctx: &Context, out: &mut TcpBurstOutput) -> anyhow::Result<()> {
+    if out.plan.connections > MAX_CONNECTIONS {
+        bail!("tcp_burst.connections must be at most {MAX_CONNECTIONS}");
+    }
+    let timeout = out.plan.timeout.0.to_std().unwrap_or_default();
+    let remote = dns::lookup(ctx, &out.plan.host, out.plan.port)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("no addresses found for '{}'", out.plan.host))?;
+    out.remote_addr = Some(remote.to_string());
+    let local = ctx
+        .egress
+        .as_ref()
+        .and_then(|egress| egress.local_addr(remote))
+        .unwrap_or_else(|| {
+            if remote.is_ipv4() {
+                SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0)
+            } else {
+                SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0)
+            }
+        });
+
+    // The whole burst counts as one connection against the egress rate limit.
+    if let Some(egress) = &ctx.egress {
+        egress.acquire().await;
+    }
+    let burst_start = Instant::now();
+    let attempts = (0..out.plan.connections).map(|_| {
+        let connect = ctx.sockets.connect_tcp(local, remote);
+        async move {
+            let started = Instant::now();
+            let result = tokio::time::timeout(timeout, connect).await;
+            let connect_duration = started.elapsed();
+            match result {
+                Ok(Ok(stream)) => (started, Ok((stream, connect_duration))),
+                Ok(Err(e)) => (started, Err(e.to_string())),
+                Err(_) => (started, Err("timed out".to_owned())),
+            }
+        }
+    });
+    let results = join_all(attempts).await;
+
+    let mut durations = Vec::with_capacity(results.len());
+    let mut last_start = burst_start;
+    for (index, (started, result)) in results.into_iter().enumerate() {
+        last_start = last_start.max(started);
+        let mut conn = BurstConnectionOutput {
+            index: index as u64,
+            local_port: None,
+            started_at: to_duration(started.saturating_duration_since(burst_start)),
+            connect_duration: None,
+            error: None,
+        };
+        match result {
+            Ok((_, duration)) => {
+                conn.connect_duration = Some(to_duration(duration));
+                durations.push(duration);
+                out.established += 1;
+            }
+            Err(e) => {
+                conn.error = Some(e);
+                out.failed += 1;
+            }
+        }
+        out.connections.push(conn);
+    }
+    out.spread = Some(to_duration(last_start - burst_start));
+
+    durations.sort();
+    let percentile = |p: usize| {
+        (!durations.is_empty()).then(|| to_duration(durations[(durations.len() - 1) * p / 100]))
+    };
+    out.connect_min = percentile(0);
+    out.connect_p50 = percentile(50);
+    out.connect_p90 = percentile(90);
+    out.connect_p99 = percentile(99);
+    out.connect_max = percentile(100);
+    Ok(())
+}
+
+fn to_duration(duration: std::time::Duration) -> cel_interpreter::Duration {
+    TimeDelta::from_std(duration)
+        .unwrap_or(TimeDelta::MAX)
+        .into()
+}
@@ -1,36 +1,66 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
 use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
 use std::task::Poll;
 use std::time::Instant;
 use std::{pin::pin, sync::Arc};
 
 use anyhow::{anyhow, bail};
+use base64::Engine;
 use bytes::Bytes;
 use chrono::Duration;
 use derivative::Derivative;
-use rustls::pki_types::ServerName;
-use rustls::RootCertStore;
+use itertools::Itertools;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::{
+    ClientSessionMemoryCache, ClientSessionStore, ResolvesClientCert, Resumption,
+    Tls12ClientSessionValue, Tls12Resumption, Tls13ClientSessionValue, WebPkiServerVerifier,
+};
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, WebPkiSupportedAlgorithms};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::sign::CertifiedKey;
+use rustls::{
+    ClientConfig, ConfigBuilder, DigitallySignedStruct, NamedGroup, ProtocolVersion, RootCertStore,
+    SignatureScheme, WantsVerifier,
+};
+use sha2::{Digest, Sha256};
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio_rustls::client::TlsStream;
 use tokio_rustls::TlsConnector;
 
+use super::expect::{self, ExpectReader};
 use super::pause::{self, PauseStream};
 use super::runner::Runner;
 use super::tee::Tee;
 use super::timing::Timing;
-use super::Context;
+use super::{x509, Context};
 use crate::exec::pause::{Pause, PauseSpec};
 use crate::{
-    MaybeUtf8, PduName, ProtocolDiscriminants, ProtocolName, TlsError, TlsOutput, TlsPlanOutput,
-    TlsReceivedOutput, TlsSentOutput, TlsVersion,
+    MaybeUtf8, PduName, ProtocolDiscriminants, ProtocolName, TlsCertificateOutput,
+    TlsClientPlanOutput, TlsError, TlsOutput, TlsPinPlanOutput, TlsPlanOutput, TlsReceivedOutput,
+    TlsResumption, TlsSentOutput, TlsVerification, TlsVersion,
 };
 
+/// Chooses how a step's TLS handshakes verify the server's certificate, for trust decisions
+/// plans can't express like pinning the first certificate seen, accepting any certificate while
+/// recording it, or checking against a corporate CA's policy.
+pub trait VerifierProvider: Debug + Send + Sync {
+    /// The verifier for handshakes in the named step, or None to verify as the step's plan says.
+    fn verifier(&self, step: &str) -> Option<Arc<dyn ServerCertVerifier>>;
+}
+
 #[derive(Debug)]
 pub(super) struct TlsRunner {
     ctx: Arc<Context>,
     out: TlsOutput,
     state: State,
     size_hint: Option<usize>,
+    reset: Arc<AtomicBool>,
+    client_auth: Arc<ClientAuthRecorder>,
+    verifier: Arc<VerifyRecorder>,
 }
 
 #[derive(Derivative)]
@@ -43,7 +73,7 @@ enum State {
     },
     Open {
         start: Instant,
-        transport: PauseStream<Tee<Timing<TlsStream<Runner>>>>,
+        transport: PauseStream<Tee<Timing<ExpectReader<TlsStream<Runner>>>>>,
     },
     Completed {
         transport: Runner,
@@ -55,20 +85,79 @@ enum State {
 }
 
 impl TlsRunner {
-    pub(super) fn new(ctx: Arc<Context>, plan: TlsPlanOutput) -> Self {
-        let root_cert_store = RootCertStore {
-            roots: webpki_roots::TLS_SERVER_ROOTS.into(),
+    pub(super) fn new(ctx: Arc<Context>, plan: TlsPlanOutput) -> crate::Result<Self> {
+        let builder = config_builder(&plan)?;
+        let custom = ctx
+            .verifiers
+            .as_ref()
+            .and_then(|verifiers| verifiers.verifier(&ctx.job_name.step));
+        let (verifier, verification) = if let Some(verifier) = custom {
+            (verifier, TlsVerification::Custom)
+        } else if plan.insecure {
+            let verifier: Arc<dyn ServerCertVerifier> = Arc::new(InsecureVerifier(
+                rustls::crypto::ring::default_provider().signature_verification_algorithms,
+            ));
+            (verifier, TlsVerification::Insecure)
+        } else {
+            let (roots, verification) = root_store(&plan)?;
+            let verifier: Arc<dyn ServerCertVerifier> =
+                WebPkiServerVerifier::builder(Arc::new(roots)).build()?;
+            (verifier, verification)
+        };
+        // Resumed handshakes skip verification, which is how resumption is detected.
+        let verifier = Arc::new(VerifyRecorder {
+            inner: verifier,
+            verified: AtomicBool::new(false),
+        });
+        let builder = builder
+            .dangerous()
+            .with_custom_certificate_verifier(verifier.clone());
+        let mut tls_config = match &plan.client {
+            Some(client) => {
+                let (certs, key) = load_client_cert(client)?;
+                builder.with_client_auth_cert(certs, key)?
+            }
+            None => builder.with_no_client_auth(),
+        };
+        let client_auth = Arc::new(ClientAuthRecorder {
+            inner: tls_config.client_auth_cert_resolver.clone(),
+            requested: AtomicBool::new(false),
+            sent: AtomicBool::new(false),
+        });
+        tls_config.client_auth_cert_resolver = client_auth.clone();
+        let resume = match &plan.resume {
+            Some(step) => Some(
+                ctx.tls_sessions
+                    .get(step)
+                    .ok_or_else(|| anyhow!("tls.resume step {step} has no TLS sessions"))?,
+            ),
+            None => None,
         };
-        let mut tls_config = rustls::ClientConfig::builder()
-            .with_root_certificates(root_cert_store)
-            .with_no_client_auth();
+        let store = SessionStore {
+            save: ctx.tls_sessions.get_or_create(&ctx.job_name.step),
+            resume,
+        };
+        tls_config.resumption =
+            Resumption::store(Arc::new(store)).tls12_resumption(if plan.session_tickets {
+                Tls12Resumption::SessionIdOrTickets
+            } else {
+                Tls12Resumption::SessionIdOnly
+            });
         tls_config.alpn_protocols = plan.alpn.iter().map(|alpn| alpn.to_vec()).collect();
+        let domain = match plan.server_name.as_deref() {
+            Some("") => {
+                tls_config.enable_sni = false;
+                plan.host.clone()
+            }
+            Some(name) => name.to_owned(),
+            None => plan.host.clone(),
+        };
         let connector = tokio_rustls::TlsConnector::from(Arc::new(tls_config));
 
-        TlsRunner {
+        Ok(TlsRunner {
             state: State::Pending {
                 connector,
-                domain: Box::new(plan.host.clone()),
+                domain: Box::new(domain),
             },
             out: TlsOutput {
                 name: ProtocolName::with_job(ctx.job_name.clone(), ProtocolDiscriminants::Tls),
@@ -86,12 +175,30 @@ impl TlsRunner {
                 received: None,
                 errors: Vec::new(),
                 version: None,
+                cipher_suite: None,
+                alpn: None,
+                certificate: None,
+                chain: Vec::new(),
+                client_cert_requested: false,
+                client_cert_sent: false,
+                verification,
+                resumed: false,
+                resumption: None,
+                expect: Vec::new(),
                 duration: Duration::zero().into(),
                 handshake_duration: None,
             },
             size_hint: None,
+            reset: Arc::new(AtomicBool::new(false)),
+            client_auth,
+            verifier,
             ctx,
-        }
+        })
+    }
+
+    /// Set to true to close the underlying connection with a TCP reset once it's dropped.
+    pub(super) fn reset_on_close(&self) -> Arc<AtomicBool> {
+        self.reset.clone()
     }
 
     pub(super) fn size_hint(&mut self, hint: Option<usize>) -> Option<usize> {
@@ -139,14 +246,72 @@ impl TlsRunner {
         //        .start
         //        .push(Pause::new(&self.ctx, p).await?);
         //}
+        // Expectations tear down the transport's connection, if it's one that can be reset.
+        if let Some(reset) = transport.reset_on_close() {
+            self.reset = reset;
+        }
         // Perform the TLS handshake.
-        let connection = match connector.connect(domain, transport).await {
+        let result = connector.connect(domain, transport).into_fallible().await;
+        self.out.client_cert_requested = self.client_auth.requested.load(Ordering::Relaxed);
+        self.out.client_cert_sent = self.client_auth.sent.load(Ordering::Relaxed);
+        let connection = match result {
             Ok(conn) => conn,
-            Err(e) => {
-                panic!("TLS handshake failure: {e}");
+            Err((e, transport)) => {
+                self.out.errors.push(TlsError {
+                    kind: "handshake".to_owned(),
+                    message: e.to_string(),
+                });
+                self.state = State::StartFailed { transport };
+                self.complete();
+                return Err(e.into());
             }
         };
         let handshake_duration = start.elapsed();
+        self.out.resumed = !self.verifier.verified.load(Ordering::Relaxed);
+        self.out.resumption = match connection.get_ref().1.protocol_version() {
+            _ if !self.out.resumed => None,
+            Some(ProtocolVersion::TLSv1_3) => Some(TlsResumption::Ticket),
+            Some(ProtocolVersion::TLSv1_2) if !self.out.plan.session_tickets => {
+                Some(TlsResumption::SessionId)
+            }
+            _ => None,
+        };
+        self.out.alpn = connection
+            .get_ref()
+            .1
+            .alpn_protocol()
+            .map(|alpn| MaybeUtf8(Bytes::copy_from_slice(alpn).into()));
+        let ders = connection
+            .get_ref()
+            .1
+            .peer_certificates()
+            .unwrap_or_default();
+        let certificates: Vec<_> = ders
+            .iter()
+            .filter_map(|der| Some((der, x509::parse(der).ok()?)))
+            .collect();
+        self.out.chain = certificates
+            .iter()
+            .map(|(der, cert)| TlsCertificateOutput {
+                der: der.to_vec().into(),
+                serial: normalize_serial(&hex(cert.serial)),
+                spki_sha256: spki_sha256(cert.spki),
+                subject: cert.subject.clone(),
+                issuer: cert.issuer.clone(),
+                sans: cert.sans.clone(),
+                not_before: cert.not_before.clone(),
+                not_after: cert.not_after.clone(),
+                key_algorithm: cert.key_algorithm.clone(),
+            })
+            .collect();
+        self.out.certificate = self.out.chain.first().cloned();
+        let certificates = certificates.into_iter().map(|(_, cert)| cert).collect_vec();
+        let pin_error = self
+            .out
+            .plan
+            .pin
+            .as_ref()
+            .and_then(|pin| check_pin(pin, &certificates));
         //for p in &self.out.plan.pause.handshake.end {
         //    if p.offset_bytes != 0 {
         //        bail!("pause offset not yet supported for tls handshake");
@@ -166,7 +331,12 @@ impl TlsRunner {
             start,
             transport: pause::new_stream(
                 self.ctx.clone(),
-                Tee::new(Timing::new(connection)),
+                Tee::new(Timing::new(ExpectReader::new(
+                    connection,
+                    start,
+                    &self.out.plan.expect,
+                    self.reset.clone(),
+                ))),
                 // TODO: Implement read size hints.
                 vec![/*PauseSpec {
                     group_offset: 0,
@@ -195,9 +365,21 @@ impl TlsRunner {
                 //},
             ),
         };
+        if let Some(message) = pin_error {
+            self.out.errors.push(TlsError {
+                kind: "pin mismatch".to_owned(),
+                message: message.clone(),
+            });
+            bail!("{message}");
+        }
         Ok(())
     }
 
+    /// The protocol the server selected with ALPN, once the handshake completes.
+    pub fn alpn(&self) -> Option<&MaybeUtf8> {
+        self.out.alpn.as_ref()
+    }
+
     pub fn executor_size_hint(&self) -> Option<usize> {
         Some(self.out.plan.body.len())
     }
@@ -229,11 +411,14 @@ impl TlsRunner {
                 message: e.to_string(),
             });
         }
-        if let Err(e) = writer.shutdown().await {
-            self.out.errors.push(TlsError {
-                kind: "read failure".to_owned(),
-                message: e.to_string(),
-            });
+        // A reset was asked for, so don't send close_notify or a FIN first.
+        if !self.reset.load(Ordering::Relaxed) {
+            if let Err(e) = writer.shutdown().await {
+                self.out.errors.push(TlsError {
+                    kind: "read failure".to_owned(),
+                    message: e.to_string(),
+                });
+            }
         }
         let (reader, read_result) = handle.await.expect("tls reader should not panic");
         if let Err(e) = read_result {
@@ -310,11 +495,23 @@ impl TlsRunner {
         }
         self.out.duration = Duration::from_std(end_time - start).unwrap().into();
 
-        let (inner, conn) = stream.into_inner().into_inner();
+        let (stream, outcomes) = stream.into_inner().into_parts();
+        self.out.errors.extend(
+            expect::failures(&self.out.plan.expect, &outcomes).map(|message| TlsError {
+                kind: "expectation failed".to_owned(),
+                message,
+            }),
+        );
+        self.out.expect = outcomes;
+
+        let (inner, conn) = stream.into_inner();
 
         self.state = State::Completed { transport: inner };
 
         self.out.version = conn.protocol_version().map(TlsVersion::from);
+        self.out.cipher_suite = conn
+            .negotiated_cipher_suite()
+            .map(|suite| format!("{:?}", suite.suite()));
     }
 }
 
@@ -377,3 +574,330 @@ impl AsyncWrite for TlsRunner {
 }
 
 impl Unpin for TlsRunner {}
+
+/// A config builder offering only the versions and cipher suites the plan allows.
+fn config_builder(
+    plan: &TlsPlanOutput,
+) -> anyhow::Result<ConfigBuilder<ClientConfig, WantsVerifier>> {
+    let mut provider = rustls::crypto::ring::default_provider();
+    if !plan.cipher_suites.is_empty() {
+        let suites = plan
+            .cipher_suites
+            .iter()
+            .map(|name| {
+                provider
+                    .cipher_suites
+                    .iter()
+                    .find(|suite| format!("{:?}", suite.suite()).eq_ignore_ascii_case(name))
+                    .copied()
+                    .ok_or_else(|| anyhow!("unsupported tls cipher suite {name}"))
+            })
+            .try_collect()?;
+        provider.cipher_suites = suites;
+    }
+    let min = plan.min_version.as_ref().map_or(0, |version| version.raw);
+    let max = plan
+        .max_version
+        .as_ref()
+        .map_or(u16::MAX, |version| version.raw);
+    let versions = rustls::ALL_VERSIONS
+        .iter()
+        .copied()
+        .filter(|version| (min..=max).contains(&version.version.get_u16()))
+        .collect_vec();
+    if versions.is_empty() {
+        bail!("tls.min_version and tls.max_version exclude tls1.2 and tls1.3");
+    }
+    ClientConfig::builder_with_provider(Arc::new(provider))
+        .with_protocol_versions(&versions)
+        .map_err(|e| anyhow!("tls.cipher_suites has none usable with the allowed versions: {e}"))
+}
+
+/// The built in root certificates plus any the plan adds.
+fn root_store(plan: &TlsPlanOutput) -> anyhow::Result<(RootCertStore, TlsVerification)> {
+    let mut roots = RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.into(),
+    };
+    if plan.ca.is_none() && plan.ca_file.is_none() {
+        return Ok((roots, TlsVerification::Default));
+    }
+    let mut pem = plan.ca.clone().unwrap_or_default().into_bytes();
+    if let Some(path) = &plan.ca_file {
+        pem.push(b'\n');
+        pem.extend(std::fs::read(path).map_err(|e| anyhow!("read tls.ca_file {path}: {e}"))?);
+    }
+    let mut added = 0;
+    for cert in rustls_pemfile::certs(&mut pem.as_slice()) {
+        let cert = cert.map_err(|e| anyhow!("parse tls.ca: {e}"))?;
+        roots
+            .add(cert)
+            .map_err(|e| anyhow!("add tls.ca root certificate: {e}"))?;
+        added += 1;
+    }
+    if added == 0 {
+        bail!("tls.ca and tls.ca_file have no PEM encoded certificates");
+    }
+    Ok((roots, TlsVerification::CustomRoots))
+}
+
+/// Accepts any server certificate, while still checking that the server holds its key.
+#[derive(Debug)]
+struct InsecureVerifier(WebPkiSupportedAlgorithms);
+
+impl ServerCertVerifier for InsecureVerifier {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.0)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.0)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.supported_schemes()
+    }
+}
+
+/// Notes whether the server's certificate was verified, which resumed handshakes skip.
+#[derive(Debug)]
+struct VerifyRecorder {
+    inner: Arc<dyn ServerCertVerifier>,
+    verified: AtomicBool,
+}
+
+impl ServerCertVerifier for VerifyRecorder {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        self.verified.store(true, Ordering::Relaxed);
+        self.inner
+            .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+/// The TLS sessions established by each step, shared by an executor's steps so later ones can
+/// resume them with tls.resume.
+#[derive(Debug, Default)]
+pub(super) struct TlsSessions(Mutex<HashMap<String, Arc<ClientSessionMemoryCache>>>);
+
+impl TlsSessions {
+    /// The number of sessions kept per step.
+    const SIZE: usize = 256;
+
+    fn get(&self, step: &str) -> Option<Arc<ClientSessionMemoryCache>> {
+        self.0.lock().unwrap().get(step).cloned()
+    }
+
+    fn get_or_create(&self, step: &str) -> Arc<ClientSessionMemoryCache> {
+        self.0
+            .lock()
+            .unwrap()
+            .entry(step.to_owned())
+            .or_insert_with(|| Arc::new(ClientSessionMemoryCache::new(Self::SIZE)))
+            .clone()
+    }
+}
+
+/// Saves sessions for the current step and offers only those from the step being resumed, so
+/// a step's own jobs don't resume each other's sessions.
+#[derive(Debug)]
+struct SessionStore {
+    save: Arc<ClientSessionMemoryCache>,
+    resume: Option<Arc<ClientSessionMemoryCache>>,
+}
+
+impl ClientSessionStore for SessionStore {
+    fn set_kx_hint(&self, server_name: ServerName<'static>, group: NamedGroup) {
+        self.save.set_kx_hint(server_name, group);
+    }
+
+    fn kx_hint(&self, server_name: &ServerName<'_>) -> Option<NamedGroup> {
+        self.resume.as_ref()?.kx_hint(server_name)
+    }
+
+    fn set_tls12_session(&self, server_name: ServerName<'static>, value: Tls12ClientSessionValue) {
+        self.save.set_tls12_session(server_name, value);
+    }
+
+    fn tls12_session(&self, server_name: &ServerName<'_>) -> Option<Tls12ClientSessionValue> {
+        self.resume.as_ref()?.tls12_session(server_name)
+    }
+
+    fn remove_tls12_session(&self, server_name: &ServerName<'static>) {
+        if let Some(resume) = &self.resume {
+            resume.remove_tls12_session(server_name);
+        }
+    }
+
+    fn insert_tls13_ticket(
+        &self,
+        server_name: ServerName<'static>,
+        value: Tls13ClientSessionValue,
+    ) {
+        self.save.insert_tls13_ticket(server_name, value);
+    }
+
+    fn take_tls13_ticket(
+        &self,
+        server_name: &ServerName<'static>,
+    ) -> Option<Tls13ClientSessionValue> {
+        self.resume.as_ref()?.take_tls13_ticket(server_name)
+    }
+}
+
+/// Notes whether the server asked for a client certificate and whether one was offered.
+#[derive(Debug)]
+struct ClientAuthRecorder {
+    inner: Arc<dyn ResolvesClientCert>,
+    requested: AtomicBool,
+    sent: AtomicBool,
+}
+
+impl ResolvesClientCert for ClientAuthRecorder {
+    fn resolve(
+        &self,
+        root_hint_subjects: &[&[u8]],
+        sigschemes: &[SignatureScheme],
+    ) -> Option<Arc<CertifiedKey>> {
+        self.requested.store(true, Ordering::Relaxed);
+        let key = self.inner.resolve(root_hint_subjects, sigschemes);
+        self.sent.store(key.is_some(), Ordering::Relaxed);
+        key
+    }
+
+    fn has_certs(&self) -> bool {
+        self.inner.has_certs()
+    }
+}
+
+/// Read the client's PEM certificate chain and private key, from the plan or the files it names.
+fn load_client_cert(
+    client: &TlsClientPlanOutput,
+) -> anyhow::Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)> {
+    let cert = match (&client.cert, &client.cert_file) {
+        (Some(cert), _) => cert.as_bytes().to_vec(),
+        (None, Some(path)) => {
+            std::fs::read(path).map_err(|e| anyhow!("read tls.client.cert_file {path}: {e}"))?
+        }
+        (None, None) => bail!("tls.client.cert or tls.client.cert_file is required"),
+    };
+    let key = match (&client.key, &client.key_file) {
+        (Some(key), _) => key.expose().as_bytes().to_vec(),
+        (None, Some(path)) => {
+            std::fs::read(path).map_err(|e| anyhow!("read tls.client.key_file {path}: {e}"))?
+        }
+        (None, None) => bail!("tls.client.key or tls.client.key_file is required"),
+    };
+    let certs: Vec<_> = rustls_pemfile::certs(&mut cert.as_slice())
+        .try_collect()
+        .map_err(|e| anyhow!("parse tls.client certificate: {e}"))?;
+    if certs.is_empty() {
+        bail!("tls.client certificate has no PEM encoded certificates");
+    }
+    let key = rustls_pemfile::private_key(&mut key.as_slice())
+        .map_err(|e| anyhow!("parse tls.client key: {e}"))?
+        .ok_or_else(|| anyhow!("tls.client key has no PEM encoded private key"))?;
+    Ok((certs, key))
+}
+
+/// Describe why the certificate chain doesn't satisfy the pins, if it doesn't.
+fn check_pin(pin: &TlsPinPlanOutput, certificates: &[x509::Certificate]) -> Option<String> {
+    if !pin.spki_sha256.is_empty() {
+        let hashes: Vec<_> = certificates.iter().map(|c| spki_sha256(c.spki)).collect();
+        let pinned = pin
+            .spki_sha256
+            .iter()
+            .map(|hash| hash.trim_start_matches("sha256/"));
+        if !pinned.clone().any(|hash| hashes.iter().any(|h| h == hash)) {
+            return Some(format!(
+                "certificate chain public keys [{}] match none of the pinned [{}]",
+                hashes.join(", "),
+                pinned.join(", "),
+            ));
+        }
+    }
+    if !pin.serial.is_empty() {
+        let serial = certificates
+            .first()
+            .map(|leaf| normalize_serial(&hex(leaf.serial)))
+            .unwrap_or_default();
+        if !pin.serial.iter().any(|s| normalize_serial(s) == serial) {
+            return Some(format!(
+                "certificate serial {serial} matches none of the pinned [{}]",
+                pin.serial.join(", "),
+            ));
+        }
+    }
+    None
+}
+
+fn spki_sha256(spki: &[u8]) -> String {
+    base64::prelude::BASE64_STANDARD.encode(Sha256::digest(spki))
+}
+
+/// Lowercase hex digits without separators or leading zeros.
+fn normalize_serial(serial: &str) -> String {
+    let digits: String = serial
+        .chars()
+        .filter(char::is_ascii_hexdigit)
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    let trimmed = digits.trim_start_matches('0');
+    if trimmed.is_empty() {
+        "0".to_owned()
+    } else {
+        trimmed.to_owned()
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
@@ -0,0 +1,259 @@
+use std::sync::{Arc, OnceLock};
+
+use anyhow::anyhow;
+use rand::distributions::{Alphanumeric, DistString};
+use scraper::{Html, Selector};
+
+use crate::{
+    AddContentLength, CacheMode, Http1PlanOutput, HttpHeader, IterableKey, JobOutput, MaybeUtf8,
+    RawTcpPlanOutput, StepPlanOutput, TcpPlanOutput, TlsPlanOutput, VhostClusterOutput,
+    VhostOutput, VhostPlanOutput, VhostProbeOutput,
+};
+
+use super::runner::Runner;
+use super::{Context, Executor};
+
+/// Request each candidate host from the plan's address and group the responses. A random host is
+/// requested first for each server name mode to find the server's default response.
+pub(super) async fn vhost(
+    ctx: &Context,
+    plan: VhostPlanOutput,
+) -> (VhostOutput, Vec<(IterableKey, Arc<JobOutput>)>) {
+    let mut out = VhostOutput {
+        probes: Vec::new(),
+        clusters: Vec::new(),
+        distinct: Vec::new(),
+        plan,
+    };
+    let mut jobs = Vec::new();
+    let https = out.plan.url.scheme() == "https";
+    let address = out.plan.url.host_str().unwrap_or_default().to_owned();
+    let random = format!(
+        "{}.invalid",
+        Alphanumeric
+            .sample_string(&mut rand::thread_rng(), 16)
+            .to_ascii_lowercase()
+    );
+
+    let candidates = std::iter::once((random, true))
+        .chain(out.plan.hosts.iter().map(|h| (h.clone(), false)))
+        .collect::<Vec<_>>();
+    for (host, baseline) in candidates {
+        // Without TLS there's no server name to align.
+        let server_names = if !https {
+            vec![None]
+        } else {
+            let mut names = Vec::new();
+            if out.plan.sni.aligned() {
+                names.push(Some(host.clone()));
+            }
+            if out.plan.sni.unaligned() && host != address {
+                names.push(Some(address.clone()));
+            }
+            names
+        };
+        for sni in server_names {
+            let key = IterableKey::Uint(jobs.len().try_into().unwrap_or(u64::MAX));
+            let mut job_name = ctx.job_name.clone();
+            job_name.job = key.clone();
+            let mut probe = VhostProbeOutput {
+                host: host.clone(),
+                sni: sni.clone(),
+                baseline,
+                status_code: None,
+                length: 0,
+                title: None,
+                location: None,
+                error: None,
+                cluster: 0,
+            };
+            match fetch(ctx, &out.plan, &host, sni.as_deref(), job_name).await {
+                Ok(job) => {
+                    fingerprint(&mut probe, &job);
+                    jobs.push((key, Arc::new(job)));
+                }
+                Err(e) => probe.error = Some(format!("{e:#}")),
+            }
+            probe.cluster = cluster(&mut out.clusters, &probe, out.plan.length_tolerance);
+            out.probes.push(probe);
+        }
+    }
+
+    for probe in &out.probes {
+        if !probe.baseline
+            && probe.error.is_none()
+            && !out.clusters[usize::try_from(probe.cluster).unwrap_or(usize::MAX)].default
+            && !out.distinct.contains(&probe.host)
+        {
+            out.distinct.push(probe.host.clone());
+        }
+    }
+    (out, jobs)
+}
+
+/// Record the parts of a job's response used for clustering.
+fn fingerprint(probe: &mut VhostProbeOutput, job: &JobOutput) {
+    static TITLE: OnceLock<Selector> = OnceLock::new();
+    let http = job.h1.as_ref().or(job.h1c.as_ref());
+    let Some(response) = http.and_then(|h| h.response.as_ref()) else {
+        let tls_error = job.tls.as_ref().and_then(|t| t.errors.first());
+        probe.error = Some(
+            http.and_then(|h| h.errors.first())
+                .map(|e| e.message.clone())
+                .or_else(|| tls_error.map(|e| e.message.clone()))
+                .unwrap_or_else(|| "no response".to_owned()),
+        );
+        return;
+    };
+    let body = String::from_utf8_lossy(response.body.as_deref().unwrap_or_default());
+    // Servers often echo the requested host, so remove it to make lengths comparable.
+    probe.length = body
+        .replace(&probe.host, "")
+        .len()
+        .try_into()
+        .unwrap_or(u64::MAX);
+    probe.status_code = response.status_code;
+    let title =
+        TITLE.get_or_init(|| Selector::parse("title").expect("title selector should be valid"));
+    probe.title = Html::parse_document(&body)
+        .select(title)
+        .next()
+        .map(|t| t.text().collect::<String>().trim().to_owned());
+    probe.location = response
+        .headers
+        .as_ref()
+        .and_then(|headers| {
+            headers.iter().find(|h| {
+                h.key
+                    .as_ref()
+                    .is_some_and(|k| k.eq_ignore_ascii_case(b"location"))
+            })
+        })
+        .map(|h| String::from_utf8_lossy(&h.value).replace(&probe.host, ""));
+}
+
+/// Add the probe to the first matching cluster or start a new one, returning its index.
+fn cluster(
+    clusters: &mut Vec<VhostClusterOutput>,
+    probe: &VhostProbeOutput,
+    tolerance: u64,
+) -> u64 {
+    let existing = clusters.iter().position(|c| {
+        c.status_code == probe.status_code
+            && c.title == probe.title
+            && c.length.abs_diff(probe.length) <= tolerance
+    });
+    let index = existing.unwrap_or_else(|| {
+        clusters.push(VhostClusterOutput {
+            status_code: probe.status_code,
+            length: probe.length,
+            title: probe.title.clone(),
+            default: false,
+            hosts: Vec::new(),
+        });
+        clusters.len() - 1
+    });
+    let cluster = &mut clusters[index];
+    cluster.default |= probe.baseline;
+    if !probe.baseline && !cluster.hosts.contains(&probe.host) {
+        cluster.hosts.push(probe.host.clone());
+    }
+    index.try_into().unwrap_or(u64::MAX)
+}
+
+/// Send a request for the host to the plan's address, using the given TLS server name.
+async fn fetch(
+    ctx: &Context,
+    plan: &VhostPlanOutput,
+    host: &str,
+    sni: Option<&str>,
+    job_name: crate::JobName,
+) -> anyhow::Result<JobOutput> {
+    let ctx = Arc::new(ctx.for_job(job_name.clone()));
+    let address = plan
+        .url
+        .host_str()
+        .ok_or_else(|| anyhow!("vhost.url is missing host"))?
+        .to_owned();
+    let port = plan
+        .url
+        .port_or_known_default()
+        .ok_or_else(|| anyhow!("vhost.url is missing port"))?;
+    let mut url = plan.url.clone();
+    url.set_host(Some(host))?;
+    let request = Http1PlanOutput {
+        headers: vec![HttpHeader {
+            key: Some(MaybeUtf8("Host".into())),
+            value: MaybeUtf8(super::crawl::authority(&url)?.into()),
+        }],
+        url,
+        method: Some(MaybeUtf8("GET".into())),
+        version_string: Some(MaybeUtf8("HTTP/1.1".into())),
+        add_content_length: AddContentLength::Auto,
+        body: MaybeUtf8::default(),
+        sign: None,
+        cache: CacheMode::Off,
+        chunked: None,
+        expect_continue: None,
+        head: None,
+        read: None,
+        permissive: false,
+    };
+
+    let mut stack = Vec::with_capacity(4);
+    if let Some(sni) = sni {
+        stack.push(StepPlanOutput::H1(request));
+        stack.push(StepPlanOutput::Tls(TlsPlanOutput {
+            host: sni.to_owned(),
+            port,
+            server_name: None,
+            alpn: vec![MaybeUtf8("http/1.1".into())],
+            body: MaybeUtf8::default(),
+            min_version: None,
+            max_version: None,
+            cipher_suites: Vec::new(),
+            pin: None,
+            client: None,
+            ca: None,
+            ca_file: None,
+            insecure: false,
+            resume: None,
+            session_tickets: true,
+            expect: Vec::new(),
+        }));
+    } else {
+        stack.push(StepPlanOutput::H1c(request));
+    }
+    stack.push(StepPlanOutput::Tcp(TcpPlanOutput {
+        host: address.clone(),
+        port,
+        body: MaybeUtf8::default(),
+        proxies: Vec::new(),
+        proxy_protocol: None,
+        fault: None,
+        expect: Vec::new(),
+    }));
+    stack.push(StepPlanOutput::RawTcp(RawTcpPlanOutput {
+        dest_host: address,
+        dest_port: port,
+        src_host: None,
+        src_port: None,
+        isn: 0,
+        window: 1000,
+        segments: Vec::new(),
+    }));
+
+    let mut runners = stack
+        .into_iter()
+        .enumerate()
+        .map(|(i, req)| Runner::new(ctx.clone(), req, i == 0))
+        .collect::<crate::Result<Vec<_>>>()?;
+    let mut size_hint = runners[0].executor_size_hint();
+    for r in &mut runners {
+        size_hint = r.size_hint(size_hint);
+    }
+    let runner = Executor::start_runners(None, runners, 1)
+        .await?
+        .expect("any stack should have at least one protocol");
+    Ok(Executor::iteration(runner, None, job_name).await?.0)
+}
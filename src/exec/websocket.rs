@@ -0,0 +1,666 @@
+use std::mem;
+use std::sync::Arc;
+use std::time::Instant;
+
+use anyhow::{anyhow, bail};
+use base64::Engine;
+use bytes::{Buf, BufMut, BytesMut};
+use chrono::TimeDelta;
+use rand::RngCore;
+use sha1::{Digest, Sha1};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use url::Position;
+
+use super::raw_tcp::RawTcpRunner;
+use super::runner::Runner;
+use super::tcp::TcpRunner;
+use super::tls::TlsRunner;
+use super::{crawl, Context};
+use crate::{
+    Direction, HttpHeader, MaybeUtf8, ParsedWebSocketOpcode, PduName, ProtocolDiscriminants,
+    ProtocolName, RawTcpPlanOutput, TcpPlanOutput, TlsPlanOutput, WebSocketError,
+    WebSocketFrameOutput, WebSocketFramePlanOutput, WebSocketHandshakeOutput, WebSocketOpcode,
+    WebSocketOutput, WebSocketPlanOutput,
+};
+
+/// Appended to Sec-WebSocket-Key before hashing to produce Sec-WebSocket-Accept (RFC 6455
+/// section 1.3).
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// The most response header bytes to buffer before giving up on the handshake.
+const MAX_HEAD: usize = 64 * 1024;
+/// The largest frame payload to buffer from the server.
+const MAX_PAYLOAD: u64 = 16 * 1024 * 1024;
+
+#[derive(Debug)]
+pub(super) struct WebSocketRunner {
+    out: WebSocketOutput,
+    state: State,
+    key: String,
+    start_time: Option<Instant>,
+}
+
+#[derive(Debug)]
+enum State {
+    Pending { transports: Vec<Runner> },
+    Open { transport: Runner },
+    Completed { transport: Option<Runner> },
+    Invalid,
+}
+
+impl WebSocketRunner {
+    pub(super) fn new(ctx: Arc<Context>, plan: WebSocketPlanOutput) -> crate::Result<Self> {
+        let host = plan
+            .url
+            .host()
+            .ok_or_else(|| anyhow!("url is missing host"))?
+            .to_string();
+        let port = plan
+            .url
+            .port_or_known_default()
+            .ok_or_else(|| anyhow!("url is missing port"))?;
+
+        let mut transports = Vec::with_capacity(3);
+        transports.push(Runner::RawTcp(Box::new(RawTcpRunner::new(
+            ctx.clone(),
+            RawTcpPlanOutput {
+                dest_host: host.clone(),
+                dest_port: port,
+                src_host: None,
+                src_port: None,
+                isn: 0,
+                window: 1000,
+                segments: Vec::new(),
+            },
+        ))));
+        transports.push(Runner::Tcp(Box::new(TcpRunner::new(
+            ctx.clone(),
+            TcpPlanOutput {
+                host: host.clone(),
+                port,
+                body: MaybeUtf8::default(),
+                proxies: Vec::new(),
+                proxy_protocol: None,
+                fault: None,
+                expect: Vec::new(),
+            },
+        ))));
+        if plan.url.scheme() == "wss" {
+            // The upgrade is only defined for HTTP/1.1, so don't offer anything else.
+            transports.push(Runner::Tls(Box::new(TlsRunner::new(
+                ctx.clone(),
+                TlsPlanOutput {
+                    host,
+                    port,
+                    server_name: None,
+                    alpn: vec![MaybeUtf8("http/1.1".into())],
+                    body: MaybeUtf8::default(),
+                    min_version: None,
+                    max_version: None,
+                    cipher_suites: Vec::new(),
+                    pin: None,
+                    client: None,
+                    ca: None,
+                    ca_file: None,
+                    insecure: false,
+                    resume: None,
+                    session_tickets: true,
+                    expect: Vec::new(),
+                },
+            )?)));
+        }
+
+        // Use the plan's key if it sets one so mismatched accept values can be tested.
+        let key = plan
+            .headers
+            .iter()
+            .find(|header| is_header(header, "sec-websocket-key"))
+            .map(|header| header.value.to_string())
+            .unwrap_or_else(|| {
+                let mut nonce = [0; 16];
+                rand::thread_rng().fill_bytes(&mut nonce);
+                base64::prelude::BASE64_STANDARD.encode(nonce)
+            });
+
+        Ok(Self {
+            out: WebSocketOutput {
+                name: ProtocolName::with_job(ctx.job_name.clone(), ProtocolDiscriminants::Ws),
+                plan,
+                handshake: None,
+                frames: Vec::new(),
+                errors: Vec::new(),
+                duration: TimeDelta::zero().into(),
+            },
+            state: State::Pending { transports },
+            key,
+            start_time: None,
+        })
+    }
+
+    pub(super) fn size_hint(&mut self, _hint: Option<usize>) -> Option<usize> {
+        let State::Pending { transports } = &mut self.state else {
+            panic!("invalid state to call size_hint")
+        };
+        // Frames are written one at a time, so there's no total size to pass down.
+        let mut size_hint = None;
+        for t in transports.iter_mut().rev() {
+            size_hint = t.size_hint(size_hint);
+        }
+        size_hint
+    }
+
+    pub(super) fn executor_size_hint(&self) -> Option<usize> {
+        None
+    }
+
+    pub async fn start(&mut self) -> anyhow::Result<()> {
+        let State::Pending { transports } = mem::replace(&mut self.state, State::Invalid) else {
+            bail!("invalid state to call start")
+        };
+        self.start_time = Some(Instant::now());
+
+        let mut transport = None;
+        for mut t in transports {
+            t.start(transport, 1).await?;
+            transport = Some(t);
+        }
+        let mut transport = transport.expect("websocket should always provide a transport");
+
+        let result = async {
+            let request = self.upgrade_request()?;
+            transport.write_all(&request).await?;
+            transport.flush().await?;
+            Ok::<_, anyhow::Error>(())
+        }
+        .await;
+        if let Err(e) = result {
+            self.out.errors.push(WebSocketError {
+                kind: "send handshake".to_owned(),
+                message: format!("{e:#}"),
+            });
+            self.state = State::Completed {
+                transport: Some(transport),
+            };
+            return Err(e);
+        }
+        self.state = State::Open { transport };
+        Ok(())
+    }
+
+    fn upgrade_request(&self) -> anyhow::Result<Vec<u8>> {
+        let url = &self.out.plan.url;
+        let mut request = format!(
+            "GET {} HTTP/1.1\r\n",
+            &url[Position::BeforePath..Position::AfterQuery],
+        )
+        .into_bytes();
+        let defaults = [
+            ("Host", crawl::authority(url)?),
+            ("Upgrade", "websocket".to_owned()),
+            ("Connection", "Upgrade".to_owned()),
+            ("Sec-WebSocket-Key", self.key.clone()),
+            ("Sec-WebSocket-Version", "13".to_owned()),
+        ];
+        // Headers from the plan replace the defaults so malformed handshakes can be sent.
+        for (key, value) in defaults {
+            if !self
+                .out
+                .plan
+                .headers
+                .iter()
+                .any(|header| is_header(header, key))
+            {
+                request.extend_from_slice(format!("{key}: {value}\r\n").as_bytes());
+            }
+        }
+        for header in &self.out.plan.headers {
+            if let Some(key) = &header.key {
+                request.extend_from_slice(key.as_slice());
+                request.extend_from_slice(b": ");
+            }
+            request.extend_from_slice(header.value.as_slice());
+            request.extend_from_slice(b"\r\n");
+        }
+        request.extend_from_slice(b"\r\n");
+        Ok(request)
+    }
+
+    pub async fn execute(&mut self) {
+        let State::Open { transport } = &mut self.state else {
+            return;
+        };
+        let start = self
+            .start_time
+            .expect("start time should be set for open websocket");
+        let timeout = self.out.plan.timeout.0.to_std().unwrap_or_default();
+        let mut buf = BytesMut::new();
+
+        let mut handshake = WebSocketHandshakeOutput {
+            name: PduName::with_protocol(self.out.name.clone(), 0),
+            key: self.key.clone(),
+            status_code: None,
+            headers: None,
+            accept_valid: false,
+            duration: TimeDelta::zero().into(),
+        };
+        let result = tokio::time::timeout(
+            timeout,
+            read_handshake(transport, &mut buf, &self.key, &mut handshake),
+        )
+        .await
+        .unwrap_or_else(|_| Err(anyhow!("timed out")));
+        handshake.duration = since(start);
+        self.out.handshake = Some(Arc::new(handshake));
+        if let Err(e) = result {
+            self.out.errors.push(WebSocketError {
+                kind: "handshake".to_owned(),
+                message: format!("{e:#}"),
+            });
+            return;
+        }
+
+        let opened = Instant::now();
+        let mut id = 1;
+        for plan in self.out.plan.frames.clone() {
+            let name = PduName::with_protocol(self.out.name.clone(), id);
+            let (frame, bytes) = encode(&plan, name);
+            let result = async {
+                transport.write_all(&bytes).await?;
+                transport.flush().await
+            }
+            .await;
+            if let Err(e) = result {
+                self.out.errors.push(WebSocketError {
+                    kind: "send frame".to_owned(),
+                    message: e.to_string(),
+                });
+                return;
+            }
+            self.out.frames.push(Arc::new(WebSocketFrameOutput {
+                time: since(opened),
+                ..frame
+            }));
+            id += 1;
+
+            for _ in 0..plan.receive {
+                let name = PduName::with_protocol(self.out.name.clone(), id);
+                let result =
+                    tokio::time::timeout(timeout, read_frame(transport, &mut buf, name)).await;
+                match result {
+                    Ok(Ok(frame)) => {
+                        self.out.frames.push(Arc::new(WebSocketFrameOutput {
+                            time: since(opened),
+                            ..frame
+                        }));
+                        id += 1;
+                    }
+                    // Later frames may still prompt a response, so keep following the script.
+                    Err(_) => {
+                        self.out.errors.push(WebSocketError {
+                            kind: "receive frame".to_owned(),
+                            message: "timed out".to_owned(),
+                        });
+                        break;
+                    }
+                    Ok(Err(e)) => {
+                        self.out.errors.push(WebSocketError {
+                            kind: "receive frame".to_owned(),
+                            message: format!("{e:#}"),
+                        });
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    pub async fn finish(mut self) -> (WebSocketOutput, Option<Runner>) {
+        let transport = match mem::replace(&mut self.state, State::Invalid) {
+            State::Open { transport } => Some(transport),
+            State::Completed { transport } => transport,
+            State::Pending { .. } | State::Invalid => None,
+        };
+        if let Some(start) = self.start_time {
+            self.out.duration = since(start);
+        }
+        (self.out, transport)
+    }
+}
+
+fn is_header(header: &HttpHeader, name: &str) -> bool {
+    header
+        .key
+        .as_ref()
+        .is_some_and(|key| key.as_slice().eq_ignore_ascii_case(name.as_bytes()))
+}
+
+/// Read the server's response to the upgrade request, leaving anything after it in buf.
+async fn read_handshake(
+    transport: &mut Runner,
+    buf: &mut BytesMut,
+    key: &str,
+    out: &mut WebSocketHandshakeOutput,
+) -> anyhow::Result<()> {
+    loop {
+        let mut headers = [httparse::EMPTY_HEADER; 64];
+        let mut resp = httparse::Response::new(&mut headers);
+        if let httparse::Status::Complete(len) = resp.parse(&buf[..])? {
+            out.status_code = resp.code;
+            out.headers = Some(
+                resp.headers
+                    .iter()
+                    .map(|header| HttpHeader {
+                        key: Some(MaybeUtf8(header.name.to_owned().into())),
+                        value: MaybeUtf8(header.value.to_vec().into()),
+                    })
+                    .collect(),
+            );
+            let expected = base64::prelude::BASE64_STANDARD
+                .encode(Sha1::digest(format!("{key}{ACCEPT_GUID}").as_bytes()));
+            out.accept_valid = resp.headers.iter().any(|header| {
+                header.name.eq_ignore_ascii_case("sec-websocket-accept")
+                    && header.value == expected.as_bytes()
+            });
+            let code = resp.code;
+            buf.advance(len);
+            if code != Some(101) {
+                bail!(
+                    "server responded with status {} instead of 101",
+                    code.unwrap_or(0)
+                );
+            }
+            return Ok(());
+        }
+        if buf.len() > MAX_HEAD {
+            bail!("response head exceeded {MAX_HEAD} bytes");
+        }
+        if transport.read_buf(buf).await? == 0 {
+            bail!("connection closed before handshake response");
+        }
+    }
+}
+
+/// Serialize a planned frame, returning its output alongside the bytes to send.
+fn encode(plan: &WebSocketFramePlanOutput, name: PduName) -> (WebSocketFrameOutput, Vec<u8>) {
+    let mut payload = Vec::with_capacity(plan.payload.len() + 2);
+    if let Some(code) = plan.close_code {
+        payload.extend_from_slice(&code.to_be_bytes());
+    }
+    payload.extend_from_slice(plan.payload.as_slice());
+    let masking_key = plan.mask.then(|| rand::thread_rng().next_u32());
+
+    let mut bytes = Vec::with_capacity(payload.len() + 14);
+    bytes.push((u8::from(plan.fin) << 7) | (plan.rsv << 4) | plan.opcode.raw);
+    let mask_bit = if masking_key.is_some() { 0x80 } else { 0 };
+    match payload.len() {
+        len @ 0..=125 => bytes.push(mask_bit | len as u8),
+        len @ 126..=0xFFFF => {
+            bytes.push(mask_bit | 126);
+            bytes.put_u16(len as u16);
+        }
+        len => {
+            bytes.push(mask_bit | 127);
+            bytes.put_u64(len as u64);
+        }
+    }
+    match masking_key {
+        Some(key) => {
+            let key = key.to_be_bytes();
+            bytes.extend_from_slice(&key);
+            bytes.extend(payload.iter().enumerate().map(|(i, b)| b ^ key[i % 4]));
+        }
+        None => bytes.extend_from_slice(&payload),
+    }
+
+    (
+        WebSocketFrameOutput {
+            name,
+            direction: Direction::Send,
+            opcode: plan.opcode,
+            fin: plan.fin,
+            rsv: plan.rsv,
+            masking_key,
+            payload: plan.payload.clone(),
+            close_code: plan.close_code,
+            time: TimeDelta::zero().into(),
+        },
+        bytes,
+    )
+}
+
+/// Read the next frame from the server, buffering from the transport as needed.
+async fn read_frame(
+    transport: &mut Runner,
+    buf: &mut BytesMut,
+    name: PduName,
+) -> anyhow::Result<WebSocketFrameOutput> {
+    loop {
+        if let Some(frame) = decode(buf, &name)? {
+            return Ok(frame);
+        }
+        if transport.read_buf(buf).await? == 0 {
+            bail!("connection closed");
+        }
+    }
+}
+
+/// Parse a frame from the start of buf, or return None if it isn't complete yet.
+fn decode(buf: &mut BytesMut, name: &PduName) -> anyhow::Result<Option<WebSocketFrameOutput>> {
+    if buf.len() < 2 {
+        return Ok(None);
+    }
+    let masked = buf[1] & 0x80 != 0;
+    let (len, mut header_len) = match buf[1] & 0x7F {
+        126 if buf.len() < 4 => return Ok(None),
+        126 => (u64::from(u16::from_be_bytes([buf[2], buf[3]])), 4),
+        127 if buf.len() < 10 => return Ok(None),
+        127 => (
+            u64::from_be_bytes(buf[2..10].try_into().expect("slice should be 8 bytes")),
+            10,
+        ),
+        len => (u64::from(len), 2),
+    };
+    if len > MAX_PAYLOAD {
+        bail!("frame payload of {len} bytes exceeds {MAX_PAYLOAD} byte limit");
+    }
+    let masking_key = if masked {
+        if buf.len() < header_len + 4 {
+            return Ok(None);
+        }
+        let key = u32::from_be_bytes(
+            buf[header_len..header_len + 4]
+                .try_into()
+                .expect("slice should be 4 bytes"),
+        );
+        header_len += 4;
+        Some(key)
+    } else {
+        None
+    };
+    let len = len as usize;
+    if buf.len() < header_len + len {
+        return Ok(None);
+    }
+
+    let first = buf[0];
+    buf.advance(header_len);
+    let mut payload = buf.split_to(len).to_vec();
+    if let Some(key) = masking_key {
+        let key = key.to_be_bytes();
+        for (i, b) in payload.iter_mut().enumerate() {
+            *b ^= key[i % 4];
+        }
+    }
+    let opcode = WebSocketOpcode::from(first & 0x0F);
+    let close_code = if opcode.parsed == Some(ParsedWebSocketOpcode::Close) && payload.len() >= 2 {
+        let code = u16::from_be_bytes([payload[0], payload[1]]);
+        payload.drain(..2);
+        Some(code)
+    } else {
+        None
+    };
+    Ok(Some(WebSocketFrameOutput {
+        name: name.clone(),
+        direction: Direction::Recv,
+        opcode,
+        fin: first & 0x80 != 0,
+        rsv: (first >> 4) & 0x7,
+        masking_key,
+        payload: MaybeUtf8(payload.into()),
+        close_code,
+        time: TimeDelta::zero().into(),
+    }))
+}
+
+fn since(start: Instant) -> cel_interpreter::Duration {
+    TimeDelta::from_std(start.elapsed())
+        .unwrap_or(TimeDelta::MAX)
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::{IterableKey, JobName, RunName};
+
+    fn name() -> PduName {
+        let job = JobName::with_run(
+            RunName::new(Arc::new("plan".to_owned())),
+            Arc::new("step".to_owned()),
+            IterableKey::Uint(0),
+        );
+        PduName::with_job(job, ProtocolDiscriminants::Ws, 0)
+    }
+
+    fn frame(
+        opcode: ParsedWebSocketOpcode,
+        fin: bool,
+        mask: bool,
+        payload: Vec<u8>,
+    ) -> WebSocketFramePlanOutput {
+        WebSocketFramePlanOutput {
+            opcode: opcode.into(),
+            fin,
+            rsv: 0,
+            mask,
+            close_code: None,
+            payload: MaybeUtf8(Bytes::from(payload).into()),
+            receive: 0,
+        }
+    }
+
+    /// Encode a frame and decode it again, checking that the whole frame was consumed.
+    fn round_trip(plan: &WebSocketFramePlanOutput) -> (Vec<u8>, WebSocketFrameOutput) {
+        let (sent, bytes) = encode(plan, name());
+        let mut buf = BytesMut::from(bytes.as_slice());
+        let received = decode(&mut buf, &name())
+            .unwrap()
+            .expect("the frame should be complete");
+        assert!(buf.is_empty());
+        assert!(received.direction.is_recv());
+        assert_eq!(received.masking_key, sent.masking_key);
+        assert_eq!(received.opcode, sent.opcode);
+        assert_eq!(received.fin, sent.fin);
+        assert_eq!(received.close_code, sent.close_code);
+        assert_eq!(received.payload.as_slice(), plan.payload.as_slice());
+        (bytes, received)
+    }
+
+    #[test]
+    fn test_masked_round_trip() {
+        let plan = frame(ParsedWebSocketOpcode::Text, true, true, b"hello".to_vec());
+        let (bytes, received) = round_trip(&plan);
+        assert_eq!(bytes[0], 0x81);
+        assert_eq!(bytes[1], 0x80 | 5);
+        let key = received.masking_key.expect("the frame should be masked");
+        assert_eq!(bytes[2..6], key.to_be_bytes());
+        let masked: Vec<_> = b"hello"
+            .iter()
+            .zip(key.to_be_bytes().iter().cycle())
+            .map(|(b, k)| b ^ k)
+            .collect();
+        assert_eq!(bytes[6..], masked);
+    }
+
+    #[test]
+    fn test_extended_lengths() {
+        for (len, header) in [
+            (125, vec![0x82, 125]),
+            (126, vec![0x82, 126, 0, 126]),
+            (0xFFFF, vec![0x82, 126, 0xFF, 0xFF]),
+            (0x10000, vec![0x82, 127, 0, 0, 0, 0, 0, 1, 0, 0]),
+        ] {
+            let plan = frame(ParsedWebSocketOpcode::Binary, true, false, vec![7; len]);
+            let (bytes, _) = round_trip(&plan);
+            assert_eq!(bytes[..header.len()], header, "payload of {len} bytes");
+            assert_eq!(bytes.len(), header.len() + len);
+
+            // A masked frame puts the key after the extended length.
+            let plan = frame(ParsedWebSocketOpcode::Binary, true, true, vec![7; len]);
+            let (bytes, _) = round_trip(&plan);
+            assert_eq!(bytes[1], header[1] | 0x80);
+            assert_eq!(bytes.len(), header.len() + 4 + len);
+        }
+    }
+
+    #[test]
+    fn test_fragmented_with_control_frame() {
+        let frames = [
+            frame(ParsedWebSocketOpcode::Text, false, true, b"hel".to_vec()),
+            frame(ParsedWebSocketOpcode::Ping, true, true, b"ping".to_vec()),
+            frame(
+                ParsedWebSocketOpcode::Continuation,
+                true,
+                true,
+                b"lo".to_vec(),
+            ),
+        ];
+        let mut buf = BytesMut::new();
+        for plan in &frames {
+            buf.extend_from_slice(&encode(plan, name()).1);
+        }
+        for plan in &frames {
+            let received = decode(&mut buf, &name()).unwrap().unwrap();
+            assert_eq!(received.opcode, plan.opcode);
+            assert_eq!(received.fin, plan.fin);
+            assert_eq!(received.payload.as_slice(), plan.payload.as_slice());
+        }
+        assert!(buf.is_empty());
+        assert!(WebSocketOpcode::from(ParsedWebSocketOpcode::Ping).is_control());
+        assert!(!WebSocketOpcode::from(ParsedWebSocketOpcode::Continuation).is_control());
+    }
+
+    #[test]
+    fn test_close_code() {
+        let mut plan = frame(ParsedWebSocketOpcode::Close, true, true, b"bye".to_vec());
+        plan.close_code = Some(1000);
+        let (_, received) = round_trip(&plan);
+        assert_eq!(received.close_code, Some(1000));
+
+        // A close frame without a body has no code.
+        let plan = frame(ParsedWebSocketOpcode::Close, true, false, Vec::new());
+        let (bytes, received) = round_trip(&plan);
+        assert_eq!(bytes, [0x88, 0]);
+        assert_eq!(received.close_code, None);
+    }
+
+    #[test]
+    fn test_decode_incomplete() {
+        let plan = frame(ParsedWebSocketOpcode::Text, true, true, vec![b'a'; 300]);
+        let (_, bytes) = encode(&plan, name());
+        // Stop inside the extended length, the masking key and the payload.
+        for end in [1, 3, 6, bytes.len() - 1] {
+            let mut buf = BytesMut::from(&bytes[..end]);
+            assert!(decode(&mut buf, &name()).unwrap().is_none());
+            assert_eq!(buf.len(), end, "nothing should be consumed");
+        }
+    }
+
+    #[test]
+    fn test_decode_payload_limit() {
+        let mut buf = BytesMut::from([0x82, 127].as_slice());
+        buf.put_u64(MAX_PAYLOAD + 1);
+        assert!(decode(&mut buf, &name()).is_err());
+    }
+}
@@ -0,0 +1,354 @@
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use anyhow::{anyhow, bail};
+use chrono::{NaiveDateTime, SecondsFormat};
+use itertools::Itertools;
+
+/// The parts of an X.509 certificate checked against pins and reported in the output.
+#[derive(Debug, Clone)]
+pub(super) struct Certificate<'a> {
+    /// The serial number's content bytes, which may have a leading zero for the sign.
+    pub serial: &'a [u8],
+    /// The full DER encoded SubjectPublicKeyInfo.
+    pub spki: &'a [u8],
+    pub issuer: String,
+    pub subject: String,
+    /// RFC 3339 times, or None if the validity couldn't be parsed.
+    pub not_before: Option<String>,
+    pub not_after: Option<String>,
+    /// DNS names, IP addresses, emails, and URIs from the subject alternative name extension.
+    pub sans: Vec<String>,
+    pub key_algorithm: String,
+}
+
+/// Parse a DER encoded certificate. Only the serial and public key are required, the rest are
+/// left empty if they're malformed.
+pub(super) fn parse(der: &[u8]) -> anyhow::Result<Certificate<'_>> {
+    let (_, cert, _) = read(der)?;
+    let (_, tbs, _) = read(cert)?;
+    let (mut tag, mut content, mut rest) = read(tbs)?;
+    // The version is an explicitly tagged optional field.
+    if tag == 0xa0 {
+        (tag, content, rest) = read(rest)?;
+    }
+    if tag != 0x02 {
+        bail!("certificate serial is not an integer");
+    }
+    let serial = content;
+    // Skip the signature algorithm.
+    (_, _, rest) = read(rest)?;
+    let (_, issuer, rest) = read(rest)?;
+    let (_, validity, rest) = read(rest)?;
+    let (_, subject, rest) = read(rest)?;
+    let (tag, key_info, extensions) = read(rest)?;
+    if tag != 0x30 {
+        bail!("certificate public key info is not a sequence");
+    }
+    let (not_before, not_after) = match read(validity) {
+        Ok((before_tag, before, rest)) => (
+            time(before_tag, before).ok(),
+            read(rest).and_then(|(tag, after, _)| time(tag, after)).ok(),
+        ),
+        Err(_) => (None, None),
+    };
+    Ok(Certificate {
+        serial,
+        spki: &rest[..rest.len() - extensions.len()],
+        issuer: name(issuer).unwrap_or_default(),
+        subject: name(subject).unwrap_or_default(),
+        not_before,
+        not_after,
+        sans: sans(extensions).unwrap_or_default(),
+        key_algorithm: key_algorithm(key_info).unwrap_or_default(),
+    })
+}
+
+/// Format a distinguished name like CN=example.com, O=Example, in the certificate's order.
+fn name(mut input: &[u8]) -> anyhow::Result<String> {
+    let mut parts = Vec::new();
+    while !input.is_empty() {
+        let (_, mut rdn, rest) = read(input)?;
+        input = rest;
+        while !rdn.is_empty() {
+            let (_, attribute, rest) = read(rdn)?;
+            rdn = rest;
+            let (_, oid, value) = read(attribute)?;
+            let (tag, value, _) = read(value)?;
+            let key = match oid {
+                [0x55, 0x04, 0x03] => "CN".to_owned(),
+                [0x55, 0x04, 0x05] => "serialNumber".to_owned(),
+                [0x55, 0x04, 0x06] => "C".to_owned(),
+                [0x55, 0x04, 0x07] => "L".to_owned(),
+                [0x55, 0x04, 0x08] => "ST".to_owned(),
+                [0x55, 0x04, 0x0a] => "O".to_owned(),
+                [0x55, 0x04, 0x0b] => "OU".to_owned(),
+                [0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x01] => "emailAddress".to_owned(),
+                _ => oid_string(oid),
+            };
+            let value = if tag == 0x1e {
+                // BMPString is UTF-16.
+                String::from_utf16_lossy(
+                    &value
+                        .chunks_exact(2)
+                        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                        .collect_vec(),
+                )
+            } else {
+                String::from_utf8_lossy(value).into_owned()
+            };
+            parts.push(format!("{key}={value}"));
+        }
+    }
+    Ok(parts.join(", "))
+}
+
+/// Convert a UTCTime or GeneralizedTime to RFC 3339.
+fn time(tag: u8, content: &[u8]) -> anyhow::Result<String> {
+    let text = std::str::from_utf8(content)?;
+    let text = match tag {
+        // Two digit years are 1950 through 2049.
+        0x17 if text.get(..2).is_some_and(|year| year >= "50") => format!("19{text}"),
+        0x17 => format!("20{text}"),
+        0x18 => text.to_owned(),
+        _ => bail!("certificate validity is not a time"),
+    };
+    let time = NaiveDateTime::parse_from_str(&text, "%Y%m%d%H%M%SZ")?;
+    Ok(time.and_utc().to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
+/// The subject alternative names in the extensions following the public key.
+fn sans(mut input: &[u8]) -> anyhow::Result<Vec<String>> {
+    // Skip the issuer and subject unique IDs.
+    while let Ok((tag, content, rest)) = read(input) {
+        input = rest;
+        if tag != 0xa3 {
+            continue;
+        }
+        let (_, mut extensions, _) = read(content)?;
+        while !extensions.is_empty() {
+            let (_, extension, rest) = read(extensions)?;
+            extensions = rest;
+            let (_, oid, fields) = read(extension)?;
+            if oid != [0x55, 0x1d, 0x11] {
+                continue;
+            }
+            let (mut tag, mut value, rest) = read(fields)?;
+            // Skip the critical flag.
+            if tag == 0x01 {
+                (tag, value, _) = read(rest)?;
+            }
+            if tag != 0x04 {
+                bail!("certificate extension value is not an octet string");
+            }
+            let (_, mut names, _) = read(value)?;
+            let mut out = Vec::new();
+            while !names.is_empty() {
+                let (tag, name, rest) = read(names)?;
+                names = rest;
+                match (tag, name.len()) {
+                    (0x87, 4) => out.push(Ipv4Addr::from(<[u8; 4]>::try_from(name)?).to_string()),
+                    (0x87, 16) => {
+                        out.push(Ipv6Addr::from(<[u8; 16]>::try_from(name)?).to_string());
+                    }
+                    // rfc822Name, dNSName, and uniformResourceIdentifier.
+                    (0x81 | 0x82 | 0x86, _) => {
+                        out.push(String::from_utf8_lossy(name).into_owned());
+                    }
+                    _ => {}
+                }
+            }
+            return Ok(out);
+        }
+    }
+    Ok(Vec::new())
+}
+
+/// Name the public key's algorithm, like rsa, ec-p256, or ed25519, from the contents of its
+/// SubjectPublicKeyInfo.
+fn key_algorithm(key_info: &[u8]) -> anyhow::Result<String> {
+    let (_, algorithm, _) = read(key_info)?;
+    let (_, oid, params) = read(algorithm)?;
+    Ok(match oid {
+        [0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01] => "rsa".to_owned(),
+        [0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01] => match read(params)?.1 {
+            [0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07] => "ec-p256".to_owned(),
+            [0x2b, 0x81, 0x04, 0x00, 0x22] => "ec-p384".to_owned(),
+            [0x2b, 0x81, 0x04, 0x00, 0x23] => "ec-p521".to_owned(),
+            curve => format!("ec-{}", oid_string(curve)),
+        },
+        [0x2b, 0x65, 0x70] => "ed25519".to_owned(),
+        [0x2b, 0x65, 0x71] => "ed448".to_owned(),
+        _ => oid_string(oid),
+    })
+}
+
+/// Format an object identifier in dotted decimal.
+fn oid_string(oid: &[u8]) -> String {
+    let mut arcs = Vec::new();
+    let mut arc: u64 = 0;
+    for byte in oid {
+        arc = arc << 7 | u64::from(byte & 0x7f);
+        if byte & 0x80 == 0 {
+            arcs.push(arc);
+            arc = 0;
+        }
+    }
+    let Some(first) = arcs.first_mut() else {
+        return String::new();
+    };
+    // The first two arcs share a value.
+    let (root, second) = match *first {
+        x if x < 80 => (x / 40, x % 40),
+        x => (2, x - 80),
+    };
+    *first = second;
+    std::iter::once(root).chain(arcs).join(".")
+}
+
+/// Read one DER element, returning its tag, contents, and the remaining input.
+fn read(input: &[u8]) -> anyhow::Result<(u8, &[u8], &[u8])> {
+    let truncated = || anyhow!("certificate truncated");
+    let (&tag, input) = input.split_first().ok_or_else(truncated)?;
+    let (&first, mut input) = input.split_first().ok_or_else(truncated)?;
+    let len = if first < 0x80 {
+        usize::from(first)
+    } else {
+        let count = usize::from(first & 0x7f);
+        if count == 0 || count > 4 || input.len() < count {
+            bail!("invalid certificate length");
+        }
+        let len = input[..count]
+            .iter()
+            .fold(0, |len, byte| len << 8 | usize::from(*byte));
+        input = &input[count..];
+        len
+    };
+    if input.len() < len {
+        return Err(truncated());
+    }
+    let (content, rest) = input.split_at(len);
+    Ok((tag, content, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::{Digest, Sha256};
+
+    use super::*;
+
+    /// An RSA leaf valid until 2055, so its expiry is a GeneralizedTime, signed by CA.
+    const LEAF: &[u8] = include_bytes!("../../tests/certs/leaf.der");
+    /// A self-signed P-256 CA with UTCTime validity.
+    const CA: &[u8] = include_bytes!("../../tests/certs/ca.der");
+
+    fn spki_sha256(cert: &Certificate) -> String {
+        Sha256::digest(cert.spki)
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_leaf() {
+        let cert = parse(LEAF).unwrap();
+        assert_eq!(cert.serial, [0x00, 0xc0, 0xff, 0xee]);
+        assert_eq!(cert.issuer, "C=US, O=Devil Test, CN=Devil Test CA");
+        assert_eq!(cert.subject, "CN=example.com, O=Example");
+        assert_eq!(cert.not_before.as_deref(), Some("2024-01-01T00:00:00Z"));
+        assert_eq!(cert.not_after.as_deref(), Some("2055-01-01T00:00:00Z"));
+        assert_eq!(
+            cert.sans,
+            [
+                "example.com",
+                "*.example.com",
+                "192.0.2.1",
+                "2001:db8::1",
+                "admin@example.com",
+                "https://example.com/",
+            ]
+        );
+        assert_eq!(cert.key_algorithm, "rsa");
+        assert_eq!(
+            spki_sha256(&cert),
+            "83b5b02a0e5080b33d807ab41d7f6c26978dcd2d127df5bb7b7ba1faccd21738"
+        );
+    }
+
+    #[test]
+    fn test_parse_ca() {
+        let cert = parse(CA).unwrap();
+        assert_eq!(cert.serial, [0x01]);
+        assert_eq!(cert.issuer, cert.subject);
+        assert_eq!(parse(LEAF).unwrap().issuer, cert.subject);
+        assert_eq!(cert.not_before.as_deref(), Some("2024-01-01T00:00:00Z"));
+        assert_eq!(cert.not_after.as_deref(), Some("2034-01-01T00:00:00Z"));
+        assert!(cert.sans.is_empty());
+        assert_eq!(cert.key_algorithm, "ec-p256");
+        assert_eq!(
+            spki_sha256(&cert),
+            "241094cd8163a2652413fd5f32afe5e8e4ad775e546e740a56c2da274a300da4"
+        );
+    }
+
+    #[test]
+    fn test_time() {
+        assert_eq!(
+            time(0x17, b"491231235959Z").unwrap(),
+            "2049-12-31T23:59:59Z"
+        );
+        assert_eq!(
+            time(0x17, b"500101000000Z").unwrap(),
+            "1950-01-01T00:00:00Z"
+        );
+        assert_eq!(
+            time(0x18, b"20550101000000Z").unwrap(),
+            "2055-01-01T00:00:00Z"
+        );
+        assert_eq!(
+            time(0x18, b"19491231235959Z").unwrap(),
+            "1949-12-31T23:59:59Z"
+        );
+        assert!(time(0x04, b"20550101000000Z").is_err());
+        assert!(time(0x18, b"2055010100Z").is_err());
+    }
+
+    #[test]
+    fn test_parse_malformed() {
+        assert!(parse(&[]).is_err());
+        assert!(parse(&LEAF[..LEAF.len() / 2]).is_err());
+        // Lengths of more than four bytes.
+        assert!(parse(&[0x30, 0x85, 1, 0, 0, 0, 0]).is_err());
+        // A length longer than the input.
+        assert!(parse(&[0x30, 0x82, 0xff, 0xff, 0x30, 0x00]).is_err());
+        // A serial which isn't an integer.
+        let mut cert = LEAF.to_vec();
+        let serial = LEAF
+            .windows(6)
+            .position(|w| w == [0x02, 0x04, 0x00, 0xc0, 0xff, 0xee])
+            .unwrap();
+        cert[serial] = 0x04;
+        assert!(parse(&cert).is_err());
+    }
+
+    #[test]
+    fn test_parse_bad_validity() {
+        // Corrupting the times leaves them unset without failing the parse.
+        let mut cert = LEAF.to_vec();
+        let before = LEAF.windows(2).position(|w| w == [0x17, 0x0d]).unwrap();
+        cert[before] = 0x04;
+        let cert = parse(&cert).unwrap();
+        assert_eq!(cert.not_before, None);
+        assert_eq!(cert.not_after.as_deref(), Some("2055-01-01T00:00:00Z"));
+        assert_eq!(cert.subject, "CN=example.com, O=Example");
+    }
+
+    #[test]
+    fn test_oid_string() {
+        assert_eq!(
+            oid_string(&[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01]),
+            "1.2.840.113549.1.1.1"
+        );
+        assert_eq!(oid_string(&[0x55, 0x1d, 0x11]), "2.5.29.17");
+        assert_eq!(oid_string(&[]), "");
+    }
+}
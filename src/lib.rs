@@ -1,10 +1,28 @@
 mod bindings;
 mod cel_functions;
+#[cfg(feature = "native")]
+pub mod compare;
+mod csp;
+#[cfg(feature = "native")]
+pub mod distributed;
 mod error;
+#[cfg(feature = "native")]
+pub mod evidence;
 pub mod exec;
+pub mod notify;
+mod ntlm;
 mod output;
 mod plan;
+#[cfg(feature = "python")]
+mod python;
 pub mod record;
+#[cfg(feature = "native")]
+pub mod repro;
+pub mod secret;
+#[cfg(feature = "native")]
+pub mod smoke;
+pub mod testing;
+pub mod wsdl;
 
 pub use output::*;
 pub use plan::*;
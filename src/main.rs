@@ -1,12 +1,25 @@
 use anyhow::anyhow;
 use async_broadcast::{broadcast, Receiver, RecvError, Sender};
+use cel_interpreter::Value;
 use clap::{Parser, ValueEnum};
 use devil::exec::Executor;
-use devil::record::{BigQueryWriter, FileWriter, RecordWriter, StdoutWriter};
-use devil::{Normalized, Plan, ProtocolDiscriminants, RunName, RunOutput, StepOutput};
+use devil::notify::{Notification, Notifier, Severity, WebhookFormat, WebhookNotifier};
+use devil::record::{
+    BigQueryWriter, DirectoryWriter, FileWriter, RecordWriter, S3Writer, SqliteWriter, StdoutWriter,
+};
+#[cfg(feature = "keychain")]
+use devil::secret::KeychainSecrets;
+#[cfg(feature = "vault")]
+use devil::secret::VaultSecrets;
+use devil::secret::{self, EnvSecrets, FileSecrets, SecretProvider};
+use devil::{
+    Diagnostic, Normalized, Plan, ProtocolDiscriminants, RunName, RunOutput, StepOutput,
+    TargetSummary, TargetsSummary,
+};
 use futures::future::try_join_all;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
+use std::io::{BufRead, IsTerminal, Write};
 use std::sync::Arc;
 use strum::Display;
 use tokio::spawn;
@@ -48,6 +61,99 @@ struct Args {
     /// Print more details.
     #[arg(long)]
     debug: bool,
+
+    /// Run plan shards sent by a controller, listening on ADDR. Plans must be signed with the
+    /// secret in DEVIL_WORKER_SECRET.
+    #[arg(long, value_name = "ADDR", conflicts_with_all = ["file", "workers"])]
+    worker: Option<String>,
+
+    /// Split each step's jobs across the workers at ADDR, printing the merged steps as JSON.
+    /// Assignments are signed with the secret in DEVIL_WORKER_SECRET.
+    #[arg(
+        long,
+        value_name = "ADDR",
+        value_delimiter = ',',
+        conflicts_with = "out"
+    )]
+    workers: Vec<String>,
+
+    /// Compare two runs stored in a SQLite output, printing the report as JSON and exiting with
+    /// an error if latency regressed or there are new findings.
+    #[arg(long, num_args = 3, value_names = ["DB", "BASE", "HEAD"], conflicts_with = "file")]
+    compare: Vec<String>,
+
+    /// Package a step's findings, outputs, and wire bytes from a SQLite output into a tar archive,
+    /// printing its manifest as JSON. The plan's step is included if FILE is given.
+    #[arg(long, num_args = 4, value_names = ["DB", "RUN", "STEP", "ARCHIVE"])]
+    export_evidence: Vec<String>,
+
+    /// Print a plan reproducing a step from a run stored in a SQLite output, with the values its
+    /// expressions resolved to inlined. Reads the original plan from FILE.
+    #[arg(long, num_args = 3, value_names = ["DB", "RUN", "STEP"], requires = "file")]
+    repro: Vec<String>,
+
+    /// Print a smoke profile of RUN in a SQLite output: a plan with only the steps that succeeded,
+    /// and the ones they read from, for monitoring the target. Reads the original plan from FILE.
+    #[arg(long, num_args = 2, value_names = ["DB", "RUN"], requires = "file")]
+    smoke: Vec<String>,
+
+    /// Also search responses for the markers sent in RUN of a SQLite output, flagging any found
+    /// as stored injection.
+    #[arg(long, num_args = 2, value_names = ["DB", "RUN"], requires = "file")]
+    verify_markers: Vec<String>,
+
+    /// Make the mean response timings of each step in RUN of a SQLite output available to
+    /// baseline_timing() in CEL.
+    #[arg(long, num_args = 2, value_names = ["DB", "RUN"], requires = "file")]
+    timing_baseline: Vec<String>,
+
+    /// Send findings and failures to a webhook, like -n url=URL,format=slack,min_severity=high.
+    #[arg(short, long, value_parser = parse_notify)]
+    notify: Vec<Notify>,
+
+    /// Print a plan with a step for each SOAP operation in a WSDL file.
+    #[arg(long, value_name = "WSDL", conflicts_with_all = ["file", "compare"])]
+    import_wsdl: Option<String>,
+
+    /// How much a step's mean response time can grow before --compare counts it as a regression.
+    #[arg(long, default_value_t = 0.2)]
+    latency_threshold: f64,
+
+    /// Emulate a network profile, like 3g, satellite, lossy-wifi, or one in devil.network, for
+    /// steps that don't set run.network.
+    #[arg(long, value_name = "PROFILE")]
+    network: Option<String>,
+
+    /// Run steps marked destructive without asking. Otherwise they're confirmed interactively,
+    /// or refused if stdin isn't a terminal.
+    #[arg(long)]
+    allow_destructive: bool,
+
+    /// Run command steps, which start local programs with the plan's arguments and environment.
+    /// Only use this with plans you trust, since commands aren't isolated from this host.
+    #[arg(long)]
+    allow_commands: bool,
+
+    /// Run script steps. Scripts can't reach this host's files or network, but use its CPU up to
+    /// their script.max_operations.
+    #[arg(long)]
+    allow_scripts: bool,
+
+    /// Look up secrets from files named after them in DIR before the DEVIL_SECRET_* environment
+    /// variables.
+    #[arg(long, value_name = "DIR")]
+    secrets_dir: Option<String>,
+
+    /// Look up secrets from passwords for SERVICE in the OS keychain.
+    #[cfg(feature = "keychain")]
+    #[arg(long, value_name = "SERVICE")]
+    keychain_service: Option<String>,
+
+    /// Look up secrets from the Vault KV engine mounted at MOUNT, using VAULT_ADDR and
+    /// VAULT_TOKEN.
+    #[cfg(feature = "vault")]
+    #[arg(long, value_name = "MOUNT")]
+    vault_mount: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -81,6 +187,31 @@ enum Output {
         #[serde(default)]
         normalize: Normalize,
     },
+    Directory {
+        directory: String,
+        #[serde(default)]
+        layers: Vec<Protocol>,
+        #[serde(default)]
+        normalize: Normalize,
+    },
+    S3 {
+        bucket: String,
+        #[serde(default)]
+        prefix: String,
+        endpoint: Option<String>,
+        region: Option<String>,
+        #[serde(default)]
+        layers: Vec<Protocol>,
+        #[serde(default)]
+        normalize: Normalize,
+    },
+    Sqlite {
+        sqlite: String,
+        #[serde(default)]
+        layers: Vec<Protocol>,
+        #[serde(default)]
+        normalize: Normalize,
+    },
 }
 
 fn parse_outputs(s: &str) -> anyhow::Result<Output> {
@@ -100,6 +231,39 @@ fn parse_outputs(s: &str) -> anyhow::Result<Output> {
     Ok(serde_json::from_value(serde_json::Value::Object(args)).unwrap())
 }
 
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Notify {
+    url: url::Url,
+    #[serde(default)]
+    format: WebhookFormat,
+    #[serde(default = "Notify::default_min_severity")]
+    min_severity: Severity,
+}
+
+impl Notify {
+    fn default_min_severity() -> Severity {
+        Severity::Medium
+    }
+}
+
+fn parse_notify(s: &str) -> anyhow::Result<Notify> {
+    let args = s
+        .split(",")
+        .map(|pair| {
+            pair.split_once("=")
+                .map(|(k, v)| {
+                    (
+                        k.trim().to_string(),
+                        serde_json::Value::String(v.trim().into()),
+                    )
+                })
+                .ok_or_else(|| anyhow!("invalid notify flag format"))
+        })
+        .try_collect()?;
+    Ok(serde_json::from_value(serde_json::Value::Object(args))?)
+}
+
 #[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 enum Normalize {
@@ -134,18 +298,21 @@ impl From<Normalize> for devil::Normalize {
 #[serde(rename_all = "snake_case")]
 enum Protocol {
     Graphql,
+    Grpc,
     Http,
     H1,
     H1c,
     H2,
     H2c,
+    H3,
     RawH2,
     RawH2c,
     Tls,
     Tcp,
     RawTcp,
     //Udp,
-    //Quic,
+    Quic,
+    Ws,
     //Ip,
 }
 
@@ -153,18 +320,21 @@ impl From<&Protocol> for devil::ProtocolDiscriminants {
     fn from(value: &Protocol) -> Self {
         match value {
             Protocol::Graphql => Self::Graphql,
+            Protocol::Grpc => Self::Grpc,
             Protocol::Http => Self::Http,
             Protocol::H1 => Self::H1,
             Protocol::H1c => Self::H1c,
             Protocol::H2 => Self::H2,
             Protocol::H2c => Self::H2c,
+            Protocol::H3 => Self::H3,
             Protocol::RawH2 => Self::RawH2,
             Protocol::RawH2c => Self::RawH2c,
             Protocol::Tls => Self::Tls,
             Protocol::Tcp => Self::Tcp,
             Protocol::RawTcp => Self::RawTcp,
             //Protocol::Udp => Self::Udp,
-            //Protocol::Quic => Self::Quic,
+            Protocol::Quic => Self::Quic,
+            Protocol::Ws => Self::Ws,
             //Protocol::Ip => Self::Ip,
         }
     }
@@ -198,6 +368,45 @@ async fn main() -> anyhow::Result<()> {
         .init();
 
     let mut args = Args::parse();
+    if let Some(addr) = &args.worker {
+        return devil::distributed::serve(addr, &worker_secret()?).await;
+    }
+    if let Some(path) = &args.import_wsdl {
+        let text = tokio::fs::read_to_string(path).await?;
+        let name = std::path::Path::new(path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy())
+            .unwrap_or_default();
+        print!("{}", devil::wsdl::import(&text, &name)?);
+        return Ok(());
+    }
+    if let [db, base, head] = args.compare.as_slice() {
+        let report = devil::compare::compare(db, base, head, args.latency_threshold)?;
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        if report.regressed() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if let [db, run, step, archive] = args.export_evidence.as_slice() {
+        let plan = match args.file.first() {
+            Some(path) => Some(tokio::fs::read_to_string(path).await?),
+            None => None,
+        };
+        let manifest = devil::evidence::export(db, run, step, plan.as_deref(), archive)?;
+        println!("{}", serde_json::to_string_pretty(&manifest)?);
+        return Ok(());
+    }
+    if let ([db, run, step], Some(path)) = (args.repro.as_slice(), args.file.first()) {
+        let text = tokio::fs::read_to_string(path).await?;
+        print!("{}", devil::repro::from_run(db, run, step, &text)?);
+        return Ok(());
+    }
+    if let ([db, run], Some(path)) = (args.smoke.as_slice(), args.file.first()) {
+        let text = tokio::fs::read_to_string(path).await?;
+        print!("{}", devil::smoke::from_run(db, run, &text)?);
+        return Ok(());
+    }
     if args.out.is_empty() {
         args.out.push(Output::Stdout {
             format: args.format.unwrap_or_default(),
@@ -207,6 +416,9 @@ async fn main() -> anyhow::Result<()> {
     }
 
     let writers = try_join_all(args.out.into_iter().map(Writer::new)).await?;
+    // Steps are streamed to writers as they finish, so the whole run only needs to be kept for
+    // writers that output it unnormalized.
+    let keep_run = writers.iter().any(|w| w.normalize == Normalize::None);
 
     let (mut sender, recv) = broadcast(args.out_buffer.try_into().unwrap());
 
@@ -220,6 +432,9 @@ async fn main() -> anyhow::Result<()> {
                         error!("write output to {}: {e}", w.inner.name())
                     }
                 }
+                if let Err(e) = w.inner.finish().await {
+                    error!("finish output to {}: {e}", w.inner.name())
+                }
             })
         })
         .collect_vec();
@@ -227,35 +442,158 @@ async fn main() -> anyhow::Result<()> {
     // Allow the queue to drain properly.
     drop(recv);
 
+    let notifiers: Vec<Box<dyn Notifier>> = args
+        .notify
+        .iter()
+        .map(|n| {
+            Box::new(WebhookNotifier::new(
+                n.url.clone(),
+                n.format,
+                n.min_severity,
+            )) as Box<dyn Notifier>
+        })
+        .collect();
+
+    let mut secret_providers: Vec<Box<dyn SecretProvider>> = Vec::new();
+    #[cfg(feature = "vault")]
+    if let Some(mount) = &args.vault_mount {
+        secret_providers.push(Box::new(VaultSecrets::from_env(mount)?));
+    }
+    #[cfg(feature = "keychain")]
+    if let Some(service) = &args.keychain_service {
+        secret_providers.push(Box::new(KeychainSecrets::new(service)));
+    }
+    if let Some(dir) = &args.secrets_dir {
+        secret_providers.push(Box::new(FileSecrets::new(dir)));
+    }
+    secret_providers.push(Box::new(EnvSecrets::default()));
+    secret::set_providers(secret_providers);
+
     for file in &args.file {
         let buffer = std::fs::read(file)?;
         let text = String::from_utf8(buffer)?;
-        let plan = Plan::parse(&text)?;
+        let plan = Plan::parse(&text).map_err(|e| match e.downcast::<Diagnostic>() {
+            Ok(d) => d.with_file(file).into(),
+            Err(e) => e,
+        })?;
+        for warning in Plan::check(&text) {
+            warn!("{}", warning.with_file(file));
+        }
         if args.debug {
             println!("query plan: {:#?}", plan);
         }
         if args.dry_run {
             return Ok(());
         }
+        if !args.workers.is_empty() {
+            let secret = worker_secret()?;
+            for step in devil::distributed::coordinate(&text, &args.workers, &secret).await? {
+                println!("{step}");
+            }
+            continue;
+        }
 
-        let mut plan_output = RunOutput::new(RunName::new(plan.name.clone()));
-        let mut executor = Executor::new(&plan, plan_output.name.clone())?;
-        for (name, _) in plan.steps.iter() {
-            let step_output = Arc::new(executor.next().await?);
-            send(
-                &mut sender,
-                FlushMessages::Step(step_output.clone()),
-                &args.overflow_behavior,
-            )
-            .await;
-            plan_output.steps.insert(name.clone(), step_output);
+        // Plans with targets run once per target, like an extra matrix dimension.
+        let targets = match &plan.targets {
+            Some(targets) => targets.load()?.into_iter().map(Some).collect(),
+            None => vec![None],
+        };
+        let mut summaries = Vec::new();
+        for target in targets {
+            let mut plan_output = RunOutput::new(RunName::new(plan.name.clone()));
+            // Parsed URLs end bare hosts with a slash, which would double up when appending a path.
+            plan_output.target = target
+                .as_ref()
+                .map(|url| url.as_str().trim_end_matches('/').to_owned());
+            let params = plan_output
+                .target
+                .iter()
+                .map(|url| ("target".to_owned(), Value::String(Arc::new(url.clone()))))
+                .collect();
+            let mut summary = plan_output
+                .target
+                .clone()
+                .map(|url| TargetSummary::new(url, plan_output.name.clone()));
+            let mut executor = Executor::with_params(&plan, plan_output.name.clone(), params)?
+                .with_plan_path(file);
+            if let Some(network) = &args.network {
+                executor = executor.with_network(network.clone())?;
+            }
+            if let [db, run] = args.verify_markers.as_slice() {
+                executor = executor.with_markers_from(db, run)?;
+            }
+            if let [db, run] = args.timing_baseline.as_slice() {
+                executor = executor.with_timing_baseline(db, run)?;
+            }
+            if args.allow_commands {
+                executor = executor.allow_commands();
+            }
+            if args.allow_scripts {
+                executor = executor.allow_scripts();
+            }
+            if args.allow_destructive {
+                executor = executor.allow_destructive();
+            } else if std::io::stdin().is_terminal() {
+                executor = executor.with_destructive_confirmation(Arc::new(confirm_destructive));
+            }
+            // Steps can be added by crawls or skipped by on_error jumps and devil.budget, so run
+            // whatever the executor has left rather than the plan's steps.
+            while executor.current().is_some() {
+                let step_output = match executor.next().await {
+                    Ok(step) => Arc::new(step),
+                    Err(e) => {
+                        let failure = Notification {
+                            severity: Severity::Critical,
+                            source: plan_output.name.to_string(),
+                            kind: "run failed".to_owned(),
+                            message: format!("{e:#}"),
+                        };
+                        devil::notify::dispatch(&notifiers, &[failure]).await;
+                        // One broken target shouldn't stop the rest of the fleet.
+                        let Some(summary) = &mut summary else {
+                            return Err(e);
+                        };
+                        summary.error = Some(format!("{e:#}"));
+                        break;
+                    }
+                };
+                devil::notify::dispatch(&notifiers, &Notification::for_step(&step_output)).await;
+                if let Some(summary) = &mut summary {
+                    summary.add_step(&step_output);
+                }
+                send(
+                    &mut sender,
+                    FlushMessages::Step(step_output.clone()),
+                    &args.overflow_behavior,
+                )
+                .await;
+                if keep_run {
+                    plan_output
+                        .steps
+                        .insert(step_output.name.step.clone(), step_output);
+                }
+            }
+            if keep_run {
+                plan_output.usage = Some(executor.usage());
+                send(
+                    &mut sender,
+                    FlushMessages::Plan(Arc::new(plan_output)),
+                    &args.overflow_behavior,
+                )
+                .await;
+            }
+            summaries.extend(summary);
+        }
+        if !summaries.is_empty() {
+            // Printed to stderr so it doesn't interleave with records written to stdout.
+            eprint!(
+                "{}",
+                TargetsSummary {
+                    plan: plan.name.clone(),
+                    targets: summaries,
+                }
+            );
         }
-        send(
-            &mut sender,
-            FlushMessages::Plan(Arc::new(plan_output)),
-            &args.overflow_behavior,
-        )
-        .await;
     }
 
     // Shutdown and wait for all writers.
@@ -265,6 +603,27 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// The secret shared by a controller and its workers, read from the environment so it stays out
+/// of the process list.
+fn worker_secret() -> anyhow::Result<String> {
+    use devil::distributed::SECRET_ENV;
+    std::env::var(SECRET_ENV)
+        .ok()
+        .filter(|secret| !secret.is_empty())
+        .ok_or_else(|| anyhow!("{SECRET_ENV} must be set to the secret shared with the workers"))
+}
+
+/// Ask on the terminal whether to run a destructive step.
+fn confirm_destructive(step: &str) -> bool {
+    eprint!("step {step} is marked destructive, run it? [y/N] ");
+    let _ = std::io::stderr().flush();
+    let mut answer = String::new();
+    if std::io::stdin().lock().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim(), "y" | "Y" | "yes")
+}
+
 async fn send(sender: &mut Sender<FlushMessages>, out: FlushMessages, overflow: &OverflowBehavior) {
     let broadcast_result = sender.broadcast_direct(out).await;
     match (broadcast_result, overflow) {
@@ -287,9 +646,11 @@ enum FlushMessages {
 
 impl FlushMessages {
     fn normalize(self, target: Normalize) -> Vec<Normalized> {
-        match self {
-            Self::Plan(p) => p.normalize(target.into()),
-            Self::Step(s) => s.normalize(target.into()),
+        match (self, target) {
+            // Normalized writers already got each step as it finished.
+            (Self::Plan(p), Normalize::None) => p.normalize(target.into()),
+            (Self::Plan(_), _) | (Self::Step(_), Normalize::None) => Vec::new(),
+            (Self::Step(s), _) => s.normalize(target.into()),
             //Self::Job(j) => j.normalize(target.into()),
         }
     }
@@ -366,6 +727,50 @@ impl Writer {
                     .collect(),
                 normalize,
             }),
+            Output::Directory {
+                directory,
+                layers,
+                normalize,
+            } => Ok(Writer {
+                inner: RecordWriter::Sink(Box::new(DirectoryWriter::new(&directory).await?)),
+                layers: layers
+                    .iter()
+                    .map(devil::ProtocolDiscriminants::from)
+                    .collect(),
+                normalize,
+            }),
+            Output::S3 {
+                bucket,
+                prefix,
+                endpoint,
+                region,
+                layers,
+                normalize,
+            } => Ok(Writer {
+                inner: RecordWriter::Sink(Box::new(S3Writer::new(
+                    &bucket,
+                    prefix,
+                    endpoint.as_deref(),
+                    region.as_deref(),
+                )?)),
+                layers: layers
+                    .iter()
+                    .map(devil::ProtocolDiscriminants::from)
+                    .collect(),
+                normalize,
+            }),
+            Output::Sqlite {
+                sqlite,
+                layers,
+                normalize,
+            } => Ok(Writer {
+                inner: RecordWriter::Sink(Box::new(SqliteWriter::new(&sqlite)?)),
+                layers: layers
+                    .iter()
+                    .map(devil::ProtocolDiscriminants::from)
+                    .collect(),
+                normalize,
+            }),
         }
     }
 
@@ -0,0 +1,146 @@
+//! Alerts for findings and failures, so long campaigns can get a human's attention when
+//! something interesting happens.
+
+use std::fmt::Debug;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use strum::Display;
+use tracing::warn;
+
+use crate::StepOutput;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum Severity {
+    Info,
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Notification {
+    pub severity: Severity,
+    /// The dotted name of the step or run it's about.
+    pub source: String,
+    pub kind: String,
+    pub message: String,
+}
+
+impl Notification {
+    /// The notifications worth sending for a finished step.
+    pub fn for_step(step: &StepOutput) -> Vec<Self> {
+        let mut out = Vec::new();
+        for error in &step.errors {
+            out.push(Self {
+                severity: Severity::Medium,
+                source: step.name.to_string(),
+                kind: error.kind.clone(),
+                message: error.message.clone(),
+            });
+        }
+        for finding in step.takeover.iter().flat_map(|t| &t.findings) {
+            out.push(Self {
+                severity: Severity::High,
+                source: step.name.to_string(),
+                kind: "takeover".to_owned(),
+                message: format!(
+                    "possible {} takeover of {} via {}: {}",
+                    finding.service, finding.host, finding.target, finding.evidence,
+                ),
+            });
+        }
+        out
+    }
+}
+
+#[async_trait]
+pub trait Notifier: Debug + Send + Sync {
+    /// The least severe notification to send.
+    fn min_severity(&self) -> Severity;
+    async fn notify(&self, notification: &Notification) -> anyhow::Result<()>;
+}
+
+/// Send each notification to every notifier that wants it. Failures are logged rather than
+/// returned so a broken webhook doesn't stop the run.
+pub async fn dispatch(notifiers: &[Box<dyn Notifier>], notifications: &[Notification]) {
+    for n in notifications {
+        for notifier in notifiers {
+            if n.severity < notifier.min_severity() {
+                continue;
+            }
+            if let Err(e) = notifier.notify(n).await {
+                warn!("send {} notification: {e:#}", n.kind);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookFormat {
+    /// The notification as a JSON object.
+    #[default]
+    Json,
+    /// A Slack compatible `{"text": ...}` message, also accepted by Mattermost and others.
+    Slack,
+}
+
+/// POSTs notifications to a URL.
+#[cfg(feature = "native")]
+#[derive(Debug)]
+pub struct WebhookNotifier {
+    client: reqwest::Client,
+    url: url::Url,
+    format: WebhookFormat,
+    min_severity: Severity,
+}
+
+#[cfg(feature = "native")]
+impl WebhookNotifier {
+    pub fn new(url: url::Url, format: WebhookFormat, min_severity: Severity) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url,
+            format,
+            min_severity,
+        }
+    }
+}
+
+#[cfg(feature = "native")]
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    fn min_severity(&self) -> Severity {
+        self.min_severity
+    }
+
+    async fn notify(&self, notification: &Notification) -> anyhow::Result<()> {
+        let body = match self.format {
+            WebhookFormat::Json => crate::secret::to_json(notification)?,
+            WebhookFormat::Slack => {
+                let mut body = serde_json::json!({
+                    "text": format!(
+                        "[{}] {} in {}: {}",
+                        notification.severity,
+                        notification.kind,
+                        notification.source,
+                        notification.message,
+                    ),
+                });
+                crate::secret::redact_json(&mut body);
+                body
+            }
+        };
+        self.client
+            .post(self.url.clone())
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
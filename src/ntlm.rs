@@ -0,0 +1,326 @@
+//! Message encoding for the NTLM challenge-response handshake used by the NTLM and Negotiate
+//! HTTP authentication schemes.
+
+use anyhow::{anyhow, bail};
+use byteorder::{ByteOrder, LittleEndian};
+use hmac::digest::KeyInit;
+use hmac::{Hmac, Mac};
+use md4::{Digest, Md4};
+use rand::RngCore;
+
+const SIGNATURE: &[u8; 8] = b"NTLMSSP\0";
+
+const NEGOTIATE_UNICODE: u32 = 0x0000_0001;
+const NEGOTIATE_OEM: u32 = 0x0000_0002;
+const REQUEST_TARGET: u32 = 0x0000_0004;
+const NEGOTIATE_NTLM: u32 = 0x0000_0200;
+const NEGOTIATE_ALWAYS_SIGN: u32 = 0x0000_8000;
+const NEGOTIATE_EXTENDED_SESSIONSECURITY: u32 = 0x0008_0000;
+const NEGOTIATE_TARGET_INFO: u32 = 0x0080_0000;
+const NEGOTIATE_128: u32 = 0x2000_0000;
+const NEGOTIATE_56: u32 = 0x8000_0000;
+
+const NEGOTIATE_FLAGS: u32 = NEGOTIATE_UNICODE
+    | NEGOTIATE_OEM
+    | REQUEST_TARGET
+    | NEGOTIATE_NTLM
+    | NEGOTIATE_ALWAYS_SIGN
+    | NEGOTIATE_EXTENDED_SESSIONSECURITY
+    | NEGOTIATE_TARGET_INFO
+    | NEGOTIATE_128
+    | NEGOTIATE_56;
+
+/// Seconds between the Windows FILETIME epoch (1601) and the unix epoch.
+const FILETIME_UNIX_OFFSET: u64 = 11_644_473_600;
+
+const AV_ID_TIMESTAMP: u16 = 7;
+
+/// Build the type 1 message which starts the handshake.
+pub fn negotiate() -> Vec<u8> {
+    let mut msg = Vec::with_capacity(32);
+    msg.extend_from_slice(SIGNATURE);
+    push_u32(&mut msg, 1);
+    push_u32(&mut msg, NEGOTIATE_FLAGS);
+    // Empty domain and workstation buffers.
+    msg.extend_from_slice(&[0; 16]);
+    msg
+}
+
+/// The fields of a server's type 2 message needed to respond.
+#[derive(Debug)]
+pub struct Challenge {
+    pub flags: u32,
+    pub server_challenge: [u8; 8],
+    pub target_info: Vec<u8>,
+}
+
+impl Challenge {
+    pub fn parse(msg: &[u8]) -> crate::Result<Self> {
+        if msg.len() < 32 || &msg[..8] != SIGNATURE {
+            bail!("not an NTLM message");
+        }
+        if LittleEndian::read_u32(&msg[8..12]) != 2 {
+            bail!("expected an NTLM challenge message");
+        }
+        let flags = LittleEndian::read_u32(&msg[20..24]);
+        let target_info = if msg.len() >= 48 {
+            let len = usize::from(LittleEndian::read_u16(&msg[40..42]));
+            let offset = usize::try_from(LittleEndian::read_u32(&msg[44..48]))?;
+            msg.get(offset..offset + len)
+                .ok_or_else(|| anyhow!("NTLM target info out of bounds"))?
+                .to_vec()
+        } else {
+            Vec::new()
+        };
+        Ok(Self {
+            flags,
+            server_challenge: msg[24..32].try_into().unwrap(),
+            target_info,
+        })
+    }
+
+    /// The server's timestamp from the target info, if it sent one.
+    fn timestamp(&self) -> Option<[u8; 8]> {
+        let mut info = self.target_info.as_slice();
+        while info.len() >= 4 {
+            let id = LittleEndian::read_u16(&info[..2]);
+            let len = usize::from(LittleEndian::read_u16(&info[2..4]));
+            let value = info.get(4..4 + len)?;
+            if id == AV_ID_TIMESTAMP {
+                return value.try_into().ok();
+            }
+            info = &info[4 + len..];
+        }
+        None
+    }
+}
+
+/// Build the type 3 message answering a challenge with NTLMv2 responses.
+pub fn authenticate(
+    challenge: &Challenge,
+    username: &str,
+    password: &str,
+    domain: &str,
+    workstation: &str,
+) -> crate::Result<Vec<u8>> {
+    let mut client_challenge = [0; 8];
+    rand::thread_rng().fill_bytes(&mut client_challenge);
+    let timestamp = challenge.timestamp().unwrap_or_else(|| {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        let filetime = (now.as_secs() + FILETIME_UNIX_OFFSET) * 10_000_000
+            + u64::from(now.subsec_nanos() / 100);
+        filetime.to_le_bytes()
+    });
+    authenticate_with(
+        challenge,
+        username,
+        password,
+        domain,
+        workstation,
+        client_challenge,
+        timestamp,
+    )
+}
+
+fn authenticate_with(
+    challenge: &Challenge,
+    username: &str,
+    password: &str,
+    domain: &str,
+    workstation: &str,
+    client_challenge: [u8; 8],
+    timestamp: [u8; 8],
+) -> crate::Result<Vec<u8>> {
+    let nt_hash = Md4::digest(utf16le(password));
+    let v2_hash = hmac_md5(&nt_hash, &utf16le(&(username.to_uppercase() + domain)));
+
+    let mut blob = Vec::with_capacity(32 + challenge.target_info.len());
+    push_u32(&mut blob, 0x0000_0101);
+    push_u32(&mut blob, 0);
+    blob.extend_from_slice(&timestamp);
+    blob.extend_from_slice(&client_challenge);
+    push_u32(&mut blob, 0);
+    blob.extend_from_slice(&challenge.target_info);
+    push_u32(&mut blob, 0);
+
+    let nt_proof = hmac_md5(&v2_hash, &[&challenge.server_challenge[..], &blob].concat());
+    let nt_response = [&nt_proof[..], &blob].concat();
+    let lm_response = [
+        &hmac_md5(
+            &v2_hash,
+            &[challenge.server_challenge, client_challenge].concat(),
+        )[..],
+        &client_challenge,
+    ]
+    .concat();
+
+    let unicode = challenge.flags & NEGOTIATE_UNICODE != 0;
+    let encode = |s: &str| {
+        if unicode {
+            utf16le(s)
+        } else {
+            s.as_bytes().to_vec()
+        }
+    };
+    let payloads = [
+        lm_response,
+        nt_response,
+        encode(domain),
+        encode(username),
+        encode(workstation),
+        // No session key is exchanged.
+        Vec::new(),
+    ];
+
+    const HEADER_LEN: usize = 64;
+    let mut msg = Vec::with_capacity(HEADER_LEN + payloads.iter().map(Vec::len).sum::<usize>());
+    msg.extend_from_slice(SIGNATURE);
+    push_u32(&mut msg, 3);
+    let mut offset = HEADER_LEN;
+    for payload in &payloads {
+        push_security_buffer(&mut msg, payload.len(), offset)?;
+        offset += payload.len();
+    }
+    push_u32(&mut msg, challenge.flags & NEGOTIATE_FLAGS);
+    for payload in &payloads {
+        msg.extend_from_slice(payload);
+    }
+    Ok(msg)
+}
+
+fn hmac_md5(key: &[u8], data: &[u8]) -> [u8; 16] {
+    let mut mac =
+        <Hmac<md5::Md5> as KeyInit>::new_from_slice(key).expect("hmac accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().into()
+}
+
+fn utf16le(s: &str) -> Vec<u8> {
+    s.encode_utf16().flat_map(u16::to_le_bytes).collect()
+}
+
+fn push_u32(buf: &mut Vec<u8>, val: u32) {
+    buf.extend_from_slice(&val.to_le_bytes());
+}
+
+fn push_security_buffer(buf: &mut Vec<u8>, len: usize, offset: usize) -> crate::Result<()> {
+    let len = u16::try_from(len).map_err(|_| anyhow!("NTLM field of {len} bytes is too long"))?;
+    let offset =
+        u32::try_from(offset).map_err(|_| anyhow!("NTLM message of {offset} bytes is too long"))?;
+    buf.extend_from_slice(&len.to_le_bytes());
+    buf.extend_from_slice(&len.to_le_bytes());
+    push_u32(buf, offset);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Values from MS-NLMP 4.2.4, NTLMv2 Authentication.
+    const SERVER_CHALLENGE: [u8; 8] = [0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef];
+    const CLIENT_CHALLENGE: [u8; 8] = [0xaa; 8];
+
+    fn challenge() -> Challenge {
+        let mut target_info = Vec::new();
+        target_info.extend_from_slice(&[0x02, 0x00, 0x0c, 0x00]);
+        target_info.extend_from_slice(&utf16le("Domain"));
+        target_info.extend_from_slice(&[0x01, 0x00, 0x0c, 0x00]);
+        target_info.extend_from_slice(&utf16le("Server"));
+        target_info.extend_from_slice(&[0x00, 0x00, 0x00, 0x00]);
+        Challenge {
+            flags: 0xe28a_8233,
+            server_challenge: SERVER_CHALLENGE,
+            target_info,
+        }
+    }
+
+    fn field(msg: &[u8], header_offset: usize) -> &[u8] {
+        let len = usize::from(LittleEndian::read_u16(&msg[header_offset..]));
+        let offset = LittleEndian::read_u32(&msg[header_offset + 4..]) as usize;
+        &msg[offset..offset + len]
+    }
+
+    #[test]
+    fn test_ntowfv2() {
+        let nt_hash = Md4::digest(utf16le("Password"));
+        assert_eq!(
+            hmac_md5(&nt_hash, &utf16le("USERDomain")),
+            [
+                0x0c, 0x86, 0x8a, 0x40, 0x3b, 0xfd, 0x7a, 0x93, 0xa3, 0x00, 0x1e, 0xf2, 0x2e, 0xf0,
+                0x2e, 0x3f,
+            ],
+        );
+    }
+
+    #[test]
+    fn test_authenticate_v2_responses() {
+        let msg = authenticate_with(
+            &challenge(),
+            "User",
+            "Password",
+            "Domain",
+            "COMPUTER",
+            CLIENT_CHALLENGE,
+            [0; 8],
+        )
+        .unwrap();
+        assert_eq!(&msg[..8], SIGNATURE);
+        assert_eq!(LittleEndian::read_u32(&msg[8..12]), 3);
+
+        assert_eq!(
+            field(&msg, 12),
+            [
+                0x86, 0xc3, 0x50, 0x97, 0xac, 0x9c, 0xec, 0x10, 0x25, 0x54, 0x76, 0x4a, 0x57, 0xcc,
+                0xcc, 0x19, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa,
+            ],
+        );
+
+        let nt_response = field(&msg, 20);
+        assert_eq!(
+            nt_response[..16],
+            [
+                0x68, 0xcd, 0x0a, 0xb8, 0x51, 0xe5, 0x1c, 0x96, 0xaa, 0xbc, 0x92, 0x7b, 0xeb, 0xef,
+                0x6a, 0x1c,
+            ],
+        );
+        let mut blob = vec![0x01, 0x01, 0, 0, 0, 0, 0, 0];
+        blob.extend_from_slice(&[0; 8]);
+        blob.extend_from_slice(&CLIENT_CHALLENGE);
+        blob.extend_from_slice(&[0; 4]);
+        blob.extend_from_slice(&challenge().target_info);
+        blob.extend_from_slice(&[0; 4]);
+        assert_eq!(nt_response[16..], blob);
+
+        assert_eq!(field(&msg, 28), utf16le("Domain"));
+        assert_eq!(field(&msg, 36), utf16le("User"));
+        assert_eq!(field(&msg, 44), utf16le("COMPUTER"));
+        assert!(field(&msg, 52).is_empty());
+    }
+
+    #[test]
+    fn test_authenticate_rejects_oversized_fields() {
+        let username = "u".repeat(usize::from(u16::MAX));
+        let err = authenticate(&challenge(), &username, "Password", "Domain", "").unwrap_err();
+        assert!(err.to_string().contains("too long"), "{err}");
+    }
+
+    #[test]
+    fn test_challenge_parse_bounds() {
+        let mut msg = SIGNATURE.to_vec();
+        msg.extend_from_slice(&2u32.to_le_bytes());
+        msg.extend_from_slice(&[0; 8]);
+        msg.extend_from_slice(&NEGOTIATE_UNICODE.to_le_bytes());
+        msg.extend_from_slice(&SERVER_CHALLENGE);
+        msg.extend_from_slice(&[0; 8]);
+        // Target info claims more bytes than were sent.
+        msg.extend_from_slice(&[0x10, 0x00, 0x10, 0x00, 0x30, 0x00, 0x00, 0x00]);
+        assert!(Challenge::parse(&msg).is_err());
+        msg.extend_from_slice(&[0; 16]);
+        let challenge = Challenge::parse(&msg).unwrap();
+        assert_eq!(challenge.server_challenge, SERVER_CHALLENGE);
+        assert_eq!(challenge.target_info, [0; 16]);
+    }
+}
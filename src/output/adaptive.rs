@@ -0,0 +1,36 @@
+use cel_interpreter::Duration;
+use devil_derive::BigQuerySchema;
+use serde::Serialize;
+
+use crate::AdaptiveAlgorithm;
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct AdaptivePlanOutput {
+    pub algorithm: AdaptiveAlgorithm,
+    /// Jobs slower than this count against the limit.
+    pub target_latency: Option<Duration>,
+    /// The highest fraction of failing jobs, smoothed over recent jobs, before backing off.
+    pub max_error_rate: Option<f64>,
+    pub initial: u64,
+    pub min: u64,
+    pub max: u64,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct AdaptiveOutput {
+    pub plan: AdaptivePlanOutput,
+    /// The concurrency limit each time it changed.
+    pub samples: Vec<ConcurrencySampleOutput>,
+    /// The highest limit reached without exceeding the targets.
+    pub max_healthy_limit: u64,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct ConcurrencySampleOutput {
+    /// Time since the step started.
+    pub elapsed: Duration,
+    pub limit: u64,
+    pub in_flight: u64,
+    pub latency: Duration,
+    pub error_rate: f64,
+}
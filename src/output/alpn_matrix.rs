@@ -0,0 +1,38 @@
+use devil_derive::BigQuerySchema;
+use serde::Serialize;
+use url::Url;
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct AlpnMatrixPlanOutput {
+    /// An https URL to request with each ALPN offer.
+    pub url: Url,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct AlpnMatrixOutput {
+    pub plan: AlpnMatrixPlanOutput,
+    /// One attempt offering h2, then http/1.1, then no ALPN extension at all.
+    pub attempts: Vec<AlpnAttemptOutput>,
+    /// The offers the server completed a handshake for.
+    pub accepted: Vec<String>,
+    /// Whether every successful attempt got the same status and body.
+    pub consistent: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct AlpnAttemptOutput {
+    /// The protocol offered, or none for a ClientHello without ALPN.
+    pub offered: String,
+    /// The protocol the server selected, if any.
+    pub negotiated: Option<String>,
+    /// The HTTP version the request was sent with.
+    pub http_version: String,
+    pub status_code: Option<u16>,
+    pub length: u64,
+    /// The base64 SHA-256 hash of the body.
+    pub body_sha256: Option<String>,
+    /// How the response differs from the first successful attempt.
+    pub differences: Vec<String>,
+    pub error: Option<String>,
+}
@@ -0,0 +1,24 @@
+use devil_derive::BigQuerySchema;
+use indexmap::IndexMap;
+use serde::Serialize;
+
+use super::ModuleOutput;
+
+#[derive(Debug, Clone)]
+pub struct AuthPlanOutput {
+    pub path: String,
+    pub params: IndexMap<String, cel_interpreter::Value>,
+    pub header: String,
+}
+
+/// How a step tagged with run.auth was authenticated.
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct AuthOutput {
+    /// The login's name in devil.auth.
+    pub name: String,
+    /// The login plans run for the step, either because no earlier step had logged in or because
+    /// the step was rejected as unauthorized.
+    pub logins: Vec<ModuleOutput>,
+    /// Whether the step was run again after logging in again.
+    pub replayed: bool,
+}
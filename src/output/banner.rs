@@ -0,0 +1,38 @@
+use cel_interpreter::Duration;
+use devil_derive::BigQuerySchema;
+use serde::Serialize;
+
+use super::MaybeUtf8;
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct BannerPlanOutput {
+    /// Targets in host:port form.
+    pub targets: Vec<String>,
+    /// Data sent after connecting, for services that wait for the client to speak first.
+    pub probe: Option<MaybeUtf8>,
+    /// How long to wait for each connection and banner.
+    pub timeout: Duration,
+    pub max_bytes: u64,
+    pub concurrency: u64,
+}
+
+/// What each target sent after connecting.
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct BannerOutput {
+    pub plan: BannerPlanOutput,
+    pub results: Vec<BannerResultOutput>,
+    /// Targets which accepted a connection.
+    pub open: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct BannerResultOutput {
+    pub target: String,
+    /// The resolved address connected to.
+    pub address: Option<String>,
+    pub open: bool,
+    pub banner: Option<MaybeUtf8>,
+    pub connect_duration: Option<Duration>,
+    pub time_to_first_byte: Option<Duration>,
+    pub error: Option<String>,
+}
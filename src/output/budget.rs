@@ -0,0 +1,14 @@
+use cel_interpreter::Duration;
+use devil_derive::BigQuerySchema;
+use serde::Serialize;
+
+/// Resources used by a step or run. Only traffic over TCP connections is counted.
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct UsageOutput {
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub connections: u64,
+    /// The number of jobs run.
+    pub attempts: u64,
+    pub duration: Duration,
+}
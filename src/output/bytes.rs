@@ -1,14 +1,17 @@
 use core::str;
+use std::borrow::Cow;
 use std::fmt::{Display, Write};
 use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 use std::{fmt::Debug, ops::Deref};
 
 use bytes::Bytes;
+#[cfg(feature = "native")]
 use gcp_bigquery_client::model::table_field_schema::TableFieldSchema;
 use serde::ser::SerializeStruct;
 use serde::Serialize;
 
+#[cfg(feature = "native")]
 use crate::record::BigQuerySchema;
 
 #[derive(Debug, Clone, Eq)]
@@ -19,6 +22,7 @@ pub enum BytesOutput {
     Bytes(Bytes),
 }
 
+#[cfg(feature = "native")]
 impl BigQuerySchema for BytesOutput {
     fn big_query_schema(name: &str) -> TableFieldSchema {
         // TODO: use bytes format with newer protobuf API.
@@ -67,7 +71,7 @@ impl Serialize for BytesOutput {
         if serializer.is_human_readable() {
             Base64Field(self).serialize(serializer)
         } else {
-            serializer.serialize_bytes(self)
+            BytesField(self).serialize(serializer)
         }
     }
 }
@@ -164,6 +168,7 @@ impl Default for &MaybeUtf8 {
     }
 }
 
+#[cfg(feature = "native")]
 impl BigQuerySchema for MaybeUtf8 {
     fn big_query_schema(name: &str) -> TableFieldSchema {
         TableFieldSchema::record(
@@ -259,7 +264,10 @@ impl Serialize for Utf8Field<'_> {
     where
         S: serde::Serializer,
     {
-        serializer.serialize_str(self.0)
+        match crate::secret::redact_for_output(self.0.as_bytes()) {
+            Cow::Borrowed(_) => serializer.serialize_str(self.0),
+            Cow::Owned(redacted) => serializer.serialize_str(&String::from_utf8_lossy(&redacted)),
+        }
     }
 }
 
@@ -271,7 +279,7 @@ impl Serialize for Base64Field<'_> {
         S: serde::Serializer,
     {
         serializer.collect_str(&base64::display::Base64Display::new(
-            &self.0,
+            &crate::secret::redact_for_output(self.0),
             &base64::prelude::BASE64_STANDARD,
         ))
     }
@@ -284,6 +292,6 @@ impl Serialize for BytesField<'_> {
     where
         S: serde::Serializer,
     {
-        serializer.serialize_bytes(self.0)
+        serializer.serialize_bytes(&crate::secret::redact_for_output(self.0))
     }
 }
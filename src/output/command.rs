@@ -0,0 +1,33 @@
+use cel_interpreter::Duration;
+use devil_derive::BigQuerySchema;
+use indexmap::IndexMap;
+use serde::Serialize;
+
+use super::MaybeUtf8;
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct CommandPlanOutput {
+    pub program: String,
+    pub args: Vec<String>,
+    pub stdin: Option<MaybeUtf8>,
+    /// The only environment variables set besides PATH.
+    pub env: IndexMap<String, String>,
+    /// The working directory, which defaults to a new temporary directory.
+    pub dir: Option<String>,
+    pub timeout: Duration,
+    /// How many bytes of each of stdout and stderr to keep.
+    pub max_output: u64,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct CommandOutput {
+    pub plan: CommandPlanOutput,
+    pub stdout: MaybeUtf8,
+    pub stderr: MaybeUtf8,
+    /// None if the program was killed by a signal or timed out.
+    pub exit_code: Option<i64>,
+    pub timed_out: bool,
+    /// Whether stdout or stderr was longer than max_output.
+    pub truncated: bool,
+    pub duration: Duration,
+}
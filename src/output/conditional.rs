@@ -0,0 +1,35 @@
+use devil_derive::BigQuerySchema;
+use serde::Serialize;
+use url::Url;
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct ConditionalPlanOutput {
+    pub url: Url,
+}
+
+/// How the server answered each conditional request compared to RFC 9110.
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct ConditionalOutput {
+    pub plan: ConditionalPlanOutput,
+    pub status_code: Option<u16>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub cases: Vec<ConditionalCaseOutput>,
+    /// How many cases got the expected status.
+    pub passed: u64,
+    pub failed: u64,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct ConditionalCaseOutput {
+    pub name: String,
+    /// The conditional headers sent, like `If-None-Match: "abc"`.
+    pub headers: Vec<String>,
+    pub expected_status: u16,
+    pub status_code: Option<u16>,
+    pub passed: bool,
+    /// Why the case failed beyond the status, like a 304 with a body.
+    pub issue: Option<String>,
+    pub error: Option<String>,
+}
@@ -0,0 +1,50 @@
+use devil_derive::BigQuerySchema;
+use serde::Serialize;
+use url::Url;
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct CrawlPlanOutput {
+    pub url: Url,
+    /// Only URLs starting with this prefix are fetched.
+    pub scope: String,
+    pub max_depth: u64,
+    pub max_pages: u64,
+    /// Whether to add a step submitting each discovered form.
+    pub form_steps: bool,
+}
+
+/// The surface discovered by a crawl.
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct CrawlOutput {
+    pub plan: CrawlPlanOutput,
+    pub pages: Vec<CrawlPageOutput>,
+    pub forms: Vec<CrawlFormOutput>,
+    /// The names of steps generated for forms.
+    pub form_steps: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct CrawlPageOutput {
+    pub url: Url,
+    pub depth: u64,
+    pub status_code: Option<u16>,
+    /// Every link on the page, including those out of scope.
+    pub links: Vec<Url>,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct CrawlFormOutput {
+    /// The page the form was found on.
+    pub page: Url,
+    pub action: Url,
+    pub method: String,
+    pub enctype: Option<String>,
+    pub fields: Vec<CrawlFieldOutput>,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct CrawlFieldOutput {
+    pub name: String,
+    pub kind: String,
+    pub value: Option<String>,
+}
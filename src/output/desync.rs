@@ -0,0 +1,40 @@
+use cel_interpreter::Duration;
+use devil_derive::BigQuerySchema;
+use serde::Serialize;
+
+/// A response on a reused HTTP/1 connection which doesn't line up with the request it should
+/// answer.
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct DesyncOutput {
+    /// The key of the job whose response was affected.
+    pub job: String,
+    pub kind: DesyncKind,
+    pub detail: String,
+    /// How many bytes followed the body's declared end.
+    pub extra_bytes: Option<u64>,
+    /// The response's time to first byte, and the step's median, for early responses.
+    pub time_to_first_byte: Option<Duration>,
+    pub median_time_to_first_byte: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, BigQuerySchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DesyncKind {
+    /// Bytes past the end of the response's declared framing, or a response that arrived much
+    /// sooner than the step's others as if it was already waiting on the connection.
+    EarlyBytes,
+    /// A second status line right after the response's declared framing.
+    DoubleResponse,
+    /// A request got no response, then the next request's response arrived early or doubled.
+    TimeoutThenBurst,
+}
+
+impl DesyncKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::EarlyBytes => "early_bytes",
+            Self::DoubleResponse => "double_response",
+            Self::TimeoutThenBurst => "timeout_then_burst",
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use cel_interpreter::Duration;
+use devil_derive::BigQuerySchema;
+use serde::Serialize;
+
+use super::{MaybeUtf8, TlsVersion};
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct DetectPlanOutput {
+    pub host: String,
+    pub port: u16,
+    pub alpn: Vec<MaybeUtf8>,
+    pub wait: Duration,
+    /// How long to wait for each connection and reply.
+    pub timeout: Duration,
+    pub local: Option<String>,
+}
+
+/// The protocol a port was found to speak, and the evidence for it.
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct DetectOutput {
+    pub plan: DetectPlanOutput,
+    /// The protocol identified. HTTP is named like the step field that speaks it: h2 or h1 over
+    /// TLS, or h1c without it. Other TLS services are tls, services which spoke first are named
+    /// by their banner or reply (ssh, smtp, ftp, pop3, imap, redis or mysql), anything else that
+    /// accepted the connection is tcp, and one that couldn't be made is closed.
+    pub verdict: String,
+    /// Whether the connection was accepted.
+    pub open: bool,
+    /// What the server sent before the client said anything.
+    pub banner: Option<MaybeUtf8>,
+    /// Whether a TLS handshake completed.
+    pub tls: bool,
+    pub tls_version: Option<TlsVersion>,
+    /// The protocol the server selected from plan.alpn.
+    pub alpn: Option<String>,
+    /// The status of the HTTP response to the probe request, over TLS if the handshake
+    /// completed.
+    pub status_code: Option<u16>,
+    /// The start of the reply to a plaintext HTTP request, if the server didn't speak TLS.
+    pub reply: Option<MaybeUtf8>,
+    pub errors: Vec<String>,
+}
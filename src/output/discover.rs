@@ -0,0 +1,27 @@
+use devil_derive::BigQuerySchema;
+use serde::Serialize;
+use url::Url;
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct DiscoverPlanOutput {
+    /// The site to discover, only its origin is used.
+    pub url: Url,
+    pub robots: bool,
+    pub sitemaps: bool,
+    /// The most sitemap documents to fetch, including those referenced by sitemap indexes.
+    pub max_sitemaps: u64,
+}
+
+/// Paths advertised by a site's robots.txt and sitemaps.
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct DiscoverOutput {
+    pub plan: DiscoverPlanOutput,
+    /// Allow and Disallow rules from robots.txt, without wildcards.
+    pub robots_paths: Vec<String>,
+    /// The sitemap documents fetched.
+    pub sitemaps: Vec<Url>,
+    /// Page URLs listed in sitemaps.
+    pub urls: Vec<Url>,
+    /// Unique same-origin paths from every source, in the order they were found.
+    pub paths: Vec<String>,
+}
@@ -0,0 +1,188 @@
+use std::str::FromStr;
+
+use anyhow::bail;
+use cel_interpreter::Duration;
+use devil_derive::BigQuerySchema;
+use serde::Serialize;
+
+use super::MaybeUtf8;
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct DnsPlanOutput {
+    pub name: String,
+    pub record_type: DnsRecordType,
+    /// The resolver to query, or the egress or system resolver if unset.
+    pub server: Option<String>,
+    pub transport: DnsTransport,
+    pub recursion_desired: bool,
+    pub timeout: Duration,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct DnsOutput {
+    pub plan: DnsPlanOutput,
+    /// The address the query was sent to.
+    pub server: Option<String>,
+    /// The query message, without the length prefix used over TCP.
+    pub query: Option<MaybeUtf8>,
+    /// The reply message, without the length prefix used over TCP.
+    pub reply: Option<MaybeUtf8>,
+    /// The response code from the reply header, like 0 for NOERROR or 3 for NXDOMAIN.
+    pub rcode: Option<u8>,
+    pub authoritative: bool,
+    /// Whether the reply was cut short to fit in a UDP datagram. Query over TCP for the rest.
+    pub truncated: bool,
+    pub recursion_available: bool,
+    pub answers: Vec<DnsRecordOutput>,
+    pub authority: Vec<DnsRecordOutput>,
+    pub additional: Vec<DnsRecordOutput>,
+    /// The time between sending the query and receiving the reply.
+    pub latency: Option<Duration>,
+    pub error: Option<String>,
+    pub duration: Duration,
+}
+
+impl DnsOutput {
+    /// The mnemonic for the response code, if it's a common one.
+    pub fn rcode_name(&self) -> Option<&'static str> {
+        Some(match self.rcode? {
+            0 => "NOERROR",
+            1 => "FORMERR",
+            2 => "SERVFAIL",
+            3 => "NXDOMAIN",
+            4 => "NOTIMP",
+            5 => "REFUSED",
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct DnsRecordOutput {
+    pub name: String,
+    pub record_type: DnsRecordType,
+    pub class: u16,
+    pub ttl: u32,
+    /// The record data in zone file presentation format, or RFC 3597 hex for unknown types.
+    pub data: String,
+    pub raw: MaybeUtf8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, BigQuerySchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DnsTransport {
+    Udp,
+    Tcp,
+}
+
+impl FromStr for DnsTransport {
+    type Err = crate::Error;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(match s {
+            "udp" => Self::Udp,
+            "tcp" => Self::Tcp,
+            _ => bail!("invalid dns transport {s}"),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, BigQuerySchema)]
+pub struct DnsRecordType {
+    pub parsed: Option<ParsedDnsRecordType>,
+    pub raw: u16,
+}
+
+impl From<u16> for DnsRecordType {
+    fn from(value: u16) -> Self {
+        Self {
+            parsed: match value {
+                1 => Some(ParsedDnsRecordType::A),
+                2 => Some(ParsedDnsRecordType::Ns),
+                5 => Some(ParsedDnsRecordType::Cname),
+                6 => Some(ParsedDnsRecordType::Soa),
+                12 => Some(ParsedDnsRecordType::Ptr),
+                15 => Some(ParsedDnsRecordType::Mx),
+                16 => Some(ParsedDnsRecordType::Txt),
+                28 => Some(ParsedDnsRecordType::Aaaa),
+                33 => Some(ParsedDnsRecordType::Srv),
+                _ => None,
+            },
+            raw: value,
+        }
+    }
+}
+
+impl From<ParsedDnsRecordType> for DnsRecordType {
+    fn from(value: ParsedDnsRecordType) -> Self {
+        Self {
+            parsed: Some(value),
+            raw: value.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, BigQuerySchema)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ParsedDnsRecordType {
+    A,
+    Ns,
+    Cname,
+    Soa,
+    Ptr,
+    Mx,
+    Txt,
+    Aaaa,
+    Srv,
+}
+
+impl From<ParsedDnsRecordType> for u16 {
+    fn from(value: ParsedDnsRecordType) -> Self {
+        match value {
+            ParsedDnsRecordType::A => 1,
+            ParsedDnsRecordType::Ns => 2,
+            ParsedDnsRecordType::Cname => 5,
+            ParsedDnsRecordType::Soa => 6,
+            ParsedDnsRecordType::Ptr => 12,
+            ParsedDnsRecordType::Mx => 15,
+            ParsedDnsRecordType::Txt => 16,
+            ParsedDnsRecordType::Aaaa => 28,
+            ParsedDnsRecordType::Srv => 33,
+        }
+    }
+}
+
+impl FromStr for ParsedDnsRecordType {
+    type Err = crate::Error;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(match s.to_ascii_uppercase().as_str() {
+            "A" => Self::A,
+            "NS" => Self::Ns,
+            "CNAME" => Self::Cname,
+            "SOA" => Self::Soa,
+            "PTR" => Self::Ptr,
+            "MX" => Self::Mx,
+            "TXT" => Self::Txt,
+            "AAAA" => Self::Aaaa,
+            "SRV" => Self::Srv,
+            _ => bail!("invalid dns record type {s}"),
+        })
+    }
+}
+
+impl std::fmt::Display for DnsRecordType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.parsed {
+            Some(ParsedDnsRecordType::A) => write!(f, "A"),
+            Some(ParsedDnsRecordType::Ns) => write!(f, "NS"),
+            Some(ParsedDnsRecordType::Cname) => write!(f, "CNAME"),
+            Some(ParsedDnsRecordType::Soa) => write!(f, "SOA"),
+            Some(ParsedDnsRecordType::Ptr) => write!(f, "PTR"),
+            Some(ParsedDnsRecordType::Mx) => write!(f, "MX"),
+            Some(ParsedDnsRecordType::Txt) => write!(f, "TXT"),
+            Some(ParsedDnsRecordType::Aaaa) => write!(f, "AAAA"),
+            Some(ParsedDnsRecordType::Srv) => write!(f, "SRV"),
+            // The RFC 3597 notation for unknown types.
+            None => write!(f, "TYPE{}", self.raw),
+        }
+    }
+}
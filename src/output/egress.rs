@@ -0,0 +1,17 @@
+use devil_derive::BigQuerySchema;
+use serde::Serialize;
+
+use super::TcpProxyPlanOutput;
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct EgressPlanOutput {
+    pub name: String,
+    /// The local address to connect from.
+    pub bind: Option<String>,
+    /// A proxy every connection is tunneled through, ahead of any the step sets.
+    pub proxy: Option<TcpProxyPlanOutput>,
+    /// The DNS server used to resolve hosts instead of the system's.
+    pub resolver: Option<String>,
+    /// The most connections opened per second across all steps using the profile.
+    pub rate: Option<u64>,
+}
@@ -0,0 +1,73 @@
+use std::str::FromStr;
+
+use anyhow::bail;
+use cel_interpreter::Duration;
+use devil_derive::BigQuerySchema;
+use serde::Serialize;
+
+use super::MaybeUtf8;
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct ExpectPlanOutput {
+    /// A regex searched for in everything received so far each time more bytes arrive.
+    pub pattern: String,
+    /// How long after the connection opened the pattern must match by.
+    pub within: Option<Duration>,
+    /// Stop reading once the expectation passes or fails, so the rest of a long response isn't
+    /// waited for.
+    pub abort: bool,
+    /// How to end the connection if the expectation fails.
+    pub on_fail: ExpectTeardown,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct ExpectOutput {
+    pub pattern: String,
+    /// Whether the pattern matched before the deadline.
+    pub passed: bool,
+    /// The matched bytes, even if they arrived too late to pass.
+    pub matched: Option<MaybeUtf8>,
+    /// Where the match starts in the received bytes.
+    pub offset: Option<u64>,
+    /// When the read completing the match arrived, relative to when the connection opened.
+    pub time: Option<Duration>,
+    /// Whether this expectation ended the stream early.
+    pub aborted: bool,
+    /// How the connection was ended because of this expectation, if it was the one deciding.
+    pub teardown: Option<ExpectTeardown>,
+}
+
+/// What happens to the connection once an expectation decides to stop it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, BigQuerySchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpectTeardown {
+    /// Keep reading until the server closes the connection.
+    Finish,
+    /// Stop reading and close the connection normally, sending a FIN once the body is sent.
+    HalfClose,
+    /// Stop reading and close the connection with a TCP reset. The write half is left open, so
+    /// no FIN is sent unless the body finished sending before the expectation was decided.
+    Reset,
+}
+
+impl FromStr for ExpectTeardown {
+    type Err = crate::Error;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(match s {
+            "finish" => Self::Finish,
+            "half_close" => Self::HalfClose,
+            "reset" => Self::Reset,
+            _ => bail!("invalid expect teardown {s}"),
+        })
+    }
+}
+
+impl std::fmt::Display for ExpectTeardown {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Finish => write!(f, "finish"),
+            Self::HalfClose => write!(f, "half_close"),
+            Self::Reset => write!(f, "reset"),
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use devil_derive::BigQuerySchema;
+use serde::Serialize;
+use url::Url;
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct ForcedBrowsePlanOutput {
+    /// The directory to start from, always ending in a slash.
+    pub url: Url,
+    pub words: Vec<String>,
+    /// Extensions tried for each word in addition to the bare word, without leading dots.
+    pub extensions: Vec<String>,
+    /// How many directories deep to recurse into discovered directories.
+    pub max_depth: u64,
+    pub max_requests: u64,
+    pub concurrency: u64,
+    /// Body length difference from the calibrated not found response still treated as a match.
+    pub length_tolerance: u64,
+}
+
+/// Resources found by forced browsing.
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct ForcedBrowseOutput {
+    pub plan: ForcedBrowsePlanOutput,
+    /// How each directory responded to requests for random names.
+    pub calibration: Vec<ForcedBrowseCalibrationOutput>,
+    pub resources: Vec<ForcedBrowseResourceOutput>,
+    pub requests: u64,
+}
+
+/// The response fingerprint for a missing resource with a given extension.
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct ForcedBrowseCalibrationOutput {
+    pub directory: Url,
+    pub extension: Option<String>,
+    pub status_code: Option<u16>,
+    /// The body length with the requested name removed.
+    pub length: u64,
+    /// The allowed length difference, widened by any variation between calibration responses.
+    pub tolerance: u64,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct ForcedBrowseResourceOutput {
+    pub url: Url,
+    pub depth: u64,
+    pub status_code: Option<u16>,
+    pub length: u64,
+    /// The Location header for redirects.
+    pub location: Option<String>,
+}
@@ -2,7 +2,7 @@ use std::{collections::HashMap, sync::Arc};
 
 use cel_interpreter::Duration;
 use devil_derive::{BigQuerySchema, Record};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use url::Url;
 
 use super::{MaybeUtf8, PduName, ProtocolName};
@@ -48,11 +48,29 @@ pub struct GraphqlRequestOutput {
 pub struct GraphqlResponse {
     pub name: PduName,
     pub data: serde_json::Value,
-    pub errors: serde_json::Value,
+    /// The response's errors array, empty when the server reported none.
+    pub errors: Vec<GraphqlResponseError>,
     pub full: serde_json::Value,
     pub duration: Duration,
 }
 
+/// An entry in a response's errors array, as laid out by the GraphQL spec.
+#[derive(Debug, Clone, Serialize, Deserialize, BigQuerySchema)]
+pub struct GraphqlResponseError {
+    pub message: String,
+    #[serde(default)]
+    pub locations: Vec<GraphqlLocation>,
+    #[serde(default)]
+    pub path: Vec<serde_json::Value>,
+    pub extensions: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, BigQuerySchema)]
+pub struct GraphqlLocation {
+    pub line: u64,
+    pub column: u64,
+}
+
 #[derive(Debug, Clone, Serialize, BigQuerySchema)]
 pub struct GraphqlError {
     pub kind: String,
@@ -0,0 +1,51 @@
+use cel_interpreter::Duration;
+use devil_derive::{BigQuerySchema, Record};
+use serde::Serialize;
+use url::Url;
+
+use super::{HttpHeader, MaybeUtf8, ProtocolName};
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema, Record)]
+#[serde(tag = "kind", rename = "grpc")]
+#[bigquery(tag = "kind")]
+#[record(rename = "grpc")]
+pub struct GrpcOutput {
+    pub name: ProtocolName,
+    pub plan: GrpcPlanOutput,
+    /// The length-prefixed request message as written to the stream.
+    pub request: Option<MaybeUtf8>,
+    pub messages: Vec<GrpcMessageOutput>,
+    /// The grpc-status from the trailers, or from the headers of a trailers-only response.
+    pub status: Option<u32>,
+    pub status_message: Option<String>,
+    pub trailers: Option<Vec<HttpHeader>>,
+    pub errors: Vec<GrpcError>,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct GrpcPlanOutput {
+    /// The server url with the /service/method path appended.
+    pub url: Url,
+    pub service: String,
+    pub method: String,
+    /// The protobuf encoded request message, or the JSON body once encoded.
+    pub message: MaybeUtf8,
+    pub json: Option<serde_json::Value>,
+    /// A FileDescriptorSet used to encode json and decode response messages.
+    pub descriptor_set: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct GrpcMessageOutput {
+    pub compressed: bool,
+    pub data: MaybeUtf8,
+    /// The message decoded with the method's output type when a descriptor set was given.
+    pub json: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct GrpcError {
+    pub kind: String,
+    pub message: String,
+}
@@ -0,0 +1,48 @@
+use cel_interpreter::Duration;
+use devil_derive::BigQuerySchema;
+use serde::Serialize;
+use url::Url;
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct GrpcReflectPlanOutput {
+    /// The server to query, over h2c for http and TLS for https.
+    pub url: Url,
+    pub timeout: Duration,
+    /// Where to write the discovered descriptors as a FileDescriptorSet, for use with
+    /// parse_protobuf.
+    pub save_descriptors: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct GrpcReflectOutput {
+    pub plan: GrpcReflectPlanOutput,
+    /// The reflection API version the server answered, either v1 or v1alpha.
+    pub version: Option<String>,
+    pub services: Vec<String>,
+    /// Every method of every service, flattened so later steps can iterate them.
+    pub methods: Vec<GrpcMethodOutput>,
+    pub error: Option<String>,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct GrpcMethodOutput {
+    pub service: String,
+    pub name: String,
+    /// The HTTP/2 path to call the method, like /pkg.Service/Method.
+    pub path: String,
+    pub input_type: String,
+    pub output_type: String,
+    pub client_streaming: bool,
+    pub server_streaming: bool,
+    pub input_fields: Vec<GrpcFieldOutput>,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct GrpcFieldOutput {
+    pub name: String,
+    pub number: u32,
+    /// The scalar type like int32 or string, or the full name of a message or enum.
+    pub kind: String,
+    pub repeated: bool,
+}
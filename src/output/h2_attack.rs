@@ -0,0 +1,57 @@
+use cel_interpreter::Duration;
+use devil_derive::BigQuerySchema;
+use serde::Serialize;
+use url::Url;
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct H2AttackPlanOutput {
+    /// The server to attack, over h2c for http and TLS for https.
+    pub url: Url,
+    /// One of empty_headers, continuation_flood, rapid_reset, made_you_reset, or
+    /// hpack_table_update.
+    pub attack: String,
+    /// How many streams or frames the attack sends.
+    pub count: u64,
+    /// How long to wait for more frames from the server after each exchange.
+    pub timeout: Duration,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct H2AttackOutput {
+    pub plan: H2AttackPlanOutput,
+    /// The handshake, each batch of attack frames, and a final request on the same connection.
+    pub exchanges: Vec<H2ExchangeOutput>,
+    /// Whether the server still answered a normal request after the attack.
+    pub alive: bool,
+    pub error: Option<String>,
+    pub duration: Duration,
+}
+
+/// A batch of frames sent and everything the server sent back before going quiet.
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct H2ExchangeOutput {
+    pub name: String,
+    /// How many frames were written before the batch finished or the write failed.
+    pub frames_sent: u64,
+    pub frames_received: u64,
+    /// How many streams the server reset.
+    pub rst_streams: u64,
+    /// The error code of the server's GOAWAY, if it sent one.
+    pub goaway: Option<u32>,
+    /// Whether the server closed the connection.
+    pub closed: bool,
+    /// The first frames received, to keep floods from bloating the output.
+    pub received: Vec<H2FrameSummaryOutput>,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct H2FrameSummaryOutput {
+    /// The frame type, like HEADERS or RST_STREAM.
+    pub kind: String,
+    pub stream_id: u32,
+    pub flags: u8,
+    pub length: u32,
+    /// The error code of RST_STREAM and GOAWAY frames.
+    pub error_code: Option<u32>,
+}
@@ -2,10 +2,11 @@ use std::sync::Arc;
 
 use cel_interpreter::Duration;
 use devil_derive::{BigQuerySchema, Record};
+use indexmap::IndexMap;
 use serde::Serialize;
 use url::Url;
 
-use crate::AddContentLength;
+use crate::{AddContentLength, SimilarityHash};
 
 use super::{MaybeUtf8, PduName, ProtocolName};
 
@@ -30,6 +31,10 @@ pub struct HttpPlanOutput {
     pub add_content_length: AddContentLength,
     pub headers: Vec<HttpHeader>,
     pub body: MaybeUtf8,
+    /// Send the request over HTTP/3 on QUIC instead of negotiating over TCP.
+    pub http3: bool,
+    /// Similarity hashes to compute over the response body.
+    pub similarity: Vec<SimilarityHash>,
 }
 
 impl From<(MaybeUtf8, MaybeUtf8)> for HttpHeader {
@@ -50,6 +55,17 @@ impl From<(Option<MaybeUtf8>, MaybeUtf8)> for HttpHeader {
     }
 }
 
+/// How the client-side cache handled a request.
+#[derive(Debug, Clone, Default, Serialize, BigQuerySchema)]
+pub struct HttpCacheOutput {
+    /// Whether validators from a stored response were sent.
+    pub conditional: bool,
+    /// Whether the server answered a conditional request with 304 Not Modified.
+    pub not_modified: bool,
+    /// Whether the response was stored for later requests.
+    pub stored: bool,
+}
+
 #[derive(Debug, Clone, Serialize, BigQuerySchema)]
 pub struct HttpHeader {
     pub key: Option<MaybeUtf8>,
@@ -81,7 +97,27 @@ pub struct HttpResponse {
     pub protocol: Option<MaybeUtf8>,
     pub status_code: Option<u16>,
     pub headers: Option<Vec<HttpHeader>>,
+    /// Header values by lowercase name, in the order received.
+    pub header_map: Option<IndexMap<String, Vec<MaybeUtf8>>>,
+    /// The status line and headers exactly as received, for HTTP/1 responses. HTTP/2 and HTTP/3
+    /// compress headers, so they have no equivalent.
+    pub raw_header: Option<MaybeUtf8>,
+    /// The body with any content codings removed.
     pub body: Option<MaybeUtf8>,
+    /// The body as read before removing chunked framing, for HTTP/1 responses.
+    pub raw_body: Option<MaybeUtf8>,
+    /// The body before removing its content codings, if it was decoded.
+    pub encoded_body: Option<MaybeUtf8>,
+    /// The codings listed by Content-Encoding, lowercase and in the order they were applied.
+    pub content_encoding: Option<String>,
+    /// The charset used to decode the body into text.
+    pub charset: Option<String>,
+    /// The body decoded using its detected charset.
+    pub text: Option<String>,
+    /// The body's simhash in hex, if plan.similarity includes simhash.
+    pub simhash: Option<String>,
+    /// The body's ssdeep hash, if plan.similarity includes ssdeep.
+    pub ssdeep: Option<String>,
     pub duration: Duration,
     pub header_duration: Option<Duration>,
     pub time_to_first_byte: Option<Duration>,
@@ -5,9 +5,9 @@ use devil_derive::{BigQuerySchema, Record};
 use serde::Serialize;
 use url::Url;
 
-use crate::AddContentLength;
+use crate::{AddContentLength, CacheMode};
 
-use super::{HttpHeader, MaybeUtf8, PduName, ProtocolName};
+use super::{HttpCacheOutput, HttpHeader, MaybeUtf8, PduName, ProtocolName, SignPlanOutput};
 
 #[derive(Debug, Clone, Serialize, BigQuerySchema, Record)]
 #[serde(tag = "kind", rename = "http1")]
@@ -18,6 +18,13 @@ pub struct Http1Output {
     pub plan: Http1PlanOutput,
     pub request: Option<Arc<Http1RequestOutput>>,
     pub response: Option<Arc<Http1Response>>,
+    /// Interim 1xx responses received before the final response, like 100 Continue.
+    pub informational_responses: Vec<Http1InformationalResponse>,
+    /// How waiting for 100 Continue ended, if the plan set expect_continue.
+    pub expect_continue: Option<ExpectContinueOutcome>,
+    /// Set when the request was pipelined with the step's other jobs.
+    pub pipeline: Option<Http1PipelineOutput>,
+    pub cache: Option<HttpCacheOutput>,
     pub errors: Vec<Http1Error>,
     pub duration: Duration,
 }
@@ -30,6 +37,36 @@ pub struct Http1PlanOutput {
     pub add_content_length: AddContentLength,
     pub headers: Vec<HttpHeader>,
     pub body: MaybeUtf8,
+    pub sign: Option<SignPlanOutput>,
+    pub cache: CacheMode,
+    pub chunked: Option<Http1ChunkedPlanOutput>,
+    pub expect_continue: Option<Http1ExpectContinuePlanOutput>,
+    /// The request line and headers to send exactly as written instead of building them.
+    pub head: Option<MaybeUtf8>,
+    pub read: Option<Http1ReadPlanOutput>,
+    /// Whether the response head was read with the permissive parser.
+    pub permissive: bool,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct Http1ChunkedPlanOutput {
+    pub sizes: Vec<u64>,
+    pub extensions: Vec<MaybeUtf8>,
+    pub trailers: Vec<HttpHeader>,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct Http1ReadPlanOutput {
+    pub content_length: bool,
+    pub idle: Option<Duration>,
+    pub pattern: Option<MaybeUtf8>,
+    pub bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct Http1ExpectContinuePlanOutput {
+    pub header: bool,
+    pub timeout: Duration,
 }
 
 #[derive(Debug, Clone, Serialize, BigQuerySchema, Record)]
@@ -42,6 +79,8 @@ pub struct Http1RequestOutput {
     pub method: Option<MaybeUtf8>,
     pub version_string: Option<MaybeUtf8>,
     pub headers: Vec<HttpHeader>,
+    /// The request line and headers exactly as sent, when the plan wrote them in http1.head.
+    pub raw_header: Option<MaybeUtf8>,
     pub body: MaybeUtf8,
     pub duration: Duration,
     pub body_duration: Option<Duration>,
@@ -59,12 +98,120 @@ pub struct Http1Response {
     pub status_reason: Option<MaybeUtf8>,
     pub content_length: Option<u64>,
     pub headers: Option<Vec<HttpHeader>>,
+    /// The status line and headers exactly as received, once they're complete.
+    pub raw_header: Option<MaybeUtf8>,
+    /// The body with any chunked framing and content codings removed, and without bytes past its
+    /// declared length.
     pub body: Option<MaybeUtf8>,
+    /// The body as read from the connection, including any bytes read past its end.
+    pub raw_body: Option<MaybeUtf8>,
+    /// The body before removing its content codings, if it was decoded.
+    pub encoded_body: Option<MaybeUtf8>,
+    /// The codings listed by Content-Encoding, lowercase and in the order they were applied.
+    pub content_encoding: Option<String>,
+    /// How the status line and headers strayed from RFC 9112, if the plan set permissive.
+    pub deviations: Vec<Http1Deviation>,
     pub duration: Duration,
     pub header_duration: Option<Duration>,
     pub time_to_first_byte: Option<Duration>,
 }
 
+/// A line of a response head which the permissive parser accepted despite RFC 9112.
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct Http1Deviation {
+    pub kind: Http1DeviationKind,
+    /// The line's offset in the response head.
+    pub offset: u64,
+    /// The line as received, without its line ending.
+    pub line: MaybeUtf8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, BigQuerySchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Http1DeviationKind {
+    /// Empty lines came before the status line.
+    LeadingEmptyLine,
+    /// The line ended with a bare LF instead of CRLF.
+    BareLf,
+    /// The status line didn't start with HTTP/1.0 or HTTP/1.1.
+    Protocol,
+    /// The status code wasn't three digits.
+    StatusCode,
+    /// Nothing followed the status code, not even the space before the reason phrase.
+    MissingReason,
+    /// A header line started with whitespace, continuing the previous header's value.
+    ObsFold,
+    /// A header line had no colon, so it was recorded without a name.
+    MissingColon,
+    /// A header name was empty or had characters which aren't allowed in tokens.
+    HeaderName,
+    /// Whitespace came between a header name and its colon.
+    SpaceBeforeColon,
+    /// The reason phrase or a header value had control characters.
+    ControlCharacter,
+}
+
+impl Http1DeviationKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::LeadingEmptyLine => "leading_empty_line",
+            Self::BareLf => "bare_lf",
+            Self::Protocol => "protocol",
+            Self::StatusCode => "status_code",
+            Self::MissingReason => "missing_reason",
+            Self::ObsFold => "obs_fold",
+            Self::MissingColon => "missing_colon",
+            Self::HeaderName => "header_name",
+            Self::SpaceBeforeColon => "space_before_colon",
+            Self::ControlCharacter => "control_character",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct Http1InformationalResponse {
+    pub status_code: u16,
+    pub status_reason: Option<MaybeUtf8>,
+    pub headers: Vec<HttpHeader>,
+    /// Time from starting to send the request until the response was read.
+    pub time: Duration,
+}
+
+/// Where a request was among those written to a connection before any of their responses were
+/// read.
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct Http1PipelineOutput {
+    /// The request's position on the connection, starting at 0. The response read in the same
+    /// position is recorded as its response.
+    pub index: u64,
+    /// Bytes of the response which had already been read along with earlier responses.
+    pub buffered_bytes: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, BigQuerySchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpectContinueOutcome {
+    /// An informational response arrived, so the body was sent.
+    Continue,
+    /// Nothing arrived before the timeout, so the body was sent anyway.
+    Timeout,
+    /// A final response arrived first, so the body wasn't sent.
+    FinalResponse,
+    /// The connection closed or failed while waiting.
+    Closed,
+}
+
+impl ExpectContinueOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Continue => "continue",
+            Self::Timeout => "timeout",
+            Self::FinalResponse => "final_response",
+            Self::Closed => "closed",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, BigQuerySchema)]
 pub struct Http1Error {
     pub kind: String,
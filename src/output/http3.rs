@@ -0,0 +1,72 @@
+use std::sync::Arc;
+
+use cel_interpreter::Duration;
+use devil_derive::{BigQuerySchema, Record};
+use serde::Serialize;
+use url::Url;
+
+use crate::AddContentLength;
+
+use super::{HttpHeader, MaybeUtf8, PduName, ProtocolName};
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema, Record)]
+#[serde(tag = "kind", rename = "http3")]
+#[bigquery(tag = "kind")]
+#[record(rename = "http3")]
+pub struct Http3Output {
+    pub name: ProtocolName,
+    pub plan: Http3PlanOutput,
+    pub request: Option<Arc<Http3RequestOutput>>,
+    pub response: Option<Arc<Http3Response>>,
+    pub errors: Vec<Http3Error>,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct Http3PlanOutput {
+    pub url: Url,
+    pub method: Option<MaybeUtf8>,
+    pub add_content_length: AddContentLength,
+    pub headers: Vec<HttpHeader>,
+    pub body: MaybeUtf8,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema, Record)]
+#[serde(tag = "kind", rename = "http3_request")]
+#[bigquery(tag = "kind")]
+#[record(rename = "http3_request")]
+pub struct Http3RequestOutput {
+    pub name: PduName,
+    pub url: Url,
+    pub method: Option<MaybeUtf8>,
+    pub headers: Vec<HttpHeader>,
+    pub body: MaybeUtf8,
+    /// The QUIC stream the request was sent on.
+    pub stream_id: Option<u64>,
+    pub duration: Duration,
+    pub headers_duration: Option<Duration>,
+    pub body_duration: Option<Duration>,
+    pub time_to_first_byte: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema, Record)]
+#[serde(tag = "kind", rename = "http3_response")]
+#[bigquery(tag = "kind")]
+#[record(rename = "http3_response")]
+pub struct Http3Response {
+    pub name: PduName,
+    pub stream_id: Option<u64>,
+    pub status_code: Option<u16>,
+    pub headers: Option<Vec<HttpHeader>>,
+    pub trailers: Option<Vec<HttpHeader>>,
+    pub body: Option<MaybeUtf8>,
+    pub duration: Duration,
+    pub header_duration: Option<Duration>,
+    pub time_to_first_byte: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct Http3Error {
+    pub kind: String,
+    pub message: String,
+}
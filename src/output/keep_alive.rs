@@ -0,0 +1,47 @@
+use cel_interpreter::Duration;
+use devil_derive::BigQuerySchema;
+use serde::Serialize;
+use url::Url;
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct KeepAlivePlanOutput {
+    /// The server to connect to, over TLS for https.
+    pub url: Url,
+    /// How many connections to open, one after another.
+    pub connections: u64,
+    /// How long to keep holding the connections after the last one is opened.
+    pub hold: Duration,
+    /// How often to send a no-op CRLF on each held connection.
+    pub interval: Duration,
+    /// How long to wait for each connection to open and answer its first request.
+    pub timeout: Duration,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct KeepAliveOutput {
+    pub plan: KeepAlivePlanOutput,
+    pub connections: Vec<HeldConnectionOutput>,
+    /// The most connections held open at once.
+    pub max_open: u64,
+    /// How many other connections were held open when the server first refused or closed one.
+    pub limit: Option<u64>,
+    /// Whether a new connection was answered after the held ones were released.
+    pub recovered: bool,
+    pub error: Option<String>,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct HeldConnectionOutput {
+    pub index: u64,
+    /// The status of the HEAD request that put the connection into keep-alive.
+    pub status_code: Option<u16>,
+    /// When the connection was ready, relative to the start of the step.
+    pub opened_at: Option<Duration>,
+    /// When the server refused or closed the connection, relative to the start of the step.
+    pub closed_at: Option<Duration>,
+    /// How many other connections were held open when this one was refused or closed.
+    pub open_at_close: Option<u64>,
+    pub noops_sent: u64,
+    pub error: Option<String>,
+}
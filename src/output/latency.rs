@@ -0,0 +1,27 @@
+use cel_interpreter::Duration;
+use devil_derive::BigQuerySchema;
+use serde::Serialize;
+
+/// Timing statistics for each phase across a step's jobs.
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct LatencyOutput {
+    /// Time to complete the TCP handshake.
+    pub connect: Option<HistogramOutput>,
+    /// Time until the first byte of the response.
+    pub time_to_first_byte: Option<HistogramOutput>,
+    /// Duration of the step's top protocol.
+    pub total: Option<HistogramOutput>,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct HistogramOutput {
+    pub count: u64,
+    pub min: Duration,
+    pub max: Duration,
+    pub mean: Duration,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+    pub p999: Duration,
+}
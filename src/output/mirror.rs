@@ -0,0 +1,40 @@
+use std::sync::Arc;
+
+use devil_derive::BigQuerySchema;
+use serde::Serialize;
+use url::Url;
+
+use super::JobOutput;
+use crate::MirrorMode;
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct MirrorPlanOutput {
+    pub url: Url,
+    pub mode: MirrorMode,
+}
+
+/// A job's request as sent to the mirror target, and how its response compared.
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct MirrorOutput {
+    pub url: Url,
+    pub job: Arc<JobOutput>,
+    pub status_changed: bool,
+    pub body_changed: bool,
+    /// The mirror's response body length minus the original's.
+    pub length_delta: i64,
+}
+
+impl MirrorOutput {
+    pub fn compare(url: Url, original: &JobOutput, job: Arc<JobOutput>) -> Self {
+        let (status, body) = original.response_summary();
+        let (mirror_status, mirror_body) = job.response_summary();
+        Self {
+            url,
+            status_changed: status != mirror_status,
+            body_changed: body != mirror_body,
+            length_delta: i64::try_from(mirror_body.len()).unwrap_or(i64::MAX)
+                - i64::try_from(body.len()).unwrap_or(i64::MAX),
+            job,
+        }
+    }
+}
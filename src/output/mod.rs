@@ -9,33 +9,103 @@ use indexmap::IndexMap;
 use serde::Serialize;
 use strum::EnumIs;
 
-use crate::{location, IterableKey, Parallelism, ProtocolField};
+use crate::{location, IterableKey, OnError, Parallelism, ProtocolField};
 
+mod adaptive;
+mod alpn_matrix;
+mod auth;
+mod banner;
+mod budget;
 mod bytes;
+mod command;
+mod conditional;
+mod crawl;
+mod desync;
+mod detect;
+mod discover;
+mod dns;
+mod egress;
+mod expect;
+mod forced_browse;
 mod graphql;
+mod grpc;
+mod grpc_reflect;
+mod h2_attack;
 mod http;
 mod http1;
 mod http2;
+mod http3;
+mod keep_alive;
+mod latency;
+mod mirror;
+mod module;
 mod name;
+mod network;
 mod normalize;
+mod quic;
+mod range;
 mod raw_http2;
 mod raw_tcp;
+mod reflect;
+mod script;
+mod session;
+mod sign;
+mod takeover;
+mod targets;
 mod tcp;
+mod tcp_burst;
 mod tls;
 mod value;
-
+mod vhost;
+mod websocket;
+
+pub use adaptive::*;
+pub use alpn_matrix::*;
+pub use auth::*;
+pub use banner::*;
+pub use budget::*;
 pub use bytes::*;
+pub use command::*;
+pub use conditional::*;
+pub use crawl::*;
+pub use desync::*;
+pub use detect::*;
+pub use discover::*;
+pub use dns::*;
+pub use egress::*;
+pub use expect::*;
+pub use forced_browse::*;
 pub use graphql::*;
+pub use grpc::*;
+pub use grpc_reflect::*;
+pub use h2_attack::*;
 pub use http::*;
 pub use http1::*;
 pub use http2::*;
+pub use http3::*;
+pub use keep_alive::*;
+pub use latency::*;
+pub use mirror::*;
+pub use module::*;
 pub use name::*;
+pub use network::*;
 pub use normalize::*;
+pub use quic::*;
+pub use range::*;
 pub use raw_http2::*;
 pub use raw_tcp::*;
+pub use reflect::*;
+pub use script::*;
+pub use session::*;
+pub use sign::*;
+pub use takeover::*;
+pub use targets::*;
 pub use tcp::*;
+pub use tcp_burst::*;
 pub use tls::*;
 pub use value::*;
+pub use vhost::*;
+pub use websocket::*;
 
 pub trait State<'a, O: Into<&'a Arc<String>>, I: IntoIterator<Item = O>> {
     fn get(&self, name: &'a Arc<String>) -> Option<&StepOutput>;
@@ -43,16 +113,21 @@ pub trait State<'a, O: Into<&'a Arc<String>>, I: IntoIterator<Item = O>> {
     fn run_for(&self) -> &Option<RunForOutput>;
     fn run_while(&self) -> &Option<RunWhileOutput>;
     fn run_count(&self) -> &Option<RunCountOutput>;
+    /// The output of the previous job in the current step when jobs run serially.
+    fn previous(&self) -> Option<&JobOutput>;
     fn locals(&self) -> cel_interpreter::objects::Map;
     fn iter(&self) -> I;
     fn run_name(&self) -> &RunName;
     fn job_name(&self) -> Option<&JobName>;
+    /// The state kept by the run's stateful CEL functions, like its random generator.
+    fn cel_state(&self) -> &Arc<crate::cel_functions::CelState>;
     // Check for matching singed int indexes too.
 }
 
 #[derive(Debug, Clone)]
 pub enum StepPlanOutput {
     Graphql(GraphqlPlanOutput),
+    Grpc(GrpcPlanOutput),
     Http(HttpPlanOutput),
     H1c(Http1PlanOutput),
     H1(Http1PlanOutput),
@@ -60,15 +135,57 @@ pub enum StepPlanOutput {
     RawH2c(RawHttp2PlanOutput),
     H2(Http2PlanOutput),
     RawH2(RawHttp2PlanOutput),
-    //Http3(Http3PlanOutput),
+    H3(Http3PlanOutput),
     Tls(TlsPlanOutput),
     Tcp(TcpPlanOutput),
     RawTcp(RawTcpPlanOutput),
+    Quic(QuicPlanOutput),
+    Ws(WebSocketPlanOutput),
+}
+
+impl StepPlanOutput {
+    /// The URL requested, for protocols addressed by one.
+    pub fn url(&self) -> Option<&url::Url> {
+        match self {
+            Self::Graphql(p) => Some(&p.url),
+            Self::Grpc(p) => Some(&p.url),
+            Self::Http(p) => Some(&p.url),
+            Self::H1c(p) | Self::H1(p) => Some(&p.url),
+            Self::H2c(p) | Self::H2(p) => Some(&p.url),
+            Self::H3(p) => Some(&p.url),
+            Self::Ws(p) => Some(&p.url),
+            _ => None,
+        }
+    }
+
+    /// Point an HTTP request at another base URL, keeping its path and query. The base URL's
+    /// path is prepended to the request's path.
+    pub fn rebase_url(&mut self, base: &url::Url) {
+        let url = match self {
+            Self::Graphql(p) => &mut p.url,
+            Self::Grpc(p) => &mut p.url,
+            Self::Http(p) => &mut p.url,
+            Self::H1c(p) | Self::H1(p) => &mut p.url,
+            Self::H2c(p) | Self::H2(p) => &mut p.url,
+            Self::H3(p) => &mut p.url,
+            _ => return,
+        };
+        // Both schemes are special so changing between them can't fail.
+        let _ = url.set_scheme(base.scheme());
+        let _ = url.set_host(base.host_str());
+        let _ = url.set_port(base.port());
+        let prefix = base.path().trim_end_matches('/');
+        if !prefix.is_empty() {
+            let path = format!("{prefix}{}", url.path());
+            url.set_path(&path);
+        }
+    }
 }
 
 #[derive(Debug, Clone, Default, Serialize)]
 pub struct StepPlanOutputs {
     pub graphql: Option<PlanWrapper<GraphqlPlanOutput>>,
+    pub grpc: Option<PlanWrapper<GrpcPlanOutput>>,
     pub http: Option<PlanWrapper<HttpPlanOutput>>,
     pub h1c: Option<PlanWrapper<Http1PlanOutput>>,
     pub h1: Option<PlanWrapper<Http1PlanOutput>>,
@@ -76,10 +193,12 @@ pub struct StepPlanOutputs {
     pub raw_h2c: Option<PlanWrapper<RawHttp2PlanOutput>>,
     pub h2: Option<PlanWrapper<Http2PlanOutput>>,
     pub raw_h2: Option<PlanWrapper<RawHttp2PlanOutput>>,
-    //pub http3: Option<Http3PlanOutput>>,
+    pub h3: Option<PlanWrapper<Http3PlanOutput>>,
     pub tls: Option<PlanWrapper<TlsPlanOutput>>,
     pub tcp: Option<PlanWrapper<TcpPlanOutput>>,
     pub raw_tcp: Option<PlanWrapper<RawTcpPlanOutput>>,
+    pub quic: Option<PlanWrapper<QuicPlanOutput>>,
+    pub ws: Option<PlanWrapper<WebSocketPlanOutput>>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -99,14 +218,20 @@ impl<T: Debug + Clone> PlanWrapper<T> {
 #[record(rename = "run")]
 pub struct RunOutput {
     pub name: RunName,
+    /// The base URL this run was for, when the plan runs against several targets.
+    pub target: Option<String>,
     pub steps: IndexMap<Arc<String>, Arc<StepOutput>>,
+    /// Resources used by the run's steps.
+    pub usage: Option<UsageOutput>,
 }
 
 impl RunOutput {
     pub fn new(name: RunName) -> Self {
         Self {
             name,
+            target: None,
             steps: IndexMap::default(),
+            usage: None,
         }
     }
 }
@@ -118,6 +243,46 @@ impl RunOutput {
 pub struct StepOutput {
     pub name: StepName,
     pub jobs: IndexMap<IterableKey, Arc<JobOutput>>,
+    pub module: Option<ModuleOutput>,
+    pub crawl: Option<Arc<CrawlOutput>>,
+    pub discover: Option<Arc<DiscoverOutput>>,
+    pub forced_browse: Option<Arc<ForcedBrowseOutput>>,
+    pub vhost: Option<Arc<VhostOutput>>,
+    pub takeover: Option<Arc<TakeoverOutput>>,
+    pub banner: Option<Arc<BannerOutput>>,
+    pub session: Option<Arc<SessionOutput>>,
+    pub command: Option<Arc<CommandOutput>>,
+    pub script: Option<Arc<ScriptOutput>>,
+    pub grpc_reflect: Option<Arc<GrpcReflectOutput>>,
+    pub range: Option<Arc<RangeOutput>>,
+    pub conditional: Option<Arc<ConditionalOutput>>,
+    pub h2_attack: Option<Arc<H2AttackOutput>>,
+    pub alpn_matrix: Option<Arc<AlpnMatrixOutput>>,
+    pub keep_alive: Option<Arc<KeepAliveOutput>>,
+    pub tcp_burst: Option<Arc<TcpBurstOutput>>,
+    pub dns: Option<Arc<DnsOutput>>,
+    pub detect: Option<Arc<DetectOutput>>,
+    /// Jobs sent to the plan's mirror target, keyed like jobs.
+    pub mirror: IndexMap<IterableKey, Arc<MirrorOutput>>,
+    pub errors: Vec<StepError>,
+    /// The egress profile the step's traffic was sent through.
+    pub egress: Option<String>,
+    /// The network conditions emulated on the step's connections.
+    pub network: Option<NetworkPlanOutput>,
+    /// The login whose credentials the step sent.
+    pub auth: Option<AuthOutput>,
+    /// Timing percentiles across the step's jobs when it ran more than once.
+    pub latency: Option<LatencyOutput>,
+    /// How the concurrency limit changed when run.adaptive was set.
+    pub adaptive: Option<AdaptiveOutput>,
+    /// Markers from marker() first sent by this step.
+    pub markers: Vec<String>,
+    /// Where markers sent by this or earlier steps appeared in this step's responses.
+    pub reflections: Vec<ReflectionOutput>,
+    /// Responses which fell out of step with their requests on a shared HTTP/1 connection.
+    pub desync: Vec<DesyncOutput>,
+    /// Resources used by the step.
+    pub usage: Option<UsageOutput>,
 }
 
 impl StepOutput {
@@ -125,10 +290,46 @@ impl StepOutput {
         Self {
             name,
             jobs: IndexMap::new(),
+            module: None,
+            crawl: None,
+            discover: None,
+            forced_browse: None,
+            vhost: None,
+            takeover: None,
+            banner: None,
+            session: None,
+            command: None,
+            script: None,
+            grpc_reflect: None,
+            range: None,
+            conditional: None,
+            h2_attack: None,
+            alpn_matrix: None,
+            keep_alive: None,
+            tcp_burst: None,
+            dns: None,
+            detect: None,
+            mirror: IndexMap::new(),
+            errors: Vec::new(),
+            egress: None,
+            network: None,
+            auth: None,
+            latency: None,
+            adaptive: None,
+            markers: Vec::new(),
+            reflections: Vec::new(),
+            desync: Vec::new(),
+            usage: None,
         }
     }
 }
 
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct StepError {
+    pub kind: String,
+    pub message: String,
+}
+
 #[derive(Debug, Clone, Serialize, BigQuerySchema, Record)]
 #[serde(tag = "kind", rename = "job")]
 #[bigquery(tag = "kind")]
@@ -136,6 +337,7 @@ impl StepOutput {
 pub struct JobOutput {
     pub name: JobName,
     pub graphql: Option<Arc<GraphqlOutput>>,
+    pub grpc: Option<Arc<GrpcOutput>>,
     pub http: Option<Arc<HttpOutput>>,
     pub h1: Option<Arc<Http1Output>>,
     pub h1c: Option<Arc<Http1Output>>,
@@ -143,10 +345,12 @@ pub struct JobOutput {
     pub h2c: Option<Arc<Http2Output>>,
     pub raw_h2: Option<Arc<RawHttp2Output>>,
     pub raw_h2c: Option<Arc<RawHttp2Output>>,
-    //pub http3: Option<Http3Output>>,
+    pub h3: Option<Arc<Http3Output>>,
     pub tls: Option<Arc<TlsOutput>>,
     pub tcp: Option<Arc<TcpOutput>>,
     pub raw_tcp: Option<Arc<RawTcpOutput>>,
+    pub quic: Option<Arc<QuicOutput>>,
+    pub ws: Option<Arc<WebSocketOutput>>,
 }
 
 impl JobOutput {
@@ -154,6 +358,7 @@ impl JobOutput {
         Self {
             name,
             graphql: None,
+            grpc: None,
             http: None,
             h1: None,
             h1c: None,
@@ -161,10 +366,12 @@ impl JobOutput {
             h2c: None,
             raw_h2: None,
             raw_h2c: None,
-            // http3: None,
+            h3: None,
             tls: None,
             tcp: None,
             raw_tcp: None,
+            quic: None,
+            ws: None,
         }
     }
     pub fn http1(&self) -> Option<&Arc<Http1Output>> {
@@ -176,6 +383,20 @@ impl JobOutput {
     pub fn raw_http2(&self) -> Option<&Arc<RawHttp2Output>> {
         self.raw_h2.as_ref().or_else(|| self.raw_h2c.as_ref())
     }
+    /// The status code and body of the highest level HTTP response in the job.
+    pub fn response_summary(&self) -> (Option<u16>, &[u8]) {
+        if let Some(r) = self.http.as_ref().and_then(|h| h.response.as_ref()) {
+            (r.status_code, r.body.as_deref().unwrap_or_default())
+        } else if let Some(r) = self.http1().and_then(|h| h.response.as_ref()) {
+            (r.status_code, r.body.as_deref().unwrap_or_default())
+        } else if let Some(r) = self.http2().and_then(|h| h.response.as_ref()) {
+            (r.status_code, r.body.as_deref().unwrap_or_default())
+        } else if let Some(r) = self.h3.as_ref().and_then(|h| h.response.as_ref()) {
+            (r.status_code, r.body.as_deref().unwrap_or_default())
+        } else {
+            (None, &[])
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -277,6 +498,7 @@ pub struct RunPlanOutput {
     pub count: u64,
     pub parallel: Parallelism,
     pub share: Option<ProtocolField>,
+    pub on_error: OnError,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -0,0 +1,30 @@
+use std::sync::Arc;
+
+#[cfg(feature = "native")]
+use gcp_bigquery_client::model::table_field_schema::TableFieldSchema;
+use indexmap::IndexMap;
+use serde::Serialize;
+
+#[cfg(feature = "native")]
+use crate::record::BigQuerySchema;
+
+use super::RunOutput;
+
+#[derive(Debug, Clone)]
+pub struct ModulePlanOutput {
+    pub path: String,
+    pub params: IndexMap<String, cel_interpreter::Value>,
+}
+
+/// The output of a plan run as a module step.
+#[derive(Debug, Clone, Serialize)]
+#[serde(transparent)]
+pub struct ModuleOutput(pub Arc<RunOutput>);
+
+#[cfg(feature = "native")]
+impl BigQuerySchema for ModuleOutput {
+    fn big_query_schema(name: &str) -> TableFieldSchema {
+        // Modules can nest arbitrarily deep, so a fixed schema isn't possible.
+        TableFieldSchema::json(name)
+    }
+}
@@ -1,10 +1,13 @@
 use std::{fmt::Display, sync::Arc};
 
+#[cfg(feature = "native")]
 use gcp_bigquery_client::model::table_field_schema::TableFieldSchema;
 use serde::Serialize;
 use svix_ksuid::{KsuidLike, KsuidMs};
 
-use crate::{record::BigQuerySchema, IterableKey, ProtocolDiscriminants};
+#[cfg(feature = "native")]
+use crate::record::BigQuerySchema;
+use crate::{IterableKey, ProtocolDiscriminants};
 
 #[derive(Debug, Clone)]
 pub struct RunName {
@@ -21,6 +24,7 @@ impl RunName {
     }
 }
 
+#[cfg(feature = "native")]
 impl BigQuerySchema for RunName {
     fn big_query_schema(name: &str) -> TableFieldSchema {
         TableFieldSchema::string(name)
@@ -59,6 +63,7 @@ impl StepName {
     }
 }
 
+#[cfg(feature = "native")]
 impl BigQuerySchema for StepName {
     fn big_query_schema(name: &str) -> TableFieldSchema {
         TableFieldSchema::string(name)
@@ -122,6 +127,7 @@ impl JobName {
     }
 }
 
+#[cfg(feature = "native")]
 impl BigQuerySchema for JobName {
     fn big_query_schema(name: &str) -> TableFieldSchema {
         TableFieldSchema::string(name)
@@ -164,6 +170,7 @@ impl ProtocolName {
     }
 }
 
+#[cfg(feature = "native")]
 impl BigQuerySchema for ProtocolName {
     fn big_query_schema(name: &str) -> TableFieldSchema {
         TableFieldSchema::string(name)
@@ -223,6 +230,7 @@ impl PduName {
     }
 }
 
+#[cfg(feature = "native")]
 impl BigQuerySchema for PduName {
     fn big_query_schema(name: &str) -> TableFieldSchema {
         TableFieldSchema::string(name)
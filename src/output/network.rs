@@ -0,0 +1,20 @@
+use cel_interpreter::Duration;
+use devil_derive::BigQuerySchema;
+use serde::Serialize;
+
+use super::TcpFaultPlanOutput;
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct NetworkPlanOutput {
+    pub name: String,
+    /// The most bits per second sent or received in each direction.
+    pub bandwidth: Option<u64>,
+    /// The delay added to everything sent or received in each direction.
+    pub latency: Option<Duration>,
+    /// The most random extra delay added on top of the latency.
+    pub jitter: Option<Duration>,
+    /// The fraction of writes and reads delayed as though they were lost and retransmitted.
+    pub loss: Option<f64>,
+    /// Faults injected when the step doesn't set tcp.fault itself.
+    pub fault: Option<TcpFaultPlanOutput>,
+}
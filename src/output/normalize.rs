@@ -7,11 +7,12 @@ use crate::{
 };
 
 use super::{
-    GraphqlOutput, GraphqlRequestOutput, GraphqlResponse, Http1Output, Http1RequestOutput,
-    Http1Response, Http2FrameOutput, Http2Output, Http2RequestOutput, Http2Response, HttpOutput,
-    HttpRequestOutput, HttpResponse, JobOutput, RawHttp2Output, RawTcpOutput, RunOutput,
-    StepOutput, TcpOutput, TcpReceivedOutput, TcpSegmentOutput, TcpSentOutput, TlsOutput,
-    TlsReceivedOutput, TlsSentOutput,
+    GraphqlOutput, GraphqlRequestOutput, GraphqlResponse, GrpcOutput, Http1Output,
+    Http1RequestOutput, Http1Response, Http2FrameOutput, Http2Output, Http2RequestOutput,
+    Http2Response, Http3Output, Http3RequestOutput, Http3Response, HttpOutput, HttpRequestOutput,
+    HttpResponse, JobOutput, QuicOutput, RawHttp2Output, RawTcpOutput, RunOutput, StepOutput,
+    TcpOutput, TcpReceivedOutput, TcpSegmentOutput, TcpSentOutput, TlsOutput, TlsReceivedOutput,
+    TlsSentOutput, WebSocketFrameOutput, WebSocketHandshakeOutput, WebSocketOutput,
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -30,6 +31,7 @@ pub enum Normalized {
     Job(Vec<Arc<JobOutput>>),
 
     Graphql(Vec<Arc<GraphqlOutput>>),
+    Grpc(Vec<Arc<GrpcOutput>>),
     Http(Vec<Arc<HttpOutput>>),
     H1c(Vec<Arc<Http1Output>>),
     H1(Vec<Arc<Http1Output>>),
@@ -37,10 +39,12 @@ pub enum Normalized {
     RawH2c(Vec<Arc<RawHttp2Output>>),
     H2(Vec<Arc<Http2Output>>),
     RawH2(Vec<Arc<RawHttp2Output>>),
-    //Http3(Arc<Http3Output>),
+    H3(Vec<Arc<Http3Output>>),
     Tls(Vec<Arc<TlsOutput>>),
     Tcp(Vec<Arc<TcpOutput>>),
     RawTcp(Vec<Arc<RawTcpOutput>>),
+    Quic(Vec<Arc<QuicOutput>>),
+    Ws(Vec<Arc<WebSocketOutput>>),
 
     GraphqlRequest(Vec<Arc<GraphqlRequestOutput>>),
     GraphqlResponse(Vec<Arc<GraphqlResponse>>),
@@ -54,8 +58,12 @@ pub enum Normalized {
     H2cResponse(Vec<Arc<Http2Response>>),
     H2Request(Vec<Arc<Http2RequestOutput>>),
     H2Response(Vec<Arc<Http2Response>>),
+    H3Request(Vec<Arc<Http3RequestOutput>>),
+    H3Response(Vec<Arc<Http3Response>>),
     RawH2cFrame(Vec<Arc<Http2FrameOutput>>),
     RawH2Frame(Vec<Arc<Http2FrameOutput>>),
+    WsHandshake(Vec<Arc<WebSocketHandshakeOutput>>),
+    WsFrame(Vec<Arc<WebSocketFrameOutput>>),
     TlsSent(Vec<Arc<TlsSentOutput>>),
     TlsReceived(Vec<Arc<TlsReceivedOutput>>),
     TcpSent(Vec<Arc<TcpSentOutput>>),
@@ -72,6 +80,7 @@ impl Normalized {
             Self::Job(x) => x.is_empty(),
 
             Self::Graphql(x) => x.is_empty(),
+            Self::Grpc(x) => x.is_empty(),
             Self::Http(x) => x.is_empty(),
             Self::H1c(x) => x.is_empty(),
             Self::H1(x) => x.is_empty(),
@@ -79,9 +88,12 @@ impl Normalized {
             Self::RawH2c(x) => x.is_empty(),
             Self::H2(x) => x.is_empty(),
             Self::RawH2(x) => x.is_empty(),
+            Self::H3(x) => x.is_empty(),
             Self::Tls(x) => x.is_empty(),
             Self::Tcp(x) => x.is_empty(),
             Self::RawTcp(x) => x.is_empty(),
+            Self::Quic(x) => x.is_empty(),
+            Self::Ws(x) => x.is_empty(),
 
             Self::GraphqlRequest(x) => x.is_empty(),
             Self::GraphqlResponse(x) => x.is_empty(),
@@ -95,8 +107,12 @@ impl Normalized {
             Self::H2cResponse(x) => x.is_empty(),
             Self::H2Request(x) => x.is_empty(),
             Self::H2Response(x) => x.is_empty(),
+            Self::H3Request(x) => x.is_empty(),
+            Self::H3Response(x) => x.is_empty(),
             Self::RawH2cFrame(x) => x.is_empty(),
             Self::RawH2Frame(x) => x.is_empty(),
+            Self::WsHandshake(x) => x.is_empty(),
+            Self::WsFrame(x) => x.is_empty(),
             Self::TlsSent(x) => x.is_empty(),
             Self::TlsReceived(x) => x.is_empty(),
             Self::TcpSent(x) => x.is_empty(),
@@ -116,6 +132,7 @@ impl Normalized {
             Self::Job(x) => w.write(x, layers).await?,
 
             Self::Graphql(x) => w.write(x, layers).await?,
+            Self::Grpc(x) => w.write(x, layers).await?,
             Self::Http(x) => w.write(x, layers).await?,
             Self::H1c(x) => w.write(x, layers).await?,
             Self::H1(x) => w.write(x, layers).await?,
@@ -123,9 +140,12 @@ impl Normalized {
             Self::RawH2c(x) => w.write(x, layers).await?,
             Self::H2(x) => w.write(x, layers).await?,
             Self::RawH2(x) => w.write(x, layers).await?,
+            Self::H3(x) => w.write(x, layers).await?,
             Self::Tls(x) => w.write(x, layers).await?,
             Self::Tcp(x) => w.write(x, layers).await?,
             Self::RawTcp(x) => w.write(x, layers).await?,
+            Self::Quic(x) => w.write(x, layers).await?,
+            Self::Ws(x) => w.write(x, layers).await?,
 
             Self::GraphqlRequest(x) => w.write(x, layers).await?,
             Self::GraphqlResponse(x) => w.write(x, layers).await?,
@@ -139,8 +159,12 @@ impl Normalized {
             Self::H2cResponse(x) => w.write(x, layers).await?,
             Self::H2Request(x) => w.write(x, layers).await?,
             Self::H2Response(x) => w.write(x, layers).await?,
+            Self::H3Request(x) => w.write(x, layers).await?,
+            Self::H3Response(x) => w.write(x, layers).await?,
             Self::RawH2cFrame(x) => w.write(x, layers).await?,
             Self::RawH2Frame(x) => w.write(x, layers).await?,
+            Self::WsHandshake(x) => w.write(x, layers).await?,
+            Self::WsFrame(x) => w.write(x, layers).await?,
             Self::TlsSent(x) => w.write(x, layers).await?,
             Self::TlsReceived(x) => w.write(x, layers).await?,
             Self::TcpSent(x) => w.write(x, layers).await?,
@@ -160,6 +184,10 @@ impl JobOutput {
                     .as_ref()
                     .cloned()
                     .map(|x| Normalized::Graphql(vec![x])),
+                self.grpc
+                    .as_ref()
+                    .cloned()
+                    .map(|x| Normalized::Grpc(vec![x])),
                 self.http
                     .as_ref()
                     .cloned()
@@ -168,6 +196,7 @@ impl JobOutput {
                 self.h1c.as_ref().cloned().map(|x| Normalized::H1c(vec![x])),
                 self.h2.as_ref().cloned().map(|x| Normalized::H2(vec![x])),
                 self.h2c.as_ref().cloned().map(|x| Normalized::H2c(vec![x])),
+                self.h3.as_ref().cloned().map(|x| Normalized::H3(vec![x])),
                 self.raw_h2
                     .as_ref()
                     .cloned()
@@ -182,6 +211,11 @@ impl JobOutput {
                     .as_ref()
                     .cloned()
                     .map(|x| Normalized::RawTcp(vec![x])),
+                self.quic
+                    .as_ref()
+                    .cloned()
+                    .map(|x| Normalized::Quic(vec![x])),
+                self.ws.as_ref().cloned().map(|x| Normalized::Ws(vec![x])),
             ]
             .into_iter()
             .filter_map(|x| x)
@@ -247,6 +281,16 @@ impl JobOutput {
                     .map(|x| x.response.clone())
                     .flatten()
                     .map(|resp| Normalized::H2cResponse(vec![resp])),
+                self.h3
+                    .as_ref()
+                    .map(|x| x.request.clone())
+                    .flatten()
+                    .map(|req| Normalized::H3Request(vec![req])),
+                self.h3
+                    .as_ref()
+                    .map(|x| x.response.clone())
+                    .flatten()
+                    .map(|resp| Normalized::H3Response(vec![resp])),
                 self.raw_h2.as_ref().map(|x| {
                     Normalized::RawH2Frame(
                         x.sent.iter().chain(x.received.iter()).cloned().collect(),
@@ -257,6 +301,14 @@ impl JobOutput {
                         x.sent.iter().chain(x.received.iter()).cloned().collect(),
                     )
                 }),
+                self.ws
+                    .as_ref()
+                    .map(|x| x.handshake.clone())
+                    .flatten()
+                    .map(|handshake| Normalized::WsHandshake(vec![handshake])),
+                self.ws
+                    .as_ref()
+                    .map(|x| Normalized::WsFrame(x.frames.clone())),
                 self.tls
                     .as_ref()
                     .map(|x| x.sent.clone())
@@ -303,6 +355,12 @@ impl StepOutput {
                         .filter_map(|job| job.graphql.clone())
                         .collect(),
                 ),
+                Normalized::Grpc(
+                    self.jobs
+                        .values()
+                        .filter_map(|job| job.grpc.clone())
+                        .collect(),
+                ),
                 Normalized::Http(
                     self.jobs
                         .values()
@@ -333,6 +391,12 @@ impl StepOutput {
                         .filter_map(|job| job.h2c.clone())
                         .collect(),
                 ),
+                Normalized::H3(
+                    self.jobs
+                        .values()
+                        .filter_map(|job| job.h3.clone())
+                        .collect(),
+                ),
                 Normalized::RawH2(
                     self.jobs
                         .values()
@@ -363,6 +427,18 @@ impl StepOutput {
                         .filter_map(|job| job.raw_tcp.clone())
                         .collect(),
                 ),
+                Normalized::Quic(
+                    self.jobs
+                        .values()
+                        .filter_map(|job| job.quic.clone())
+                        .collect(),
+                ),
+                Normalized::Ws(
+                    self.jobs
+                        .values()
+                        .filter_map(|job| job.ws.clone())
+                        .collect(),
+                ),
             ]
             .into_iter()
             .filter(|x| !x.is_empty())
@@ -452,6 +528,20 @@ impl StepOutput {
                         .filter_map(|proto| proto.response.clone())
                         .collect(),
                 ),
+                Normalized::H3Request(
+                    self.jobs
+                        .values()
+                        .filter_map(|job| job.h3.as_ref())
+                        .filter_map(|proto| proto.request.clone())
+                        .collect(),
+                ),
+                Normalized::H3Response(
+                    self.jobs
+                        .values()
+                        .filter_map(|job| job.h3.as_ref())
+                        .filter_map(|proto| proto.response.clone())
+                        .collect(),
+                ),
                 Normalized::RawH2Frame(
                     self.jobs
                         .values()
@@ -468,6 +558,21 @@ impl StepOutput {
                         .flatten()
                         .collect(),
                 ),
+                Normalized::WsHandshake(
+                    self.jobs
+                        .values()
+                        .filter_map(|job| job.ws.as_ref())
+                        .filter_map(|proto| proto.handshake.clone())
+                        .collect(),
+                ),
+                Normalized::WsFrame(
+                    self.jobs
+                        .values()
+                        .filter_map(|job| job.ws.as_ref())
+                        .map(|proto| proto.frames.iter().cloned())
+                        .flatten()
+                        .collect(),
+                ),
                 Normalized::TlsSent(
                     self.jobs
                         .values()
@@ -534,6 +639,14 @@ impl RunOutput {
                         .filter_map(|job| job.graphql.clone())
                         .collect(),
                 ),
+                Normalized::Grpc(
+                    self.steps
+                        .values()
+                        .map(|step| step.jobs.values())
+                        .flatten()
+                        .filter_map(|job| job.grpc.clone())
+                        .collect(),
+                ),
                 Normalized::Http(
                     self.steps
                         .values()
@@ -574,6 +687,14 @@ impl RunOutput {
                         .filter_map(|job| job.h2c.clone())
                         .collect(),
                 ),
+                Normalized::H3(
+                    self.steps
+                        .values()
+                        .map(|step| step.jobs.values())
+                        .flatten()
+                        .filter_map(|job| job.h3.clone())
+                        .collect(),
+                ),
                 Normalized::RawH2(
                     self.steps
                         .values()
@@ -614,6 +735,22 @@ impl RunOutput {
                         .filter_map(|job| job.raw_tcp.clone())
                         .collect(),
                 ),
+                Normalized::Quic(
+                    self.steps
+                        .values()
+                        .map(|step| step.jobs.values())
+                        .flatten()
+                        .filter_map(|job| job.quic.clone())
+                        .collect(),
+                ),
+                Normalized::Ws(
+                    self.steps
+                        .values()
+                        .map(|step| step.jobs.values())
+                        .flatten()
+                        .filter_map(|job| job.ws.clone())
+                        .collect(),
+                ),
             ]
             .into_iter()
             .filter(|x| !x.is_empty())
@@ -727,6 +864,24 @@ impl RunOutput {
                         .filter_map(|proto| proto.response.clone())
                         .collect(),
                 ),
+                Normalized::H3Request(
+                    self.steps
+                        .values()
+                        .map(|step| step.jobs.values())
+                        .flatten()
+                        .filter_map(|job| job.h3.as_ref())
+                        .filter_map(|proto| proto.request.clone())
+                        .collect(),
+                ),
+                Normalized::H3Response(
+                    self.steps
+                        .values()
+                        .map(|step| step.jobs.values())
+                        .flatten()
+                        .filter_map(|job| job.h3.as_ref())
+                        .filter_map(|proto| proto.response.clone())
+                        .collect(),
+                ),
                 Normalized::RawH2Frame(
                     self.steps
                         .values()
@@ -747,6 +902,25 @@ impl RunOutput {
                         .flatten()
                         .collect(),
                 ),
+                Normalized::WsHandshake(
+                    self.steps
+                        .values()
+                        .map(|step| step.jobs.values())
+                        .flatten()
+                        .filter_map(|job| job.ws.as_ref())
+                        .filter_map(|proto| proto.handshake.clone())
+                        .collect(),
+                ),
+                Normalized::WsFrame(
+                    self.steps
+                        .values()
+                        .map(|step| step.jobs.values())
+                        .flatten()
+                        .filter_map(|job| job.ws.as_ref())
+                        .map(|proto| proto.frames.iter().cloned())
+                        .flatten()
+                        .collect(),
+                ),
                 Normalized::TlsSent(
                     self.steps
                         .values()
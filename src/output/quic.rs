@@ -0,0 +1,41 @@
+use cel_interpreter::Duration;
+use devil_derive::{BigQuerySchema, Record};
+use serde::Serialize;
+
+use super::{MaybeUtf8, ProtocolName, TlsVersion};
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema, Record)]
+#[serde(tag = "kind", rename = "quic")]
+#[bigquery(tag = "kind")]
+#[record(rename = "quic")]
+pub struct QuicOutput {
+    pub name: ProtocolName,
+    pub plan: QuicPlanOutput,
+    /// The UDP address the connection was sent from.
+    pub local_addr: Option<String>,
+    pub remote_addr: Option<String>,
+    /// The application protocol the server selected from plan.alpn.
+    pub alpn: Option<MaybeUtf8>,
+    /// The connection's smoothed round trip time when it closed.
+    pub rtt: Option<Duration>,
+    pub sent_packets: u64,
+    pub lost_packets: u64,
+    pub errors: Vec<QuicError>,
+    pub duration: Duration,
+    pub handshake_duration: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct QuicPlanOutput {
+    pub host: String,
+    pub port: u16,
+    pub alpn: Vec<MaybeUtf8>,
+    /// QUIC always uses TLS 1.3, so anything else fails to connect.
+    pub tls_version: Option<TlsVersion>,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct QuicError {
+    pub kind: String,
+    pub message: String,
+}
@@ -0,0 +1,40 @@
+use devil_derive::BigQuerySchema;
+use serde::Serialize;
+use url::Url;
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct RangePlanOutput {
+    pub url: Url,
+    /// How many ranges to ask for in the many parts case.
+    pub max_parts: u64,
+}
+
+/// The results of each Range case checked against the full resource.
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct RangeOutput {
+    pub plan: RangePlanOutput,
+    pub status_code: Option<u16>,
+    /// The length of the full resource.
+    pub length: u64,
+    pub accept_ranges: Option<String>,
+    pub cases: Vec<RangeCaseOutput>,
+    /// Whether the full resource was unchanged when requested again after the Range requests,
+    /// which a cache storing partial responses as complete ones would break.
+    pub cache_consistent: Option<bool>,
+    /// Whether any case found a problem.
+    pub vulnerable: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct RangeCaseOutput {
+    pub name: String,
+    /// The Range header sent.
+    pub range: String,
+    pub status_code: Option<u16>,
+    pub content_range: Option<String>,
+    /// How many ranges the response contained.
+    pub parts: u64,
+    pub issues: Vec<String>,
+    pub error: Option<String>,
+}
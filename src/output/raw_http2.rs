@@ -6,10 +6,12 @@ use byteorder::{ByteOrder, NetworkEndian};
 use bytes::{Buf, Bytes};
 use cel_interpreter::Duration;
 use devil_derive::{BigQuerySchema, Record};
+#[cfg(feature = "native")]
 use gcp_bigquery_client::model::table_field_schema::TableFieldSchema;
 use serde::Serialize;
 use tokio::io::{AsyncWrite, AsyncWriteExt};
 
+#[cfg(feature = "native")]
 use crate::record::BigQuerySchema;
 
 use super::{BytesOutput, Direction, MaybeUtf8, PduName, ProtocolName};
@@ -26,6 +28,8 @@ pub struct Http2FrameOutput {
     #[serde(flatten)]
     pub payload: Http2FramePayloadOutput,
     pub direction: Direction,
+    /// When the frame was fully sent or received, relative to the start of the connection.
+    pub time: Option<Duration>,
 }
 
 impl Http2FrameOutput {
@@ -45,6 +49,7 @@ impl Http2FrameOutput {
             stream_id,
             payload: Http2FramePayloadOutput::new(frame_type, flags, payload),
             direction,
+            time: None,
         }
     }
 
@@ -338,6 +343,7 @@ pub enum Http2FrameFlag {
     Priority = 0x20,
 }
 
+#[cfg(feature = "native")]
 impl BigQuerySchema for Http2FrameFlag {
     fn big_query_schema(name: &str) -> TableFieldSchema {
         TableFieldSchema::integer(name)
@@ -804,10 +810,57 @@ pub struct RawHttp2Output {
     pub plan: RawHttp2PlanOutput,
     pub sent: Vec<Arc<Http2FrameOutput>>,
     pub received: Vec<Arc<Http2FrameOutput>>,
+    /// The peer's view of the header blocks in sent frames.
+    pub sent_hpack: HpackOutput,
+    /// Our view of the header blocks in received frames.
+    pub received_hpack: HpackOutput,
     pub errors: Vec<RawHttp2Error>,
     pub duration: Duration,
 }
 
+/// What an HPACK decoder made of every header block sent in one direction.
+#[derive(Debug, Clone, Default, Serialize, BigQuerySchema)]
+pub struct HpackOutput {
+    pub blocks: Vec<HpackBlockOutput>,
+    /// The dynamic table after the last decoded block, newest entry first.
+    pub dynamic_table: Vec<HpackEntryOutput>,
+    /// The dynamic table's size in octets, counting 32 octets of overhead per entry.
+    pub table_size: u64,
+    /// The table size set by the last size update, which starts at the header table size
+    /// setting.
+    pub max_table_size: u64,
+    /// Problems assembling blocks from frames, like CONTINUATION frames without a HEADERS frame.
+    pub anomalies: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct HpackBlockOutput {
+    pub stream_id: u32,
+    pub fields: Vec<HpackFieldOutput>,
+    /// Dynamic table size updates at the start of the block.
+    pub table_size_updates: Vec<u64>,
+    /// Fields a server should reject, and the compression error that stopped decoding if any.
+    pub anomalies: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct HpackFieldOutput {
+    pub name: MaybeUtf8,
+    pub value: MaybeUtf8,
+    /// One of indexed, incremental, without_indexing, or never_indexed.
+    pub representation: String,
+    /// The table index the field or its name came from.
+    pub index: Option<u64>,
+    /// Whether any literal string in the field was Huffman coded.
+    pub huffman: bool,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct HpackEntryOutput {
+    pub name: MaybeUtf8,
+    pub value: MaybeUtf8,
+}
+
 #[derive(Debug, Clone, Serialize, BigQuerySchema)]
 pub struct RawHttp2PlanOutput {
     pub host: String,
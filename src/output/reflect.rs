@@ -0,0 +1,42 @@
+use devil_derive::BigQuerySchema;
+use serde::Serialize;
+
+/// A marker from marker() found in a response.
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct ReflectionOutput {
+    pub marker: String,
+    /// The first step whose request carried the marker.
+    pub source: String,
+    /// The earlier run the source step belongs to, when searching for markers from another run.
+    pub source_run: Option<String>,
+    /// Whether the marker was sent by an earlier step or run rather than this step, which points
+    /// at a stored injection sink.
+    pub stored: bool,
+    /// The key of the job whose response held the marker.
+    pub job: String,
+    /// The protocol the response was read from.
+    pub protocol: String,
+    pub location: ReflectionLocation,
+    /// The header's name, for reflections in a header.
+    pub header: Option<String>,
+    /// The byte offset of the marker within the status reason, header value, or body.
+    pub offset: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, BigQuerySchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReflectionLocation {
+    StatusLine,
+    Header,
+    Body,
+}
+
+impl ReflectionLocation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::StatusLine => "status_line",
+            Self::Header => "header",
+            Self::Body => "body",
+        }
+    }
+}
@@ -0,0 +1,23 @@
+use cel_interpreter::Duration;
+use devil_derive::BigQuerySchema;
+use indexmap::IndexMap;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct ScriptPlanOutput {
+    pub source: String,
+    /// How many operations the script may run before it's stopped.
+    pub max_operations: u64,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct ScriptOutput {
+    pub plan: ScriptPlanOutput,
+    /// The value of the script's last expression.
+    pub result: serde_json::Value,
+    /// Locals the script added or changed, which later steps see.
+    pub locals: IndexMap<String, serde_json::Value>,
+    /// Lines passed to print or debug.
+    pub printed: Vec<String>,
+    pub duration: Duration,
+}
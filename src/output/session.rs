@@ -0,0 +1,45 @@
+use cel_interpreter::Duration;
+use devil_derive::BigQuerySchema;
+use indexmap::IndexMap;
+use serde::Serialize;
+
+use super::ModuleOutput;
+use crate::ThinkDistribution;
+
+#[derive(Debug, Clone)]
+pub struct SessionPlanOutput {
+    pub path: String,
+    pub params: IndexMap<String, cel_interpreter::Value>,
+    pub users: u64,
+    /// How many times each user runs the journey.
+    pub iterations: u64,
+    /// Users start evenly spread over this long.
+    pub ramp_up: Option<Duration>,
+    pub think_time: Option<ThinkTimePlanOutput>,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct ThinkTimePlanOutput {
+    pub distribution: ThinkDistribution,
+    pub min: Option<Duration>,
+    pub max: Option<Duration>,
+    pub mean: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct SessionOutput {
+    pub path: String,
+    pub think_time: Option<ThinkTimePlanOutput>,
+    pub users: Vec<SessionUserOutput>,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct SessionUserOutput {
+    pub user: u64,
+    /// The output of each run through the journey.
+    pub iterations: Vec<ModuleOutput>,
+    /// Total time spent pausing between steps.
+    pub think_time: Duration,
+    /// Why the user stopped early, if it did.
+    pub error: Option<String>,
+}
@@ -0,0 +1,56 @@
+use devil_derive::BigQuerySchema;
+#[cfg(feature = "native")]
+use gcp_bigquery_client::model::table_field_schema::TableFieldSchema;
+use serde::Serialize;
+
+#[cfg(feature = "native")]
+use crate::record::BigQuerySchema;
+use crate::SignKind;
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct SignPlanOutput {
+    pub kind: SignKind,
+    pub access_key: Option<String>,
+    pub secret_key: Option<Secret>,
+    pub session_token: Option<Secret>,
+    pub region: Option<String>,
+    pub service: Option<String>,
+    pub algorithm: Option<String>,
+    pub components: Option<String>,
+    pub separator: Option<String>,
+    pub encoding: Option<String>,
+    pub header: Option<String>,
+    pub prefix: Option<String>,
+}
+
+/// A value which is used while running but never written to output.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(pub String);
+
+impl Secret {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Secret([redacted])")
+    }
+}
+
+impl Serialize for Secret {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str("[redacted]")
+    }
+}
+
+#[cfg(feature = "native")]
+impl BigQuerySchema for Secret {
+    fn big_query_schema(name: &str) -> TableFieldSchema {
+        TableFieldSchema::string(name)
+    }
+}
@@ -0,0 +1,43 @@
+use devil_derive::BigQuerySchema;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct TakeoverPlanOutput {
+    pub hosts: Vec<String>,
+    /// The resolver's address, or none to use the system resolver.
+    pub resolver: Option<String>,
+}
+
+/// How each host resolved and which look open to takeover.
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct TakeoverOutput {
+    pub plan: TakeoverPlanOutput,
+    /// The resolver actually queried.
+    pub resolver: String,
+    pub hosts: Vec<TakeoverHostOutput>,
+    pub findings: Vec<TakeoverFindingOutput>,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct TakeoverHostOutput {
+    pub host: String,
+    /// Each CNAME target in order, ending with the final name.
+    pub chain: Vec<String>,
+    /// Whether the final name doesn't exist.
+    pub nxdomain: bool,
+    pub addresses: Vec<String>,
+    /// The hosting service the chain points to, if recognized.
+    pub service: Option<String>,
+    pub status_code: Option<u16>,
+    pub error: Option<String>,
+}
+
+/// A host whose CNAME points at a resource that can likely be claimed by someone else.
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct TakeoverFindingOutput {
+    pub host: String,
+    pub target: String,
+    pub service: String,
+    /// Why the target looks unclaimed.
+    pub evidence: String,
+}
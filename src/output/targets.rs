@@ -0,0 +1,85 @@
+use std::fmt::Display;
+use std::sync::Arc;
+
+use serde::Serialize;
+
+use super::{RunName, StepOutput};
+use crate::notify::{Notification, Severity};
+
+/// How one target fared when a plan runs against several.
+#[derive(Debug, Clone, Serialize)]
+pub struct TargetSummary {
+    pub target: String,
+    pub run: RunName,
+    pub steps: u64,
+    /// Steps which recorded at least one error.
+    pub failed_steps: u64,
+    /// Notifications of high severity or above, like possible subdomain takeovers.
+    pub findings: u64,
+    /// Why the run stopped before its last step, if it did.
+    pub error: Option<String>,
+}
+
+impl TargetSummary {
+    pub fn new(target: String, run: RunName) -> Self {
+        Self {
+            target,
+            run,
+            steps: 0,
+            failed_steps: 0,
+            findings: 0,
+            error: None,
+        }
+    }
+
+    /// Count a finished step toward the summary.
+    pub fn add_step(&mut self, step: &StepOutput) {
+        self.steps += 1;
+        if !step.errors.is_empty() {
+            self.failed_steps += 1;
+        }
+        self.findings += Notification::for_step(step)
+            .iter()
+            .filter(|n| n.severity >= Severity::High)
+            .count() as u64;
+    }
+
+    fn clean(&self) -> bool {
+        self.failed_steps == 0 && self.findings == 0 && self.error.is_none()
+    }
+}
+
+/// Each target's results side by side, so differences across a fleet stand out.
+#[derive(Debug, Clone, Serialize)]
+pub struct TargetsSummary {
+    pub plan: Arc<String>,
+    pub targets: Vec<TargetSummary>,
+}
+
+impl Display for TargetsSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "------- {} across {} targets --------",
+            self.plan,
+            self.targets.len()
+        )?;
+        for target in &self.targets {
+            write!(
+                f,
+                "{}: {} steps, {} failed, {} findings",
+                target.target, target.steps, target.failed_steps, target.findings,
+            )?;
+            if let Some(error) = &target.error {
+                write!(f, ", stopped: {error}")?;
+            }
+            writeln!(f)?;
+        }
+        let flagged = self.targets.iter().filter(|t| !t.clean()).count();
+        writeln!(
+            f,
+            "{flagged} of {} targets had failures or findings",
+            self.targets.len()
+        )
+    }
+}
@@ -4,7 +4,8 @@ use cel_interpreter::Duration;
 use devil_derive::{BigQuerySchema, Record};
 use serde::Serialize;
 
-use super::{MaybeUtf8, PduName, ProtocolName};
+use super::{ExpectOutput, ExpectPlanOutput, HttpHeader, MaybeUtf8, PduName, ProtocolName, Secret};
+use crate::ProxyKind;
 
 #[derive(Debug, Clone, Serialize, BigQuerySchema, Record)]
 #[serde(tag = "kind", rename = "tcp")]
@@ -17,6 +18,12 @@ pub struct TcpOutput {
     pub received: Option<Arc<TcpReceivedOutput>>,
     //pub close: TcpCloseOutput,
     pub errors: Vec<TcpError>,
+    /// The handshake with each proxy hop, in connection order.
+    pub proxies: Vec<TcpProxyOutput>,
+    /// The PROXY protocol header sent ahead of the body, if any.
+    pub proxy_protocol_header: Option<MaybeUtf8>,
+    /// The outcome of each of plan.expect, in the same order.
+    pub expect: Vec<ExpectOutput>,
     pub duration: Duration,
     pub handshake_duration: Option<Duration>,
 }
@@ -33,9 +40,64 @@ pub struct TcpPlanOutput {
     pub host: String,
     pub port: u16,
     pub body: MaybeUtf8,
+    pub proxies: Vec<TcpProxyPlanOutput>,
+    pub proxy_protocol: Option<TcpProxyProtocolPlanOutput>,
+    pub fault: Option<TcpFaultPlanOutput>,
+    pub expect: Vec<ExpectPlanOutput>,
     //pub close: TcpPlanCloseOutput,
 }
 
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct TcpFaultPlanOutput {
+    /// Drop the connection once this many bytes have been sent.
+    pub drop_after: Option<u64>,
+    /// The offset in the sent bytes of a range to corrupt by inverting each bit.
+    pub corrupt_offset: Option<u64>,
+    pub corrupt_length: u64,
+    /// Send every write twice.
+    pub duplicate_writes: bool,
+    /// Disable quick ACKs where the platform supports it, so the peer sees delayed ACKs.
+    pub delay_acks: bool,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct TcpProxyProtocolPlanOutput {
+    pub version: u8,
+    pub source_host: String,
+    pub source_port: u16,
+    /// Defaults to the connected peer's address.
+    pub dest_host: Option<String>,
+    /// Defaults to the connected peer's port.
+    pub dest_port: Option<u16>,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct TcpProxyPlanOutput {
+    pub kind: ProxyKind,
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<Secret>,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct TcpProxyOutput {
+    pub kind: ProxyKind,
+    pub host: String,
+    pub port: u16,
+    /// The address the proxy was asked to connect to.
+    pub target_host: String,
+    pub target_port: u16,
+    pub sent: MaybeUtf8,
+    pub received: MaybeUtf8,
+    /// The status of an http proxy's reply to CONNECT.
+    pub status_code: Option<u16>,
+    /// The headers of an http proxy's reply to CONNECT.
+    pub headers: Vec<HttpHeader>,
+    pub duration: Duration,
+    pub error: Option<String>,
+}
+
 //#[derive(Debug, Clone, Default)]
 //pub struct TcpPlanCloseOutput {
 //    pub min_duration: Option<Duration>,
@@ -0,0 +1,44 @@
+use cel_interpreter::Duration;
+use devil_derive::BigQuerySchema;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct TcpBurstPlanOutput {
+    pub host: String,
+    pub port: u16,
+    /// How many connections to open at once.
+    pub connections: u64,
+    /// How long to wait for each connection to be established.
+    pub timeout: Duration,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct TcpBurstOutput {
+    pub plan: TcpBurstPlanOutput,
+    /// The address every connection was made to, resolved once before the burst.
+    pub remote_addr: Option<String>,
+    pub connections: Vec<BurstConnectionOutput>,
+    pub established: u64,
+    pub failed: u64,
+    /// The time between the first and last connection attempts starting.
+    pub spread: Option<Duration>,
+    /// The distribution of how long established connections took from SYN to established.
+    pub connect_min: Option<Duration>,
+    pub connect_p50: Option<Duration>,
+    pub connect_p90: Option<Duration>,
+    pub connect_p99: Option<Duration>,
+    pub connect_max: Option<Duration>,
+    pub error: Option<String>,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct BurstConnectionOutput {
+    pub index: u64,
+    /// Unset since socket providers don't report the local address they connected from.
+    pub local_port: Option<u16>,
+    /// When the connection attempt started, relative to the first attempt.
+    pub started_at: Duration,
+    pub connect_duration: Option<Duration>,
+    pub error: Option<String>,
+}
@@ -5,7 +5,9 @@ use cel_interpreter::Duration;
 use devil_derive::{BigQuerySchema, Record};
 use serde::Serialize;
 
-use super::{MaybeUtf8, PduName, ProtocolName};
+use super::{
+    BytesOutput, ExpectOutput, ExpectPlanOutput, MaybeUtf8, PduName, ProtocolName, Secret,
+};
 
 #[derive(Debug, Clone, Serialize, BigQuerySchema, Record)]
 #[serde(tag = "kind", rename = "tls")]
@@ -18,6 +20,28 @@ pub struct TlsOutput {
     pub received: Option<Arc<TlsReceivedOutput>>,
     pub errors: Vec<TlsError>,
     pub version: Option<TlsVersion>,
+    /// The negotiated cipher suite.
+    pub cipher_suite: Option<String>,
+    /// The application protocol the server selected from plan.alpn.
+    pub alpn: Option<MaybeUtf8>,
+    /// The server's leaf certificate.
+    pub certificate: Option<TlsCertificateOutput>,
+    /// The certificates the server presented, starting with the leaf.
+    pub chain: Vec<TlsCertificateOutput>,
+    /// Whether the server asked for a client certificate during the handshake.
+    pub client_cert_requested: bool,
+    /// Whether a client certificate was sent in response.
+    pub client_cert_sent: bool,
+    /// How the server's certificate was verified.
+    pub verification: TlsVerification,
+    /// Whether the handshake resumed an earlier session instead of verifying the certificate.
+    pub resumed: bool,
+    /// How the session was resumed, if known. TLS 1.3 always resumes with a ticket. TLS 1.2 is
+    /// only known to resume by session ID when plan.session_tickets is false, since servers may
+    /// accept either when a ticket is offered.
+    pub resumption: Option<TlsResumption>,
+    /// The outcome of each of plan.expect, in the same order.
+    pub expect: Vec<ExpectOutput>,
     pub duration: Duration,
     pub handshake_duration: Option<Duration>,
 }
@@ -26,8 +50,91 @@ pub struct TlsOutput {
 pub struct TlsPlanOutput {
     pub host: String,
     pub port: u16,
+    /// The name sent for SNI and verified against the certificate in place of host. An empty
+    /// name sends no SNI and verifies against host.
+    pub server_name: Option<String>,
     pub alpn: Vec<MaybeUtf8>,
     pub body: MaybeUtf8,
+    /// The oldest version offered.
+    pub min_version: Option<TlsVersion>,
+    /// The newest version offered.
+    pub max_version: Option<TlsVersion>,
+    /// The cipher suites offered in order of preference, named like TLS13_AES_128_GCM_SHA256, or
+    /// every supported suite if empty. Only suites for TLS 1.2 and 1.3 are supported.
+    pub cipher_suites: Vec<String>,
+    pub pin: Option<TlsPinPlanOutput>,
+    pub client: Option<TlsClientPlanOutput>,
+    /// PEM root certificates trusted in addition to the built in ones.
+    pub ca: Option<String>,
+    /// A file of PEM root certificates trusted in addition to the built in ones.
+    pub ca_file: Option<String>,
+    /// Accept any server certificate. Handshake signatures are still checked.
+    pub insecure: bool,
+    /// The earlier step whose TLS sessions to offer for resumption.
+    pub resume: Option<String>,
+    /// Whether TLS 1.2 sessions may be resumed with tickets, not only by session ID.
+    pub session_tickets: bool,
+    pub expect: Vec<ExpectPlanOutput>,
+}
+
+/// Pins checked after the handshake. The step fails unless the certificate chain includes a public
+/// key with one of the SPKI hashes and the leaf has one of the serials, for each list that is set.
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct TlsPinPlanOutput {
+    /// Base64 SHA-256 hashes of DER encoded SubjectPublicKeyInfo, as used by HPKP.
+    pub spki_sha256: Vec<String>,
+    /// Hex serial numbers, ignoring case, colons, and leading zeros.
+    pub serial: Vec<String>,
+}
+
+/// The certificate chain and private key presented if the server asks for a client certificate,
+/// each PEM encoded and set either inline or by file path.
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct TlsClientPlanOutput {
+    pub cert: Option<String>,
+    pub cert_file: Option<String>,
+    pub key: Option<Secret>,
+    pub key_file: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, BigQuerySchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TlsVerification {
+    /// Against the built in root certificates.
+    Default,
+    /// Against the built in root certificates and those in plan.ca or plan.ca_file.
+    CustomRoots,
+    /// Not at all, since plan.insecure is set.
+    Insecure,
+    /// By a verifier from the executor's VerifierProvider.
+    Custom,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, BigQuerySchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TlsResumption {
+    SessionId,
+    Ticket,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct TlsCertificateOutput {
+    /// The DER encoded certificate.
+    pub der: BytesOutput,
+    /// The serial number in lowercase hex.
+    pub serial: String,
+    /// The base64 SHA-256 hash of the DER encoded SubjectPublicKeyInfo.
+    pub spki_sha256: String,
+    /// Distinguished names like CN=example.com, O=Example, in the certificate's order.
+    pub subject: String,
+    pub issuer: String,
+    /// DNS names, IP addresses, emails, and URIs from the subject alternative name extension.
+    pub sans: Vec<String>,
+    /// RFC 3339 times, for parsing with timestamp() in CEL.
+    pub not_before: Option<String>,
+    pub not_after: Option<String>,
+    /// The public key's algorithm, like rsa, ec-p256, or ed25519.
+    pub key_algorithm: String,
 }
 
 #[derive(Debug, Clone, Serialize, BigQuerySchema, Record)]
@@ -0,0 +1,52 @@
+use devil_derive::BigQuerySchema;
+use serde::Serialize;
+use url::Url;
+
+use crate::VhostSni;
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct VhostPlanOutput {
+    /// The address to connect to along with the scheme, port, and path to request.
+    pub url: Url,
+    pub hosts: Vec<String>,
+    pub sni: VhostSni,
+    /// Body length difference still treated as the same response.
+    pub length_tolerance: u64,
+}
+
+/// Responses to each candidate Host, grouped by similarity.
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct VhostOutput {
+    pub plan: VhostPlanOutput,
+    pub probes: Vec<VhostProbeOutput>,
+    pub clusters: Vec<VhostClusterOutput>,
+    /// Candidate hosts with a response unlike the server's default.
+    pub distinct: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct VhostProbeOutput {
+    pub host: String,
+    /// The TLS server name sent, if any.
+    pub sni: Option<String>,
+    /// Whether the host is a random name used to find the default response.
+    pub baseline: bool,
+    pub status_code: Option<u16>,
+    /// The body length with the host removed.
+    pub length: u64,
+    pub title: Option<String>,
+    pub location: Option<String>,
+    pub error: Option<String>,
+    /// Index into the step's clusters.
+    pub cluster: u64,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct VhostClusterOutput {
+    pub status_code: Option<u16>,
+    pub length: u64,
+    pub title: Option<String>,
+    /// Whether any baseline probe landed in this cluster.
+    pub default: bool,
+    pub hosts: Vec<String>,
+}
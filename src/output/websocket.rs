@@ -0,0 +1,180 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::bail;
+use cel_interpreter::Duration;
+use devil_derive::{BigQuerySchema, Record};
+use serde::Serialize;
+use url::Url;
+
+use super::{Direction, HttpHeader, MaybeUtf8, PduName, ProtocolName};
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema, Record)]
+#[serde(tag = "kind", rename = "ws")]
+#[bigquery(tag = "kind")]
+#[record(rename = "ws")]
+pub struct WebSocketOutput {
+    pub name: ProtocolName,
+    pub plan: WebSocketPlanOutput,
+    pub handshake: Option<Arc<WebSocketHandshakeOutput>>,
+    /// Frames in the order they were sent or received.
+    pub frames: Vec<Arc<WebSocketFrameOutput>>,
+    pub errors: Vec<WebSocketError>,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct WebSocketPlanOutput {
+    pub url: Url,
+    pub headers: Vec<HttpHeader>,
+    pub frames: Vec<WebSocketFramePlanOutput>,
+    /// How long to wait for each frame the server is expected to send.
+    pub timeout: Duration,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct WebSocketFramePlanOutput {
+    pub opcode: WebSocketOpcode,
+    pub fin: bool,
+    /// The three reserved bits, which must be zero unless an extension was negotiated.
+    pub rsv: u8,
+    /// Whether to mask the payload. Clients are required to, so servers should reject unmasked
+    /// frames.
+    pub mask: bool,
+    /// A status code written before the payload, for close frames.
+    pub close_code: Option<u16>,
+    pub payload: MaybeUtf8,
+    /// The number of frames to read after sending this one.
+    pub receive: u64,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema, Record)]
+#[serde(tag = "kind", rename = "ws_handshake")]
+#[bigquery(tag = "kind")]
+#[record(rename = "ws_handshake")]
+pub struct WebSocketHandshakeOutput {
+    pub name: PduName,
+    /// The Sec-WebSocket-Key sent with the upgrade request.
+    pub key: String,
+    pub status_code: Option<u16>,
+    pub headers: Option<Vec<HttpHeader>>,
+    /// Whether the server's Sec-WebSocket-Accept matched the key.
+    pub accept_valid: bool,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema, Record)]
+#[serde(tag = "kind", rename = "ws_frame")]
+#[bigquery(tag = "kind")]
+#[record(rename = "ws_frame")]
+pub struct WebSocketFrameOutput {
+    pub name: PduName,
+    pub direction: Direction,
+    pub opcode: WebSocketOpcode,
+    pub fin: bool,
+    pub rsv: u8,
+    pub masking_key: Option<u32>,
+    /// The payload after unmasking.
+    pub payload: MaybeUtf8,
+    pub close_code: Option<u16>,
+    /// When the frame was fully sent or received, relative to the end of the handshake.
+    pub time: Duration,
+}
+
+#[derive(Debug, Clone, Serialize, BigQuerySchema)]
+pub struct WebSocketError {
+    pub kind: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, BigQuerySchema)]
+pub struct WebSocketOpcode {
+    pub parsed: Option<ParsedWebSocketOpcode>,
+    pub raw: u8,
+}
+
+impl WebSocketOpcode {
+    /// Whether the opcode is for a control frame, which can't be fragmented.
+    pub fn is_control(&self) -> bool {
+        self.raw & 0x8 != 0
+    }
+}
+
+impl From<u8> for WebSocketOpcode {
+    fn from(value: u8) -> Self {
+        Self {
+            parsed: match value {
+                0x0 => Some(ParsedWebSocketOpcode::Continuation),
+                0x1 => Some(ParsedWebSocketOpcode::Text),
+                0x2 => Some(ParsedWebSocketOpcode::Binary),
+                0x8 => Some(ParsedWebSocketOpcode::Close),
+                0x9 => Some(ParsedWebSocketOpcode::Ping),
+                0xA => Some(ParsedWebSocketOpcode::Pong),
+                _ => None,
+            },
+            raw: value,
+        }
+    }
+}
+
+impl From<ParsedWebSocketOpcode> for WebSocketOpcode {
+    fn from(value: ParsedWebSocketOpcode) -> Self {
+        Self {
+            parsed: Some(value),
+            raw: value.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, BigQuerySchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ParsedWebSocketOpcode {
+    Continuation,
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+}
+
+impl From<ParsedWebSocketOpcode> for u8 {
+    fn from(value: ParsedWebSocketOpcode) -> Self {
+        match value {
+            ParsedWebSocketOpcode::Continuation => 0x0,
+            ParsedWebSocketOpcode::Text => 0x1,
+            ParsedWebSocketOpcode::Binary => 0x2,
+            ParsedWebSocketOpcode::Close => 0x8,
+            ParsedWebSocketOpcode::Ping => 0x9,
+            ParsedWebSocketOpcode::Pong => 0xA,
+        }
+    }
+}
+
+impl FromStr for ParsedWebSocketOpcode {
+    type Err = crate::Error;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(match s {
+            "continuation" => Self::Continuation,
+            "text" => Self::Text,
+            "binary" => Self::Binary,
+            "close" => Self::Close,
+            "ping" => Self::Ping,
+            "pong" => Self::Pong,
+            _ => bail!("invalid websocket opcode {s}"),
+        })
+    }
+}
+
+impl std::fmt::Display for WebSocketOpcode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.parsed {
+            Some(ParsedWebSocketOpcode::Continuation) => write!(f, "continuation"),
+            Some(ParsedWebSocketOpcode::Text) => write!(f, "text"),
+            Some(ParsedWebSocketOpcode::Binary) => write!(f, "binary"),
+            Some(ParsedWebSocketOpcode::Close) => write!(f, "close"),
+            Some(ParsedWebSocketOpcode::Ping) => write!(f, "ping"),
+            Some(ParsedWebSocketOpcode::Pong) => write!(f, "pong"),
+            None => write!(f, "{:#x}", self.raw),
+        }
+    }
+}
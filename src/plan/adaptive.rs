@@ -0,0 +1,121 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::bail;
+use cel_interpreter::Duration;
+use devil_derive::BigQuerySchema;
+use serde::Serialize;
+
+use super::{Evaluate, PlanData, PlanValue, TryFromPlanData};
+use crate::bindings::Literal;
+use crate::{bindings, Error, Result, State};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, BigQuerySchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AdaptiveAlgorithm {
+    /// Add one to the limit per limit's worth of healthy jobs, and cut it by a tenth on each
+    /// unhealthy one.
+    #[default]
+    Aimd,
+    /// Scale the limit by how far latency is from the target, leaving headroom to probe for more
+    /// capacity.
+    Gradient,
+}
+
+impl FromStr for AdaptiveAlgorithm {
+    type Err = Error;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "aimd" => Ok(Self::Aimd),
+            "gradient" => Ok(Self::Gradient),
+            val => bail!("unrecognized run.adaptive.algorithm string {val}"),
+        }
+    }
+}
+
+impl TryFromPlanData for AdaptiveAlgorithm {
+    type Error = Error;
+    fn try_from_plan_data(value: PlanData) -> std::result::Result<Self, Self::Error> {
+        match value.0 {
+            cel_interpreter::Value::String(s) => s.parse(),
+            val => bail!("unsupported value {val:?} for field run.adaptive.algorithm"),
+        }
+    }
+}
+
+impl TryFrom<bindings::Value> for PlanValue<AdaptiveAlgorithm> {
+    type Error = Error;
+    fn try_from(binding: bindings::Value) -> Result<Self> {
+        match binding {
+            bindings::Value::ExpressionCel { cel, vars } => Ok(Self::Dynamic {
+                cel,
+                vars: vars.unwrap_or_default().into_iter().collect(),
+            }),
+            bindings::Value::Literal(Literal::String(x)) => Ok(Self::Literal(x.parse()?)),
+            val => bail!("invalid value {val:?} for field run.adaptive.algorithm"),
+        }
+    }
+}
+
+/// Adjusts a parallel step's concurrency to hold latency or errors under a target, for finding
+/// how much load a service can take without pushing it over.
+#[derive(Debug, Clone)]
+pub struct AdaptiveRequest {
+    pub algorithm: PlanValue<AdaptiveAlgorithm>,
+    pub target_latency: PlanValue<Option<Duration>>,
+    pub max_error_rate: PlanValue<Option<f64>>,
+    pub initial: PlanValue<Option<u64>>,
+    pub min: PlanValue<u64>,
+    pub max: PlanValue<u64>,
+}
+
+impl Evaluate<crate::AdaptivePlanOutput> for AdaptiveRequest {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> Result<crate::AdaptivePlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        let target_latency = self.target_latency.evaluate(state)?;
+        let max_error_rate = self.max_error_rate.evaluate(state)?;
+        if target_latency.is_none() && max_error_rate.is_none() {
+            bail!("run.adaptive requires target_latency or max_error_rate");
+        }
+        let min = self.min.evaluate(state)?.max(1);
+        let max = self.max.evaluate(state)?.max(min);
+        Ok(crate::AdaptivePlanOutput {
+            algorithm: self.algorithm.evaluate(state)?,
+            target_latency,
+            max_error_rate,
+            initial: self.initial.evaluate(state)?.unwrap_or(min).clamp(min, max),
+            min,
+            max,
+        })
+    }
+}
+
+impl TryFrom<bindings::Adaptive> for AdaptiveRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::Adaptive) -> Result<Self> {
+        Ok(Self {
+            algorithm: binding
+                .algorithm
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or_default(),
+            target_latency: binding.target_latency.try_into()?,
+            max_error_rate: binding.max_error_rate.try_into()?,
+            initial: binding.initial.try_into()?,
+            min: binding
+                .min
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(1)),
+            max: binding
+                .max
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(1000)),
+        })
+    }
+}
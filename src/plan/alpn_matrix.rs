@@ -0,0 +1,38 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use url::Url;
+
+use super::{Evaluate, PlanValue};
+use crate::{bindings, Error, Result, State};
+
+/// Requests a URL offering each ALPN protocol in turn and compares the responses.
+#[derive(Debug, Clone)]
+pub struct AlpnMatrixRequest {
+    pub url: PlanValue<Url>,
+}
+
+impl Evaluate<crate::AlpnMatrixPlanOutput> for AlpnMatrixRequest {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> Result<crate::AlpnMatrixPlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        Ok(crate::AlpnMatrixPlanOutput {
+            url: self.url.evaluate(state)?,
+        })
+    }
+}
+
+impl TryFrom<bindings::AlpnMatrix> for AlpnMatrixRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::AlpnMatrix) -> Result<Self> {
+        Ok(Self {
+            url: binding
+                .url
+                .map(PlanValue::try_from)
+                .ok_or_else(|| anyhow!("alpn_matrix.url is required"))??,
+        })
+    }
+}
@@ -0,0 +1,63 @@
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use indexmap::IndexMap;
+
+use super::{Evaluate, PlanData, PlanValue};
+use crate::{bindings, Error, Result, State};
+
+/// A login plan run before the first step tagged with its name in run.auth, and again whenever a
+/// tagged step's response is 401 Unauthorized. Tagged steps send the cookies the login was given
+/// and its token.
+#[derive(Debug, Clone)]
+pub struct AuthRequest {
+    pub path: PlanValue<String>,
+    pub params: IndexMap<String, PlanValue<PlanData, Infallible>>,
+    /// The header the token is sent in.
+    pub header: PlanValue<String>,
+    /// The header's value, evaluated against the login plan's steps once it has run.
+    pub token: Option<PlanValue<String>>,
+}
+
+impl Evaluate<crate::AuthPlanOutput> for AuthRequest {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> Result<crate::AuthPlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        Ok(crate::AuthPlanOutput {
+            path: self.path.evaluate(state)?,
+            params: self
+                .params
+                .iter()
+                .map(|(k, v)| Ok((k.clone(), v.evaluate(state)?.0)))
+                .collect::<Result<_>>()?,
+            header: self.header.evaluate(state)?,
+        })
+    }
+}
+
+impl TryFrom<bindings::Auth> for AuthRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::Auth) -> Result<Self> {
+        Ok(Self {
+            path: binding
+                .path
+                .map(PlanValue::try_from)
+                .ok_or_else(|| anyhow!("devil.auth.path is required"))??,
+            params: binding
+                .params
+                .into_iter()
+                .map(|(k, v)| Ok((k, PlanValue::try_from(v)?)))
+                .collect::<Result<_>>()?,
+            header: binding
+                .header
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or_else(|| PlanValue::Literal("Authorization".to_owned())),
+            token: binding.token.map(PlanValue::try_from).transpose()?,
+        })
+    }
+}
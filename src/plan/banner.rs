@@ -0,0 +1,70 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use cel_interpreter::Duration;
+use chrono::TimeDelta;
+
+use super::{Evaluate, IterablePlanValue, PlanValue, TryFromPlanData};
+use crate::{bindings, Error, MaybeUtf8, Result, State};
+
+/// Connects to many host:port targets at once and records what each sends first.
+#[derive(Debug, Clone)]
+pub struct BannerRequest {
+    pub targets: IterablePlanValue,
+    pub probe: PlanValue<Option<MaybeUtf8>>,
+    pub timeout: PlanValue<Duration>,
+    pub max_bytes: PlanValue<u64>,
+    pub concurrency: PlanValue<u64>,
+}
+
+impl Evaluate<crate::BannerPlanOutput> for BannerRequest {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> Result<crate::BannerPlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        Ok(crate::BannerPlanOutput {
+            targets: self
+                .targets
+                .evaluate(state)?
+                .into_iter()
+                .map(|(_, v)| {
+                    String::try_from_plan_data(v).map_err(|e| anyhow!("banner.targets: {e}"))
+                })
+                .collect::<Result<_>>()?,
+            probe: self.probe.evaluate(state)?,
+            timeout: self.timeout.evaluate(state)?,
+            max_bytes: self.max_bytes.evaluate(state)?,
+            concurrency: self.concurrency.evaluate(state)?.max(1),
+        })
+    }
+}
+
+impl TryFrom<bindings::Banner> for BannerRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::Banner) -> Result<Self> {
+        Ok(Self {
+            targets: binding
+                .targets
+                .map(IterablePlanValue::try_from)
+                .ok_or_else(|| anyhow!("banner.targets is required"))??,
+            probe: binding.probe.try_into()?,
+            timeout: binding
+                .timeout
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(Duration(TimeDelta::seconds(3)))),
+            max_bytes: binding
+                .max_bytes
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(1024)),
+            concurrency: binding
+                .concurrency
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(50)),
+        })
+    }
+}
@@ -0,0 +1,100 @@
+use anyhow::anyhow;
+use cel_interpreter::Duration;
+use chrono::TimeDelta;
+use go_parse_duration::parse_duration;
+
+use crate::{bindings, locate, Error, Result, UsageOutput};
+
+/// Limits on the resources each run and each of its steps may use. A run stops after the step
+/// that exceeds a limit, and connections aren't opened once a limit on bytes or connections is
+/// reached.
+#[derive(Debug, Clone, Default)]
+pub struct Budget {
+    pub run: BudgetLimits,
+    pub step: BudgetLimits,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BudgetLimits {
+    pub bytes_sent: Option<u64>,
+    pub bytes_received: Option<u64>,
+    pub connections: Option<u64>,
+    /// The most jobs to run.
+    pub attempts: Option<u64>,
+    pub duration: Option<Duration>,
+}
+
+impl BudgetLimits {
+    /// Describe the first limit that usage exceeds, if any.
+    pub fn exceeded(&self, usage: &UsageOutput) -> Option<String> {
+        let counts = [
+            ("bytes sent", usage.bytes_sent, self.bytes_sent),
+            ("bytes received", usage.bytes_received, self.bytes_received),
+            ("connections", usage.connections, self.connections),
+            ("attempts", usage.attempts, self.attempts),
+        ];
+        for (name, used, limit) in counts {
+            if let Some(limit) = limit.filter(|limit| used > *limit) {
+                return Some(format!("{used} {name}, over the limit of {limit}"));
+            }
+        }
+        self.duration
+            .as_ref()
+            .filter(|limit| usage.duration.0 > limit.0)
+            .map(|limit| format!("{}, over the limit of {}", usage.duration.0, limit.0))
+    }
+
+    /// Whether usage has reached a limit on bytes or connections, so no more connections should
+    /// be opened.
+    pub fn blocks_connections(&self, usage: &UsageOutput) -> bool {
+        [
+            (usage.bytes_sent, self.bytes_sent),
+            (usage.bytes_received, self.bytes_received),
+            (usage.connections, self.connections),
+        ]
+        .into_iter()
+        .any(|(used, limit)| limit.is_some_and(|limit| used >= limit))
+    }
+}
+
+impl TryFrom<bindings::Budget> for Budget {
+    type Error = Error;
+    fn try_from(binding: bindings::Budget) -> Result<Self> {
+        Ok(Self {
+            run: binding
+                .run
+                .map(BudgetLimits::try_from)
+                .transpose()
+                .map_err(|e| locate(e, "run"))?
+                .unwrap_or_default(),
+            step: binding
+                .step
+                .map(BudgetLimits::try_from)
+                .transpose()
+                .map_err(|e| locate(e, "step"))?
+                .unwrap_or_default(),
+        })
+    }
+}
+
+impl TryFrom<bindings::BudgetLimits> for BudgetLimits {
+    type Error = Error;
+    fn try_from(binding: bindings::BudgetLimits) -> Result<Self> {
+        let duration = binding
+            .duration
+            .map(|duration| {
+                parse_duration(&duration)
+                    .map(TimeDelta::nanoseconds)
+                    .map(Duration)
+                    .map_err(|e| locate(anyhow!("invalid duration string: {e:?}"), "duration"))
+            })
+            .transpose()?;
+        Ok(Self {
+            bytes_sent: binding.bytes_sent,
+            bytes_received: binding.bytes_received,
+            connections: binding.connections,
+            attempts: binding.attempts,
+            duration,
+        })
+    }
+}
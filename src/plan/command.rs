@@ -0,0 +1,86 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use cel_interpreter::Duration;
+use chrono::TimeDelta;
+use indexmap::IndexMap;
+
+use super::{Evaluate, IterablePlanValue, PlanValue, TryFromPlanData};
+use crate::{bindings, Error, MaybeUtf8, Result, State};
+
+/// Runs a local program with templated arguments and input.
+#[derive(Debug, Clone)]
+pub struct CommandRequest {
+    pub program: PlanValue<String>,
+    pub args: Option<IterablePlanValue>,
+    pub stdin: PlanValue<Option<MaybeUtf8>>,
+    pub env: IndexMap<String, PlanValue<String>>,
+    pub dir: PlanValue<Option<String>>,
+    pub timeout: PlanValue<Duration>,
+    pub max_output: PlanValue<u64>,
+}
+
+impl Evaluate<crate::CommandPlanOutput> for CommandRequest {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> Result<crate::CommandPlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        Ok(crate::CommandPlanOutput {
+            program: self.program.evaluate(state)?,
+            args: self
+                .args
+                .as_ref()
+                .map(|args| {
+                    args.evaluate(state)?
+                        .into_iter()
+                        .map(|(_, v)| {
+                            String::try_from_plan_data(v).map_err(|e| anyhow!("command.args: {e}"))
+                        })
+                        .collect::<Result<_>>()
+                })
+                .transpose()?
+                .unwrap_or_default(),
+            stdin: self.stdin.evaluate(state)?,
+            env: self
+                .env
+                .iter()
+                .map(|(k, v)| Ok((k.clone(), v.evaluate(state)?)))
+                .collect::<Result<_>>()?,
+            dir: self.dir.evaluate(state)?,
+            timeout: self.timeout.evaluate(state)?,
+            max_output: self.max_output.evaluate(state)?,
+        })
+    }
+}
+
+impl TryFrom<bindings::Command> for CommandRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::Command) -> Result<Self> {
+        Ok(Self {
+            program: binding
+                .program
+                .map(PlanValue::try_from)
+                .ok_or_else(|| anyhow!("command.program is required"))??,
+            args: binding.args.map(IterablePlanValue::try_from).transpose()?,
+            stdin: binding.stdin.try_into()?,
+            env: binding
+                .env
+                .into_iter()
+                .map(|(k, v)| Ok((k, PlanValue::try_from(v)?)))
+                .collect::<Result<_>>()?,
+            dir: binding.dir.try_into()?,
+            timeout: binding
+                .timeout
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(Duration(TimeDelta::seconds(30)))),
+            max_output: binding
+                .max_output
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(1 << 20)),
+        })
+    }
+}
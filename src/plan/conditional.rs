@@ -0,0 +1,38 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use url::Url;
+
+use super::{Evaluate, PlanValue};
+use crate::{bindings, Error, Result, State};
+
+/// Checks how a resource handles conditional request headers.
+#[derive(Debug, Clone)]
+pub struct ConditionalRequest {
+    pub url: PlanValue<Url>,
+}
+
+impl Evaluate<crate::ConditionalPlanOutput> for ConditionalRequest {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> Result<crate::ConditionalPlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        Ok(crate::ConditionalPlanOutput {
+            url: self.url.evaluate(state)?,
+        })
+    }
+}
+
+impl TryFrom<bindings::Conditional> for ConditionalRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::Conditional) -> Result<Self> {
+        Ok(Self {
+            url: binding
+                .url
+                .map(PlanValue::try_from)
+                .ok_or_else(|| anyhow!("conditional.url is required"))??,
+        })
+    }
+}
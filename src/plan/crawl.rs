@@ -0,0 +1,67 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use url::Url;
+
+use super::{Evaluate, PlanValue};
+use crate::{bindings, Error, Result, State};
+
+/// Fetches a seed URL and the pages it links to, recording the links and forms found.
+#[derive(Debug, Clone)]
+pub struct CrawlRequest {
+    pub url: PlanValue<Url>,
+    pub scope: PlanValue<Option<String>>,
+    pub max_depth: PlanValue<u64>,
+    pub max_pages: PlanValue<u64>,
+    pub form_steps: PlanValue<bool>,
+}
+
+impl Evaluate<crate::CrawlPlanOutput> for CrawlRequest {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> Result<crate::CrawlPlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        let url = self.url.evaluate(state)?;
+        Ok(crate::CrawlPlanOutput {
+            // Stay on the seed's origin unless told otherwise.
+            scope: self
+                .scope
+                .evaluate(state)?
+                .unwrap_or_else(|| url.origin().ascii_serialization() + "/"),
+            url,
+            max_depth: self.max_depth.evaluate(state)?,
+            max_pages: self.max_pages.evaluate(state)?,
+            form_steps: self.form_steps.evaluate(state)?,
+        })
+    }
+}
+
+impl TryFrom<bindings::Crawl> for CrawlRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::Crawl) -> Result<Self> {
+        Ok(Self {
+            url: binding
+                .url
+                .map(PlanValue::try_from)
+                .ok_or_else(|| anyhow!("crawl.url is required"))??,
+            scope: binding.scope.try_into()?,
+            max_depth: binding
+                .max_depth
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(2)),
+            max_pages: binding
+                .max_pages
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(100)),
+            form_steps: binding
+                .form_steps
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(false)),
+        })
+    }
+}
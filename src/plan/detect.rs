@@ -0,0 +1,76 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use cel_interpreter::Duration;
+use chrono::TimeDelta;
+use itertools::Itertools;
+
+use super::{Evaluate, PlanValue};
+use crate::{bindings, Error, MaybeUtf8, Result, State};
+
+/// Connects to a port and identifies the protocol it speaks from its banner, a TLS handshake, and
+/// a plaintext HTTP request, in that order.
+#[derive(Debug, Clone)]
+pub struct DetectRequest {
+    pub host: PlanValue<String>,
+    pub port: PlanValue<u16>,
+    /// The protocols offered in the TLS handshake.
+    pub alpn: Vec<PlanValue<MaybeUtf8>>,
+    /// How long to wait for the server to speak first.
+    pub wait: PlanValue<Duration>,
+    pub timeout: PlanValue<Duration>,
+    /// A local to set to the verdict for later steps.
+    pub local: PlanValue<Option<String>>,
+}
+
+impl Evaluate<crate::DetectPlanOutput> for DetectRequest {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> Result<crate::DetectPlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        Ok(crate::DetectPlanOutput {
+            host: self.host.evaluate(state)?,
+            port: self.port.evaluate(state)?,
+            alpn: self.alpn.evaluate(state)?,
+            wait: self.wait.evaluate(state)?,
+            timeout: self.timeout.evaluate(state)?,
+            local: self.local.evaluate(state)?,
+        })
+    }
+}
+
+impl TryFrom<bindings::Detect> for DetectRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::Detect) -> Result<Self> {
+        Ok(Self {
+            host: binding
+                .host
+                .map(PlanValue::try_from)
+                .ok_or_else(|| anyhow!("detect.host is required"))??,
+            port: binding
+                .port
+                .map(PlanValue::try_from)
+                .ok_or_else(|| anyhow!("detect.port is required"))??,
+            alpn: match binding.alpn {
+                Some(alpn) => alpn.into_iter().map(PlanValue::try_from).try_collect()?,
+                None => ["h2", "http/1.1"]
+                    .into_iter()
+                    .map(|p| PlanValue::Literal(MaybeUtf8(p.into())))
+                    .collect(),
+            },
+            wait: binding
+                .wait
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(Duration(TimeDelta::seconds(1)))),
+            timeout: binding
+                .timeout
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(Duration(TimeDelta::seconds(3)))),
+            local: binding.local.try_into()?,
+        })
+    }
+}
@@ -0,0 +1,175 @@
+use std::fmt::Display;
+use std::ops::Range;
+use std::path::PathBuf;
+
+use serde::Serialize;
+use toml_edit::{ImDocument, Item, TableLike};
+
+use super::Lint;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Error => f.write_str("error"),
+            Self::Warning => f.write_str("warning"),
+        }
+    }
+}
+
+/// A location in plan source. Lines and columns start at 1, and the range is in bytes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub range: Range<usize>,
+}
+
+impl Span {
+    fn new(source: &str, range: Range<usize>) -> Self {
+        let before = &source[..range.start.min(source.len())];
+        let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        Self {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            range,
+        }
+    }
+}
+
+/// A problem found while parsing or validating a plan.
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub file: Option<PathBuf>,
+    /// The keys leading to the problematic value, like `["my_step", "h1c"]`.
+    pub path: Vec<String>,
+    pub span: Option<Span>,
+    /// The kind of warning, for problems found by [`Plan::lint`](super::Plan::lint).
+    pub lint: Option<Lint>,
+}
+
+impl Diagnostic {
+    pub fn with_file(mut self, file: impl Into<PathBuf>) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+
+    pub(super) fn error(source: &str, err: crate::Error) -> Self {
+        // TOML syntax and type errors already know where they happened.
+        if let Some(e) = err.downcast_ref::<toml::de::Error>() {
+            return Self {
+                severity: Severity::Error,
+                message: e.message().to_owned(),
+                file: None,
+                path: Vec::new(),
+                span: e.span().map(|range| Span::new(source, range)),
+                lint: None,
+            };
+        }
+        let path = err
+            .chain()
+            .find_map(|e| e.downcast_ref::<Located>())
+            .map(|l| l.path.clone())
+            .unwrap_or_default();
+        Self {
+            severity: Severity::Error,
+            message: format!("{err:#}"),
+            file: None,
+            span: find_span(source, &path),
+            path,
+            lint: None,
+        }
+    }
+
+    pub(super) fn warning(source: &str, lint: Lint, path: Vec<String>, message: String) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message,
+            file: None,
+            span: find_span(source, &path),
+            path,
+            lint: Some(lint),
+        }
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file.display())?;
+        }
+        if let Some(span) = &self.span {
+            write!(f, "{}:{}:", span.line, span.column)?;
+        }
+        if self.file.is_some() || self.span.is_some() {
+            f.write_str(" ")?;
+        }
+        write!(f, "{}", self.severity)?;
+        if let Some(lint) = self.lint {
+            write!(f, "[{lint}]")?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+/// Attaches the key path of a plan value to an error so it can be reported with a span.
+#[derive(Debug)]
+pub(crate) struct Located {
+    path: Vec<String>,
+    error: crate::Error,
+}
+
+impl Display for Located {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:#}", self.error)
+    }
+}
+
+impl std::error::Error for Located {}
+
+/// Marks `err` as having happened under `key`. Errors which were already located under a nested
+/// key keep their path with `key` prepended.
+pub(crate) fn locate(err: crate::Error, key: impl Into<String>) -> crate::Error {
+    match err.downcast::<Located>() {
+        Ok(mut located) => {
+            located.path.insert(0, key.into());
+            located.into()
+        }
+        Err(error) => Located {
+            path: vec![key.into()],
+            error,
+        }
+        .into(),
+    }
+}
+
+/// Find the span of the deepest key along path which exists in source.
+fn find_span(source: &str, path: &[String]) -> Option<Span> {
+    let doc = ImDocument::parse(source).ok()?;
+    let mut table: &dyn TableLike = doc.as_table();
+    let mut found = None;
+    for key in path {
+        let Some((k, item)) = table.get_key_value(key) else {
+            break;
+        };
+        if let Some(range) = k.span().or_else(|| item.span()) {
+            found = Some(range);
+        }
+        match item {
+            Item::Table(t) => table = t,
+            Item::Value(toml_edit::Value::InlineTable(t)) => table = t,
+            _ => break,
+        }
+    }
+    found.map(|range| Span::new(source, range))
+}
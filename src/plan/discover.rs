@@ -0,0 +1,59 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use url::Url;
+
+use super::{Evaluate, PlanValue};
+use crate::{bindings, Error, Result, State};
+
+/// Fetches robots.txt and sitemaps from a site, collecting the paths they mention.
+#[derive(Debug, Clone)]
+pub struct DiscoverRequest {
+    pub url: PlanValue<Url>,
+    pub robots: PlanValue<bool>,
+    pub sitemaps: PlanValue<bool>,
+    pub max_sitemaps: PlanValue<u64>,
+}
+
+impl Evaluate<crate::DiscoverPlanOutput> for DiscoverRequest {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> Result<crate::DiscoverPlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        Ok(crate::DiscoverPlanOutput {
+            url: self.url.evaluate(state)?,
+            robots: self.robots.evaluate(state)?,
+            sitemaps: self.sitemaps.evaluate(state)?,
+            max_sitemaps: self.max_sitemaps.evaluate(state)?,
+        })
+    }
+}
+
+impl TryFrom<bindings::Discover> for DiscoverRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::Discover) -> Result<Self> {
+        Ok(Self {
+            url: binding
+                .url
+                .map(PlanValue::try_from)
+                .ok_or_else(|| anyhow!("discover.url is required"))??,
+            robots: binding
+                .robots
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(true)),
+            sitemaps: binding
+                .sitemaps
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(true)),
+            max_sitemaps: binding
+                .max_sitemaps
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(20)),
+        })
+    }
+}
@@ -0,0 +1,114 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail};
+use cel_interpreter::Duration;
+use chrono::TimeDelta;
+
+use super::{Evaluate, PlanData, PlanValue, TryFromPlanData};
+use crate::bindings::Literal;
+use crate::{bindings, DnsRecordType, DnsTransport, Error, ParsedDnsRecordType, Result, State};
+
+impl TryFromPlanData for DnsRecordType {
+    type Error = Error;
+    fn try_from_plan_data(value: PlanData) -> Result<Self> {
+        Ok(match value.0 {
+            cel_interpreter::Value::String(x) => x.parse::<ParsedDnsRecordType>()?.into(),
+            cel_interpreter::Value::Int(raw) => u16::try_from(raw)?.into(),
+            cel_interpreter::Value::UInt(raw) => u16::try_from(raw)?.into(),
+            _ => bail!("dns record type must be a string or 16 bit unsigned integer"),
+        })
+    }
+}
+
+impl TryFrom<Literal> for DnsRecordType {
+    type Error = Error;
+    fn try_from(binding: Literal) -> Result<Self> {
+        Ok(match binding {
+            Literal::String(x) => x.parse::<ParsedDnsRecordType>()?.into(),
+            Literal::Int(raw) => u16::try_from(raw)?.into(),
+            _ => bail!("invalid value {binding:?} for dns record type field"),
+        })
+    }
+}
+
+impl TryFromPlanData for DnsTransport {
+    type Error = Error;
+    fn try_from_plan_data(value: PlanData) -> Result<Self> {
+        match value.0 {
+            cel_interpreter::Value::String(x) => x.parse(),
+            val => bail!("unsupported value {val:?} for field dns.transport"),
+        }
+    }
+}
+
+impl TryFrom<Literal> for DnsTransport {
+    type Error = Error;
+    fn try_from(binding: Literal) -> Result<Self> {
+        match binding {
+            Literal::String(x) => x.parse(),
+            _ => bail!("invalid value {binding:?} for dns.transport field"),
+        }
+    }
+}
+
+/// Sends one DNS query and records the raw reply and its parsed records.
+#[derive(Debug, Clone)]
+pub struct DnsRequest {
+    pub name: PlanValue<String>,
+    pub record_type: PlanValue<DnsRecordType>,
+    pub server: PlanValue<Option<String>>,
+    pub transport: PlanValue<DnsTransport>,
+    pub recursion_desired: PlanValue<bool>,
+    pub timeout: PlanValue<Duration>,
+}
+
+impl Evaluate<crate::DnsPlanOutput> for DnsRequest {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> Result<crate::DnsPlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        Ok(crate::DnsPlanOutput {
+            name: self.name.evaluate(state)?,
+            record_type: self.record_type.evaluate(state)?,
+            server: self.server.evaluate(state)?,
+            transport: self.transport.evaluate(state)?,
+            recursion_desired: self.recursion_desired.evaluate(state)?,
+            timeout: self.timeout.evaluate(state)?,
+        })
+    }
+}
+
+impl TryFrom<bindings::Dns> for DnsRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::Dns) -> Result<Self> {
+        Ok(Self {
+            name: binding
+                .name
+                .map(PlanValue::try_from)
+                .ok_or_else(|| anyhow!("dns.name is required"))??,
+            record_type: binding
+                .record_type
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(ParsedDnsRecordType::A.into())),
+            server: binding.server.try_into()?,
+            transport: binding
+                .transport
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(DnsTransport::Udp)),
+            recursion_desired: binding
+                .recursion_desired
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(true)),
+            timeout: binding
+                .timeout
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(Duration(TimeDelta::seconds(5)))),
+        })
+    }
+}
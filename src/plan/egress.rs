@@ -0,0 +1,43 @@
+use std::sync::Arc;
+
+use super::{Evaluate, PlanValue, TcpProxyRequest};
+use crate::{bindings, Error, Result, State};
+
+/// A named vantage point bundling how connections are made, so the same steps can be run from
+/// different networks.
+#[derive(Debug, Clone)]
+pub struct EgressRequest {
+    pub bind: PlanValue<Option<String>>,
+    pub proxy: Option<TcpProxyRequest>,
+    pub resolver: PlanValue<Option<String>>,
+    pub rate: PlanValue<Option<u64>>,
+}
+
+impl EgressRequest {
+    pub fn evaluate<'a, S, O, I>(&self, name: &str, state: &S) -> Result<crate::EgressPlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        Ok(crate::EgressPlanOutput {
+            name: name.to_owned(),
+            bind: self.bind.evaluate(state)?,
+            proxy: self.proxy.as_ref().map(|p| p.evaluate(state)).transpose()?,
+            resolver: self.resolver.evaluate(state)?,
+            rate: self.rate.evaluate(state)?,
+        })
+    }
+}
+
+impl TryFrom<bindings::Egress> for EgressRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::Egress) -> Result<Self> {
+        Ok(Self {
+            bind: binding.bind.try_into()?,
+            proxy: binding.proxy.map(TcpProxyRequest::try_from).transpose()?,
+            resolver: binding.resolver.try_into()?,
+            rate: binding.rate.try_into()?,
+        })
+    }
+}
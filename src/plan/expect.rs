@@ -0,0 +1,81 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail};
+use cel_interpreter::Duration;
+
+use super::{Evaluate, PlanData, PlanValue, TryFromPlanData};
+use crate::bindings::Literal;
+use crate::{bindings, Error, ExpectTeardown, Result, State};
+
+impl TryFromPlanData for ExpectTeardown {
+    type Error = Error;
+    fn try_from_plan_data(value: PlanData) -> Result<Self> {
+        match value.0 {
+            cel_interpreter::Value::String(x) => x.parse(),
+            val => bail!("unsupported value {val:?} for field expect.on_fail"),
+        }
+    }
+}
+
+impl TryFrom<Literal> for ExpectTeardown {
+    type Error = Error;
+    fn try_from(binding: Literal) -> Result<Self> {
+        match binding {
+            Literal::String(x) => x.parse(),
+            _ => bail!("invalid value {binding:?} for expect.on_fail field"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ExpectRequest {
+    pub pattern: PlanValue<String>,
+    pub within: PlanValue<Option<Duration>>,
+    pub abort: PlanValue<bool>,
+    pub on_fail: PlanValue<Option<ExpectTeardown>>,
+}
+
+impl Evaluate<crate::ExpectPlanOutput> for ExpectRequest {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> Result<crate::ExpectPlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        let pattern = self.pattern.evaluate(state)?;
+        // Compile the pattern now so a typo fails the step before it connects.
+        regex::bytes::Regex::new(&pattern)
+            .map_err(|e| anyhow!("invalid expect.pattern {pattern:?}: {e}"))?;
+        let abort = self.abort.evaluate(state)?;
+        Ok(crate::ExpectPlanOutput {
+            pattern,
+            within: self.within.evaluate(state)?,
+            abort,
+            // Failing stops reading the same way passing does unless on_fail says otherwise.
+            on_fail: self.on_fail.evaluate(state)?.unwrap_or(if abort {
+                ExpectTeardown::HalfClose
+            } else {
+                ExpectTeardown::Finish
+            }),
+        })
+    }
+}
+
+impl TryFrom<bindings::Expect> for ExpectRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::Expect) -> Result<Self> {
+        Ok(Self {
+            pattern: binding
+                .pattern
+                .map(PlanValue::try_from)
+                .ok_or_else(|| anyhow!("expect.pattern is required"))??,
+            within: binding.within.try_into()?,
+            abort: binding
+                .abort
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(false)),
+            on_fail: binding.on_fail.try_into()?,
+        })
+    }
+}
@@ -0,0 +1,98 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use url::Url;
+
+use super::{Evaluate, IterablePlanValue, PlanValue, TryFromPlanData};
+use crate::{bindings, Error, Result, State};
+
+/// Requests wordlist entries under a URL, keeping those which don't look like the server's
+/// response for missing resources.
+#[derive(Debug, Clone)]
+pub struct ForcedBrowseRequest {
+    pub url: PlanValue<Url>,
+    pub words: IterablePlanValue,
+    pub extensions: IterablePlanValue,
+    pub max_depth: PlanValue<u64>,
+    pub max_requests: PlanValue<u64>,
+    pub concurrency: PlanValue<u64>,
+    pub length_tolerance: PlanValue<u64>,
+}
+
+impl Evaluate<crate::ForcedBrowsePlanOutput> for ForcedBrowseRequest {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> Result<crate::ForcedBrowsePlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        let strings = |values: &IterablePlanValue, field: &str| -> Result<Vec<String>> {
+            values
+                .evaluate(state)?
+                .into_iter()
+                .map(|(_, v)| {
+                    String::try_from_plan_data(v).map_err(|e| anyhow!("forced_browse.{field}: {e}"))
+                })
+                .collect()
+        };
+        let mut url = self.url.evaluate(state)?;
+        // Words are always appended as children of the given path.
+        if !url.path().ends_with('/') {
+            url.set_path(&(url.path().to_owned() + "/"));
+        }
+        Ok(crate::ForcedBrowsePlanOutput {
+            url,
+            words: strings(&self.words, "words")?,
+            extensions: strings(&self.extensions, "extensions")?
+                .into_iter()
+                .map(|ext| ext.trim_start_matches('.').to_owned())
+                .filter(|ext| !ext.is_empty())
+                .collect(),
+            max_depth: self.max_depth.evaluate(state)?,
+            max_requests: self.max_requests.evaluate(state)?,
+            concurrency: self.concurrency.evaluate(state)?.max(1),
+            length_tolerance: self.length_tolerance.evaluate(state)?,
+        })
+    }
+}
+
+impl TryFrom<bindings::ForcedBrowse> for ForcedBrowseRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::ForcedBrowse) -> Result<Self> {
+        Ok(Self {
+            url: binding
+                .url
+                .map(PlanValue::try_from)
+                .ok_or_else(|| anyhow!("forced_browse.url is required"))??,
+            words: binding
+                .words
+                .map(IterablePlanValue::try_from)
+                .ok_or_else(|| anyhow!("forced_browse.words is required"))??,
+            extensions: binding
+                .extensions
+                .map(IterablePlanValue::try_from)
+                .transpose()?
+                .unwrap_or_default(),
+            max_depth: binding
+                .max_depth
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(1)),
+            max_requests: binding
+                .max_requests
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(10_000)),
+            concurrency: binding
+                .concurrency
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(10)),
+            length_tolerance: binding
+                .length_tolerance
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(0)),
+        })
+    }
+}
@@ -0,0 +1,68 @@
+use std::sync::Arc;
+
+use super::{Evaluate, PlanValue};
+use crate::{bindings, Error, MaybeUtf8, Result, State};
+use anyhow::{anyhow, bail};
+use url::Url;
+
+#[derive(Debug, Clone)]
+pub struct GrpcRequest {
+    pub url: PlanValue<Url>,
+    pub service: PlanValue<String>,
+    pub method: PlanValue<String>,
+    pub message: PlanValue<Option<MaybeUtf8>>,
+    pub json: PlanValue<Option<serde_json::Value>>,
+    pub descriptor_set: PlanValue<Option<String>>,
+}
+
+impl TryFrom<bindings::Grpc> for GrpcRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::Grpc) -> Result<Self> {
+        if binding.message.is_some() && binding.json.is_some() {
+            bail!("grpc.message and grpc.json are mutually exclusive");
+        }
+        if binding.json.is_some() && binding.descriptor_set.is_none() {
+            bail!("grpc.json requires grpc.descriptor_set");
+        }
+        Ok(Self {
+            url: binding
+                .url
+                .map(PlanValue::try_from)
+                .ok_or_else(|| anyhow!("grpc.url is required"))??,
+            service: binding
+                .service
+                .map(PlanValue::try_from)
+                .ok_or_else(|| anyhow!("grpc.service is required"))??,
+            method: binding
+                .method
+                .map(PlanValue::try_from)
+                .ok_or_else(|| anyhow!("grpc.method is required"))??,
+            message: binding.message.try_into()?,
+            json: binding.json.try_into()?,
+            descriptor_set: binding.descriptor_set.try_into()?,
+        })
+    }
+}
+
+impl Evaluate<crate::GrpcPlanOutput> for GrpcRequest {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> crate::Result<crate::GrpcPlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        let service = self.service.evaluate(state)?;
+        let method = self.method.evaluate(state)?;
+        let mut url = self.url.evaluate(state)?;
+        let path = format!("{}/{service}/{method}", url.path().trim_end_matches('/'));
+        url.set_path(&path);
+        Ok(crate::GrpcPlanOutput {
+            url,
+            service,
+            method,
+            message: self.message.evaluate(state)?.unwrap_or_default(),
+            json: self.json.evaluate(state)?,
+            descriptor_set: self.descriptor_set.evaluate(state)?,
+        })
+    }
+}
@@ -0,0 +1,50 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use cel_interpreter::Duration;
+use chrono::TimeDelta;
+use url::Url;
+
+use super::{Evaluate, PlanValue};
+use crate::{bindings, Error, Result, State};
+
+/// Lists a gRPC server's services and methods using server reflection.
+#[derive(Debug, Clone)]
+pub struct GrpcReflectRequest {
+    pub url: PlanValue<Url>,
+    pub timeout: PlanValue<Duration>,
+    pub save_descriptors: PlanValue<Option<String>>,
+}
+
+impl Evaluate<crate::GrpcReflectPlanOutput> for GrpcReflectRequest {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> Result<crate::GrpcReflectPlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        Ok(crate::GrpcReflectPlanOutput {
+            url: self.url.evaluate(state)?,
+            timeout: self.timeout.evaluate(state)?,
+            save_descriptors: self.save_descriptors.evaluate(state)?,
+        })
+    }
+}
+
+impl TryFrom<bindings::GrpcReflect> for GrpcReflectRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::GrpcReflect) -> Result<Self> {
+        Ok(Self {
+            url: binding
+                .url
+                .map(PlanValue::try_from)
+                .ok_or_else(|| anyhow!("grpc_reflect.url is required"))??,
+            timeout: binding
+                .timeout
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(Duration(TimeDelta::seconds(10)))),
+            save_descriptors: binding.save_descriptors.try_into()?,
+        })
+    }
+}
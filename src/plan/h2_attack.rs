@@ -0,0 +1,60 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use cel_interpreter::Duration;
+use chrono::TimeDelta;
+use url::Url;
+
+use super::{Evaluate, PlanValue};
+use crate::{bindings, Error, Result, State};
+
+/// Sends HTTP/2 frame sequences known to exhaust or confuse servers and records the replies.
+#[derive(Debug, Clone)]
+pub struct H2AttackRequest {
+    pub url: PlanValue<Url>,
+    pub attack: PlanValue<String>,
+    pub count: PlanValue<u64>,
+    pub timeout: PlanValue<Duration>,
+}
+
+impl Evaluate<crate::H2AttackPlanOutput> for H2AttackRequest {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> Result<crate::H2AttackPlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        Ok(crate::H2AttackPlanOutput {
+            url: self.url.evaluate(state)?,
+            attack: self.attack.evaluate(state)?,
+            count: self.count.evaluate(state)?,
+            timeout: self.timeout.evaluate(state)?,
+        })
+    }
+}
+
+impl TryFrom<bindings::H2Attack> for H2AttackRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::H2Attack) -> Result<Self> {
+        Ok(Self {
+            url: binding
+                .url
+                .map(PlanValue::try_from)
+                .ok_or_else(|| anyhow!("h2_attack.url is required"))??,
+            attack: binding
+                .attack
+                .map(PlanValue::try_from)
+                .ok_or_else(|| anyhow!("h2_attack.attack is required"))??,
+            count: binding
+                .count
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(100)),
+            timeout: binding
+                .timeout
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(Duration(TimeDelta::seconds(2)))),
+        })
+    }
+}
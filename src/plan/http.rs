@@ -3,6 +3,7 @@ use crate::bindings::Literal;
 use crate::{bindings, Error, HttpHeader, MaybeUtf8, Result, State};
 use anyhow::{anyhow, bail};
 use devil_derive::BigQuerySchema;
+use itertools::Itertools;
 use serde::Serialize;
 use std::str::FromStr;
 use std::sync::Arc;
@@ -62,6 +63,101 @@ impl TryFrom<bindings::Value> for PlanValue<AddContentLength> {
     }
 }
 
+/// How a request interacts with the executor's client-side HTTP cache.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, BigQuerySchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheMode {
+    /// Neither read nor write the cache.
+    #[default]
+    Off,
+    /// Store cacheable responses for later steps.
+    Store,
+    /// Store cacheable responses and send validators from previously stored responses with
+    /// If-None-Match and If-Modified-Since.
+    Conditional,
+}
+
+impl FromStr for CacheMode {
+    type Err = Error;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "store" => Ok(Self::Store),
+            "conditional" => Ok(Self::Conditional),
+            val => bail!("unrecognized cache string {val}"),
+        }
+    }
+}
+
+impl TryFromPlanData for CacheMode {
+    type Error = Error;
+    fn try_from_plan_data(value: PlanData) -> std::result::Result<Self, Self::Error> {
+        match value.0 {
+            cel_interpreter::Value::String(s) => s.parse(),
+            val => bail!("unsupported value {val:?} for field cache"),
+        }
+    }
+}
+
+impl TryFrom<bindings::Value> for PlanValue<CacheMode> {
+    type Error = Error;
+    fn try_from(binding: bindings::Value) -> Result<Self> {
+        match binding {
+            bindings::Value::ExpressionCel { cel, vars } => Ok(Self::Dynamic {
+                cel,
+                vars: vars.unwrap_or_default().into_iter().collect(),
+            }),
+            bindings::Value::Literal(Literal::String(x)) => Ok(Self::Literal(x.parse()?)),
+            val => bail!("invalid value {val:?} for field cache"),
+        }
+    }
+}
+
+/// A similarity hash to compute over each response body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, BigQuerySchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SimilarityHash {
+    /// A 64 bit simhash of the body's words.
+    Simhash,
+    /// A context triggered piecewise hash in ssdeep's format.
+    Ssdeep,
+}
+
+impl FromStr for SimilarityHash {
+    type Err = Error;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "simhash" => Ok(Self::Simhash),
+            "ssdeep" => Ok(Self::Ssdeep),
+            val => bail!("unrecognized similarity string {val}"),
+        }
+    }
+}
+
+impl TryFromPlanData for SimilarityHash {
+    type Error = Error;
+    fn try_from_plan_data(value: PlanData) -> std::result::Result<Self, Self::Error> {
+        match value.0 {
+            cel_interpreter::Value::String(s) => s.parse(),
+            val => bail!("unsupported value {val:?} for field similarity"),
+        }
+    }
+}
+
+impl TryFrom<bindings::Value> for PlanValue<SimilarityHash> {
+    type Error = Error;
+    fn try_from(binding: bindings::Value) -> Result<Self> {
+        match binding {
+            bindings::Value::ExpressionCel { cel, vars } => Ok(Self::Dynamic {
+                cel,
+                vars: vars.unwrap_or_default().into_iter().collect(),
+            }),
+            bindings::Value::Literal(Literal::String(x)) => Ok(Self::Literal(x.parse()?)),
+            val => bail!("invalid value {val:?} for field similarity"),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct HttpRequest {
     pub url: PlanValue<Url>,
@@ -69,6 +165,8 @@ pub struct HttpRequest {
     pub headers: PlanValueTable<MaybeUtf8, MaybeUtf8>,
     pub add_content_length: PlanValue<AddContentLength>,
     pub body: PlanValue<Option<MaybeUtf8>>,
+    pub http3: PlanValue<bool>,
+    pub similarity: Vec<PlanValue<SimilarityHash>>,
 }
 
 impl TryFrom<bindings::Http> for HttpRequest {
@@ -86,6 +184,17 @@ impl TryFrom<bindings::Http> for HttpRequest {
                 .ok_or_else(|| anyhow!("http.add_content_length is required"))??,
             body: binding.body.try_into()?,
             headers: PlanValueTable::try_from(binding.headers.unwrap_or_default())?,
+            http3: binding
+                .http3
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(false)),
+            similarity: binding
+                .similarity
+                .into_iter()
+                .flatten()
+                .map(PlanValue::try_from)
+                .try_collect()?,
         })
     }
 }
@@ -108,6 +217,8 @@ impl Evaluate<crate::HttpPlanOutput> for HttpRequest {
                 .map(HttpHeader::from)
                 .collect(),
             body: self.body.evaluate(state)?.unwrap_or_default(),
+            http3: self.http3.evaluate(state)?,
+            similarity: self.similarity.evaluate(state)?,
         })
     }
 }
@@ -1,8 +1,11 @@
 use std::sync::Arc;
 
-use super::{AddContentLength, Evaluate, PlanValue, PlanValueTable};
+use super::{AddContentLength, CacheMode, Evaluate, PlanValue, PlanValueTable, SignRequest};
 use crate::{bindings, Error, HttpHeader, MaybeUtf8, Result, State};
 use anyhow::anyhow;
+use cel_interpreter::Duration;
+use chrono::TimeDelta;
+use itertools::Itertools;
 use url::Url;
 
 #[derive(Debug, Clone)]
@@ -13,6 +16,14 @@ pub struct Http1Request {
     pub add_content_length: PlanValue<AddContentLength>,
     pub headers: PlanValueTable<MaybeUtf8, MaybeUtf8>,
     pub body: PlanValue<Option<MaybeUtf8>>,
+    pub sign: Option<SignRequest>,
+    pub cache: PlanValue<CacheMode>,
+    pub chunked: Option<Http1ChunkedRequest>,
+    pub expect_continue: Option<Http1ExpectContinueRequest>,
+    pub head: PlanValue<Option<MaybeUtf8>>,
+    pub read: Option<Http1ReadRequest>,
+    /// Whether to accept malformed response heads, recording how they deviate from the spec.
+    pub permissive: PlanValue<bool>,
 }
 
 impl Evaluate<crate::Http1PlanOutput> for Http1Request {
@@ -34,6 +45,21 @@ impl Evaluate<crate::Http1PlanOutput> for Http1Request {
                 .map(HttpHeader::from)
                 .collect(),
             body: self.body.evaluate(state)?.unwrap_or_default(),
+            sign: self.sign.as_ref().map(|s| s.evaluate(state)).transpose()?,
+            cache: self.cache.evaluate(state)?,
+            chunked: self
+                .chunked
+                .as_ref()
+                .map(|c| c.evaluate(state))
+                .transpose()?,
+            expect_continue: self
+                .expect_continue
+                .as_ref()
+                .map(|e| e.evaluate(state))
+                .transpose()?,
+            head: self.head.evaluate(state)?,
+            read: self.read.as_ref().map(|r| r.evaluate(state)).transpose()?,
+            permissive: self.permissive.evaluate(state)?,
         })
     }
 }
@@ -56,6 +82,167 @@ impl TryFrom<bindings::Http1> for Http1Request {
                 .ok_or_else(|| anyhow!("http.add_content_length is required"))??,
             headers: PlanValueTable::try_from(binding.common.headers.unwrap_or_default())?,
             body: binding.common.body.try_into()?,
+            sign: binding.sign.map(SignRequest::try_from).transpose()?,
+            cache: binding
+                .cache
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or_default(),
+            chunked: binding
+                .chunked
+                .map(Http1ChunkedRequest::try_from)
+                .transpose()?,
+            expect_continue: binding
+                .expect_continue
+                .map(Http1ExpectContinueRequest::try_from)
+                .transpose()?,
+            head: binding.head.try_into()?,
+            read: binding.read.map(Http1ReadRequest::try_from).transpose()?,
+            permissive: binding
+                .permissive
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(false)),
+        })
+    }
+}
+
+/// Sends the body in chunks of the given sizes, the last of which repeats until the body is
+/// sent. A size of 0 ends the chunked body early, and the rest of the body follows the trailers
+/// without any chunk framing.
+#[derive(Debug, Clone)]
+pub struct Http1ChunkedRequest {
+    pub sizes: Vec<PlanValue<u64>>,
+    /// Appended to each chunk's size line in order, counting the final empty chunk.
+    pub extensions: Vec<PlanValue<MaybeUtf8>>,
+    pub trailers: PlanValueTable<MaybeUtf8, MaybeUtf8>,
+}
+
+impl Evaluate<crate::Http1ChunkedPlanOutput> for Http1ChunkedRequest {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> Result<crate::Http1ChunkedPlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        Ok(crate::Http1ChunkedPlanOutput {
+            sizes: self.sizes.evaluate(state)?,
+            extensions: self.extensions.evaluate(state)?,
+            trailers: self
+                .trailers
+                .evaluate(state)?
+                .into_iter()
+                .map(HttpHeader::from)
+                .collect(),
+        })
+    }
+}
+
+impl TryFrom<bindings::Http1Chunked> for Http1ChunkedRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::Http1Chunked) -> Result<Self> {
+        Ok(Self {
+            sizes: binding
+                .sizes
+                .into_iter()
+                .flatten()
+                .map(PlanValue::try_from)
+                .try_collect()?,
+            extensions: binding
+                .extensions
+                .into_iter()
+                .flatten()
+                .map(PlanValue::try_from)
+                .try_collect()?,
+            trailers: PlanValueTable::try_from(binding.trailers.unwrap_or_default())?,
+        })
+    }
+}
+
+/// Holds back the body until the server sends 100 Continue, a final response, or the timeout
+/// passes.
+#[derive(Debug, Clone)]
+pub struct Http1ExpectContinueRequest {
+    /// Whether to add `Expect: 100-continue` to the request, unless it already has an Expect
+    /// header.
+    pub header: PlanValue<bool>,
+    pub timeout: PlanValue<Duration>,
+}
+
+impl Evaluate<crate::Http1ExpectContinuePlanOutput> for Http1ExpectContinueRequest {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> Result<crate::Http1ExpectContinuePlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        Ok(crate::Http1ExpectContinuePlanOutput {
+            header: self.header.evaluate(state)?,
+            timeout: self.timeout.evaluate(state)?,
+        })
+    }
+}
+
+impl TryFrom<bindings::Http1ExpectContinue> for Http1ExpectContinueRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::Http1ExpectContinue) -> Result<Self> {
+        Ok(Self {
+            header: binding
+                .header
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(true)),
+            timeout: binding
+                .timeout
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(Duration(TimeDelta::seconds(1)))),
+        })
+    }
+}
+
+/// Ends the response early, for servers that keep the connection open without framing the body.
+/// Reading stops at whichever condition is met first.
+#[derive(Debug, Clone)]
+pub struct Http1ReadRequest {
+    /// Whether to stop at Content-Length even if the response is also chunked.
+    pub content_length: PlanValue<bool>,
+    /// Stop once nothing has been received for this long.
+    pub idle: PlanValue<Option<Duration>>,
+    /// Stop once the body contains these bytes.
+    pub pattern: PlanValue<Option<MaybeUtf8>>,
+    /// Stop once this many bytes of the body have been read.
+    pub bytes: PlanValue<Option<u64>>,
+}
+
+impl Evaluate<crate::Http1ReadPlanOutput> for Http1ReadRequest {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> Result<crate::Http1ReadPlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        Ok(crate::Http1ReadPlanOutput {
+            content_length: self.content_length.evaluate(state)?,
+            idle: self.idle.evaluate(state)?,
+            pattern: self.pattern.evaluate(state)?,
+            bytes: self.bytes.evaluate(state)?,
+        })
+    }
+}
+
+impl TryFrom<bindings::Http1Read> for Http1ReadRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::Http1Read) -> Result<Self> {
+        Ok(Self {
+            content_length: binding
+                .content_length
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(false)),
+            idle: binding.idle.try_into()?,
+            pattern: binding.pattern.try_into()?,
+            bytes: binding.bytes.try_into()?,
         })
     }
 }
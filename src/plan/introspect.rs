@@ -0,0 +1,107 @@
+use std::sync::Arc;
+
+use indexmap::IndexMap;
+use serde::Serialize;
+
+use super::{Plan, Protocol, ProtocolDiscriminants, Step, StepProtocols, CEL_FUNCTIONS};
+
+/// The CEL variables which can be referenced while evaluating a step.
+#[derive(Debug, Clone, Serialize)]
+pub struct StepScope {
+    /// Names available under `locals`.
+    pub locals: Vec<String>,
+    /// Steps which run earlier, and therefore are available under `steps`, with the fields each
+    /// exposes.
+    pub steps: IndexMap<Arc<String>, Vec<String>>,
+    /// Protocol fields available under `current` as the step's own stack is evaluated.
+    pub current: Vec<String>,
+    /// Whether `for` is set, which happens when the step uses run.for.
+    pub run_for: bool,
+    /// Whether `while` is set, which happens when the step uses run.while.
+    pub run_while: bool,
+    pub functions: &'static [&'static str],
+}
+
+impl Plan {
+    /// Resolve the variables available to expressions in each step, for completion in editors
+    /// and other tooling.
+    pub fn scopes(&self) -> IndexMap<Arc<String>, StepScope> {
+        let locals: Vec<_> = self.locals.keys().cloned().collect();
+        let mut previous = IndexMap::new();
+        let mut scopes = IndexMap::with_capacity(self.steps.len());
+        for (name, step) in &self.steps {
+            let fields = step_fields(step);
+            scopes.insert(
+                name.clone(),
+                StepScope {
+                    locals: locals.clone(),
+                    steps: previous.clone(),
+                    current: protocol_names(step),
+                    run_for: step.run.run_for.is_some(),
+                    run_while: step.run.run_while.is_some(),
+                    functions: CEL_FUNCTIONS,
+                },
+            );
+            previous.insert(name.clone(), fields);
+        }
+        scopes
+    }
+}
+
+fn protocol_names(step: &Step) -> Vec<String> {
+    step.protocols
+        .clone()
+        .into_stack()
+        .iter()
+        .map(|p| ProtocolDiscriminants::from(p).to_string())
+        .collect()
+}
+
+/// The fields of a step's output as exposed in CEL.
+fn step_fields(step: &Step) -> Vec<String> {
+    let stack = step.protocols.clone().into_stack();
+    let mut fields = vec!["errors".to_owned()];
+    fields.extend(
+        stack
+            .iter()
+            .map(|p| ProtocolDiscriminants::from(p).to_string()),
+    );
+    if stack.iter().any(|p| {
+        matches!(
+            p,
+            Protocol::Graphql(_)
+                | Protocol::Http(_)
+                | Protocol::H1c(_)
+                | Protocol::H1(_)
+                | Protocol::H2c(_)
+                | Protocol::H2(_)
+                | Protocol::H3(_)
+        )
+    }) {
+        fields.push("request".to_owned());
+        fields.push("response".to_owned());
+    }
+    match step.protocols {
+        StepProtocols::Module { .. } => fields.push("steps".to_owned()),
+        StepProtocols::Crawl { .. } => fields.push("crawl".to_owned()),
+        StepProtocols::Discover { .. } => fields.push("discover".to_owned()),
+        StepProtocols::ForcedBrowse { .. } => fields.push("forced_browse".to_owned()),
+        StepProtocols::Vhost { .. } => fields.push("vhost".to_owned()),
+        StepProtocols::Takeover { .. } => fields.push("takeover".to_owned()),
+        StepProtocols::Banner { .. } => fields.push("banner".to_owned()),
+        StepProtocols::Session { .. } => fields.push("session".to_owned()),
+        StepProtocols::Command { .. } => fields.push("command".to_owned()),
+        StepProtocols::Script { .. } => fields.push("script".to_owned()),
+        StepProtocols::GrpcReflect { .. } => fields.push("grpc_reflect".to_owned()),
+        StepProtocols::Range { .. } => fields.push("range".to_owned()),
+        StepProtocols::Conditional { .. } => fields.push("conditional".to_owned()),
+        StepProtocols::H2Attack { .. } => fields.push("h2_attack".to_owned()),
+        StepProtocols::AlpnMatrix { .. } => fields.push("alpn_matrix".to_owned()),
+        StepProtocols::KeepAlive { .. } => fields.push("keep_alive".to_owned()),
+        StepProtocols::TcpBurst { .. } => fields.push("tcp_burst".to_owned()),
+        StepProtocols::Dns { .. } => fields.push("dns".to_owned()),
+        StepProtocols::Detect { .. } => fields.push("detect".to_owned()),
+        _ => {}
+    }
+    fields
+}
@@ -0,0 +1,68 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use cel_interpreter::Duration;
+use chrono::TimeDelta;
+use url::Url;
+
+use super::{Evaluate, PlanValue};
+use crate::{bindings, Error, Result, State};
+
+/// Holds many idle keep-alive connections open to find how many a server will keep.
+#[derive(Debug, Clone)]
+pub struct KeepAliveRequest {
+    pub url: PlanValue<Url>,
+    pub connections: PlanValue<u64>,
+    pub hold: PlanValue<Duration>,
+    pub interval: PlanValue<Duration>,
+    pub timeout: PlanValue<Duration>,
+}
+
+impl Evaluate<crate::KeepAlivePlanOutput> for KeepAliveRequest {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> Result<crate::KeepAlivePlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        Ok(crate::KeepAlivePlanOutput {
+            url: self.url.evaluate(state)?,
+            connections: self.connections.evaluate(state)?,
+            hold: self.hold.evaluate(state)?,
+            interval: self.interval.evaluate(state)?,
+            timeout: self.timeout.evaluate(state)?,
+        })
+    }
+}
+
+impl TryFrom<bindings::KeepAlive> for KeepAliveRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::KeepAlive) -> Result<Self> {
+        Ok(Self {
+            url: binding
+                .url
+                .map(PlanValue::try_from)
+                .ok_or_else(|| anyhow!("keep_alive.url is required"))??,
+            connections: binding
+                .connections
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(100)),
+            hold: binding
+                .hold
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(Duration(TimeDelta::seconds(30)))),
+            interval: binding
+                .interval
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(Duration(TimeDelta::seconds(5)))),
+            timeout: binding
+                .timeout
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(Duration(TimeDelta::seconds(5)))),
+        })
+    }
+}
@@ -0,0 +1,366 @@
+//! Warnings for plans which are valid but likely mistaken or unsafe to run.
+
+use std::fmt::Display;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use go_parse_duration::parse_duration;
+use serde::Serialize;
+use url::Url;
+
+use super::{Diagnostic, Plan, PlanValue, Scope, Step, StepProtocols};
+
+/// Headers whose values authenticate the sender, besides any with "token" or "secret" in their
+/// name.
+const SECRET_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "x-api-key",
+    "api-key",
+];
+
+/// Request methods which change state on the target.
+const DESTRUCTIVE_METHODS: &[&str] = &["DELETE", "PATCH", "PUT"];
+
+/// The kind of problem a warning is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Lint {
+    /// A step whose run.if is always false.
+    UnreachableStep,
+    /// A CEL expression reading a step, local or loop value which won't exist when it runs.
+    UnresolvedVariable,
+    /// Credentials sent to a host or URL outside devil.scope.
+    SecretOutOfScope,
+    /// A step sending state-changing requests without being marked destructive.
+    UnmarkedDestructive,
+    /// A pause longer than a timeout of the same step.
+    PauseExceedsTimeout,
+}
+
+impl Lint {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::UnreachableStep => "unreachable_step",
+            Self::UnresolvedVariable => "unresolved_variable",
+            Self::SecretOutOfScope => "secret_out_of_scope",
+            Self::UnmarkedDestructive => "unmarked_destructive",
+            Self::PauseExceedsTimeout => "pause_exceeds_timeout",
+        }
+    }
+}
+
+impl Display for Lint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Plan {
+    /// Find constructs which are valid but likely mistaken or unsafe to run, without running
+    /// anything. `source` is the text the plan was parsed from, which warnings point into.
+    pub fn lint(&self, source: &str) -> Vec<Diagnostic> {
+        let doc: toml::Table = toml::from_str(source).unwrap_or_default();
+        let mut linter = Linter {
+            plan: self,
+            source,
+            diagnostics: Vec::new(),
+        };
+        for (index, (name, step)) in self.steps.iter().enumerate() {
+            if matches!(step.run.run_if, PlanValue::Literal(false)) {
+                linter.warn(
+                    Lint::UnreachableStep,
+                    vec![name.to_string(), "run".to_owned(), "if".to_owned()],
+                    format!("step {name} is unreachable since run.if is always false"),
+                );
+            }
+            let Some(table) = doc.get(name.as_str()).and_then(toml::Value::as_table) else {
+                continue;
+            };
+            linter.unresolved_variables(index, name, step, table);
+            linter.secrets_out_of_scope(name, table);
+            linter.unmarked_destructive(name, step, table);
+            linter.long_pauses(name, table);
+        }
+        linter.diagnostics
+    }
+}
+
+struct Linter<'a> {
+    plan: &'a Plan,
+    source: &'a str,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Linter<'_> {
+    fn warn(&mut self, lint: Lint, path: Vec<String>, message: String) {
+        self.diagnostics
+            .push(Diagnostic::warning(self.source, lint, path, message));
+    }
+
+    fn unresolved_variables(
+        &mut self,
+        index: usize,
+        name: &Arc<String>,
+        step: &Step,
+        table: &toml::Table,
+    ) {
+        let scripted = self
+            .plan
+            .steps
+            .values()
+            .take(index)
+            .any(|step| matches!(step.protocols, StepProtocols::Script { .. }));
+        let mut found = Vec::new();
+        walk(name, table, &mut |path, value| {
+            if let (Some("cel"), toml::Value::String(cel)) =
+                (path.last().map(String::as_str), value)
+            {
+                found.extend(references(cel).into_iter().map(|r| (path.to_vec(), r)));
+            }
+        });
+        for (path, (root, field)) in found {
+            let message = match root {
+                "steps" => match self.plan.steps.get_index_of(&field) {
+                    None => format!("step {name} reads steps.{field}, which doesn't exist"),
+                    Some(i) if i > index => {
+                        format!("step {name} reads steps.{field}, which runs after it")
+                    }
+                    Some(_) => continue,
+                },
+                // Scripts and targets set locals while running.
+                "locals" if field == "target" && self.plan.targets.is_some() => continue,
+                "locals" if scripted => continue,
+                "locals" if !self.plan.locals.contains_key(&field) => {
+                    format!("step {name} reads locals.{field}, which isn't set in devil.locals")
+                }
+                "for" if step.run.run_for.is_none() && step.run.follow.is_none() => {
+                    format!("step {name} reads for.{field} but has no run.for or run.follow")
+                }
+                "while" if step.run.run_while.is_none() => {
+                    format!("step {name} reads while.{field} but has no run.while")
+                }
+                _ => continue,
+            };
+            self.warn(Lint::UnresolvedVariable, path, message);
+        }
+    }
+
+    fn secrets_out_of_scope(&mut self, name: &str, table: &toml::Table) {
+        let Some(scope) = &self.plan.scope else {
+            return;
+        };
+        let mut secret = None;
+        let mut destinations = Vec::new();
+        walk(name, table, &mut |path, value| {
+            let key = path.last().map(String::as_str).unwrap_or_default();
+            let parent = path.len().checked_sub(2).map(|i| path[i].as_str());
+            let sends_secret = matches!(key, "sign" | "password")
+                || (parent == Some("headers") && is_secret_header(key));
+            if sends_secret && secret.is_none() {
+                secret = Some(path[1..].join("."));
+            }
+            match (key, value) {
+                ("url", toml::Value::String(url)) => {
+                    if let Ok(url) = Url::parse(url) {
+                        if url_out_of_scope(scope, &url) {
+                            destinations.push((path.to_vec(), url.to_string()));
+                        }
+                    }
+                }
+                ("host", toml::Value::String(host)) if host_out_of_scope(scope, host) => {
+                    destinations.push((path.to_vec(), host.clone()));
+                }
+                _ => {}
+            }
+        });
+        let Some(secret) = secret else {
+            return;
+        };
+        for (path, destination) in destinations {
+            self.warn(
+                Lint::SecretOutOfScope,
+                path,
+                format!("step {name} sends {secret} to {destination} outside devil.scope"),
+            );
+        }
+    }
+
+    fn unmarked_destructive(&mut self, name: &str, step: &Step, table: &toml::Table) {
+        if step.destructive {
+            return;
+        }
+        let mut found = Vec::new();
+        walk(name, table, &mut |path, value| {
+            if let (Some("method"), toml::Value::String(method)) =
+                (path.last().map(String::as_str), value)
+            {
+                let method = method.to_ascii_uppercase();
+                if DESTRUCTIVE_METHODS.contains(&method.as_str()) {
+                    found.push((path.to_vec(), method));
+                }
+            }
+        });
+        for (path, method) in found {
+            self.warn(
+                Lint::UnmarkedDestructive,
+                path,
+                format!(
+                    "step {name} sends {method} requests but isn't marked destructive, so it runs \
+                    without confirmation"
+                ),
+            );
+        }
+    }
+
+    fn long_pauses(&mut self, name: &str, table: &toml::Table) {
+        // Only a protocol's own timeout bounds the whole step, not those of nested options.
+        let Some((protocol, timeout, limit)) = table
+            .iter()
+            .filter_map(|(protocol, value)| {
+                let timeout = value.get("timeout")?.as_str()?;
+                Some((protocol, timeout, parse_duration(timeout).ok()?))
+            })
+            .min_by_key(|(_, _, limit)| *limit)
+        else {
+            return;
+        };
+        let mut found = Vec::new();
+        walk(name, table, &mut |path, value| {
+            if !path.iter().any(|key| key == "pause") || path.last().unwrap() != "duration" {
+                return;
+            }
+            if let Some(duration) = value.as_str() {
+                if parse_duration(duration).is_ok_and(|nanos| nanos > limit) {
+                    found.push((path.to_vec(), duration.to_owned()));
+                }
+            }
+        });
+        for (path, duration) in found {
+            self.warn(
+                Lint::PauseExceedsTimeout,
+                path,
+                format!(
+                    "step {name} pauses for {duration}, longer than its {protocol}.timeout of \
+                    {timeout}"
+                ),
+            );
+        }
+    }
+}
+
+/// Call `visit` with every value nested in a step's table and the keys leading to it, starting
+/// with the step's name. Tables in arrays are visited under the array's key.
+fn walk<'t>(name: &str, table: &'t toml::Table, visit: &mut dyn FnMut(&[String], &'t toml::Value)) {
+    fn walk_table<'t>(
+        table: &'t toml::Table,
+        path: &mut Vec<String>,
+        visit: &mut dyn FnMut(&[String], &'t toml::Value),
+    ) {
+        for (key, value) in table {
+            path.push(key.clone());
+            visit(path, value);
+            match value {
+                toml::Value::Table(table) => walk_table(table, path, visit),
+                toml::Value::Array(values) => {
+                    for table in values.iter().filter_map(toml::Value::as_table) {
+                        walk_table(table, path, visit);
+                    }
+                }
+                _ => {}
+            }
+            path.pop();
+        }
+    }
+    walk_table(table, &mut vec![name.to_owned()], visit);
+}
+
+/// The fields read from the `steps`, `locals`, `for` and `while` variables in a CEL expression,
+/// like `("steps", "login")` for `steps.login.response`.
+fn references(cel: &str) -> Vec<(&'static str, String)> {
+    let bytes = cel.as_bytes();
+    let is_ident = |b: u8| b.is_ascii_alphanumeric() || b == b'_';
+    let mut found = Vec::new();
+    let mut quote = None;
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        if let Some(q) = quote {
+            if b == b'\\' {
+                i += 1;
+            } else if b == q {
+                quote = None;
+            }
+            i += 1;
+            continue;
+        }
+        if b == b'"' || b == b'\'' {
+            quote = Some(b);
+            i += 1;
+            continue;
+        }
+        if !is_ident(b) {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < bytes.len() && is_ident(bytes[i]) {
+            i += 1;
+        }
+        // Skip fields of other values, like the `steps` in `x.steps`.
+        if start > 0 && bytes[start - 1] == b'.' {
+            continue;
+        }
+        let root = match &cel[start..i] {
+            "steps" => "steps",
+            "locals" => "locals",
+            "for" => "for",
+            "while" => "while",
+            _ => continue,
+        };
+        if let Some(field) = field(&cel[i..]) {
+            found.push((root, field));
+        }
+    }
+    found
+}
+
+/// The field accessed at the start of `rest`, as in `.name` or `["name"]`.
+fn field(rest: &str) -> Option<String> {
+    if let Some(rest) = rest.strip_prefix('.') {
+        let end = rest
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or(rest.len());
+        return (end > 0).then(|| rest[..end].to_owned());
+    }
+    let rest = rest.strip_prefix('[')?.trim_start();
+    let quote = rest.chars().next().filter(|c| matches!(c, '"' | '\''))?;
+    let rest = &rest[1..];
+    Some(rest[..rest.find(quote)?].to_owned())
+}
+
+fn is_secret_header(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    SECRET_HEADERS.contains(&name.as_str()) || name.contains("token") || name.contains("secret")
+}
+
+/// Whether the scope would block a URL, judging its host by name alone.
+fn url_out_of_scope(scope: &Scope, url: &Url) -> bool {
+    scope.denies_url(url)
+        || scope.limits_urls() && !scope.allows_url(url)
+        || url
+            .host_str()
+            .is_some_and(|host| host_out_of_scope(scope, host))
+}
+
+/// Whether the scope would block a host. Names which may resolve to an allowed address range
+/// aren't counted since that can't be known before running.
+fn host_out_of_scope(scope: &Scope, host: &str) -> bool {
+    let bare = host.trim_start_matches('[').trim_end_matches(']');
+    if let Ok(ip) = bare.parse::<IpAddr>() {
+        return scope.denies_ip(ip) || scope.limits_hosts() && !scope.allows_ip(ip);
+    }
+    scope.denies_host(host)
+        || scope.limits_hosts() && !scope.allows_host(host) && !scope.allows_networks()
+}
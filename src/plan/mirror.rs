@@ -0,0 +1,94 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail};
+use devil_derive::BigQuerySchema;
+use serde::Serialize;
+use url::Url;
+
+use super::{Evaluate, PlanData, PlanValue, TryFromPlanData};
+use crate::bindings::Literal;
+use crate::{bindings, Error, Result, State};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, BigQuerySchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MirrorMode {
+    /// Send the mirrored request in the background without recording its output.
+    FireAndForget,
+    /// Wait for the mirrored request and record it along with how it differed.
+    #[default]
+    Compare,
+}
+
+impl FromStr for MirrorMode {
+    type Err = Error;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "fire_and_forget" => Ok(Self::FireAndForget),
+            "compare" => Ok(Self::Compare),
+            val => bail!("unrecognized devil.mirror.mode string {val}"),
+        }
+    }
+}
+
+impl TryFromPlanData for MirrorMode {
+    type Error = Error;
+    fn try_from_plan_data(value: PlanData) -> std::result::Result<Self, Self::Error> {
+        match value.0 {
+            cel_interpreter::Value::String(s) => s.parse(),
+            val => bail!("unsupported value {val:?} for field devil.mirror.mode"),
+        }
+    }
+}
+
+impl TryFrom<bindings::Value> for PlanValue<MirrorMode> {
+    type Error = Error;
+    fn try_from(binding: bindings::Value) -> Result<Self> {
+        match binding {
+            bindings::Value::ExpressionCel { cel, vars } => Ok(Self::Dynamic {
+                cel,
+                vars: vars.unwrap_or_default().into_iter().collect(),
+            }),
+            bindings::Value::Literal(Literal::String(x)) => Ok(Self::Literal(x.parse()?)),
+            val => bail!("invalid value {val:?} for field devil.mirror.mode"),
+        }
+    }
+}
+
+/// Sends every HTTP request in the plan to a second base URL as well.
+#[derive(Debug, Clone)]
+pub struct MirrorRequest {
+    pub url: PlanValue<Url>,
+    pub mode: PlanValue<MirrorMode>,
+}
+
+impl Evaluate<crate::MirrorPlanOutput> for MirrorRequest {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> Result<crate::MirrorPlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        Ok(crate::MirrorPlanOutput {
+            url: self.url.evaluate(state)?,
+            mode: self.mode.evaluate(state)?,
+        })
+    }
+}
+
+impl TryFrom<bindings::Mirror> for MirrorRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::Mirror) -> Result<Self> {
+        Ok(Self {
+            url: binding
+                .url
+                .map(PlanValue::try_from)
+                .ok_or_else(|| anyhow!("devil.mirror.url is required"))??,
+            mode: binding
+                .mode
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or_default(),
+        })
+    }
+}
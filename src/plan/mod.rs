@@ -1,4 +1,5 @@
 mod graphql;
+mod grpc;
 mod http;
 mod http1;
 mod raw_http2;
@@ -9,10 +10,44 @@ mod tcp;
 mod raw_tcp;
 mod udp;
 mod quic;
+mod module;
+mod crawl;
+mod discover;
+mod forced_browse;
+mod vhost;
+mod takeover;
+mod banner;
+mod session;
+mod diagnostic;
+mod introspect;
+mod sign;
+mod mirror;
+mod egress;
+mod network;
+mod adaptive;
+mod command;
+mod script;
+mod grpc_reflect;
+mod range;
+mod conditional;
+mod h2_attack;
+mod alpn_matrix;
+mod keep_alive;
+mod tcp_burst;
+mod websocket;
+mod expect;
+mod dns;
+mod detect;
+mod targets;
+mod scope;
+mod budget;
+mod lint;
+mod auth;
 pub mod location;
 
 use bytes::Bytes;
 pub use graphql::*;
+pub use grpc::*;
 pub use http::*;
 pub use http1::*;
 use location::{HttpLocation, Side};
@@ -23,6 +58,39 @@ use strum::{Display, EnumDiscriminants};
 pub use tls::*;
 pub use udp::*;
 pub use quic::*;
+pub use module::*;
+pub use crawl::*;
+pub use discover::*;
+pub use forced_browse::*;
+pub use vhost::*;
+pub use takeover::*;
+pub use banner::*;
+pub use session::*;
+pub use diagnostic::*;
+pub use introspect::*;
+pub use sign::*;
+pub use mirror::*;
+pub use egress::*;
+pub use network::*;
+pub use adaptive::*;
+pub use command::*;
+pub use script::*;
+pub use grpc_reflect::*;
+pub use range::*;
+pub use conditional::*;
+pub use h2_attack::*;
+pub use alpn_matrix::*;
+pub use keep_alive::*;
+pub use tcp_burst::*;
+pub use websocket::*;
+pub use expect::*;
+pub use dns::*;
+pub use detect::*;
+pub use targets::*;
+pub use scope::*;
+pub use budget::*;
+pub use lint::*;
+pub use auth::*;
 pub use tcp::*;
 pub use raw_tcp::*;
 
@@ -51,12 +119,43 @@ pub struct Plan {
     pub name: Arc<String>,
     pub steps: IndexMap<Arc<String>, Step>,
     pub locals: IndexMap<String, PlanValue<PlanData, Infallible>>,
+    pub mirror: Option<MirrorRequest>,
+    pub egress: IndexMap<String, EgressRequest>,
+    /// Network conditions steps can emulate, in addition to the built-in profiles.
+    pub network: IndexMap<String, NetworkRequest>,
+    /// Seeds the random functions in CEL so runs generate the same data.
+    pub seed: Option<u64>,
+    /// Replaces the current time returned by now() in CEL.
+    pub now: Option<chrono::DateTime<chrono::FixedOffset>>,
+    /// Base URLs to run the plan against once each, instead of a single run.
+    pub targets: Option<Targets>,
+    /// Where steps may send traffic. Connections and requests outside it are blocked.
+    pub scope: Option<Scope>,
+    pub budget: Option<Budget>,
+    /// Logins which steps can send the credentials of with run.auth.
+    pub auth: IndexMap<String, AuthRequest>,
 }
 
 impl<'a> Plan {
+    /// Parse and validate a plan. Errors are returned as a [`Diagnostic`] pointing into `input`.
     pub fn parse(input: &'a str) -> Result<Self> {
-        let parsed = bindings::Plan::parse(input)?;
-        Self::from_binding(parsed)
+        bindings::Plan::parse(input)
+            .and_then(Self::from_binding)
+            .map_err(|e| Diagnostic::error(input, e).into())
+    }
+
+    /// Parse a plan and report all problems found, including warnings for plans which are valid
+    /// but likely mistaken.
+    pub fn check(input: &'a str) -> Vec<Diagnostic> {
+        let plan = match Self::parse(input) {
+            Ok(plan) => plan,
+            Err(e) => {
+                return vec![e
+                    .downcast::<Diagnostic>()
+                    .unwrap_or_else(|e| Diagnostic::error(input, e))]
+            }
+        };
+        plan.lint(input)
     }
 
     pub fn from_binding(mut plan: bindings::Plan) -> Result<Self> {
@@ -79,9 +178,16 @@ impl<'a> Plan {
             .into_iter()
             .map(|(name, value)| {
                 // Apply the user and implicit defaults.
-                let value = value.apply_defaults(plan.devil.defaults.clone());
+                let mut value = value.apply_defaults(plan.devil.defaults.clone());
+                // The plan-wide error policy applies to any step that doesn't set its own.
+                if let Some(on_error) = &plan.devil.on_error {
+                    let run = value.run.get_or_insert_with(Default::default);
+                    run.on_error = run.on_error.take().or_else(|| Some(on_error.clone()));
+                }
                 // Apply planner requirements and convert to planner structure.
-                Ok((Arc::new(name), Step::from_bindings(value)?))
+                let step = Step::from_bindings(value)
+                    .map_err(|e| locate(e, name.clone()).context(format!("step {name}")))?;
+                Ok((Arc::new(name), step))
             })
             .collect::<Result<_>>()?;
         let locals = plan
@@ -91,7 +197,82 @@ impl<'a> Plan {
             .map(|(k, v)| Ok((k, PlanValue::try_from(v)?)))
             .collect::<Result<_>>()?;
 
-        Ok(Plan { name: plan.devil.name.into(), steps, locals })
+        let mirror = plan
+            .devil
+            .mirror
+            .map(MirrorRequest::try_from)
+            .transpose()
+            .map_err(|e| locate(locate(e, "mirror"), "devil"))?;
+        let egress = plan
+            .devil
+            .egress
+            .into_iter()
+            .map(|(name, egress)| {
+                let request = EgressRequest::try_from(egress)
+                    .map_err(|e| locate(locate(locate(e, &name), "egress"), "devil"))?;
+                Ok((name, request))
+            })
+            .collect::<Result<_>>()?;
+        let network = plan
+            .devil
+            .network
+            .into_iter()
+            .map(|(name, network)| {
+                let request = NetworkRequest::try_from(network)
+                    .map_err(|e| locate(locate(locate(e, &name), "network"), "devil"))?;
+                Ok((name, request))
+            })
+            .collect::<Result<_>>()?;
+
+        let now = plan
+            .devil
+            .now
+            .map(|now| chrono::DateTime::parse_from_rfc3339(&now))
+            .transpose()
+            .map_err(|e| locate(locate(anyhow!("invalid RFC 3339 time: {e}"), "now"), "devil"))?;
+        let targets = plan
+            .devil
+            .targets
+            .map(Targets::try_from)
+            .transpose()
+            .map_err(|e| locate(locate(e, "targets"), "devil"))?;
+        let scope = plan
+            .devil
+            .scope
+            .map(Scope::try_from)
+            .transpose()
+            .map_err(|e| locate(locate(e, "scope"), "devil"))?;
+        let budget = plan
+            .devil
+            .budget
+            .map(Budget::try_from)
+            .transpose()
+            .map_err(|e| locate(locate(e, "budget"), "devil"))?;
+        let auth = plan
+            .devil
+            .auth
+            .into_iter()
+            .map(|(name, auth)| {
+                let request = AuthRequest::try_from(auth)
+                    .map_err(|e| locate(locate(locate(e, &name), "auth"), "devil"))?;
+                Ok((name, request))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Plan {
+            name: plan.devil.name.into(),
+            steps,
+            locals,
+            mirror,
+            egress,
+            network,
+            seed: plan.devil.seed,
+            now,
+            targets,
+            scope,
+            budget,
+            auth,
+        })
     }
 }
 
@@ -374,6 +555,18 @@ impl TryFromPlanData for i64 {
     }
 }
 
+impl TryFromPlanData for f64 {
+    type Error = Error;
+    fn try_from_plan_data(value: PlanData) -> std::result::Result<Self, Self::Error> {
+        match value.0 {
+            cel_interpreter::Value::Float(x) => Ok(x),
+            cel_interpreter::Value::Int(x) => Ok(x as f64),
+            cel_interpreter::Value::UInt(x) => Ok(x as f64),
+            val => bail!("{val:?} has invalid type for float value"),
+        }
+    }
+}
+
 impl TryFromPlanData for usize {
     type Error = Error;
     fn try_from_plan_data(value: PlanData) -> std::result::Result<Self, Self::Error> {
@@ -783,6 +976,8 @@ pub struct WebsocketRequest {}
 pub struct Step {
     pub protocols: StepProtocols,
     pub run: Run,
+    /// Only run once the executor allows destructive steps or confirms this one.
+    pub destructive: bool,
     pub sync: IndexMap<String, Synchronizer>,
     pub pause: IndexMap<String, PauseValue>,
     pub signal: IndexMap<String, SignalValue>,
@@ -791,10 +986,16 @@ pub struct Step {
 impl Step {
     pub fn from_bindings(binding: bindings::Step) -> Result<Step> {
         let protocols = match binding.protocols {
-            bindings::StepProtocols::Graphql { graphql, http } => StepProtocols::GraphqlHttp {
-                graphql: graphql.try_into()?,
-                http: http.unwrap_or_default().try_into()?,
-            },
+            bindings::StepProtocols::Graphql { graphql, http } => {
+                let http = http.unwrap_or_default();
+                if http.http3.is_some() {
+                    bail!("http.http3 is not yet supported under graphql");
+                }
+                StepProtocols::GraphqlHttp {
+                    graphql: graphql.try_into()?,
+                    http: http.try_into()?,
+                }
+            }
             bindings::StepProtocols::GraphqlH1c {
                 graphql,
                 h1c,
@@ -847,6 +1048,34 @@ impl Step {
                 tcp: tcp.unwrap_or_default().try_into()?,
                 raw_tcp: raw_tcp.unwrap_or_default().try_into()?,
             },
+            bindings::StepProtocols::GrpcH2c {
+                grpc,
+                h2c,
+                raw_h2c,
+                tcp,
+                raw_tcp,
+            } => StepProtocols::GrpcH2c {
+                grpc: grpc.try_into()?,
+                h2c: h2c.try_into()?,
+                raw_h2c: raw_h2c.unwrap_or_default().try_into()?,
+                tcp: tcp.unwrap_or_default().try_into()?,
+                raw_tcp: raw_tcp.unwrap_or_default().try_into()?,
+            },
+            bindings::StepProtocols::GrpcH2 {
+                grpc,
+                h2,
+                raw_h2,
+                tls,
+                tcp,
+                raw_tcp,
+            } => StepProtocols::GrpcH2 {
+                grpc: grpc.try_into()?,
+                h2: h2.unwrap_or_default().try_into()?,
+                raw_h2: raw_h2.unwrap_or_default().try_into()?,
+                tls: tls.unwrap_or_default().try_into()?,
+                tcp: tcp.unwrap_or_default().try_into()?,
+                raw_tcp: raw_tcp.unwrap_or_default().try_into()?,
+            },
             //bindings::StepProtocols::GraphqlH3 {
             //    graphql,
             //    h3,
@@ -905,11 +1134,21 @@ impl Step {
                 tcp: tcp.unwrap_or_default().try_into()?,
                 raw_tcp: raw_tcp.unwrap_or_default().try_into()?,
             },
-            //bindings::StepProtocols::H3 { h3, quic, udp } => StepProtocols::H3 {
-            //    h3: h3.try_into()?,
-            //    quic: quic.unwrap_or_default().try_into()?,
-            //    udp: udp.unwrap_or_default().try_into()?,
-            //},
+            bindings::StepProtocols::H3 { h3, quic, udp } => {
+                // QUIC binds its own UDP socket, so there's nothing for udp settings to apply to.
+                if udp.is_some_and(|udp| {
+                    udp.host.is_some()
+                        || udp.port.is_some()
+                        || udp.source_port.is_some()
+                        || udp.body.is_some()
+                }) {
+                    bail!("udp is not yet supported under h3");
+                }
+                StepProtocols::H3 {
+                    h3: h3.try_into()?,
+                    quic: quic.unwrap_or_default().try_into()?,
+                }
+            }
             bindings::StepProtocols::RawH2c {
                 raw_h2c,
                 tcp,
@@ -957,11 +1196,77 @@ impl Step {
             //bindings::StepProtocols::Udp { udp } => StepProtocols::Udp {
             //    udp: udp.try_into()?,
             //},
+            bindings::StepProtocols::Module { module } => StepProtocols::Module {
+                module: module.try_into()?,
+            },
+            bindings::StepProtocols::Crawl { crawl } => StepProtocols::Crawl {
+                crawl: crawl.try_into()?,
+            },
+            bindings::StepProtocols::Discover { discover } => StepProtocols::Discover {
+                discover: discover.try_into()?,
+            },
+            bindings::StepProtocols::ForcedBrowse { forced_browse } => {
+                StepProtocols::ForcedBrowse {
+                    forced_browse: forced_browse.try_into()?,
+                }
+            }
+            bindings::StepProtocols::Vhost { vhost } => StepProtocols::Vhost {
+                vhost: vhost.try_into()?,
+            },
+            bindings::StepProtocols::Takeover { takeover } => StepProtocols::Takeover {
+                takeover: takeover.try_into()?,
+            },
+            bindings::StepProtocols::Banner { banner } => StepProtocols::Banner {
+                banner: banner.try_into()?,
+            },
+            bindings::StepProtocols::Session { session } => StepProtocols::Session {
+                session: session.try_into()?,
+            },
+            bindings::StepProtocols::Command { command } => StepProtocols::Command {
+                command: command.try_into()?,
+            },
+            bindings::StepProtocols::Script { script } => StepProtocols::Script {
+                script: script.try_into()?,
+            },
+            bindings::StepProtocols::GrpcReflect { grpc_reflect } => StepProtocols::GrpcReflect {
+                grpc_reflect: grpc_reflect.try_into()?,
+            },
+            bindings::StepProtocols::Range { range } => StepProtocols::Range {
+                range: range.try_into()?,
+            },
+            bindings::StepProtocols::Conditional { conditional } => StepProtocols::Conditional {
+                conditional: conditional.try_into()?,
+            },
+            bindings::StepProtocols::H2Attack { h2_attack } => StepProtocols::H2Attack {
+                h2_attack: h2_attack.try_into()?,
+            },
+            bindings::StepProtocols::AlpnMatrix { alpn_matrix } => StepProtocols::AlpnMatrix {
+                alpn_matrix: alpn_matrix.try_into()?,
+            },
+            bindings::StepProtocols::KeepAlive { keep_alive } => StepProtocols::KeepAlive {
+                keep_alive: keep_alive.try_into()?,
+            },
+            bindings::StepProtocols::TcpBurst { tcp_burst } => StepProtocols::TcpBurst {
+                tcp_burst: tcp_burst.try_into()?,
+            },
+            bindings::StepProtocols::Ws { ws } => StepProtocols::Ws {
+                ws: ws.try_into()?,
+            },
+            bindings::StepProtocols::Dns { dns } => StepProtocols::Dns {
+                dns: dns.try_into()?,
+            },
+            bindings::StepProtocols::Detect { detect } => StepProtocols::Detect {
+                detect: detect.try_into()?,
+            },
             _ => unimplemented!(),
         };
+        if protocols.is_composite() {
+            check_composite_run(binding.run.as_ref(), &binding.sync, &binding.pause, &binding.signal)?;
+        }
 
         Ok(Step {
             protocols,
+            destructive: binding.destructive,
             sync: binding.sync.into_iter().map(|(k, v)| Ok::<_, crate::Error>((k, <Synchronizer>::try_from(v)?))).try_collect()?,
             pause: binding.pause.into_iter().map(|(k, v)| Ok::<_, crate::Error>((k, <PauseValue>::try_from(v)?))).try_collect()?,
             signal: binding.signal.into_iter().map(|(k, v)| Ok::<_, crate::Error>((k, <SignalValue>::try_from(v)?))).try_collect()?,
@@ -1000,6 +1305,16 @@ impl Step {
                             .transpose()?
                             .unwrap_or_default(),
                         share: run.share.try_into()?,
+                        on_error: run
+                            .on_error
+                            .map(PlanValue::try_from)
+                            .transpose()?
+                            .unwrap_or_default(),
+                        follow: run.follow.map(Follow::try_from).transpose()?,
+                        egress: run.egress.try_into()?,
+                        network: run.network.try_into()?,
+                        adaptive: run.adaptive.map(AdaptiveRequest::try_from).transpose()?,
+                        auth: run.auth.try_into()?,
                     })
                 })
                 .transpose()?
@@ -1008,6 +1323,32 @@ impl Step {
     }
 }
 
+/// Reject the run controls which repeat or coordinate a step's jobs on steps the executor runs
+/// itself, since those make their own jobs.
+fn check_composite_run(
+    run: Option<&bindings::Run>,
+    sync: &IndexMap<String, bindings::Sync>,
+    pause: &IndexMap<String, bindings::PauseValue>,
+    signal: &IndexMap<String, bindings::SignalValue>,
+) -> Result<()> {
+    let controls = [
+        ("run.while", run.is_some_and(|run| run.run_while.is_some())),
+        ("run.for", run.is_some_and(|run| run.run_for.is_some())),
+        ("run.count", run.is_some_and(|run| run.count.is_some())),
+        ("run.parallel", run.is_some_and(|run| run.parallel.is_some())),
+        ("run.share", run.is_some_and(|run| run.share.is_some())),
+        ("run.follow", run.is_some_and(|run| run.follow.is_some())),
+        ("run.adaptive", run.is_some_and(|run| run.adaptive.is_some())),
+        ("sync", !sync.is_empty()),
+        ("pause", !pause.is_empty()),
+        ("signal", !signal.is_empty()),
+    ];
+    if let Some((field, _)) = controls.into_iter().find(|(_, set)| *set) {
+        bail!("{field} can't be used with steps that run their own jobs, like crawl or dns");
+    }
+    Ok(())
+}
+
 #[derive(Debug, Clone)]
 pub enum Synchronizer {
     Barrier{ count: PlanValue<usize> },
@@ -1097,6 +1438,38 @@ impl TryFromPlanData for Parallelism {
     }
 }
 
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum OnError {
+    #[default]
+    Abort,
+    Continue,
+    Jump(String),
+}
+
+impl FromStr for OnError {
+    type Err = Error;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "abort" => Ok(Self::Abort),
+            "continue" => Ok(Self::Continue),
+            val => match val.strip_prefix("jump:") {
+                Some(step) if !step.is_empty() => Ok(Self::Jump(step.to_owned())),
+                _ => bail!("unrecognized on_error policy {val}"),
+            },
+        }
+    }
+}
+
+impl TryFromPlanData for OnError {
+    type Error = Error;
+    fn try_from_plan_data(value: PlanData) -> std::result::Result<Self, Self::Error> {
+        match value.0 {
+            cel_interpreter::Value::String(s) => s.parse(),
+            val => bail!("unsupported value {val:?} for field run.on_error"),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Run {
     pub run_if: PlanValue<bool>,
@@ -1105,6 +1478,44 @@ pub struct Run {
     pub count: PlanValue<u64>,
     pub parallel: PlanValue<Parallelism>,
     pub share: PlanValue<Option<ProtocolField>>,
+    pub on_error: PlanValue<OnError>,
+    pub follow: Option<Follow>,
+    pub egress: PlanValue<Option<String>>,
+    pub network: PlanValue<Option<String>>,
+    pub adaptive: Option<AdaptiveRequest>,
+    /// The login in devil.auth whose credentials to send.
+    pub auth: PlanValue<Option<String>>,
+}
+
+/// Runs a step again for each new value found in its jobs' outputs, such as links in a response.
+/// Values are only followed once per step.
+#[derive(Debug, Clone)]
+pub struct Follow {
+    pub values: IterablePlanValue,
+    pub max_depth: PlanValue<u64>,
+    pub max_jobs: PlanValue<u64>,
+}
+
+impl TryFrom<bindings::Follow> for Follow {
+    type Error = Error;
+    fn try_from(binding: bindings::Follow) -> Result<Self> {
+        Ok(Self {
+            values: binding
+                .values
+                .map(IterablePlanValue::try_from)
+                .ok_or_else(|| anyhow!("run.follow.values is required"))??,
+            max_depth: binding
+                .max_depth
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(3)),
+            max_jobs: binding
+                .max_jobs
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(100)),
+        })
+    }
 }
 
 impl Default for Run {
@@ -1116,6 +1527,12 @@ impl Default for Run {
             count: PlanValue::Literal(1),
             parallel: PlanValue::default(),
             share: PlanValue::default(),
+            on_error: PlanValue::default(),
+            follow: None,
+            egress: PlanValue::Literal(None),
+            network: PlanValue::Literal(None),
+            adaptive: None,
+            auth: PlanValue::Literal(None),
         }
     }
 }
@@ -1144,6 +1561,7 @@ impl Evaluate<crate::RunPlanOutput> for Run {
             count: self.count.evaluate(state)?,
             parallel: self.parallel.evaluate(state)?,
             share: self.share.evaluate(state)?,
+            on_error: self.on_error.evaluate(state)?,
         };
         // Only one of while or for may be used.
         if out.run_while.is_some() && out.run_for.is_some() {
@@ -1192,6 +1610,21 @@ pub enum StepProtocols {
         tcp: TcpRequest,
         raw_tcp: RawTcpRequest,
     },
+    GrpcH2c {
+        grpc: GrpcRequest,
+        h2c: Http2Request,
+        raw_h2c: RawHttp2Request,
+        tcp: TcpRequest,
+        raw_tcp: RawTcpRequest,
+    },
+    GrpcH2 {
+        grpc: GrpcRequest,
+        h2: Http2Request,
+        raw_h2: RawHttp2Request,
+        tls: TlsRequest,
+        tcp: TcpRequest,
+        raw_tcp: RawTcpRequest,
+    },
     //GraphqlH3 {
     //    graphql: GraphqlRequest,
     //    h3: Http3Request,
@@ -1225,11 +1658,10 @@ pub enum StepProtocols {
         tcp: TcpRequest,
         raw_tcp: RawTcpRequest,
     },
-    //H3 {
-    //    h3: Http3Request,
-    //    quic: QuicRequest,
-    //    udp: UdpRequest,
-    //},
+    H3 {
+        h3: Http3Request,
+        quic: QuicRequest,
+    },
     RawH2c {
         raw_h2c: RawHttp2Request,
         tcp: TcpRequest,
@@ -1264,11 +1696,118 @@ pub enum StepProtocols {
     //Udp {
     //    udp: UdpRequest,
     //},
+    Module {
+        module: ModuleRequest,
+    },
+    Crawl {
+        crawl: CrawlRequest,
+    },
+    Discover {
+        discover: DiscoverRequest,
+    },
+    ForcedBrowse {
+        forced_browse: ForcedBrowseRequest,
+    },
+    Vhost {
+        vhost: VhostRequest,
+    },
+    Takeover {
+        takeover: TakeoverRequest,
+    },
+    Banner {
+        banner: BannerRequest,
+    },
+    Session {
+        session: SessionRequest,
+    },
+    Command {
+        command: CommandRequest,
+    },
+    Script {
+        script: ScriptRequest,
+    },
+    GrpcReflect {
+        grpc_reflect: GrpcReflectRequest,
+    },
+    Range {
+        range: RangeRequest,
+    },
+    Conditional {
+        conditional: ConditionalRequest,
+    },
+    H2Attack {
+        h2_attack: H2AttackRequest,
+    },
+    AlpnMatrix {
+        alpn_matrix: AlpnMatrixRequest,
+    },
+    KeepAlive {
+        keep_alive: KeepAliveRequest,
+    },
+    TcpBurst {
+        tcp_burst: TcpBurstRequest,
+    },
+    Ws {
+        ws: WebSocketRequest,
+    },
+    Dns {
+        dns: DnsRequest,
+    },
+    Detect {
+        detect: DetectRequest,
+    },
 }
 
 impl StepProtocols {
+    /// Whether the executor runs the step itself rather than through a protocol stack.
+    pub fn is_composite(&self) -> bool {
+        matches!(
+            self,
+            Self::Module { .. }
+                | Self::Crawl { .. }
+                | Self::Discover { .. }
+                | Self::ForcedBrowse { .. }
+                | Self::Vhost { .. }
+                | Self::Takeover { .. }
+                | Self::Banner { .. }
+                | Self::Session { .. }
+                | Self::Command { .. }
+                | Self::Script { .. }
+                | Self::GrpcReflect { .. }
+                | Self::Range { .. }
+                | Self::Conditional { .. }
+                | Self::H2Attack { .. }
+                | Self::AlpnMatrix { .. }
+                | Self::KeepAlive { .. }
+                | Self::TcpBurst { .. }
+                | Self::Dns { .. }
+                | Self::Detect { .. }
+        )
+    }
+
     pub fn into_stack(self) -> Vec<Protocol> {
         match self {
+            // Modules, crawls, and discovery are run by the executor directly rather than through
+            // a protocol stack.
+            Self::Module { .. }
+            | Self::Crawl { .. }
+            | Self::Discover { .. }
+            | Self::ForcedBrowse { .. }
+            | Self::Vhost { .. }
+            | Self::Takeover { .. }
+            | Self::Banner { .. }
+            | Self::Session { .. }
+            | Self::Command { .. }
+            | Self::Script { .. }
+            | Self::GrpcReflect { .. }
+            | Self::Range { .. }
+            | Self::Conditional { .. }
+            | Self::H2Attack { .. }
+            | Self::AlpnMatrix { .. }
+            | Self::KeepAlive { .. }
+            | Self::TcpBurst { .. }
+            | Self::Dns { .. }
+            | Self::Detect { .. } => Vec::new(),
             Self::GraphqlHttp { graphql, http } => {
                 vec![Protocol::Graphql(graphql), Protocol::Http(http)]
             }
@@ -1332,6 +1871,38 @@ impl StepProtocols {
                     Protocol::RawTcp(raw_tcp),
                 ]
             }
+            Self::GrpcH2c {
+                grpc,
+                h2c,
+                raw_h2c,
+                tcp,
+                raw_tcp,
+            } => {
+                vec![
+                    Protocol::Grpc(grpc),
+                    Protocol::H2c(h2c),
+                    Protocol::RawH2c(raw_h2c),
+                    Protocol::Tcp(tcp),
+                    Protocol::RawTcp(raw_tcp),
+                ]
+            }
+            Self::GrpcH2 {
+                grpc,
+                h2,
+                raw_h2,
+                tls,
+                tcp,
+                raw_tcp,
+            } => {
+                vec![
+                    Protocol::Grpc(grpc),
+                    Protocol::H2(h2),
+                    Protocol::RawH2(raw_h2),
+                    Protocol::Tls(tls),
+                    Protocol::Tcp(tcp),
+                    Protocol::RawTcp(raw_tcp),
+                ]
+            }
             //Self::GraphqlH3 {
             //    graphql,
             //    h3,
@@ -1400,9 +1971,9 @@ impl StepProtocols {
                     Protocol::RawTcp(raw_tcp),
                 ]
             }
-            //Self::H3 { h3, quic, udp } => {
-            //    vec![Protocol::H3(h3), Protocol::Quic(quic), Protocol::Udp(udp)]
-            //}
+            Self::H3 { h3, quic } => {
+                vec![Protocol::H3(h3), Protocol::Quic(quic)]
+            }
             Self::RawH2c {
                 raw_h2c,
                 tcp,
@@ -1447,6 +2018,9 @@ impl StepProtocols {
             Self::RawTcp { raw_tcp } => {
                 vec![Protocol::RawTcp(raw_tcp)]
             }
+            Self::Ws { ws } => {
+                vec![Protocol::Ws(ws)]
+            }
             //Self::Quic { quic, udp } => {
             //    vec![Protocol::Udp(udp), Protocol::Quic(quic)]
             //}
@@ -1462,6 +2036,7 @@ impl StepProtocols {
 #[strum(serialize_all = "snake_case")]
 pub enum Protocol {
     Graphql(GraphqlRequest),
+    Grpc(GrpcRequest),
     Http(HttpRequest),
     H1c(Http1Request),
     H1(Http1Request),
@@ -1469,11 +2044,12 @@ pub enum Protocol {
     RawH2c(RawHttp2Request),
     H2(Http2Request),
     RawH2(RawHttp2Request),
-    //H3(Http3Request),
+    H3(Http3Request),
     Tls(TlsRequest),
     Tcp(TcpRequest),
     RawTcp(RawTcpRequest),
-    //Quic(QuicRequest),
+    Quic(QuicRequest),
+    Ws(WebSocketRequest),
     //Udp(UdpRequest),
 }
 
@@ -1481,6 +2057,7 @@ impl Protocol {
     pub fn field(&self) -> ProtocolField {
         match self {
             Self::Graphql(_) => ProtocolField::Graphql,
+            Self::Grpc(_) => ProtocolField::Grpc,
             Self::Http(_) => ProtocolField::Http,
             Self::H1c(_) => ProtocolField::H1c,
             Self::H1(_) => ProtocolField::H1,
@@ -1488,11 +2065,12 @@ impl Protocol {
             Self::RawH2c(_) => ProtocolField::RawH2c,
             Self::H2(_) => ProtocolField::H2,
             Self::RawH2(_) => ProtocolField::RawH2,
-            //Self::H3(_) => ProtocolField::H3,
+            Self::H3(_) => ProtocolField::H3,
             Self::Tls(_) => ProtocolField::Tls,
             Self::Tcp(_) => ProtocolField::Tcp,
             Self::RawTcp(_) => ProtocolField::RawTcp,
-            //Self::Quic(_) => ProtocolField::Quic,
+            Self::Quic(_) => ProtocolField::Quic,
+            Self::Ws(_) => ProtocolField::Ws,
             //Self::Udp(_) => ProtocolField::Udp,
         }
     }
@@ -1507,6 +2085,7 @@ impl Evaluate<StepPlanOutput> for Protocol {
     {
         Ok(match self {
             Self::Graphql(proto) => StepPlanOutput::Graphql(proto.evaluate(state)?),
+            Self::Grpc(proto) => StepPlanOutput::Grpc(proto.evaluate(state)?),
             Self::Http(proto) => StepPlanOutput::Http(proto.evaluate(state)?),
             Self::H1c(proto) => StepPlanOutput::H1c(proto.evaluate(state)?),
             Self::H1(proto) => StepPlanOutput::H1(proto.evaluate(state)?),
@@ -1514,15 +2093,13 @@ impl Evaluate<StepPlanOutput> for Protocol {
             Self::RawH2c(proto) => StepPlanOutput::RawH2c(proto.evaluate(state)?),
             Self::H2(proto) => StepPlanOutput::H2(proto.evaluate(state)?),
             Self::RawH2(proto) => StepPlanOutput::RawH2(proto.evaluate(state)?),
-            //Self::Http3(proto) => ProtocolOutput::Http3(proto.evaluate(state)?),
+            Self::H3(proto) => StepPlanOutput::H3(proto.evaluate(state)?),
             Self::Tls(proto) => StepPlanOutput::Tls(proto.evaluate(state)?),
             Self::Tcp(proto) => StepPlanOutput::Tcp(proto.evaluate(state)?),
             Self::RawTcp(proto) => StepPlanOutput::RawTcp(proto.evaluate(state)?),
-            //Self::Quic(proto) => ProtocolOutput::Quic(proto.evaluate(state)?),
+            Self::Quic(proto) => StepPlanOutput::Quic(proto.evaluate(state)?),
+            Self::Ws(proto) => StepPlanOutput::Ws(proto.evaluate(state)?),
             //Self::Udp(proto) => ProtocolOutput::Udp(proto.evaluate(state)?),
-            proto => {
-                bail!("support for protocol {proto:?} is incomplete")
-            }
         })
     }
 }
@@ -1530,6 +2107,7 @@ impl Evaluate<StepPlanOutput> for Protocol {
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum ProtocolField {
     Graphql,
+    Grpc,
     Http,
     H1c,
     H1,
@@ -1544,6 +2122,7 @@ pub enum ProtocolField {
     Dtls,
     Quic,
     Udp,
+    Ws,
 }
 
 impl FromStr for ProtocolField {
@@ -1565,6 +2144,8 @@ impl FromStr for ProtocolField {
             "raw_h2" => Ok(Self::RawH2),
             "h3" => Ok(Self::H3),
             "graphql" => Ok(Self::Graphql),
+            "grpc" => Ok(Self::Grpc),
+            "ws" => Ok(Self::Ws),
             _ => bail!("invalid tls version string {}", s),
         }
     }
@@ -1766,6 +2347,16 @@ impl TryFrom<Literal> for i64 {
         }
     }
 }
+impl TryFrom<Literal> for f64 {
+    type Error = Error;
+    fn try_from(binding: Literal) -> Result<Self> {
+        match binding {
+            Literal::Float(x) => Ok(x),
+            Literal::Int(x) => Ok(x as f64),
+            _ => bail!("invalid type {binding:?} for float field"),
+        }
+    }
+}
 impl TryFrom<Literal> for usize {
     type Error = Error;
     fn try_from(binding: Literal) -> Result<Self> {
@@ -2040,6 +2631,16 @@ impl TryFrom<Literal> for Parallelism {
     }
 }
 
+impl TryFrom<Literal> for OnError {
+    type Error = Error;
+    fn try_from(binding: Literal) -> Result<Self> {
+        match binding {
+            Literal::String(x) => Ok(x.parse()?),
+            val => bail!("invalid value {val:?} for field run.on_error"),
+        }
+    }
+}
+
 impl TryFrom<Literal> for Url {
     type Error = Error;
     fn try_from(binding: Literal) -> Result<Self> {
@@ -2421,34 +3022,201 @@ where
             .iter()
             .into_iter()
             .map(O::into)
-            .map(|name| {
-                (
-                    name,
-                    state
-                        .get(name)
-                        .unwrap()
-                        .to_owned()
-                        .jobs
-                        .into_iter()
-                        .collect::<HashMap<_, _>>(),
-                )
-            })
+            .map(|name| (name, StepAccessor(state.get(name).unwrap())))
             .collect::<HashMap<_, _>>(),
     ).unwrap();
     ctx.add_variable("current", state.current()).unwrap();
     ctx.add_variable("for", state.run_for()).unwrap();
     ctx.add_variable("while", state.run_while()).unwrap();
     ctx.add_variable("count", state.run_count()).unwrap();
-    ctx.add_function("parse_url", cel_functions::url);
-    ctx.add_function(
-        "parse_form_urlencoded",
-        cel_functions::form_urlencoded_parts,
-    );
-    ctx.add_function("bytes", cel_functions::bytes);
-    ctx.add_function("char", cel_functions::char);
-    ctx.add_function("randomDuration", cel_functions::random_duration);
-    ctx.add_function("randomInt", cel_functions::random_int);
-    ctx.add_function("printf", cel_functions::printf);
+    ctx.add_variable("previous", state.previous()).unwrap();
+    add_functions(ctx);
+}
+
+/// Registers each CEL function and lists their names in [`CEL_FUNCTIONS`] from the same table, so
+/// completions can't drift from what plans can call.
+macro_rules! cel_functions {
+    ($($name:literal => $handler:path),* $(,)?) => {
+        /// Functions registered in every CEL context.
+        pub const CEL_FUNCTIONS: &[&str] = &[$($name),*];
+
+        fn add_functions(ctx: &mut cel_interpreter::Context) {
+            $(ctx.add_function($name, $handler);)*
+        }
+    };
+}
+
+cel_functions! {
+    "parse_url" => cel_functions::url,
+    "parse_form_urlencoded" => cel_functions::form_urlencoded_parts,
+    "bytes" => cel_functions::bytes,
+    "char" => cel_functions::char,
+    "randomDuration" => cel_functions::random_duration,
+    "randomInt" => cel_functions::random_int,
+    "uuid" => cel_functions::uuid,
+    "random_bytes" => cel_functions::random_bytes,
+    "random_hex" => cel_functions::random_hex,
+    "random_base64" => cel_functions::random_base64,
+    "lorem" => cel_functions::lorem,
+    "random_name" => cel_functions::random_name,
+    "random_email" => cel_functions::random_email,
+    "now" => cel_functions::now,
+    "format_time" => cel_functions::format_time,
+    "parse_time" => cel_functions::parse_time,
+    "add_duration" => cel_functions::add_duration,
+    "unix_time" => cel_functions::unix_time,
+    "from_unix" => cel_functions::from_unix,
+    "duration_within" => cel_functions::duration_within,
+    "baseline_timing" => cel_functions::baseline_timing,
+    "hmac_sha256" => cel_functions::hmac_sha256,
+    "sha1" => cel_functions::sha1,
+    "sha256" => cel_functions::sha256,
+    "md5" => cel_functions::md5,
+    "base64_encode" => cel_functions::base64_encode,
+    "base64_decode" => cel_functions::base64_decode,
+    "hex_encode" => cel_functions::hex_encode,
+    "hex_decode" => cel_functions::hex_decode,
+    "aes_gcm_encrypt" => cel_functions::aes_gcm_encrypt,
+    "aes_gcm_decrypt" => cel_functions::aes_gcm_decrypt,
+    "printf" => cel_functions::printf,
+    "counter_next" => cel_functions::counter_next,
+    "counter_add" => cel_functions::counter_add,
+    "counter_get" => cel_functions::counter_get,
+    "marker" => cel_functions::marker,
+    "secret" => cel_functions::secret,
+    "ntlm_negotiate" => cel_functions::ntlm_negotiate,
+    "ntlm_authenticate" => cel_functions::ntlm_authenticate,
+    "parse_json" => cel_functions::parse_json,
+    "parse_csp" => cel_functions::parse_csp,
+    "xpath" => cel_functions::xpath,
+    "css_select" => cel_functions::css_select,
+    "soap_envelope" => cel_functions::soap_envelope,
+    "wsse_username_token" => cel_functions::wsse_username_token,
+    "xxe_payloads" => cel_functions::xxe_payloads,
+    "xxe_exfil_dtd" => cel_functions::xxe_exfil_dtd,
+    "xml_billion_laughs" => cel_functions::xml_billion_laughs,
+    "multipart_form" => cel_functions::multipart_form,
+    "upload_payloads" => cel_functions::upload_payloads,
+    "upload_oversized" => cel_functions::upload_oversized,
+    "parse_protobuf" => cel_functions::parse_protobuf,
+    "forwarded_spoof_headers" => cel_functions::forwarded_spoof_headers,
+    "response_diff" => cel_functions::response_diff,
+    "crlf_payloads" => cel_functions::crlf_payloads,
+    "crlf_check" => cel_functions::crlf_check,
+}
+
+/// Exposes a step's output to CEL. Jobs are keyed by their iteration key, and the protocols,
+/// request, and response of the first job are also available directly so single job steps can be
+/// accessed like `steps.login.response.status_code`.
+struct StepAccessor<'a>(&'a crate::StepOutput);
+
+impl Serialize for StepAccessor<'_> {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeMap;
+
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("errors", &self.0.errors)?;
+        map.serialize_entry("reflections", &self.0.reflections)?;
+        map.serialize_entry("desync", &self.0.desync)?;
+        map.serialize_entry("usage", &self.0.usage)?;
+        if let Some(job) = self.0.jobs.values().next() {
+            macro_rules! protocols {
+                ($($field:ident),*) => {
+                    $(if let Some(value) = &job.$field {
+                        map.serialize_entry(stringify!($field), value)?;
+                    })*
+                };
+            }
+            protocols!(graphql, grpc, http, h1, h1c, h2, h2c, raw_h2, raw_h2c, tls, tcp, raw_tcp);
+            // Use the highest level protocol for the request and response shortcuts.
+            if let Some(p) = &job.graphql {
+                map.serialize_entry("request", &p.request)?;
+                map.serialize_entry("response", &p.response)?;
+            } else if let Some(p) = &job.http {
+                map.serialize_entry("request", &p.request)?;
+                map.serialize_entry("response", &p.response)?;
+            } else if let Some(p) = job.http1() {
+                map.serialize_entry("request", &p.request)?;
+                map.serialize_entry("response", &p.response)?;
+            } else if let Some(p) = job.h2.as_ref().or(job.h2c.as_ref()) {
+                map.serialize_entry("request", &p.request)?;
+                map.serialize_entry("response", &p.response)?;
+            }
+        }
+        if let Some(module) = &self.0.module {
+            map.serialize_entry(
+                "steps",
+                &module
+                    .0
+                    .steps
+                    .iter()
+                    .map(|(name, step)| (name, StepAccessor(&**step)))
+                    .collect::<HashMap<_, _>>(),
+            )?;
+        }
+        if let Some(crawl) = &self.0.crawl {
+            map.serialize_entry("crawl", crawl)?;
+        }
+        if let Some(discover) = &self.0.discover {
+            map.serialize_entry("discover", discover)?;
+        }
+        if let Some(forced_browse) = &self.0.forced_browse {
+            map.serialize_entry("forced_browse", forced_browse)?;
+        }
+        if let Some(vhost) = &self.0.vhost {
+            map.serialize_entry("vhost", vhost)?;
+        }
+        if let Some(takeover) = &self.0.takeover {
+            map.serialize_entry("takeover", takeover)?;
+        }
+        if let Some(banner) = &self.0.banner {
+            map.serialize_entry("banner", banner)?;
+        }
+        if let Some(session) = &self.0.session {
+            map.serialize_entry("session", session)?;
+        }
+        if let Some(command) = &self.0.command {
+            map.serialize_entry("command", command)?;
+        }
+        if let Some(script) = &self.0.script {
+            map.serialize_entry("script", script)?;
+        }
+        if let Some(grpc_reflect) = &self.0.grpc_reflect {
+            map.serialize_entry("grpc_reflect", grpc_reflect)?;
+        }
+        if let Some(range) = &self.0.range {
+            map.serialize_entry("range", range)?;
+        }
+        if let Some(conditional) = &self.0.conditional {
+            map.serialize_entry("conditional", conditional)?;
+        }
+        if let Some(h2_attack) = &self.0.h2_attack {
+            map.serialize_entry("h2_attack", h2_attack)?;
+        }
+        if let Some(alpn_matrix) = &self.0.alpn_matrix {
+            map.serialize_entry("alpn_matrix", alpn_matrix)?;
+        }
+        if let Some(keep_alive) = &self.0.keep_alive {
+            map.serialize_entry("keep_alive", keep_alive)?;
+        }
+        if let Some(tcp_burst) = &self.0.tcp_burst {
+            map.serialize_entry("tcp_burst", tcp_burst)?;
+        }
+        if let Some(dns) = &self.0.dns {
+            map.serialize_entry("dns", dns)?;
+        }
+        if let Some(detect) = &self.0.detect {
+            map.serialize_entry("detect", detect)?;
+        }
+        // Jobs go last so they take precedence over any field with the same key.
+        for (key, job) in &self.0.jobs {
+            map.serialize_entry(key, job)?;
+        }
+        map.end()
+    }
 }
 
 pub trait Evaluate<T> {
@@ -2498,8 +3266,11 @@ where
             .collect::<HashMap<cel_interpreter::objects::Key, cel_interpreter::Value>>(),
     );
     add_state_to_context(state, &mut context);
-    Ok(PlanData(program.execute(&context).map_err(|e| {
-        anyhow!("execute cel {cel}: {e}")
-    })?))
+    Ok(PlanData(
+        state
+            .cel_state()
+            .enter(|| program.execute(&context))
+            .map_err(|e| anyhow!("execute cel {cel}: {e}"))?,
+    ))
 }
 
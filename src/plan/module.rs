@@ -0,0 +1,49 @@
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use indexmap::IndexMap;
+
+use super::{Evaluate, PlanData, PlanValue};
+use crate::{bindings, Error, Result, State};
+use anyhow::anyhow;
+
+#[derive(Debug, Clone)]
+pub struct ModuleRequest {
+    pub path: PlanValue<String>,
+    pub params: IndexMap<String, PlanValue<PlanData, Infallible>>,
+}
+
+impl Evaluate<crate::ModulePlanOutput> for ModuleRequest {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> crate::Result<crate::ModulePlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        Ok(crate::ModulePlanOutput {
+            path: self.path.evaluate(state)?,
+            params: self
+                .params
+                .iter()
+                .map(|(k, v)| Ok((k.clone(), v.evaluate(state)?.0)))
+                .collect::<Result<_>>()?,
+        })
+    }
+}
+
+impl TryFrom<bindings::Module> for ModuleRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::Module) -> Result<Self> {
+        Ok(Self {
+            path: binding
+                .path
+                .map(PlanValue::try_from)
+                .ok_or_else(|| anyhow!("module.path is required"))??,
+            params: binding
+                .params
+                .into_iter()
+                .map(|(k, v)| Ok((k, PlanValue::try_from(v)?)))
+                .collect::<Result<_>>()?,
+        })
+    }
+}
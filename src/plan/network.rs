@@ -0,0 +1,53 @@
+use std::sync::Arc;
+
+use anyhow::bail;
+use cel_interpreter::Duration;
+
+use super::{Evaluate, PlanValue, TcpFaultRequest};
+use crate::{bindings, Error, Result, State};
+
+/// Named network conditions to emulate, so findings can note how a service behaves over slow or
+/// unreliable links.
+#[derive(Debug, Clone)]
+pub struct NetworkRequest {
+    pub bandwidth: PlanValue<Option<u64>>,
+    pub latency: PlanValue<Option<Duration>>,
+    pub jitter: PlanValue<Option<Duration>>,
+    pub loss: PlanValue<Option<f64>>,
+    pub fault: Option<TcpFaultRequest>,
+}
+
+impl NetworkRequest {
+    pub fn evaluate<'a, S, O, I>(&self, name: &str, state: &S) -> Result<crate::NetworkPlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        let loss = self.loss.evaluate(state)?;
+        if loss.is_some_and(|loss| !(0.0..=1.0).contains(&loss)) {
+            bail!("devil.network.{name}.loss must be between 0 and 1");
+        }
+        Ok(crate::NetworkPlanOutput {
+            name: name.to_owned(),
+            bandwidth: self.bandwidth.evaluate(state)?.filter(|b| *b > 0),
+            latency: self.latency.evaluate(state)?,
+            jitter: self.jitter.evaluate(state)?,
+            loss,
+            fault: self.fault.as_ref().map(|f| f.evaluate(state)).transpose()?,
+        })
+    }
+}
+
+impl TryFrom<bindings::Network> for NetworkRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::Network) -> Result<Self> {
+        Ok(Self {
+            bandwidth: binding.bandwidth.try_into()?,
+            latency: binding.latency.try_into()?,
+            jitter: binding.jitter.try_into()?,
+            loss: binding.loss.try_into()?,
+            fault: binding.fault.map(TcpFaultRequest::try_from).transpose()?,
+        })
+    }
+}
@@ -1,18 +1,41 @@
-use super::PlanValue;
-use crate::{bindings, Error, MaybeUtf8, Result, TlsVersion};
-use anyhow::anyhow;
+use std::sync::Arc;
+
+use super::{Evaluate, PlanValue};
+use crate::{bindings, Error, MaybeUtf8, Result, State, TlsVersion};
+use anyhow::{anyhow, bail};
+use itertools::Itertools;
 
 #[derive(Debug, Default, Clone)]
 pub struct QuicRequest {
     pub host: PlanValue<String>,
     pub port: PlanValue<u16>,
-    pub body: PlanValue<MaybeUtf8>,
+    pub alpn: Vec<PlanValue<MaybeUtf8>>,
     pub version: PlanValue<Option<TlsVersion>>,
 }
 
+impl Evaluate<crate::QuicPlanOutput> for QuicRequest {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> Result<crate::QuicPlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        Ok(crate::QuicPlanOutput {
+            host: self.host.evaluate(state)?,
+            port: self.port.evaluate(state)?,
+            alpn: self.alpn.evaluate(state)?,
+            tls_version: self.version.evaluate(state)?,
+        })
+    }
+}
+
 impl TryFrom<bindings::Quic> for QuicRequest {
     type Error = Error;
     fn try_from(binding: bindings::Quic) -> Result<Self> {
+        // Streams are only opened by the protocol above, so there's nowhere to send a body yet.
+        if binding.body.is_some() {
+            bail!("quic.body is not yet supported");
+        }
         Ok(Self {
             host: binding
                 .host
@@ -22,11 +45,12 @@ impl TryFrom<bindings::Quic> for QuicRequest {
                 .port
                 .map(PlanValue::try_from)
                 .ok_or_else(|| anyhow!("quic.port is required"))??,
-            body: binding
-                .body
+            alpn: binding
+                .alpn
+                .into_iter()
+                .flatten()
                 .map(PlanValue::try_from)
-                .transpose()?
-                .unwrap_or_default(),
+                .try_collect()?,
             version: binding.tls_version.try_into()?,
         })
     }
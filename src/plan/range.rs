@@ -0,0 +1,45 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use url::Url;
+
+use super::{Evaluate, PlanValue};
+use crate::{bindings, Error, Result, State};
+
+/// Sends unusual Range requests for a resource and checks the partial responses.
+#[derive(Debug, Clone)]
+pub struct RangeRequest {
+    pub url: PlanValue<Url>,
+    pub max_parts: PlanValue<u64>,
+}
+
+impl Evaluate<crate::RangePlanOutput> for RangeRequest {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> Result<crate::RangePlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        Ok(crate::RangePlanOutput {
+            url: self.url.evaluate(state)?,
+            max_parts: self.max_parts.evaluate(state)?,
+        })
+    }
+}
+
+impl TryFrom<bindings::Range> for RangeRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::Range) -> Result<Self> {
+        Ok(Self {
+            url: binding
+                .url
+                .map(PlanValue::try_from)
+                .ok_or_else(|| anyhow!("range.url is required"))??,
+            max_parts: binding
+                .max_parts
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(100)),
+        })
+    }
+}
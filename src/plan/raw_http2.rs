@@ -165,6 +165,7 @@ impl Http2Frame {
             payload,
             // HACK: currently we only specify values to send in plans.
             direction: Direction::Send,
+            time: None,
         })
     }
 }
@@ -0,0 +1,275 @@
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail};
+use regex::Regex;
+use url::{Position, Url};
+
+use crate::{bindings, Error, Result};
+
+/// Where a plan may send traffic. Anything matching a deny rule is blocked, and when allow has
+/// rules of a kind then traffic must also match one of them. When allow has address ranges,
+/// every address connected to must be in one, including those of hosts allowed by name.
+#[derive(Debug, Clone, Default)]
+pub struct Scope {
+    pub allow: Vec<ScopeRule>,
+    pub deny: Vec<ScopeRule>,
+}
+
+#[derive(Debug, Clone)]
+pub enum ScopeRule {
+    /// A host name, or with a leading *. any of its subdomains.
+    Host(String),
+    /// An address range in CIDR notation, or a single address.
+    Network { addr: IpAddr, prefix: u8 },
+    /// A URL prefix, where * matches any run of characters. The prefix must end where a host,
+    /// path segment or query does, and a * in the host doesn't match past it.
+    Url { pattern: String, regex: Regex },
+}
+
+impl Scope {
+    pub fn denies_host(&self, host: &str) -> bool {
+        self.deny.iter().any(|rule| rule.matches_host(host))
+    }
+
+    pub fn allows_host(&self, host: &str) -> bool {
+        self.allow.iter().any(|rule| rule.matches_host(host))
+    }
+
+    pub fn denies_ip(&self, ip: IpAddr) -> bool {
+        self.deny.iter().any(|rule| rule.matches_ip(ip))
+    }
+
+    pub fn allows_ip(&self, ip: IpAddr) -> bool {
+        self.allow.iter().any(|rule| rule.matches_ip(ip))
+    }
+
+    pub fn denies_url(&self, url: &Url) -> bool {
+        self.deny.iter().any(|rule| rule.matches_url(url))
+    }
+
+    pub fn allows_url(&self, url: &Url) -> bool {
+        self.allow.iter().any(|rule| rule.matches_url(url))
+    }
+
+    /// Whether only some hosts and addresses may be connected to.
+    pub fn limits_hosts(&self) -> bool {
+        self.allow
+            .iter()
+            .any(|rule| matches!(rule, ScopeRule::Host(_) | ScopeRule::Network { .. }))
+    }
+
+    /// Whether any address range is allowed, so hosts not allowed by name may still resolve to
+    /// an allowed address.
+    pub fn allows_networks(&self) -> bool {
+        self.allow
+            .iter()
+            .any(|rule| matches!(rule, ScopeRule::Network { .. }))
+    }
+
+    /// Whether only some URLs may be requested.
+    pub fn limits_urls(&self) -> bool {
+        self.allow
+            .iter()
+            .any(|rule| matches!(rule, ScopeRule::Url { .. }))
+    }
+}
+
+impl ScopeRule {
+    fn matches_host(&self, host: &str) -> bool {
+        let Self::Host(rule) = self else {
+            return false;
+        };
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        match rule.strip_prefix("*.") {
+            Some(parent) => host
+                .strip_suffix(parent)
+                .is_some_and(|sub| sub.len() > 1 && sub.ends_with('.')),
+            None => host == *rule,
+        }
+    }
+
+    fn matches_ip(&self, ip: IpAddr) -> bool {
+        let Self::Network { addr, prefix } = self else {
+            return false;
+        };
+        match (addr, ip) {
+            (IpAddr::V4(addr), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - u32::from(*prefix)).unwrap_or(0);
+                u32::from(*addr) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(addr), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - u32::from(*prefix)).unwrap_or(0);
+                u128::from(*addr) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+
+    fn matches_url(&self, url: &Url) -> bool {
+        let Self::Url { regex, .. } = self else {
+            return false;
+        };
+        // Leave out any user info, which could otherwise pose as the host.
+        regex.is_match(&format!(
+            "{}://{}{}",
+            url.scheme(),
+            &url[Position::BeforeHost..Position::AfterPort],
+            &url[Position::BeforePath..],
+        ))
+    }
+}
+
+impl FromStr for ScopeRule {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some((scheme, rest)) = s.split_once("://") {
+            let wildcard = |part: &str, any: &str| {
+                part.split('*')
+                    .map(regex::escape)
+                    .collect::<Vec<_>>()
+                    .join(any)
+            };
+            let end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+            let (authority, path) = rest.split_at(end);
+            // Stop https://api.example.com from matching api.example.com.evil.net and /v1 from
+            // matching /v1-admin.
+            let boundary = if path.ends_with(['/', '?', '#', '&', '=', '*']) {
+                ""
+            } else {
+                "(?:[/?#]|$)"
+            };
+            let pattern = format!(
+                "^{}://{}{}{boundary}",
+                wildcard(&scheme.to_ascii_lowercase(), "[^:/]*"),
+                wildcard(&authority.to_ascii_lowercase(), "[^/?#@]*"),
+                wildcard(path, ".*"),
+            );
+            return Ok(Self::Url {
+                pattern: s.to_owned(),
+                regex: Regex::new(&pattern)?,
+            });
+        }
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let Ok(addr) = addr
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+        else {
+            if prefix.is_some() {
+                bail!("invalid address range {s:?}");
+            }
+            return Ok(Self::Host(s.trim_end_matches('.').to_ascii_lowercase()));
+        };
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = prefix
+            .map(|p| p.parse::<u8>().ok().filter(|p| *p <= max))
+            .unwrap_or(Some(max))
+            .ok_or_else(|| anyhow!("invalid prefix length in {s:?}"))?;
+        Ok(Self::Network { addr, prefix })
+    }
+}
+
+impl TryFrom<bindings::Scope> for Scope {
+    type Error = Error;
+    fn try_from(binding: bindings::Scope) -> Result<Self> {
+        let parse = |rules: Vec<String>, field: &str| {
+            rules
+                .iter()
+                .map(|rule| rule.parse().map_err(|e| anyhow!("{field}: {e}")))
+                .collect::<Result<Vec<_>>>()
+        };
+        Ok(Self {
+            allow: parse(binding.allow, "allow")?,
+            deny: parse(binding.deny, "deny")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scope(allow: &[&str], deny: &[&str]) -> Scope {
+        let parse = |rules: &[&str]| rules.iter().map(|r| r.parse().unwrap()).collect();
+        Scope {
+            allow: parse(allow),
+            deny: parse(deny),
+        }
+    }
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_host_rules() {
+        let scope = scope(
+            &["api.example.com", "*.example.net"],
+            &["admin.example.net"],
+        );
+        assert!(scope.allows_host("api.example.com"));
+        assert!(scope.allows_host("API.example.com."));
+        assert!(!scope.allows_host("api.example.com.evil.net"));
+        assert!(!scope.allows_host("xapi.example.com"));
+        assert!(scope.allows_host("a.b.example.net"));
+        assert!(!scope.allows_host("example.net"));
+        assert!(!scope.allows_host("evilexample.net"));
+        assert!(scope.denies_host("admin.example.net"));
+        assert!(scope.limits_hosts());
+        assert!(!scope.allows_networks());
+    }
+
+    #[test]
+    fn test_network_rules() {
+        let scope = scope(&["10.1.0.0/16", "2001:db8::/32", "192.0.2.7"], &[]);
+        assert!(scope.allows_ip("10.1.255.1".parse().unwrap()));
+        assert!(!scope.allows_ip("10.2.0.1".parse().unwrap()));
+        assert!(scope.allows_ip("2001:db8::1".parse().unwrap()));
+        assert!(!scope.allows_ip("2001:db9::1".parse().unwrap()));
+        assert!(scope.allows_ip("192.0.2.7".parse().unwrap()));
+        assert!(!scope.allows_ip("192.0.2.8".parse().unwrap()));
+        assert!(scope.allows_networks());
+        assert!("10.0.0.0/33".parse::<ScopeRule>().is_err());
+        assert!("example.com/8".parse::<ScopeRule>().is_err());
+    }
+
+    #[test]
+    fn test_url_rules_end_at_boundaries() {
+        let scope = scope(&["https://api.example.com/v1"], &[]);
+        assert!(scope.allows_url(&url("https://api.example.com/v1")));
+        assert!(scope.allows_url(&url("https://api.example.com/v1/users")));
+        assert!(scope.allows_url(&url("https://api.example.com/v1?page=2")));
+        assert!(!scope.allows_url(&url("https://api.example.com/v1-admin")));
+        assert!(!scope.allows_url(&url("https://api.example.com/v10")));
+        assert!(!scope.allows_url(&url("http://api.example.com/v1")));
+
+        let scope = self::scope(&["https://api.example.com"], &[]);
+        assert!(scope.allows_url(&url("https://api.example.com")));
+        assert!(scope.allows_url(&url("https://API.example.com/anything")));
+        assert!(!scope.allows_url(&url("https://api.example.com.attacker.net/")));
+        assert!(!scope.allows_url(&url("https://api.example.com:8443/")));
+        assert!(!scope.allows_url(&url("https://api.example.com@attacker.net/")));
+    }
+
+    #[test]
+    fn test_url_rule_wildcards() {
+        let scope = scope(&["https://*.example.com/api/*/read"], &[]);
+        assert!(scope.allows_url(&url("https://a.b.example.com/api/v2/read")));
+        assert!(scope.allows_url(&url("https://a.example.com/api/v2/x/read?q=1")));
+        assert!(!scope.allows_url(&url("https://attacker.net/.example.com/api/v2/read")));
+        assert!(!scope.allows_url(&url("https://attacker.net?.example.com/api/v2/read")));
+        assert!(!scope.allows_url(&url("https://a.example.com/api/v2/readme")));
+
+        let scope = self::scope(
+            &["https://api.example.com/"],
+            &["https://api.example.com/admin"],
+        );
+        assert!(scope.allows_url(&url("https://api.example.com/anything")));
+        assert!(scope.denies_url(&url("https://api.example.com/admin/users")));
+        assert!(!scope.denies_url(&url("https://api.example.com/administrators")));
+    }
+}
@@ -0,0 +1,49 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail};
+
+use super::{Evaluate, PlanValue};
+use crate::{bindings, Error, Result, State};
+
+/// Runs an embedded script with read access to earlier steps and write access to locals.
+#[derive(Debug, Clone)]
+pub struct ScriptRequest {
+    pub source: PlanValue<String>,
+    pub max_operations: PlanValue<u64>,
+}
+
+impl Evaluate<crate::ScriptPlanOutput> for ScriptRequest {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> Result<crate::ScriptPlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        let max_operations = self.max_operations.evaluate(state)?;
+        // The engine treats a limit of 0 as no limit at all.
+        if max_operations == 0 {
+            bail!("script.max_operations must be at least 1");
+        }
+        Ok(crate::ScriptPlanOutput {
+            source: self.source.evaluate(state)?,
+            max_operations,
+        })
+    }
+}
+
+impl TryFrom<bindings::Script> for ScriptRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::Script) -> Result<Self> {
+        Ok(Self {
+            source: binding
+                .source
+                .map(PlanValue::try_from)
+                .ok_or_else(|| anyhow!("script.source is required"))??,
+            max_operations: binding
+                .max_operations
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(1_000_000)),
+        })
+    }
+}
@@ -0,0 +1,180 @@
+use std::convert::Infallible;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail};
+use cel_interpreter::Duration;
+use devil_derive::BigQuerySchema;
+use indexmap::IndexMap;
+use serde::Serialize;
+
+use super::{Evaluate, PlanData, PlanValue, TryFromPlanData};
+use crate::bindings::Literal;
+use crate::{bindings, Error, Result, State};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, BigQuerySchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ThinkDistribution {
+    /// Always wait the mean.
+    #[default]
+    Constant,
+    /// Wait anywhere between min and max with equal likelihood.
+    Uniform,
+    /// Wait min plus an exponentially distributed time with the given mean, capped at max if set.
+    Exponential,
+}
+
+impl FromStr for ThinkDistribution {
+    type Err = Error;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "constant" => Ok(Self::Constant),
+            "uniform" => Ok(Self::Uniform),
+            "exponential" => Ok(Self::Exponential),
+            val => bail!("unrecognized session.think_time.distribution string {val}"),
+        }
+    }
+}
+
+impl TryFromPlanData for ThinkDistribution {
+    type Error = Error;
+    fn try_from_plan_data(value: PlanData) -> std::result::Result<Self, Self::Error> {
+        match value.0 {
+            cel_interpreter::Value::String(s) => s.parse(),
+            val => bail!("unsupported value {val:?} for field session.think_time.distribution"),
+        }
+    }
+}
+
+impl TryFrom<bindings::Value> for PlanValue<ThinkDistribution> {
+    type Error = Error;
+    fn try_from(binding: bindings::Value) -> Result<Self> {
+        match binding {
+            bindings::Value::ExpressionCel { cel, vars } => Ok(Self::Dynamic {
+                cel,
+                vars: vars.unwrap_or_default().into_iter().collect(),
+            }),
+            bindings::Value::Literal(Literal::String(x)) => Ok(Self::Literal(x.parse()?)),
+            val => bail!("invalid value {val:?} for field session.think_time.distribution"),
+        }
+    }
+}
+
+/// Runs a plan as a journey for each of many virtual users, pausing between steps like a person
+/// would.
+#[derive(Debug, Clone)]
+pub struct SessionRequest {
+    pub path: PlanValue<String>,
+    pub params: IndexMap<String, PlanValue<PlanData, Infallible>>,
+    pub users: PlanValue<u64>,
+    pub iterations: PlanValue<u64>,
+    pub ramp_up: PlanValue<Option<Duration>>,
+    pub think_time: Option<ThinkTimeRequest>,
+}
+
+impl Evaluate<crate::SessionPlanOutput> for SessionRequest {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> Result<crate::SessionPlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        Ok(crate::SessionPlanOutput {
+            path: self.path.evaluate(state)?,
+            params: self
+                .params
+                .iter()
+                .map(|(k, v)| Ok((k.clone(), v.evaluate(state)?.0)))
+                .collect::<Result<_>>()?,
+            users: self.users.evaluate(state)?,
+            iterations: self.iterations.evaluate(state)?,
+            ramp_up: self.ramp_up.evaluate(state)?,
+            think_time: self
+                .think_time
+                .as_ref()
+                .map(|think| think.evaluate(state))
+                .transpose()?,
+        })
+    }
+}
+
+impl TryFrom<bindings::Session> for SessionRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::Session) -> Result<Self> {
+        Ok(Self {
+            path: binding
+                .path
+                .map(PlanValue::try_from)
+                .ok_or_else(|| anyhow!("session.path is required"))??,
+            params: binding
+                .params
+                .into_iter()
+                .map(|(k, v)| Ok((k, PlanValue::try_from(v)?)))
+                .collect::<Result<_>>()?,
+            users: binding
+                .users
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(1)),
+            iterations: binding
+                .iterations
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(1)),
+            ramp_up: binding.ramp_up.try_into()?,
+            think_time: binding
+                .think_time
+                .map(ThinkTimeRequest::try_from)
+                .transpose()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ThinkTimeRequest {
+    pub distribution: PlanValue<ThinkDistribution>,
+    pub min: PlanValue<Option<Duration>>,
+    pub max: PlanValue<Option<Duration>>,
+    pub mean: PlanValue<Option<Duration>>,
+}
+
+impl Evaluate<crate::ThinkTimePlanOutput> for ThinkTimeRequest {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> Result<crate::ThinkTimePlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        let out = crate::ThinkTimePlanOutput {
+            distribution: self.distribution.evaluate(state)?,
+            min: self.min.evaluate(state)?,
+            max: self.max.evaluate(state)?,
+            mean: self.mean.evaluate(state)?,
+        };
+        match out.distribution {
+            ThinkDistribution::Constant | ThinkDistribution::Exponential if out.mean.is_none() => {
+                bail!("session.think_time.mean is required for this distribution")
+            }
+            ThinkDistribution::Uniform if out.max.is_none() => {
+                bail!("session.think_time.max is required for uniform distribution")
+            }
+            _ => Ok(out),
+        }
+    }
+}
+
+impl TryFrom<bindings::ThinkTime> for ThinkTimeRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::ThinkTime) -> Result<Self> {
+        Ok(Self {
+            distribution: binding
+                .distribution
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or_default(),
+            min: binding.min.try_into()?,
+            max: binding.max.try_into()?,
+            mean: binding.mean.try_into()?,
+        })
+    }
+}
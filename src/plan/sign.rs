@@ -0,0 +1,116 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail};
+use devil_derive::BigQuerySchema;
+use serde::Serialize;
+
+use super::{Evaluate, PlanData, PlanValue, TryFromPlanData};
+use crate::bindings::Literal;
+use crate::{bindings, Error, Result, Secret, State};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, BigQuerySchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SignKind {
+    AwsSigv4,
+    Hmac,
+}
+
+impl FromStr for SignKind {
+    type Err = Error;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "aws_sigv4" => Ok(Self::AwsSigv4),
+            "hmac" => Ok(Self::Hmac),
+            val => bail!("unrecognized sign.kind string {val}"),
+        }
+    }
+}
+
+impl TryFromPlanData for SignKind {
+    type Error = Error;
+    fn try_from_plan_data(value: PlanData) -> std::result::Result<Self, Self::Error> {
+        match value.0 {
+            cel_interpreter::Value::String(s) => s.parse(),
+            val => bail!("unsupported value {val:?} for field sign.kind"),
+        }
+    }
+}
+
+impl TryFrom<bindings::Value> for PlanValue<SignKind> {
+    type Error = Error;
+    fn try_from(binding: bindings::Value) -> Result<Self> {
+        match binding {
+            bindings::Value::ExpressionCel { cel, vars } => Ok(Self::Dynamic {
+                cel,
+                vars: vars.unwrap_or_default().into_iter().collect(),
+            }),
+            bindings::Value::Literal(Literal::String(x)) => Ok(Self::Literal(x.parse()?)),
+            val => bail!("invalid value {val:?} for field sign.kind"),
+        }
+    }
+}
+
+/// Computes a signature over the request after templating and adds it as a header.
+#[derive(Debug, Clone)]
+pub struct SignRequest {
+    pub kind: PlanValue<SignKind>,
+    pub access_key: PlanValue<Option<String>>,
+    pub secret_key: PlanValue<Option<String>>,
+    pub session_token: PlanValue<Option<String>>,
+    pub region: PlanValue<Option<String>>,
+    pub service: PlanValue<Option<String>>,
+    pub algorithm: PlanValue<Option<String>>,
+    pub components: PlanValue<Option<String>>,
+    pub separator: PlanValue<Option<String>>,
+    pub encoding: PlanValue<Option<String>>,
+    pub header: PlanValue<Option<String>>,
+    pub prefix: PlanValue<Option<String>>,
+}
+
+impl Evaluate<crate::SignPlanOutput> for SignRequest {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> Result<crate::SignPlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        Ok(crate::SignPlanOutput {
+            kind: self.kind.evaluate(state)?,
+            access_key: self.access_key.evaluate(state)?,
+            secret_key: self.secret_key.evaluate(state)?.map(Secret),
+            session_token: self.session_token.evaluate(state)?.map(Secret),
+            region: self.region.evaluate(state)?,
+            service: self.service.evaluate(state)?,
+            algorithm: self.algorithm.evaluate(state)?,
+            components: self.components.evaluate(state)?,
+            separator: self.separator.evaluate(state)?,
+            encoding: self.encoding.evaluate(state)?,
+            header: self.header.evaluate(state)?,
+            prefix: self.prefix.evaluate(state)?,
+        })
+    }
+}
+
+impl TryFrom<bindings::Sign> for SignRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::Sign) -> Result<Self> {
+        Ok(Self {
+            kind: binding
+                .kind
+                .map(PlanValue::try_from)
+                .ok_or_else(|| anyhow!("sign.kind is required"))??,
+            access_key: binding.access_key.try_into()?,
+            secret_key: binding.secret_key.try_into()?,
+            session_token: binding.session_token.try_into()?,
+            region: binding.region.try_into()?,
+            service: binding.service.try_into()?,
+            algorithm: binding.algorithm.try_into()?,
+            components: binding.components.try_into()?,
+            separator: binding.separator.try_into()?,
+            encoding: binding.encoding.try_into()?,
+            header: binding.header.try_into()?,
+            prefix: binding.prefix.try_into()?,
+        })
+    }
+}
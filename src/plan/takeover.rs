@@ -0,0 +1,48 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+
+use super::{Evaluate, IterablePlanValue, PlanValue, TryFromPlanData};
+use crate::{bindings, Error, Result, State};
+
+/// Resolves each host's CNAME chain and checks whether the final target is an unclaimed resource
+/// on a known hosting service.
+#[derive(Debug, Clone)]
+pub struct TakeoverRequest {
+    pub hosts: IterablePlanValue,
+    pub resolver: PlanValue<Option<String>>,
+}
+
+impl Evaluate<crate::TakeoverPlanOutput> for TakeoverRequest {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> Result<crate::TakeoverPlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        Ok(crate::TakeoverPlanOutput {
+            hosts: self
+                .hosts
+                .evaluate(state)?
+                .into_iter()
+                .map(|(_, v)| {
+                    String::try_from_plan_data(v).map_err(|e| anyhow!("takeover.hosts: {e}"))
+                })
+                .collect::<Result<_>>()?,
+            resolver: self.resolver.evaluate(state)?,
+        })
+    }
+}
+
+impl TryFrom<bindings::Takeover> for TakeoverRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::Takeover) -> Result<Self> {
+        Ok(Self {
+            hosts: binding
+                .hosts
+                .map(IterablePlanValue::try_from)
+                .ok_or_else(|| anyhow!("takeover.hosts is required"))??,
+            resolver: binding.resolver.try_into()?,
+        })
+    }
+}
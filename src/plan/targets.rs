@@ -0,0 +1,53 @@
+use anyhow::{anyhow, bail};
+use url::Url;
+
+use crate::{bindings, Error, Result};
+
+/// Base URLs to run the whole plan against. Each target gets its own run, with locals.target set
+/// to its URL.
+#[derive(Debug, Clone)]
+pub enum Targets {
+    List(Vec<Url>),
+    /// A file with one URL per line. Blank lines and lines starting with # are skipped.
+    File(String),
+}
+
+impl Targets {
+    /// The target URLs, reading them from the file if needed.
+    pub fn load(&self) -> Result<Vec<Url>> {
+        let path = match self {
+            Self::List(urls) => return Ok(urls.clone()),
+            Self::File(path) => path,
+        };
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("read devil.targets.file {path}: {e}"))?;
+        let urls = text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .map(|(i, line)| {
+                Url::parse(line)
+                    .map_err(|e| anyhow!("{path} line {}: invalid target {line:?}: {e}", i + 1))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if urls.is_empty() {
+            bail!("devil.targets.file {path} has no targets");
+        }
+        Ok(urls)
+    }
+}
+
+impl TryFrom<bindings::Targets> for Targets {
+    type Error = Error;
+    fn try_from(binding: bindings::Targets) -> Result<Self> {
+        Ok(match binding {
+            bindings::Targets::List(list) => Self::List(
+                list.iter()
+                    .map(|url| Url::parse(url).map_err(|e| anyhow!("invalid target {url:?}: {e}")))
+                    .collect::<Result<_>>()?,
+            ),
+            bindings::Targets::File { file, .. } => Self::File(file),
+        })
+    }
+}
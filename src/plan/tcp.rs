@@ -1,14 +1,22 @@
+use std::str::FromStr;
 use std::sync::Arc;
 
-use super::{Evaluate, PlanValue};
-use crate::{bindings, Error, MaybeUtf8, Result, State};
-use anyhow::anyhow;
+use super::{Evaluate, ExpectRequest, PlanData, PlanValue, TryFromPlanData};
+use crate::bindings::Literal;
+use crate::{bindings, Error, MaybeUtf8, Result, Secret, State};
+use anyhow::{anyhow, bail};
+use devil_derive::BigQuerySchema;
+use serde::Serialize;
 
 #[derive(Debug, Clone)]
 pub struct TcpRequest {
     pub host: PlanValue<String>,
     pub port: PlanValue<u16>,
     pub body: PlanValue<MaybeUtf8>,
+    pub proxies: Vec<TcpProxyRequest>,
+    pub proxy_protocol: Option<TcpProxyProtocolRequest>,
+    pub fault: Option<TcpFaultRequest>,
+    pub expect: Vec<ExpectRequest>,
     //pub close: TcpClose,
 }
 
@@ -23,6 +31,18 @@ impl Evaluate<crate::TcpPlanOutput> for TcpRequest {
             host: self.host.evaluate(state)?,
             port: self.port.evaluate(state)?,
             body: self.body.evaluate(state)?.into(),
+            proxies: self
+                .proxies
+                .iter()
+                .map(|p| p.evaluate(state))
+                .collect::<Result<_>>()?,
+            proxy_protocol: self
+                .proxy_protocol
+                .as_ref()
+                .map(|p| p.evaluate(state))
+                .transpose()?,
+            fault: self.fault.as_ref().map(|f| f.evaluate(state)).transpose()?,
+            expect: self.expect.evaluate(state)?,
             //close: self.close.evaluate(state)?.into(),
         })
     }
@@ -45,11 +65,226 @@ impl TryFrom<bindings::Tcp> for TcpRequest {
                 .map(PlanValue::try_from)
                 .transpose()?
                 .unwrap_or_default(),
+            proxies: binding
+                .proxies
+                .unwrap_or_default()
+                .into_iter()
+                .map(TcpProxyRequest::try_from)
+                .collect::<Result<_>>()?,
+            proxy_protocol: binding
+                .proxy_protocol
+                .map(TcpProxyProtocolRequest::try_from)
+                .transpose()?,
+            fault: binding.fault.map(TcpFaultRequest::try_from).transpose()?,
+            expect: binding
+                .expect
+                .into_iter()
+                .flatten()
+                .map(ExpectRequest::try_from)
+                .collect::<Result<_>>()?,
             //close: binding.close.unwrap_or_default().try_into()?,
         })
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, BigQuerySchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProxyKind {
+    Http,
+    Socks5,
+}
+
+impl FromStr for ProxyKind {
+    type Err = Error;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "http" => Ok(Self::Http),
+            "socks5" => Ok(Self::Socks5),
+            val => bail!("unrecognized tcp.proxies.kind string {val}"),
+        }
+    }
+}
+
+impl TryFromPlanData for ProxyKind {
+    type Error = Error;
+    fn try_from_plan_data(value: PlanData) -> std::result::Result<Self, Self::Error> {
+        match value.0 {
+            cel_interpreter::Value::String(s) => s.parse(),
+            val => bail!("unsupported value {val:?} for field tcp.proxies.kind"),
+        }
+    }
+}
+
+impl TryFrom<bindings::Value> for PlanValue<ProxyKind> {
+    type Error = Error;
+    fn try_from(binding: bindings::Value) -> Result<Self> {
+        match binding {
+            bindings::Value::ExpressionCel { cel, vars } => Ok(Self::Dynamic {
+                cel,
+                vars: vars.unwrap_or_default().into_iter().collect(),
+            }),
+            bindings::Value::Literal(Literal::String(x)) => Ok(Self::Literal(x.parse()?)),
+            val => bail!("invalid value {val:?} for field tcp.proxies.kind"),
+        }
+    }
+}
+
+/// A proxy hop to tunnel a tcp connection through.
+#[derive(Debug, Clone)]
+pub struct TcpProxyRequest {
+    pub kind: PlanValue<ProxyKind>,
+    pub host: PlanValue<String>,
+    pub port: PlanValue<u16>,
+    pub username: PlanValue<Option<String>>,
+    pub password: PlanValue<Option<String>>,
+}
+
+impl Evaluate<crate::TcpProxyPlanOutput> for TcpProxyRequest {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> crate::Result<crate::TcpProxyPlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        Ok(crate::TcpProxyPlanOutput {
+            kind: self.kind.evaluate(state)?,
+            host: self.host.evaluate(state)?,
+            port: self.port.evaluate(state)?,
+            username: self.username.evaluate(state)?,
+            password: self.password.evaluate(state)?.map(Secret),
+        })
+    }
+}
+
+impl TryFrom<bindings::TcpProxy> for TcpProxyRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::TcpProxy) -> Result<Self> {
+        Ok(Self {
+            kind: binding
+                .kind
+                .map(PlanValue::try_from)
+                .ok_or_else(|| anyhow!("tcp.proxies.kind is required"))??,
+            host: binding
+                .host
+                .map(PlanValue::try_from)
+                .ok_or_else(|| anyhow!("tcp.proxies.host is required"))??,
+            port: binding
+                .port
+                .map(PlanValue::try_from)
+                .ok_or_else(|| anyhow!("tcp.proxies.port is required"))??,
+            username: binding.username.try_into()?,
+            password: binding.password.try_into()?,
+        })
+    }
+}
+
+/// A HAProxy PROXY protocol header to send before any other data on the connection.
+#[derive(Debug, Clone)]
+pub struct TcpProxyProtocolRequest {
+    pub version: PlanValue<u8>,
+    pub source_host: PlanValue<String>,
+    pub source_port: PlanValue<u16>,
+    pub dest_host: PlanValue<Option<String>>,
+    pub dest_port: PlanValue<Option<u16>>,
+}
+
+impl Evaluate<crate::TcpProxyProtocolPlanOutput> for TcpProxyProtocolRequest {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> crate::Result<crate::TcpProxyProtocolPlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        let version = self.version.evaluate(state)?;
+        if !matches!(version, 1 | 2) {
+            bail!("tcp.proxy_protocol.version must be 1 or 2, got {version}");
+        }
+        Ok(crate::TcpProxyProtocolPlanOutput {
+            version,
+            source_host: self.source_host.evaluate(state)?,
+            source_port: self.source_port.evaluate(state)?,
+            dest_host: self.dest_host.evaluate(state)?,
+            dest_port: self.dest_port.evaluate(state)?,
+        })
+    }
+}
+
+impl TryFrom<bindings::TcpProxyProtocol> for TcpProxyProtocolRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::TcpProxyProtocol) -> Result<Self> {
+        Ok(Self {
+            version: binding
+                .version
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(1)),
+            source_host: binding
+                .source_host
+                .map(PlanValue::try_from)
+                .ok_or_else(|| anyhow!("tcp.proxy_protocol.source_host is required"))??,
+            source_port: binding
+                .source_port
+                .map(PlanValue::try_from)
+                .ok_or_else(|| anyhow!("tcp.proxy_protocol.source_port is required"))??,
+            dest_host: binding.dest_host.try_into()?,
+            dest_port: binding.dest_port.try_into()?,
+        })
+    }
+}
+
+/// Faults to inject into the connection after any proxy handshakes, so error handling can be
+/// tested deterministically.
+#[derive(Debug, Clone)]
+pub struct TcpFaultRequest {
+    pub drop_after: PlanValue<Option<u64>>,
+    pub corrupt_offset: PlanValue<Option<u64>>,
+    pub corrupt_length: PlanValue<u64>,
+    pub duplicate_writes: PlanValue<bool>,
+    pub delay_acks: PlanValue<bool>,
+}
+
+impl Evaluate<crate::TcpFaultPlanOutput> for TcpFaultRequest {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> crate::Result<crate::TcpFaultPlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        Ok(crate::TcpFaultPlanOutput {
+            drop_after: self.drop_after.evaluate(state)?,
+            corrupt_offset: self.corrupt_offset.evaluate(state)?,
+            corrupt_length: self.corrupt_length.evaluate(state)?,
+            duplicate_writes: self.duplicate_writes.evaluate(state)?,
+            delay_acks: self.delay_acks.evaluate(state)?,
+        })
+    }
+}
+
+impl TryFrom<bindings::TcpFault> for TcpFaultRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::TcpFault) -> Result<Self> {
+        Ok(Self {
+            drop_after: binding.drop_after.try_into()?,
+            corrupt_offset: binding.corrupt_offset.try_into()?,
+            corrupt_length: binding
+                .corrupt_length
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(1)),
+            duplicate_writes: binding
+                .duplicate_writes
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(false)),
+            delay_acks: binding
+                .delay_acks
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(false)),
+        })
+    }
+}
+
 //#[derive(Debug, Clone)]
 //pub struct TcpClose {
 //    timeout: Option<PlanValue<Duration>>,
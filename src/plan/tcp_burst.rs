@@ -0,0 +1,59 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use cel_interpreter::Duration;
+use chrono::TimeDelta;
+
+use super::{Evaluate, PlanValue};
+use crate::{bindings, Error, Result, State};
+
+/// Opens many TCP connections at once and records how long each took to establish.
+#[derive(Debug, Clone)]
+pub struct TcpBurstRequest {
+    pub host: PlanValue<String>,
+    pub port: PlanValue<u16>,
+    pub connections: PlanValue<u64>,
+    pub timeout: PlanValue<Duration>,
+}
+
+impl Evaluate<crate::TcpBurstPlanOutput> for TcpBurstRequest {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> Result<crate::TcpBurstPlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        Ok(crate::TcpBurstPlanOutput {
+            host: self.host.evaluate(state)?,
+            port: self.port.evaluate(state)?,
+            connections: self.connections.evaluate(state)?,
+            timeout: self.timeout.evaluate(state)?,
+        })
+    }
+}
+
+impl TryFrom<bindings::TcpBurst> for TcpBurstRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::TcpBurst) -> Result<Self> {
+        Ok(Self {
+            host: binding
+                .host
+                .map(PlanValue::try_from)
+                .ok_or_else(|| anyhow!("tcp_burst.host is required"))??,
+            port: binding
+                .port
+                .map(PlanValue::try_from)
+                .ok_or_else(|| anyhow!("tcp_burst.port is required"))??,
+            connections: binding
+                .connections
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(100)),
+            timeout: binding
+                .timeout
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(Duration(TimeDelta::seconds(5)))),
+        })
+    }
+}
@@ -1,6 +1,6 @@
-use super::{Evaluate, PlanData, PlanValue, TryFromPlanData};
+use super::{Evaluate, ExpectRequest, PlanData, PlanValue, TryFromPlanData};
 use crate::bindings::Literal;
-use crate::{bindings, Error, MaybeUtf8, ParsedTlsVersion, Result, State, TlsVersion};
+use crate::{bindings, Error, MaybeUtf8, ParsedTlsVersion, Result, Secret, State, TlsVersion};
 use anyhow::{anyhow, bail};
 use itertools::Itertools;
 use std::sync::Arc;
@@ -20,8 +20,20 @@ impl TryFromPlanData for TlsVersion {
 pub struct TlsRequest {
     pub host: PlanValue<String>,
     pub port: PlanValue<u16>,
+    pub server_name: PlanValue<Option<String>>,
     pub alpn: Vec<PlanValue<MaybeUtf8>>,
     pub body: PlanValue<MaybeUtf8>,
+    pub min_version: PlanValue<Option<TlsVersion>>,
+    pub max_version: PlanValue<Option<TlsVersion>>,
+    pub cipher_suites: Vec<PlanValue<String>>,
+    pub pin: Option<TlsPinRequest>,
+    pub client: Option<TlsClientRequest>,
+    pub ca: PlanValue<Option<String>>,
+    pub ca_file: PlanValue<Option<String>>,
+    pub insecure: PlanValue<bool>,
+    pub resume: PlanValue<Option<String>>,
+    pub session_tickets: PlanValue<bool>,
+    pub expect: Vec<ExpectRequest>,
 }
 
 impl Evaluate<crate::TlsPlanOutput> for TlsRequest {
@@ -34,8 +46,28 @@ impl Evaluate<crate::TlsPlanOutput> for TlsRequest {
         Ok(crate::TlsPlanOutput {
             host: self.host.evaluate(state)?,
             port: self.port.evaluate(state)?,
+            server_name: self.server_name.evaluate(state)?,
             alpn: self.alpn.evaluate(state)?,
             body: self.body.evaluate(state)?.into(),
+            min_version: self.min_version.evaluate(state)?,
+            max_version: self.max_version.evaluate(state)?,
+            cipher_suites: self.cipher_suites.evaluate(state)?,
+            pin: self
+                .pin
+                .as_ref()
+                .map(|pin| pin.evaluate(state))
+                .transpose()?,
+            client: self
+                .client
+                .as_ref()
+                .map(|client| client.evaluate(state))
+                .transpose()?,
+            ca: self.ca.evaluate(state)?,
+            ca_file: self.ca_file.evaluate(state)?,
+            insecure: self.insecure.evaluate(state)?,
+            resume: self.resume.evaluate(state)?,
+            session_tickets: self.session_tickets.evaluate(state)?,
+            expect: self.expect.evaluate(state)?,
         })
     }
 }
@@ -52,6 +84,7 @@ impl TryFrom<bindings::Tls> for TlsRequest {
                 .port
                 .map(PlanValue::try_from)
                 .ok_or_else(|| anyhow!("tls.port is required"))??,
+            server_name: binding.server_name.try_into()?,
             alpn: binding
                 .alpn
                 .into_iter()
@@ -63,6 +96,125 @@ impl TryFrom<bindings::Tls> for TlsRequest {
                 .map(PlanValue::try_from)
                 .transpose()?
                 .unwrap_or_default(),
+            min_version: binding.min_version.try_into()?,
+            max_version: binding.max_version.try_into()?,
+            cipher_suites: binding
+                .cipher_suites
+                .into_iter()
+                .flatten()
+                .map(PlanValue::try_from)
+                .try_collect()?,
+            pin: binding.pin.map(TlsPinRequest::try_from).transpose()?,
+            client: binding.client.map(TlsClientRequest::try_from).transpose()?,
+            ca: binding.ca.try_into()?,
+            ca_file: binding.ca_file.try_into()?,
+            insecure: binding
+                .insecure
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(false)),
+            resume: binding.resume.try_into()?,
+            session_tickets: binding
+                .session_tickets
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(true)),
+            expect: binding
+                .expect
+                .into_iter()
+                .flatten()
+                .map(ExpectRequest::try_from)
+                .try_collect()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TlsPinRequest {
+    pub spki_sha256: Vec<PlanValue<String>>,
+    pub serial: Vec<PlanValue<String>>,
+}
+
+impl Evaluate<crate::TlsPinPlanOutput> for TlsPinRequest {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> Result<crate::TlsPinPlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        Ok(crate::TlsPinPlanOutput {
+            spki_sha256: self.spki_sha256.evaluate(state)?,
+            serial: self.serial.evaluate(state)?,
+        })
+    }
+}
+
+impl TryFrom<bindings::TlsPin> for TlsPinRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::TlsPin) -> Result<Self> {
+        Ok(Self {
+            spki_sha256: binding
+                .spki_sha256
+                .into_iter()
+                .flatten()
+                .map(PlanValue::try_from)
+                .try_collect()?,
+            serial: binding
+                .serial
+                .into_iter()
+                .flatten()
+                .map(PlanValue::try_from)
+                .try_collect()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TlsClientRequest {
+    pub cert: PlanValue<Option<String>>,
+    pub cert_file: PlanValue<Option<String>>,
+    pub key: PlanValue<Option<String>>,
+    pub key_file: PlanValue<Option<String>>,
+}
+
+impl Evaluate<crate::TlsClientPlanOutput> for TlsClientRequest {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> Result<crate::TlsClientPlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        Ok(crate::TlsClientPlanOutput {
+            cert: self.cert.evaluate(state)?,
+            cert_file: self.cert_file.evaluate(state)?,
+            key: self.key.evaluate(state)?.map(Secret),
+            key_file: self.key_file.evaluate(state)?,
+        })
+    }
+}
+
+impl TryFrom<bindings::TlsClient> for TlsClientRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::TlsClient) -> Result<Self> {
+        match (&binding.cert, &binding.cert_file) {
+            (Some(_), Some(_)) => {
+                bail!("tls.client.cert and tls.client.cert_file are mutually exclusive")
+            }
+            (None, None) => bail!("tls.client.cert or tls.client.cert_file is required"),
+            _ => {}
+        }
+        match (&binding.key, &binding.key_file) {
+            (Some(_), Some(_)) => {
+                bail!("tls.client.key and tls.client.key_file are mutually exclusive")
+            }
+            (None, None) => bail!("tls.client.key or tls.client.key_file is required"),
+            _ => {}
+        }
+        Ok(Self {
+            cert: binding.cert.try_into()?,
+            cert_file: binding.cert_file.try_into()?,
+            key: binding.key.try_into()?,
+            key_file: binding.key_file.try_into()?,
         })
     }
 }
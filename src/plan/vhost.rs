@@ -0,0 +1,127 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail};
+use devil_derive::BigQuerySchema;
+use serde::Serialize;
+use url::Url;
+
+use super::{Evaluate, IterablePlanValue, PlanData, PlanValue, TryFromPlanData};
+use crate::bindings::Literal;
+use crate::{bindings, Error, Result, State};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, BigQuerySchema)]
+#[serde(rename_all = "snake_case")]
+pub enum VhostSni {
+    /// Send each candidate host as the TLS server name.
+    Aligned,
+    /// Send the URL's host as the server name regardless of the Host header.
+    Unaligned,
+    /// Try each candidate both ways.
+    #[default]
+    Both,
+}
+
+impl VhostSni {
+    pub fn aligned(self) -> bool {
+        matches!(self, Self::Aligned | Self::Both)
+    }
+
+    pub fn unaligned(self) -> bool {
+        matches!(self, Self::Unaligned | Self::Both)
+    }
+}
+
+impl FromStr for VhostSni {
+    type Err = Error;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "aligned" => Ok(Self::Aligned),
+            "unaligned" => Ok(Self::Unaligned),
+            "both" => Ok(Self::Both),
+            val => bail!("unrecognized vhost.sni string {val}"),
+        }
+    }
+}
+
+impl TryFromPlanData for VhostSni {
+    type Error = Error;
+    fn try_from_plan_data(value: PlanData) -> std::result::Result<Self, Self::Error> {
+        match value.0 {
+            cel_interpreter::Value::String(s) => s.parse(),
+            val => bail!("unsupported value {val:?} for field vhost.sni"),
+        }
+    }
+}
+
+impl TryFrom<bindings::Value> for PlanValue<VhostSni> {
+    type Error = Error;
+    fn try_from(binding: bindings::Value) -> Result<Self> {
+        match binding {
+            bindings::Value::ExpressionCel { cel, vars } => Ok(Self::Dynamic {
+                cel,
+                vars: vars.unwrap_or_default().into_iter().collect(),
+            }),
+            bindings::Value::Literal(Literal::String(x)) => Ok(Self::Literal(x.parse()?)),
+            val => bail!("invalid value {val:?} for field vhost.sni"),
+        }
+    }
+}
+
+/// Sends requests for each candidate Host to a fixed address, grouping similar responses.
+#[derive(Debug, Clone)]
+pub struct VhostRequest {
+    pub url: PlanValue<Url>,
+    pub hosts: IterablePlanValue,
+    pub sni: PlanValue<VhostSni>,
+    pub length_tolerance: PlanValue<u64>,
+}
+
+impl Evaluate<crate::VhostPlanOutput> for VhostRequest {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> Result<crate::VhostPlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        Ok(crate::VhostPlanOutput {
+            url: self.url.evaluate(state)?,
+            hosts: self
+                .hosts
+                .evaluate(state)?
+                .into_iter()
+                .map(|(_, v)| {
+                    String::try_from_plan_data(v).map_err(|e| anyhow!("vhost.hosts: {e}"))
+                })
+                .collect::<Result<_>>()?,
+            sni: self.sni.evaluate(state)?,
+            length_tolerance: self.length_tolerance.evaluate(state)?,
+        })
+    }
+}
+
+impl TryFrom<bindings::Vhost> for VhostRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::Vhost) -> Result<Self> {
+        Ok(Self {
+            url: binding
+                .url
+                .map(PlanValue::try_from)
+                .ok_or_else(|| anyhow!("vhost.url is required"))??,
+            hosts: binding
+                .hosts
+                .map(IterablePlanValue::try_from)
+                .ok_or_else(|| anyhow!("vhost.hosts is required"))??,
+            sni: binding
+                .sni
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or_default(),
+            length_tolerance: binding
+                .length_tolerance
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(0)),
+        })
+    }
+}
@@ -0,0 +1,176 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail};
+use cel_interpreter::Duration;
+use chrono::TimeDelta;
+use itertools::Itertools;
+use url::Url;
+
+use super::{Evaluate, PlanData, PlanValue, PlanValueTable, TryFromPlanData};
+use crate::bindings::Literal;
+use crate::{
+    bindings, Error, HttpHeader, MaybeUtf8, ParsedWebSocketOpcode, Result, State,
+    WebSocketFramePlanOutput, WebSocketOpcode,
+};
+
+impl TryFromPlanData for WebSocketOpcode {
+    type Error = Error;
+    fn try_from_plan_data(value: PlanData) -> Result<Self> {
+        Ok(match value.0 {
+            cel_interpreter::Value::String(x) => x.parse::<ParsedWebSocketOpcode>()?.into(),
+            cel_interpreter::Value::Int(raw) => u8::try_from(raw)?.into(),
+            cel_interpreter::Value::UInt(raw) => u8::try_from(raw)?.into(),
+            _ => bail!("websocket opcode must be a string or 4 bit unsigned integer"),
+        })
+    }
+}
+
+impl TryFrom<Literal> for WebSocketOpcode {
+    type Error = Error;
+    fn try_from(binding: Literal) -> Result<Self> {
+        Ok(match binding {
+            Literal::String(x) => x.parse::<ParsedWebSocketOpcode>()?.into(),
+            Literal::Int(raw) => u8::try_from(raw)?.into(),
+            _ => bail!("invalid value {binding:?} for websocket opcode field"),
+        })
+    }
+}
+
+/// Upgrades an HTTP/1.1 connection to a WebSocket and exchanges a scripted sequence of frames.
+#[derive(Debug, Clone)]
+pub struct WebSocketRequest {
+    pub url: PlanValue<Url>,
+    pub headers: PlanValueTable<MaybeUtf8, MaybeUtf8>,
+    pub frames: Vec<WebSocketFrame>,
+    pub timeout: PlanValue<Duration>,
+}
+
+impl Evaluate<crate::WebSocketPlanOutput> for WebSocketRequest {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> Result<crate::WebSocketPlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        let url = self.url.evaluate(state)?;
+        if !matches!(url.scheme(), "ws" | "wss") {
+            bail!("ws.url must use the ws or wss scheme");
+        }
+        Ok(crate::WebSocketPlanOutput {
+            url,
+            headers: self
+                .headers
+                .evaluate(state)?
+                .into_iter()
+                .map(HttpHeader::from)
+                .collect(),
+            frames: self
+                .frames
+                .iter()
+                .map(|frame| frame.evaluate(state))
+                .try_collect()?,
+            timeout: self.timeout.evaluate(state)?,
+        })
+    }
+}
+
+impl TryFrom<bindings::WebSocket> for WebSocketRequest {
+    type Error = Error;
+    fn try_from(binding: bindings::WebSocket) -> Result<Self> {
+        Ok(Self {
+            url: binding
+                .url
+                .map(PlanValue::try_from)
+                .ok_or_else(|| anyhow!("ws.url is required"))??,
+            headers: PlanValueTable::try_from(binding.headers.unwrap_or_default())?,
+            frames: binding
+                .frames
+                .into_iter()
+                .flatten()
+                .map(WebSocketFrame::try_from)
+                .try_collect()?,
+            timeout: binding
+                .timeout
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(Duration(TimeDelta::seconds(5)))),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WebSocketFrame {
+    pub opcode: PlanValue<WebSocketOpcode>,
+    pub fin: PlanValue<bool>,
+    pub rsv: PlanValue<u8>,
+    pub mask: PlanValue<bool>,
+    pub close_code: PlanValue<Option<u16>>,
+    pub payload: PlanValue<MaybeUtf8>,
+    pub receive: PlanValue<u64>,
+}
+
+impl WebSocketFrame {
+    fn evaluate<'a, S, O, I>(&self, state: &S) -> Result<WebSocketFramePlanOutput>
+    where
+        S: State<'a, O, I>,
+        O: Into<&'a Arc<String>>,
+        I: IntoIterator<Item = O>,
+    {
+        let opcode = self.opcode.evaluate(state)?;
+        if opcode.raw > 0xF {
+            bail!("ws.frames.opcode must fit in 4 bits");
+        }
+        let rsv = self.rsv.evaluate(state)?;
+        if rsv > 0x7 {
+            bail!("ws.frames.rsv must fit in 3 bits");
+        }
+        Ok(WebSocketFramePlanOutput {
+            opcode,
+            fin: self.fin.evaluate(state)?,
+            rsv,
+            mask: self.mask.evaluate(state)?,
+            close_code: self.close_code.evaluate(state)?,
+            payload: self.payload.evaluate(state)?,
+            receive: self.receive.evaluate(state)?,
+        })
+    }
+}
+
+impl TryFrom<bindings::WebSocketFrame> for WebSocketFrame {
+    type Error = Error;
+    fn try_from(binding: bindings::WebSocketFrame) -> Result<Self> {
+        Ok(Self {
+            opcode: binding
+                .opcode
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(ParsedWebSocketOpcode::Text.into())),
+            fin: binding
+                .fin
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(true)),
+            rsv: binding
+                .rsv
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or_default(),
+            mask: binding
+                .mask
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or(PlanValue::Literal(true)),
+            close_code: binding.close_code.try_into()?,
+            payload: binding
+                .payload
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or_default(),
+            receive: binding
+                .receive
+                .map(PlanValue::try_from)
+                .transpose()?
+                .unwrap_or_default(),
+        })
+    }
+}
@@ -0,0 +1,112 @@
+use std::sync::Arc;
+
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+
+use crate::exec::{self, Executor};
+use crate::{Plan, RunName, RunOutput};
+
+/// A parsed plan.
+#[pyclass(name = "Plan", frozen, unsendable)]
+struct PyPlan {
+    inner: Arc<Plan>,
+}
+
+#[pymethods]
+impl PyPlan {
+    #[staticmethod]
+    fn parse(text: &str) -> PyResult<Self> {
+        let plan = Plan::parse(text).map_err(|e| PyValueError::new_err(format!("{e:#}")))?;
+        Ok(Self {
+            inner: Arc::new(plan),
+        })
+    }
+
+    #[getter]
+    fn name(&self) -> &str {
+        &self.inner.name
+    }
+
+    #[getter]
+    fn steps(&self) -> Vec<String> {
+        self.inner.steps.keys().map(|k| k.to_string()).collect()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Plan(name={:?})", self.inner.name)
+    }
+}
+
+/// Runs a plan one step at a time. Iterating yields each step's output as a dict in the same shape
+/// as the JSON output format.
+#[pyclass(name = "Executor", unsendable)]
+struct PyExecutor {
+    runtime: tokio::runtime::Runtime,
+    inner: Executor,
+    output: RunOutput,
+}
+
+#[pymethods]
+impl PyExecutor {
+    #[new]
+    fn new(plan: &PyPlan) -> PyResult<Self> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()?;
+        let output = RunOutput::new(RunName::new(plan.inner.name.clone()));
+        let inner = Executor::new(&plan.inner, output.name.clone()).map_err(runtime_error)?;
+        Ok(Self {
+            runtime,
+            inner,
+            output,
+        })
+    }
+
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python<'_>) -> PyResult<Option<PyObject>> {
+        // Release the GIL so other Python threads keep running while the step does.
+        let next = py.allow_threads(|| self.runtime.block_on(self.inner.next()));
+        let step = match next {
+            Ok(step) => Arc::new(step),
+            Err(e) if matches!(e.downcast_ref(), Some(exec::Error::Done)) => return Ok(None),
+            Err(e) => return Err(runtime_error(e)),
+        };
+        self.output
+            .steps
+            .insert(step.name.step.clone(), step.clone());
+        self.output.usage = Some(self.inner.usage());
+        Ok(Some(pythonize_redacted(py, &*step)?))
+    }
+
+    /// Run all remaining steps and return the output of the whole run.
+    fn run(&mut self, py: Python<'_>) -> PyResult<PyObject> {
+        while self.__next__(py)?.is_some() {}
+        self.output(py)
+    }
+
+    /// The output of all steps run so far.
+    fn output(&self, py: Python<'_>) -> PyResult<PyObject> {
+        pythonize_redacted(py, &self.output)
+    }
+}
+
+/// Convert an output to Python objects with secret values redacted.
+fn pythonize_redacted<T: serde::Serialize>(py: Python<'_>, output: &T) -> PyResult<PyObject> {
+    let output =
+        crate::secret::to_json(output).map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+    Ok(pythonize::pythonize(py, &output)?.unbind())
+}
+
+fn runtime_error(e: crate::Error) -> PyErr {
+    PyRuntimeError::new_err(format!("{e:#}"))
+}
+
+#[pymodule]
+fn devil(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyPlan>()?;
+    m.add_class::<PyExecutor>()?;
+    Ok(())
+}
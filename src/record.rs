@@ -1,7 +1,15 @@
-use std::{collections::HashMap, io::Write, mem, sync::Arc};
+use std::fmt::Debug;
+use std::{io::Write, sync::Arc};
 
+use async_trait::async_trait;
+use itertools::Itertools;
+use serde::Serialize;
+
+#[cfg(feature = "native")]
 use anyhow::bail;
+#[cfg(feature = "native")]
 use derivative::Derivative;
+#[cfg(feature = "native")]
 use gcp_bigquery_client::{
     error::{BQError, NestedResponseError},
     model::{
@@ -10,34 +18,58 @@ use gcp_bigquery_client::{
         table_field_schema::TableFieldSchema,
     },
 };
+#[cfg(feature = "native")]
 use indexmap::IndexMap;
-use itertools::Itertools;
-use serde::Serialize;
+#[cfg(feature = "native")]
+use object_store::aws::{AmazonS3, AmazonS3Builder};
+#[cfg(feature = "native")]
+use object_store::ObjectStore;
+#[cfg(feature = "native")]
+use std::collections::{hash_map::Entry, BTreeSet, HashMap};
+#[cfg(feature = "native")]
+use std::{mem, path::PathBuf};
+#[cfg(feature = "native")]
+use svix_ksuid::{KsuidLike, KsuidMs};
+#[cfg(feature = "native")]
 use tokio::{
     fs::File,
     io::{stdout, AsyncWriteExt, Stdout},
 };
+#[cfg(feature = "native")]
 use tracing::{debug, info, info_span, span, Instrument};
 
+use crate::secret;
 use crate::{
-    Direction, GraphqlOutput, GraphqlRequestOutput, GraphqlResponse, Http1Output,
+    Direction, GraphqlOutput, GraphqlRequestOutput, GraphqlResponse, GrpcOutput, Http1Output,
     Http1RequestOutput, Http1Response, Http2FrameOutput, Http2FramePayloadOutput, Http2Output,
-    Http2RequestOutput, Http2Response, HttpHeader, HttpOutput, HttpRequestOutput, HttpResponse,
-    JobOutput, ProtocolDiscriminants, RawHttp2Output, RawTcpOutput, Result, RunOutput, StepOutput,
-    TcpOutput, TcpReceivedOutput, TcpSegmentOutput, TcpSentOutput, TlsOutput, TlsReceivedOutput,
-    TlsSentOutput,
+    Http2RequestOutput, Http2Response, Http3Output, Http3RequestOutput, Http3Response, HttpHeader,
+    HttpOutput, HttpRequestOutput, HttpResponse, JobOutput, ProtocolDiscriminants, QuicOutput,
+    RawHttp2Output, RawTcpOutput, Result, RunOutput, StepOutput, TcpOutput, TcpReceivedOutput,
+    TcpSegmentOutput, TcpSentOutput, TlsOutput, TlsReceivedOutput, TlsResumption, TlsSentOutput,
+    TlsVerification, WebSocketFrameOutput, WebSocketHandshakeOutput, WebSocketOutput,
 };
 
+#[cfg(feature = "native")]
 pub trait BigQuerySchema {
     fn big_query_schema(name: &str) -> TableFieldSchema;
 }
 
+/// Schemas are only needed by the BigQuery writer, so without the native feature every type has
+/// an empty one.
+#[cfg(not(feature = "native"))]
+pub trait BigQuerySchema {}
+
+#[cfg(not(feature = "native"))]
+impl<T: ?Sized> BigQuerySchema for T {}
+
+#[cfg(feature = "native")]
 impl<T: BigQuerySchema> BigQuerySchema for Arc<T> {
     fn big_query_schema(name: &str) -> TableFieldSchema {
         T::big_query_schema(name)
     }
 }
 
+#[cfg(feature = "native")]
 impl<T: BigQuerySchema> BigQuerySchema for Option<T> {
     fn big_query_schema(name: &str) -> TableFieldSchema {
         let mut inner = T::big_query_schema(name);
@@ -50,6 +82,7 @@ impl<T: BigQuerySchema> BigQuerySchema for Option<T> {
     }
 }
 
+#[cfg(feature = "native")]
 impl<T: BigQuerySchema> BigQuerySchema for Vec<T> {
     fn big_query_schema(name: &str) -> TableFieldSchema {
         let mut inner = T::big_query_schema(name);
@@ -58,72 +91,91 @@ impl<T: BigQuerySchema> BigQuerySchema for Vec<T> {
     }
 }
 
+#[cfg(feature = "native")]
 impl<K: ToString, V: BigQuerySchema> BigQuerySchema for IndexMap<K, V> {
     fn big_query_schema(name: &str) -> TableFieldSchema {
         TableFieldSchema::json(name)
     }
 }
 
+#[cfg(feature = "native")]
 impl<K: ToString, V: BigQuerySchema> BigQuerySchema for HashMap<K, V> {
     fn big_query_schema(name: &str) -> TableFieldSchema {
         TableFieldSchema::json(name)
     }
 }
 
+#[cfg(feature = "native")]
 impl BigQuerySchema for String {
     fn big_query_schema(name: &str) -> TableFieldSchema {
         TableFieldSchema::string(name)
     }
 }
 
+#[cfg(feature = "native")]
 impl BigQuerySchema for &str {
     fn big_query_schema(name: &str) -> TableFieldSchema {
         TableFieldSchema::string(name)
     }
 }
 
+#[cfg(feature = "native")]
 impl BigQuerySchema for usize {
     fn big_query_schema(name: &str) -> TableFieldSchema {
         TableFieldSchema::integer(name)
     }
 }
 
+#[cfg(feature = "native")]
 impl BigQuerySchema for u64 {
     fn big_query_schema(name: &str) -> TableFieldSchema {
         TableFieldSchema::integer(name)
     }
 }
 
+#[cfg(feature = "native")]
 impl BigQuerySchema for u32 {
     fn big_query_schema(name: &str) -> TableFieldSchema {
         TableFieldSchema::integer(name)
     }
 }
 
+#[cfg(feature = "native")]
 impl BigQuerySchema for u16 {
     fn big_query_schema(name: &str) -> TableFieldSchema {
         TableFieldSchema::integer(name)
     }
 }
 
+#[cfg(feature = "native")]
 impl BigQuerySchema for u8 {
     fn big_query_schema(name: &str) -> TableFieldSchema {
         TableFieldSchema::integer(name)
     }
 }
 
+#[cfg(feature = "native")]
 impl BigQuerySchema for i64 {
     fn big_query_schema(name: &str) -> TableFieldSchema {
         TableFieldSchema::integer(name)
     }
 }
 
+#[cfg(feature = "native")]
+impl BigQuerySchema for f64 {
+    fn big_query_schema(name: &str) -> TableFieldSchema {
+        TableFieldSchema::float(name)
+    }
+}
+
+#[cfg(feature = "native")]
 impl BigQuerySchema for bool {
     fn big_query_schema(name: &str) -> TableFieldSchema {
         TableFieldSchema::bool(name)
     }
 }
 
+#[cfg(feature = "native")]
 impl BigQuerySchema for cel_interpreter::Duration {
     fn big_query_schema(name: &str) -> TableFieldSchema {
         TableFieldSchema::record(
@@ -137,12 +189,14 @@ impl BigQuerySchema for cel_interpreter::Duration {
     }
 }
 
+#[cfg(feature = "native")]
 impl BigQuerySchema for url::Url {
     fn big_query_schema(name: &str) -> TableFieldSchema {
         TableFieldSchema::string(name)
     }
 }
 
+#[cfg(feature = "native")]
 impl BigQuerySchema for serde_json::Value {
     fn big_query_schema(name: &str) -> TableFieldSchema {
         TableFieldSchema::json(name)
@@ -166,6 +220,7 @@ pub enum Serializer {
     Toml,
 }
 
+#[cfg(feature = "native")]
 impl Serializer {
     fn serialize<W: Write, R: Record>(
         &mut self,
@@ -200,9 +255,13 @@ impl Serializer {
 
 #[derive(Debug)]
 pub enum RecordWriter {
+    #[cfg(feature = "native")]
     Stdout(StdoutWriter),
+    #[cfg(feature = "native")]
     File(FileWriter),
+    #[cfg(feature = "native")]
     BigQuery(BigQueryWriter),
+    Sink(Box<dyn Sink>),
 }
 
 impl RecordWriter {
@@ -212,21 +271,191 @@ impl RecordWriter {
         layers: &[ProtocolDiscriminants],
     ) -> Result<()> {
         match self {
+            #[cfg(feature = "native")]
             Self::Stdout(w) => w.write(records, layers).await,
+            #[cfg(feature = "native")]
             Self::File(w) => w.write(records, layers).await,
+            #[cfg(feature = "native")]
             Self::BigQuery(w) => w.write(records, layers).await,
+            Self::Sink(w) => {
+                let records = records.iter().map(secret::to_json).try_collect()?;
+                w.write(R::table_name(), records).await
+            }
+        }
+    }
+
+    /// Write out anything still buffered. Called once no more records will be written.
+    pub async fn finish(&mut self) -> Result<()> {
+        match self {
+            #[cfg(feature = "native")]
+            Self::Stdout(w) => Ok(w.inner.flush().await?),
+            #[cfg(feature = "native")]
+            Self::File(w) => Ok(w.inner.flush().await?),
+            #[cfg(feature = "native")]
+            Self::BigQuery(_) => Ok(()),
+            Self::Sink(w) => w.finish().await,
         }
     }
 
     pub fn name(&self) -> &'static str {
         match self {
+            #[cfg(feature = "native")]
             Self::Stdout(_) => "stdout",
+            #[cfg(feature = "native")]
             Self::File(_) => "file",
+            #[cfg(feature = "native")]
             Self::BigQuery(_) => "BigQuery",
+            Self::Sink(w) => w.name(),
+        }
+    }
+}
+
+/// A destination for records as they're produced during a run.
+///
+/// Records arrive already serialized, grouped by the table they belong in, so implementations
+/// don't need to know about every output type.
+#[async_trait]
+pub trait Sink: Debug + Send {
+    fn name(&self) -> &'static str;
+    async fn write(&mut self, table: &'static str, records: Vec<serde_json::Value>) -> Result<()>;
+    async fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Appends records as JSON lines to one file per table in a directory.
+#[cfg(feature = "native")]
+#[derive(Debug)]
+pub struct DirectoryWriter {
+    root: PathBuf,
+    files: HashMap<&'static str, File>,
+    buf: Vec<u8>,
+}
+
+#[cfg(feature = "native")]
+impl DirectoryWriter {
+    pub async fn new(path: &str) -> Result<Self> {
+        tokio::fs::create_dir_all(path).await?;
+        Ok(Self {
+            root: PathBuf::from(path),
+            files: HashMap::new(),
+            buf: Vec::new(),
+        })
+    }
+}
+
+#[cfg(feature = "native")]
+#[async_trait]
+impl Sink for DirectoryWriter {
+    fn name(&self) -> &'static str {
+        "directory"
+    }
+
+    async fn write(&mut self, table: &'static str, records: Vec<serde_json::Value>) -> Result<()> {
+        let file = match self.files.entry(table) {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => {
+                let path = self.root.join(format!("{table}.jsonl"));
+                e.insert(File::options().create(true).append(true).open(path).await?)
+            }
+        };
+        self.buf.clear();
+        for r in records {
+            serde_json::to_writer(&mut self.buf, &r)?;
+            self.buf.push(b'\n');
+        }
+        file.write_all(&self.buf).await?;
+        Ok(())
+    }
+
+    async fn finish(&mut self) -> Result<()> {
+        for file in self.files.values_mut() {
+            file.flush().await?;
+        }
+        Ok(())
+    }
+}
+
+/// Uploads records as JSON lines objects to an S3 compatible bucket, starting a new object for a
+/// table each time enough of its records are buffered.
+#[cfg(feature = "native")]
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct S3Writer {
+    #[derivative(Debug = "ignore")]
+    store: AmazonS3,
+    prefix: String,
+    buffers: HashMap<&'static str, Vec<u8>>,
+}
+
+#[cfg(feature = "native")]
+impl S3Writer {
+    const OBJECT_BYTES: usize = 8 << 20;
+
+    /// Credentials and any unset options are read from the standard AWS environment variables.
+    pub fn new(
+        bucket: &str,
+        prefix: String,
+        endpoint: Option<&str>,
+        region: Option<&str>,
+    ) -> Result<Self> {
+        let mut builder = AmazonS3Builder::from_env().with_bucket_name(bucket);
+        if let Some(endpoint) = endpoint {
+            builder = builder
+                .with_endpoint(endpoint)
+                .with_allow_http(endpoint.starts_with("http://"));
+        }
+        if let Some(region) = region {
+            builder = builder.with_region(region);
+        }
+        Ok(Self {
+            store: builder.build()?,
+            prefix,
+            buffers: HashMap::new(),
+        })
+    }
+
+    async fn upload(&self, table: &str, body: Vec<u8>) -> Result<()> {
+        // KSUIDs sort by creation time, so listing a table's objects returns them in order.
+        let key = format!("{}{table}/{}.jsonl", self.prefix, KsuidMs::new(None, None));
+        self.store
+            .put(&object_store::path::Path::from(key), body.into())
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "native")]
+#[async_trait]
+impl Sink for S3Writer {
+    fn name(&self) -> &'static str {
+        "S3"
+    }
+
+    async fn write(&mut self, table: &'static str, records: Vec<serde_json::Value>) -> Result<()> {
+        let buf = self.buffers.entry(table).or_default();
+        for r in records {
+            serde_json::to_writer(&mut *buf, &r)?;
+            buf.push(b'\n');
+        }
+        if buf.len() >= Self::OBJECT_BYTES {
+            let body = mem::take(buf);
+            self.upload(table, body).await?;
         }
+        Ok(())
+    }
+
+    async fn finish(&mut self) -> Result<()> {
+        for (table, body) in mem::take(&mut self.buffers) {
+            if !body.is_empty() {
+                self.upload(table, body).await?;
+            }
+        }
+        Ok(())
     }
 }
 
+#[cfg(feature = "native")]
 #[derive(Debug)]
 pub struct StdoutWriter {
     inner: Stdout,
@@ -234,6 +463,7 @@ pub struct StdoutWriter {
     buf: Vec<u8>,
 }
 
+#[cfg(feature = "native")]
 impl StdoutWriter {
     pub fn new(serializer: Serializer) -> Self {
         Self {
@@ -249,13 +479,14 @@ impl StdoutWriter {
         layers: &[ProtocolDiscriminants],
     ) -> Result<()> {
         self.buf.clear();
-        self.ser.serialize(&mut self.buf, records, layers)?;
-        self.inner.write_all(&self.buf).await?;
+        secret::redacting(|| self.ser.serialize(&mut self.buf, records, layers))?;
+        self.inner.write_all(&secret::redact(&self.buf)).await?;
         // TODO: Do we want to flush self.inner here?
         Ok(())
     }
 }
 
+#[cfg(feature = "native")]
 #[derive(Debug)]
 pub struct FileWriter {
     inner: File,
@@ -263,6 +494,7 @@ pub struct FileWriter {
     buf: Vec<u8>,
 }
 
+#[cfg(feature = "native")]
 impl FileWriter {
     pub async fn new(path: &str, serializer: Serializer) -> Result<Self> {
         Ok(Self {
@@ -278,13 +510,14 @@ impl FileWriter {
         layers: &[ProtocolDiscriminants],
     ) -> Result<()> {
         self.buf.clear();
-        self.ser.serialize(&mut self.buf, records, layers)?;
-        self.inner.write_all(&self.buf).await?;
+        secret::redacting(|| self.ser.serialize(&mut self.buf, records, layers))?;
+        self.inner.write_all(&secret::redact(&self.buf)).await?;
         // TODO: Do we want to flush self.inner here?
         Ok(())
     }
 }
 
+#[cfg(feature = "native")]
 #[derive(Derivative)]
 #[derivative(Debug)]
 pub struct BigQueryWriter {
@@ -298,6 +531,7 @@ pub struct BigQueryWriter {
     //trace_id: String,
 }
 
+#[cfg(feature = "native")]
 impl BigQueryWriter {
     //const BUFFER_RECORDS: usize = 100;
 
@@ -334,7 +568,7 @@ impl BigQueryWriter {
             .map(|r| {
                 Ok::<_, serde_json::Error>(TableDataInsertAllRequestRows {
                     insert_id: None,
-                    json: serde_json::to_value(r)?,
+                    json: secret::to_json(r)?,
                 })
             })
             .try_collect()?;
@@ -395,6 +629,313 @@ impl BigQueryWriter {
     //}
 }
 
+/// Stores records in a SQLite database so results from many runs can be queried together.
+///
+/// Requests and responses are pulled out of run, step, and job records, so the writer should be
+/// used with one of those normalizations. Records of other kinds are kept as is in `records`. Each
+/// table also keeps the full JSON of what its rows came from, which can be queried with SQLite's
+/// JSON functions.
+#[cfg(feature = "native")]
+#[derive(Debug)]
+pub struct SqliteWriter {
+    conn: rusqlite::Connection,
+}
+
+#[cfg(feature = "native")]
+impl SqliteWriter {
+    /// The database layout. Views at the end answer common questions across runs:
+    ///
+    /// - `slowest_steps`: response time statistics per step, slowest first.
+    /// - `server_errors`: every response with a 5xx status.
+    /// - `finding_counts`: how many findings of each kind each run produced.
+    pub const SCHEMA: &'static str = "
+        CREATE TABLE IF NOT EXISTS runs (
+            run TEXT PRIMARY KEY,
+            plan TEXT NOT NULL,
+            record TEXT
+        );
+        CREATE TABLE IF NOT EXISTS steps (
+            run TEXT NOT NULL,
+            step TEXT NOT NULL,
+            jobs INTEGER NOT NULL,
+            record TEXT NOT NULL,
+            PRIMARY KEY (run, step)
+        );
+        CREATE TABLE IF NOT EXISTS requests (
+            run TEXT NOT NULL,
+            step TEXT NOT NULL,
+            job TEXT NOT NULL,
+            protocol TEXT NOT NULL,
+            method TEXT,
+            url TEXT,
+            duration_ms REAL,
+            record TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS responses (
+            run TEXT NOT NULL,
+            step TEXT NOT NULL,
+            job TEXT NOT NULL,
+            protocol TEXT NOT NULL,
+            status INTEGER,
+            duration_ms REAL,
+            record TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS findings (
+            run TEXT NOT NULL,
+            step TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            target TEXT NOT NULL,
+            detail TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS records (
+            kind TEXT NOT NULL,
+            name TEXT,
+            record TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS responses_step ON responses (run, step);
+        CREATE INDEX IF NOT EXISTS responses_status ON responses (status);
+
+        CREATE VIEW IF NOT EXISTS slowest_steps AS
+            SELECT run, step, count(*) AS responses, avg(duration_ms) AS mean_ms,
+                max(duration_ms) AS max_ms
+            FROM responses
+            GROUP BY run, step
+            ORDER BY max_ms DESC;
+        CREATE VIEW IF NOT EXISTS server_errors AS
+            SELECT run, step, job, protocol, status, duration_ms, record
+            FROM responses
+            WHERE status BETWEEN 500 AND 599;
+        CREATE VIEW IF NOT EXISTS finding_counts AS
+            SELECT run, kind, count(*) AS findings
+            FROM findings
+            GROUP BY run, kind
+            ORDER BY run, findings DESC;
+    ";
+
+    /// Protocols whose outputs have a request and response.
+    const PROTOCOLS: [&'static str; 7] = ["graphql", "http", "h1", "h1c", "h2", "h2c", "h3"];
+
+    pub fn new(path: &str) -> Result<Self> {
+        let conn = rusqlite::Connection::open(path)?;
+        conn.execute_batch(Self::SCHEMA)?;
+        Ok(Self { conn })
+    }
+
+    fn insert(&self, table: &str, record: &serde_json::Value) -> Result<()> {
+        match table {
+            "run" => {
+                let [plan, run] = name_parts(record);
+                self.conn.execute(
+                    "INSERT OR REPLACE INTO runs (run, plan, record) VALUES (?1, ?2, ?3)",
+                    (&run, &plan, record.to_string()),
+                )?;
+                if let Some(steps) = record.get("steps").and_then(serde_json::Value::as_object) {
+                    for step in steps.values() {
+                        self.insert_step(step)?;
+                    }
+                }
+            }
+            "step" => self.insert_step(record)?,
+            "job" => self.insert_job(record)?,
+            _ => {
+                self.conn.execute(
+                    "INSERT INTO records (kind, name, record) VALUES (?1, ?2, ?3)",
+                    (
+                        table,
+                        record.get("name").and_then(serde_json::Value::as_str),
+                        record.to_string(),
+                    ),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn insert_step(&self, step: &serde_json::Value) -> Result<()> {
+        let [plan, run, name] = name_parts(step);
+        // Steps not written by an earlier run record still get a row.
+        self.conn.execute(
+            "INSERT OR IGNORE INTO runs (run, plan) VALUES (?1, ?2)",
+            (&run, &plan),
+        )?;
+        let jobs = step.get("jobs").and_then(serde_json::Value::as_object);
+        self.conn.execute(
+            "INSERT OR REPLACE INTO steps (run, step, jobs, record) VALUES (?1, ?2, ?3, ?4)",
+            (
+                &run,
+                &name,
+                jobs.map_or(0, |jobs| jobs.len()),
+                step.to_string(),
+            ),
+        )?;
+        for job in jobs.into_iter().flat_map(|jobs| jobs.values()) {
+            self.insert_job(job)?;
+        }
+        let findings = step
+            .pointer("/takeover/findings")
+            .and_then(serde_json::Value::as_array);
+        for finding in findings.into_iter().flatten() {
+            let field = |key| finding.get(key).and_then(serde_json::Value::as_str);
+            self.conn.execute(
+                "INSERT INTO findings (run, step, kind, target, detail)
+                    VALUES (?1, ?2, ?3, ?4, ?5)",
+                (
+                    &run,
+                    &name,
+                    "takeover",
+                    field("host").unwrap_or_default(),
+                    format!(
+                        "{} via {}: {}",
+                        field("service").unwrap_or_default(),
+                        field("target").unwrap_or_default(),
+                        field("evidence").unwrap_or_default(),
+                    ),
+                ),
+            )?;
+        }
+        // Markers differ between runs, so leave them and offsets out for runs to compare.
+        let reflections: BTreeSet<_> = step
+            .get("reflections")
+            .and_then(serde_json::Value::as_array)
+            .into_iter()
+            .flatten()
+            .map(|reflection| {
+                let field = |key| reflection.get(key).and_then(serde_json::Value::as_str);
+                let location = match field("header") {
+                    Some(header) => format!("{header} header"),
+                    None => field("location").unwrap_or_default().replace('_', " "),
+                };
+                let stored = reflection
+                    .get("stored")
+                    .and_then(serde_json::Value::as_bool);
+                (
+                    if stored == Some(true) {
+                        "stored_injection"
+                    } else {
+                        "reflection"
+                    },
+                    field("job").unwrap_or_default().to_owned(),
+                    format!(
+                        "marker from step {} in {} response {location}",
+                        field("source").unwrap_or_default(),
+                        field("protocol").unwrap_or_default(),
+                    ),
+                )
+            })
+            .collect();
+        for (kind, job, detail) in reflections {
+            self.conn.execute(
+                "INSERT INTO findings (run, step, kind, target, detail)
+                    VALUES (?1, ?2, ?3, ?4, ?5)",
+                (&run, &name, kind, job, detail),
+            )?;
+        }
+        let desync = step
+            .get("desync")
+            .and_then(serde_json::Value::as_array)
+            .into_iter()
+            .flatten();
+        for finding in desync {
+            let field = |key| finding.get(key).and_then(serde_json::Value::as_str);
+            self.conn.execute(
+                "INSERT INTO findings (run, step, kind, target, detail)
+                    VALUES (?1, ?2, ?3, ?4, ?5)",
+                (
+                    &run,
+                    &name,
+                    field("kind").unwrap_or_default(),
+                    field("job").unwrap_or_default(),
+                    field("detail").unwrap_or_default(),
+                ),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn insert_job(&self, job: &serde_json::Value) -> Result<()> {
+        let [_, run, step, key] = name_parts(job);
+        for protocol in Self::PROTOCOLS {
+            let Some(output) = job.get(protocol).filter(|output| !output.is_null()) else {
+                continue;
+            };
+            if let Some(req) = output.get("request").filter(|req| !req.is_null()) {
+                self.conn.execute(
+                    "INSERT INTO requests
+                        (run, step, job, protocol, method, url, duration_ms, record)
+                        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                    (
+                        &run,
+                        &step,
+                        &key,
+                        protocol,
+                        req.pointer("/method/utf8")
+                            .and_then(serde_json::Value::as_str),
+                        req.get("url").and_then(serde_json::Value::as_str),
+                        millis(req.get("duration")),
+                        req.to_string(),
+                    ),
+                )?;
+            }
+            if let Some(resp) = output.get("response").filter(|resp| !resp.is_null()) {
+                self.conn.execute(
+                    "INSERT INTO responses (run, step, job, protocol, status, duration_ms, record)
+                        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    (
+                        &run,
+                        &step,
+                        &key,
+                        protocol,
+                        resp.get("status_code").and_then(serde_json::Value::as_u64),
+                        millis(resp.get("duration")),
+                        resp.to_string(),
+                    ),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Split a record's dotted name, like plan.run.step, into its first N parts.
+#[cfg(feature = "native")]
+fn name_parts<const N: usize>(record: &serde_json::Value) -> [String; N] {
+    let name = record
+        .get("name")
+        .and_then(serde_json::Value::as_str)
+        .unwrap_or_default();
+    let mut parts = name.splitn(N, '.');
+    std::array::from_fn(|_| parts.next().unwrap_or_default().to_owned())
+}
+
+/// Convert a serialized duration to fractional milliseconds.
+#[cfg(feature = "native")]
+fn millis(duration: Option<&serde_json::Value>) -> Option<f64> {
+    let duration = duration?;
+    let secs = duration.get("secs")?.as_f64()?;
+    let nanos = duration.get("nanos")?.as_f64()?;
+    Some(secs * 1000.0 + nanos / 1_000_000.0)
+}
+
+#[cfg(feature = "native")]
+#[async_trait]
+impl Sink for SqliteWriter {
+    fn name(&self) -> &'static str {
+        "SQLite"
+    }
+
+    async fn write(&mut self, table: &'static str, records: Vec<serde_json::Value>) -> Result<()> {
+        // SQLite calls block, so keep them from stalling other tasks on this worker thread.
+        tokio::task::block_in_place(|| {
+            let tx = self.conn.unchecked_transaction()?;
+            for record in &records {
+                self.insert(table, record)?;
+            }
+            tx.commit()?;
+            Ok(())
+        })
+    }
+}
+
 pub trait Describe {
     fn describe<W: Write>(&self, w: W, layers: &[ProtocolDiscriminants]) -> std::io::Result<()>;
 }
@@ -417,10 +958,24 @@ impl Describe for RunOutput {
         mut w: W,
         layers: &[ProtocolDiscriminants],
     ) -> std::io::Result<()> {
+        if let Some(target) = &self.target {
+            writeln!(w, "------- target {target} --------")?;
+        }
         for (step_name, step) in &self.steps {
             writeln!(w, "------- step {step_name} --------")?;
             step.describe(&mut w, layers)?;
         }
+        if let Some(usage) = &self.usage {
+            writeln!(
+                w,
+                "used {} connections sending {} bytes and receiving {} bytes for {} jobs in {}",
+                usage.connections,
+                usage.bytes_sent,
+                usage.bytes_received,
+                usage.attempts,
+                usage.duration.0,
+            )?;
+        }
         Ok(())
     }
 }
@@ -431,10 +986,473 @@ impl Describe for StepOutput {
         mut w: W,
         layers: &[ProtocolDiscriminants],
     ) -> std::io::Result<()> {
+        if let Some(egress) = &self.egress {
+            writeln!(w, "egress: {egress}")?;
+        }
+        if let Some(network) = &self.network {
+            writeln!(w, "network: {}", network.name)?;
+        }
+        if let Some(auth) = &self.auth {
+            for login in &auth.logins {
+                writeln!(w, "---- login {} ----", auth.name)?;
+                login.0.describe(&mut w, layers)?;
+            }
+            if auth.replayed {
+                writeln!(w, "replayed after logging in again with {}", auth.name)?;
+            }
+        }
         for (_, job) in &self.jobs {
             writeln!(w, "---- job {} ----", job.name)?;
             job.describe(&mut w, layers)?;
         }
+        if let Some(module) = &self.module {
+            writeln!(w, "---- module {} ----", module.0.name)?;
+            module.0.describe(&mut w, layers)?;
+        }
+        if let Some(crawl) = &self.crawl {
+            writeln!(w, "---- crawl {} ----", crawl.plan.url)?;
+            for page in &crawl.pages {
+                writeln!(
+                    w,
+                    "page {} (depth {}, status {}, {} links)",
+                    page.url,
+                    page.depth,
+                    page.status_code
+                        .map_or_else(|| "none".to_owned(), |c| c.to_string()),
+                    page.links.len(),
+                )?;
+            }
+            for form in &crawl.forms {
+                let fields = form.fields.iter().map(|f| f.name.as_str()).join(", ");
+                writeln!(w, "form {} {} [{fields}]", form.method, form.action)?;
+            }
+        }
+        if let Some(discover) = &self.discover {
+            writeln!(w, "---- discover {} ----", discover.plan.url)?;
+            for sitemap in &discover.sitemaps {
+                writeln!(w, "sitemap {sitemap}")?;
+            }
+            for path in &discover.paths {
+                writeln!(w, "path {path}")?;
+            }
+        }
+        if let Some(browse) = &self.forced_browse {
+            writeln!(
+                w,
+                "---- forced browse {} ({} requests) ----",
+                browse.plan.url, browse.requests
+            )?;
+            for resource in &browse.resources {
+                writeln!(
+                    w,
+                    "found {} (status {}, length {})",
+                    resource.url,
+                    resource
+                        .status_code
+                        .map_or_else(|| "none".to_owned(), |c| c.to_string()),
+                    resource.length,
+                )?;
+            }
+        }
+        if let Some(vhost) = &self.vhost {
+            writeln!(w, "---- vhost {} ----", vhost.plan.url)?;
+            for cluster in &vhost.clusters {
+                writeln!(
+                    w,
+                    "{}cluster status {}, length {}, title {:?}: {}",
+                    if cluster.default { "default " } else { "" },
+                    cluster
+                        .status_code
+                        .map_or_else(|| "none".to_owned(), |c| c.to_string()),
+                    cluster.length,
+                    cluster.title.as_deref().unwrap_or_default(),
+                    cluster.hosts.join(", "),
+                )?;
+            }
+        }
+        if let Some(range) = &self.range {
+            writeln!(w, "---- range {} ----", range.plan.url)?;
+            if let Some(e) = &range.error {
+                writeln!(w, "error: {e}")?;
+            }
+            for case in &range.cases {
+                writeln!(
+                    w,
+                    "{} ({}): status {}, {} parts",
+                    case.name,
+                    case.range,
+                    case.status_code
+                        .map_or_else(|| "none".to_owned(), |c| c.to_string()),
+                    case.parts,
+                )?;
+                for issue in &case.issues {
+                    writeln!(w, "  {issue}")?;
+                }
+            }
+            if range.cache_consistent == Some(false) {
+                writeln!(w, "full response changed after range requests")?;
+            }
+        }
+        if let Some(conditional) = &self.conditional {
+            writeln!(w, "---- conditional {} ----", conditional.plan.url)?;
+            if let Some(e) = &conditional.error {
+                writeln!(w, "error: {e}")?;
+            }
+            for case in &conditional.cases {
+                writeln!(
+                    w,
+                    "{} ({}): status {}, expected {}{}",
+                    case.name,
+                    case.headers.join(", "),
+                    case.status_code
+                        .map_or_else(|| "none".to_owned(), |c| c.to_string()),
+                    case.expected_status,
+                    if case.passed { "" } else { " FAILED" },
+                )?;
+                if let Some(issue) = &case.issue {
+                    writeln!(w, "  {issue}")?;
+                }
+            }
+        }
+        if let Some(attack) = &self.h2_attack {
+            writeln!(w, "---- h2 {} {} ----", attack.plan.attack, attack.plan.url)?;
+            for exchange in &attack.exchanges {
+                writeln!(
+                    w,
+                    "{}: sent {} frames, received {} with {} resets{}{} in {}",
+                    exchange.name,
+                    exchange.frames_sent,
+                    exchange.frames_received,
+                    exchange.rst_streams,
+                    exchange
+                        .goaway
+                        .map_or_else(String::new, |code| format!(", goaway {code:#x}")),
+                    if exchange.closed { ", closed" } else { "" },
+                    exchange.duration.0,
+                )?;
+            }
+            if let Some(e) = &attack.error {
+                writeln!(w, "error: {e}")?;
+            }
+            writeln!(w, "alive after attack: {}", attack.alive)?;
+        }
+        if let Some(matrix) = &self.alpn_matrix {
+            writeln!(w, "---- alpn matrix {} ----", matrix.plan.url)?;
+            if let Some(e) = &matrix.error {
+                writeln!(w, "error: {e}")?;
+            }
+            for attempt in &matrix.attempts {
+                writeln!(
+                    w,
+                    "offered {}: negotiated {}, {} status {}, length {}",
+                    attempt.offered,
+                    attempt.negotiated.as_deref().unwrap_or("none"),
+                    attempt.http_version,
+                    attempt
+                        .status_code
+                        .map_or_else(|| "none".to_owned(), |c| c.to_string()),
+                    attempt.length,
+                )?;
+                for difference in &attempt.differences {
+                    writeln!(w, "  {difference}")?;
+                }
+                if let Some(e) = &attempt.error {
+                    writeln!(w, "  error: {e}")?;
+                }
+            }
+        }
+        if let Some(keep_alive) = &self.keep_alive {
+            writeln!(w, "---- keep-alive {} ----", keep_alive.plan.url)?;
+            if let Some(e) = &keep_alive.error {
+                writeln!(w, "error: {e}")?;
+            }
+            for conn in &keep_alive.connections {
+                let Some(error) = &conn.error else {
+                    continue;
+                };
+                writeln!(
+                    w,
+                    "connection {} {} with {} others open: {error}",
+                    conn.index,
+                    if conn.opened_at.is_some() {
+                        "closed"
+                    } else {
+                        "refused"
+                    },
+                    conn.open_at_close.unwrap_or_default(),
+                )?;
+            }
+            writeln!(w, "max open: {}", keep_alive.max_open)?;
+            if let Some(limit) = keep_alive.limit {
+                writeln!(w, "limit: {limit}")?;
+            }
+            writeln!(w, "recovered: {}", keep_alive.recovered)?;
+        }
+        if let Some(burst) = &self.tcp_burst {
+            writeln!(
+                w,
+                "---- tcp burst {}:{} ----",
+                burst.plan.host, burst.plan.port
+            )?;
+            if let Some(e) = &burst.error {
+                writeln!(w, "error: {e}")?;
+            }
+            writeln!(
+                w,
+                "established {}, failed {}",
+                burst.established, burst.failed
+            )?;
+            if let Some(spread) = &burst.spread {
+                writeln!(w, "attempts started within {}", spread.0)?;
+            }
+            if let (Some(min), Some(p50), Some(p90), Some(p99), Some(max)) = (
+                &burst.connect_min,
+                &burst.connect_p50,
+                &burst.connect_p90,
+                &burst.connect_p99,
+                &burst.connect_max,
+            ) {
+                writeln!(
+                    w,
+                    "connect min {}, p50 {}, p90 {}, p99 {}, max {}",
+                    min.0, p50.0, p90.0, p99.0, max.0,
+                )?;
+            }
+        }
+        if let Some(dns) = &self.dns {
+            writeln!(
+                w,
+                "---- dns {} {} via {} ----",
+                dns.plan.record_type,
+                dns.plan.name,
+                dns.server.as_deref().unwrap_or("unknown server"),
+            )?;
+            if let Some(e) = &dns.error {
+                writeln!(w, "error: {e}")?;
+            }
+            if let Some(rcode) = dns.rcode {
+                writeln!(
+                    w,
+                    "rcode: {}{}",
+                    dns.rcode_name()
+                        .map_or_else(|| rcode.to_string(), str::to_owned),
+                    if dns.truncated { " (truncated)" } else { "" },
+                )?;
+            }
+            for (section, records) in [
+                ("answer", &dns.answers),
+                ("authority", &dns.authority),
+                ("additional", &dns.additional),
+            ] {
+                for record in records {
+                    writeln!(
+                        w,
+                        "{section}: {}. {} {} {}",
+                        record.name, record.ttl, record.record_type, record.data,
+                    )?;
+                }
+            }
+            if let Some(latency) = &dns.latency {
+                writeln!(w, "latency: {}", latency.0)?;
+            }
+        }
+        if let Some(detect) = &self.detect {
+            writeln!(
+                w,
+                "---- detect {}:{} ----",
+                detect.plan.host, detect.plan.port
+            )?;
+            writeln!(w, "verdict: {}", detect.verdict)?;
+            if let Some(banner) = &detect.banner {
+                writeln!(w, "banner: {}", banner.to_string().trim_end())?;
+            }
+            if detect.tls {
+                writeln!(w, "tls alpn: {}", detect.alpn.as_deref().unwrap_or("none"))?;
+            }
+            if let Some(status_code) = detect.status_code {
+                writeln!(w, "status: {status_code}")?;
+            }
+            for e in &detect.errors {
+                writeln!(w, "error: {e}")?;
+            }
+        }
+        if let Some(takeover) = &self.takeover {
+            writeln!(w, "---- takeover via {} ----", takeover.resolver)?;
+            for host in &takeover.hosts {
+                writeln!(
+                    w,
+                    "{} -> {}{}",
+                    host.host,
+                    host.chain.join(" -> "),
+                    if host.nxdomain { " (nxdomain)" } else { "" },
+                )?;
+            }
+            for finding in &takeover.findings {
+                writeln!(
+                    w,
+                    "possible {} takeover of {} via {}: {}",
+                    finding.service, finding.host, finding.target, finding.evidence,
+                )?;
+            }
+        }
+        if let Some(banner) = &self.banner {
+            writeln!(w, "---- banner ----")?;
+            for result in &banner.results {
+                match (&result.banner, &result.error) {
+                    (Some(b), _) => writeln!(
+                        w,
+                        "{} open: {:?}",
+                        result.target,
+                        String::from_utf8_lossy(b)
+                    )?,
+                    (None, Some(e)) => writeln!(w, "{} error: {e}", result.target)?,
+                    (None, None) => writeln!(w, "{} open, no banner", result.target)?,
+                }
+            }
+        }
+        if let Some(session) = &self.session {
+            writeln!(w, "---- session {} ----", session.path)?;
+            for user in &session.users {
+                writeln!(
+                    w,
+                    "user {}: {} iterations, {} think time",
+                    user.user,
+                    user.iterations.len(),
+                    user.think_time.0,
+                )?;
+                if let Some(e) = &user.error {
+                    writeln!(w, "user {} error: {e}", user.user)?;
+                }
+            }
+        }
+        if let Some(command) = &self.command {
+            writeln!(
+                w,
+                "---- command {} {} ----",
+                command.plan.program,
+                command.plan.args.join(" "),
+            )?;
+            match command.exit_code {
+                _ if command.timed_out => writeln!(w, "timed out after {}", command.duration.0)?,
+                Some(code) => writeln!(w, "exited {code} after {}", command.duration.0)?,
+                None => writeln!(w, "killed by signal after {}", command.duration.0)?,
+            }
+            writeln!(w, "stdout: {:?}", String::from_utf8_lossy(&command.stdout))?;
+            writeln!(w, "stderr: {:?}", String::from_utf8_lossy(&command.stderr))?;
+            if command.truncated {
+                writeln!(w, "output truncated to {} bytes", command.plan.max_output)?;
+            }
+        }
+        if let Some(script) = &self.script {
+            writeln!(w, "---- script ----")?;
+            for line in &script.printed {
+                writeln!(w, "{line}")?;
+            }
+            for (name, value) in &script.locals {
+                writeln!(w, "set {name} = {value}")?;
+            }
+            writeln!(w, "result: {} in {}", script.result, script.duration.0)?;
+        }
+        if let Some(reflect) = &self.grpc_reflect {
+            writeln!(w, "---- grpc reflection {} ----", reflect.plan.url)?;
+            if let Some(version) = &reflect.version {
+                writeln!(
+                    w,
+                    "reflection {version}: {} services",
+                    reflect.services.len()
+                )?;
+            }
+            for method in &reflect.methods {
+                writeln!(
+                    w,
+                    "{} {}{}) returns ({}{})",
+                    method.path,
+                    if method.client_streaming {
+                        "(stream "
+                    } else {
+                        "("
+                    },
+                    method.input_type,
+                    if method.server_streaming {
+                        "stream "
+                    } else {
+                        ""
+                    },
+                    method.output_type,
+                )?;
+            }
+            if let Some(e) = &reflect.error {
+                writeln!(w, "error: {e}")?;
+            }
+        }
+        for (_, mirror) in &self.mirror {
+            writeln!(w, "---- mirror {} to {} ----", mirror.job.name, mirror.url)?;
+            mirror.job.describe(&mut w, layers)?;
+            writeln!(
+                w,
+                "status changed: {}, body changed: {}, length delta: {}",
+                mirror.status_changed, mirror.body_changed, mirror.length_delta,
+            )?;
+        }
+        if let Some(latency) = &self.latency {
+            for (phase, histogram) in [
+                ("connect", &latency.connect),
+                ("time to first byte", &latency.time_to_first_byte),
+                ("total", &latency.total),
+            ] {
+                if let Some(h) = histogram {
+                    writeln!(
+                        w,
+                        "{phase} latency over {} jobs: p50 {} p90 {} p99 {} max {}",
+                        h.count, h.p50.0, h.p90.0, h.p99.0, h.max.0,
+                    )?;
+                }
+            }
+        }
+        for r in &self.reflections {
+            write!(
+                w,
+                "marker {} from step {}{} {} in job {} {} {}",
+                r.marker,
+                r.source,
+                r.source_run
+                    .as_ref()
+                    .map_or_else(String::new, |run| format!(" of run {run}")),
+                if r.stored { "stored" } else { "reflected" },
+                r.job,
+                r.protocol,
+                r.location.as_str(),
+            )?;
+            if let Some(header) = &r.header {
+                write!(w, " {header}")?;
+            }
+            writeln!(w, " at offset {}", r.offset)?;
+        }
+        for d in &self.desync {
+            write!(
+                w,
+                "job {} {}: {}",
+                d.job,
+                d.kind.as_str().replace('_', " "),
+                d.detail
+            )?;
+            if let Some(bytes) = d.extra_bytes {
+                write!(w, " ({bytes} bytes)")?;
+            }
+            if let (Some(ttfb), Some(median)) =
+                (&d.time_to_first_byte, &d.median_time_to_first_byte)
+            {
+                write!(
+                    w,
+                    " (first byte after {} against a median of {})",
+                    ttfb.0, median.0
+                )?;
+            }
+            writeln!(w)?;
+        }
+        for e in &self.errors {
+            writeln!(w, "step {} {} error: {}", self.name, e.kind, e.message)?;
+        }
         Ok(())
     }
 }
@@ -448,8 +1466,10 @@ impl JobOutput {
             input
         } else if self.graphql.is_some() {
             &[ProtocolDiscriminants::Graphql]
-        //} else if proto.http3.is_some() {
-        //    vec![ProtocolDiscriminants::HTTP]
+        } else if self.grpc.is_some() {
+            &[ProtocolDiscriminants::Grpc]
+        } else if self.h3.is_some() {
+            &[ProtocolDiscriminants::H3]
         } else if self.h2.is_some() {
             &[ProtocolDiscriminants::H2]
         } else if self.h2c.is_some() {
@@ -470,6 +1490,10 @@ impl JobOutput {
             &[ProtocolDiscriminants::Tcp]
         } else if self.raw_tcp.is_some() {
             &[ProtocolDiscriminants::RawTcp]
+        } else if self.quic.is_some() {
+            &[ProtocolDiscriminants::Quic]
+        } else if self.ws.is_some() {
+            &[ProtocolDiscriminants::Ws]
         } else {
             &[]
         }
@@ -537,11 +1561,31 @@ impl Describe for JobOutput {
                         http.describe(&mut w, layers)?;
                     }
                 }
+                ProtocolDiscriminants::H3 => {
+                    if let Some(http) = &self.h3 {
+                        http.describe(&mut w, layers)?;
+                    }
+                }
+                ProtocolDiscriminants::Quic => {
+                    if let Some(quic) = &self.quic {
+                        quic.describe(&mut w, layers)?;
+                    }
+                }
+                ProtocolDiscriminants::Ws => {
+                    if let Some(ws) = &self.ws {
+                        ws.describe(&mut w, layers)?;
+                    }
+                }
                 ProtocolDiscriminants::Graphql => {
                     if let Some(graphql) = &self.graphql {
                         graphql.describe(&mut w, layers)?;
                     }
                 }
+                ProtocolDiscriminants::Grpc => {
+                    if let Some(grpc) = &self.grpc {
+                        grpc.describe(&mut w, layers)?;
+                    }
+                }
             }
         }
         Ok(())
@@ -604,6 +1648,41 @@ impl Describe for GraphqlResponse {
     }
 }
 
+impl Describe for GrpcOutput {
+    fn describe<W: Write>(
+        &self,
+        mut w: W,
+        layers: &[ProtocolDiscriminants],
+    ) -> std::io::Result<()> {
+        if !layers.contains(&ProtocolDiscriminants::Grpc) {
+            return Ok(());
+        }
+        writeln!(w, "> {}/{}", self.plan.service, self.plan.method)?;
+        if let Some(json) = &self.plan.json {
+            writeln!(w, "> {json}")?;
+        } else {
+            writeln!(w, "> {}", self.plan.message)?;
+        }
+        for msg in &self.messages {
+            if let Some(json) = &msg.json {
+                writeln!(w, "< {json}")?;
+            } else {
+                writeln!(w, "< {}", msg.data)?;
+            }
+        }
+        if let Some(status) = self.status {
+            writeln!(w, "grpc-status: {status}")?;
+        }
+        if let Some(message) = &self.status_message {
+            writeln!(w, "grpc-message: {message}")?;
+        }
+        for e in &self.errors {
+            writeln!(w, "{} error: {}", e.kind, e.message)?;
+        }
+        writeln!(w, "total duration: {}", self.duration.0)
+    }
+}
+
 impl Describe for Http2Output {
     fn describe<W: Write>(
         &self,
@@ -709,6 +1788,84 @@ impl Describe for Http2Response {
     }
 }
 
+impl Describe for Http3Output {
+    fn describe<W: Write>(
+        &self,
+        mut w: W,
+        layers: &[ProtocolDiscriminants],
+    ) -> std::io::Result<()> {
+        if !layers.contains(&ProtocolDiscriminants::H3) {
+            return Ok(());
+        }
+        if let Some(req) = &self.request {
+            req.describe(&mut w, layers)?;
+        }
+        if let Some(resp) = &self.response {
+            resp.describe(&mut w, layers)?;
+        }
+        for e in &self.errors {
+            writeln!(w, "{} error: {}", e.kind, e.message)?;
+        }
+        writeln!(w, "total duration: {}", self.duration.0)
+    }
+}
+
+impl Describe for Http3RequestOutput {
+    fn describe<W: Write>(
+        &self,
+        mut w: W,
+        layers: &[ProtocolDiscriminants],
+    ) -> std::io::Result<()> {
+        if !layers.contains(&ProtocolDiscriminants::H3) {
+            return Ok(());
+        }
+        writeln!(
+            w,
+            "> {}{}{} HTTP/3",
+            self.method.as_ref().unwrap_or_default(),
+            if self.method.is_some() { " " } else { "" },
+            self.url,
+        )?;
+        for header in &self.headers {
+            header.describe(&mut w, layers)?;
+        }
+        writeln!(w, "> {}", &self.body.to_string().replace("\n", "\n> "))?;
+        if let Some(id) = self.stream_id {
+            writeln!(w, "request stream id: {id}")?;
+        }
+        if let Some(ttfb) = &self.time_to_first_byte {
+            writeln!(w, "request time to first byte: {}", ttfb.0)?;
+        }
+        writeln!(w, "request duration: {}", self.duration.0)
+    }
+}
+
+impl Describe for Http3Response {
+    fn describe<W: Write>(
+        &self,
+        mut w: W,
+        layers: &[ProtocolDiscriminants],
+    ) -> std::io::Result<()> {
+        if !layers.contains(&ProtocolDiscriminants::H3) {
+            return Ok(());
+        }
+        writeln!(w, "< {} HTTP/3", self.status_code.unwrap_or(0))?;
+        if let Some(headers) = &self.headers {
+            for header in headers {
+                header.describe(&mut w, layers)?;
+            }
+        }
+        writeln!(w, "< ")?;
+        if let Some(body) = &self.body {
+            writeln!(w, "< {}", &body.to_string().replace("\n", "\n< "))?;
+        }
+        if let Some(ttfb) = &self.time_to_first_byte {
+            writeln!(w, "response time to first byte: {}", ttfb.0)?;
+        }
+        writeln!(w, "response duration: {}", self.duration.0)
+    }
+}
+
 impl Describe for Http1Output {
     fn describe<W: Write>(
         &self,
@@ -723,6 +1880,19 @@ impl Describe for Http1Output {
         if let Some(req) = &self.request {
             req.describe(&mut w, layers)?;
         }
+        for info in &self.informational_responses {
+            writeln!(w, "< {} after {}", info.status_code, info.time.0)?;
+        }
+        if let Some(outcome) = &self.expect_continue {
+            writeln!(w, "expect continue: {}", outcome.as_str())?;
+        }
+        if let Some(pipeline) = &self.pipeline {
+            writeln!(
+                w,
+                "pipelined as request {} with {} response bytes already read",
+                pipeline.index, pipeline.buffered_bytes,
+            )?;
+        }
         if let Some(resp) = &self.response {
             resp.describe(&mut w, layers)?;
         }
@@ -769,21 +1939,25 @@ impl Describe for Http1RequestOutput {
         {
             return Ok(());
         }
-        writeln!(
-            w,
-            "> {}{}{}{}{}",
-            self.method.as_ref().unwrap_or_default(),
-            if self.method.is_some() { " " } else { "" },
-            self.url,
-            if self.version_string.is_some() {
-                " "
-            } else {
-                ""
-            },
-            self.version_string.as_ref().unwrap_or_default(),
-        )?;
-        for header in &self.headers {
-            header.describe(&mut w, layers)?;
+        if let Some(head) = &self.raw_header {
+            writeln!(w, "> {}", head.to_string().trim_end().replace("\n", "\n> "))?;
+        } else {
+            writeln!(
+                w,
+                "> {}{}{}{}{}",
+                self.method.as_ref().unwrap_or_default(),
+                if self.method.is_some() { " " } else { "" },
+                self.url,
+                if self.version_string.is_some() {
+                    " "
+                } else {
+                    ""
+                },
+                self.version_string.as_ref().unwrap_or_default(),
+            )?;
+            for header in &self.headers {
+                header.describe(&mut w, layers)?;
+            }
         }
         writeln!(w, "> {}", &self.body.to_string().replace("\n", "\n> "))?;
         if let Some(ttfb) = &self.time_to_first_byte {
@@ -822,6 +1996,15 @@ impl Describe for Http1Response {
         if let Some(body) = &self.body {
             writeln!(w, "< {}", body.to_string().replace("\n", "\n< "))?;
         }
+        for deviation in &self.deviations {
+            writeln!(
+                w,
+                "response deviation {} at {}: {}",
+                deviation.kind.as_str(),
+                deviation.offset,
+                deviation.line,
+            )?;
+        }
         if let Some(ttfb) = &self.time_to_first_byte {
             writeln!(w, "response time to first byte: {}", ttfb.0)?;
         }
@@ -926,7 +2109,10 @@ impl Describe for HttpResponse {
             }
         }
         writeln!(w, "< ")?;
-        if let Some(body) = &self.body {
+        // Prefer the decoded text so non-UTF-8 bodies are readable.
+        if let Some(text) = &self.text {
+            writeln!(w, "< {}", text.replace("\n", "\n< "))?;
+        } else if let Some(body) = &self.body {
             writeln!(w, "< {}", &body.to_string().replace("\n", "\n< "))?;
         }
         if let Some(ttfb) = &self.time_to_first_byte {
@@ -967,6 +2153,20 @@ impl Describe for RawHttp2Output {
         for frame in &self.received {
             frame.describe(&mut w, layers)?;
         }
+        for (d, hpack) in [('>', &self.sent_hpack), ('<', &self.received_hpack)] {
+            for block in &hpack.blocks {
+                writeln!(w, "{d} header block on stream {}", block.stream_id)?;
+                for field in &block.fields {
+                    writeln!(w, "{d}   {}: {}", field.name, field.value)?;
+                }
+                for anomaly in &block.anomalies {
+                    writeln!(w, "{d}   anomaly: {anomaly}")?;
+                }
+            }
+            for anomaly in &hpack.anomalies {
+                writeln!(w, "{d} hpack anomaly: {anomaly}")?;
+            }
+        }
         Ok(())
     }
 }
@@ -987,6 +2187,9 @@ impl Describe for Http2FrameOutput {
             Direction::Recv => '<',
         };
         writeln!(w, "{d} type: {:}", self.payload.r#type())?;
+        if let Some(time) = &self.time {
+            writeln!(w, "{d} time: {}", time.0)?;
+        }
         writeln!(w, "{d} flags: {:#010b}", self.flags.bits())?;
         match &self.payload {
             Http2FramePayloadOutput::Data(frame) => {
@@ -1099,12 +2302,61 @@ impl Describe for TlsOutput {
         if !layers.contains(&ProtocolDiscriminants::Tls) {
             return Ok(());
         }
+        if let Some(name) = &self.plan.server_name {
+            writeln!(w, "server name: {name:?}")?;
+        }
         if let Some(req) = &self.sent {
             req.describe(&mut w, layers)?;
         }
         if let Some(resp) = &self.received {
             resp.describe(&mut w, layers)?;
         }
+        if let Some(alpn) = &self.alpn {
+            writeln!(w, "alpn: {alpn}")?;
+        }
+        if let Some(suite) = &self.cipher_suite {
+            writeln!(w, "cipher suite: {suite}")?;
+        }
+        if let Some(cert) = &self.certificate {
+            writeln!(
+                w,
+                "certificate serial {} spki sha256 {}",
+                cert.serial, cert.spki_sha256
+            )?;
+        }
+        for cert in &self.chain {
+            writeln!(
+                w,
+                "chain: subject {:?} issuer {:?} expires {}",
+                cert.subject,
+                cert.issuer,
+                cert.not_after.as_deref().unwrap_or("unknown"),
+            )?;
+        }
+        match self.verification {
+            TlsVerification::Insecure => {
+                writeln!(w, "certificate not verified since tls.insecure is set")?
+            }
+            TlsVerification::Custom => writeln!(w, "certificate verified by a custom verifier")?,
+            TlsVerification::Default | TlsVerification::CustomRoots => {}
+        }
+        if self.resumed {
+            match self.resumption {
+                Some(TlsResumption::SessionId) => writeln!(w, "resumed session by session id")?,
+                Some(TlsResumption::Ticket) => writeln!(w, "resumed session with a ticket")?,
+                None => writeln!(w, "resumed session")?,
+            }
+        }
+        for expect in self.expect.iter().filter(|expect| expect.passed) {
+            if let Some(time) = &expect.time {
+                writeln!(w, "expect {:?} passed at {}", expect.pattern, time.0)?;
+            }
+        }
+        for expect in &self.expect {
+            if let Some(teardown) = expect.teardown {
+                writeln!(w, "expect {:?} chose {teardown} teardown", expect.pattern)?;
+            }
+        }
         for e in &self.errors {
             writeln!(w, "{} error: {}", e.kind, e.message)?;
         }
@@ -1130,6 +2382,122 @@ impl Describe for TlsOutput {
     }
 }
 
+impl Describe for QuicOutput {
+    fn describe<W: Write>(
+        &self,
+        mut w: W,
+        layers: &[ProtocolDiscriminants],
+    ) -> std::io::Result<()> {
+        if !layers.contains(&ProtocolDiscriminants::Quic) {
+            return Ok(());
+        }
+        if let Some(remote) = &self.remote_addr {
+            writeln!(
+                w,
+                "quic connection {} -> {remote}",
+                self.local_addr.as_deref().unwrap_or("?"),
+            )?;
+        }
+        if let Some(alpn) = &self.alpn {
+            writeln!(w, "alpn: {alpn}")?;
+        }
+        if let Some(rtt) = &self.rtt {
+            writeln!(w, "rtt: {}", rtt.0)?;
+        }
+        writeln!(
+            w,
+            "packets sent: {} lost: {}",
+            self.sent_packets, self.lost_packets
+        )?;
+        for e in &self.errors {
+            writeln!(w, "{} error: {}", e.kind, e.message)?;
+        }
+        if let Some(handshake) = &self.handshake_duration {
+            writeln!(w, "handshake duration: {}", handshake.0)?;
+        }
+        writeln!(w, "total duration: {}", self.duration.0)
+    }
+}
+
+impl Describe for WebSocketOutput {
+    fn describe<W: Write>(
+        &self,
+        mut w: W,
+        layers: &[ProtocolDiscriminants],
+    ) -> std::io::Result<()> {
+        if !layers.contains(&ProtocolDiscriminants::Ws) {
+            return Ok(());
+        }
+        if let Some(handshake) = &self.handshake {
+            handshake.describe(&mut w, layers)?;
+        }
+        for frame in &self.frames {
+            frame.describe(&mut w, layers)?;
+        }
+        for e in &self.errors {
+            writeln!(w, "{} error: {}", e.kind, e.message)?;
+        }
+        writeln!(w, "total duration: {}", self.duration.0)
+    }
+}
+
+impl Describe for WebSocketHandshakeOutput {
+    fn describe<W: Write>(
+        &self,
+        mut w: W,
+        layers: &[ProtocolDiscriminants],
+    ) -> std::io::Result<()> {
+        if !layers.contains(&ProtocolDiscriminants::Ws) {
+            return Ok(());
+        }
+        writeln!(w, "> Sec-WebSocket-Key: {}", self.key)?;
+        writeln!(w, "< {} HTTP/1.1", self.status_code.unwrap_or(0))?;
+        if let Some(headers) = &self.headers {
+            for header in headers {
+                header.describe(&mut w, layers)?;
+            }
+        }
+        writeln!(w, "accept valid: {}", self.accept_valid)?;
+        writeln!(w, "handshake duration: {}", self.duration.0)
+    }
+}
+
+impl Describe for WebSocketFrameOutput {
+    fn describe<W: Write>(
+        &self,
+        mut w: W,
+        layers: &[ProtocolDiscriminants],
+    ) -> std::io::Result<()> {
+        if !layers.contains(&ProtocolDiscriminants::Ws) {
+            return Ok(());
+        }
+        let d = match self.direction {
+            Direction::Send => '>',
+            Direction::Recv => '<',
+        };
+        writeln!(
+            w,
+            "{d} {} fin: {} rsv: {:#05b} masked: {}",
+            self.opcode,
+            self.fin,
+            self.rsv,
+            self.masking_key.is_some(),
+        )?;
+        if let Some(code) = self.close_code {
+            writeln!(w, "{d}   close code: {code}")?;
+        }
+        if !self.payload.is_empty() {
+            let indent = format!("\n{d}   ");
+            writeln!(
+                w,
+                "{d}   {}",
+                self.payload.to_string().replace('\n', &indent)
+            )?;
+        }
+        writeln!(w, "{d}   time: {}", self.time.0)
+    }
+}
+
 impl Describe for TlsSentOutput {
     fn describe<W: Write>(
         &self,
@@ -1173,12 +2541,34 @@ impl Describe for TcpOutput {
         if !layers.contains(&ProtocolDiscriminants::Tcp) {
             return Ok(());
         }
+        for proxy in &self.proxies {
+            if let Some(code) = proxy.status_code {
+                writeln!(
+                    w,
+                    "proxy {}:{} replied {code} to CONNECT",
+                    proxy.host, proxy.port
+                )?;
+            }
+            if let Some(e) = &proxy.error {
+                writeln!(w, "proxy {}:{} error: {e}", proxy.host, proxy.port)?;
+            }
+        }
         if let Some(req) = &self.sent {
             req.describe(&mut w, layers)?;
         }
         if let Some(resp) = &self.received {
             resp.describe(&mut w, layers)?;
         }
+        for expect in self.expect.iter().filter(|expect| expect.passed) {
+            if let Some(time) = &expect.time {
+                writeln!(w, "expect {:?} passed at {}", expect.pattern, time.0)?;
+            }
+        }
+        for expect in &self.expect {
+            if let Some(teardown) = expect.teardown {
+                writeln!(w, "expect {:?} chose {teardown} teardown", expect.pattern)?;
+            }
+        }
         for e in &self.errors {
             writeln!(&mut w, "{} error: {}", e.kind, e.message)?;
         }
@@ -0,0 +1,202 @@
+//! Build a standalone plan that reproduces one step, like the one that reported a finding, so it
+//! can be re-run without the rest of the plan.
+
+use std::collections::BTreeSet;
+use std::sync::OnceLock;
+
+use anyhow::anyhow;
+use regex::Regex;
+use toml_edit::{DocumentMut, Item, TableLike};
+
+/// Build a plan reproducing `step` in `run` from a SQLite output, using the plan's text in `plan`.
+pub fn from_run(db: &str, run: &str, step: &str, plan: &str) -> anyhow::Result<String> {
+    let conn =
+        rusqlite::Connection::open_with_flags(db, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let record: String = conn
+        .query_row(
+            "SELECT record FROM steps WHERE run = ?1 AND step = ?2",
+            [run, step],
+            |row| row.get(0),
+        )
+        .map_err(|e| anyhow!("find step {step} in run {run}: {e}"))?;
+    reproduce(plan, step, Some(&serde_json::from_str(&record)?))
+}
+
+/// Build a plan that runs `step` from the plan in `text`, along with the settings and the earlier
+/// steps its expressions read from.
+///
+/// If the step's recorded output is given, each expression is replaced by the value it resolved
+/// to, as long as every job resolved it to the same text, number, or boolean. Steps only read by
+/// replaced expressions are left out. The step's run.if is dropped too, since it held.
+pub fn reproduce(
+    text: &str,
+    step: &str,
+    record: Option<&serde_json::Value>,
+) -> anyhow::Result<String> {
+    let plan: DocumentMut = text.parse()?;
+    let mut target = plan
+        .get(step)
+        .ok_or_else(|| anyhow!("plan has no step {step}"))?
+        .clone();
+    let jobs: Vec<&serde_json::Value> = record
+        .and_then(|record| record.get("jobs"))
+        .and_then(serde_json::Value::as_object)
+        .map(|jobs| jobs.values().collect())
+        .unwrap_or_default();
+    if let (false, Some(table)) = (jobs.is_empty(), target.as_table_like_mut()) {
+        for (protocol, fields) in table.iter_mut() {
+            let outputs: Option<Vec<_>> = jobs
+                .iter()
+                .map(|job| job.get(protocol.get())?.get("plan"))
+                .collect();
+            if let Some(outputs) = outputs {
+                inline(fields, &outputs);
+            }
+        }
+        if let Some(run) = table.get_mut("run").and_then(Item::as_table_like_mut) {
+            run.remove("if");
+        }
+    }
+
+    let mut pending = BTreeSet::new();
+    item_references(&target, false, &mut pending);
+    if let Some(devil) = plan.get("devil") {
+        item_references(devil, false, &mut pending);
+    }
+    let mut needed = BTreeSet::new();
+    while let Some(name) = pending.pop_first() {
+        if name == step || !needed.insert(name.clone()) {
+            continue;
+        }
+        let dependency = plan
+            .get(&name)
+            .ok_or_else(|| anyhow!("step {step} reads from missing step {name}"))?;
+        item_references(dependency, false, &mut pending);
+    }
+
+    let mut out = DocumentMut::new();
+    if let Some(devil) = plan.get("devil") {
+        out.insert("devil", devil.clone());
+    }
+    if let Some(name) = out.get_mut("devil").and_then(|devil| devil.get_mut("name")) {
+        if let Some(plan_name) = name.as_str() {
+            *name = toml_edit::value(format!("{plan_name}_repro"));
+        }
+    }
+    for (name, item) in plan.iter() {
+        if needed.contains(name) {
+            out.insert(name, item.clone());
+        }
+    }
+    out.insert(step, target);
+    Ok(out.to_string())
+}
+
+/// Replace expressions in a plan table with the values recorded for them in the matching outputs
+/// of each job.
+fn inline(item: &mut Item, outputs: &[&serde_json::Value]) {
+    let Some(table) = item.as_table_like_mut() else {
+        return;
+    };
+    let keys: Vec<String> = table.iter().map(|(key, _)| key.to_owned()).collect();
+    for key in keys {
+        let Some(outputs) = outputs
+            .iter()
+            .map(|output| field(output, &key))
+            .collect::<Option<Vec<_>>>()
+        else {
+            continue;
+        };
+        let Some(item) = table.get_mut(&key) else {
+            continue;
+        };
+        let expression = item
+            .as_table_like()
+            .is_some_and(|fields| fields.contains_key("cel") || fields.contains_key("vars"));
+        if !expression {
+            inline(item, &outputs);
+            continue;
+        }
+        let same = outputs.windows(2).all(|pair| pair[0] == pair[1]);
+        if let Some(value) = outputs.first().filter(|_| same).and_then(|x| literal(x)) {
+            *item = Item::Value(value);
+        }
+    }
+}
+
+fn field<'a>(output: &'a serde_json::Value, key: &str) -> Option<&'a serde_json::Value> {
+    match output {
+        serde_json::Value::Object(fields) => fields.get(key),
+        // Headers are recorded as a list of pairs.
+        serde_json::Value::Array(entries) => entries
+            .iter()
+            .find(|entry| {
+                entry
+                    .pointer("/key/utf8")
+                    .and_then(serde_json::Value::as_str)
+                    == Some(key)
+            })?
+            .get("value"),
+        _ => None,
+    }
+}
+
+/// Convert a recorded value to a plan literal. Bytes that aren't UTF-8 have no literal form.
+fn literal(value: &serde_json::Value) -> Option<toml_edit::Value> {
+    match value {
+        serde_json::Value::Bool(b) => Some((*b).into()),
+        serde_json::Value::String(s) => Some(s.as_str().into()),
+        serde_json::Value::Number(n) => n
+            .as_i64()
+            .map(Into::into)
+            .or_else(|| n.as_f64().map(Into::into)),
+        serde_json::Value::Object(fields) if fields.len() == 1 => {
+            fields.get("utf8")?.as_str().map(Into::into)
+        }
+        _ => None,
+    }
+}
+
+/// Add the names of steps read by expressions in item to out.
+pub(crate) fn item_references(item: &Item, expression: bool, out: &mut BTreeSet<String>) {
+    match item {
+        Item::Value(value) => value_references(value, expression, out),
+        Item::Table(table) => table_references(table, expression, out),
+        Item::ArrayOfTables(tables) => {
+            for table in tables.iter() {
+                table_references(table, expression, out);
+            }
+        }
+        Item::None => {}
+    }
+}
+
+fn table_references(table: &dyn TableLike, expression: bool, out: &mut BTreeSet<String>) {
+    for (key, item) in table.iter() {
+        item_references(item, expression || key == "cel" || key == "vars", out);
+    }
+}
+
+fn value_references(value: &toml_edit::Value, expression: bool, out: &mut BTreeSet<String>) {
+    static STEPS: OnceLock<Regex> = OnceLock::new();
+    let steps = STEPS.get_or_init(|| {
+        Regex::new(r#"\bsteps\s*(?:\.\s*([A-Za-z_][A-Za-z0-9_]*)|\[\s*["']([^"']+)["']\s*\])"#)
+            .expect("steps regex should be valid")
+    });
+    match value {
+        toml_edit::Value::String(s) if expression => {
+            for captures in steps.captures_iter(s.value()) {
+                if let Some(name) = captures.get(1).or_else(|| captures.get(2)) {
+                    out.insert(name.as_str().to_owned());
+                }
+            }
+        }
+        toml_edit::Value::Array(values) => {
+            for value in values.iter() {
+                value_references(value, expression, out);
+            }
+        }
+        toml_edit::Value::InlineTable(table) => table_references(table, expression, out),
+        _ => {}
+    }
+}
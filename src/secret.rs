@@ -0,0 +1,427 @@
+//! Credentials which plans refer to by name with the `secret` CEL function instead of containing
+//! them. Names are looked up from the configured providers while the plan runs, and the values
+//! found are redacted from written records.
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use anyhow::{anyhow, bail};
+use base64::prelude::{BASE64_STANDARD, BASE64_URL_SAFE_NO_PAD};
+use base64::Engine;
+use itertools::Itertools;
+use serde::Serialize;
+
+/// Written in place of secret values.
+pub(crate) const REDACTED: &str = "[redacted]";
+
+/// A source of secret values.
+pub trait SecretProvider: Debug + Send + Sync {
+    /// Look up a secret, returning None if this provider doesn't have it.
+    fn get(&self, name: &str) -> anyhow::Result<Option<String>>;
+}
+
+/// Reads secrets from environment variables named by a prefix and the secret's name in upper
+/// case, like `DEVIL_SECRET_API_TOKEN` for `api_token`.
+#[derive(Debug)]
+pub struct EnvSecrets {
+    prefix: String,
+}
+
+impl EnvSecrets {
+    pub fn new(prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+        }
+    }
+}
+
+impl Default for EnvSecrets {
+    fn default() -> Self {
+        Self::new("DEVIL_SECRET_")
+    }
+}
+
+impl SecretProvider for EnvSecrets {
+    fn get(&self, name: &str) -> anyhow::Result<Option<String>> {
+        let var: String = name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_uppercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        match std::env::var(self.prefix.clone() + &var) {
+            Ok(value) => Ok(Some(value)),
+            Err(std::env::VarError::NotPresent) => Ok(None),
+            Err(e) => Err(anyhow!("read {}{var}: {e}", self.prefix)),
+        }
+    }
+}
+
+/// Reads each secret from the file of the same name in a directory, as mounted by container
+/// orchestrators. A single trailing newline is removed.
+#[derive(Debug)]
+pub struct FileSecrets {
+    dir: PathBuf,
+}
+
+impl FileSecrets {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+impl SecretProvider for FileSecrets {
+    fn get(&self, name: &str) -> anyhow::Result<Option<String>> {
+        // Names which aren't plain file names, like Vault paths, can't be in the directory.
+        let mut components = Path::new(name).components();
+        if !matches!(
+            (components.next(), components.next()),
+            (Some(Component::Normal(_)), None)
+        ) {
+            return Ok(None);
+        }
+        let path = self.dir.join(name);
+        match std::fs::read_to_string(&path) {
+            Ok(mut value) => {
+                if value.ends_with('\n') {
+                    value.pop();
+                    if value.ends_with('\r') {
+                        value.pop();
+                    }
+                }
+                Ok(Some(value))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(anyhow!("read {}: {e}", path.display())),
+        }
+    }
+}
+
+/// Reads secrets from the OS keychain, stored as passwords for a service with the secret's name
+/// as the user.
+#[cfg(feature = "keychain")]
+#[derive(Debug)]
+pub struct KeychainSecrets {
+    service: String,
+}
+
+#[cfg(feature = "keychain")]
+impl KeychainSecrets {
+    pub fn new(service: impl Into<String>) -> Self {
+        Self {
+            service: service.into(),
+        }
+    }
+}
+
+#[cfg(feature = "keychain")]
+impl SecretProvider for KeychainSecrets {
+    fn get(&self, name: &str) -> anyhow::Result<Option<String>> {
+        match keyring::Entry::new(&self.service, name)?.get_password() {
+            Ok(value) => Ok(Some(value)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(anyhow!("read {name} from keychain: {e}")),
+        }
+    }
+}
+
+/// Reads secrets from a HashiCorp Vault KV version 2 engine. Names are a secret's path, with the
+/// field to read after a `#`, like `services/api#token`. The field defaults to `value`.
+#[cfg(feature = "vault")]
+#[derive(Debug)]
+pub struct VaultSecrets {
+    addr: url::Url,
+    token: crate::Secret,
+    mount: String,
+}
+
+#[cfg(feature = "vault")]
+impl VaultSecrets {
+    pub fn new(addr: url::Url, token: String, mount: impl Into<String>) -> Self {
+        Self {
+            addr,
+            token: crate::Secret(token),
+            mount: mount.into(),
+        }
+    }
+
+    /// Connect to the server at VAULT_ADDR with the token in VAULT_TOKEN.
+    pub fn from_env(mount: impl Into<String>) -> anyhow::Result<Self> {
+        let addr = std::env::var("VAULT_ADDR").map_err(|e| anyhow!("read VAULT_ADDR: {e}"))?;
+        let token = std::env::var("VAULT_TOKEN").map_err(|e| anyhow!("read VAULT_TOKEN: {e}"))?;
+        Ok(Self::new(addr.parse()?, token, mount))
+    }
+
+    async fn read(&self, path: &str, field: &str) -> anyhow::Result<Option<String>> {
+        let url = self.addr.join(&format!("v1/{}/data/{path}", self.mount))?;
+        // Each lookup runs on its own runtime, so a pooled connection from an earlier one couldn't be
+        // reused.
+        let resp = reqwest::Client::new()
+            .get(url)
+            .header("X-Vault-Token", self.token.expose())
+            .send()
+            .await?;
+        if resp.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let body: serde_json::Value = resp.error_for_status()?.json().await?;
+        Ok(body["data"]["data"][field].as_str().map(str::to_owned))
+    }
+}
+
+#[cfg(feature = "vault")]
+impl SecretProvider for VaultSecrets {
+    fn get(&self, name: &str) -> anyhow::Result<Option<String>> {
+        let (path, field) = name.split_once('#').unwrap_or((name, "value"));
+        // CEL functions are synchronous, and block_in_place panics on a current_thread runtime, so
+        // make the request on a thread of its own with its own runtime.
+        std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    tokio::runtime::Builder::new_current_thread()
+                        .enable_all()
+                        .build()?
+                        .block_on(self.read(path, field))
+                })
+                .join()
+                .map_err(|_| anyhow!("vault request panicked"))?
+        })
+        .map_err(|e| anyhow!("read {name} from vault: {e:#}"))
+    }
+}
+
+#[derive(Debug)]
+struct Registry {
+    providers: Vec<Box<dyn SecretProvider>>,
+    /// Values looked up so far by name, which are reused and redacted.
+    resolved: HashMap<String, Arc<String>>,
+    /// Values built from secrets, like encoded credentials, which are also redacted.
+    derived: HashSet<String>,
+}
+
+fn registry() -> &'static Mutex<Registry> {
+    static REGISTRY: OnceLock<Mutex<Registry>> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        Mutex::new(Registry {
+            providers: vec![Box::new(EnvSecrets::default())],
+            resolved: HashMap::new(),
+            derived: HashSet::new(),
+        })
+    })
+}
+
+/// Look up secrets from `providers`, asking each in order, instead of from the environment.
+/// Names already looked up keep their values, which are still redacted.
+pub fn set_providers(providers: Vec<Box<dyn SecretProvider>>) {
+    registry().lock().unwrap().providers = providers;
+}
+
+/// Look up a secret by name. Each name is only looked up once.
+pub fn resolve(name: &str) -> anyhow::Result<Arc<String>> {
+    let mut guard = registry().lock().unwrap();
+    let registry = &mut *guard;
+    if let Some(value) = registry.resolved.get(name) {
+        return Ok(value.clone());
+    }
+    for provider in &registry.providers {
+        if let Some(value) = provider.get(name)? {
+            let value = Arc::new(value);
+            registry.resolved.insert(name.to_owned(), value.clone());
+            return Ok(value);
+        }
+    }
+    bail!("secret {name} not found")
+}
+
+/// Redact `value` too, for values built from secrets which don't contain them literally, like
+/// encoded credentials.
+pub fn register(value: impl Into<String>) {
+    let value = value.into();
+    if !value.is_empty() {
+        registry().lock().unwrap().derived.insert(value);
+    }
+}
+
+/// Redact `encoded` too if `input` contains a secret.
+pub fn register_encoding(input: &[u8], encoded: &str) {
+    if patterns()
+        .iter()
+        .any(|pattern| input.windows(pattern.len()).any(|w| w == pattern))
+    {
+        register(encoded);
+    }
+}
+
+/// The byte strings to redact: each secret value as is, as escaped in JSON, and in base64.
+fn patterns() -> Vec<Vec<u8>> {
+    let registry = registry().lock().unwrap();
+    registry
+        .resolved
+        .values()
+        .map(|value| value.as_str())
+        .chain(registry.derived.iter().map(String::as_str))
+        .filter(|value| !value.is_empty())
+        .flat_map(|value| {
+            let escaped = serde_json::to_string(value).unwrap_or_default();
+            let escaped = escaped.trim_matches('"');
+            [
+                value.as_bytes().to_vec(),
+                escaped.as_bytes().to_vec(),
+                BASE64_STANDARD.encode(value).into_bytes(),
+                BASE64_URL_SAFE_NO_PAD.encode(value).into_bytes(),
+            ]
+        })
+        .unique()
+        .collect()
+}
+
+thread_local! {
+    /// The patterns to redact while serializing for output, set by [`redacting`].
+    static REDACTING: RefCell<Option<Arc<Vec<Vec<u8>>>>> = const { RefCell::new(None) };
+}
+
+/// Serialize for output inside `f`, so byte fields have secrets redacted before they're encoded.
+/// Outside of it outputs serialize as is, since CEL expressions see them that way.
+pub fn redacting<T>(f: impl FnOnce() -> T) -> T {
+    struct Reset(Option<Arc<Vec<Vec<u8>>>>);
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            REDACTING.with_borrow_mut(|current| *current = self.0.take());
+        }
+    }
+    let patterns = Arc::new(patterns());
+    let _reset = Reset(REDACTING.with_borrow_mut(|current| current.replace(patterns)));
+    f()
+}
+
+/// Redact `bytes` if serializing inside [`redacting`].
+pub(crate) fn redact_for_output(bytes: &[u8]) -> Cow<'_, [u8]> {
+    match REDACTING.with_borrow(Clone::clone) {
+        Some(patterns) => replace_patterns(bytes, &patterns),
+        None => Cow::Borrowed(bytes),
+    }
+}
+
+/// Convert an output to JSON with secret values redacted, for anything leaving the executor.
+pub fn to_json<T: Serialize + ?Sized>(output: &T) -> serde_json::Result<serde_json::Value> {
+    let mut value = redacting(|| serde_json::to_value(output))?;
+    redact_json(&mut value);
+    Ok(value)
+}
+
+/// Replace each secret value looked up so far in written output with a placeholder.
+pub fn redact(output: &[u8]) -> Cow<'_, [u8]> {
+    replace_patterns(output, &patterns())
+}
+
+fn replace_patterns<'a>(bytes: &'a [u8], patterns: &[Vec<u8>]) -> Cow<'a, [u8]> {
+    let mut bytes = Cow::Borrowed(bytes);
+    for pattern in patterns {
+        if let Some(replaced) = replace_all(&bytes, pattern) {
+            bytes = Cow::Owned(replaced);
+        }
+    }
+    bytes
+}
+
+/// Replace each secret value looked up so far in the strings of a JSON record with a placeholder.
+pub fn redact_json(value: &mut serde_json::Value) {
+    let patterns = patterns();
+    if patterns.is_empty() {
+        return;
+    }
+    fn visit(value: &mut serde_json::Value, patterns: &[Vec<u8>]) {
+        match value {
+            serde_json::Value::String(s) => {
+                for pattern in patterns {
+                    if let Some(replaced) = replace_all(s.as_bytes(), pattern) {
+                        *s = String::from_utf8_lossy(&replaced).into_owned();
+                    }
+                }
+            }
+            serde_json::Value::Array(values) => {
+                values.iter_mut().for_each(|v| visit(v, patterns));
+            }
+            serde_json::Value::Object(fields) => {
+                fields.values_mut().for_each(|v| visit(v, patterns));
+            }
+            _ => {}
+        }
+    }
+    visit(value, &patterns);
+}
+
+/// Replace each occurrence of `pattern` in `haystack`, returning None if there were none.
+fn replace_all(haystack: &[u8], pattern: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut rest = haystack;
+    while let Some(i) = rest.windows(pattern.len()).position(|w| w == pattern) {
+        out.extend_from_slice(&rest[..i]);
+        out.extend_from_slice(REDACTED.as_bytes());
+        rest = &rest[i + pattern.len()..];
+    }
+    if out.is_empty() {
+        return None;
+    }
+    out.extend_from_slice(rest);
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MaybeUtf8;
+
+    #[test]
+    fn test_redacts_registered_values_and_encodings() {
+        register("registered-secret-value");
+        assert_eq!(
+            redact(b"a=registered-secret-value").as_ref(),
+            b"a=[redacted]"
+        );
+        let encoded = BASE64_STANDARD.encode("registered-secret-value");
+        assert_eq!(
+            redact(format!("Basic {encoded}").as_bytes()).as_ref(),
+            b"Basic [redacted]"
+        );
+    }
+
+    #[test]
+    fn test_register_encoding() {
+        register("encoded-secret-value");
+        let input = b"user:encoded-secret-value";
+        let encoded = BASE64_STANDARD.encode(input);
+        register_encoding(input, &encoded);
+        assert_eq!(redact(encoded.as_bytes()).as_ref(), REDACTED.as_bytes());
+        // Encodings of values without secrets are left alone.
+        register_encoding(b"public", "cHVibGlj");
+        assert_eq!(redact(b"cHVibGlj").as_ref(), b"cHVibGlj");
+    }
+
+    #[test]
+    fn test_to_json_redacts_bytes() {
+        register("binary-secret-value");
+        let mut bytes = vec![0xff, 0];
+        bytes.extend_from_slice(b"binary-secret-value");
+        let output = MaybeUtf8(bytes.into());
+        let mut expected = vec![0xff, 0];
+        expected.extend_from_slice(REDACTED.as_bytes());
+        assert_eq!(
+            to_json(&output).unwrap(),
+            serde_json::json!({ "base64": BASE64_STANDARD.encode(expected) })
+        );
+        // Outside of output the value is kept, since CEL expressions read it.
+        assert_eq!(
+            serde_json::to_value(&output).unwrap()["base64"],
+            BASE64_STANDARD.encode(output.as_bytes())
+        );
+    }
+}
@@ -0,0 +1,143 @@
+//! Reduce an assessment run to a smoke profile: a small plan which repeats only the requests that
+//! worked, with their expectations, so the same API can be monitored once the assessment is over.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::anyhow;
+use toml_edit::{DocumentMut, Item};
+
+use crate::repro::item_references;
+
+/// Protocols whose outputs have a response with a status code.
+const PROTOCOLS: [&str; 6] = ["http", "h1", "h1c", "h2", "h2c", "h3"];
+
+/// Settings in run which only multiply the requests a step sends.
+const LOAD_SETTINGS: [&str; 3] = ["count", "parallel", "adaptive"];
+
+/// Build a smoke profile from `run` in a SQLite output, using the plan's text in `plan`.
+pub fn from_run(db: &str, run: &str, plan: &str) -> anyhow::Result<String> {
+    let conn =
+        rusqlite::Connection::open_with_flags(db, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let mut stmt = conn.prepare("SELECT step, record FROM steps WHERE run = ?1")?;
+    let mut records = BTreeMap::new();
+    let mut rows = stmt.query([run])?;
+    while let Some(row) = rows.next()? {
+        let record: String = row.get(1)?;
+        records.insert(row.get(0)?, serde_json::from_str(&record)?);
+    }
+    if records.is_empty() {
+        anyhow::bail!("no steps stored for run {run}");
+    }
+    profile(plan, &records)
+}
+
+/// Build a smoke profile from the plan in `text` and the recorded output of each step that ran,
+/// keyed by step name.
+///
+/// A step is kept if every job got a 2xx or 3xx response, nothing reported an error, each of its
+/// expectations passed, and it found no reflections or desync. If the plan has logins in
+/// devil.auth only steps sent with one are kept, since the rest were probing what happens without
+/// credentials. Destructive steps are left out, but the steps that kept ones read from are brought
+/// along whether or not they qualified. Kept steps drop run.count, run.parallel, and run.adaptive
+/// so each request is only sent once per job.
+pub fn profile(
+    text: &str,
+    records: &BTreeMap<String, serde_json::Value>,
+) -> anyhow::Result<String> {
+    let plan: DocumentMut = text.parse()?;
+    let authenticated = plan
+        .get("devil")
+        .and_then(|devil| devil.get("auth"))
+        .and_then(Item::as_table_like)
+        .is_some_and(|auth| !auth.is_empty());
+
+    let mut kept = BTreeSet::new();
+    for (name, step) in plan.iter() {
+        if name == "devil" || step.get("destructive").and_then(Item::as_bool) == Some(true) {
+            continue;
+        }
+        let Some(record) = records.get(name) else {
+            continue;
+        };
+        if succeeded(record) && (!authenticated || !record["auth"].is_null()) {
+            kept.insert(name.to_owned());
+        }
+    }
+    if kept.is_empty() {
+        anyhow::bail!("no steps succeeded in the run");
+    }
+
+    let mut pending = BTreeSet::new();
+    for name in &kept {
+        item_references(&plan[name.as_str()], false, &mut pending);
+    }
+    if let Some(devil) = plan.get("devil") {
+        item_references(devil, false, &mut pending);
+    }
+    while let Some(name) = pending.pop_first() {
+        if !kept.insert(name.clone()) {
+            continue;
+        }
+        let dependency = plan
+            .get(&name)
+            .ok_or_else(|| anyhow!("plan reads from missing step {name}"))?;
+        item_references(dependency, false, &mut pending);
+    }
+
+    let mut out = DocumentMut::new();
+    if let Some(devil) = plan.get("devil") {
+        out.insert("devil", devil.clone());
+    }
+    if let Some(name) = out.get_mut("devil").and_then(|devil| devil.get_mut("name")) {
+        if let Some(plan_name) = name.as_str() {
+            *name = toml_edit::value(format!("{plan_name}_smoke"));
+        }
+    }
+    for (name, item) in plan.iter() {
+        if name == "devil" || !kept.contains(name) {
+            continue;
+        }
+        let mut item = item.clone();
+        if let Some(run) = item.get_mut("run").and_then(Item::as_table_like_mut) {
+            for setting in LOAD_SETTINGS {
+                run.remove(setting);
+            }
+        }
+        out.insert(name, item);
+    }
+    Ok(out.to_string())
+}
+
+/// Whether a recorded step ran cleanly and every job got a successful response.
+fn succeeded(record: &serde_json::Value) -> bool {
+    let empty = |value: &serde_json::Value| value.as_array().map_or(true, Vec::is_empty);
+    if !empty(&record["errors"]) || !empty(&record["reflections"]) || !empty(&record["desync"]) {
+        return false;
+    }
+    let Some(jobs) = record["jobs"].as_object().filter(|jobs| !jobs.is_empty()) else {
+        return false;
+    };
+    jobs.values().all(|job| {
+        let Some(outputs) = job.as_object() else {
+            return false;
+        };
+        let clean = outputs.values().all(|output| {
+            // GraphQL reports errors from the server in its response.
+            empty(&output["errors"])
+                && empty(&output["response"]["errors"])
+                && output["expect"].as_array().map_or(true, |expect| {
+                    expect
+                        .iter()
+                        .all(|e| e["passed"].as_bool().unwrap_or_default())
+                })
+        });
+        let mut statuses = PROTOCOLS
+            .iter()
+            .filter_map(|protocol| outputs.get(*protocol))
+            .map(|output| output.pointer("/response/status_code")?.as_u64())
+            .peekable();
+        clean
+            && statuses.peek().is_some()
+            && statuses.all(|status| status.is_some_and(|s| (200..400).contains(&s)))
+    })
+}
@@ -0,0 +1,223 @@
+//! Support for testing runners against scripted peers and golden output files.
+
+use std::collections::VecDeque;
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use anyhow::{anyhow, bail};
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use regex::Regex;
+use serde::Serialize;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::task::JoinHandle;
+
+use crate::exec::socket::{BoxStream, SocketProvider};
+use crate::exec::{self, Executor};
+use crate::{Plan, RunName, RunOutput};
+
+/// Set to rewrite golden files with the current output instead of comparing against them.
+pub const UPDATE_ENV: &str = "DEVIL_UPDATE_GOLDEN";
+
+/// Fields whose values change between runs and are replaced before comparing with golden files.
+const VOLATILE_FIELDS: &[&str] = &[
+    "duration",
+    "handshake_duration",
+    "header_duration",
+    "body_duration",
+    "time_to_first_byte",
+    "time_to_last_byte",
+    "src_port",
+    "sent",
+    "received",
+    // raw_tcp picks a random initial sequence number unless the plan sets one.
+    "isn",
+];
+
+/// The address every host resolves to under a [`ScriptedSocketProvider`].
+const SCRIPTED_PEER_IP: [u8; 4] = [192, 0, 2, 1];
+
+/// The bytes a scripted peer sends for one connection.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptedConnection {
+    pub response: Bytes,
+}
+
+impl ScriptedConnection {
+    pub fn new(response: impl Into<Bytes>) -> Self {
+        Self {
+            response: response.into(),
+        }
+    }
+}
+
+/// A [`SocketProvider`] which answers each connection in order with the next scripted peer,
+/// recording the bytes it receives.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptedSocketProvider {
+    script: Arc<Mutex<VecDeque<ScriptedConnection>>>,
+    peers: Arc<Mutex<Vec<JoinHandle<Bytes>>>>,
+}
+
+impl ScriptedSocketProvider {
+    pub fn new(script: impl IntoIterator<Item = ScriptedConnection>) -> Self {
+        Self {
+            script: Arc::new(Mutex::new(script.into_iter().collect())),
+            peers: Arc::default(),
+        }
+    }
+
+    /// Wait for the connected peers to close and return the bytes each received, in connection
+    /// order.
+    pub async fn received(&self) -> Vec<Bytes> {
+        let peers = std::mem::take(&mut *self.peers.lock().unwrap());
+        let mut out = Vec::with_capacity(peers.len());
+        for peer in peers {
+            out.push(peer.await.expect("scripted peer should not panic"));
+        }
+        out
+    }
+}
+
+impl SocketProvider for ScriptedSocketProvider {
+    fn connect_tcp(
+        &self,
+        _: SocketAddr,
+        remote_addr: SocketAddr,
+    ) -> BoxFuture<'static, io::Result<BoxStream>> {
+        let next = self.script.lock().unwrap().pop_front();
+        let peers = self.peers.clone();
+        Box::pin(async move {
+            let Some(conn) = next else {
+                return Err(io::Error::new(
+                    io::ErrorKind::ConnectionRefused,
+                    format!("no scripted peer left for {remote_addr}"),
+                ));
+            };
+            let (client, server) = tokio::io::duplex(64 * 1024);
+            let peer = tokio::spawn(async move {
+                let (mut reader, mut writer) = tokio::io::split(server);
+                let write = async {
+                    writer.write_all(&conn.response).await?;
+                    writer.shutdown().await
+                };
+                let read = async {
+                    let mut buf = Vec::new();
+                    reader.read_to_end(&mut buf).await?;
+                    Ok::<_, io::Error>(buf)
+                };
+                let (_, buf) = tokio::join!(write, read);
+                Bytes::from(buf.unwrap_or_default())
+            });
+            peers.lock().unwrap().push(peer);
+            Ok(Box::new(client) as BoxStream)
+        })
+    }
+
+    /// Every host resolves to the documentation address 192.0.2.1, so runs never touch the
+    /// system resolver.
+    fn lookup_host(&self, _: String, port: u16) -> BoxFuture<'static, io::Result<Vec<SocketAddr>>> {
+        Box::pin(async move { Ok(vec![SocketAddr::from((SCRIPTED_PEER_IP, port))]) })
+    }
+
+    fn capture_raw_tcp(&self) -> bool {
+        false
+    }
+}
+
+/// Parse and run a plan to completion using the given socket provider.
+pub async fn run_plan(input: &str, sockets: Arc<dyn SocketProvider>) -> crate::Result<RunOutput> {
+    let plan = Plan::parse(input)?;
+    let mut output = RunOutput::new(RunName::new(plan.name.clone()));
+    let mut executor = Executor::new(&plan, output.name.clone())?.with_socket_provider(sockets);
+    loop {
+        match executor.next().await {
+            Ok(step) => {
+                output.steps.insert(step.name.step.clone(), Arc::new(step));
+            }
+            Err(e) if matches!(e.downcast_ref(), Some(exec::Error::Done)) => {
+                output.usage = Some(executor.usage());
+                return Ok(output);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Compares serialized outputs against JSON files in a directory.
+#[derive(Debug, Clone)]
+pub struct Golden {
+    dir: PathBuf,
+}
+
+impl Golden {
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        Self {
+            dir: dir.as_ref().to_owned(),
+        }
+    }
+
+    /// Check that `value` matches the golden file `name`.json. The file is written instead if
+    /// [`UPDATE_ENV`] is set, and a missing file is an error otherwise.
+    pub fn check<T: Serialize>(&self, name: &str, value: &T) -> crate::Result<()> {
+        let mut actual = serde_json::to_value(value)?;
+        redact(&mut actual);
+        let actual = serde_json::to_string_pretty(&actual)? + "\n";
+
+        let path = self.dir.join(name).with_extension("json");
+        if std::env::var_os(UPDATE_ENV).is_some() {
+            std::fs::create_dir_all(&self.dir)?;
+            std::fs::write(&path, actual)?;
+            return Ok(());
+        }
+        let expected = std::fs::read_to_string(&path).map_err(|e| {
+            anyhow!(
+                "read golden file {} (set {UPDATE_ENV}=1 to create it): {e}",
+                path.display()
+            )
+        })?;
+        if expected != actual {
+            bail!(
+                "output doesn't match golden file {} (set {UPDATE_ENV}=1 to update)\nexpected:\n{expected}\nactual:\n{actual}",
+                path.display()
+            );
+        }
+        Ok(())
+    }
+}
+
+fn redact(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (k, v) in map.iter_mut() {
+                if VOLATILE_FIELDS.contains(&k.as_str()) && is_volatile(v) {
+                    *v = serde_json::Value::String("[redacted]".to_owned());
+                } else {
+                    redact(v);
+                }
+            }
+        }
+        serde_json::Value::Array(list) => list.iter_mut().for_each(redact),
+        // Output names embed the run's ksuid after the plan name.
+        serde_json::Value::String(s) => {
+            static RUN_ID: OnceLock<Regex> = OnceLock::new();
+            let run_id = RUN_ID.get_or_init(|| Regex::new(r"^([^.]+)\.[0-9A-Za-z]{27}").unwrap());
+            let replaced = run_id.replace(s, "$1.[run]").into_owned();
+            *s = replaced;
+        }
+        _ => {}
+    }
+}
+
+// Some volatile field names are reused for structured outputs, like tcp.sent, which should still
+// be compared.
+fn is_volatile(value: &serde_json::Value) -> bool {
+    !matches!(
+        value,
+        serde_json::Value::Object(_) | serde_json::Value::Array(_)
+    ) || value
+        .as_object()
+        .is_some_and(|o| o.contains_key("secs") && o.contains_key("nanos"))
+}
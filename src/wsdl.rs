@@ -0,0 +1,308 @@
+//! Generate a plan from a WSDL with a step for each SOAP operation, as a starting point for
+//! assessing legacy web services.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+use anyhow::{anyhow, bail};
+use sxd_document::dom::{ChildOfElement, ChildOfRoot, Element};
+
+use crate::cel_functions::xml_escape;
+
+const WSDL: &str = "http://schemas.xmlsoap.org/wsdl/";
+const SOAP11: &str = "http://schemas.xmlsoap.org/wsdl/soap/";
+const SOAP12: &str = "http://schemas.xmlsoap.org/wsdl/soap12/";
+const XSD: &str = "http://www.w3.org/2001/XMLSchema";
+
+/// An operation on one SOAP port of a service.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Operation {
+    pub name: String,
+    pub port: String,
+    pub endpoint: String,
+    pub action: Option<String>,
+    pub soap12: bool,
+    /// The namespace and local name of the request's body element.
+    pub element: (String, String),
+    /// The child elements of the body element, if the schema defines them inline.
+    pub fields: Vec<String>,
+    /// Whether the fields are in the body element's namespace.
+    pub qualified: bool,
+}
+
+/// Find every operation of every SOAP port in the WSDL.
+pub fn operations(text: &str) -> anyhow::Result<Vec<Operation>> {
+    let package = sxd_document::parser::parse(text).map_err(|e| anyhow!("parse wsdl: {e}"))?;
+    let doc = package.as_document();
+    let defs = doc
+        .root()
+        .children()
+        .into_iter()
+        .find_map(|c| match c {
+            ChildOfRoot::Element(e) => Some(e),
+            _ => None,
+        })
+        .ok_or_else(|| anyhow!("wsdl has no root element"))?;
+    if defs.name().namespace_uri() != Some(WSDL) || defs.name().local_part() != "definitions" {
+        bail!("root element is not a WSDL 1.1 definitions element");
+    }
+    let tns = defs.attribute_value("targetNamespace").unwrap_or_default();
+    let schemas: Vec<_> = children(defs, WSDL, "types")
+        .flat_map(|types| children(types, XSD, "schema"))
+        .collect();
+
+    // Message name to the element of its first part. RPC style parts have types instead of
+    // elements, and are wrapped in an element named for the operation.
+    let mut messages = HashMap::new();
+    for message in children(defs, WSDL, "message") {
+        let Some(name) = message.attribute_value("name") else {
+            continue;
+        };
+        let parts: Vec<_> = children(message, WSDL, "part").collect();
+        let element = parts
+            .iter()
+            .find_map(|p| p.attribute_value("element"))
+            .and_then(|qname| resolve(message, tns, qname));
+        let part_names = parts
+            .iter()
+            .filter_map(|p| p.attribute_value("name").map(str::to_owned))
+            .collect::<Vec<_>>();
+        messages.insert(name, (element, part_names));
+    }
+
+    // Port type name to each operation's input message.
+    let mut port_types = HashMap::new();
+    for port_type in children(defs, WSDL, "portType") {
+        let Some(name) = port_type.attribute_value("name") else {
+            continue;
+        };
+        let ops: HashMap<_, _> = children(port_type, WSDL, "operation")
+            .filter_map(|op| {
+                let input = children(op, WSDL, "input")
+                    .next()?
+                    .attribute_value("message")?;
+                Some((op.attribute_value("name")?, local(input)))
+            })
+            .collect();
+        port_types.insert(name, ops);
+    }
+
+    let bindings: HashMap<_, _> = children(defs, WSDL, "binding")
+        .filter_map(|b| Some((b.attribute_value("name")?, b)))
+        .collect();
+
+    let mut out = Vec::new();
+    for port in children(defs, WSDL, "service").flat_map(|s| children(s, WSDL, "port")) {
+        let (Some(port_name), Some(binding)) = (
+            port.attribute_value("name"),
+            port.attribute_value("binding"),
+        ) else {
+            continue;
+        };
+        let Some(&binding) = bindings.get(local(binding)) else {
+            bail!("port {port_name} uses undefined binding {binding}");
+        };
+        // Skip ports that aren't SOAP, like HTTP GET and POST bindings.
+        let (address, soap12) = match (
+            children(port, SOAP11, "address").next(),
+            children(port, SOAP12, "address").next(),
+        ) {
+            (Some(a), _) => (a, false),
+            (None, Some(a)) => (a, true),
+            (None, None) => continue,
+        };
+        let Some(endpoint) = address.attribute_value("location") else {
+            continue;
+        };
+        let ns = if soap12 { SOAP12 } else { SOAP11 };
+        let rpc = children(binding, ns, "binding")
+            .next()
+            .and_then(|b| b.attribute_value("style"))
+            == Some("rpc");
+        let port_type = binding
+            .attribute_value("type")
+            .and_then(|t| port_types.get(local(t)));
+        for op in children(binding, WSDL, "operation") {
+            let Some(name) = op.attribute_value("name") else {
+                continue;
+            };
+            let soap_op = children(op, ns, "operation").next();
+            let input = port_type
+                .and_then(|ops| ops.get(name))
+                .and_then(|message| messages.get(message));
+            let (element, fields) = match input {
+                Some((Some(element), _)) if !rpc => {
+                    let fields = schema_fields(&schemas, element);
+                    (element.clone(), fields)
+                }
+                Some((_, parts)) => ((tns.to_owned(), name.to_owned()), parts.clone()),
+                None => ((tns.to_owned(), name.to_owned()), Vec::new()),
+            };
+            let qualified = !rpc
+                && schemas.iter().any(|s| {
+                    s.attribute_value("targetNamespace") == Some(element.0.as_str())
+                        && s.attribute_value("elementFormDefault") == Some("qualified")
+                });
+            out.push(Operation {
+                name: name.to_owned(),
+                port: port_name.to_owned(),
+                endpoint: endpoint.to_owned(),
+                action: soap_op
+                    .and_then(|o| o.attribute_value("soapAction"))
+                    .filter(|a| !a.is_empty())
+                    .map(str::to_owned),
+                soap12,
+                element,
+                fields,
+                qualified,
+            });
+        }
+    }
+    if out.is_empty() {
+        bail!("wsdl has no SOAP operations");
+    }
+    Ok(out)
+}
+
+/// Generate a plan named `name` with an HTTP step for each operation. Request bodies contain
+/// the body element's direct children with empty values, ready to be filled in.
+pub fn import(text: &str, name: &str) -> anyhow::Result<String> {
+    let mut out = String::new();
+    writeln!(out, "devil.version = 0")?;
+    writeln!(out, "devil.name = {}", toml::Value::from(name))?;
+    let mut used = HashSet::new();
+    for op in operations(text)? {
+        let mut step = step_name(&op.name);
+        if !used.insert(step.clone()) {
+            step = step_name(&format!("{}_{}", op.port, op.name));
+            used.insert(step.clone());
+        }
+
+        let (ns, element) = &op.element;
+        let mut body = format!(r#"<m:{element} xmlns:m="{}">"#, xml_escape(ns));
+        for field in &op.fields {
+            let prefix = if op.qualified { "m:" } else { "" };
+            write!(body, "<{prefix}{field}></{prefix}{field}>")?;
+        }
+        write!(body, "</m:{element}>")?;
+        let options = if op.soap12 {
+            "{'version': '1.2'}"
+        } else {
+            "{}"
+        };
+        let body = format!(
+            "soap_envelope('{}', {options})",
+            body.replace('\\', "\\\\").replace('\'', "\\'"),
+        );
+
+        writeln!(out)?;
+        writeln!(out, "# {} on port {}", op.name, op.port)?;
+        writeln!(out, "[{step}.http]")?;
+        writeln!(out, "    url = {}", toml::Value::from(op.endpoint.as_str()))?;
+        writeln!(out, r#"    method = "POST""#)?;
+        match (&op.action, op.soap12) {
+            (Some(action), true) => {
+                let content_type =
+                    format!(r#"application/soap+xml; charset=utf-8; action="{action}""#);
+                writeln!(
+                    out,
+                    "    headers.Content-Type = {}",
+                    toml::Value::from(content_type)
+                )?;
+            }
+            (None, true) => writeln!(
+                out,
+                r#"    headers.Content-Type = "application/soap+xml; charset=utf-8""#,
+            )?,
+            (action, false) => {
+                writeln!(
+                    out,
+                    r#"    headers.Content-Type = "text/xml; charset=utf-8""#
+                )?;
+                let action = format!(r#""{}""#, action.as_deref().unwrap_or_default());
+                writeln!(
+                    out,
+                    "    headers.SOAPAction = {}",
+                    toml::Value::from(action)
+                )?;
+            }
+        }
+        writeln!(out, "    body.cel = {}", toml::Value::from(body))?;
+    }
+    Ok(out)
+}
+
+/// Make a name usable as a bare TOML key.
+fn step_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn children<'d>(
+    parent: Element<'d>,
+    ns: &'static str,
+    name: &'static str,
+) -> impl Iterator<Item = Element<'d>> {
+    parent.children().into_iter().filter_map(move |c| match c {
+        ChildOfElement::Element(e)
+            if e.name().namespace_uri() == Some(ns) && e.name().local_part() == name =>
+        {
+            Some(e)
+        }
+        _ => None,
+    })
+}
+
+fn local(qname: &str) -> &str {
+    qname.rsplit_once(':').map_or(qname, |(_, local)| local)
+}
+
+/// Resolve a name like tns:GetQuote to its namespace and local name. Unprefixed names are in
+/// the target namespace.
+fn resolve(scope: Element, tns: &str, qname: &str) -> Option<(String, String)> {
+    let Some((prefix, local)) = qname.split_once(':') else {
+        return Some((tns.to_owned(), qname.to_owned()));
+    };
+    let ns = scope.namespace_uri_for_prefix(prefix)?;
+    Some((ns.to_owned(), local.to_owned()))
+}
+
+/// The names of an element's direct children, when the element is declared at the top of a
+/// schema with an inline or named complex type.
+fn schema_fields(schemas: &[Element], (ns, name): &(String, String)) -> Vec<String> {
+    let Some(schema) = schemas
+        .iter()
+        .find(|s| s.attribute_value("targetNamespace") == Some(ns.as_str()))
+    else {
+        return Vec::new();
+    };
+    let Some(element) =
+        children(*schema, XSD, "element").find(|e| e.attribute_value("name") == Some(name))
+    else {
+        return Vec::new();
+    };
+    let complex = match element.attribute_value("type") {
+        Some(t) => children(*schema, XSD, "complexType")
+            .find(|c| c.attribute_value("name") == Some(local(t))),
+        None => children(element, XSD, "complexType").next(),
+    };
+    let Some(complex) = complex else {
+        return Vec::new();
+    };
+    children(complex, XSD, "sequence")
+        .chain(children(complex, XSD, "all"))
+        .flat_map(|group| children(group, XSD, "element"))
+        .filter_map(|e| {
+            e.attribute_value("name")
+                .or_else(|| e.attribute_value("ref").map(local))
+                .map(str::to_owned)
+        })
+        .collect()
+}
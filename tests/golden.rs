@@ -0,0 +1,30 @@
+use std::sync::Arc;
+
+use devil::testing::{run_plan, Golden, ScriptedConnection, ScriptedSocketProvider};
+
+#[tokio::test]
+async fn h1c_response() {
+    let sockets = ScriptedSocketProvider::new([ScriptedConnection::new(
+        "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello",
+    )]);
+    let output = run_plan(
+        r#"
+devil.version = 0
+devil.name = "golden"
+
+[get.h1c]
+url = "http://127.0.0.1:8080/"
+"#,
+        Arc::new(sockets.clone()),
+    )
+    .await
+    .unwrap();
+
+    let received = sockets.received().await;
+    assert_eq!(received.len(), 1);
+    assert!(received[0].starts_with(b"GET / HTTP/1.1\r\n"));
+    // The whole step is compared, with run ids and timings redacted.
+    Golden::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden"))
+        .check("h1c_response", &*output.steps[0])
+        .unwrap();
+}